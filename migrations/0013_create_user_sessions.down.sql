@@ -0,0 +1,2 @@
+-- Rollback user sessions
+DROP TABLE IF EXISTS user_sessions;
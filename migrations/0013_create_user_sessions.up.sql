@@ -0,0 +1,26 @@
+-- Session management: refresh-token backed user sessions
+-- This migration creates the table used for refresh token rotation and revocation
+-- User sessions (one row per signed-in device)
+CREATE TABLE
+    user_sessions (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+        company_id UUID REFERENCES companies (id) ON DELETE SET NULL,
+        refresh_token_hash VARCHAR(255) NOT NULL UNIQUE,
+        previous_refresh_token_hash VARCHAR(255),
+        user_agent TEXT,
+        ip_address VARCHAR(45),
+        expires_at TIMESTAMPTZ NOT NULL,
+        last_used_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        revoked_at TIMESTAMPTZ,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+    );
+
+-- Indexes for performance
+CREATE INDEX idx_user_sessions_user_id ON user_sessions (user_id);
+
+CREATE INDEX idx_user_sessions_refresh_token_hash ON user_sessions (refresh_token_hash);
+
+CREATE INDEX idx_user_sessions_previous_refresh_token_hash ON user_sessions (previous_refresh_token_hash);
+
+CREATE INDEX idx_user_sessions_expires_at ON user_sessions (expires_at);
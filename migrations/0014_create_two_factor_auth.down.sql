@@ -0,0 +1,11 @@
+-- Remove session and company columns
+ALTER TABLE user_sessions
+DROP COLUMN mfa_verified;
+
+ALTER TABLE companies
+DROP COLUMN require_two_factor;
+
+-- Drop two-factor tables
+DROP TABLE IF EXISTS two_factor_recovery_codes;
+
+DROP TABLE IF EXISTS user_two_factor;
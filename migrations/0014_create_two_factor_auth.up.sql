@@ -0,0 +1,33 @@
+-- Two-factor authentication: TOTP secrets, recovery codes and company enforcement
+-- This migration creates the tables backing TOTP 2FA and the company policy flag
+-- TOTP secret per user (enabled_at stays NULL until enrolment is confirmed)
+CREATE TABLE
+    user_two_factor (
+        user_id UUID PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+        secret VARCHAR(64) NOT NULL,
+        enabled_at TIMESTAMPTZ,
+        last_used_step BIGINT,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+    );
+
+-- One-time recovery codes (stored as SHA-256 hashes)
+CREATE TABLE
+    two_factor_recovery_codes (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+        code_hash VARCHAR(255) NOT NULL,
+        used_at TIMESTAMPTZ,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+    );
+
+-- Company policy requiring 2FA for admins and managers
+ALTER TABLE companies
+ADD COLUMN require_two_factor BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Sessions remember whether they were established with a second factor
+ALTER TABLE user_sessions
+ADD COLUMN mfa_verified BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Indexes for performance
+CREATE INDEX idx_two_factor_recovery_codes_user_id ON two_factor_recovery_codes (user_id);
@@ -0,0 +1,6 @@
+-- Drop single sign-on tables
+DROP TABLE IF EXISTS user_identities;
+
+DROP TABLE IF EXISTS sso_login_states;
+
+DROP TABLE IF EXISTS company_sso_configs;
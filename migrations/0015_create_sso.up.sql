@@ -0,0 +1,50 @@
+-- Single sign-on: per-company OIDC providers, pending logins and linked identities
+-- This migration creates the tables backing the OIDC authorization code + PKCE flow
+-- OIDC provider per company (endpoints are resolved from discovery when saved)
+CREATE TABLE
+    company_sso_configs (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        company_id UUID NOT NULL UNIQUE REFERENCES companies (id) ON DELETE CASCADE,
+        issuer VARCHAR(500) NOT NULL,
+        client_id VARCHAR(255) NOT NULL,
+        client_secret VARCHAR(500) NOT NULL,
+        allowed_domains TEXT[] NOT NULL DEFAULT '{}',
+        default_role VARCHAR(50) NOT NULL DEFAULT 'employee',
+        enabled BOOLEAN NOT NULL DEFAULT TRUE,
+        authorization_endpoint VARCHAR(500) NOT NULL,
+        token_endpoint VARCHAR(500) NOT NULL,
+        jwks_uri VARCHAR(500) NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+    );
+
+-- In-flight logins, consumed by the callback
+CREATE TABLE
+    sso_login_states (
+        state VARCHAR(255) PRIMARY KEY,
+        company_id UUID NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+        code_verifier VARCHAR(255) NOT NULL,
+        nonce VARCHAR(255) NOT NULL,
+        redirect_uri VARCHAR(500) NOT NULL,
+        expires_at TIMESTAMPTZ NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+    );
+
+-- External identities linked to local users
+CREATE TABLE
+    user_identities (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+        company_id UUID NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+        issuer VARCHAR(500) NOT NULL,
+        subject VARCHAR(255) NOT NULL,
+        email VARCHAR(255) NOT NULL,
+        last_login_at TIMESTAMPTZ,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        UNIQUE (issuer, subject)
+    );
+
+-- Indexes for performance
+CREATE INDEX idx_sso_login_states_expires_at ON sso_login_states (expires_at);
+
+CREATE INDEX idx_user_identities_user_id ON user_identities (user_id);
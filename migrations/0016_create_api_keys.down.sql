@@ -0,0 +1,6 @@
+-- Remove activity attribution
+ALTER TABLE company_activity
+DROP COLUMN api_key_id;
+
+-- Drop API keys table
+DROP TABLE IF EXISTS api_keys;
@@ -0,0 +1,29 @@
+-- Company API keys for integrations
+-- This migration creates hashed, scoped API keys and attributes activity to them
+-- API keys (only a SHA-256 hash of the secret is stored; the prefix identifies the key)
+CREATE TABLE
+    api_keys (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        company_id UUID NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+        name VARCHAR(255) NOT NULL,
+        prefix VARCHAR(32) NOT NULL UNIQUE,
+        key_hash VARCHAR(255) NOT NULL,
+        scopes TEXT[] NOT NULL DEFAULT '{}',
+        rate_limit_per_minute INTEGER NOT NULL DEFAULT 60,
+        created_by UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+        last_used_at TIMESTAMPTZ,
+        last_used_ip VARCHAR(45),
+        expires_at TIMESTAMPTZ,
+        revoked_at TIMESTAMPTZ,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+    );
+
+-- Activity performed with an API key is attributed to the key
+ALTER TABLE company_activity
+ADD COLUMN api_key_id UUID REFERENCES api_keys (id) ON DELETE SET NULL;
+
+-- Indexes for performance
+CREATE INDEX idx_api_keys_company_id ON api_keys (company_id);
+
+CREATE INDEX idx_company_activity_api_key_id ON company_activity (api_key_id);
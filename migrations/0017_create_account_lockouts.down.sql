@@ -0,0 +1,2 @@
+-- Drop account lockout tracking
+DROP TABLE IF EXISTS account_lockouts;
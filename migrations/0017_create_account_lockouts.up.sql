@@ -0,0 +1,16 @@
+-- Account lockout: per-account failed login tracking
+-- This migration creates the table backing progressive login delays and temporary lockouts
+-- One row per account with recent failed attempts (removed on successful login or unlock)
+CREATE TABLE
+    account_lockouts (
+        user_id UUID PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+        failed_attempts INTEGER NOT NULL DEFAULT 0,
+        last_failed_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        locked_until TIMESTAMPTZ,
+        locked_at TIMESTAMPTZ,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+    );
+
+-- Indexes for performance
+CREATE INDEX idx_account_lockouts_locked_until ON account_lockouts (locked_until);
@@ -0,0 +1,6 @@
+-- Drop email verification tokens
+DROP TABLE IF EXISTS email_verification_tokens;
+
+-- Remove verified flag
+ALTER TABLE users
+DROP COLUMN email_verified_at;
@@ -0,0 +1,30 @@
+-- Email verification: verified flag on users and single-use verification tokens
+-- This migration creates the tables backing email verification and email changes
+-- When the user's current email address was confirmed
+ALTER TABLE users
+ADD COLUMN email_verified_at TIMESTAMPTZ;
+
+-- Accounts created before verification existed are treated as verified
+UPDATE users
+SET
+    email_verified_at = created_at;
+
+-- Verification tokens (email is the address being verified, which differs from
+-- users.email while an email change is pending)
+CREATE TABLE
+    email_verification_tokens (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+        email VARCHAR(255) NOT NULL,
+        token VARCHAR(255) NOT NULL UNIQUE,
+        expires_at TIMESTAMPTZ NOT NULL,
+        used_at TIMESTAMPTZ,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+    );
+
+-- Indexes for performance
+CREATE INDEX idx_email_verification_tokens_user_id ON email_verification_tokens (user_id);
+
+CREATE INDEX idx_email_verification_tokens_token ON email_verification_tokens (token);
+
+CREATE INDEX idx_email_verification_tokens_expires_at ON email_verification_tokens (expires_at);
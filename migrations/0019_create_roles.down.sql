@@ -0,0 +1,8 @@
+-- Drop custom roles
+ALTER TABLE user_company
+DROP CONSTRAINT IF EXISTS user_company_role_check;
+
+ALTER TABLE user_company
+DROP COLUMN IF EXISTS role_id;
+
+DROP TABLE IF EXISTS roles;
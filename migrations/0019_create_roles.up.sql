@@ -0,0 +1,104 @@
+-- Custom roles with fine-grained permissions
+-- This migration creates per-company roles and seeds the built-in roles for every company
+-- Roles belong to a company; built-in roles match the behaviour of the fixed company roles
+CREATE TABLE
+    roles (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        company_id UUID NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+        name VARCHAR(100) NOT NULL,
+        description TEXT,
+        permissions TEXT[] NOT NULL DEFAULT '{}',
+        is_builtin BOOLEAN NOT NULL DEFAULT FALSE,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        UNIQUE (company_id, name)
+    );
+
+-- Seed the built-in roles for existing companies; new companies get them when created
+INSERT INTO
+    roles (company_id, name, description, permissions, is_builtin)
+SELECT
+    c.id,
+    b.name,
+    b.description,
+    b.permissions,
+    TRUE
+FROM
+    companies c
+    CROSS JOIN (
+        VALUES
+            (
+                'admin',
+                'Full access to the company',
+                ARRAY[
+                    'shifts.view',
+                    'shifts.create',
+                    'shifts.edit',
+                    'shifts.assign',
+                    'timeoff.view',
+                    'timeoff.approve',
+                    'swaps.view',
+                    'swaps.approve',
+                    'schedules.manage',
+                    'skills.manage',
+                    'pto.view',
+                    'pto.manage',
+                    'employees.view',
+                    'employees.manage',
+                    'employees.invite',
+                    'locations.manage',
+                    'teams.manage',
+                    'stats.view',
+                    'wages.view',
+                    'wages.edit',
+                    'roles.manage',
+                    'company.settings'
+                ]::TEXT[]
+            ),
+            (
+                'manager',
+                'Manages scheduling, time off and employees',
+                ARRAY[
+                    'shifts.view',
+                    'shifts.create',
+                    'shifts.edit',
+                    'shifts.assign',
+                    'timeoff.view',
+                    'timeoff.approve',
+                    'swaps.view',
+                    'swaps.approve',
+                    'schedules.manage',
+                    'skills.manage',
+                    'pto.view',
+                    'pto.manage',
+                    'employees.view',
+                    'employees.manage',
+                    'employees.invite',
+                    'locations.manage',
+                    'teams.manage',
+                    'stats.view'
+                ]::TEXT[]
+            ),
+            (
+                'employee',
+                'Works shifts and manages their own requests',
+                '{}'::TEXT[]
+            )
+    ) AS b (name, description, permissions);
+
+-- A member's custom role replaces the permissions of their built-in role
+ALTER TABLE user_company
+ADD COLUMN role_id UUID REFERENCES roles (id) ON DELETE SET NULL;
+
+-- 'owner' was never a role the application understood; owners are admins
+UPDATE user_company
+SET
+    role = 'admin'
+WHERE
+    role = 'owner';
+
+ALTER TABLE user_company
+ADD CONSTRAINT user_company_role_check CHECK (role IN ('admin', 'manager', 'employee'));
+
+-- Indexes for performance
+CREATE INDEX idx_user_company_role_id ON user_company (role_id);
@@ -0,0 +1,2 @@
+-- Drop manager scopes
+DROP TABLE IF EXISTS user_company_scopes;
@@ -0,0 +1,21 @@
+-- Location- and team-scoped managers
+-- This migration lets a company membership be limited to specific locations or teams
+-- Each row grants one location or one team; members without rows act company-wide
+CREATE TABLE
+    user_company_scopes (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        user_company_id UUID NOT NULL REFERENCES user_company (id) ON DELETE CASCADE,
+        location_id UUID REFERENCES locations (id) ON DELETE CASCADE,
+        team_id UUID REFERENCES teams (id) ON DELETE CASCADE,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        CHECK (num_nonnulls (location_id, team_id) = 1),
+        UNIQUE (user_company_id, location_id),
+        UNIQUE (user_company_id, team_id)
+    );
+
+-- Indexes for performance
+CREATE INDEX idx_user_company_scopes_user_company_id ON user_company_scopes (user_company_id);
+
+CREATE INDEX idx_user_company_scopes_location_id ON user_company_scopes (location_id);
+
+CREATE INDEX idx_user_company_scopes_team_id ON user_company_scopes (team_id);
@@ -0,0 +1,5 @@
+-- Drop admin impersonation
+ALTER TABLE company_activity
+DROP COLUMN IF EXISTS impersonated_user_id;
+
+DROP TABLE IF EXISTS impersonation_sessions;
@@ -0,0 +1,30 @@
+-- Admin impersonation
+-- This migration records every time an admin acts as another user and attributes the
+-- activity performed during that time to the admin
+-- Impersonation sessions (one row per "act as user" token issued)
+CREATE TABLE
+    impersonation_sessions (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        company_id UUID NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+        admin_user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+        target_user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+        reason TEXT,
+        user_agent TEXT,
+        ip_address VARCHAR(45),
+        expires_at TIMESTAMPTZ NOT NULL,
+        ended_at TIMESTAMPTZ,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+    );
+
+-- Activity performed while impersonating keeps the admin as the actor
+ALTER TABLE company_activity
+ADD COLUMN impersonated_user_id UUID REFERENCES users (id) ON DELETE SET NULL;
+
+-- Indexes for performance
+CREATE INDEX idx_impersonation_sessions_company_id ON impersonation_sessions (company_id);
+
+CREATE INDEX idx_impersonation_sessions_admin_user_id ON impersonation_sessions (admin_user_id);
+
+CREATE INDEX idx_impersonation_sessions_target_user_id ON impersonation_sessions (target_user_id);
+
+CREATE INDEX idx_company_activity_impersonated_user_id ON company_activity (impersonated_user_id);
@@ -0,0 +1,3 @@
+-- Remove erasure marker
+ALTER TABLE users
+DROP COLUMN erased_at;
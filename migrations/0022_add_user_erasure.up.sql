@@ -0,0 +1,5 @@
+-- Account erasure: erased users keep their id so shift and payroll history stays intact
+-- This migration records when a user's personal data was erased
+-- Set once the account is anonymized; erased accounts can no longer sign in
+ALTER TABLE users
+ADD COLUMN erased_at TIMESTAMPTZ;
@@ -0,0 +1,18 @@
+-- Drop the activity hash chain
+DROP TABLE IF EXISTS activity_checkpoints;
+
+DROP TABLE IF EXISTS activity_chain_heads;
+
+DROP INDEX IF EXISTS idx_company_activity_chain;
+
+ALTER TABLE company_activity
+DROP COLUMN IF EXISTS chain_seq,
+DROP COLUMN IF EXISTS content_hash,
+DROP COLUMN IF EXISTS prev_hash,
+DROP COLUMN IF EXISTS row_hash,
+DROP COLUMN IF EXISTS redacted_at;
+
+ALTER TABLE company_activity
+ADD CONSTRAINT company_activity_user_id_fkey FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE SET NULL,
+ADD CONSTRAINT company_activity_impersonated_user_id_fkey FOREIGN KEY (impersonated_user_id) REFERENCES users (id) ON DELETE SET NULL,
+ADD CONSTRAINT company_activity_api_key_id_fkey FOREIGN KEY (api_key_id) REFERENCES api_keys (id) ON DELETE SET NULL;
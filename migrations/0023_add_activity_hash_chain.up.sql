@@ -0,0 +1,43 @@
+-- Tamper-evident activity log
+-- This migration chains every company's activity rows together with hashes and adds signed
+-- checkpoints of the chain head that can be kept outside the database
+-- Each row hashes its own content plus the previous row's hash. Rows logged before this
+-- migration have no hash and are not part of the chain.
+ALTER TABLE company_activity
+ADD COLUMN chain_seq BIGINT,
+ADD COLUMN content_hash VARCHAR(64),
+ADD COLUMN prev_hash VARCHAR(64),
+ADD COLUMN row_hash VARCHAR(64),
+ADD COLUMN redacted_at TIMESTAMPTZ;
+
+-- The chain covers the actor ids, so deleting a user or API key must not rewrite them
+ALTER TABLE company_activity
+DROP CONSTRAINT IF EXISTS company_activity_user_id_fkey,
+DROP CONSTRAINT IF EXISTS company_activity_impersonated_user_id_fkey,
+DROP CONSTRAINT IF EXISTS company_activity_api_key_id_fkey;
+
+-- Latest link of each company's chain. Appends lock this row, and it survives the
+-- rows themselves being archived.
+CREATE TABLE
+    activity_chain_heads (
+        company_id UUID PRIMARY KEY REFERENCES companies (id) ON DELETE CASCADE,
+        chain_seq BIGINT NOT NULL DEFAULT 0,
+        row_hash VARCHAR(64) NOT NULL DEFAULT '0000000000000000000000000000000000000000000000000000000000000000',
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+    );
+
+-- Signed checkpoints (one row per chain head that was signed)
+CREATE TABLE
+    activity_checkpoints (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        company_id UUID NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+        chain_seq BIGINT NOT NULL,
+        row_hash VARCHAR(64) NOT NULL,
+        signature TEXT NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+    );
+
+-- Indexes for performance
+CREATE UNIQUE INDEX idx_company_activity_chain ON company_activity (company_id, chain_seq);
+
+CREATE INDEX idx_activity_checkpoints_company_id ON activity_checkpoints (company_id, chain_seq);
@@ -0,0 +1,96 @@
+-- Drop activity log partitioning, retention and archival
+DROP TABLE IF EXISTS activity_archives;
+
+ALTER TABLE companies
+DROP COLUMN IF EXISTS activity_retention_months;
+
+ALTER TABLE company_activity
+RENAME TO company_activity_partitioned;
+
+ALTER TABLE company_activity_partitioned
+RENAME CONSTRAINT company_activity_pkey TO company_activity_partitioned_pkey;
+
+ALTER TABLE company_activity_partitioned
+DROP CONSTRAINT company_activity_company_id_fkey;
+
+DROP INDEX IF EXISTS idx_company_activity_company_created;
+
+DROP INDEX IF EXISTS idx_company_activity_entity;
+
+DROP INDEX IF EXISTS idx_company_activity_chain;
+
+DROP INDEX IF EXISTS idx_company_activity_user_id;
+
+DROP INDEX IF EXISTS idx_company_activity_impersonated_user_id;
+
+DROP INDEX IF EXISTS idx_company_activity_api_key_id;
+
+CREATE TABLE
+    company_activity (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        company_id UUID NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+        user_id UUID,
+        activity_type VARCHAR(100) NOT NULL,
+        entity_type VARCHAR(100) NOT NULL,
+        entity_id UUID NOT NULL,
+        action VARCHAR(100) NOT NULL,
+        description TEXT NOT NULL,
+        metadata JSONB,
+        ip_address VARCHAR(45),
+        user_agent TEXT,
+        api_key_id UUID,
+        impersonated_user_id UUID,
+        chain_seq BIGINT,
+        content_hash VARCHAR(64),
+        prev_hash VARCHAR(64),
+        row_hash VARCHAR(64),
+        redacted_at TIMESTAMPTZ,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+    );
+
+INSERT INTO
+    company_activity
+SELECT
+    id,
+    company_id,
+    user_id,
+    activity_type,
+    entity_type,
+    entity_id,
+    action,
+    description,
+    metadata,
+    ip_address,
+    user_agent,
+    api_key_id,
+    impersonated_user_id,
+    chain_seq,
+    content_hash,
+    prev_hash,
+    row_hash,
+    redacted_at,
+    created_at
+FROM
+    company_activity_partitioned;
+
+DROP TABLE company_activity_partitioned;
+
+CREATE INDEX idx_company_activity_company_id ON company_activity (company_id);
+
+CREATE INDEX idx_company_activity_user_id ON company_activity (user_id);
+
+CREATE INDEX idx_company_activity_activity_type ON company_activity (activity_type);
+
+CREATE INDEX idx_company_activity_entity_type ON company_activity (entity_type);
+
+CREATE INDEX idx_company_activity_entity_id ON company_activity (entity_id);
+
+CREATE INDEX idx_company_activity_action ON company_activity (action);
+
+CREATE INDEX idx_company_activity_created_at ON company_activity (created_at);
+
+CREATE INDEX idx_company_activity_api_key_id ON company_activity (api_key_id);
+
+CREATE INDEX idx_company_activity_impersonated_user_id ON company_activity (impersonated_user_id);
+
+CREATE UNIQUE INDEX idx_company_activity_chain ON company_activity (company_id, chain_seq);
@@ -0,0 +1,173 @@
+-- Activity log partitioning, retention and archival
+-- This migration moves company_activity into a table partitioned by month, replaces its
+-- single-column indexes with ones that match how the log is read, and adds a per-company
+-- retention period plus a record of the months archived to disk
+-- Free the existing names for the partitioned table
+DROP INDEX IF EXISTS idx_company_activity_company_id;
+
+DROP INDEX IF EXISTS idx_company_activity_user_id;
+
+DROP INDEX IF EXISTS idx_company_activity_activity_type;
+
+DROP INDEX IF EXISTS idx_company_activity_entity_type;
+
+DROP INDEX IF EXISTS idx_company_activity_entity_id;
+
+DROP INDEX IF EXISTS idx_company_activity_action;
+
+DROP INDEX IF EXISTS idx_company_activity_created_at;
+
+DROP INDEX IF EXISTS idx_company_activity_api_key_id;
+
+DROP INDEX IF EXISTS idx_company_activity_impersonated_user_id;
+
+DROP INDEX IF EXISTS idx_company_activity_chain;
+
+ALTER TABLE company_activity
+RENAME TO company_activity_unpartitioned;
+
+ALTER TABLE company_activity_unpartitioned
+RENAME CONSTRAINT company_activity_pkey TO company_activity_unpartitioned_pkey;
+
+ALTER TABLE company_activity_unpartitioned
+DROP CONSTRAINT company_activity_company_id_fkey;
+
+-- Company activity logs, one partition per calendar month (UTC)
+CREATE TABLE
+    company_activity (
+        id UUID NOT NULL DEFAULT gen_random_uuid (),
+        company_id UUID NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+        user_id UUID,
+        activity_type VARCHAR(100) NOT NULL,
+        entity_type VARCHAR(100) NOT NULL,
+        entity_id UUID NOT NULL,
+        action VARCHAR(100) NOT NULL,
+        description TEXT NOT NULL,
+        metadata JSONB,
+        ip_address VARCHAR(45),
+        user_agent TEXT,
+        api_key_id UUID,
+        impersonated_user_id UUID,
+        chain_seq BIGINT,
+        content_hash VARCHAR(64),
+        prev_hash VARCHAR(64),
+        row_hash VARCHAR(64),
+        redacted_at TIMESTAMPTZ,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        PRIMARY KEY (id, created_at)
+    )
+PARTITION BY
+    RANGE (created_at);
+
+-- Catches entries for months the archival job has not created a partition for yet
+CREATE TABLE
+    company_activity_default PARTITION OF company_activity DEFAULT;
+
+-- Monthly partitions from the oldest entry through two months ahead
+DO $$
+DECLARE
+    month_start TIMESTAMP := date_trunc(
+        'month',
+        COALESCE(
+            (SELECT MIN(created_at) FROM company_activity_unpartitioned),
+            NOW ()
+        ) AT TIME ZONE 'UTC'
+    );
+BEGIN
+    WHILE month_start <= date_trunc('month', (NOW () AT TIME ZONE 'UTC') + INTERVAL '2 months') LOOP
+        EXECUTE format(
+            'CREATE TABLE %I PARTITION OF company_activity FOR VALUES FROM (%L) TO (%L)',
+            'company_activity_' || to_char(month_start, 'YYYY_MM'),
+            to_char(month_start, 'YYYY-MM-DD') || ' 00:00:00+00',
+            to_char(month_start + INTERVAL '1 month', 'YYYY-MM-DD') || ' 00:00:00+00'
+        );
+        month_start := month_start + INTERVAL '1 month';
+    END LOOP;
+END $$;
+
+INSERT INTO
+    company_activity (
+        id,
+        company_id,
+        user_id,
+        activity_type,
+        entity_type,
+        entity_id,
+        action,
+        description,
+        metadata,
+        ip_address,
+        user_agent,
+        api_key_id,
+        impersonated_user_id,
+        chain_seq,
+        content_hash,
+        prev_hash,
+        row_hash,
+        redacted_at,
+        created_at
+    )
+SELECT
+    id,
+    company_id,
+    user_id,
+    activity_type,
+    entity_type,
+    entity_id,
+    action,
+    description,
+    metadata,
+    ip_address,
+    user_agent,
+    api_key_id,
+    impersonated_user_id,
+    chain_seq,
+    content_hash,
+    prev_hash,
+    row_hash,
+    redacted_at,
+    created_at
+FROM
+    company_activity_unpartitioned;
+
+DROP TABLE company_activity_unpartitioned;
+
+-- How long each company keeps activity online before it is archived
+ALTER TABLE companies
+ADD COLUMN activity_retention_months INTEGER NOT NULL DEFAULT 24;
+
+-- Archived activity (one row per company and month written to disk)
+CREATE TABLE
+    activity_archives (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        company_id UUID NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+        period_start TIMESTAMPTZ NOT NULL,
+        period_end TIMESTAMPTZ NOT NULL,
+        file_name VARCHAR(255) NOT NULL UNIQUE,
+        entry_count INTEGER NOT NULL,
+        size_bytes BIGINT NOT NULL,
+        sha256 VARCHAR(64) NOT NULL,
+        first_seq BIGINT,
+        last_seq BIGINT,
+        last_row_hash VARCHAR(64),
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+    );
+
+-- Indexes for performance
+CREATE INDEX idx_company_activity_company_created ON company_activity (company_id, created_at, id);
+
+CREATE INDEX idx_company_activity_entity ON company_activity (company_id, entity_type, entity_id, created_at);
+
+CREATE INDEX idx_company_activity_chain ON company_activity (company_id, chain_seq);
+
+CREATE INDEX idx_company_activity_user_id ON company_activity (user_id);
+
+CREATE INDEX idx_company_activity_impersonated_user_id ON company_activity (impersonated_user_id)
+WHERE
+    impersonated_user_id IS NOT NULL;
+
+CREATE INDEX idx_company_activity_api_key_id ON company_activity (api_key_id)
+WHERE
+    api_key_id IS NOT NULL;
+
+CREATE INDEX idx_activity_archives_company_id ON activity_archives (company_id, period_start);
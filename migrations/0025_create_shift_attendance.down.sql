@@ -0,0 +1,2 @@
+-- Drop shift attendance
+DROP TABLE IF EXISTS shift_attendance;
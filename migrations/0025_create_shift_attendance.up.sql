@@ -0,0 +1,25 @@
+-- Shift attendance
+-- This migration records when employees clock in and out of the shifts they work, which the
+-- hours and attendance reports are built from
+-- Attendance (one row per worker and shift)
+CREATE TABLE
+    shift_attendance (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        company_id UUID NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+        shift_id UUID NOT NULL REFERENCES shifts (id) ON DELETE CASCADE,
+        user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+        clock_in_at TIMESTAMPTZ NOT NULL,
+        clock_out_at TIMESTAMPTZ,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        UNIQUE (shift_id, user_id),
+        CHECK (
+            clock_out_at IS NULL
+            OR clock_out_at >= clock_in_at
+        )
+    );
+
+-- Indexes for performance
+CREATE INDEX idx_shift_attendance_company_id ON shift_attendance (company_id, clock_in_at);
+
+CREATE INDEX idx_shift_attendance_user_id ON shift_attendance (user_id);
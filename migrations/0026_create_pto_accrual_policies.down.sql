@@ -0,0 +1,8 @@
+-- Drop PTO accrual policies
+DROP TABLE IF EXISTS pto_accrual_state;
+
+DROP TABLE IF EXISTS pto_policy_assignments;
+
+DROP TABLE IF EXISTS pto_accrual_tiers;
+
+DROP TABLE IF EXISTS pto_accrual_policies;
@@ -0,0 +1,76 @@
+-- PTO accrual policies
+-- This migration lets companies define how balances accrue and assign the rules to groups of
+-- employees, replacing the single flat rate on the membership for those employees
+-- Policies accrue into one balance, per pay period or per hour worked
+CREATE TABLE
+    pto_accrual_policies (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        company_id UUID NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+        name VARCHAR(100) NOT NULL,
+        description TEXT,
+        balance_type VARCHAR(20) NOT NULL DEFAULT 'pto',
+        accrual_method VARCHAR(20) NOT NULL,
+        pay_period VARCHAR(20),
+        annual_cap_hours DECIMAL(8, 2),
+        max_balance_hours DECIMAL(8, 2),
+        carryover_limit_hours DECIMAL(8, 2),
+        year_start VARCHAR(20) NOT NULL DEFAULT 'calendar',
+        year_start_month INTEGER NOT NULL DEFAULT 1,
+        year_start_day INTEGER NOT NULL DEFAULT 1,
+        waiting_period_days INTEGER NOT NULL DEFAULT 0,
+        is_active BOOLEAN NOT NULL DEFAULT TRUE,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        UNIQUE (company_id, name),
+        CHECK (
+            accrual_method <> 'per_pay_period'
+            OR pay_period IS NOT NULL
+        )
+    );
+
+-- Tenure tiers: the rate for members employed at least `min_tenure_months`
+CREATE TABLE
+    pto_accrual_tiers (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        policy_id UUID NOT NULL REFERENCES pto_accrual_policies (id) ON DELETE CASCADE,
+        min_tenure_months INTEGER NOT NULL DEFAULT 0,
+        accrual_rate DECIMAL(8, 4) NOT NULL,
+        UNIQUE (policy_id, min_tenure_months)
+    );
+
+-- Who a policy applies to: one user, team or location, or the whole company when none is set
+CREATE TABLE
+    pto_policy_assignments (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        policy_id UUID NOT NULL REFERENCES pto_accrual_policies (id) ON DELETE CASCADE,
+        company_id UUID NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+        user_id UUID REFERENCES users (id) ON DELETE CASCADE,
+        team_id UUID REFERENCES teams (id) ON DELETE CASCADE,
+        location_id UUID REFERENCES locations (id) ON DELETE CASCADE,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        CHECK (num_nonnulls (user_id, team_id, location_id) <= 1),
+        UNIQUE NULLS NOT DISTINCT (policy_id, user_id, team_id, location_id)
+    );
+
+-- How far each member's balance has accrued under policies, and what the accrual year holds.
+-- Balances hold whole hours, so the part of an hour accrued beyond them waits in fractional_hours
+CREATE TABLE
+    pto_accrual_state (
+        user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+        company_id UUID NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+        balance_type VARCHAR(20) NOT NULL,
+        policy_id UUID REFERENCES pto_accrual_policies (id) ON DELETE SET NULL,
+        accrued_through DATE NOT NULL,
+        year_start DATE NOT NULL,
+        accrued_this_year DECIMAL(10, 4) NOT NULL DEFAULT 0,
+        fractional_hours DECIMAL(10, 4) NOT NULL DEFAULT 0,
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        PRIMARY KEY (user_id, company_id, balance_type)
+    );
+
+-- Indexes for performance
+CREATE INDEX idx_pto_accrual_policies_company_id ON pto_accrual_policies (company_id);
+
+CREATE INDEX idx_pto_policy_assignments_company_id ON pto_policy_assignments (company_id);
+
+CREATE INDEX idx_pto_policy_assignments_policy_id ON pto_policy_assignments (policy_id);
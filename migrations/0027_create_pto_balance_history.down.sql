@@ -0,0 +1,10 @@
+-- Drop the PTO ledger
+DROP TABLE IF EXISTS pto_balance_history;
+
+ALTER TABLE pto_accrual_state
+ADD COLUMN fractional_hours DECIMAL(10, 4) NOT NULL DEFAULT 0;
+
+ALTER TABLE user_company
+ALTER COLUMN pto_balance_hours TYPE INTEGER USING FLOOR(pto_balance_hours),
+ALTER COLUMN sick_balance_hours TYPE INTEGER USING FLOOR(sick_balance_hours),
+ALTER COLUMN personal_balance_hours TYPE INTEGER USING FLOOR(personal_balance_hours);
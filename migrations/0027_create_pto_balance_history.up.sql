@@ -0,0 +1,116 @@
+-- PTO ledger
+-- This migration records every balance change as an entry in an append-only ledger and moves
+-- balances to decimal hours. The balance columns on user_company keep the running total of the
+-- ledger, so current balances can still be read without summing it
+ALTER TABLE user_company
+ALTER COLUMN pto_balance_hours TYPE DECIMAL(10, 4),
+ALTER COLUMN sick_balance_hours TYPE DECIMAL(10, 4),
+ALTER COLUMN personal_balance_hours TYPE DECIMAL(10, 4);
+
+-- Ledger entries are never updated or deleted; corrections are new adjustment entries.
+-- entry_number orders entries made in the same transaction
+CREATE TABLE
+    pto_balance_history (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        entry_number BIGSERIAL NOT NULL UNIQUE,
+        user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+        company_id UUID NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+        balance_type VARCHAR(20) NOT NULL,
+        change_type VARCHAR(20) NOT NULL,
+        hours_changed DECIMAL(10, 4) NOT NULL,
+        previous_balance DECIMAL(10, 4) NOT NULL,
+        new_balance DECIMAL(10, 4) NOT NULL,
+        effective_date DATE NOT NULL DEFAULT CURRENT_DATE,
+        description TEXT,
+        related_time_off_id UUID REFERENCES time_off_requests (id) ON DELETE SET NULL,
+        created_by UUID REFERENCES users (id) ON DELETE SET NULL,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        CHECK (new_balance = previous_balance + hours_changed)
+    );
+
+-- Fractions held back by accrual policies can now go on the balance itself
+UPDATE user_company uc
+SET
+    pto_balance_hours = pto_balance_hours + COALESCE(
+        (
+            SELECT
+                fractional_hours
+            FROM
+                pto_accrual_state s
+            WHERE
+                s.user_id = uc.user_id
+                AND s.company_id = uc.company_id
+                AND s.balance_type = 'pto'
+        ),
+        0
+    ),
+    sick_balance_hours = sick_balance_hours + COALESCE(
+        (
+            SELECT
+                fractional_hours
+            FROM
+                pto_accrual_state s
+            WHERE
+                s.user_id = uc.user_id
+                AND s.company_id = uc.company_id
+                AND s.balance_type = 'sick'
+        ),
+        0
+    ),
+    personal_balance_hours = personal_balance_hours + COALESCE(
+        (
+            SELECT
+                fractional_hours
+            FROM
+                pto_accrual_state s
+            WHERE
+                s.user_id = uc.user_id
+                AND s.company_id = uc.company_id
+                AND s.balance_type = 'personal'
+        ),
+        0
+    );
+
+ALTER TABLE pto_accrual_state
+DROP COLUMN fractional_hours;
+
+-- Open the ledger with the balances held today
+INSERT INTO
+    pto_balance_history (
+        user_id,
+        company_id,
+        balance_type,
+        change_type,
+        hours_changed,
+        previous_balance,
+        new_balance,
+        description
+    )
+SELECT
+    uc.user_id,
+    uc.company_id,
+    b.balance_type,
+    'adjustment',
+    b.hours,
+    0,
+    b.hours,
+    'Opening balance'
+FROM
+    user_company uc
+    CROSS JOIN LATERAL (
+        VALUES
+            ('pto', uc.pto_balance_hours),
+            ('sick', uc.sick_balance_hours),
+            ('personal', uc.personal_balance_hours)
+    ) AS b (balance_type, hours)
+WHERE
+    b.hours <> 0;
+
+-- Indexes for performance
+CREATE INDEX idx_pto_balance_history_user_company ON pto_balance_history (user_id, company_id, balance_type, effective_date);
+
+CREATE INDEX idx_pto_balance_history_company_date ON pto_balance_history (company_id, effective_date);
+
+CREATE INDEX idx_pto_balance_history_time_off ON pto_balance_history (related_time_off_id)
+WHERE
+    related_time_off_id IS NOT NULL;
@@ -0,0 +1,8 @@
+-- Drop time-off duration and company time-off settings
+ALTER TABLE companies
+DROP COLUMN allow_negative_pto,
+DROP COLUMN workday_hours;
+
+ALTER TABLE time_off_requests
+DROP COLUMN hours_deducted,
+DROP COLUMN duration_type;
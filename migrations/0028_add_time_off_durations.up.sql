@@ -0,0 +1,12 @@
+-- Time-off durations and automatic balance deduction
+-- Requests cover whole days, half a day or a window of hours. The hours taken from the
+-- balance on approval are kept on the request.
+ALTER TABLE time_off_requests
+ADD COLUMN duration_type VARCHAR(20) NOT NULL DEFAULT 'full_day',
+ADD COLUMN hours_deducted DECIMAL(10, 4);
+
+-- Hours in a working day for members without scheduled shifts, and whether approved time
+-- off may take a balance below zero
+ALTER TABLE companies
+ADD COLUMN workday_hours DECIMAL(5, 2) NOT NULL DEFAULT 8,
+ADD COLUMN allow_negative_pto BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,4 @@
+-- Drop company holiday calendars
+DROP FUNCTION IF EXISTS company_holiday_on;
+
+DROP TABLE IF EXISTS company_holidays;
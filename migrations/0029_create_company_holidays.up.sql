@@ -0,0 +1,54 @@
+-- Company holiday calendars
+-- Holidays apply to the whole company, or to one location where they override the company's
+-- entry for the same day. A location entry that isn't observed opens the location on a
+-- company holiday.
+CREATE TABLE
+    company_holidays (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        company_id UUID NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+        location_id UUID REFERENCES locations (id) ON DELETE CASCADE,
+        name VARCHAR(100) NOT NULL,
+        holiday_date DATE NOT NULL,
+        recurring BOOLEAN NOT NULL DEFAULT FALSE,
+        observed BOOLEAN NOT NULL DEFAULT TRUE,
+        is_closed BOOLEAN NOT NULL DEFAULT TRUE,
+        pay_multiplier DECIMAL(4, 2) NOT NULL DEFAULT 1,
+        source_uid VARCHAR(255),
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        CHECK (pay_multiplier > 0)
+    );
+
+-- The holiday a location keeps on a day: its own entry when it has one, otherwise the
+-- company's. Recurring holidays repeat every year on the month and day of their first date.
+-- Without a location, only company-wide holidays count.
+CREATE FUNCTION company_holiday_on (p_company_id UUID, p_location_id UUID, p_day DATE)
+RETURNS SETOF company_holidays
+LANGUAGE sql STABLE AS $$
+    SELECT
+        *
+    FROM
+        company_holidays h
+    WHERE
+        h.company_id = p_company_id
+        AND (
+            h.location_id IS NULL
+            OR h.location_id = p_location_id
+        )
+        AND (
+            h.holiday_date = p_day
+            OR (
+                h.recurring
+                AND h.holiday_date < p_day
+                AND EXTRACT(MONTH FROM h.holiday_date) = EXTRACT(MONTH FROM p_day)
+                AND EXTRACT(DAY FROM h.holiday_date) = EXTRACT(DAY FROM p_day)
+            )
+        )
+    ORDER BY
+        h.location_id NULLS LAST,
+        h.created_at
+    LIMIT 1
+$$;
+
+-- Indexes for performance
+CREATE INDEX idx_company_holidays_company_id ON company_holidays (company_id, location_id);
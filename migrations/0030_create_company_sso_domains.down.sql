@@ -0,0 +1,2 @@
+-- Drop verified SSO domains
+DROP TABLE IF EXISTS company_sso_domains;
@@ -0,0 +1,16 @@
+-- Email domains a company has proven it controls; SSO only links existing accounts on these
+CREATE TABLE
+    company_sso_domains (
+        id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+        company_id UUID NOT NULL REFERENCES companies (id) ON DELETE CASCADE,
+        domain VARCHAR(255) NOT NULL,
+        verification_token VARCHAR(255) NOT NULL,
+        verified_at TIMESTAMPTZ,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+        UNIQUE (company_id, domain)
+    );
+
+-- A domain can only be verified by one company
+CREATE UNIQUE INDEX idx_company_sso_domains_verified_domain ON company_sso_domains (domain)
+WHERE
+    verified_at IS NOT NULL;
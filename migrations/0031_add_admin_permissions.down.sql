@@ -0,0 +1,7 @@
+-- Remove the permissions from every role
+UPDATE roles
+SET
+    permissions = array_remove(
+        array_remove(array_remove(permissions, 'activity.view'), 'users.impersonate'),
+        'scope.bypass'
+    );
@@ -0,0 +1,9 @@
+-- Permissions for what used to be checked against the admin role
+-- Built-in admin roles of existing companies get them; new companies are seeded with them
+UPDATE roles
+SET
+    permissions = permissions || ARRAY['activity.view', 'users.impersonate', 'scope.bypass']::TEXT[],
+    updated_at = NOW ()
+WHERE
+    is_builtin
+    AND name = 'admin';
@@ -0,0 +1,75 @@
+//! Verify the activity log hash chain of one or more companies.
+//!
+//! Usage: `verify_activity [company_id ...]`. Without ids every company that has logged
+//! activity is checked. Exits with status 1 when any chain is broken.
+
+use std::process::ExitCode;
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use be::{
+    config::Config,
+    database::{init_database, repositories::activity as activity_repo},
+    services::{activity_chain, jwt_keys::init_key_ring},
+};
+
+#[tokio::main]
+async fn main() -> Result<ExitCode> {
+    dotenvy::dotenv().ok();
+    env_logger::init();
+
+    // Checkpoint signatures are checked against the same keys the server signs with
+    let config = Config::from_env()?;
+    init_key_ring(&config)?;
+    init_database(&config.database_url, false).await?;
+
+    let company_ids = match std::env::args().skip(1).collect::<Vec<_>>() {
+        args if args.is_empty() => activity_repo::get_chain_heads()
+            .await?
+            .into_iter()
+            .map(|head| head.company_id)
+            .collect(),
+        args => args
+            .iter()
+            .map(|arg| Uuid::parse_str(arg))
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+
+    let mut broken = 0;
+    for company_id in company_ids {
+        let verification = activity_chain::verify(company_id)
+            .await
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+
+        match &verification.first_break {
+            None => println!(
+                "✅ {}: {} entries verified up to {} ({} redacted, {} checkpoints)",
+                company_id,
+                verification.checked,
+                verification.head_seq,
+                verification.redacted,
+                verification.checkpoints_checked
+            ),
+            Some(chain_break) => {
+                broken += 1;
+                println!(
+                    "❌ {}: broken at entry {}{}: {}",
+                    company_id,
+                    chain_break.chain_seq,
+                    chain_break
+                        .activity_id
+                        .map(|id| format!(" ({})", id))
+                        .unwrap_or_default(),
+                    chain_break.reason
+                );
+            }
+        }
+    }
+
+    Ok(if broken > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
@@ -1,13 +1,47 @@
 use std::{env, sync::OnceLock};
 
-use anyhow::Result;
+use anyhow::{Result, bail};
+
+/// Fallback HMAC secret used when `JWT_SECRET` is not set; never acceptable in production
+pub const DEFAULT_JWT_SECRET: &str = "your-super-secret-jwt-key-change-this-in-production-12345";
+
+/// A retired signing key that is still accepted when verifying tokens
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationKeyConfig {
+    pub kid: String,
+    /// HS256, RS256 or EdDSA
+    pub algorithm: String,
+    /// The shared secret for HS256, otherwise the path to a public key PEM file
+    pub key: String,
+}
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub database_url: String,
     pub run_migrations: bool,
     pub jwt_secret: String,
+    /// Algorithm used to sign new tokens: HS256, RS256 or EdDSA
+    pub jwt_algorithm: String,
+    /// `kid` header written into new tokens
+    pub jwt_key_id: String,
+    /// PEM private key used for signing with RS256/EdDSA
+    pub jwt_private_key_path: Option<String>,
+    /// PEM public key matching `jwt_private_key_path`
+    pub jwt_public_key_path: Option<String>,
+    /// Previously used keys that tokens may still be signed with
+    pub jwt_previous_keys: Vec<VerificationKeyConfig>,
+    /// Lifetime of a login session (refresh token), in days
     pub jwt_expiration_days: i64,
+    /// Lifetime of an access token, in minutes
+    pub access_token_minutes: i64,
+    /// Lifetime of an admin impersonation token, in minutes
+    pub impersonation_minutes: i64,
+    /// How often the activity log chain heads are signed, in minutes; 0 turns it off
+    pub activity_checkpoint_minutes: u64,
+    /// Where expired activity is archived to
+    pub activity_archive_dir: String,
+    /// How often expired activity is archived, in hours; 0 turns it off
+    pub activity_archive_hours: u64,
     pub host: String,
     pub port: u16,
     pub environment: String,
@@ -28,13 +62,36 @@ impl Config {
                 .unwrap_or_else(|_| "false".to_string())
                 .parse()
                 .unwrap_or(false),
-            jwt_secret: env::var("JWT_SECRET").unwrap_or_else(|_| {
-                "your-super-secret-jwt-key-change-this-in-production-12345".to_string()
-            }),
+            jwt_secret: env::var("JWT_SECRET").unwrap_or_else(|_| DEFAULT_JWT_SECRET.to_string()),
+            jwt_algorithm: env::var("JWT_ALGORITHM").unwrap_or_else(|_| "HS256".to_string()),
+            jwt_key_id: env::var("JWT_KEY_ID").unwrap_or_else(|_| "primary".to_string()),
+            jwt_private_key_path: env::var("JWT_PRIVATE_KEY_PATH").ok(),
+            jwt_public_key_path: env::var("JWT_PUBLIC_KEY_PATH").ok(),
+            jwt_previous_keys: parse_previous_keys(
+                &env::var("JWT_PREVIOUS_KEYS").unwrap_or_default(),
+            )?,
             jwt_expiration_days: env::var("JWT_EXPIRATION_DAYS")
                 .unwrap_or_else(|_| "30".to_string())
                 .parse()
                 .unwrap_or(30),
+            access_token_minutes: env::var("ACCESS_TOKEN_MINUTES")
+                .unwrap_or_else(|_| "15".to_string())
+                .parse()
+                .unwrap_or(15),
+            impersonation_minutes: env::var("IMPERSONATION_MINUTES")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            activity_checkpoint_minutes: env::var("ACTIVITY_CHECKPOINT_MINUTES")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .unwrap_or(60),
+            activity_archive_dir: env::var("ACTIVITY_ARCHIVE_DIR")
+                .unwrap_or_else(|_| "./data/activity-archives".to_string()),
+            activity_archive_hours: env::var("ACTIVITY_ARCHIVE_HOURS")
+                .unwrap_or_else(|_| "24".to_string())
+                .parse()
+                .unwrap_or(24),
             host: env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string()),
             port: env::var("PORT")
                 .unwrap_or_else(|_| "8080".to_string())
@@ -44,6 +101,7 @@ impl Config {
             client_base_url: env::var("BASE_URL")
                 .unwrap_or_else(|_| "http://localhost:3000".to_string()),
         };
+        config.validate()?;
         CONFIG
             .set(config.clone())
             .expect("Config can only be set once");
@@ -60,13 +118,36 @@ impl Config {
                 .unwrap_or_else(|_| "false".to_string())
                 .parse()
                 .unwrap_or(false),
-            jwt_secret: env::var("JWT_SECRET").unwrap_or_else(|_| {
-                "your-super-secret-jwt-key-change-this-in-production-12345".to_string()
-            }),
+            jwt_secret: env::var("JWT_SECRET").unwrap_or_else(|_| DEFAULT_JWT_SECRET.to_string()),
+            jwt_algorithm: env::var("JWT_ALGORITHM").unwrap_or_else(|_| "HS256".to_string()),
+            jwt_key_id: env::var("JWT_KEY_ID").unwrap_or_else(|_| "primary".to_string()),
+            jwt_private_key_path: env::var("JWT_PRIVATE_KEY_PATH").ok(),
+            jwt_public_key_path: env::var("JWT_PUBLIC_KEY_PATH").ok(),
+            jwt_previous_keys: parse_previous_keys(
+                &env::var("JWT_PREVIOUS_KEYS").unwrap_or_default(),
+            )?,
             jwt_expiration_days: env::var("JWT_EXPIRATION_DAYS")
                 .unwrap_or_else(|_| "30".to_string())
                 .parse()
                 .unwrap_or(30),
+            access_token_minutes: env::var("ACCESS_TOKEN_MINUTES")
+                .unwrap_or_else(|_| "15".to_string())
+                .parse()
+                .unwrap_or(15),
+            impersonation_minutes: env::var("IMPERSONATION_MINUTES")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            activity_checkpoint_minutes: env::var("ACTIVITY_CHECKPOINT_MINUTES")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .unwrap_or(60),
+            activity_archive_dir: env::var("ACTIVITY_ARCHIVE_DIR")
+                .unwrap_or_else(|_| "./data/activity-archives".to_string()),
+            activity_archive_hours: env::var("ACTIVITY_ARCHIVE_HOURS")
+                .unwrap_or_else(|_| "24".to_string())
+                .parse()
+                .unwrap_or(24),
             host: env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string()),
             port: env::var("PORT")
                 .unwrap_or_else(|_| "8080".to_string())
@@ -76,12 +157,24 @@ impl Config {
             client_base_url: env::var("BASE_URL")
                 .unwrap_or_else(|_| "http://localhost:3000".to_string()),
         };
+        config.validate()?;
         CONFIG
             .set(config.clone())
             .expect("Config can only be set once");
         Ok(config)
     }
 
+    /// Reject configurations that are unsafe to run with
+    pub fn validate(&self) -> Result<()> {
+        if self.is_production()
+            && self.jwt_algorithm == "HS256"
+            && self.jwt_secret == DEFAULT_JWT_SECRET
+        {
+            bail!("JWT_SECRET must be set in production; the default secret is public");
+        }
+        Ok(())
+    }
+
     pub fn is_production(&self) -> bool {
         self.environment == "production"
     }
@@ -95,6 +188,27 @@ impl Config {
     }
 }
 
+/// Parse `JWT_PREVIOUS_KEYS`, a comma separated list of `kid:ALGORITHM:key` entries
+pub fn parse_previous_keys(raw: &str) -> Result<Vec<VerificationKeyConfig>> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let mut parts = entry.splitn(3, ':');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(kid), Some(algorithm), Some(key)) if !kid.is_empty() && !key.is_empty() => {
+                    Ok(VerificationKeyConfig {
+                        kid: kid.to_string(),
+                        algorithm: algorithm.to_string(),
+                        key: key.to_string(),
+                    })
+                }
+                _ => bail!("Invalid JWT_PREVIOUS_KEYS entry: expected kid:ALGORITHM:key"),
+            }
+        })
+        .collect()
+}
+
 pub fn config() -> &'static Config {
     CONFIG.get().expect("Config not initialized")
 }
@@ -0,0 +1,28 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountLockout {
+    pub user_id: Uuid,                       // UUID primary key / foreign key
+    pub failed_attempts: i32,                // Consecutive failures within the attempt window
+    pub last_failed_at: DateTime<Utc>,       // TIMESTAMPTZ
+    pub locked_until: Option<DateTime<Utc>>, // No login attempts are checked before this
+    pub locked_at: Option<DateTime<Utc>>,    // Set when the account was fully locked out
+    pub created_at: DateTime<Utc>,           // TIMESTAMPTZ
+    pub updated_at: DateTime<Utc>,           // TIMESTAMPTZ
+}
+
+impl AccountLockout {
+    /// Whether login attempts are currently refused
+    pub fn is_blocked(&self) -> bool {
+        self.locked_until
+            .is_some_and(|locked_until| locked_until > Utc::now())
+    }
+
+    /// Whether the account is locked out, rather than just delayed
+    pub fn is_locked(&self) -> bool {
+        self.is_blocked() && self.locked_at.is_some()
+    }
+}
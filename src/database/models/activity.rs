@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 
+use chrono::SecondsFormat;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlx::types::chrono::{DateTime, Utc};
 use uuid::Uuid;
 
@@ -18,9 +20,73 @@ pub struct CompanyActivity {
     pub metadata: Option<serde_json::Value>, // JSONB in PostgreSQL
     pub ip_address: Option<String>,
     pub user_agent: Option<String>,
+    pub api_key_id: Option<Uuid>, // Set instead of user_id for actions taken with an API key
+    pub impersonated_user_id: Option<Uuid>, // Set when an admin acted as this user; user_id is the admin
+    pub chain_seq: Option<i64>, // Position in the company's hash chain; None for rows logged before it
+    pub content_hash: Option<String>, // SHA-256 of description, metadata, IP and user agent
+    pub prev_hash: Option<String>,
+    pub row_hash: Option<String>,
+    pub redacted_at: Option<DateTime<Utc>>, // Personal data was scrubbed; content_hash is the original
     pub created_at: DateTime<Utc>,
 }
 
+/// Copy of a JSON value with object keys in sorted order, so it hashes the same however the
+/// database returned it
+fn sorted_keys(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(fields) => {
+            let mut keys: Vec<&String> = fields.keys().collect();
+            keys.sort();
+            serde_json::Value::Object(
+                keys.into_iter()
+                    .map(|key| (key.clone(), sorted_keys(&fields[key])))
+                    .collect(),
+            )
+        }
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.iter().map(sorted_keys).collect())
+        }
+        other => other.clone(),
+    }
+}
+
+/// Hash the first row of a company's chain points back to
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+impl CompanyActivity {
+    /// Hash of the fields personal data erasure may scrub. It is hashed separately so a
+    /// redacted row keeps its place in the chain.
+    pub fn compute_content_hash(&self) -> String {
+        let content = serde_json::json!([
+            self.description,
+            self.metadata.as_ref().map(sorted_keys),
+            self.ip_address,
+            self.user_agent,
+        ]);
+        format!("{:x}", Sha256::digest(content.to_string().as_bytes()))
+    }
+
+    /// Hash of the row's place in the chain, its identifying fields and its content hash
+    pub fn compute_row_hash(&self) -> String {
+        let row = serde_json::json!([
+            self.chain_seq,
+            self.prev_hash,
+            self.id,
+            self.company_id,
+            self.user_id,
+            self.api_key_id,
+            self.impersonated_user_id,
+            self.activity_type,
+            self.entity_type,
+            self.entity_id,
+            self.action,
+            self.created_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+            self.content_hash,
+        ]);
+        format!("{:x}", Sha256::digest(row.to_string().as_bytes()))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateActivityInput {
@@ -34,6 +100,8 @@ pub struct CreateActivityInput {
     pub metadata: Option<HashMap<String, serde_json::Value>>,
     pub ip_address: String,
     pub user_agent: String,
+    pub api_key_id: Option<Uuid>,
+    pub impersonated_user_id: Option<Uuid>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,11 +110,140 @@ pub struct ActivityFilter {
     pub company_id: Uuid,
     pub activity_type: Option<String>,
     pub entity_type: Option<String>,
+    pub entity_id: Option<Uuid>,
+    pub user_id: Option<Uuid>, // Actor
+    pub action: Option<String>,
+    pub start_date: Option<DateTime<Utc>>,
+    pub end_date: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+    pub cursor: Option<ActivityCursor>, // Continue after this entry
+    pub oldest_first: bool,
+    pub changes_only: bool, // Only entries that recorded a field-level diff
+}
+
+/// Position in the log, as the created time and id of the last entry returned
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+/// Filters accepted by the activity log endpoints
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityQuery {
+    pub activity_type: Option<String>,
+    pub entity_type: Option<String>,
+    pub entity_id: Option<Uuid>,
     pub user_id: Option<Uuid>,
+    pub action: Option<String>,
     pub start_date: Option<DateTime<Utc>>,
     pub end_date: Option<DateTime<Utc>>,
     pub limit: Option<i64>,
-    pub offset: Option<i64>,
+    pub cursor: Option<String>,
+    pub format: Option<String>, // Export only: csv or ndjson
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityPage {
+    pub items: Vec<CompanyActivity>,
+    pub next_cursor: Option<String>, // Pass as `cursor` to get the next page; None on the last page
+}
+
+/// One recorded update of an entity, as `{ "field": { "old": .., "new": .. } }`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityChange {
+    pub activity_id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub impersonated_user_id: Option<Uuid>,
+    pub api_key_id: Option<Uuid>,
+    pub action: String,
+    pub description: String,
+    pub changes: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityChangePage {
+    pub items: Vec<EntityChange>,
+    pub next_cursor: Option<String>,
+}
+
+/// Latest link of a company's activity chain
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityChainHead {
+    pub company_id: Uuid,
+    pub chain_seq: i64, // 0 until the first entry is logged
+    pub row_hash: String,
+    pub updated_at: DateTime<Utc>, // TIMESTAMPTZ
+}
+
+/// Signed statement of a company's chain head, for safekeeping outside the database. The
+/// signature is a JWT over the same values, verifiable with `/.well-known/jwks.json`.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityCheckpoint {
+    pub id: Uuid,
+    pub company_id: Uuid,
+    pub chain_seq: i64,
+    pub row_hash: String,
+    pub signature: String,
+    pub created_at: DateTime<Utc>, // TIMESTAMPTZ
+}
+
+/// Where and why verification of a company's chain failed
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainBreak {
+    pub chain_seq: i64,
+    pub activity_id: Option<Uuid>,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainVerification {
+    pub company_id: Uuid,
+    pub verified: bool,
+    pub first_seq: Option<i64>,        // First entry still in the database
+    pub archived_through: Option<i64>, // Entries up to here were archived
+    pub head_seq: i64,
+    pub head_hash: String,
+    pub checked: i64,
+    pub redacted: i64,
+    pub checkpoints_checked: i64,
+    pub first_break: Option<ChainBreak>,
+}
+
+/// One company's activity for one month, written to a compressed NDJSON file and removed
+/// from the database. `lastSeq` and `lastRowHash` let the chain be verified across the gap.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityArchive {
+    pub id: Uuid,
+    pub company_id: Uuid,
+    pub period_start: DateTime<Utc>, // TIMESTAMPTZ
+    pub period_end: DateTime<Utc>,   // TIMESTAMPTZ
+    pub file_name: String,           // Relative to the archive directory
+    pub entry_count: i32,
+    pub size_bytes: i64,
+    pub sha256: String, // Of the compressed file
+    pub first_seq: Option<i64>,
+    pub last_seq: Option<i64>,
+    pub last_row_hash: Option<String>,
+    pub created_at: DateTime<Utc>, // TIMESTAMPTZ
+}
+
+/// How many months of activity a company keeps online
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityRetention {
+    pub months: i32,
 }
 
 // Common activity types for consistency
@@ -76,6 +273,11 @@ pub mod EntityType {
     pub const COMPANY: &str = "company";
     pub const SKILL: &str = "skill";
     pub const SCHEDULE: &str = "schedule";
+    pub const API_KEY: &str = "api_key";
+    pub const ROLE: &str = "role";
+    pub const INVITE: &str = "invite";
+    pub const PTO_POLICY: &str = "pto_policy";
+    pub const HOLIDAY: &str = "holiday";
 }
 
 // Common actions
@@ -85,8 +287,24 @@ pub mod Action {
     pub const UPDATED: &str = "updated";
     pub const DELETED: &str = "deleted";
     pub const LOGIN: &str = "login";
+    pub const LOGIN_FAILED: &str = "login_failed";
     pub const LOGOUT: &str = "logout";
+    pub const ACCOUNT_LOCKED: &str = "account_locked";
+    pub const ACCOUNT_UNLOCKED: &str = "account_unlocked";
+    pub const EMAIL_VERIFIED: &str = "email_verified";
+    pub const EMAIL_CHANGED: &str = "email_changed";
+    pub const ACCOUNT_ERASED: &str = "account_erased";
+    pub const ACTIVITY_REDACTED: &str = "activity_redacted";
+    pub const EXPORTED: &str = "exported";
+    pub const SSO_LOGIN: &str = "sso_login";
+    pub const SSO_LINKED: &str = "sso_linked";
+    pub const TWO_FACTOR_ENABLED: &str = "two_factor_enabled";
+    pub const TWO_FACTOR_DISABLED: &str = "two_factor_disabled";
+    pub const IMPERSONATION_STARTED: &str = "impersonation_started";
+    pub const IMPERSONATION_ENDED: &str = "impersonation_ended";
     pub const INVITED: &str = "invited";
+    pub const INVITE_RESENT: &str = "invite_resent";
+    pub const INVITE_REVOKED: &str = "invite_revoked";
     pub const ACTIVATED: &str = "activated";
     pub const DEACTIVATED: &str = "deactivated";
     pub const ASSIGNED: &str = "assigned";
@@ -94,6 +312,9 @@ pub mod Action {
     pub const APPROVED: &str = "approved";
     pub const REJECTED: &str = "rejected";
     pub const CLAIMED: &str = "claimed";
+    pub const CLOCKED_IN: &str = "clocked_in";
+    pub const CLOCKED_OUT: &str = "clocked_out";
+    pub const ACCRUED: &str = "accrued";
     pub const RELEASED: &str = "released";
     pub const CANCELLED: &str = "cancelled";
     pub const SWITCH_COMPANY: &str = "switch_company";
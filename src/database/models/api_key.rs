@@ -0,0 +1,107 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKey {
+    pub id: Uuid,         // UUID primary key
+    pub company_id: Uuid, // UUID foreign key
+    pub name: String,
+    pub prefix: String, // Public part of the key, e.g. "slk_ab12cd34"
+    #[serde(skip_serializing, default)]
+    pub key_hash: String, // SHA-256 of the full key
+    pub scopes: Vec<String>, // TEXT[], e.g. ["shifts:read"]
+    pub rate_limit_per_minute: i32,
+    pub created_by: Uuid, // User whose company role the key acts with
+    pub last_used_at: Option<DateTime<Utc>>, // TIMESTAMPTZ
+    pub last_used_ip: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>, // TIMESTAMPTZ
+    pub revoked_at: Option<DateTime<Utc>>, // TIMESTAMPTZ
+    pub created_at: DateTime<Utc>,         // TIMESTAMPTZ
+    pub updated_at: DateTime<Utc>,         // TIMESTAMPTZ
+}
+
+impl ApiKey {
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none()
+            && self
+                .expires_at
+                .is_none_or(|expires_at| expires_at > Utc::now())
+    }
+
+    /// Whether the key grants a scope; `area:write` implies `area:read`
+    pub fn has_scope(&self, scope: &str) -> bool {
+        let implied_by = scope
+            .strip_suffix(":read")
+            .map(|area| format!("{}:write", area));
+
+        self.scopes
+            .iter()
+            .any(|granted| granted == scope || Some(granted) == implied_by.as_ref())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiKeyInput {
+    pub name: String,
+    pub scopes: Vec<String>,
+    /// Requests per minute; defaults to 60
+    pub rate_limit_per_minute: Option<i32>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedApiKeyResponse {
+    pub api_key: ApiKey,
+    /// Full key; shown only once
+    pub key: String,
+}
+
+// Scopes that can be granted to API keys
+#[allow(non_snake_case)]
+pub mod ApiKeyScope {
+    pub const SHIFTS_READ: &str = "shifts:read";
+    pub const SHIFTS_WRITE: &str = "shifts:write";
+    pub const TIMEOFF_READ: &str = "timeoff:read";
+    pub const TIMEOFF_WRITE: &str = "timeoff:write";
+    pub const SWAPS_READ: &str = "swaps:read";
+    pub const SWAPS_WRITE: &str = "swaps:write";
+    pub const SCHEDULES_READ: &str = "schedules:read";
+    pub const SCHEDULES_WRITE: &str = "schedules:write";
+    pub const SKILLS_READ: &str = "skills:read";
+    pub const SKILLS_WRITE: &str = "skills:write";
+    pub const PTO_READ: &str = "pto:read";
+    pub const PTO_WRITE: &str = "pto:write";
+    pub const EMPLOYEES_READ: &str = "employees:read";
+    pub const EMPLOYEES_WRITE: &str = "employees:write";
+    pub const LOCATIONS_READ: &str = "locations:read";
+    pub const LOCATIONS_WRITE: &str = "locations:write";
+    pub const TEAMS_READ: &str = "teams:read";
+    pub const TEAMS_WRITE: &str = "teams:write";
+    pub const STATS_READ: &str = "stats:read";
+
+    pub const ALL: &[&str] = &[
+        SHIFTS_READ,
+        SHIFTS_WRITE,
+        TIMEOFF_READ,
+        TIMEOFF_WRITE,
+        SWAPS_READ,
+        SWAPS_WRITE,
+        SCHEDULES_READ,
+        SCHEDULES_WRITE,
+        SKILLS_READ,
+        SKILLS_WRITE,
+        PTO_READ,
+        PTO_WRITE,
+        EMPLOYEES_READ,
+        EMPLOYEES_WRITE,
+        LOCATIONS_READ,
+        LOCATIONS_WRITE,
+        TEAMS_READ,
+        TEAMS_WRITE,
+        STATS_READ,
+    ];
+}
@@ -0,0 +1,17 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// When a worker clocked in and out of a shift
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ShiftAttendance {
+    pub id: Uuid,
+    pub company_id: Uuid,
+    pub shift_id: Uuid,
+    pub user_id: Uuid,
+    pub clock_in_at: DateTime<Utc>,          // TIMESTAMPTZ
+    pub clock_out_at: Option<DateTime<Utc>>, // TIMESTAMPTZ, set when they clock out
+    pub created_at: DateTime<Utc>,           // TIMESTAMPTZ
+    pub updated_at: DateTime<Utc>,           // TIMESTAMPTZ
+}
@@ -2,7 +2,7 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::database::models::CompanyInfo;
+use crate::database::models::{CompanyInfo, SsoLinkChallenge, TwoFactorChallenge};
 
 use super::user::User;
 
@@ -20,12 +20,27 @@ pub struct LoginInput {
 pub struct AuthResponse {
     /// JWT authentication token
     pub token: String,
+    /// Refresh token used to obtain new access tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
     /// User information
     pub user: User,
     // Company information if available
     pub company: Option<CompanyInfo>,
 }
 
+/// Result of the password step of login
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    /// Signed in; tokens issued
+    Authenticated(Box<AuthResponse>),
+    /// A second factor is needed before tokens are issued
+    TwoFactorRequired(TwoFactorChallenge),
+    /// Single sign-on found an existing account whose owner has to confirm the link
+    SsoLinkRequired(SsoLinkChallenge),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 #[serde(rename_all = "camelCase")]
 pub struct PasswordResetToken {
@@ -61,3 +76,30 @@ pub struct ResetPasswordInput {
 pub struct ResetPasswordResponse {
     pub message: String,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct EmailVerificationToken {
+    pub id: Uuid,      // UUID primary key
+    pub user_id: Uuid, // UUID foreign key
+    pub email: String, // Address being verified; differs from the user's during an email change
+    pub token: String,
+    pub expires_at: DateTime<Utc>,      // TIMESTAMPTZ
+    pub used_at: Option<DateTime<Utc>>, // TIMESTAMPTZ
+    pub created_at: DateTime<Utc>,      // TIMESTAMPTZ
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyEmailInput {
+    /// Verification token from email
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeEmailInput {
+    /// Address to move the account to once it is verified
+    pub new_email: String,
+    /// Current password, to confirm the request
+    pub password: String,
+}
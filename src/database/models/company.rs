@@ -17,6 +17,7 @@ pub struct Company {
     pub address: Option<String>,
     pub logo_url: Option<String>,
     pub timezone: String,
+    pub require_two_factor: bool, // 2FA required for admins and managers
     pub created_at: DateTime<Utc>, // TIMESTAMPTZ maps to DateTime<Utc>
     pub updated_at: DateTime<Utc>,
 }
@@ -30,9 +31,9 @@ pub struct CompanyEmployee {
     pub role: CompanyRole,
     pub is_primary: bool,
     pub hire_date: Option<NaiveDate>, // DATE maps to NaiveDate
-    pub pto_balance_hours: i32,
-    pub sick_balance_hours: i32,
-    pub personal_balance_hours: i32,
+    pub pto_balance_hours: BigDecimal,
+    pub sick_balance_hours: BigDecimal,
+    pub personal_balance_hours: BigDecimal,
     pub pto_accrual_rate: BigDecimal, // DECIMAL maps to BigDecimal
     pub last_accrual_date: Option<NaiveDate>, // DATE maps to NaiveDate
     pub created_at: DateTime<Utc>,    // TIMESTAMPTZ maps to DateTime<Utc>
@@ -80,7 +81,9 @@ pub struct CompanyInfo {
     pub address: Option<String>,
     pub logo_url: Option<String>,
     pub timezone: String,
+    pub require_two_factor: bool,
     pub role: CompanyRole,
+    pub role_id: Option<Uuid>, // Custom role, if one replaces the built-in role's permissions
     pub is_primary: bool,
     pub hire_date: Option<NaiveDate>, // DATE type for hire dates
     pub created_at: DateTime<Utc>,    // TIMESTAMPTZ
@@ -103,7 +106,36 @@ pub struct CompanyEmployeeInfo {
     pub email: String,
     pub name: String,
     pub role: CompanyRole,
+    pub role_id: Option<Uuid>,             // Custom role, if any
     pub hire_date: Option<NaiveDate>,      // DATE type
     pub created_at: Option<DateTime<Utc>>, // TIMESTAMPTZ
     pub updated_at: Option<DateTime<Utc>>, // TIMESTAMPTZ
 }
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmployeeImportQuery {
+    pub dry_run: Option<bool>, // Validate only, without importing anything
+}
+
+/// Outcome of one line of an employee import
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmployeeImportRowResult {
+    pub line: usize, // 1-based line in the uploaded CSV
+    pub email: String,
+    pub valid: bool,
+    pub errors: Vec<String>,
+    pub user_id: Option<Uuid>, // Set once the row has been imported
+    pub new_user: bool,        // No account exists yet for the email
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmployeeImportResponse {
+    pub dry_run: bool,
+    pub imported: bool, // Nothing is imported unless every row is valid
+    pub total: usize,
+    pub failed: usize,
+    pub results: Vec<EmployeeImportRowResult>,
+}
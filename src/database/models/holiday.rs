@@ -0,0 +1,89 @@
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct CompanyHoliday {
+    pub id: Uuid,
+    pub company_id: Uuid,
+    pub location_id: Option<Uuid>, // Unset for the whole company
+    pub name: String,
+    pub holiday_date: NaiveDate,    // First day the holiday falls on
+    pub recurring: bool,            // Repeats every year on the same month and day
+    pub observed: bool,             // Unset on a location entry to work a company holiday
+    pub is_closed: bool,            // No shifts are expected
+    pub pay_multiplier: BigDecimal, // Applied to hours worked on the holiday
+    pub source_uid: Option<String>, // UID of the imported calendar event
+    pub created_at: DateTime<Utc>,  // TIMESTAMPTZ
+    pub updated_at: DateTime<Utc>,  // TIMESTAMPTZ
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompanyHolidayInput {
+    pub name: String,
+    pub holiday_date: NaiveDate,
+    pub location_id: Option<Uuid>,
+    pub recurring: Option<bool>,            // Defaults to false
+    pub observed: Option<bool>,             // Defaults to true
+    pub is_closed: Option<bool>,            // Defaults to true
+    pub pay_multiplier: Option<BigDecimal>, // Defaults to 1
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HolidayListQuery {
+    pub location_id: Option<Uuid>, // The location's entries and the company's
+}
+
+/// Location and period for the observed holiday calendar
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HolidayCalendarQuery {
+    pub location_id: Option<Uuid>, // Company-wide holidays only when unset
+    pub start_date: Option<NaiveDate>, // Defaults to today
+    pub end_date: Option<NaiveDate>, // Defaults to a year after the start
+}
+
+/// A day a holiday is kept on, with the entry that applies to it
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ObservedHoliday {
+    pub date: NaiveDate,
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub holiday: CompanyHoliday,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HolidayImportQuery {
+    pub location_id: Option<Uuid>, // Import for one location instead of the whole company
+    pub is_closed: Option<bool>,   // Defaults to true
+    pub pay_multiplier: Option<BigDecimal>, // Defaults to 1
+    pub dry_run: Option<bool>,     // Parse only, without importing anything
+}
+
+/// A holiday read from a calendar file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedHoliday {
+    pub uid: String,
+    pub name: String,
+    pub holiday_date: NaiveDate,
+    pub recurring: bool,
+    pub holiday_id: Option<Uuid>, // Set once the holiday has been imported
+    pub created: bool,            // Not imported before, so a new entry was added
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HolidayImportResponse {
+    pub dry_run: bool,
+    pub total: usize,
+    pub created: usize,
+    pub updated: usize,
+    pub holidays: Vec<ImportedHoliday>,
+}
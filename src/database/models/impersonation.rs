@@ -0,0 +1,65 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::user::User;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ImpersonationSession {
+    pub id: Uuid,             // UUID primary key
+    pub company_id: Uuid,     // UUID foreign key
+    pub admin_user_id: Uuid,  // The admin acting as the user
+    pub target_user_id: Uuid, // The user being impersonated
+    pub reason: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub expires_at: DateTime<Utc>,       // TIMESTAMPTZ
+    pub ended_at: Option<DateTime<Utc>>, // TIMESTAMPTZ
+    pub created_at: DateTime<Utc>,       // TIMESTAMPTZ
+}
+
+impl ImpersonationSession {
+    /// Impersonation lasts until the admin ends it or the token expires
+    pub fn is_active(&self) -> bool {
+        self.ended_at.is_none() && self.expires_at > Utc::now()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateImpersonationInput {
+    pub company_id: Uuid,
+    pub admin_user_id: Uuid,
+    pub target_user_id: Uuid,
+    pub reason: Option<String>,
+    pub user_agent: String,
+    pub ip_address: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImpersonateInput {
+    /// Why support needs to act as the user, kept in the audit trail
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImpersonationResponse {
+    /// Access token acting as `user`; there is no refresh token
+    pub token: String,
+    pub user: User,
+    pub session: ImpersonationSession,
+}
+
+/// Shown to clients so they can display an impersonation banner
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImpersonationInfo {
+    pub session_id: Uuid,
+    pub admin_user_id: Uuid,
+    pub admin_name: String,
+    pub admin_email: String,
+    pub expires_at: DateTime<Utc>,
+}
@@ -24,7 +24,8 @@ pub struct InviteToken {
 }
 
 string_enum!(
-    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
     pub enum InviteTokenStatus {
         Pending => "pending",
         Accepted => "accepted",
@@ -50,6 +51,34 @@ pub struct CreateInviteResponse {
     pub expires_at: DateTime<Utc>, // TIMESTAMPTZ
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InviteListQuery {
+    pub status: Option<InviteTokenStatus>,
+    /// Matches any part of the invited address
+    pub email: Option<String>,
+}
+
+/// Outcome of one line of a bulk invite upload
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkInviteRowResult {
+    pub line: usize, // 1-based line in the uploaded CSV
+    pub email: String,
+    pub success: bool,
+    pub error: Option<String>,
+    pub invite_link: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>, // TIMESTAMPTZ
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkInviteResponse {
+    pub invited: usize,
+    pub failed: usize,
+    pub results: Vec<BulkInviteRowResult>,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetInviteInput {
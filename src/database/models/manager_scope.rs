@@ -0,0 +1,61 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct UserCompanyScope {
+    pub id: Uuid,                  // UUID primary key
+    pub user_company_id: Uuid,     // UUID foreign key
+    pub location_id: Option<Uuid>, // Set when the row grants a location
+    pub team_id: Option<Uuid>,     // Set when the row grants a team
+    pub created_at: DateTime<Utc>, // TIMESTAMPTZ
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManagerScopeInput {
+    /// Replaces the member's scope; both empty makes them company-wide
+    #[serde(default)]
+    pub location_ids: Vec<Uuid>,
+    #[serde(default)]
+    pub team_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManagerScopeResponse {
+    pub user_id: Uuid,
+    pub company_wide: bool,
+    pub location_ids: Vec<Uuid>,
+    pub team_ids: Vec<Uuid>,
+}
+
+impl ManagerScopeResponse {
+    pub fn new(user_id: Uuid, scopes: &[UserCompanyScope]) -> Self {
+        Self {
+            user_id,
+            company_wide: scopes.is_empty(),
+            location_ids: scopes
+                .iter()
+                .filter_map(|scope| scope.location_id)
+                .collect(),
+            team_ids: scopes.iter().filter_map(|scope| scope.team_id).collect(),
+        }
+    }
+}
+
+/// The locations and teams a scoped manager may act on.
+/// `team_ids` also holds every team at one of the `location_ids`.
+#[derive(Debug, Clone, Default)]
+pub struct ScopeFilter {
+    pub location_ids: Vec<Uuid>,
+    pub team_ids: Vec<Uuid>,
+}
+
+impl ScopeFilter {
+    pub fn includes_shift(&self, location_id: Uuid, team_id: Option<Uuid>) -> bool {
+        self.location_ids.contains(&location_id)
+            || team_id.is_some_and(|team_id| self.team_ids.contains(&team_id))
+    }
+}
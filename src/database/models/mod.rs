@@ -1,37 +1,61 @@
+pub mod account_lockout;
 pub mod activity;
+pub mod api_key;
+pub mod attendance;
 pub mod auth;
 pub mod company;
+pub mod holiday;
+pub mod impersonation;
 pub mod invite;
 pub mod location;
 pub mod macros;
+pub mod manager_scope;
+pub mod personal_data;
 pub mod pto;
+pub mod pto_policy;
+pub mod role;
 pub mod schedule;
+pub mod session;
 pub mod shift;
 pub mod skill;
+pub mod sso;
 pub mod stats;
 pub mod subscription;
 pub mod swap;
 pub mod team;
 pub mod time_off;
+pub mod two_factor;
 pub mod user;
 pub mod user_company;
 pub mod wage;
 
 // Re-export all models for easy importing
+pub use account_lockout::*;
 pub use activity::*;
+pub use api_key::*;
+pub use attendance::*;
 pub use auth::*;
 pub use company::*;
+pub use holiday::*;
+pub use impersonation::*;
 pub use invite::*;
 pub use location::*;
+pub use manager_scope::*;
+pub use personal_data::*;
 pub use pto::*;
+pub use pto_policy::*;
+pub use role::*;
 pub use schedule::*;
+pub use session::*;
 pub use shift::*;
 pub use skill::*;
+pub use sso::*;
 pub use stats::*;
 pub use subscription::*;
 pub use swap::*;
 pub use team::*;
 pub use time_off::*;
-pub use user::{CreateUserInput, User}; // Specific re-export to avoid conflict
+pub use two_factor::*;
+pub use user::*;
 pub use user_company::*;
 pub use wage::*;
@@ -0,0 +1,43 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    activity::CompanyActivity,
+    attendance::ShiftAttendance,
+    company::CompanyEmployee,
+    pto::PtoBalanceHistory,
+    schedule::ShiftAssignment,
+    shift::{Shift, ShiftClaim},
+    skill::UserSkill,
+    swap::{ShiftSwap, ShiftSwapResponse},
+    time_off::TimeOffRequest,
+    user::User,
+    wage::WageHistory,
+};
+
+/// Everything stored about a user, across all of their companies
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PersonalDataExport {
+    pub exported_at: DateTime<Utc>, // TIMESTAMPTZ
+    pub profile: User,
+    pub memberships: Vec<CompanyEmployee>, // Includes PTO balances per company
+    pub pto_balance_history: Vec<PtoBalanceHistory>,
+    pub shifts: Vec<Shift>, // Shifts the user was assigned to or claimed
+    pub shift_assignments: Vec<ShiftAssignment>,
+    pub shift_claims: Vec<ShiftClaim>,
+    pub attendance: Vec<ShiftAttendance>,
+    pub shift_swaps: Vec<ShiftSwap>, // Requested by or offered to the user
+    pub shift_swap_responses: Vec<ShiftSwapResponse>,
+    pub time_off_requests: Vec<TimeOffRequest>,
+    pub skills: Vec<UserSkill>,
+    pub wage_history: Vec<WageHistory>,
+    pub activity: Vec<CompanyActivity>, // Entries the user performed, or an admin performed as them
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EraseAccountInput {
+    /// Current password, to confirm the request
+    pub password: String,
+}
@@ -13,16 +13,30 @@ pub struct PtoBalanceHistory {
     pub company_id: Uuid,
     pub balance_type: PtoBalanceType,
     pub change_type: PtoChangeType,
-    pub hours_changed: i32,
-    pub previous_balance: i32,
-    pub new_balance: i32,
+    pub hours_changed: BigDecimal,
+    pub previous_balance: BigDecimal,
+    pub new_balance: BigDecimal,   // Running total after this entry
+    pub effective_date: NaiveDate, // Day the change counts from
     pub description: Option<String>,
     pub related_time_off_id: Option<Uuid>, // Reference to time_off_requests table
-    pub created_at: DateTime<Utc>,         // TIMESTAMPTZ
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>, // TIMESTAMPTZ
+}
+
+/// A change to record on the ledger
+#[derive(Debug, Clone)]
+pub struct PtoLedgerEntryInput {
+    pub balance_type: PtoBalanceType,
+    pub change_type: PtoChangeType,
+    pub hours_changed: BigDecimal,
+    pub effective_date: NaiveDate,
+    pub description: String,
+    pub related_time_off_id: Option<Uuid>,
+    pub created_by: Option<Uuid>,
 }
 
 string_enum! {
-    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
     #[serde(rename_all = "lowercase")]
     pub enum PtoBalanceType {
         Pto => "pto",
@@ -32,12 +46,14 @@ string_enum! {
 }
 
 string_enum! {
-    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
     #[serde(rename_all = "lowercase")]
     pub enum PtoChangeType {
         Accrual => "accrual",
         Usage => "usage",
         Adjustment => "adjustment",
+        Forfeiture => "forfeiture", // Lost over a carryover limit
+        Refund => "refund",         // Usage returned when time off is cancelled
     }
 }
 
@@ -45,9 +61,9 @@ string_enum! {
 #[serde(rename_all = "camelCase")]
 pub struct PtoBalance {
     pub user_id: Uuid,
-    pub pto_balance_hours: i32,
-    pub sick_balance_hours: i32,
-    pub personal_balance_hours: i32,
+    pub pto_balance_hours: BigDecimal,
+    pub sick_balance_hours: BigDecimal,
+    pub personal_balance_hours: BigDecimal,
     pub pto_accrual_rate: BigDecimal, // DECIMAL type from PostgreSQL
     pub hire_date: Option<NaiveDate>, // DATE type
     pub last_accrual_date: Option<NaiveDate>, // DATE type
@@ -56,9 +72,9 @@ pub struct PtoBalance {
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PtoBalanceUpdateInput {
-    pub pto_balance_hours: Option<i32>,
-    pub sick_balance_hours: Option<i32>,
-    pub personal_balance_hours: Option<i32>,
+    pub pto_balance_hours: Option<BigDecimal>,
+    pub sick_balance_hours: Option<BigDecimal>,
+    pub personal_balance_hours: Option<BigDecimal>,
     pub pto_accrual_rate: Option<BigDecimal>, // DECIMAL type
     pub hire_date: Option<NaiveDate>,         // DATE type
 }
@@ -68,8 +84,9 @@ pub struct PtoBalanceUpdateInput {
 pub struct PtoBalanceAdjustmentInput {
     pub change_type: PtoChangeType,
     pub balance_type: PtoBalanceType,
-    pub hours_changed: i32,
+    pub hours_changed: BigDecimal,
     pub description: String,
+    pub effective_date: Option<NaiveDate>, // Defaults to today
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -80,6 +97,25 @@ pub struct PtoBalanceAccrual {
     pub hire_date: Option<NaiveDate>,
     pub last_accrual_date: Option<NaiveDate>,
     pub months_since_last_accrual: i32,
-    pub hours_to_accrue: i32,
-    pub new_balance: i32,
+    pub hours_to_accrue: BigDecimal,
+    pub new_balance: BigDecimal,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PtoBalanceAsOfQuery {
+    pub date: Option<NaiveDate>, // Defaults to today
+    pub user_id: Option<Uuid>,   // Only this member
+}
+
+/// A member's balances summed from the ledger entries effective on or before a date
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PtoBalanceAsOf {
+    pub user_id: Uuid,
+    pub name: String,
+    pub as_of: NaiveDate,
+    pub pto_balance_hours: BigDecimal,
+    pub sick_balance_hours: BigDecimal,
+    pub personal_balance_hours: BigDecimal,
 }
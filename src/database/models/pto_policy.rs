@@ -0,0 +1,186 @@
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{macros::string_enum, pto::PtoBalanceType};
+
+string_enum! {
+    #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum AccrualMethod {
+        PerPayPeriod => "per_pay_period",
+        PerHourWorked => "per_hour_worked",
+    }
+}
+
+string_enum! {
+    #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    pub enum PayPeriod {
+        Weekly => "weekly",
+        Biweekly => "biweekly",
+        Semimonthly => "semimonthly",
+        Monthly => "monthly",
+    }
+}
+
+string_enum! {
+    #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    pub enum AccrualYearStart {
+        Calendar => "calendar",       // Every year on year_start_month/year_start_day
+        Anniversary => "anniversary", // Every year on the member's hire date
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PtoAccrualPolicy {
+    pub id: Uuid,
+    pub company_id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub balance_type: PtoBalanceType,
+    pub accrual_method: AccrualMethod,
+    pub pay_period: Option<PayPeriod>, // Required for per-pay-period accrual
+    pub annual_cap_hours: Option<BigDecimal>, // Most that accrues in one accrual year
+    pub max_balance_hours: Option<BigDecimal>, // Accrual stops at this balance
+    pub carryover_limit_hours: Option<BigDecimal>, // Most kept when the year resets; all when unset
+    pub year_start: AccrualYearStart,
+    pub year_start_month: i32,
+    pub year_start_day: i32,
+    pub waiting_period_days: i32, // Days after the hire date before accrual starts
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>, // TIMESTAMPTZ
+    pub updated_at: DateTime<Utc>, // TIMESTAMPTZ
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PtoAccrualTier {
+    pub id: Uuid,
+    pub policy_id: Uuid,
+    pub min_tenure_months: i32,
+    pub accrual_rate: BigDecimal, // Hours per pay period, or per hour worked
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PtoPolicyAssignment {
+    pub id: Uuid,
+    pub policy_id: Uuid,
+    pub company_id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub team_id: Option<Uuid>,
+    pub location_id: Option<Uuid>, // All three unset for the whole company
+    pub created_at: DateTime<Utc>, // TIMESTAMPTZ
+}
+
+/// A policy with its tiers and the groups it is assigned to
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PtoAccrualPolicyDetails {
+    #[serde(flatten)]
+    pub policy: PtoAccrualPolicy,
+    pub tiers: Vec<PtoAccrualTier>,
+    pub assignments: Vec<PtoPolicyAssignment>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PtoAccrualTierInput {
+    pub min_tenure_months: i32,
+    pub accrual_rate: BigDecimal,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PtoAccrualPolicyInput {
+    pub name: String,
+    pub description: Option<String>,
+    pub balance_type: Option<PtoBalanceType>, // Defaults to pto
+    pub accrual_method: AccrualMethod,
+    pub pay_period: Option<PayPeriod>,
+    pub tiers: Vec<PtoAccrualTierInput>,
+    pub annual_cap_hours: Option<BigDecimal>,
+    pub max_balance_hours: Option<BigDecimal>,
+    pub carryover_limit_hours: Option<BigDecimal>,
+    pub year_start: Option<AccrualYearStart>, // Defaults to calendar
+    pub year_start_month: Option<i32>,        // Defaults to January
+    pub year_start_day: Option<i32>,          // Defaults to the 1st
+    pub waiting_period_days: Option<i32>,
+    pub is_active: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PtoPolicyAssignmentInput {
+    pub user_id: Option<Uuid>,
+    pub team_id: Option<Uuid>,
+    pub location_id: Option<Uuid>, // At most one; none assigns the whole company
+}
+
+/// A member's accrual progress for one balance
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PtoAccrualState {
+    pub user_id: Uuid,
+    pub company_id: Uuid,
+    pub balance_type: PtoBalanceType,
+    pub policy_id: Option<Uuid>,
+    pub accrued_through: NaiveDate, // Last day accrual covers
+    pub year_start: NaiveDate,      // Start of the current accrual year
+    pub accrued_this_year: BigDecimal,
+    pub updated_at: DateTime<Utc>, // TIMESTAMPTZ
+}
+
+/// A member and the policy that applies to them for one balance
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PtoPolicyMember {
+    pub user_id: Uuid,
+    pub name: String,
+    pub policy_id: Uuid,
+    pub assigned_at: DateTime<Utc>, // TIMESTAMPTZ
+    pub hire_date: Option<NaiveDate>,
+    pub pto_balance_hours: BigDecimal,
+    pub sick_balance_hours: BigDecimal,
+    pub personal_balance_hours: BigDecimal,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PtoAccrualRunQuery {
+    pub as_of: Option<NaiveDate>, // Defaults to today
+    pub user_id: Option<Uuid>,    // Only this member
+}
+
+/// What an accrual run does, or would do, for one member and balance
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PtoAccrualResult {
+    pub user_id: Uuid,
+    pub name: String,
+    pub policy_id: Uuid,
+    pub policy_name: String,
+    pub balance_type: PtoBalanceType,
+    pub tenure_months: i32,
+    pub accrual_rate: Option<BigDecimal>,
+    pub pay_periods: i64,
+    pub hours_worked: BigDecimal,
+    pub accrued_hours: BigDecimal,   // After caps
+    pub capped_hours: BigDecimal,    // Held back by the annual and balance caps
+    pub forfeited_hours: BigDecimal, // Lost over the carryover limit at year end
+    pub previous_balance: BigDecimal,
+    pub new_balance: BigDecimal,
+    pub accrued_through: Option<NaiveDate>,
+    pub skipped_reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PtoAccrualRun {
+    pub as_of: NaiveDate,
+    pub preview: bool, // Nothing was changed
+    pub results: Vec<PtoAccrualResult>,
+}
@@ -0,0 +1,145 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::company::CompanyRole;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Role {
+    pub id: Uuid,         // UUID primary key
+    pub company_id: Uuid, // UUID foreign key
+    pub name: String,
+    pub description: Option<String>,
+    pub permissions: Vec<String>,  // TEXT[], e.g. ["shifts.create"]
+    pub is_builtin: bool,          // Seeded with the company and matching a `CompanyRole`
+    pub created_at: DateTime<Utc>, // TIMESTAMPTZ
+    pub updated_at: DateTime<Utc>, // TIMESTAMPTZ
+}
+
+/// The roles every company is created with: name, description and permissions.
+/// Members without a custom role get the permissions of the one matching their company role.
+pub fn builtin_roles() -> [(CompanyRole, &'static str, &'static [&'static str]); 3] {
+    [
+        (
+            CompanyRole::Admin,
+            "Full access to the company",
+            Permission::ALL,
+        ),
+        (
+            CompanyRole::Manager,
+            "Manages scheduling, time off and employees",
+            Permission::MANAGER,
+        ),
+        (
+            CompanyRole::Employee,
+            "Works shifts and manages their own requests",
+            &[],
+        ),
+    ]
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleInput {
+    pub name: String,
+    pub description: Option<String>,
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssignRoleInput {
+    /// Custom role to give the member; `null` returns them to their built-in role
+    pub role_id: Option<Uuid>,
+}
+
+// Permissions that can be granted to roles
+#[allow(non_snake_case)]
+pub mod Permission {
+    pub const SHIFTS_VIEW: &str = "shifts.view";
+    pub const SHIFTS_CREATE: &str = "shifts.create";
+    pub const SHIFTS_EDIT: &str = "shifts.edit";
+    pub const SHIFTS_ASSIGN: &str = "shifts.assign";
+    pub const TIMEOFF_VIEW: &str = "timeoff.view";
+    pub const TIMEOFF_APPROVE: &str = "timeoff.approve";
+    pub const SWAPS_VIEW: &str = "swaps.view";
+    pub const SWAPS_APPROVE: &str = "swaps.approve";
+    pub const SCHEDULES_MANAGE: &str = "schedules.manage";
+    pub const SKILLS_MANAGE: &str = "skills.manage";
+    pub const PTO_VIEW: &str = "pto.view";
+    pub const PTO_MANAGE: &str = "pto.manage";
+    pub const EMPLOYEES_VIEW: &str = "employees.view";
+    pub const EMPLOYEES_MANAGE: &str = "employees.manage";
+    pub const EMPLOYEES_INVITE: &str = "employees.invite";
+    pub const LOCATIONS_MANAGE: &str = "locations.manage";
+    pub const TEAMS_MANAGE: &str = "teams.manage";
+    pub const STATS_VIEW: &str = "stats.view";
+    pub const WAGES_VIEW: &str = "wages.view";
+    pub const WAGES_EDIT: &str = "wages.edit";
+    pub const ROLES_MANAGE: &str = "roles.manage";
+    pub const COMPANY_SETTINGS: &str = "company.settings";
+    pub const ACTIVITY_VIEW: &str = "activity.view";
+    pub const USERS_IMPERSONATE: &str = "users.impersonate";
+    /// Act on every location and team, whatever scope the member was given
+    pub const SCOPE_BYPASS: &str = "scope.bypass";
+
+    pub const ALL: &[&str] = &[
+        SHIFTS_VIEW,
+        SHIFTS_CREATE,
+        SHIFTS_EDIT,
+        SHIFTS_ASSIGN,
+        TIMEOFF_VIEW,
+        TIMEOFF_APPROVE,
+        SWAPS_VIEW,
+        SWAPS_APPROVE,
+        SCHEDULES_MANAGE,
+        SKILLS_MANAGE,
+        PTO_VIEW,
+        PTO_MANAGE,
+        EMPLOYEES_VIEW,
+        EMPLOYEES_MANAGE,
+        EMPLOYEES_INVITE,
+        LOCATIONS_MANAGE,
+        TEAMS_MANAGE,
+        STATS_VIEW,
+        WAGES_VIEW,
+        WAGES_EDIT,
+        ROLES_MANAGE,
+        COMPANY_SETTINGS,
+        ACTIVITY_VIEW,
+        USERS_IMPERSONATE,
+        SCOPE_BYPASS,
+    ];
+
+    /// Never granted to an admin acting as another user
+    pub const BLOCKED_WHILE_IMPERSONATING: &[&str] = &[
+        WAGES_EDIT,
+        ROLES_MANAGE,
+        COMPANY_SETTINGS,
+        USERS_IMPERSONATE,
+    ];
+
+    /// Everything but wages, roles, company settings, the activity log, impersonation and
+    /// scope bypass
+    pub const MANAGER: &[&str] = &[
+        SHIFTS_VIEW,
+        SHIFTS_CREATE,
+        SHIFTS_EDIT,
+        SHIFTS_ASSIGN,
+        TIMEOFF_VIEW,
+        TIMEOFF_APPROVE,
+        SWAPS_VIEW,
+        SWAPS_APPROVE,
+        SCHEDULES_MANAGE,
+        SKILLS_MANAGE,
+        PTO_VIEW,
+        PTO_MANAGE,
+        EMPLOYEES_VIEW,
+        EMPLOYEES_MANAGE,
+        EMPLOYEES_INVITE,
+        LOCATIONS_MANAGE,
+        TEAMS_MANAGE,
+        STATS_VIEW,
+    ];
+}
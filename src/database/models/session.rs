@@ -0,0 +1,56 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct UserSession {
+    pub id: Uuid,                 // UUID primary key
+    pub user_id: Uuid,            // UUID foreign key
+    pub company_id: Option<Uuid>, // Company the session is currently scoped to
+    #[serde(skip_serializing)]
+    pub refresh_token_hash: String,
+    #[serde(skip_serializing)]
+    pub previous_refresh_token_hash: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub expires_at: DateTime<Utc>,         // TIMESTAMPTZ
+    pub last_used_at: DateTime<Utc>,       // TIMESTAMPTZ
+    pub revoked_at: Option<DateTime<Utc>>, // TIMESTAMPTZ
+    pub mfa_verified: bool,                // Signed in with a second factor
+    pub created_at: DateTime<Utc>,         // TIMESTAMPTZ
+}
+
+impl UserSession {
+    /// A session is active until it is revoked or its refresh token expires
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none() && self.expires_at > Utc::now()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateSessionInput {
+    pub user_id: Uuid,
+    pub company_id: Option<Uuid>,
+    pub refresh_token_hash: String,
+    pub user_agent: String,
+    pub ip_address: String,
+    pub expires_at: DateTime<Utc>,
+    pub mfa_verified: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshTokenInput {
+    /// Refresh token issued at login or by a previous refresh
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionResponse {
+    #[serde(flatten)]
+    pub session: UserSession,
+    /// Whether this is the session the request was made with
+    pub is_current: bool,
+}
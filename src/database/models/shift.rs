@@ -23,6 +23,16 @@ pub struct Shift {
     pub updated_at: DateTime<Utc>, // TIMESTAMPTZ
 }
 
+/// A newly created shift with anything the scheduler should double-check, such as the
+/// location being closed for a holiday
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedShift {
+    #[serde(flatten)]
+    pub shift: Shift,
+    pub warnings: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateUpdateShiftInput {
@@ -0,0 +1,144 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::CompanyRole;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct CompanySsoConfig {
+    pub id: Uuid,         // UUID primary key
+    pub company_id: Uuid, // UUID foreign key
+    pub issuer: String,
+    pub client_id: String,
+    #[serde(skip_serializing)]
+    pub client_secret: String,
+    pub allowed_domains: Vec<String>, // TEXT[]; empty allows any email domain
+    pub default_role: CompanyRole,    // Role given to just-in-time provisioned members
+    pub enabled: bool,
+    pub authorization_endpoint: String, // Resolved from discovery
+    pub token_endpoint: String,         // Resolved from discovery
+    pub jwks_uri: String,               // Resolved from discovery
+    pub created_at: DateTime<Utc>,      // TIMESTAMPTZ
+    pub updated_at: DateTime<Utc>,      // TIMESTAMPTZ
+}
+
+impl CompanySsoConfig {
+    /// Whether an email address belongs to one of the allowed domains
+    pub fn allows_email(&self, email: &str) -> bool {
+        if self.allowed_domains.is_empty() {
+            return true;
+        }
+
+        match email.rsplit_once('@') {
+            Some((_, domain)) => self
+                .allowed_domains
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(domain)),
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SsoConfigInput {
+    /// OIDC issuer URL; `/.well-known/openid-configuration` is fetched from it
+    pub issuer: String,
+    pub client_id: String,
+    /// Required when creating; omit to keep the stored secret
+    pub client_secret: Option<String>,
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+    pub default_role: Option<CompanyRole>,
+    pub enabled: Option<bool>,
+}
+
+/// Provider endpoints resolved from discovery, stored alongside the config
+#[derive(Debug, Clone)]
+pub struct SsoEndpoints {
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SsoLoginState {
+    pub state: String, // Random value echoed back by the provider
+    pub company_id: Uuid,
+    pub code_verifier: String, // PKCE verifier
+    pub nonce: String,         // Must match the ID token's nonce claim
+    pub redirect_uri: String,
+    pub expires_at: DateTime<Utc>, // TIMESTAMPTZ
+    pub created_at: DateTime<Utc>, // TIMESTAMPTZ
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct UserIdentity {
+    pub id: Uuid,      // UUID primary key
+    pub user_id: Uuid, // UUID foreign key
+    pub company_id: Uuid,
+    pub issuer: String,
+    pub subject: String, // `sub` claim from the provider
+    pub email: String,
+    pub last_login_at: Option<DateTime<Utc>>, // TIMESTAMPTZ
+    pub created_at: DateTime<Utc>,            // TIMESTAMPTZ
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SsoStartResponse {
+    /// Provider URL to send the browser to
+    pub authorization_url: String,
+    pub state: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SsoCallbackInput {
+    /// `state` query parameter the provider redirected back with
+    pub state: String,
+    /// `code` query parameter the provider redirected back with
+    pub code: String,
+}
+
+/// An email domain the company claims for single sign-on, verified with a DNS TXT record
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct CompanySsoDomain {
+    pub id: Uuid,         // UUID primary key
+    pub company_id: Uuid, // UUID foreign key
+    pub domain: String,
+    pub verification_token: String, // Published as `shiftlinkr-verification=<token>`
+    pub verified_at: Option<DateTime<Utc>>, // TIMESTAMPTZ
+    pub created_at: DateTime<Utc>,  // TIMESTAMPTZ
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SsoDomainInput {
+    pub domain: String,
+}
+
+/// Returned by the callback when the provider's email matches an account that has to confirm
+/// the link itself
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SsoLinkChallenge {
+    /// Always true; lets clients tell this apart from a completed login
+    pub link_required: bool,
+    /// Short-lived token to send back with the account's password
+    pub link_token: String,
+    pub email: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SsoLinkInput {
+    pub link_token: String,
+    /// Password of the existing account, proving its owner wants the link
+    pub password: String,
+}
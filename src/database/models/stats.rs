@@ -1,4 +1,34 @@
-use serde::Serialize;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::database::models::ScopeFilter;
+
+/// Filters accepted by the stats endpoints
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsQuery {
+    pub start_date: Option<DateTime<Utc>>,
+    pub end_date: Option<DateTime<Utc>>,
+    pub user_id: Option<Uuid>,
+    pub location_id: Option<Uuid>,
+    pub team_id: Option<Uuid>,
+    pub compare: Option<bool>, // Also return the period of the same length just before
+}
+
+/// What the stats queries count: shifts starting, and requests overlapping, the period
+#[derive(Debug, Clone)]
+pub struct StatsFilter {
+    pub company_id: Uuid,
+    pub start_date: DateTime<Utc>,
+    pub end_date: DateTime<Utc>, // Exclusive
+    pub user_id: Option<Uuid>,   // Only shifts the user works and requests they made
+    pub location_id: Option<Uuid>,
+    pub team_id: Option<Uuid>,
+    pub scope: Option<ScopeFilter>, // A manager's locations and teams
+    pub scope_user_ids: Option<Vec<Uuid>>, // Members of those teams
+}
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -9,7 +39,7 @@ pub struct DashboardStats {
     pub pending_swap_requests: i64,
     pub approved_time_off: i64,
     pub total_hours: f64,
-    pub team_coverage: f64,
+    pub team_coverage: f64, // Filled share of the slots of shifts that were not cancelled, in %
 }
 
 #[derive(Debug, Serialize)]
@@ -20,6 +50,8 @@ pub struct ShiftStats {
     pub unassigned_shifts: i64,
     pub completed_shifts: i64,
     pub cancelled_shifts: i64,
+    pub required_slots: i64,
+    pub filled_slots: i64,
 }
 
 #[derive(Debug, Serialize)]
@@ -32,6 +64,189 @@ pub struct TimeOffStats {
     pub cancelled_requests: i64,
 }
 
+/// Stats for a period, optionally next to the period of the same length before it. `changes`
+/// holds the percent change of every figure, or null where the previous value was zero.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeriodStats<T: Serialize> {
+    #[serde(flatten)]
+    pub current: T,
+    pub period_start: DateTime<Utc>, // TIMESTAMPTZ
+    pub period_end: DateTime<Utc>,   // TIMESTAMPTZ
+    pub previous: Option<T>,
+    pub changes: Option<Map<String, Value>>,
+}
+
+/// Filters, grouping and sorting for the per-employee report
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmployeeReportQuery {
+    pub start_date: Option<DateTime<Utc>>,
+    pub end_date: Option<DateTime<Utc>>,
+    pub location_id: Option<Uuid>,
+    pub team_id: Option<Uuid>,
+    pub group_by: Option<String>, // team or location
+    pub sort: Option<String>,     // Any row field, name by default
+    pub order: Option<String>,    // asc or desc
+    pub format: Option<String>,   // json or csv
+}
+
+/// One employee's figures for the report period
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct EmployeeReportRow {
+    pub user_id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub scheduled_shifts: i64,
+    pub scheduled_hours: f64,
+    pub worked_hours: f64,   // Between clock-in and clock-out
+    pub overtime_hours: f64, // Worked past the weekly limit
+    pub late_arrivals: i64,
+    pub no_shows: i64, // Ended shifts never clocked in to, outside approved time off
+    pub dropped_shifts: i64, // Given away through approved swaps
+    pub time_off_requests: i64,
+    pub time_off_days: i64, // Approved days within the period
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmployeeReportTotals {
+    pub employees: i64,
+    pub scheduled_shifts: i64,
+    pub scheduled_hours: f64,
+    pub worked_hours: f64,
+    pub overtime_hours: f64,
+    pub late_arrivals: i64,
+    pub no_shows: i64,
+    pub dropped_shifts: i64,
+    pub time_off_requests: i64,
+    pub time_off_days: i64,
+}
+
+/// The employees of one team or location. Employees without one are grouped with no id.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmployeeReportGroup {
+    pub group_id: Option<Uuid>,
+    pub group_name: String,
+    pub employees: Vec<EmployeeReportRow>,
+    pub totals: EmployeeReportTotals,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmployeeReport {
+    pub period_start: DateTime<Utc>,               // TIMESTAMPTZ
+    pub period_end: DateTime<Utc>,                 // TIMESTAMPTZ
+    pub employees: Option<Vec<EmployeeReportRow>>, // Without grouping
+    pub groups: Option<Vec<EmployeeReportGroup>>,
+    pub totals: EmployeeReportTotals,
+}
+
+/// Location, period and bucket size for the coverage heatmap
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoverageQuery {
+    pub location_id: Uuid,
+    pub team_id: Option<Uuid>,
+    pub start_date: Option<DateTime<Utc>>,
+    pub end_date: Option<DateTime<Utc>>,
+    pub interval_minutes: Option<i32>, // 15, 30 or 60
+}
+
+/// Headcount needed and assigned across one interval of the heatmap
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct CoverageBucket {
+    pub bucket_start: DateTime<Utc>, // TIMESTAMPTZ
+    pub bucket_end: DateTime<Utc>,   // TIMESTAMPTZ
+    pub shifts: i64,
+    pub required: i64,
+    pub assigned: i64,
+    pub status: String, // unscheduled, understaffed, covered or overstaffed
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoverageHeatmap {
+    pub location_id: Uuid,
+    pub team_id: Option<Uuid>,
+    pub interval_minutes: i32,
+    pub period_start: DateTime<Utc>, // TIMESTAMPTZ
+    pub period_end: DateTime<Utc>,   // TIMESTAMPTZ
+    pub understaffed_buckets: usize,
+    pub overstaffed_buckets: usize,
+    pub buckets: Vec<CoverageBucket>,
+}
+
+/// Period, team or location and holidays for the fairness report
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FairnessQuery {
+    pub start_date: Option<DateTime<Utc>>,
+    pub end_date: Option<DateTime<Utc>>,
+    pub location_id: Option<Uuid>,
+    pub team_id: Option<Uuid>,
+    pub trend: Option<String>,    // week or month
+    pub holidays: Option<String>, // Comma-separated dates; the holiday calendar when unset
+}
+
+/// One member's shifts starting in one trend period
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct FairnessRow {
+    pub user_id: Uuid,
+    pub name: String,
+    pub trend_start: Option<NaiveDate>, // None for members without shifts
+    pub shifts: i64,
+    pub hours: f64,
+    pub weekend_shifts: i64,
+    pub night_shifts: i64,
+    pub holiday_shifts: i64,
+    pub short_notice_shifts: i64,
+    pub min_hours_per_week: Option<i32>,
+    pub max_hours_per_week: Option<i32>,
+}
+
+/// A member's unpopular shifts, each with their share of the group's total in %
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmployeeFairness {
+    pub user_id: Uuid,
+    pub name: String,
+    pub shifts: i64,
+    pub weekend_shifts: i64,
+    pub weekend_share: f64,
+    pub night_shifts: i64,
+    pub night_share: f64,
+    pub holiday_shifts: i64,
+    pub holiday_share: f64,
+    pub short_notice_shifts: i64,
+    pub short_notice_share: f64,
+    pub hours: f64,
+    pub hours_per_week: f64,
+    pub min_hours_per_week: Option<i32>,
+    pub max_hours_per_week: Option<i32>,
+    pub hours_status: String, // below_min, above_max or within
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FairnessTrendPoint {
+    pub period_start: NaiveDate,
+    pub score: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FairnessReport {
+    pub period_start: DateTime<Utc>, // TIMESTAMPTZ
+    pub period_end: DateTime<Utc>,   // TIMESTAMPTZ
+    pub score: f64, // 100 when unpopular shifts are spread evenly, lower as they concentrate
+    pub employees: Vec<EmployeeFairness>,
+    pub trend: Vec<FairnessTrendPoint>,
+}
+
 // Request/Response DTOs for approvals
 #[derive(Debug, serde::Deserialize)]
 pub struct ApprovalInput {
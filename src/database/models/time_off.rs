@@ -1,8 +1,9 @@
+use bigdecimal::BigDecimal;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use super::macros::string_enum;
+use super::{macros::string_enum, pto::PtoBalanceType};
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 #[serde(rename_all = "camelCase")]
@@ -15,7 +16,9 @@ pub struct TimeOffRequest {
     pub reason: Option<String>,
     pub request_type: TimeOffType,
     pub status: TimeOffStatus,
-    pub actioned_by: Option<Uuid>, // UUID for user references
+    pub duration_type: TimeOffDuration,
+    pub hours_deducted: Option<BigDecimal>, // Taken from the balance on approval
+    pub actioned_by: Option<Uuid>,          // UUID for user references
     pub action_notes: Option<String>,
     pub created_at: DateTime<Utc>, // TIMESTAMPTZ
     pub updated_at: DateTime<Utc>, // TIMESTAMPTZ
@@ -30,6 +33,8 @@ pub struct TimeOffRequestInput {
     pub end_date: DateTime<Utc>,   // DATE type
     pub reason: String,
     pub request_type: TimeOffType,
+    #[serde(default)]
+    pub duration_type: TimeOffDuration,
 }
 
 string_enum! {
@@ -45,6 +50,29 @@ string_enum! {
     }
 }
 
+impl TimeOffType {
+    /// Balance the time off is taken from. Bereavement and parental leave don't use a balance.
+    pub fn balance_type(&self) -> Option<PtoBalanceType> {
+        match self {
+            TimeOffType::Vacation | TimeOffType::Other => Some(PtoBalanceType::Pto),
+            TimeOffType::Sick => Some(PtoBalanceType::Sick),
+            TimeOffType::Personal | TimeOffType::Emergency => Some(PtoBalanceType::Personal),
+            TimeOffType::Bereavement | TimeOffType::MaternityPaternity => None,
+        }
+    }
+}
+
+string_enum! {
+    #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+    #[serde(rename_all = "snake_case")]
+    pub enum TimeOffDuration {
+        #[default]
+        FullDay => "full_day",       // Every day from the start date to the end date
+        PartialDay => "partial_day", // Half of a single day
+        Hourly => "hourly",          // The hours from the start time to the end time
+    }
+}
+
 string_enum! {
     #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
     pub enum TimeOffStatus {
@@ -54,3 +82,18 @@ string_enum! {
         Cancelled => "cancelled",
     }
 }
+
+/// Company settings for deducting time off from balances
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct CompanyTimeOffSettings {
+    pub workday_hours: BigDecimal, // Hours in a day for members without scheduled shifts
+    pub allow_negative_pto: bool,  // Approved time off may take a balance below zero
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompanyTimeOffSettingsInput {
+    pub workday_hours: BigDecimal,
+    pub allow_negative_pto: bool,
+}
@@ -0,0 +1,83 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct UserTwoFactor {
+    pub user_id: Uuid, // UUID primary key / foreign key
+    #[serde(skip_serializing)]
+    pub secret: String, // Base32 TOTP secret
+    pub enabled_at: Option<DateTime<Utc>>, // NULL until enrolment is confirmed
+    #[serde(skip_serializing)]
+    pub last_used_step: Option<i64>, // Last accepted TOTP time step (replay protection)
+    pub created_at: DateTime<Utc>, // TIMESTAMPTZ
+    pub updated_at: DateTime<Utc>, // TIMESTAMPTZ
+}
+
+impl UserTwoFactor {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled_at.is_some()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorCodeInput {
+    /// Six digit authenticator code, or a recovery code where accepted
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorLoginInput {
+    /// Token returned by the password step of login
+    pub mfa_token: String,
+    /// Six digit authenticator code or a one-time recovery code
+    pub code: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorSetupResponse {
+    /// Base32 secret for manual entry
+    pub secret: String,
+    /// otpauth:// URI to render as a QR code
+    pub provisioning_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorEnabledResponse {
+    /// One-time recovery codes; shown only once
+    pub recovery_codes: Vec<String>,
+    /// Fresh access token for the current session, now marked as verified
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorStatusResponse {
+    pub enabled: bool,
+    pub recovery_codes_remaining: i64,
+    /// Whether the current company requires 2FA for the user's role
+    pub required: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorChallenge {
+    /// Always true; lets clients tell this apart from a completed login
+    pub mfa_required: bool,
+    /// Short-lived token to send back with the authenticator code
+    pub mfa_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateCompanySecurityInput {
+    /// Require admins and managers to use two-factor authentication
+    pub require_two_factor: bool,
+}
@@ -2,8 +2,6 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use super::macros::string_enum;
-
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 #[serde(rename_all = "camelCase")]
 pub struct User {
@@ -12,24 +10,10 @@ pub struct User {
     #[serde(skip_serializing)]
     pub password_hash: String,
     pub name: String,
-    pub created_at: DateTime<Utc>, // TIMESTAMPTZ
-    pub updated_at: DateTime<Utc>, // TIMESTAMPTZ
-}
-
-string_enum! {
-    #[derive(Debug, Clone, Serialize, Deserialize)]
-    pub enum UserRole {
-        Owner => "owner",
-        Admin => "admin",
-        Manager => "manager",
-        Employee => "employee",
-    }
-}
-
-impl Default for UserRole {
-    fn default() -> Self {
-        UserRole::Employee
-    }
+    pub email_verified_at: Option<DateTime<Utc>>, // NULL until the current email is verified
+    pub erased_at: Option<DateTime<Utc>>,         // Set once the account's personal data was erased
+    pub created_at: DateTime<Utc>,                // TIMESTAMPTZ
+    pub updated_at: DateTime<Utc>,                // TIMESTAMPTZ
 }
 
 #[derive(Debug, Deserialize)]
@@ -49,8 +33,18 @@ impl User {
             email,
             password_hash,
             name,
+            email_verified_at: None,
+            erased_at: None,
             created_at: Utc::now(), // Use DateTime<Utc>
             updated_at: Utc::now(),
         }
     }
+
+    pub fn is_email_verified(&self) -> bool {
+        self.email_verified_at.is_some()
+    }
+
+    pub fn is_erased(&self) -> bool {
+        self.erased_at.is_some()
+    }
 }
@@ -0,0 +1,203 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::database::{get_pool, models::AccountLockout, utils::sql};
+
+pub async fn find_by_user_id(user_id: Uuid) -> Result<Option<AccountLockout>, sqlx::Error> {
+    let lockout = sqlx::query_as::<_, AccountLockout>(&sql(r#"
+        SELECT
+            user_id,
+            failed_attempts,
+            last_failed_at,
+            locked_until,
+            locked_at,
+            created_at,
+            updated_at
+        FROM
+            account_lockouts
+        WHERE
+            user_id = ?
+    "#))
+    .bind(user_id)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(lockout)
+}
+
+/// Lock the user's failure count until the transaction ends, so concurrent attempts are
+/// checked and counted one at a time. A row with no failures is created if there is none.
+pub async fn lock(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<AccountLockout, sqlx::Error> {
+    sqlx::query(&sql(r#"
+        INSERT INTO
+            account_lockouts (user_id)
+        VALUES
+            (?)
+        ON CONFLICT (user_id) DO NOTHING
+    "#))
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    let lockout = sqlx::query_as::<_, AccountLockout>(&sql(r#"
+        SELECT
+            user_id,
+            failed_attempts,
+            last_failed_at,
+            locked_until,
+            locked_at,
+            created_at,
+            updated_at
+        FROM
+            account_lockouts
+        WHERE
+            user_id = ?
+        FOR UPDATE
+    "#))
+    .bind(user_id)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(lockout)
+}
+
+/// Count a failed attempt, starting over when the previous failure is older than `window_start`
+pub async fn record_failure(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    window_start: DateTime<Utc>,
+) -> Result<AccountLockout, sqlx::Error> {
+    let lockout = sqlx::query_as::<_, AccountLockout>(&sql(r#"
+        INSERT INTO
+            account_lockouts (user_id, failed_attempts)
+        VALUES
+            (?, 1)
+        ON CONFLICT (user_id) DO UPDATE
+        SET
+            failed_attempts = CASE
+                WHEN account_lockouts.last_failed_at < ? THEN 1
+                ELSE account_lockouts.failed_attempts + 1
+            END,
+            last_failed_at = NOW(),
+            locked_at = NULL,
+            updated_at = NOW()
+        RETURNING
+            user_id,
+            failed_attempts,
+            last_failed_at,
+            locked_until,
+            locked_at,
+            created_at,
+            updated_at
+    "#))
+    .bind(user_id)
+    .bind(window_start)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(lockout)
+}
+
+/// Take back one counted failure. The block is lifted only if it is still the one that
+/// failure set (`locked_until`); a row left with no failures is removed.
+pub async fn refund_failure(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    locked_until: Option<DateTime<Utc>>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&sql(r#"
+        UPDATE account_lockouts
+        SET
+            failed_attempts = GREATEST(failed_attempts - 1, 0),
+            locked_until = CASE
+                WHEN locked_until IS NOT DISTINCT FROM ? THEN NULL
+                ELSE locked_until
+            END,
+            locked_at = CASE
+                WHEN locked_until IS NOT DISTINCT FROM ? THEN NULL
+                ELSE locked_at
+            END,
+            updated_at = NOW()
+        WHERE
+            user_id = ?
+    "#))
+    .bind(locked_until)
+    .bind(locked_until)
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query(&sql(r#"
+        DELETE FROM account_lockouts
+        WHERE
+            user_id = ?
+            AND failed_attempts = 0
+    "#))
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Refuse further attempts until `locked_until`; `locked` marks a full lockout
+pub async fn block_until(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    locked_until: DateTime<Utc>,
+    locked: bool,
+) -> Result<AccountLockout, sqlx::Error> {
+    let lockout = sqlx::query_as::<_, AccountLockout>(&sql(r#"
+        UPDATE account_lockouts
+        SET
+            locked_until = ?,
+            locked_at = CASE WHEN ? THEN NOW() ELSE NULL END,
+            updated_at = NOW()
+        WHERE
+            user_id = ?
+        RETURNING
+            user_id,
+            failed_attempts,
+            last_failed_at,
+            locked_until,
+            locked_at,
+            created_at,
+            updated_at
+    "#))
+    .bind(locked_until)
+    .bind(locked)
+    .bind(user_id)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(lockout)
+}
+
+/// Forget failed attempts, returning the cleared state if there was any
+pub async fn clear(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<Option<AccountLockout>, sqlx::Error> {
+    let lockout = sqlx::query_as::<_, AccountLockout>(&sql(r#"
+        DELETE FROM account_lockouts
+        WHERE
+            user_id = ?
+        RETURNING
+            user_id,
+            failed_attempts,
+            last_failed_at,
+            locked_until,
+            locked_at,
+            created_at,
+            updated_at
+    "#))
+    .bind(user_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(lockout)
+}
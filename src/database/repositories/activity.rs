@@ -1,12 +1,18 @@
 use anyhow::Result;
+use chrono::{DateTime, Months, NaiveDate, NaiveTime, Utc};
 use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
 
 use crate::database::{
-    models::{CompanyActivity, CreateActivityInput},
+    get_pool,
+    models::{
+        Action, ActivityArchive, ActivityChainHead, ActivityCheckpoint, ActivityFilter,
+        CompanyActivity, CreateActivityInput,
+    },
     utils::sql,
 };
 
-/// Log a new activity  
+/// Log a new activity, appending it to the company's hash chain
 pub async fn log_activity(
     tx: &mut Transaction<'_, Postgres>,
     request: CreateActivityInput,
@@ -15,9 +21,63 @@ pub async fn log_activity(
         .metadata
         .map(|m| serde_json::to_value(&m).unwrap_or_default());
 
-    let company_activity = sqlx::query_as::<_, CompanyActivity>(&sql(r#"
+    // Locking the company's chain head serializes appends until the transaction ends, so
+    // every row links to the one logged before it
+    sqlx::query(&sql(r#"
+        INSERT INTO
+            activity_chain_heads (company_id)
+        VALUES
+            (?)
+        ON CONFLICT (company_id) DO NOTHING
+    "#))
+    .bind(request.company_id)
+    .execute(&mut **tx)
+    .await?;
+
+    let (head_seq, head_hash) = sqlx::query_as::<_, (i64, String)>(&sql(r#"
+        SELECT
+            chain_seq,
+            row_hash
+        FROM
+            activity_chain_heads
+        WHERE
+            company_id = ?
+        FOR UPDATE
+    "#))
+    .bind(request.company_id)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    // The database stores microseconds; the hash must cover exactly what is stored
+    let now = Utc::now();
+    let mut activity = CompanyActivity {
+        id: Uuid::new_v4(),
+        company_id: request.company_id,
+        user_id: request.user_id,
+        activity_type: request.activity_type,
+        entity_type: request.entity_type,
+        entity_id: request.entity_id,
+        action: request.action,
+        description: request.description,
+        metadata: metadata_json,
+        ip_address: Some(request.ip_address),
+        user_agent: Some(request.user_agent),
+        api_key_id: request.api_key_id,
+        impersonated_user_id: request.impersonated_user_id,
+        chain_seq: Some(head_seq + 1),
+        content_hash: None,
+        prev_hash: Some(head_hash),
+        row_hash: None,
+        redacted_at: None,
+        created_at: DateTime::from_timestamp_micros(now.timestamp_micros()).unwrap_or(now),
+    };
+    activity.content_hash = Some(activity.compute_content_hash());
+    activity.row_hash = Some(activity.compute_row_hash());
+
+    sqlx::query(&sql(r#"
         INSERT INTO
             company_activity (
+                id,
                 company_id,
                 user_id,
                 activity_type,
@@ -27,10 +87,67 @@ pub async fn log_activity(
                 description,
                 metadata,
                 ip_address,
-                user_agent
+                user_agent,
+                api_key_id,
+                impersonated_user_id,
+                chain_seq,
+                content_hash,
+                prev_hash,
+                row_hash,
+                created_at
             )
-        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-        RETURNING
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+    "#))
+    .bind(activity.id)
+    .bind(activity.company_id)
+    .bind(activity.user_id)
+    .bind(&activity.activity_type)
+    .bind(&activity.entity_type)
+    .bind(activity.entity_id)
+    .bind(&activity.action)
+    .bind(&activity.description)
+    .bind(&activity.metadata)
+    .bind(&activity.ip_address)
+    .bind(&activity.user_agent)
+    .bind(activity.api_key_id)
+    .bind(activity.impersonated_user_id)
+    .bind(activity.chain_seq)
+    .bind(&activity.content_hash)
+    .bind(&activity.prev_hash)
+    .bind(&activity.row_hash)
+    .bind(activity.created_at)
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query(&sql(r#"
+        UPDATE activity_chain_heads
+        SET
+            chain_seq = ?,
+            row_hash = ?,
+            updated_at = NOW()
+        WHERE
+            company_id = ?
+    "#))
+    .bind(activity.chain_seq)
+    .bind(&activity.row_hash)
+    .bind(activity.company_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(activity)
+}
+
+/// Activity of a company matching the filter, newest first unless `oldest_first` is set.
+/// The cursor is exclusive, so a page starts right after the entry it points at.
+pub async fn get_activities(filter: &ActivityFilter) -> Result<Vec<CompanyActivity>, sqlx::Error> {
+    let (direction, comparison) = if filter.oldest_first {
+        ("ASC", ">")
+    } else {
+        ("DESC", "<")
+    };
+    let query = format!(
+        r#"
+        SELECT
             id,
             company_id,
             user_id,
@@ -42,20 +159,666 @@ pub async fn log_activity(
             metadata,
             ip_address,
             user_agent,
+            api_key_id,
+            impersonated_user_id,
+            chain_seq,
+            content_hash,
+            prev_hash,
+            row_hash,
+            redacted_at,
             created_at
+        FROM
+            company_activity
+        WHERE
+            company_id = ?
+            AND (?::VARCHAR IS NULL OR activity_type = ?)
+            AND (?::VARCHAR IS NULL OR entity_type = ?)
+            AND (?::UUID IS NULL OR entity_id = ?)
+            AND (?::UUID IS NULL OR user_id = ?)
+            AND (?::VARCHAR IS NULL OR action = ?)
+            AND (?::TIMESTAMPTZ IS NULL OR created_at >= ?)
+            AND (?::TIMESTAMPTZ IS NULL OR created_at < ?)
+            AND (NOT ?::BOOLEAN OR metadata -> 'changes' IS NOT NULL)
+            AND (?::TIMESTAMPTZ IS NULL OR (created_at, id) {comparison} (?, ?))
+        ORDER BY
+            created_at {direction},
+            id {direction}
+        LIMIT
+            ?
+    "#
+    );
+
+    let cursor_created_at = filter.cursor.as_ref().map(|cursor| cursor.created_at);
+    let cursor_id = filter.cursor.as_ref().map(|cursor| cursor.id);
+    let activities = sqlx::query_as::<_, CompanyActivity>(&sql(&query))
+        .bind(filter.company_id)
+        .bind(&filter.activity_type)
+        .bind(&filter.activity_type)
+        .bind(&filter.entity_type)
+        .bind(&filter.entity_type)
+        .bind(filter.entity_id)
+        .bind(filter.entity_id)
+        .bind(filter.user_id)
+        .bind(filter.user_id)
+        .bind(&filter.action)
+        .bind(&filter.action)
+        .bind(filter.start_date)
+        .bind(filter.start_date)
+        .bind(filter.end_date)
+        .bind(filter.end_date)
+        .bind(filter.changes_only)
+        .bind(cursor_created_at)
+        .bind(cursor_created_at)
+        .bind(cursor_id)
+        .bind(filter.limit.unwrap_or(50))
+        .fetch_all(&get_pool().await)
+        .await?;
+
+    Ok(activities)
+}
+
+pub async fn get_chain_head(company_id: Uuid) -> Result<Option<ActivityChainHead>, sqlx::Error> {
+    let head = sqlx::query_as::<_, ActivityChainHead>(&sql(r#"
+        SELECT
+            company_id,
+            chain_seq,
+            row_hash,
+            updated_at
+        FROM
+            activity_chain_heads
+        WHERE
+            company_id = ?
     "#))
-    .bind(request.company_id)
-    .bind(request.user_id)
-    .bind(request.activity_type)
-    .bind(request.entity_type)
-    .bind(request.entity_id)
-    .bind(request.action)
-    .bind(request.description)
-    .bind(metadata_json)
-    .bind(request.ip_address)
-    .bind(request.user_agent)
+    .bind(company_id)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(head)
+}
+
+pub async fn get_chain_heads() -> Result<Vec<ActivityChainHead>, sqlx::Error> {
+    let heads = sqlx::query_as::<_, ActivityChainHead>(&sql(r#"
+        SELECT
+            company_id,
+            chain_seq,
+            row_hash,
+            updated_at
+        FROM
+            activity_chain_heads
+        ORDER BY
+            company_id
+    "#))
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(heads)
+}
+
+/// Chain heads that moved past the company's latest checkpoint
+pub async fn get_unsigned_chain_heads() -> Result<Vec<ActivityChainHead>, sqlx::Error> {
+    let heads = sqlx::query_as::<_, ActivityChainHead>(&sql(r#"
+        SELECT
+            h.company_id,
+            h.chain_seq,
+            h.row_hash,
+            h.updated_at
+        FROM
+            activity_chain_heads h
+        WHERE
+            h.chain_seq > COALESCE(
+                (
+                    SELECT
+                        MAX(c.chain_seq)
+                    FROM
+                        activity_checkpoints c
+                    WHERE
+                        c.company_id = h.company_id
+                ),
+                0
+            )
+    "#))
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(heads)
+}
+
+/// Chained activity of a company in chain order, starting after `after_seq`
+pub async fn get_chained_activities(
+    company_id: Uuid,
+    after_seq: i64,
+    limit: i64,
+) -> Result<Vec<CompanyActivity>, sqlx::Error> {
+    let activities = sqlx::query_as::<_, CompanyActivity>(&sql(r#"
+        SELECT
+            id,
+            company_id,
+            user_id,
+            activity_type,
+            entity_type,
+            entity_id,
+            action,
+            description,
+            metadata,
+            ip_address,
+            user_agent,
+            api_key_id,
+            impersonated_user_id,
+            chain_seq,
+            content_hash,
+            prev_hash,
+            row_hash,
+            redacted_at,
+            created_at
+        FROM
+            company_activity
+        WHERE
+            company_id = ?
+            AND chain_seq > ?
+        ORDER BY
+            chain_seq ASC
+        LIMIT
+            ?
+    "#))
+    .bind(company_id)
+    .bind(after_seq)
+    .bind(limit)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(activities)
+}
+
+/// Entries recording redactions in a company's chain, oldest first
+pub async fn get_redaction_records(company_id: Uuid) -> Result<Vec<CompanyActivity>, sqlx::Error> {
+    let activities = sqlx::query_as::<_, CompanyActivity>(&sql(r#"
+        SELECT
+            id,
+            company_id,
+            user_id,
+            activity_type,
+            entity_type,
+            entity_id,
+            action,
+            description,
+            metadata,
+            ip_address,
+            user_agent,
+            api_key_id,
+            impersonated_user_id,
+            chain_seq,
+            content_hash,
+            prev_hash,
+            row_hash,
+            redacted_at,
+            created_at
+        FROM
+            company_activity
+        WHERE
+            company_id = ?
+            AND action = ?
+            AND chain_seq IS NOT NULL
+        ORDER BY
+            chain_seq ASC
+    "#))
+    .bind(company_id)
+    .bind(Action::ACTIVITY_REDACTED)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(activities)
+}
+
+pub async fn create_checkpoint(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    chain_seq: i64,
+    row_hash: &str,
+    signature: &str,
+) -> Result<ActivityCheckpoint, sqlx::Error> {
+    let checkpoint = sqlx::query_as::<_, ActivityCheckpoint>(&sql(r#"
+        INSERT INTO
+            activity_checkpoints (company_id, chain_seq, row_hash, signature)
+        VALUES
+            (?, ?, ?, ?)
+        RETURNING
+            id,
+            company_id,
+            chain_seq,
+            row_hash,
+            signature,
+            created_at
+    "#))
+    .bind(company_id)
+    .bind(chain_seq)
+    .bind(row_hash)
+    .bind(signature)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(checkpoint)
+}
+
+/// Checkpoints of a company, oldest first
+pub async fn get_checkpoints(company_id: Uuid) -> Result<Vec<ActivityCheckpoint>, sqlx::Error> {
+    let checkpoints = sqlx::query_as::<_, ActivityCheckpoint>(&sql(r#"
+        SELECT
+            id,
+            company_id,
+            chain_seq,
+            row_hash,
+            signature,
+            created_at
+        FROM
+            activity_checkpoints
+        WHERE
+            company_id = ?
+        ORDER BY
+            chain_seq ASC,
+            created_at ASC
+    "#))
+    .bind(company_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(checkpoints)
+}
+
+/// Months of activity that are past their company's retention period, as the company and
+/// the start of the month, oldest first. A month expires once `as_of` is more than the
+/// retention period past its end.
+pub async fn get_expired_months(
+    as_of: DateTime<Utc>,
+) -> Result<Vec<(Uuid, DateTime<Utc>)>, sqlx::Error> {
+    let months = sqlx::query_as::<_, (Uuid, DateTime<Utc>)>(&sql(r#"
+        SELECT
+            a.company_id,
+            date_trunc('month', a.created_at AT TIME ZONE 'UTC') AT TIME ZONE 'UTC' AS period_start
+        FROM
+            company_activity a
+            JOIN companies c ON c.id = a.company_id
+        WHERE
+            a.created_at < date_trunc(
+                'month',
+                (?::TIMESTAMPTZ AT TIME ZONE 'UTC') - make_interval(months => c.activity_retention_months)
+            ) AT TIME ZONE 'UTC'
+        GROUP BY
+            1,
+            2
+        ORDER BY
+            2 ASC,
+            1 ASC
+    "#))
+    .bind(as_of)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(months)
+}
+
+/// Remove archived entries. The period bounds let Postgres prune to the month's partition.
+pub async fn delete_activities(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    period_start: DateTime<Utc>,
+    period_end: DateTime<Utc>,
+    ids: &[Uuid],
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(&sql(r#"
+        DELETE FROM company_activity
+        WHERE
+            company_id = ?
+            AND created_at >= ?
+            AND created_at < ?
+            AND id = ANY(?)
+    "#))
+    .bind(company_id)
+    .bind(period_start)
+    .bind(period_end)
+    .bind(ids)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn create_archive(
+    tx: &mut Transaction<'_, Postgres>,
+    archive: &ActivityArchive,
+) -> Result<ActivityArchive, sqlx::Error> {
+    let archive = sqlx::query_as::<_, ActivityArchive>(&sql(r#"
+        INSERT INTO
+            activity_archives (
+                id,
+                company_id,
+                period_start,
+                period_end,
+                file_name,
+                entry_count,
+                size_bytes,
+                sha256,
+                first_seq,
+                last_seq,
+                last_row_hash
+            )
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING
+            id,
+            company_id,
+            period_start,
+            period_end,
+            file_name,
+            entry_count,
+            size_bytes,
+            sha256,
+            first_seq,
+            last_seq,
+            last_row_hash,
+            created_at
+    "#))
+    .bind(archive.id)
+    .bind(archive.company_id)
+    .bind(archive.period_start)
+    .bind(archive.period_end)
+    .bind(&archive.file_name)
+    .bind(archive.entry_count)
+    .bind(archive.size_bytes)
+    .bind(&archive.sha256)
+    .bind(archive.first_seq)
+    .bind(archive.last_seq)
+    .bind(&archive.last_row_hash)
     .fetch_one(&mut **tx)
     .await?;
 
-    Ok(company_activity)
+    Ok(archive)
+}
+
+/// Archives of a company, oldest period first
+pub async fn get_archives(company_id: Uuid) -> Result<Vec<ActivityArchive>, sqlx::Error> {
+    let archives = sqlx::query_as::<_, ActivityArchive>(&sql(r#"
+        SELECT
+            id,
+            company_id,
+            period_start,
+            period_end,
+            file_name,
+            entry_count,
+            size_bytes,
+            sha256,
+            first_seq,
+            last_seq,
+            last_row_hash,
+            created_at
+        FROM
+            activity_archives
+        WHERE
+            company_id = ?
+        ORDER BY
+            period_start ASC,
+            created_at ASC
+    "#))
+    .bind(company_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(archives)
+}
+
+/// Archives of every company, oldest period first
+pub async fn get_all_archives() -> Result<Vec<ActivityArchive>, sqlx::Error> {
+    let archives = sqlx::query_as::<_, ActivityArchive>(&sql(r#"
+        SELECT
+            id,
+            company_id,
+            period_start,
+            period_end,
+            file_name,
+            entry_count,
+            size_bytes,
+            sha256,
+            first_seq,
+            last_seq,
+            last_row_hash,
+            created_at
+        FROM
+            activity_archives
+        ORDER BY
+            period_start ASC
+    "#))
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(archives)
+}
+
+/// Point an archive at a rewritten file
+pub async fn update_archive_file(
+    tx: &mut Transaction<'_, Postgres>,
+    archive: &ActivityArchive,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&sql(r#"
+        UPDATE activity_archives
+        SET
+            file_name = ?,
+            size_bytes = ?,
+            sha256 = ?
+        WHERE
+            id = ?
+    "#))
+    .bind(&archive.file_name)
+    .bind(archive.size_bytes)
+    .bind(&archive.sha256)
+    .bind(archive.id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn find_archive(
+    company_id: Uuid,
+    archive_id: Uuid,
+) -> Result<Option<ActivityArchive>, sqlx::Error> {
+    let archive = sqlx::query_as::<_, ActivityArchive>(&sql(r#"
+        SELECT
+            id,
+            company_id,
+            period_start,
+            period_end,
+            file_name,
+            entry_count,
+            size_bytes,
+            sha256,
+            first_seq,
+            last_seq,
+            last_row_hash,
+            created_at
+        FROM
+            activity_archives
+        WHERE
+            company_id = ?
+            AND id = ?
+    "#))
+    .bind(company_id)
+    .bind(archive_id)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(archive)
+}
+
+/// The archive holding the newest chained entries of a company, where the chain that is
+/// still in the database picks up
+pub async fn get_latest_chained_archive(
+    company_id: Uuid,
+) -> Result<Option<ActivityArchive>, sqlx::Error> {
+    let archive = sqlx::query_as::<_, ActivityArchive>(&sql(r#"
+        SELECT
+            id,
+            company_id,
+            period_start,
+            period_end,
+            file_name,
+            entry_count,
+            size_bytes,
+            sha256,
+            first_seq,
+            last_seq,
+            last_row_hash,
+            created_at
+        FROM
+            activity_archives
+        WHERE
+            company_id = ?
+            AND last_seq IS NOT NULL
+        ORDER BY
+            last_seq DESC
+        LIMIT
+            1
+    "#))
+    .bind(company_id)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(archive)
+}
+
+fn partition_name(month_start: NaiveDate) -> String {
+    format!("company_activity_{}", month_start.format("%Y_%m"))
+}
+
+/// Create the monthly partition starting at `month_start` unless it exists. Entries the
+/// default partition caught for that month are moved into it. Returns whether it was created.
+pub async fn create_partition(month_start: NaiveDate) -> Result<bool, sqlx::Error> {
+    let name = partition_name(month_start);
+    let exists = sqlx::query_scalar::<_, bool>(&sql(r#"
+        SELECT
+            to_regclass(?) IS NOT NULL
+    "#))
+    .bind(&name)
+    .fetch_one(&get_pool().await)
+    .await?;
+    if exists {
+        return Ok(false);
+    }
+
+    let from = month_start.and_time(NaiveTime::MIN).and_utc();
+    let to = from + Months::new(1);
+    let create = format!(
+        "CREATE TABLE IF NOT EXISTS {} PARTITION OF company_activity \
+         FOR VALUES FROM ('{} 00:00:00+00') TO ('{} 00:00:00+00')",
+        name,
+        month_start,
+        month_start + Months::new(1)
+    );
+
+    let pool = get_pool().await;
+    let mut tx = pool.begin().await?;
+
+    // The partition cannot be created while the default partition holds entries in its range,
+    // so they are set aside and put back once it exists
+    sqlx::query(&sql(r#"
+        CREATE TEMPORARY TABLE company_activity_moved (LIKE company_activity) ON COMMIT DROP
+    "#))
+    .persistent(false)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(&sql(r#"
+        WITH moved AS (
+            DELETE FROM company_activity_default
+            WHERE
+                created_at >= ?
+                AND created_at < ?
+            RETURNING
+                *
+        )
+        INSERT INTO
+            company_activity_moved
+        SELECT
+            *
+        FROM
+            moved
+    "#))
+    .bind(from)
+    .bind(to)
+    .persistent(false)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(&create)
+        .persistent(false)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(&sql(r#"
+        INSERT INTO
+            company_activity
+        SELECT
+            *
+        FROM
+            company_activity_moved
+    "#))
+    .persistent(false)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+/// Monthly partitions of the activity table with the first day of their month, oldest first.
+/// The default partition is not included.
+pub async fn get_partitions() -> Result<Vec<(String, NaiveDate)>, sqlx::Error> {
+    let names = sqlx::query_scalar::<_, String>(&sql(r#"
+        SELECT
+            c.relname::TEXT
+        FROM
+            pg_inherits i
+            JOIN pg_class c ON c.oid = i.inhrelid
+        WHERE
+            i.inhparent = 'company_activity'::REGCLASS
+        ORDER BY
+            c.relname ASC
+    "#))
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(names
+        .into_iter()
+        .filter_map(|name| {
+            let month = name.strip_prefix("company_activity_")?;
+            let month_start =
+                NaiveDate::parse_from_str(&format!("{}_01", month), "%Y_%m_%d").ok()?;
+            Some((partition_name(month_start), month_start))
+        })
+        .collect())
+}
+
+/// Drop the monthly partition starting at `month_start` if no entries are left in it.
+/// Returns whether it was dropped.
+pub async fn drop_partition_if_empty(month_start: NaiveDate) -> Result<bool, sqlx::Error> {
+    let name = partition_name(month_start);
+    let lock = format!("LOCK TABLE {} IN ACCESS EXCLUSIVE MODE", name);
+    let is_empty = format!("SELECT NOT EXISTS (SELECT 1 FROM {})", name);
+    let drop = format!("DROP TABLE {}", name);
+
+    let pool = get_pool().await;
+    let mut tx = pool.begin().await?;
+
+    // Keep writers out between the check and the drop
+    sqlx::query(&lock)
+        .persistent(false)
+        .execute(&mut *tx)
+        .await?;
+    let empty = sqlx::query_scalar::<_, bool>(&is_empty)
+        .persistent(false)
+        .fetch_one(&mut *tx)
+        .await?;
+    if empty {
+        sqlx::query(&drop)
+            .persistent(false)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(empty)
 }
@@ -0,0 +1,185 @@
+use chrono::Utc;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::database::{
+    get_pool,
+    models::{ApiKey, CreateApiKeyInput},
+    utils::sql,
+};
+
+pub async fn create_api_key(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    created_by: Uuid,
+    input: &CreateApiKeyInput,
+    prefix: &str,
+    key_hash: &str,
+) -> Result<ApiKey, sqlx::Error> {
+    let api_key = sqlx::query_as::<_, ApiKey>(&sql(r#"
+        INSERT INTO
+            api_keys (
+                company_id,
+                created_by,
+                name,
+                prefix,
+                key_hash,
+                scopes,
+                rate_limit_per_minute,
+                expires_at
+            )
+        VALUES
+            (?, ?, ?, ?, ?, ?, COALESCE(?, 60), ?)
+        RETURNING
+            id,
+            company_id,
+            name,
+            prefix,
+            key_hash,
+            scopes,
+            rate_limit_per_minute,
+            created_by,
+            last_used_at,
+            last_used_ip,
+            expires_at,
+            revoked_at,
+            created_at,
+            updated_at
+    "#))
+    .bind(company_id)
+    .bind(created_by)
+    .bind(&input.name)
+    .bind(prefix)
+    .bind(key_hash)
+    .bind(&input.scopes)
+    .bind(input.rate_limit_per_minute)
+    .bind(input.expires_at)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(api_key)
+}
+
+pub async fn find_by_prefix(prefix: &str) -> Result<Option<ApiKey>, sqlx::Error> {
+    let api_key = sqlx::query_as::<_, ApiKey>(&sql(r#"
+        SELECT
+            id,
+            company_id,
+            name,
+            prefix,
+            key_hash,
+            scopes,
+            rate_limit_per_minute,
+            created_by,
+            last_used_at,
+            last_used_ip,
+            expires_at,
+            revoked_at,
+            created_at,
+            updated_at
+        FROM
+            api_keys
+        WHERE
+            prefix = ?
+    "#))
+    .bind(prefix)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(api_key)
+}
+
+pub async fn get_company_api_keys(company_id: Uuid) -> Result<Vec<ApiKey>, sqlx::Error> {
+    let api_keys = sqlx::query_as::<_, ApiKey>(&sql(r#"
+        SELECT
+            id,
+            company_id,
+            name,
+            prefix,
+            key_hash,
+            scopes,
+            rate_limit_per_minute,
+            created_by,
+            last_used_at,
+            last_used_ip,
+            expires_at,
+            revoked_at,
+            created_at,
+            updated_at
+        FROM
+            api_keys
+        WHERE
+            company_id = ?
+        ORDER BY
+            created_at DESC
+    "#))
+    .bind(company_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(api_keys)
+}
+
+pub async fn revoke_api_key(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    api_key_id: Uuid,
+) -> Result<Option<ApiKey>, sqlx::Error> {
+    let now = Utc::now();
+
+    let api_key = sqlx::query_as::<_, ApiKey>(&sql(r#"
+        UPDATE api_keys
+        SET
+            revoked_at = ?,
+            updated_at = ?
+        WHERE
+            id = ?
+            AND company_id = ?
+            AND revoked_at IS NULL
+        RETURNING
+            id,
+            company_id,
+            name,
+            prefix,
+            key_hash,
+            scopes,
+            rate_limit_per_minute,
+            created_by,
+            last_used_at,
+            last_used_ip,
+            expires_at,
+            revoked_at,
+            created_at,
+            updated_at
+    "#))
+    .bind(now)
+    .bind(now)
+    .bind(api_key_id)
+    .bind(company_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(api_key)
+}
+
+pub async fn record_usage(
+    tx: &mut Transaction<'_, Postgres>,
+    api_key_id: Uuid,
+    ip_address: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&sql(r#"
+        UPDATE api_keys
+        SET
+            last_used_at = ?,
+            last_used_ip = ?
+        WHERE
+            id = ?
+    "#))
+    .bind(Utc::now())
+    .bind(ip_address)
+    .bind(api_key_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
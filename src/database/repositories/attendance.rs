@@ -0,0 +1,160 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::database::{get_pool, models::ShiftAttendance, utils::sql};
+
+/// Whether the user works the shift, through an accepted assignment or an approved claim
+pub async fn works_shift(shift_id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    let works = sqlx::query_scalar::<_, bool>(&sql(r#"
+        SELECT
+            EXISTS (
+                SELECT
+                    1
+                FROM
+                    shift_assignments
+                WHERE
+                    shift_id = ?
+                    AND user_id = ?
+                    AND assignment_status = 'accepted'
+            )
+            OR EXISTS (
+                SELECT
+                    1
+                FROM
+                    shift_claims
+                WHERE
+                    shift_id = ?
+                    AND user_id = ?
+                    AND status = 'approved'
+            )
+    "#))
+    .bind(shift_id)
+    .bind(user_id)
+    .bind(shift_id)
+    .bind(user_id)
+    .fetch_one(&get_pool().await)
+    .await?;
+
+    Ok(works)
+}
+
+pub async fn find(shift_id: Uuid, user_id: Uuid) -> Result<Option<ShiftAttendance>, sqlx::Error> {
+    let attendance = sqlx::query_as::<_, ShiftAttendance>(&sql(r#"
+        SELECT
+            id,
+            company_id,
+            shift_id,
+            user_id,
+            clock_in_at,
+            clock_out_at,
+            created_at,
+            updated_at
+        FROM
+            shift_attendance
+        WHERE
+            shift_id = ?
+            AND user_id = ?
+    "#))
+    .bind(shift_id)
+    .bind(user_id)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(attendance)
+}
+
+/// Record the clock-in. Returns None when the user already clocked in to the shift.
+pub async fn clock_in(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    shift_id: Uuid,
+    user_id: Uuid,
+    clock_in_at: DateTime<Utc>,
+) -> Result<Option<ShiftAttendance>, sqlx::Error> {
+    let attendance = sqlx::query_as::<_, ShiftAttendance>(&sql(r#"
+        INSERT INTO
+            shift_attendance (company_id, shift_id, user_id, clock_in_at)
+        VALUES
+            (?, ?, ?, ?)
+        ON CONFLICT (shift_id, user_id) DO NOTHING
+        RETURNING
+            id,
+            company_id,
+            shift_id,
+            user_id,
+            clock_in_at,
+            clock_out_at,
+            created_at,
+            updated_at
+    "#))
+    .bind(company_id)
+    .bind(shift_id)
+    .bind(user_id)
+    .bind(clock_in_at)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(attendance)
+}
+
+/// Record the clock-out. Returns None unless the user is clocked in to the shift.
+pub async fn clock_out(
+    tx: &mut Transaction<'_, Postgres>,
+    shift_id: Uuid,
+    user_id: Uuid,
+    clock_out_at: DateTime<Utc>,
+) -> Result<Option<ShiftAttendance>, sqlx::Error> {
+    let attendance = sqlx::query_as::<_, ShiftAttendance>(&sql(r#"
+        UPDATE shift_attendance
+        SET
+            clock_out_at = ?,
+            updated_at = NOW()
+        WHERE
+            shift_id = ?
+            AND user_id = ?
+            AND clock_out_at IS NULL
+        RETURNING
+            id,
+            company_id,
+            shift_id,
+            user_id,
+            clock_in_at,
+            clock_out_at,
+            created_at,
+            updated_at
+    "#))
+    .bind(clock_out_at)
+    .bind(shift_id)
+    .bind(user_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(attendance)
+}
+
+/// All of a user's attendance, across companies
+pub async fn find_by_user_id(user_id: Uuid) -> Result<Vec<ShiftAttendance>, sqlx::Error> {
+    let attendance = sqlx::query_as::<_, ShiftAttendance>(&sql(r#"
+        SELECT
+            id,
+            company_id,
+            shift_id,
+            user_id,
+            clock_in_at,
+            clock_out_at,
+            created_at,
+            updated_at
+        FROM
+            shift_attendance
+        WHERE
+            user_id = ?
+        ORDER BY
+            clock_in_at ASC
+    "#))
+    .bind(user_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(attendance)
+}
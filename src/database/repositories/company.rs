@@ -5,7 +5,8 @@ use crate::database::{
     get_pool,
     models::{
         AddEmployeeToCompanyInput, Company, CompanyEmployee, CompanyEmployeeInfo, CompanyInfo,
-        CompanyRole, CreateCompanyInput,
+        CompanyRole, CompanyTimeOffSettings, CompanyTimeOffSettingsInput, CreateCompanyInput,
+        builtin_roles,
     },
     utils::sql,
 };
@@ -38,6 +39,7 @@ pub async fn create_company(
             address,
             logo_url,
             timezone,
+            require_two_factor,
             created_at,
             updated_at
     "#))
@@ -52,6 +54,22 @@ pub async fn create_company(
     .fetch_one(&mut **tx)
     .await?;
 
+    // Every company starts with the built-in roles
+    for (role, description, permissions) in builtin_roles() {
+        sqlx::query(&sql(r#"
+            INSERT INTO
+                roles (company_id, name, description, permissions, is_builtin)
+            VALUES
+                (?, ?, ?, ?, TRUE)
+        "#))
+        .bind(company.id)
+        .bind(role.to_string())
+        .bind(description)
+        .bind(permissions)
+        .execute(&mut **tx)
+        .await?;
+    }
+
     Ok(company)
 }
 
@@ -86,7 +104,9 @@ pub async fn find_user_company_info_by_id(
             c.address,
             c.logo_url,
             c.timezone,
+            c.require_two_factor,
             uc.role,
+            uc.role_id,
             uc.is_primary,
             uc.hire_date,
             uc.created_at,
@@ -119,7 +139,9 @@ pub async fn get_companies_for_user(user_id: Uuid) -> Result<Vec<CompanyInfo>, s
             c.address,
             c.logo_url,
             c.timezone,
+            c.require_two_factor,
             uc.role,
+            uc.role_id,
             uc.is_primary,
             uc.hire_date,
             uc.created_at,
@@ -154,8 +176,13 @@ pub async fn get_primary_company_for_user(
             c.address,
             c.logo_url,
             c.timezone,
+            c.require_two_factor,
             uc.role,
-            uc.is_primary
+            uc.role_id,
+            uc.is_primary,
+            uc.hire_date,
+            uc.created_at,
+            uc.updated_at
         FROM
             companies c
         JOIN
@@ -235,6 +262,7 @@ pub async fn get_company_employees(
             u.email,
             u.name,
             uc.role,
+            uc.role_id,
             uc.hire_date,
             u.created_at,
             u.updated_at
@@ -360,3 +388,124 @@ pub async fn has_primary_company(user_id: Uuid) -> Result<bool, sqlx::Error> {
 
     Ok(count > 0)
 }
+
+/// Turn the company's two-factor requirement for admins and managers on or off
+pub async fn update_require_two_factor(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    require_two_factor: bool,
+) -> Result<Option<Company>, sqlx::Error> {
+    let company = sqlx::query_as::<_, Company>(&sql(r#"
+        UPDATE companies
+        SET
+            require_two_factor = ?,
+            updated_at = NOW()
+        WHERE
+            id = ?
+        RETURNING
+            id,
+            name,
+            description,
+            website,
+            phone,
+            email,
+            address,
+            logo_url,
+            timezone,
+            require_two_factor,
+            created_at,
+            updated_at
+    "#))
+    .bind(require_two_factor)
+    .bind(company_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(company)
+}
+
+/// Months of activity the company keeps online before it is archived
+pub async fn get_activity_retention_months(company_id: Uuid) -> Result<Option<i32>, sqlx::Error> {
+    let months = sqlx::query_scalar::<_, i32>(&sql(r#"
+        SELECT
+            activity_retention_months
+        FROM
+            companies
+        WHERE
+            id = ?
+    "#))
+    .bind(company_id)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(months)
+}
+
+pub async fn update_activity_retention_months(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    months: i32,
+) -> Result<Option<i32>, sqlx::Error> {
+    let months = sqlx::query_scalar::<_, i32>(&sql(r#"
+        UPDATE companies
+        SET
+            activity_retention_months = ?,
+            updated_at = NOW()
+        WHERE
+            id = ?
+        RETURNING
+            activity_retention_months
+    "#))
+    .bind(months)
+    .bind(company_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(months)
+}
+
+/// How the company deducts approved time off from balances
+pub async fn get_time_off_settings(
+    company_id: Uuid,
+) -> Result<Option<CompanyTimeOffSettings>, sqlx::Error> {
+    let settings = sqlx::query_as::<_, CompanyTimeOffSettings>(&sql(r#"
+        SELECT
+            workday_hours,
+            allow_negative_pto
+        FROM
+            companies
+        WHERE
+            id = ?
+    "#))
+    .bind(company_id)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(settings)
+}
+
+pub async fn update_time_off_settings(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    input: &CompanyTimeOffSettingsInput,
+) -> Result<Option<CompanyTimeOffSettings>, sqlx::Error> {
+    let settings = sqlx::query_as::<_, CompanyTimeOffSettings>(&sql(r#"
+        UPDATE companies
+        SET
+            workday_hours = ?,
+            allow_negative_pto = ?,
+            updated_at = NOW()
+        WHERE
+            id = ?
+        RETURNING
+            workday_hours,
+            allow_negative_pto
+    "#))
+    .bind(&input.workday_hours)
+    .bind(input.allow_negative_pto)
+    .bind(company_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(settings)
+}
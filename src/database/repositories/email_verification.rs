@@ -0,0 +1,135 @@
+use chrono::{Duration, Utc};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::database::{get_pool, models::EmailVerificationToken, utils::sql};
+
+/// Generate a cryptographically secure random token
+fn generate_secure_token() -> String {
+    use rand::Rng;
+    const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
+                                abcdefghijklmnopqrstuvwxyz\
+                                0123456789";
+    const TOKEN_LEN: usize = 64;
+    let mut rng = rand::rng();
+
+    (0..TOKEN_LEN)
+        .map(|_| {
+            let idx = rng.random_range(0..CHARSET.len());
+            CHARSET[idx] as char
+        })
+        .collect()
+}
+
+/// Create a new verification token for `email`
+pub async fn create_token(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    email: &str,
+) -> Result<EmailVerificationToken, sqlx::Error> {
+    let token = generate_secure_token();
+    let expires_at = Utc::now() + Duration::hours(24); // 24 hour expiration
+
+    let verification_token = sqlx::query_as::<_, EmailVerificationToken>(&sql(r#"
+        INSERT INTO
+            email_verification_tokens (user_id, email, token, expires_at)
+        VALUES
+            (?, ?, ?, ?)
+        RETURNING
+            id,
+            user_id,
+            email,
+            token,
+            expires_at,
+            used_at,
+            created_at
+    "#))
+    .bind(user_id)
+    .bind(email)
+    .bind(&token)
+    .bind(expires_at)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(verification_token)
+}
+
+/// Find a valid (unused and not expired) token
+pub async fn find_valid_token(token: &str) -> Result<Option<EmailVerificationToken>, sqlx::Error> {
+    let now = Utc::now();
+
+    let result = sqlx::query_as::<_, EmailVerificationToken>(&sql(r#"
+        SELECT
+            id,
+            user_id,
+            email,
+            token,
+            expires_at,
+            used_at,
+            created_at
+        FROM
+            email_verification_tokens
+        WHERE
+            token = ?
+            AND used_at IS NULL
+            AND expires_at > ?
+    "#))
+    .bind(token)
+    .bind(now)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(result)
+}
+
+/// Mark a token as used, returning `None` if it was already used
+pub async fn mark_token_used(
+    tx: &mut Transaction<'_, Postgres>,
+    token: &str,
+) -> Result<Option<EmailVerificationToken>, sqlx::Error> {
+    let now = Utc::now();
+
+    let result = sqlx::query_as::<_, EmailVerificationToken>(&sql(r#"
+        UPDATE email_verification_tokens
+        SET
+            used_at = ?
+        WHERE
+            token = ?
+            AND used_at IS NULL
+        RETURNING
+            id,
+            user_id,
+            email,
+            token,
+            expires_at,
+            used_at,
+            created_at
+    "#))
+    .bind(now)
+    .bind(token)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(result)
+}
+
+/// Invalidate all outstanding tokens for a user (when a new one is issued or one is used)
+pub async fn invalidate_user_tokens(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let now = Utc::now();
+
+    sqlx::query(&sql(r#"
+        UPDATE email_verification_tokens
+        SET used_at = ?
+        WHERE user_id = ?
+        AND used_at IS NULL
+    "#))
+    .bind(now)
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,338 @@
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::database::{
+    get_pool,
+    models::{CompanyHoliday, CompanyHolidayInput, ImportedHoliday, ObservedHoliday},
+    utils::sql,
+};
+
+const HOLIDAY_COLUMNS: &str = r#"
+    id,
+    company_id,
+    location_id,
+    name,
+    holiday_date,
+    recurring,
+    observed,
+    is_closed,
+    pay_multiplier,
+    source_uid,
+    created_at,
+    updated_at
+"#;
+
+/// The company's holidays, with only the company-wide ones and the location's when a location
+/// is given
+pub async fn get_holidays(
+    company_id: Uuid,
+    location_id: Option<Uuid>,
+) -> Result<Vec<CompanyHoliday>, sqlx::Error> {
+    let query = format!(
+        r#"
+        SELECT
+            {HOLIDAY_COLUMNS}
+        FROM
+            company_holidays
+        WHERE
+            company_id = ?
+            AND (
+                ?::UUID IS NULL
+                OR location_id IS NULL
+                OR location_id = ?
+            )
+        ORDER BY
+            holiday_date,
+            location_id NULLS FIRST,
+            name
+    "#
+    );
+    let holidays = sqlx::query_as::<_, CompanyHoliday>(&sql(&query))
+        .bind(company_id)
+        .bind(location_id)
+        .bind(location_id)
+        .fetch_all(&get_pool().await)
+        .await?;
+
+    Ok(holidays)
+}
+
+pub async fn find_holiday(
+    company_id: Uuid,
+    holiday_id: Uuid,
+) -> Result<Option<CompanyHoliday>, sqlx::Error> {
+    let query = format!(
+        r#"
+        SELECT
+            {HOLIDAY_COLUMNS}
+        FROM
+            company_holidays
+        WHERE
+            id = ?
+            AND company_id = ?
+    "#
+    );
+    let holiday = sqlx::query_as::<_, CompanyHoliday>(&sql(&query))
+        .bind(holiday_id)
+        .bind(company_id)
+        .fetch_optional(&get_pool().await)
+        .await?;
+
+    Ok(holiday)
+}
+
+pub async fn create_holiday(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    input: &CompanyHolidayInput,
+) -> Result<CompanyHoliday, sqlx::Error> {
+    let query = format!(
+        r#"
+        INSERT INTO
+            company_holidays (
+                company_id,
+                location_id,
+                name,
+                holiday_date,
+                recurring,
+                observed,
+                is_closed,
+                pay_multiplier
+            )
+        VALUES
+            (?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING
+            {HOLIDAY_COLUMNS}
+    "#
+    );
+    let holiday = sqlx::query_as::<_, CompanyHoliday>(&sql(&query))
+        .bind(company_id)
+        .bind(input.location_id)
+        .bind(&input.name)
+        .bind(input.holiday_date)
+        .bind(input.recurring.unwrap_or(false))
+        .bind(input.observed.unwrap_or(true))
+        .bind(input.is_closed.unwrap_or(true))
+        .bind(
+            input
+                .pay_multiplier
+                .clone()
+                .unwrap_or_else(|| BigDecimal::from(1)),
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+    Ok(holiday)
+}
+
+pub async fn update_holiday(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    holiday_id: Uuid,
+    input: &CompanyHolidayInput,
+) -> Result<Option<CompanyHoliday>, sqlx::Error> {
+    let query = format!(
+        r#"
+        UPDATE company_holidays
+        SET
+            location_id = ?,
+            name = ?,
+            holiday_date = ?,
+            recurring = ?,
+            observed = ?,
+            is_closed = ?,
+            pay_multiplier = ?,
+            updated_at = NOW()
+        WHERE
+            id = ?
+            AND company_id = ?
+        RETURNING
+            {HOLIDAY_COLUMNS}
+    "#
+    );
+    let holiday = sqlx::query_as::<_, CompanyHoliday>(&sql(&query))
+        .bind(input.location_id)
+        .bind(&input.name)
+        .bind(input.holiday_date)
+        .bind(input.recurring.unwrap_or(false))
+        .bind(input.observed.unwrap_or(true))
+        .bind(input.is_closed.unwrap_or(true))
+        .bind(
+            input
+                .pay_multiplier
+                .clone()
+                .unwrap_or_else(|| BigDecimal::from(1)),
+        )
+        .bind(holiday_id)
+        .bind(company_id)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+    Ok(holiday)
+}
+
+pub async fn delete_holiday(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    holiday_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(&sql(r#"
+        DELETE FROM company_holidays
+        WHERE
+            id = ?
+            AND company_id = ?
+    "#))
+    .bind(holiday_id)
+    .bind(company_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Find the holiday imported before under the event's UID, for the same location
+pub async fn find_imported(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    location_id: Option<Uuid>,
+    uid: &str,
+) -> Result<Option<CompanyHoliday>, sqlx::Error> {
+    let query = format!(
+        r#"
+        SELECT
+            {HOLIDAY_COLUMNS}
+        FROM
+            company_holidays
+        WHERE
+            company_id = ?
+            AND location_id IS NOT DISTINCT FROM ?
+            AND source_uid = ?
+    "#
+    );
+    let holiday = sqlx::query_as::<_, CompanyHoliday>(&sql(&query))
+        .bind(company_id)
+        .bind(location_id)
+        .bind(uid)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+    Ok(holiday)
+}
+
+/// Add a holiday from a calendar file
+pub async fn create_imported(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    location_id: Option<Uuid>,
+    imported: &ImportedHoliday,
+    is_closed: bool,
+    pay_multiplier: &BigDecimal,
+) -> Result<CompanyHoliday, sqlx::Error> {
+    let query = format!(
+        r#"
+        INSERT INTO
+            company_holidays (
+                company_id,
+                location_id,
+                name,
+                holiday_date,
+                recurring,
+                is_closed,
+                pay_multiplier,
+                source_uid
+            )
+        VALUES
+            (?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING
+            {HOLIDAY_COLUMNS}
+    "#
+    );
+    let holiday = sqlx::query_as::<_, CompanyHoliday>(&sql(&query))
+        .bind(company_id)
+        .bind(location_id)
+        .bind(&imported.name)
+        .bind(imported.holiday_date)
+        .bind(imported.recurring)
+        .bind(is_closed)
+        .bind(pay_multiplier)
+        .bind(&imported.uid)
+        .fetch_one(&mut **tx)
+        .await?;
+
+    Ok(holiday)
+}
+
+/// Refresh a holiday imported before from the calendar file. Whether it is observed, closed
+/// and its pay multiplier stay as they were set.
+pub async fn update_imported(
+    tx: &mut Transaction<'_, Postgres>,
+    holiday_id: Uuid,
+    imported: &ImportedHoliday,
+) -> Result<CompanyHoliday, sqlx::Error> {
+    let query = format!(
+        r#"
+        UPDATE company_holidays
+        SET
+            name = ?,
+            holiday_date = ?,
+            recurring = ?,
+            updated_at = NOW()
+        WHERE
+            id = ?
+        RETURNING
+            {HOLIDAY_COLUMNS}
+    "#
+    );
+    let holiday = sqlx::query_as::<_, CompanyHoliday>(&sql(&query))
+        .bind(&imported.name)
+        .bind(imported.holiday_date)
+        .bind(imported.recurring)
+        .bind(holiday_id)
+        .fetch_one(&mut **tx)
+        .await?;
+
+    Ok(holiday)
+}
+
+/// The days from `from` through `through` the location keeps a holiday on, with the entry
+/// that applies to each. Without a location, only company-wide holidays count.
+pub async fn get_observed(
+    company_id: Uuid,
+    location_id: Option<Uuid>,
+    from: NaiveDate,
+    through: NaiveDate,
+) -> Result<Vec<ObservedHoliday>, sqlx::Error> {
+    let holidays = sqlx::query_as::<_, ObservedHoliday>(&sql(r#"
+        SELECT
+            d::DATE AS date,
+            h.id,
+            h.company_id,
+            h.location_id,
+            h.name,
+            h.holiday_date,
+            h.recurring,
+            h.observed,
+            h.is_closed,
+            h.pay_multiplier,
+            h.source_uid,
+            h.created_at,
+            h.updated_at
+        FROM
+            GENERATE_SERIES(?::DATE, ?::DATE, INTERVAL '1 day') d
+            CROSS JOIN LATERAL company_holiday_on (?, ?, d::DATE) h
+        WHERE
+            h.observed
+        ORDER BY
+            date
+    "#))
+    .bind(from)
+    .bind(through)
+    .bind(company_id)
+    .bind(location_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(holidays)
+}
@@ -0,0 +1,109 @@
+use chrono::Utc;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::database::{
+    get_pool,
+    models::{CreateImpersonationInput, ImpersonationSession},
+    utils::sql,
+};
+
+/// Record an admin starting to act as another user
+pub async fn create_session(
+    tx: &mut Transaction<'_, Postgres>,
+    input: &CreateImpersonationInput,
+) -> Result<ImpersonationSession, sqlx::Error> {
+    let session = sqlx::query_as::<_, ImpersonationSession>(&sql(r#"
+        INSERT INTO
+            impersonation_sessions (
+                company_id,
+                admin_user_id,
+                target_user_id,
+                reason,
+                user_agent,
+                ip_address,
+                expires_at
+            )
+        VALUES
+            (?, ?, ?, ?, ?, ?, ?)
+        RETURNING
+            id,
+            company_id,
+            admin_user_id,
+            target_user_id,
+            reason,
+            user_agent,
+            ip_address,
+            expires_at,
+            ended_at,
+            created_at
+    "#))
+    .bind(input.company_id)
+    .bind(input.admin_user_id)
+    .bind(input.target_user_id)
+    .bind(&input.reason)
+    .bind(&input.user_agent)
+    .bind(&input.ip_address)
+    .bind(input.expires_at)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(session)
+}
+
+pub async fn find_by_id(session_id: Uuid) -> Result<Option<ImpersonationSession>, sqlx::Error> {
+    let session = sqlx::query_as::<_, ImpersonationSession>(&sql(r#"
+        SELECT
+            id,
+            company_id,
+            admin_user_id,
+            target_user_id,
+            reason,
+            user_agent,
+            ip_address,
+            expires_at,
+            ended_at,
+            created_at
+        FROM
+            impersonation_sessions
+        WHERE
+            id = ?
+    "#))
+    .bind(session_id)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(session)
+}
+
+/// End an impersonation early. Returns `None` if it had already ended.
+pub async fn end_session(
+    tx: &mut Transaction<'_, Postgres>,
+    session_id: Uuid,
+) -> Result<Option<ImpersonationSession>, sqlx::Error> {
+    let session = sqlx::query_as::<_, ImpersonationSession>(&sql(r#"
+        UPDATE impersonation_sessions
+        SET
+            ended_at = ?
+        WHERE
+            id = ?
+            AND ended_at IS NULL
+        RETURNING
+            id,
+            company_id,
+            admin_user_id,
+            target_user_id,
+            reason,
+            user_agent,
+            ip_address,
+            expires_at,
+            ended_at,
+            created_at
+    "#))
+    .bind(Utc::now())
+    .bind(session_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(session)
+}
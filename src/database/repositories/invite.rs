@@ -227,3 +227,176 @@ pub async fn revoke_invite_token(
 
     Ok(result.rows_affected() > 0)
 }
+
+/// Invites sent for a company, newest first. Pending invites past their expiry are reported
+/// as expired even if the cleanup job has not marked them yet.
+pub async fn get_company_invites(
+    company_id: Uuid,
+    status: Option<&InviteTokenStatus>,
+    email: Option<&str>,
+) -> Result<Vec<InviteToken>, sqlx::Error> {
+    let invites = sqlx::query_as::<_, InviteToken>(&sql(r#"
+        SELECT
+            *
+        FROM
+            (
+                SELECT
+                    it.id,
+                    it.email,
+                    it.token,
+                    it.inviter_id,
+                    it.role,
+                    it.company_id,
+                    it.team_id,
+                    it.expires_at,
+                    CASE
+                        WHEN it.status = 'pending'
+                        AND it.expires_at < NOW () THEN 'expired'
+                        ELSE it.status
+                    END AS status,
+                    it.used_at,
+                    it.created_at,
+                    c.name AS company_name
+                FROM
+                    invite_tokens it
+                    JOIN companies c ON it.company_id = c.id
+                WHERE
+                    it.company_id = ?
+            ) invites
+        WHERE
+            (?::VARCHAR IS NULL OR status = ?)
+            AND (?::VARCHAR IS NULL OR email ILIKE '%' || ? || '%')
+        ORDER BY
+            created_at DESC
+    "#))
+    .bind(company_id)
+    .bind(status)
+    .bind(status)
+    .bind(email)
+    .bind(email)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(invites)
+}
+
+pub async fn find_company_invite(
+    company_id: Uuid,
+    invite_id: Uuid,
+) -> Result<Option<InviteToken>, sqlx::Error> {
+    let invite = sqlx::query_as::<_, InviteToken>(&sql(r#"
+        SELECT
+            it.id,
+            it.email,
+            it.token,
+            it.inviter_id,
+            it.role,
+            it.company_id,
+            it.team_id,
+            it.expires_at,
+            CASE
+                WHEN it.status = 'pending'
+                AND it.expires_at < NOW () THEN 'expired'
+                ELSE it.status
+            END AS status,
+            it.used_at,
+            it.created_at,
+            c.name AS company_name
+        FROM
+            invite_tokens it
+            JOIN companies c ON it.company_id = c.id
+        WHERE
+            it.id = ?
+            AND it.company_id = ?
+    "#))
+    .bind(invite_id)
+    .bind(company_id)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(invite)
+}
+
+/// Give an unused invite a new token and expiry, so only the newest link works.
+/// Returns `None` if the invite was accepted, rejected or revoked in the meantime.
+pub async fn renew_invite_token(
+    tx: &mut Transaction<'_, Postgres>,
+    invite_id: Uuid,
+) -> Result<Option<InviteToken>, sqlx::Error> {
+    let token = Uuid::new_v4().to_string();
+    let expires_at = Utc::now() + Duration::days(7); // 7 days to accept
+
+    let invite = sqlx::query_as::<_, InviteToken>(&sql(r#"
+        WITH renewed_invite AS (
+            UPDATE invite_tokens
+            SET
+                token = ?,
+                expires_at = ?,
+                status = ?
+            WHERE
+                id = ?
+                AND used_at IS NULL
+                AND status IN ('pending', 'expired')
+            RETURNING
+                id,
+                email,
+                token,
+                inviter_id,
+                role,
+                company_id,
+                team_id,
+                expires_at,
+                status,
+                used_at,
+                created_at
+        )
+        SELECT
+            ri.id,
+            ri.email,
+            ri.token,
+            ri.inviter_id,
+            ri.role,
+            ri.company_id,
+            ri.team_id,
+            ri.expires_at,
+            ri.status,
+            ri.used_at,
+            ri.created_at,
+            c.name AS company_name
+        FROM renewed_invite ri
+        JOIN companies c ON ri.company_id = c.id
+    "#))
+    .bind(token)
+    .bind(expires_at)
+    .bind(InviteTokenStatus::Pending)
+    .bind(invite_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(invite)
+}
+
+/// Which of the given emails already have an invite to the company waiting to be answered
+pub async fn get_pending_invite_emails(
+    company_id: Uuid,
+    emails: &[String],
+) -> Result<Vec<String>, sqlx::Error> {
+    let emails = sqlx::query_scalar::<_, String>(&sql(r#"
+        SELECT DISTINCT
+            LOWER(email)
+        FROM
+            invite_tokens
+        WHERE
+            company_id = ?
+            AND LOWER(email) = ANY(?)
+            AND status = 'pending'
+            AND used_at IS NULL
+            AND expires_at > NOW ()
+    "#))
+    .bind(company_id)
+    .bind(emails)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(emails)
+}
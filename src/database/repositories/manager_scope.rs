@@ -0,0 +1,140 @@
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::database::{get_pool, models::UserCompanyScope, utils::sql};
+
+/// The locations and teams a member is limited to; empty when they act company-wide
+pub async fn get_member_scopes(
+    user_id: Uuid,
+    company_id: Uuid,
+) -> Result<Vec<UserCompanyScope>, sqlx::Error> {
+    let scopes = sqlx::query_as::<_, UserCompanyScope>(&sql(r#"
+        SELECT
+            ucs.id,
+            ucs.user_company_id,
+            ucs.location_id,
+            ucs.team_id,
+            ucs.created_at
+        FROM
+            user_company_scopes ucs
+            JOIN user_company uc ON uc.id = ucs.user_company_id
+        WHERE
+            uc.user_id = ?
+            AND uc.company_id = ?
+        ORDER BY
+            ucs.created_at ASC
+    "#))
+    .bind(user_id)
+    .bind(company_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(scopes)
+}
+
+/// Replace a member's scope. Returns `None` if the user is not a member of the company.
+pub async fn replace_member_scopes(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    user_id: Uuid,
+    location_ids: &[Uuid],
+    team_ids: &[Uuid],
+) -> Result<Option<Vec<UserCompanyScope>>, sqlx::Error> {
+    let user_company_id = sqlx::query_scalar::<_, Uuid>(&sql(r#"
+        SELECT
+            id
+        FROM
+            user_company
+        WHERE
+            user_id = ?
+            AND company_id = ?
+    "#))
+    .bind(user_id)
+    .bind(company_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    let Some(user_company_id) = user_company_id else {
+        return Ok(None);
+    };
+
+    sqlx::query(&sql(r#"
+        DELETE FROM user_company_scopes
+        WHERE
+            user_company_id = ?
+    "#))
+    .bind(user_company_id)
+    .execute(&mut **tx)
+    .await?;
+
+    let scopes = sqlx::query_as::<_, UserCompanyScope>(&sql(r#"
+        INSERT INTO
+            user_company_scopes (user_company_id, location_id, team_id)
+        SELECT
+            ?,
+            location_id,
+            NULL
+        FROM
+            UNNEST(?::UUID[]) AS location_id
+        UNION ALL
+        SELECT
+            ?,
+            NULL,
+            team_id
+        FROM
+            UNNEST(?::UUID[]) AS team_id
+        RETURNING
+            id,
+            user_company_id,
+            location_id,
+            team_id,
+            created_at
+    "#))
+    .bind(user_company_id)
+    .bind(location_ids)
+    .bind(user_company_id)
+    .bind(team_ids)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    Ok(Some(scopes))
+}
+
+/// The given teams plus every team at one of the given locations
+pub async fn get_scoped_team_ids(
+    location_ids: &[Uuid],
+    team_ids: &[Uuid],
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    let team_ids = sqlx::query_scalar::<_, Uuid>(&sql(r#"
+        SELECT
+            id
+        FROM
+            teams
+        WHERE
+            id = ANY(?)
+            OR location_id = ANY(?)
+    "#))
+    .bind(team_ids)
+    .bind(location_ids)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(team_ids)
+}
+
+/// Users who belong to at least one of the given teams
+pub async fn get_team_member_ids(team_ids: &[Uuid]) -> Result<Vec<Uuid>, sqlx::Error> {
+    let user_ids = sqlx::query_scalar::<_, Uuid>(&sql(r#"
+        SELECT DISTINCT
+            user_id
+        FROM
+            team_members
+        WHERE
+            team_id = ANY(?)
+    "#))
+    .bind(team_ids)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(user_ids)
+}
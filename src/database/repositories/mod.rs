@@ -1,17 +1,31 @@
+pub mod account_lockout;
 pub mod activity;
+pub mod api_key;
+pub mod attendance;
 pub mod company;
+pub mod email_verification;
+pub mod holiday;
+pub mod impersonation;
 pub mod invite;
 pub mod location;
+pub mod manager_scope;
 pub mod password_reset;
+pub mod personal_data;
 pub mod pto_balance;
+pub mod pto_policy;
+pub mod role;
 pub mod schedule;
+pub mod session;
 pub mod shift;
 pub mod shift_claim;
 pub mod shift_swap;
 pub mod skill;
+pub mod sso;
 pub mod stats;
 pub mod subscription;
 pub mod team;
 pub mod time_off;
+pub mod two_factor;
 pub mod user;
 pub mod user_company;
+pub mod wage;
@@ -0,0 +1,563 @@
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::database::{
+    get_pool,
+    models::{
+        CompanyActivity, CompanyEmployee, Shift, ShiftAssignment, ShiftSwap, ShiftSwapResponse,
+        TimeOffRequest, UserSkill, WageHistory,
+    },
+    utils::sql,
+};
+
+/// Every company membership of the user, including their PTO balances
+pub async fn get_memberships(user_id: Uuid) -> Result<Vec<CompanyEmployee>, sqlx::Error> {
+    let memberships = sqlx::query_as::<_, CompanyEmployee>(&sql(r#"
+        SELECT
+            id,
+            user_id,
+            company_id,
+            role,
+            is_primary,
+            hire_date,
+            pto_balance_hours,
+            sick_balance_hours,
+            personal_balance_hours,
+            pto_accrual_rate,
+            last_accrual_date,
+            created_at,
+            updated_at
+        FROM
+            user_company
+        WHERE
+            user_id = ?
+        ORDER BY
+            created_at ASC
+    "#))
+    .bind(user_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(memberships)
+}
+
+/// Shifts the user was assigned to or has claimed
+pub async fn get_shifts(user_id: Uuid) -> Result<Vec<Shift>, sqlx::Error> {
+    let shifts = sqlx::query_as::<_, Shift>(&sql(r#"
+        SELECT
+            s.id,
+            s.company_id,
+            s.title,
+            s.description,
+            s.location_id,
+            s.team_id,
+            s.start_time,
+            s.end_time,
+            s.min_duration_minutes,
+            s.max_duration_minutes,
+            s.max_people,
+            s.status,
+            s.created_at,
+            s.updated_at
+        FROM
+            shifts s
+        WHERE
+            s.id IN (
+                SELECT
+                    shift_id
+                FROM
+                    shift_assignments
+                WHERE
+                    user_id = ?
+                UNION
+                SELECT
+                    shift_id
+                FROM
+                    shift_claims
+                WHERE
+                    user_id = ?
+            )
+        ORDER BY
+            s.start_time ASC
+    "#))
+    .bind(user_id)
+    .bind(user_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(shifts)
+}
+
+pub async fn get_shift_assignments(user_id: Uuid) -> Result<Vec<ShiftAssignment>, sqlx::Error> {
+    let assignments = sqlx::query_as::<_, ShiftAssignment>(&sql(r#"
+        SELECT
+            id,
+            shift_id,
+            user_id,
+            assigned_by,
+            assignment_status,
+            acceptance_deadline,
+            response,
+            response_notes,
+            created_at,
+            updated_at
+        FROM
+            shift_assignments
+        WHERE
+            user_id = ?
+        ORDER BY
+            created_at ASC
+    "#))
+    .bind(user_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(assignments)
+}
+
+/// Swaps the user requested or was asked to take
+pub async fn get_shift_swaps(user_id: Uuid) -> Result<Vec<ShiftSwap>, sqlx::Error> {
+    let swaps = sqlx::query_as::<_, ShiftSwap>(&sql(r#"
+        SELECT
+            id,
+            requesting_user_id,
+            original_shift_id,
+            target_user_id,
+            target_shift_id,
+            notes,
+            response,
+            type AS swap_type,
+            status,
+            actioned_by,
+            action_notes,
+            created_at,
+            updated_at
+        FROM
+            shift_swaps
+        WHERE
+            requesting_user_id = ?
+            OR target_user_id = ?
+        ORDER BY
+            created_at ASC
+    "#))
+    .bind(user_id)
+    .bind(user_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(swaps)
+}
+
+pub async fn get_shift_swap_responses(
+    user_id: Uuid,
+) -> Result<Vec<ShiftSwapResponse>, sqlx::Error> {
+    let responses = sqlx::query_as::<_, ShiftSwapResponse>(&sql(r#"
+        SELECT
+            id,
+            swap_id,
+            responding_user_id,
+            response_type,
+            notes,
+            created_at
+        FROM
+            shift_swap_responses
+        WHERE
+            responding_user_id = ?
+        ORDER BY
+            created_at ASC
+    "#))
+    .bind(user_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(responses)
+}
+
+pub async fn get_time_off_requests(user_id: Uuid) -> Result<Vec<TimeOffRequest>, sqlx::Error> {
+    let requests = sqlx::query_as::<_, TimeOffRequest>(&sql(r#"
+        SELECT
+            id,
+            user_id,
+            company_id,
+            start_date,
+            end_date,
+            reason,
+            request_type,
+            status,
+            duration_type,
+            hours_deducted,
+            actioned_by,
+            action_notes,
+            created_at,
+            updated_at
+        FROM
+            time_off_requests
+        WHERE
+            user_id = ?
+        ORDER BY
+            start_date ASC
+    "#))
+    .bind(user_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(requests)
+}
+
+pub async fn get_skills(user_id: Uuid) -> Result<Vec<UserSkill>, sqlx::Error> {
+    let skills = sqlx::query_as::<_, UserSkill>(&sql(r#"
+        SELECT
+            id,
+            user_id,
+            skill_id,
+            proficiency_level,
+            created_at,
+            updated_at
+        FROM
+            user_skills
+        WHERE
+            user_id = ?
+        ORDER BY
+            created_at ASC
+    "#))
+    .bind(user_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(skills)
+}
+
+pub async fn get_wage_history(user_id: Uuid) -> Result<Vec<WageHistory>, sqlx::Error> {
+    let wages = sqlx::query_as::<_, WageHistory>(&sql(r#"
+        SELECT
+            id,
+            user_id,
+            company_id,
+            hourly_rate,
+            overtime_rate_multiplier,
+            effective_date,
+            end_date,
+            changed_by,
+            change_reason,
+            created_at
+        FROM
+            wage_history
+        WHERE
+            user_id = ?
+        ORDER BY
+            effective_date ASC
+    "#))
+    .bind(user_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(wages)
+}
+
+/// Activity the user performed, or an admin performed while impersonating them
+pub async fn get_activity(user_id: Uuid) -> Result<Vec<CompanyActivity>, sqlx::Error> {
+    let activity = sqlx::query_as::<_, CompanyActivity>(&sql(r#"
+        SELECT
+            id,
+            company_id,
+            user_id,
+            activity_type,
+            entity_type,
+            entity_id,
+            action,
+            description,
+            metadata,
+            ip_address,
+            user_agent,
+            api_key_id,
+            impersonated_user_id,
+            chain_seq,
+            content_hash,
+            prev_hash,
+            row_hash,
+            redacted_at,
+            created_at
+        FROM
+            company_activity
+        WHERE
+            user_id = ?
+            OR impersonated_user_id = ?
+        ORDER BY
+            created_at ASC
+    "#))
+    .bind(user_id)
+    .bind(user_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(activity)
+}
+
+/// Companies where the user is the only admin but not the only member
+pub async fn get_sole_admin_company_ids(user_id: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+    let company_ids = sqlx::query_scalar::<_, Uuid>(&sql(r#"
+        SELECT
+            uc.company_id
+        FROM
+            user_company uc
+        WHERE
+            uc.user_id = ?
+            AND uc.role = 'admin'
+            AND NOT EXISTS (
+                SELECT
+                    1
+                FROM
+                    user_company other
+                WHERE
+                    other.company_id = uc.company_id
+                    AND other.user_id <> uc.user_id
+                    AND other.role = 'admin'
+            )
+            AND EXISTS (
+                SELECT
+                    1
+                FROM
+                    user_company other
+                WHERE
+                    other.company_id = uc.company_id
+                    AND other.user_id <> uc.user_id
+            )
+    "#))
+    .bind(user_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(company_ids)
+}
+
+/// Replace the user's identifying details and mark the account erased. The row itself stays
+/// so shifts, assignments, time off and wage history keep pointing at a valid user.
+pub async fn anonymize_user(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    anonymized_email: &str,
+    anonymized_name: &str,
+    password_hash: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&sql(r#"
+        UPDATE users
+        SET
+            email = ?,
+            name = ?,
+            password_hash = ?,
+            email_verified_at = NULL,
+            erased_at = NOW(),
+            updated_at = NOW()
+        WHERE
+            id = ?
+    "#))
+    .bind(anonymized_email)
+    .bind(anonymized_name)
+    .bind(password_hash)
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Delete everything that only exists to sign the user in or to place them on future
+/// schedules: sessions, second factors, linked identities, pending tokens, lockouts,
+/// memberships and their team, scope, skill and availability rows
+pub async fn delete_account_data(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    for table in [
+        "user_sessions",
+        "two_factor_recovery_codes",
+        "user_two_factor",
+        "user_identities",
+        "password_reset_tokens",
+        "email_verification_tokens",
+        "account_lockouts",
+        "team_members",
+        "user_skills",
+        "user_shift_schedules",
+        "user_company",
+    ] {
+        sqlx::query(&sql(&format!("DELETE FROM {} WHERE user_id = ?", table)))
+            .bind(user_id)
+            .execute(&mut **tx)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Cancel the user's open claims, swaps and time off, and clear the free-text notes they
+/// wrote. Dates, statuses and approvals are kept.
+pub async fn close_open_requests(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&sql(r#"
+        UPDATE shift_claims
+        SET
+            status = 'cancelled',
+            updated_at = NOW()
+        WHERE
+            user_id = ?
+            AND status = 'pending'
+    "#))
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query(&sql(r#"
+        UPDATE shift_swaps
+        SET
+            status = CASE
+                WHEN status IN ('open', 'pending') THEN 'cancelled'
+                ELSE status
+            END,
+            notes = NULL,
+            updated_at = NOW()
+        WHERE
+            requesting_user_id = ?
+    "#))
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query(&sql(r#"
+        UPDATE shift_swap_responses
+        SET
+            notes = NULL
+        WHERE
+            responding_user_id = ?
+    "#))
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query(&sql(r#"
+        UPDATE shift_assignments
+        SET
+            response_notes = NULL
+        WHERE
+            user_id = ?
+    "#))
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query(&sql(r#"
+        UPDATE time_off_requests
+        SET
+            status = CASE
+                WHEN status = 'pending' THEN 'cancelled'
+                ELSE status
+            END,
+            reason = NULL,
+            updated_at = NOW()
+        WHERE
+            user_id = ?
+    "#))
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Remove the user's email from activity entries and invites, and drop the network details
+/// recorded for their own actions. Scrubbed entries are marked redacted; the chained ones are
+/// returned in their scrubbed state so the redaction can be recorded in each company's chain.
+pub async fn scrub_activity(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    email: &str,
+    anonymized_email: &str,
+) -> Result<Vec<CompanyActivity>, sqlx::Error> {
+    let mut scrubbed = sqlx::query_scalar::<_, Uuid>(&sql(r#"
+        UPDATE company_activity
+        SET
+            ip_address = NULL,
+            user_agent = NULL,
+            redacted_at = NOW()
+        WHERE
+            user_id = ?
+        RETURNING
+            id
+    "#))
+    .bind(user_id)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    scrubbed.extend(
+        sqlx::query_scalar::<_, Uuid>(&sql(r#"
+            UPDATE company_activity
+            SET
+                description = REPLACE(description, ?, ?),
+                metadata = REPLACE(metadata::TEXT, ?, ?)::JSONB,
+                redacted_at = NOW()
+            WHERE
+                STRPOS(description, ?) > 0
+                OR STRPOS(metadata::TEXT, ?) > 0
+            RETURNING
+                id
+        "#))
+        .bind(email)
+        .bind(anonymized_email)
+        .bind(email)
+        .bind(anonymized_email)
+        .bind(email)
+        .bind(email)
+        .fetch_all(&mut **tx)
+        .await?,
+    );
+
+    sqlx::query(&sql(r#"
+        UPDATE invite_tokens
+        SET
+            email = ?
+        WHERE
+            email = ?
+    "#))
+    .bind(anonymized_email)
+    .bind(email)
+    .execute(&mut **tx)
+    .await?;
+
+    let redacted = sqlx::query_as::<_, CompanyActivity>(&sql(r#"
+        SELECT
+            id,
+            company_id,
+            user_id,
+            activity_type,
+            entity_type,
+            entity_id,
+            action,
+            description,
+            metadata,
+            ip_address,
+            user_agent,
+            api_key_id,
+            impersonated_user_id,
+            chain_seq,
+            content_hash,
+            prev_hash,
+            row_hash,
+            redacted_at,
+            created_at
+        FROM
+            company_activity
+        WHERE
+            id = ANY(?)
+            AND chain_seq IS NOT NULL
+        ORDER BY
+            company_id,
+            chain_seq
+    "#))
+    .bind(&scrubbed)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    Ok(redacted)
+}
@@ -1,17 +1,34 @@
 use bigdecimal::BigDecimal;
-use chrono::{Datelike, Utc};
+use chrono::{Datelike, NaiveDate, Utc};
 use sqlx::{Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::database::{
     get_pool,
     models::{
-        PtoBalance, PtoBalanceAccrual, PtoBalanceAdjustmentInput, PtoBalanceHistory,
-        PtoBalanceType, PtoBalanceUpdateInput, PtoChangeType,
+        PtoBalance, PtoBalanceAccrual, PtoBalanceAdjustmentInput, PtoBalanceAsOf,
+        PtoBalanceHistory, PtoBalanceType, PtoBalanceUpdateInput, PtoChangeType,
+        PtoLedgerEntryInput, TimeOffRequest,
     },
     utils::sql,
 };
 
+const HISTORY_COLUMNS: &str = r#"
+    id,
+    user_id,
+    company_id,
+    balance_type,
+    change_type,
+    hours_changed,
+    previous_balance,
+    new_balance,
+    effective_date,
+    description,
+    related_time_off_id,
+    created_by,
+    created_at
+"#;
+
 macro_rules! update_field {
     ($tx:expr, $field_value:expr, $field_name:literal, $user_id:expr, $company_id:expr, $now:expr) => {
         if let Some(value) = $field_value {
@@ -67,46 +84,190 @@ pub async fn get_balance_for_company(
     Ok(pto_balance)
 }
 
+/// Column on user_company caching the running total of a balance
+fn balance_column(balance_type: PtoBalanceType) -> &'static str {
+    match balance_type {
+        PtoBalanceType::Pto => "pto_balance_hours",
+        PtoBalanceType::Sick => "sick_balance_hours",
+        PtoBalanceType::Personal => "personal_balance_hours",
+    }
+}
+
+/// Append an entry to the PTO ledger and move the cached balance by the same hours.
+/// The membership row stays locked until the transaction ends, so running totals never interleave.
+pub async fn record_entry(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    company_id: Uuid,
+    entry: &PtoLedgerEntryInput,
+) -> Result<PtoBalanceHistory, sqlx::Error> {
+    let column = balance_column(entry.balance_type);
+    let query = format!(
+        r#"
+        UPDATE
+            user_company
+        SET
+            {column} = {column} + ?,
+            updated_at = ?
+        WHERE
+            user_id = ?
+            AND company_id = ?
+        RETURNING
+            {column}
+        "#
+    );
+    let query = sql(&query);
+    let new_balance = sqlx::query_scalar::<_, BigDecimal>(&query)
+        .bind(&entry.hours_changed)
+        .bind(Utc::now())
+        .bind(user_id)
+        .bind(company_id)
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+
+    let query = format!(
+        r#"
+        INSERT INTO
+            pto_balance_history (
+                user_id,
+                company_id,
+                balance_type,
+                change_type,
+                hours_changed,
+                previous_balance,
+                new_balance,
+                effective_date,
+                description,
+                related_time_off_id,
+                created_by
+            )
+        VALUES
+            (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING
+            {HISTORY_COLUMNS}
+        "#
+    );
+    let query = sql(&query);
+    let history_row = sqlx::query_as::<_, PtoBalanceHistory>(&query)
+        .bind(user_id)
+        .bind(company_id)
+        .bind(entry.balance_type)
+        .bind(entry.change_type)
+        .bind(&entry.hours_changed)
+        .bind(&new_balance - &entry.hours_changed)
+        .bind(&new_balance)
+        .bind(entry.effective_date)
+        .bind(&entry.description)
+        .bind(entry.related_time_off_id)
+        .bind(entry.created_by)
+        .fetch_one(&mut **tx)
+        .await?;
+
+    Ok(history_row)
+}
+
 /// Update PTO balance for a user in a specific company
 pub async fn update_balance_for_company(
     tx: &mut Transaction<'_, Postgres>,
     user_id: Uuid,
     company_id: Uuid,
     update: PtoBalanceUpdateInput,
+    created_by: Option<Uuid>,
 ) -> Result<PtoBalance, sqlx::Error> {
-    let now = Utc::now();
-
     // Get current balance first
     let current = get_balance_for_company(user_id, company_id).await?;
     if current.is_none() {
         return Err(sqlx::Error::RowNotFound);
     }
 
-    // Execute updates for each field that's provided
-    update_field!(
-        tx,
-        update.pto_balance_hours,
-        "pto_balance_hours",
-        user_id,
-        company_id,
-        now
-    );
-    update_field!(
-        tx,
-        update.sick_balance_hours,
-        "sick_balance_hours",
-        user_id,
-        company_id,
-        now
-    );
-    update_field!(
-        tx,
-        update.personal_balance_hours,
-        "personal_balance_hours",
-        user_id,
-        company_id,
-        now
-    );
+    set_balance_fields(tx, user_id, company_id, update, created_by).await?;
+
+    // Return updated balance, read inside the transaction so it includes the new values
+    let pto_balance = sqlx::query_as::<_, PtoBalance>(&sql(r#"
+        SELECT
+            user_id,
+            pto_balance_hours,
+            sick_balance_hours,
+            personal_balance_hours,
+            pto_accrual_rate,
+            hire_date,
+            last_accrual_date
+        FROM
+            user_company
+        WHERE
+            user_id = ?
+            AND company_id = ?
+    "#))
+    .bind(user_id)
+    .bind(company_id)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(pto_balance)
+}
+
+/// Write the provided fields without reading the membership back, so it also works for a
+/// membership created earlier in the same transaction. Balances are set through ledger
+/// adjustments for the difference.
+pub async fn set_balance_fields(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    company_id: Uuid,
+    update: PtoBalanceUpdateInput,
+    created_by: Option<Uuid>,
+) -> Result<(), sqlx::Error> {
+    let now = Utc::now();
+
+    for (balance_type, hours) in [
+        (PtoBalanceType::Pto, update.pto_balance_hours),
+        (PtoBalanceType::Sick, update.sick_balance_hours),
+        (PtoBalanceType::Personal, update.personal_balance_hours),
+    ] {
+        let Some(hours) = hours else {
+            continue;
+        };
+
+        let query = format!(
+            r#"
+            SELECT
+                {}
+            FROM
+                user_company
+            WHERE
+                user_id = ?
+                AND company_id = ?
+            FOR UPDATE
+            "#,
+            balance_column(balance_type)
+        );
+        let query = sql(&query);
+        let current = sqlx::query_scalar::<_, BigDecimal>(&query)
+            .bind(user_id)
+            .bind(company_id)
+            .fetch_optional(&mut **tx)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+
+        if current != hours {
+            record_entry(
+                tx,
+                user_id,
+                company_id,
+                &PtoLedgerEntryInput {
+                    balance_type,
+                    change_type: PtoChangeType::Adjustment,
+                    hours_changed: &hours - current,
+                    effective_date: now.date_naive(),
+                    description: format!("Balance set to {} hours", hours),
+                    related_time_off_id: None,
+                    created_by,
+                },
+            )
+            .await?;
+        }
+    }
+
     update_field!(
         tx,
         update.pto_accrual_rate,
@@ -117,10 +278,7 @@ pub async fn update_balance_for_company(
     );
     update_field!(tx, update.hire_date, "hire_date", user_id, company_id, now);
 
-    // Return updated balance
-    get_balance_for_company(user_id, company_id)
-        .await?
-        .ok_or_else(|| sqlx::Error::RowNotFound)
+    Ok(())
 }
 
 /// Adjust PTO balance and create history record for a specific company
@@ -129,104 +287,31 @@ pub async fn adjust_balance_for_company(
     user_id: Uuid,
     company_id: Uuid,
     adjustment: PtoBalanceAdjustmentInput,
+    created_by: Option<Uuid>,
 ) -> Result<PtoBalanceHistory, sqlx::Error> {
-    let now = Utc::now();
-
-    // Get current balance
-    let current_balance = get_balance_for_company(user_id, company_id)
-        .await?
-        .ok_or_else(|| sqlx::Error::RowNotFound)?;
-
-    // Calculate new balance
-    let (previous_balance, new_balance, field_name) = match adjustment.balance_type {
-        PtoBalanceType::Pto => (
-            current_balance.pto_balance_hours,
-            current_balance.pto_balance_hours + adjustment.hours_changed,
-            "pto_balance_hours",
-        ),
-        PtoBalanceType::Sick => (
-            current_balance.sick_balance_hours,
-            current_balance.sick_balance_hours + adjustment.hours_changed,
-            "sick_balance_hours",
-        ),
-        PtoBalanceType::Personal => (
-            current_balance.personal_balance_hours,
-            current_balance.personal_balance_hours + adjustment.hours_changed,
-            "personal_balance_hours",
-        ),
-    };
+    let history_row = record_entry(
+        tx,
+        user_id,
+        company_id,
+        &PtoLedgerEntryInput {
+            balance_type: adjustment.balance_type,
+            change_type: adjustment.change_type,
+            hours_changed: adjustment.hours_changed,
+            effective_date: adjustment
+                .effective_date
+                .unwrap_or_else(|| Utc::now().date_naive()),
+            description: adjustment.description,
+            related_time_off_id: None,
+            created_by,
+        },
+    )
+    .await?;
 
-    // Prevent negative balances
-    if new_balance < 0 {
+    // Prevent negative balances; the error rolls the entry back
+    if history_row.new_balance < BigDecimal::from(0) {
         return Err(sqlx::Error::RowNotFound);
     }
 
-    // Update balance in user_company table
-    let query = format!(
-        r#"
-            UPDATE
-                user_company
-            SET
-                {} = ?,
-                updated_at = ?
-            WHERE
-                user_id = ?
-                AND company_id = ?
-        "#,
-        field_name
-    );
-    sqlx::query(&sql(&query))
-        .bind(new_balance)
-        .bind(now)
-        .bind(user_id)
-        .bind(company_id)
-        .execute(&get_pool().await)
-        .await?;
-
-    // Create history record
-    let balance_type_str = adjustment.balance_type.to_string();
-    let change_type_str = adjustment.change_type.to_string();
-
-    let history_row = sqlx::query_as::<_, PtoBalanceHistory>(&sql(r#"
-            INSERT INTO
-                pto_balance_history (
-                    user_id,
-                    company_id,
-                    balance_type,
-                    change_type,
-                    hours_changed,
-                    previous_balance,
-                    new_balance,
-                    description,
-                    created_at
-                )
-            VALUES
-                (?, ?, ?, ?, ?, ?, ?, ?, ?)
-            RETURNING 
-                id,
-                user_id,
-                company_id,
-                balance_type,
-                change_type,
-                hours_changed,
-                previous_balance,
-                new_balance,
-                description,
-                related_time_off_id,
-                created_at
-    "#))
-    .bind(user_id)
-    .bind(company_id)
-    .bind(balance_type_str)
-    .bind(change_type_str)
-    .bind(adjustment.hours_changed)
-    .bind(previous_balance)
-    .bind(new_balance)
-    .bind(adjustment.description)
-    .bind(now)
-    .fetch_one(&mut **tx)
-    .await?;
-
     Ok(history_row)
 }
 
@@ -238,35 +323,103 @@ pub async fn get_balance_history(
 ) -> Result<Vec<PtoBalanceHistory>, sqlx::Error> {
     let limit = limit.unwrap_or(50);
 
-    let history = sqlx::query_as::<_, PtoBalanceHistory>(&sql(r#"
-        SELECT 
-            id,
-            user_id,
-            balance_type,
-            change_type,
-            hours_changed,
-            previous_balance,
-            new_balance,
-            description,
-            related_time_off_id,
-            created_at
+    let query = format!(
+        r#"
+        SELECT
+            {HISTORY_COLUMNS}
         FROM
             pto_balance_history
         WHERE
             user_id = ?
             AND company_id = ?
         ORDER BY
-            created_at DESC
+            entry_number DESC
         LIMIT
             ?
+        "#
+    );
+    let query = sql(&query);
+    let history = sqlx::query_as::<_, PtoBalanceHistory>(&query)
+        .bind(user_id)
+        .bind(company_id)
+        .bind(limit as i64)
+        .fetch_all(&get_pool().await)
+        .await?;
+
+    Ok(history)
+}
+
+/// Every ledger entry for the user, across all of their companies
+pub async fn find_history_by_user_id(user_id: Uuid) -> Result<Vec<PtoBalanceHistory>, sqlx::Error> {
+    let query = format!(
+        r#"
+        SELECT
+            {HISTORY_COLUMNS}
+        FROM
+            pto_balance_history
+        WHERE
+            user_id = ?
+        ORDER BY
+            entry_number ASC
+        "#
+    );
+    let query = sql(&query);
+    let history = sqlx::query_as::<_, PtoBalanceHistory>(&query)
+        .bind(user_id)
+        .fetch_all(&get_pool().await)
+        .await?;
+
+    Ok(history)
+}
+
+/// Balances of the company's members summed from the ledger entries effective on or before
+/// `as_of`, for reconciling against payroll
+pub async fn get_balances_as_of(
+    company_id: Uuid,
+    as_of: NaiveDate,
+    user_id: Option<Uuid>,
+) -> Result<Vec<PtoBalanceAsOf>, sqlx::Error> {
+    let balances = sqlx::query_as::<_, PtoBalanceAsOf>(&sql(r#"
+        SELECT
+            uc.user_id,
+            u.name,
+            ?::DATE AS as_of,
+            COALESCE(SUM(h.hours_changed) FILTER (
+                WHERE
+                    h.balance_type = 'pto'
+            ), 0) AS pto_balance_hours,
+            COALESCE(SUM(h.hours_changed) FILTER (
+                WHERE
+                    h.balance_type = 'sick'
+            ), 0) AS sick_balance_hours,
+            COALESCE(SUM(h.hours_changed) FILTER (
+                WHERE
+                    h.balance_type = 'personal'
+            ), 0) AS personal_balance_hours
+        FROM
+            user_company uc
+            JOIN users u ON u.id = uc.user_id
+            LEFT JOIN pto_balance_history h ON h.user_id = uc.user_id
+            AND h.company_id = uc.company_id
+            AND h.effective_date <= ?
+        WHERE
+            uc.company_id = ?
+            AND (?::UUID IS NULL OR uc.user_id = ?)
+        GROUP BY
+            uc.user_id,
+            u.name
+        ORDER BY
+            u.name ASC
     "#))
-    .bind(user_id)
+    .bind(as_of)
+    .bind(as_of)
     .bind(company_id)
-    .bind(limit as i64)
+    .bind(user_id)
+    .bind(user_id)
     .fetch_all(&get_pool().await)
     .await?;
 
-    Ok(history)
+    Ok(balances)
 }
 
 /// Process PTO accrual for a user in a specific company
@@ -274,6 +427,7 @@ pub async fn process_accrual_for_company(
     tx: &mut Transaction<'_, Postgres>,
     user_id: Uuid,
     company_id: Uuid,
+    created_by: Option<Uuid>,
 ) -> Result<Option<PtoBalanceAccrual>, sqlx::Error> {
     let current_balance = get_balance_for_company(user_id, company_id).await?;
     if current_balance.is_none() {
@@ -301,31 +455,34 @@ pub async fn process_accrual_for_company(
         return Ok(None);
     }
 
-    let hours_to_accrue = (current_balance
-        .pto_accrual_rate
-        .to_string()
-        .parse::<f32>()
-        .unwrap_or(0.0)
-        * months_since_last_accrual as f32) as i32;
-
-    if hours_to_accrue <= 0 {
-        return Ok(None);
-    }
+    let hours_to_accrue =
+        &current_balance.pto_accrual_rate * BigDecimal::from(months_since_last_accrual);
 
-    // Update balance in user_company table
-    let new_balance = current_balance.pto_balance_hours + hours_to_accrue;
+    let history_row = record_entry(
+        tx,
+        user_id,
+        company_id,
+        &PtoLedgerEntryInput {
+            balance_type: PtoBalanceType::Pto,
+            change_type: PtoChangeType::Accrual,
+            hours_changed: hours_to_accrue.clone(),
+            effective_date: today,
+            description: format!("Monthly accrual of {} hours", hours_to_accrue),
+            related_time_off_id: None,
+            created_by,
+        },
+    )
+    .await?;
 
     sqlx::query(&sql(r#"
         UPDATE user_company
         SET
-            pto_balance_hours = ?,
             last_accrual_date = ?,
             updated_at = ?
         WHERE
             user_id = ?
             AND company_id = ?
     "#))
-    .bind(new_balance)
     .bind(today)
     .bind(now)
     .bind(user_id)
@@ -333,37 +490,6 @@ pub async fn process_accrual_for_company(
     .execute(&mut **tx)
     .await?;
 
-    // Create history record
-    let balance_type_str = PtoBalanceType::Pto.to_string();
-    let change_type_str = PtoChangeType::Accrual.to_string();
-    let description = format!("Monthly accrual of {} hours", hours_to_accrue);
-
-    sqlx::query(&sql(r#"
-        INSERT INTO
-            pto_balance_history (
-                user_id,
-                balance_type,
-                change_type,
-                hours_changed,
-                previous_balance,
-                new_balance,
-                description,
-                created_at
-            )
-        VALUES
-            (?, ?, ?, ?, ?, ?, ?, ?)
-    "#))
-    .bind(user_id)
-    .bind(balance_type_str)
-    .bind(change_type_str)
-    .bind(hours_to_accrue)
-    .bind(current_balance.pto_balance_hours)
-    .bind(new_balance)
-    .bind(description)
-    .bind(now)
-    .execute(&get_pool().await)
-    .await?;
-
     Ok(Some(PtoBalanceAccrual {
         user_id,
         company_id,
@@ -371,116 +497,95 @@ pub async fn process_accrual_for_company(
         last_accrual_date: Some(last_accrual),
         months_since_last_accrual,
         hours_to_accrue,
-        new_balance,
+        new_balance: history_row.new_balance,
     }))
 }
 
-/// Use PTO balance for a time-off request in a specific company
+/// Use PTO balance for a time-off request, effective on its first day. Unless
+/// `allow_negative`, fails when the balance would drop below zero.
 pub async fn use_balance_for_time_off_for_company(
     tx: &mut Transaction<'_, Postgres>,
-    user_id: Uuid,
-    company_id: Uuid,
-    time_off_id: Uuid,
+    request: &TimeOffRequest,
     balance_type: PtoBalanceType,
-    hours_used: i32,
+    hours_used: BigDecimal,
+    allow_negative: bool,
+    created_by: Option<Uuid>,
 ) -> Result<PtoBalanceHistory, sqlx::Error> {
-    let now = Utc::now();
+    let history_row = record_entry(
+        tx,
+        request.user_id,
+        request.company_id,
+        &PtoLedgerEntryInput {
+            balance_type,
+            change_type: PtoChangeType::Usage,
+            hours_changed: -hours_used,
+            effective_date: request.start_date.date_naive(),
+            description: "Time-off request usage".to_string(),
+            related_time_off_id: Some(request.id),
+            created_by,
+        },
+    )
+    .await?;
 
-    // Get current balance
-    let current_balance = get_balance_for_company(user_id, company_id).await?;
-    if current_balance.is_none() {
+    // Check if sufficient balance; the error rolls the entry back
+    if !allow_negative && history_row.new_balance < BigDecimal::from(0) {
         return Err(sqlx::Error::RowNotFound);
     }
-    let current_balance = current_balance.unwrap();
 
-    // Calculate new balance
-    let (previous_balance, new_balance, field_name) = match balance_type {
-        PtoBalanceType::Pto => (
-            current_balance.pto_balance_hours,
-            current_balance.pto_balance_hours - hours_used,
-            "pto_balance_hours",
-        ),
-        PtoBalanceType::Sick => (
-            current_balance.sick_balance_hours,
-            current_balance.sick_balance_hours - hours_used,
-            "sick_balance_hours",
-        ),
-        PtoBalanceType::Personal => (
-            current_balance.personal_balance_hours,
-            current_balance.personal_balance_hours - hours_used,
-            "personal_balance_hours",
-        ),
-    };
-
-    // Check if sufficient balance
-    if new_balance < 0 {
-        return Err(sqlx::Error::RowNotFound);
-    }
+    Ok(history_row)
+}
 
-    // Update balance in user_company table
-    let query = format!(
-        r#"
-        UPDATE user_company
-        SET
-            {} = ?,
-            updated_at = ?
+/// Give back the hours a time-off request took from each balance, effective on the same day
+/// they were used. Refunding twice adds nothing.
+pub async fn refund_time_off_for_company(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    company_id: Uuid,
+    time_off_id: Uuid,
+    created_by: Option<Uuid>,
+) -> Result<Vec<PtoBalanceHistory>, sqlx::Error> {
+    let used = sqlx::query_as::<_, (PtoBalanceType, BigDecimal, NaiveDate)>(&sql(r#"
+        SELECT
+            balance_type,
+            SUM(hours_changed),
+            MIN(effective_date)
+        FROM
+            pto_balance_history
         WHERE
             user_id = ?
             AND company_id = ?
-        "#,
-        field_name
-    );
-    sqlx::query(&sql(&query))
-        .bind(new_balance)
-        .bind(now)
-        .bind(user_id)
-        .bind(company_id)
-        .execute(&get_pool().await)
-        .await?;
-
-    // Create history record
-    let balance_type_str = balance_type.to_string();
-    let change_type_str = PtoChangeType::Usage.to_string();
-    let hours_changed = -hours_used;
-
-    let history_row = sqlx::query_as::<_, PtoBalanceHistory>(&sql(r#"
-        INSERT INTO
-            pto_balance_history (
-                user_id,
-                balance_type,
-                change_type,
-                hours_changed,
-                previous_balance,
-                new_balance,
-                description,
-                related_time_off_id,
-                created_at
-            )
-        VALUES
-            (?, ?, ?, ?, ?, ?, ?, ?, ?)
-        RETURNING 
-            id,
-            user_id,
-            balance_type,
-            change_type,
-            hours_changed,
-            previous_balance,
-            new_balance,
-            description,
-            related_time_off_id,
-            created_at
+            AND related_time_off_id = ?
+        GROUP BY
+            balance_type
+        HAVING
+            SUM(hours_changed) < 0
     "#))
     .bind(user_id)
-    .bind(balance_type_str)
-    .bind(change_type_str)
-    .bind(hours_changed)
-    .bind(previous_balance)
-    .bind(new_balance)
-    .bind("Time-off request usage")
+    .bind(company_id)
     .bind(time_off_id)
-    .bind(now)
-    .fetch_one(&mut **tx)
+    .fetch_all(&mut **tx)
     .await?;
 
-    Ok(history_row)
+    let mut refunds = Vec::new();
+    for (balance_type, hours_changed, effective_date) in used {
+        refunds.push(
+            record_entry(
+                tx,
+                user_id,
+                company_id,
+                &PtoLedgerEntryInput {
+                    balance_type,
+                    change_type: PtoChangeType::Refund,
+                    hours_changed: -hours_changed,
+                    effective_date,
+                    description: "Time-off request cancelled".to_string(),
+                    related_time_off_id: Some(time_off_id),
+                    created_by,
+                },
+            )
+            .await?,
+        );
+    }
+
+    Ok(refunds)
 }
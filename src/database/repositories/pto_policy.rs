@@ -0,0 +1,535 @@
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::database::{
+    get_pool,
+    models::{
+        AccrualYearStart, PtoAccrualPolicy, PtoAccrualPolicyInput, PtoAccrualState, PtoAccrualTier,
+        PtoAccrualTierInput, PtoBalanceType, PtoPolicyAssignment, PtoPolicyAssignmentInput,
+        PtoPolicyMember,
+    },
+    utils::sql,
+};
+
+const POLICY_COLUMNS: &str = r#"
+    id,
+    company_id,
+    name,
+    description,
+    balance_type,
+    accrual_method,
+    pay_period,
+    annual_cap_hours,
+    max_balance_hours,
+    carryover_limit_hours,
+    year_start,
+    year_start_month,
+    year_start_day,
+    waiting_period_days,
+    is_active,
+    created_at,
+    updated_at
+"#;
+
+pub async fn get_policies(company_id: Uuid) -> Result<Vec<PtoAccrualPolicy>, sqlx::Error> {
+    let query = format!(
+        r#"
+        SELECT
+            {POLICY_COLUMNS}
+        FROM
+            pto_accrual_policies
+        WHERE
+            company_id = ?
+        ORDER BY
+            name
+    "#
+    );
+    let policies = sqlx::query_as::<_, PtoAccrualPolicy>(&sql(&query))
+        .bind(company_id)
+        .fetch_all(&get_pool().await)
+        .await?;
+
+    Ok(policies)
+}
+
+pub async fn find_policy(
+    company_id: Uuid,
+    policy_id: Uuid,
+) -> Result<Option<PtoAccrualPolicy>, sqlx::Error> {
+    let query = format!(
+        r#"
+        SELECT
+            {POLICY_COLUMNS}
+        FROM
+            pto_accrual_policies
+        WHERE
+            id = ?
+            AND company_id = ?
+    "#
+    );
+    let policy = sqlx::query_as::<_, PtoAccrualPolicy>(&sql(&query))
+        .bind(policy_id)
+        .bind(company_id)
+        .fetch_optional(&get_pool().await)
+        .await?;
+
+    Ok(policy)
+}
+
+pub async fn create_policy(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    input: &PtoAccrualPolicyInput,
+) -> Result<PtoAccrualPolicy, sqlx::Error> {
+    let query = format!(
+        r#"
+        INSERT INTO
+            pto_accrual_policies (
+                company_id,
+                name,
+                description,
+                balance_type,
+                accrual_method,
+                pay_period,
+                annual_cap_hours,
+                max_balance_hours,
+                carryover_limit_hours,
+                year_start,
+                year_start_month,
+                year_start_day,
+                waiting_period_days,
+                is_active
+            )
+        VALUES
+            (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING
+            {POLICY_COLUMNS}
+    "#
+    );
+    let policy = sqlx::query_as::<_, PtoAccrualPolicy>(&sql(&query))
+        .bind(company_id)
+        .bind(&input.name)
+        .bind(&input.description)
+        .bind(input.balance_type.unwrap_or(PtoBalanceType::Pto))
+        .bind(input.accrual_method)
+        .bind(input.pay_period)
+        .bind(&input.annual_cap_hours)
+        .bind(&input.max_balance_hours)
+        .bind(&input.carryover_limit_hours)
+        .bind(input.year_start.unwrap_or(AccrualYearStart::Calendar))
+        .bind(input.year_start_month.unwrap_or(1))
+        .bind(input.year_start_day.unwrap_or(1))
+        .bind(input.waiting_period_days.unwrap_or(0))
+        .bind(input.is_active.unwrap_or(true))
+        .fetch_one(&mut **tx)
+        .await?;
+
+    Ok(policy)
+}
+
+pub async fn update_policy(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    policy_id: Uuid,
+    input: &PtoAccrualPolicyInput,
+) -> Result<Option<PtoAccrualPolicy>, sqlx::Error> {
+    let query = format!(
+        r#"
+        UPDATE pto_accrual_policies
+        SET
+            name = ?,
+            description = ?,
+            balance_type = ?,
+            accrual_method = ?,
+            pay_period = ?,
+            annual_cap_hours = ?,
+            max_balance_hours = ?,
+            carryover_limit_hours = ?,
+            year_start = ?,
+            year_start_month = ?,
+            year_start_day = ?,
+            waiting_period_days = ?,
+            is_active = ?,
+            updated_at = NOW()
+        WHERE
+            id = ?
+            AND company_id = ?
+        RETURNING
+            {POLICY_COLUMNS}
+    "#
+    );
+    let policy = sqlx::query_as::<_, PtoAccrualPolicy>(&sql(&query))
+        .bind(&input.name)
+        .bind(&input.description)
+        .bind(input.balance_type.unwrap_or(PtoBalanceType::Pto))
+        .bind(input.accrual_method)
+        .bind(input.pay_period)
+        .bind(&input.annual_cap_hours)
+        .bind(&input.max_balance_hours)
+        .bind(&input.carryover_limit_hours)
+        .bind(input.year_start.unwrap_or(AccrualYearStart::Calendar))
+        .bind(input.year_start_month.unwrap_or(1))
+        .bind(input.year_start_day.unwrap_or(1))
+        .bind(input.waiting_period_days.unwrap_or(0))
+        .bind(input.is_active.unwrap_or(true))
+        .bind(policy_id)
+        .bind(company_id)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+    Ok(policy)
+}
+
+/// Delete a policy with its tiers and assignments. Members keep what they accrued.
+pub async fn delete_policy(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    policy_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(&sql(r#"
+        DELETE FROM pto_accrual_policies
+        WHERE
+            id = ?
+            AND company_id = ?
+    "#))
+    .bind(policy_id)
+    .bind(company_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Tiers of the policies, lowest tenure first
+pub async fn get_tiers(policy_ids: &[Uuid]) -> Result<Vec<PtoAccrualTier>, sqlx::Error> {
+    let tiers = sqlx::query_as::<_, PtoAccrualTier>(&sql(r#"
+        SELECT
+            id,
+            policy_id,
+            min_tenure_months,
+            accrual_rate
+        FROM
+            pto_accrual_tiers
+        WHERE
+            policy_id = ANY(?)
+        ORDER BY
+            policy_id,
+            min_tenure_months
+    "#))
+    .bind(policy_ids)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(tiers)
+}
+
+pub async fn replace_tiers(
+    tx: &mut Transaction<'_, Postgres>,
+    policy_id: Uuid,
+    tiers: &[PtoAccrualTierInput],
+) -> Result<Vec<PtoAccrualTier>, sqlx::Error> {
+    sqlx::query(&sql(r#"
+        DELETE FROM pto_accrual_tiers
+        WHERE
+            policy_id = ?
+    "#))
+    .bind(policy_id)
+    .execute(&mut **tx)
+    .await?;
+
+    let mut created = Vec::with_capacity(tiers.len());
+    for tier in tiers {
+        let tier = sqlx::query_as::<_, PtoAccrualTier>(&sql(r#"
+            INSERT INTO
+                pto_accrual_tiers (policy_id, min_tenure_months, accrual_rate)
+            VALUES
+                (?, ?, ?)
+            RETURNING
+                id,
+                policy_id,
+                min_tenure_months,
+                accrual_rate
+        "#))
+        .bind(policy_id)
+        .bind(tier.min_tenure_months)
+        .bind(&tier.accrual_rate)
+        .fetch_one(&mut **tx)
+        .await?;
+        created.push(tier);
+    }
+
+    Ok(created)
+}
+
+pub async fn get_assignments(company_id: Uuid) -> Result<Vec<PtoPolicyAssignment>, sqlx::Error> {
+    let assignments = sqlx::query_as::<_, PtoPolicyAssignment>(&sql(r#"
+        SELECT
+            id,
+            policy_id,
+            company_id,
+            user_id,
+            team_id,
+            location_id,
+            created_at
+        FROM
+            pto_policy_assignments
+        WHERE
+            company_id = ?
+        ORDER BY
+            created_at
+    "#))
+    .bind(company_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(assignments)
+}
+
+/// Assign the policy, or return `None` when it already has this assignment
+pub async fn create_assignment(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    policy_id: Uuid,
+    input: &PtoPolicyAssignmentInput,
+) -> Result<Option<PtoPolicyAssignment>, sqlx::Error> {
+    let assignment = sqlx::query_as::<_, PtoPolicyAssignment>(&sql(r#"
+        INSERT INTO
+            pto_policy_assignments (policy_id, company_id, user_id, team_id, location_id)
+        VALUES
+            (?, ?, ?, ?, ?)
+        ON CONFLICT DO NOTHING
+        RETURNING
+            id,
+            policy_id,
+            company_id,
+            user_id,
+            team_id,
+            location_id,
+            created_at
+    "#))
+    .bind(policy_id)
+    .bind(company_id)
+    .bind(input.user_id)
+    .bind(input.team_id)
+    .bind(input.location_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(assignment)
+}
+
+pub async fn delete_assignment(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    policy_id: Uuid,
+    assignment_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(&sql(r#"
+        DELETE FROM pto_policy_assignments
+        WHERE
+            id = ?
+            AND policy_id = ?
+            AND company_id = ?
+    "#))
+    .bind(assignment_id)
+    .bind(policy_id)
+    .bind(company_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// The active policy applying to each member for the balance. The most specific assignment
+/// wins: the member, then one of their teams, then a location of their teams, then the
+/// company; the earliest assigned breaks ties.
+pub async fn get_policy_members(
+    company_id: Uuid,
+    balance_type: PtoBalanceType,
+    user_id: Option<Uuid>,
+) -> Result<Vec<PtoPolicyMember>, sqlx::Error> {
+    let members = sqlx::query_as::<_, PtoPolicyMember>(&sql(r#"
+        SELECT DISTINCT ON (uc.user_id)
+            uc.user_id,
+            u.name,
+            p.id AS policy_id,
+            a.created_at AS assigned_at,
+            uc.hire_date,
+            uc.pto_balance_hours,
+            uc.sick_balance_hours,
+            uc.personal_balance_hours
+        FROM
+            user_company uc
+            JOIN users u ON u.id = uc.user_id
+            JOIN pto_policy_assignments a ON a.company_id = uc.company_id
+            JOIN pto_accrual_policies p ON p.id = a.policy_id
+        WHERE
+            uc.company_id = ?
+            AND p.is_active
+            AND p.balance_type = ?
+            AND (?::UUID IS NULL OR uc.user_id = ?)
+            AND (
+                a.user_id = uc.user_id
+                OR a.team_id IN (
+                    SELECT
+                        team_id
+                    FROM
+                        team_members
+                    WHERE
+                        user_id = uc.user_id
+                )
+                OR a.location_id IN (
+                    SELECT
+                        t.location_id
+                    FROM
+                        team_members tm
+                        JOIN teams t ON t.id = tm.team_id
+                    WHERE
+                        tm.user_id = uc.user_id
+                )
+                OR num_nonnulls (a.user_id, a.team_id, a.location_id) = 0
+            )
+        ORDER BY
+            uc.user_id,
+            CASE
+                WHEN a.user_id IS NOT NULL THEN 1
+                WHEN a.team_id IS NOT NULL THEN 2
+                WHEN a.location_id IS NOT NULL THEN 3
+                ELSE 4
+            END,
+            a.created_at
+    "#))
+    .bind(company_id)
+    .bind(balance_type)
+    .bind(user_id)
+    .bind(user_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(members)
+}
+
+pub async fn find_state(
+    user_id: Uuid,
+    company_id: Uuid,
+    balance_type: PtoBalanceType,
+) -> Result<Option<PtoAccrualState>, sqlx::Error> {
+    let state = sqlx::query_as::<_, PtoAccrualState>(&sql(r#"
+        SELECT
+            user_id,
+            company_id,
+            balance_type,
+            policy_id,
+            accrued_through,
+            year_start,
+            accrued_this_year,
+            updated_at
+        FROM
+            pto_accrual_state
+        WHERE
+            user_id = ?
+            AND company_id = ?
+            AND balance_type = ?
+    "#))
+    .bind(user_id)
+    .bind(company_id)
+    .bind(balance_type)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(state)
+}
+
+/// Save the member's accrual state if it is still the one accrual started from:
+/// `accrued_through` as read then, or no state at all. Returns false when another run saved
+/// it first.
+pub async fn save_state(
+    tx: &mut Transaction<'_, Postgres>,
+    state: &PtoAccrualState,
+    read_accrued_through: Option<NaiveDate>,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(&sql(r#"
+        INSERT INTO
+            pto_accrual_state (
+                user_id,
+                company_id,
+                balance_type,
+                policy_id,
+                accrued_through,
+                year_start,
+                accrued_this_year,
+                updated_at
+            )
+        VALUES
+            (?, ?, ?, ?, ?, ?, ?, NOW())
+        ON CONFLICT (user_id, company_id, balance_type) DO UPDATE
+        SET
+            policy_id = EXCLUDED.policy_id,
+            accrued_through = EXCLUDED.accrued_through,
+            year_start = EXCLUDED.year_start,
+            accrued_this_year = EXCLUDED.accrued_this_year,
+            updated_at = EXCLUDED.updated_at
+        WHERE
+            pto_accrual_state.accrued_through IS NOT DISTINCT FROM ?::DATE
+    "#))
+    .bind(state.user_id)
+    .bind(state.company_id)
+    .bind(state.balance_type)
+    .bind(state.policy_id)
+    .bind(state.accrued_through)
+    .bind(state.year_start)
+    .bind(&state.accrued_this_year)
+    .bind(read_accrued_through)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Hours the member worked per day after `after` up to and including `through`, from
+/// completed clock-ins on the company's shifts
+pub async fn get_daily_worked_hours(
+    user_id: Uuid,
+    company_id: Uuid,
+    after: NaiveDate,
+    through: NaiveDate,
+) -> Result<Vec<(NaiveDate, BigDecimal)>, sqlx::Error> {
+    let hours = sqlx::query_as::<_, (NaiveDate, BigDecimal)>(&sql(r#"
+        SELECT
+            clock_in_at::DATE AS day,
+            (SUM(EXTRACT(EPOCH FROM (clock_out_at - clock_in_at))) / 3600)::DECIMAL(10, 4)
+        FROM
+            shift_attendance
+        WHERE
+            user_id = ?
+            AND company_id = ?
+            AND clock_out_at IS NOT NULL
+            AND clock_in_at::DATE > ?
+            AND clock_in_at::DATE <= ?
+        GROUP BY
+            day
+        ORDER BY
+            day
+    "#))
+    .bind(user_id)
+    .bind(company_id)
+    .bind(after)
+    .bind(through)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(hours)
+}
+
+/// Whether a policy for the balance applies to the member, so the flat rate no longer does
+pub async fn has_policy(
+    user_id: Uuid,
+    company_id: Uuid,
+    balance_type: PtoBalanceType,
+) -> Result<bool, sqlx::Error> {
+    Ok(!get_policy_members(company_id, balance_type, Some(user_id))
+        .await?
+        .is_empty())
+}
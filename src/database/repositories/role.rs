@@ -0,0 +1,221 @@
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::database::{
+    get_pool,
+    models::{Role, RoleInput},
+    utils::sql,
+};
+
+/// The company's built-in and custom roles
+pub async fn get_company_roles(company_id: Uuid) -> Result<Vec<Role>, sqlx::Error> {
+    let roles = sqlx::query_as::<_, Role>(&sql(r#"
+        SELECT
+            id,
+            company_id,
+            name,
+            description,
+            permissions,
+            is_builtin,
+            created_at,
+            updated_at
+        FROM
+            roles
+        WHERE
+            company_id = ?
+        ORDER BY
+            is_builtin DESC,
+            name ASC
+    "#))
+    .bind(company_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(roles)
+}
+
+pub async fn find_company_role(
+    company_id: Uuid,
+    role_id: Uuid,
+) -> Result<Option<Role>, sqlx::Error> {
+    let role = sqlx::query_as::<_, Role>(&sql(r#"
+        SELECT
+            id,
+            company_id,
+            name,
+            description,
+            permissions,
+            is_builtin,
+            created_at,
+            updated_at
+        FROM
+            roles
+        WHERE
+            id = ?
+            AND company_id = ?
+    "#))
+    .bind(role_id)
+    .bind(company_id)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(role)
+}
+
+pub async fn create_role(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    input: &RoleInput,
+) -> Result<Role, sqlx::Error> {
+    let role = sqlx::query_as::<_, Role>(&sql(r#"
+        INSERT INTO
+            roles (company_id, name, description, permissions)
+        VALUES
+            (?, ?, ?, ?)
+        RETURNING
+            id,
+            company_id,
+            name,
+            description,
+            permissions,
+            is_builtin,
+            created_at,
+            updated_at
+    "#))
+    .bind(company_id)
+    .bind(&input.name)
+    .bind(&input.description)
+    .bind(&input.permissions)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(role)
+}
+
+/// Update a custom role; built-in roles cannot be changed
+pub async fn update_role(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    role_id: Uuid,
+    input: &RoleInput,
+) -> Result<Option<Role>, sqlx::Error> {
+    let role = sqlx::query_as::<_, Role>(&sql(r#"
+        UPDATE roles
+        SET
+            name = ?,
+            description = ?,
+            permissions = ?,
+            updated_at = NOW()
+        WHERE
+            id = ?
+            AND company_id = ?
+            AND NOT is_builtin
+        RETURNING
+            id,
+            company_id,
+            name,
+            description,
+            permissions,
+            is_builtin,
+            created_at,
+            updated_at
+    "#))
+    .bind(&input.name)
+    .bind(&input.description)
+    .bind(&input.permissions)
+    .bind(role_id)
+    .bind(company_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(role)
+}
+
+/// Delete a custom role; its members fall back to their built-in role
+pub async fn delete_role(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    role_id: Uuid,
+) -> Result<Option<Role>, sqlx::Error> {
+    let role = sqlx::query_as::<_, Role>(&sql(r#"
+        DELETE FROM roles
+        WHERE
+            id = ?
+            AND company_id = ?
+            AND NOT is_builtin
+        RETURNING
+            id,
+            company_id,
+            name,
+            description,
+            permissions,
+            is_builtin,
+            created_at,
+            updated_at
+    "#))
+    .bind(role_id)
+    .bind(company_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(role)
+}
+
+/// Give a member a custom role, or return them to their built-in role with `None`
+pub async fn assign_member_role(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    user_id: Uuid,
+    role_id: Option<Uuid>,
+) -> Result<Option<()>, sqlx::Error> {
+    let result = sqlx::query(&sql(r#"
+        UPDATE user_company
+        SET
+            role_id = ?,
+            updated_at = NOW()
+        WHERE
+            company_id = ?
+            AND user_id = ?
+    "#))
+    .bind(role_id)
+    .bind(company_id)
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(()))
+}
+
+/// Permissions a member holds in a company: those of their custom role if they have one,
+/// otherwise those of the built-in role matching their company role
+pub async fn get_member_permissions(
+    user_id: Uuid,
+    company_id: Uuid,
+) -> Result<Vec<String>, sqlx::Error> {
+    let permissions = sqlx::query_scalar::<_, Vec<String>>(&sql(r#"
+        SELECT
+            r.permissions
+        FROM
+            user_company uc
+            JOIN roles r ON r.id = uc.role_id
+            OR (
+                uc.role_id IS NULL
+                AND r.company_id = uc.company_id
+                AND r.is_builtin
+                AND r.name = uc.role
+            )
+        WHERE
+            uc.user_id = ?
+            AND uc.company_id = ?
+    "#))
+    .bind(user_id)
+    .bind(company_id)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(permissions.unwrap_or_default())
+}
@@ -281,29 +281,31 @@ pub async fn get_user_shift_suggestions(user_id: Uuid) -> Result<Vec<Shift>, sql
     // A more advanced implementation could also factor in user skills, location preferences, and other constraints.
     let suggestions = sqlx::query_as::<_, Shift>(&sql(r#"
             SELECT
-                s.id
-                s.title
-                s.description
-                s.location_id
-                s.team_id
-                s.start_time
-                s.end_time
-                s.min_duration_minutes
-                s.max_duration_minutes
-                s.max_people
-                s.status
-                s.created_at
+                s.id,
+                s.company_id,
+                s.title,
+                s.description,
+                s.location_id,
+                s.team_id,
+                s.start_time,
+                s.end_time,
+                s.min_duration_minutes,
+                s.max_duration_minutes,
+                s.max_people,
+                s.status,
+                s.created_at,
                 s.updated_at
             FROM
                 shifts s
                 JOIN user_shift_schedules uss ON uss.user_id = ?
+                AND uss.company_id = s.company_id
             WHERE
-                -- Consider only open shifts in the near future (e.g., next 30 days)
+                /* Consider only open shifts in the near future (e.g., next 30 days) */
                 s.status = 'open'
                 AND s.start_time BETWEEN NOW() AND NOW() + INTERVAL '30 days'
                 
-                -- Check if the shift falls within the user's availability for that day of the week.
-                -- This handles cases where availability for a day is not set (NULL).
+                /* Check if the shift falls within the user's availability for that day of the week. */
+                /* This handles cases where availability for a day is not set (NULL). */
                 AND CASE EXTRACT(ISODOW FROM s.start_time)
                     WHEN 1 THEN uss.monday_start IS NOT NULL AND s.start_time::time >= uss.monday_start AND s.end_time::time <= uss.monday_end
                     WHEN 2 THEN uss.tuesday_start IS NOT NULL AND s.start_time::time >= uss.tuesday_start AND s.end_time::time <= uss.tuesday_end
@@ -315,19 +317,27 @@ pub async fn get_user_shift_suggestions(user_id: Uuid) -> Result<Vec<Shift>, sql
                     ELSE FALSE
                 END
 
-                -- Ensure the user is not already assigned to an overlapping shift.
+                /* Ensure the user is not already assigned to an overlapping shift. */
                 AND NOT EXISTS (
                     SELECT 1
                     FROM shift_assignments sa
                     JOIN shifts assigned_shift ON sa.shift_id = assigned_shift.id
                     WHERE sa.user_id = ?
-                      AND sa.assignment_status = 'accepted' -- Consider only accepted assignments for conflicts
+                      AND sa.assignment_status = 'accepted' /* Consider only accepted assignments for conflicts */
                       AND assigned_shift.start_time < s.end_time AND assigned_shift.end_time > s.start_time
                 )
+
+                /* Skip holidays kept at the shift's location. */
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM company_holiday_on(s.company_id, s.location_id, s.start_time::DATE) h
+                    WHERE h.observed
+                )
             ORDER BY s.start_time
             LIMIT 20
         "#))
         .bind(user_id)
+        .bind(user_id)
         .fetch_all(&get_pool().await)
         .await?;
 
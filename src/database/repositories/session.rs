@@ -0,0 +1,338 @@
+use chrono::Utc;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::database::{
+    get_pool,
+    models::{CreateSessionInput, UserSession},
+    utils::sql,
+};
+
+/// Create a new session for a freshly issued refresh token
+pub async fn create_session(
+    tx: &mut Transaction<'_, Postgres>,
+    input: &CreateSessionInput,
+) -> Result<UserSession, sqlx::Error> {
+    let session = sqlx::query_as::<_, UserSession>(&sql(r#"
+        INSERT INTO
+            user_sessions (
+                user_id,
+                company_id,
+                refresh_token_hash,
+                user_agent,
+                ip_address,
+                expires_at,
+                mfa_verified
+            )
+        VALUES
+            (?, ?, ?, ?, ?, ?, ?)
+        RETURNING
+            id,
+            user_id,
+            company_id,
+            refresh_token_hash,
+            previous_refresh_token_hash,
+            user_agent,
+            ip_address,
+            expires_at,
+            last_used_at,
+            revoked_at,
+            mfa_verified,
+            created_at
+    "#))
+    .bind(input.user_id)
+    .bind(input.company_id)
+    .bind(&input.refresh_token_hash)
+    .bind(&input.user_agent)
+    .bind(&input.ip_address)
+    .bind(input.expires_at)
+    .bind(input.mfa_verified)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(session)
+}
+
+pub async fn find_by_id(session_id: Uuid) -> Result<Option<UserSession>, sqlx::Error> {
+    let session = sqlx::query_as::<_, UserSession>(&sql(r#"
+        SELECT
+            id,
+            user_id,
+            company_id,
+            refresh_token_hash,
+            previous_refresh_token_hash,
+            user_agent,
+            ip_address,
+            expires_at,
+            last_used_at,
+            revoked_at,
+            mfa_verified,
+            created_at
+        FROM
+            user_sessions
+        WHERE
+            id = ?
+    "#))
+    .bind(session_id)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(session)
+}
+
+/// Find the session whose current refresh token matches the given hash
+pub async fn find_by_refresh_token_hash(
+    token_hash: &str,
+) -> Result<Option<UserSession>, sqlx::Error> {
+    let session = sqlx::query_as::<_, UserSession>(&sql(r#"
+        SELECT
+            id,
+            user_id,
+            company_id,
+            refresh_token_hash,
+            previous_refresh_token_hash,
+            user_agent,
+            ip_address,
+            expires_at,
+            last_used_at,
+            revoked_at,
+            mfa_verified,
+            created_at
+        FROM
+            user_sessions
+        WHERE
+            refresh_token_hash = ?
+    "#))
+    .bind(token_hash)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(session)
+}
+
+/// Find the session whose already-rotated refresh token matches the given hash.
+/// A hit here means a refresh token was replayed after rotation.
+pub async fn find_by_previous_refresh_token_hash(
+    token_hash: &str,
+) -> Result<Option<UserSession>, sqlx::Error> {
+    let session = sqlx::query_as::<_, UserSession>(&sql(r#"
+        SELECT
+            id,
+            user_id,
+            company_id,
+            refresh_token_hash,
+            previous_refresh_token_hash,
+            user_agent,
+            ip_address,
+            expires_at,
+            last_used_at,
+            revoked_at,
+            mfa_verified,
+            created_at
+        FROM
+            user_sessions
+        WHERE
+            previous_refresh_token_hash = ?
+    "#))
+    .bind(token_hash)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(session)
+}
+
+/// Replace the session's refresh token, keeping the old hash for reuse detection
+pub async fn rotate_refresh_token(
+    tx: &mut Transaction<'_, Postgres>,
+    session_id: Uuid,
+    current_token_hash: &str,
+    new_token_hash: &str,
+    ip_address: &str,
+    user_agent: &str,
+) -> Result<Option<UserSession>, sqlx::Error> {
+    let now = Utc::now();
+
+    let session = sqlx::query_as::<_, UserSession>(&sql(r#"
+        UPDATE user_sessions
+        SET
+            previous_refresh_token_hash = refresh_token_hash,
+            refresh_token_hash = ?,
+            ip_address = ?,
+            user_agent = ?,
+            last_used_at = ?
+        WHERE
+            id = ?
+            AND refresh_token_hash = ?
+            AND revoked_at IS NULL
+        RETURNING
+            id,
+            user_id,
+            company_id,
+            refresh_token_hash,
+            previous_refresh_token_hash,
+            user_agent,
+            ip_address,
+            expires_at,
+            last_used_at,
+            revoked_at,
+            mfa_verified,
+            created_at
+    "#))
+    .bind(new_token_hash)
+    .bind(ip_address)
+    .bind(user_agent)
+    .bind(now)
+    .bind(session_id)
+    .bind(current_token_hash)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(session)
+}
+
+/// Point the session at a different company (used when switching companies)
+pub async fn update_session_company(
+    tx: &mut Transaction<'_, Postgres>,
+    session_id: Uuid,
+    company_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&sql(r#"
+        UPDATE user_sessions
+        SET
+            company_id = ?
+        WHERE
+            id = ?
+    "#))
+    .bind(company_id)
+    .bind(session_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Record that the session has passed a second factor check
+pub async fn mark_session_mfa_verified(
+    tx: &mut Transaction<'_, Postgres>,
+    session_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&sql(r#"
+        UPDATE user_sessions
+        SET
+            mfa_verified = TRUE
+        WHERE
+            id = ?
+    "#))
+    .bind(session_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Revoke a single session belonging to the given user
+pub async fn revoke_session(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    session_id: Uuid,
+) -> Result<Option<()>, sqlx::Error> {
+    let now = Utc::now();
+
+    let result = sqlx::query(&sql(r#"
+        UPDATE user_sessions
+        SET
+            revoked_at = ?
+        WHERE
+            id = ?
+            AND user_id = ?
+            AND revoked_at IS NULL
+    "#))
+    .bind(now)
+    .bind(session_id)
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(()))
+}
+
+/// Revoke every active session for a user, returning how many were revoked
+pub async fn revoke_all_sessions_for_user(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<u64, sqlx::Error> {
+    let now = Utc::now();
+
+    let result = sqlx::query(&sql(r#"
+        UPDATE user_sessions
+        SET
+            revoked_at = ?
+        WHERE
+            user_id = ?
+            AND revoked_at IS NULL
+    "#))
+    .bind(now)
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// List sessions that have not been revoked and have not expired
+pub async fn get_active_sessions_for_user(user_id: Uuid) -> Result<Vec<UserSession>, sqlx::Error> {
+    let now = Utc::now();
+
+    let sessions = sqlx::query_as::<_, UserSession>(&sql(r#"
+        SELECT
+            id,
+            user_id,
+            company_id,
+            refresh_token_hash,
+            previous_refresh_token_hash,
+            user_agent,
+            ip_address,
+            expires_at,
+            last_used_at,
+            revoked_at,
+            mfa_verified,
+            created_at
+        FROM
+            user_sessions
+        WHERE
+            user_id = ?
+            AND revoked_at IS NULL
+            AND expires_at > ?
+        ORDER BY
+            last_used_at DESC
+    "#))
+    .bind(user_id)
+    .bind(now)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(sessions)
+}
+
+/// Clean up sessions that expired or were revoked before the given cutoff
+pub async fn cleanup_stale_sessions(
+    tx: &mut Transaction<'_, Postgres>,
+    older_than: chrono::DateTime<Utc>,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(&sql(r#"
+        DELETE FROM user_sessions
+        WHERE
+            expires_at < ?
+            OR revoked_at < ?
+    "#))
+    .bind(older_than)
+    .bind(older_than)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(result.rows_affected())
+}
@@ -4,7 +4,7 @@ use uuid::Uuid;
 
 use crate::database::{
     get_pool,
-    models::{ShiftClaim, ShiftClaimInput},
+    models::{ScopeFilter, ShiftClaim, ShiftClaimInput},
     utils::sql,
 };
 
@@ -173,27 +173,47 @@ pub async fn find_by_company_id(company_id: Uuid) -> Result<Vec<ShiftClaim>, sql
     Ok(claims)
 }
 
-/// Get pending claims for approval (managers/admins)
-pub async fn find_pending_by_company_id(company_id: Uuid) -> Result<Vec<ShiftClaim>, sqlx::Error> {
+/// Get pending claims for approval (managers/admins), limited to `scope` when given
+pub async fn find_pending_by_company_id(
+    company_id: Uuid,
+    scope: Option<&ScopeFilter>,
+) -> Result<Vec<ShiftClaim>, sqlx::Error> {
     let claims = sqlx::query_as::<_, ShiftClaim>(&sql(r#"
         SELECT
-            id,
-            shift_id,
-            user_id,
-            status,
-            approved_by,
-            approval_notes,
-            created_at,
-            updated_at
+            sc.id,
+            sc.shift_id,
+            sc.user_id,
+            sc.status,
+            sc.actioned_by,
+            sc.action_notes,
+            sc.created_at,
+            sc.updated_at
         FROM
-            shift_claims
+            shift_claims sc
+            JOIN shifts s ON s.id = sc.shift_id
         WHERE
-            status = 'pending'
-            AND company_id = ?
+            sc.status = 'pending'
+            AND s.company_id = ?
+            AND (
+                ?
+                OR s.location_id = ANY(?)
+                OR s.team_id = ANY(?)
+            )
         ORDER BY
-            created_at ASC
+            sc.created_at ASC
     "#))
     .bind(company_id)
+    .bind(scope.is_none())
+    .bind(
+        scope
+            .map(|scope| scope.location_ids.clone())
+            .unwrap_or_default(),
+    )
+    .bind(
+        scope
+            .map(|scope| scope.team_ids.clone())
+            .unwrap_or_default(),
+    )
     .fetch_all(&get_pool().await)
     .await?;
 
@@ -225,8 +245,8 @@ pub async fn approve(
             shift_id,
             user_id,
             status,
-            approved_by,
-            approval_notes,
+            actioned_by,
+            action_notes,
             created_at,
             updated_at
     "#))
@@ -265,8 +285,8 @@ pub async fn reject(
             shift_id,
             user_id,
             status,
-            approved_by,
-            approval_notes,
+            actioned_by,
+            action_notes,
             created_at,
             updated_at
     "#))
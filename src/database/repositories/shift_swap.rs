@@ -101,8 +101,8 @@ pub async fn create_swap_request(
                 notes,
                 swap_type,
                 status,
-                approved_by,
-                approval_notes,
+                actioned_by,
+                action_notes,
                 created_at,
                 updated_at
             "#,
@@ -141,8 +141,8 @@ pub async fn get_swap_requests_for_company(
                 notes,
                 swap_type,
                 status,
-                approved_by,
-                approval_notes,
+                actioned_by,
+                action_notes,
                 created_at,
                 updated_at
             FROM
@@ -197,8 +197,8 @@ pub async fn find_swap_request_by_id(id: Uuid) -> Result<Option<ShiftSwap>, sqlx
                 notes,
                 swap_type,
                 status,
-                approved_by,
-                approval_notes,
+                actioned_by,
+                action_notes,
                 created_at,
                 updated_at
             FROM
@@ -230,8 +230,8 @@ pub async fn approve_swap(
                 shift_swaps
             SET
                 status = $1,
-                approved_by = $2,
-                approval_notes = $3,
+                actioned_by = $2,
+                action_notes = $3,
                 updated_at = $4
             WHERE
                 id = $5
@@ -244,8 +244,8 @@ pub async fn approve_swap(
                 notes,
                 swap_type,
                 status,
-                approved_by,
-                approval_notes,
+                actioned_by,
+                action_notes,
                 created_at,
                 updated_at
             "#,
@@ -277,8 +277,8 @@ pub async fn deny_swap(
                 shift_swaps
             SET
                 status = $1,
-                approved_by = $2,
-                approval_notes = $3,
+                actioned_by = $2,
+                action_notes = $3,
                 updated_at = $4
             WHERE
                 id = $5
@@ -291,8 +291,8 @@ pub async fn deny_swap(
                 notes,
                 swap_type,
                 status,
-                approved_by,
-                approval_notes,
+                actioned_by,
+                action_notes,
                 created_at,
                 updated_at
             "#,
@@ -334,8 +334,8 @@ pub async fn cancel_swap(
                 notes,
                 swap_type,
                 status,
-                approved_by,
-                approval_notes,
+                actioned_by,
+                action_notes,
                 created_at,
                 updated_at
             "#,
@@ -375,8 +375,8 @@ pub async fn complete_swap(
                 notes,
                 swap_type,
                 status,
-                approved_by,
-                approval_notes,
+                actioned_by,
+                action_notes,
                 created_at,
                 updated_at
             "#,
@@ -0,0 +1,443 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::database::{
+    get_pool,
+    models::{
+        CompanySsoConfig, CompanySsoDomain, SsoConfigInput, SsoEndpoints, SsoLoginState,
+        UserIdentity,
+    },
+    utils::sql,
+};
+
+pub async fn find_config_by_company_id(
+    company_id: Uuid,
+) -> Result<Option<CompanySsoConfig>, sqlx::Error> {
+    let config = sqlx::query_as::<_, CompanySsoConfig>(&sql(r#"
+        SELECT
+            id,
+            company_id,
+            issuer,
+            client_id,
+            client_secret,
+            allowed_domains,
+            default_role,
+            enabled,
+            authorization_endpoint,
+            token_endpoint,
+            jwks_uri,
+            created_at,
+            updated_at
+        FROM
+            company_sso_configs
+        WHERE
+            company_id = ?
+    "#))
+    .bind(company_id)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(config)
+}
+
+/// Create or replace the company's provider configuration
+pub async fn upsert_config(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    input: &SsoConfigInput,
+    client_secret: &str,
+    endpoints: &SsoEndpoints,
+) -> Result<CompanySsoConfig, sqlx::Error> {
+    let config = sqlx::query_as::<_, CompanySsoConfig>(&sql(r#"
+        INSERT INTO
+            company_sso_configs (
+                company_id,
+                issuer,
+                client_id,
+                client_secret,
+                allowed_domains,
+                default_role,
+                enabled,
+                authorization_endpoint,
+                token_endpoint,
+                jwks_uri
+            )
+        VALUES
+            (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT (company_id) DO UPDATE
+        SET
+            issuer = EXCLUDED.issuer,
+            client_id = EXCLUDED.client_id,
+            client_secret = EXCLUDED.client_secret,
+            allowed_domains = EXCLUDED.allowed_domains,
+            default_role = EXCLUDED.default_role,
+            enabled = EXCLUDED.enabled,
+            authorization_endpoint = EXCLUDED.authorization_endpoint,
+            token_endpoint = EXCLUDED.token_endpoint,
+            jwks_uri = EXCLUDED.jwks_uri,
+            updated_at = NOW()
+        RETURNING
+            id,
+            company_id,
+            issuer,
+            client_id,
+            client_secret,
+            allowed_domains,
+            default_role,
+            enabled,
+            authorization_endpoint,
+            token_endpoint,
+            jwks_uri,
+            created_at,
+            updated_at
+    "#))
+    .bind(company_id)
+    .bind(&input.issuer)
+    .bind(&input.client_id)
+    .bind(client_secret)
+    .bind(&input.allowed_domains)
+    .bind(input.default_role.clone().unwrap_or_default())
+    .bind(input.enabled.unwrap_or(true))
+    .bind(&endpoints.authorization_endpoint)
+    .bind(&endpoints.token_endpoint)
+    .bind(&endpoints.jwks_uri)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(config)
+}
+
+pub async fn delete_config(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+) -> Result<Option<()>, sqlx::Error> {
+    let result = sqlx::query(&sql(r#"
+        DELETE FROM company_sso_configs
+        WHERE
+            company_id = ?
+    "#))
+    .bind(company_id)
+    .execute(&mut **tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        Ok(None)
+    } else {
+        Ok(Some(()))
+    }
+}
+
+pub async fn create_login_state(
+    tx: &mut Transaction<'_, Postgres>,
+    state: &str,
+    company_id: Uuid,
+    code_verifier: &str,
+    nonce: &str,
+    redirect_uri: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<SsoLoginState, sqlx::Error> {
+    let login_state = sqlx::query_as::<_, SsoLoginState>(&sql(r#"
+        INSERT INTO
+            sso_login_states (
+                state,
+                company_id,
+                code_verifier,
+                nonce,
+                redirect_uri,
+                expires_at
+            )
+        VALUES
+            (?, ?, ?, ?, ?, ?)
+        RETURNING
+            state,
+            company_id,
+            code_verifier,
+            nonce,
+            redirect_uri,
+            expires_at,
+            created_at
+    "#))
+    .bind(state)
+    .bind(company_id)
+    .bind(code_verifier)
+    .bind(nonce)
+    .bind(redirect_uri)
+    .bind(expires_at)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(login_state)
+}
+
+/// Remove and return a pending login so each state can only be used once
+pub async fn take_login_state(
+    tx: &mut Transaction<'_, Postgres>,
+    state: &str,
+) -> Result<Option<SsoLoginState>, sqlx::Error> {
+    let login_state = sqlx::query_as::<_, SsoLoginState>(&sql(r#"
+        DELETE FROM sso_login_states
+        WHERE
+            state = ?
+        RETURNING
+            state,
+            company_id,
+            code_verifier,
+            nonce,
+            redirect_uri,
+            expires_at,
+            created_at
+    "#))
+    .bind(state)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(login_state)
+}
+
+pub async fn cleanup_expired_login_states(
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(&sql(r#"
+        DELETE FROM sso_login_states
+        WHERE
+            expires_at < ?
+    "#))
+    .bind(Utc::now())
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn find_identity(
+    issuer: &str,
+    subject: &str,
+) -> Result<Option<UserIdentity>, sqlx::Error> {
+    let identity = sqlx::query_as::<_, UserIdentity>(&sql(r#"
+        SELECT
+            id,
+            user_id,
+            company_id,
+            issuer,
+            subject,
+            email,
+            last_login_at,
+            created_at
+        FROM
+            user_identities
+        WHERE
+            issuer = ?
+            AND subject = ?
+    "#))
+    .bind(issuer)
+    .bind(subject)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(identity)
+}
+
+pub async fn create_identity(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    company_id: Uuid,
+    issuer: &str,
+    subject: &str,
+    email: &str,
+) -> Result<UserIdentity, sqlx::Error> {
+    let identity = sqlx::query_as::<_, UserIdentity>(&sql(r#"
+        INSERT INTO
+            user_identities (
+                user_id,
+                company_id,
+                issuer,
+                subject,
+                email,
+                last_login_at
+            )
+        VALUES
+            (?, ?, ?, ?, ?, ?)
+        RETURNING
+            id,
+            user_id,
+            company_id,
+            issuer,
+            subject,
+            email,
+            last_login_at,
+            created_at
+    "#))
+    .bind(user_id)
+    .bind(company_id)
+    .bind(issuer)
+    .bind(subject)
+    .bind(email)
+    .bind(Utc::now())
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(identity)
+}
+
+pub async fn touch_identity(
+    tx: &mut Transaction<'_, Postgres>,
+    identity_id: Uuid,
+    email: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&sql(r#"
+        UPDATE user_identities
+        SET
+            email = ?,
+            last_login_at = ?
+        WHERE
+            id = ?
+    "#))
+    .bind(email)
+    .bind(Utc::now())
+    .bind(identity_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+const DOMAIN_COLUMNS: &str = r#"
+    id,
+    company_id,
+    domain,
+    verification_token,
+    verified_at,
+    created_at
+"#;
+
+pub async fn get_domains(company_id: Uuid) -> Result<Vec<CompanySsoDomain>, sqlx::Error> {
+    let query = format!(
+        r#"
+        SELECT
+            {DOMAIN_COLUMNS}
+        FROM
+            company_sso_domains
+        WHERE
+            company_id = ?
+        ORDER BY
+            domain
+    "#
+    );
+    let domains = sqlx::query_as::<_, CompanySsoDomain>(&sql(&query))
+        .bind(company_id)
+        .fetch_all(&get_pool().await)
+        .await?;
+
+    Ok(domains)
+}
+
+pub async fn find_domain(
+    company_id: Uuid,
+    domain_id: Uuid,
+) -> Result<Option<CompanySsoDomain>, sqlx::Error> {
+    let query = format!(
+        r#"
+        SELECT
+            {DOMAIN_COLUMNS}
+        FROM
+            company_sso_domains
+        WHERE
+            id = ?
+            AND company_id = ?
+    "#
+    );
+    let domain = sqlx::query_as::<_, CompanySsoDomain>(&sql(&query))
+        .bind(domain_id)
+        .bind(company_id)
+        .fetch_optional(&get_pool().await)
+        .await?;
+
+    Ok(domain)
+}
+
+/// The company that verified a domain, if any
+pub async fn find_verified_company(domain: &str) -> Result<Option<Uuid>, sqlx::Error> {
+    let company_id = sqlx::query_scalar::<_, Uuid>(&sql(r#"
+        SELECT
+            company_id
+        FROM
+            company_sso_domains
+        WHERE
+            domain = LOWER(?)
+            AND verified_at IS NOT NULL
+    "#))
+    .bind(domain)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(company_id)
+}
+
+pub async fn create_domain(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    domain: &str,
+    verification_token: &str,
+) -> Result<CompanySsoDomain, sqlx::Error> {
+    let query = format!(
+        r#"
+        INSERT INTO
+            company_sso_domains (company_id, domain, verification_token)
+        VALUES
+            (?, ?, ?)
+        RETURNING
+            {DOMAIN_COLUMNS}
+    "#
+    );
+    let domain = sqlx::query_as::<_, CompanySsoDomain>(&sql(&query))
+        .bind(company_id)
+        .bind(domain)
+        .bind(verification_token)
+        .fetch_one(&mut **tx)
+        .await?;
+
+    Ok(domain)
+}
+
+pub async fn mark_domain_verified(
+    tx: &mut Transaction<'_, Postgres>,
+    domain_id: Uuid,
+) -> Result<CompanySsoDomain, sqlx::Error> {
+    let query = format!(
+        r#"
+        UPDATE company_sso_domains
+        SET
+            verified_at = NOW()
+        WHERE
+            id = ?
+        RETURNING
+            {DOMAIN_COLUMNS}
+    "#
+    );
+    let domain = sqlx::query_as::<_, CompanySsoDomain>(&sql(&query))
+        .bind(domain_id)
+        .fetch_one(&mut **tx)
+        .await?;
+
+    Ok(domain)
+}
+
+pub async fn delete_domain(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    domain_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(&sql(r#"
+        DELETE FROM company_sso_domains
+        WHERE
+            id = ?
+            AND company_id = ?
+    "#))
+    .bind(domain_id)
+    .bind(company_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
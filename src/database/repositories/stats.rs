@@ -1,205 +1,819 @@
-use chrono::{DateTime, Utc};
+use chrono::NaiveDate;
+use sqlx::{Postgres, postgres::PgArguments, query::QueryAs};
 use uuid::Uuid;
 
 use crate::database::{
     get_pool,
-    models::{DashboardStats, ShiftStats, TimeOffStats},
+    models::{
+        CoverageBucket, DashboardStats, EmployeeReportRow, FairnessRow, ShiftStats, StatsFilter,
+        TimeOffStats,
+    },
     utils::sql,
 };
 
-// Simple stats that just return basic counts
-pub async fn get_dashboard_stats_for_user(
-    user_id: Option<Uuid>,
-    _start_date: Option<DateTime<Utc>>,
-    _end_date: Option<DateTime<Utc>>,
-) -> Result<DashboardStats, sqlx::Error> {
-    // For now, return basic counts
-    let total_shifts: i64 = sqlx::query_scalar(&sql(r#"
+/// Hours worked in a week past which they count as overtime
+const OVERTIME_WEEKLY_HOURS: f64 = 40.0;
+/// Shifts overlapping these local hours are night shifts
+const NIGHT_START_HOUR: i32 = 22;
+const NIGHT_END_HOUR: i32 = 5;
+/// Assignments made this close to the start of a shift are short notice
+const SHORT_NOTICE_HOURS: i32 = 48;
+/// Clocking in later than this after a shift starts counts as a late arrival
+const LATE_GRACE_MINUTES: i32 = 5;
+
+/// Who works a shift: accepted assignments and approved claims
+const FILLED_SLOTS: &str = r#"
+    filled AS (
         SELECT
-            COUNT(s.*)
+            shift_id,
+            user_id
         FROM
-            shifts s
-            JOIN user_company uc ON s.company_id = uc.company_id
+            shift_assignments
         WHERE
-            uc.user_id = ?
-    "#))
-    .bind(user_id)
-    .fetch_one(&get_pool().await)
-    .await?;
+            assignment_status = 'accepted'
+        UNION
+        SELECT
+            shift_id,
+            user_id
+        FROM
+            shift_claims
+        WHERE
+            status = 'approved'
+    )
+"#;
 
-    let now = Utc::now();
-    let upcoming_shifts: i64 = sqlx::query_scalar(&sql(r#"
-            SELECT
-                COUNT(s.*)
-            FROM
-                shifts s
-                JOIN user_company uc ON s.company_id = uc.company_id
-            WHERE
-                s.start_time > ?
-                AND uc.user_id = ?
-        "#))
-    .bind(now)
-    .bind(user_id)
-    .fetch_one(&get_pool().await)
-    .await?;
+/// Shifts `s` of the company starting in the period, within the location, team and manager
+/// scope of the filter. Bound by `bind_shift_scope`.
+const SHIFT_SCOPE: &str = r#"
+    s.company_id = ?
+    AND s.start_time >= ?
+    AND s.start_time < ?
+    AND (?::UUID IS NULL OR s.location_id = ?)
+    AND (?::UUID IS NULL OR s.team_id = ?)
+    AND (
+        ?
+        OR s.location_id = ANY(?)
+        OR s.team_id = ANY(?)
+    )
+"#;
 
-    let pending_time_off_requests: i64 = sqlx::query_scalar(&sql(r#"
-            SELECT
-                COUNT(*)
-            FROM
-                time_off_requests
-            WHERE
-                status = 'pending'
-        "#))
-    .fetch_one(&get_pool().await)
-    .await?;
+fn bind_shift_scope<'q, O>(
+    query: QueryAs<'q, Postgres, O, PgArguments>,
+    filter: &'q StatsFilter,
+) -> QueryAs<'q, Postgres, O, PgArguments> {
+    query
+        .bind(filter.company_id)
+        .bind(filter.start_date)
+        .bind(filter.end_date)
+        .bind(filter.location_id)
+        .bind(filter.location_id)
+        .bind(filter.team_id)
+        .bind(filter.team_id)
+        .bind(filter.scope.is_none())
+        .bind(
+            filter
+                .scope
+                .as_ref()
+                .map(|scope| scope.location_ids.clone())
+                .unwrap_or_default(),
+        )
+        .bind(
+            filter
+                .scope
+                .as_ref()
+                .map(|scope| scope.team_ids.clone())
+                .unwrap_or_default(),
+        )
+}
 
-    let pending_swap_requests: i64 = sqlx::query_scalar(&sql(r#"
-            SELECT
-                COUNT(*)
-            FROM
-                shift_swaps
-            WHERE
-                status = 'pending'
-        "#))
-    .fetch_one(&get_pool().await)
-    .await?;
+/// The `members` of the company in the filter's location, team and manager scope. Bound by
+/// `bind_report_members`.
+const REPORT_MEMBERS: &str = r#"
+    members AS (
+        SELECT
+            u.id,
+            u.name,
+            u.email
+        FROM
+            user_company uc
+            JOIN users u ON u.id = uc.user_id
+        WHERE
+            uc.company_id = ?
+            AND (
+                ?::UUID IS NULL
+                OR u.id IN (
+                    SELECT
+                        tm.user_id
+                    FROM
+                        team_members tm
+                        JOIN teams tt ON tt.id = tm.team_id
+                    WHERE
+                        tt.location_id = ?
+                )
+            )
+            AND (
+                ?::UUID IS NULL
+                OR u.id IN (
+                    SELECT
+                        user_id
+                    FROM
+                        team_members
+                    WHERE
+                        team_id = ?
+                )
+            )
+            AND (
+                ?
+                OR u.id = ANY(?)
+            )
+    )
+"#;
 
-    let approved_time_off: i64 = sqlx::query_scalar(&sql(r#"
-            SELECT
-                COUNT(*)
-            FROM
-                time_off_requests
-            WHERE
-                status = 'approved'
-        "#))
-    .fetch_one(&get_pool().await)
-    .await?;
+fn bind_report_members<'q, O>(
+    query: QueryAs<'q, Postgres, O, PgArguments>,
+    filter: &'q StatsFilter,
+) -> QueryAs<'q, Postgres, O, PgArguments> {
+    query
+        .bind(filter.company_id)
+        .bind(filter.location_id)
+        .bind(filter.location_id)
+        .bind(filter.team_id)
+        .bind(filter.team_id)
+        .bind(filter.scope_user_ids.is_none())
+        .bind(filter.scope_user_ids.clone().unwrap_or_default())
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct ShiftTotals {
+    total_shifts: i64,
+    upcoming_shifts: i64,
+    assigned_shifts: i64,
+    unassigned_shifts: i64,
+    completed_shifts: i64,
+    cancelled_shifts: i64,
+    total_hours: f64,
+    required_slots: i64,
+    filled_slots: i64,
+}
 
-    // Calculate total hours from shifts (convert seconds to hours)
-    let total_hours: f64 = sqlx::query_scalar(&sql(r#"
+/// Count the filtered shifts by status, and add up their hours and slots. Cancelled shifts
+/// count toward neither hours nor slots. A shift needs `max_people` workers, or one when unset.
+async fn get_shift_totals(filter: &StatsFilter) -> Result<ShiftTotals, sqlx::Error> {
+    let query = format!(
+        r#"
+        WITH
+            {FILLED_SLOTS},
+            scoped AS (
+                SELECT
+                    s.id,
+                    s.status,
+                    s.start_time,
+                    s.end_time,
+                    COALESCE(s.max_people, 1) AS required
+                FROM
+                    shifts s
+                WHERE
+                    {SHIFT_SCOPE}
+                    AND (
+                        ?::UUID IS NULL
+                        OR s.id IN (
+                            SELECT
+                                shift_id
+                            FROM
+                                filled
+                            WHERE
+                                user_id = ?
+                        )
+                    )
+            )
         SELECT
-            COALESCE(SUM(EXTRACT(EPOCH FROM (end_time - start_time)) / 3600), 0)::DOUBLE PRECISION
+            COUNT(*) AS total_shifts,
+            COUNT(*) FILTER (
+                WHERE
+                    sc.status <> 'cancelled'
+                    AND sc.start_time > NOW()
+            ) AS upcoming_shifts,
+            COUNT(*) FILTER (
+                WHERE
+                    sc.status IN ('assigned', 'completed')
+            ) AS assigned_shifts,
+            COUNT(*) FILTER (
+                WHERE
+                    sc.status = 'open'
+            ) AS unassigned_shifts,
+            COUNT(*) FILTER (
+                WHERE
+                    sc.status = 'completed'
+            ) AS completed_shifts,
+            COUNT(*) FILTER (
+                WHERE
+                    sc.status = 'cancelled'
+            ) AS cancelled_shifts,
+            COALESCE(
+                SUM(EXTRACT(EPOCH FROM (sc.end_time - sc.start_time)) / 3600) FILTER (
+                    WHERE
+                        sc.status <> 'cancelled'
+                ),
+                0
+            )::DOUBLE PRECISION AS total_hours,
+            COALESCE(
+                SUM(sc.required) FILTER (
+                    WHERE
+                        sc.status <> 'cancelled'
+                ),
+                0
+            )::BIGINT AS required_slots,
+            COALESCE(
+                SUM(LEAST(f.workers, sc.required)) FILTER (
+                    WHERE
+                        sc.status <> 'cancelled'
+                ),
+                0
+            )::BIGINT AS filled_slots
         FROM
-            shifts
-    "#))
-    .fetch_one(&get_pool().await)
-    .await?;
+            scoped sc
+            LEFT JOIN LATERAL (
+                SELECT
+                    COUNT(DISTINCT filled.user_id) AS workers
+                FROM
+                    filled
+                WHERE
+                    filled.shift_id = sc.id
+            ) f ON TRUE
+    "#
+    );
+
+    let totals = bind_shift_scope(sqlx::query_as::<_, ShiftTotals>(&sql(&query)), filter)
+        .bind(filter.user_id)
+        .bind(filter.user_id)
+        .fetch_one(&get_pool().await)
+        .await?;
+
+    Ok(totals)
+}
+
+/// Swaps still waiting on a response or approval, for the filtered shifts
+async fn get_pending_swap_count(filter: &StatsFilter) -> Result<i64, sqlx::Error> {
+    let query = format!(
+        r#"
+        SELECT
+            COUNT(*)
+        FROM
+            shift_swaps sw
+            JOIN shifts s ON s.id = sw.original_shift_id
+        WHERE
+            {SHIFT_SCOPE}
+            AND sw.status IN ('open', 'pending')
+            AND (?::UUID IS NULL OR sw.requesting_user_id = ?)
+    "#
+    );
+
+    let (count,) = bind_shift_scope(sqlx::query_as::<_, (i64,)>(&sql(&query)), filter)
+        .bind(filter.user_id)
+        .bind(filter.user_id)
+        .fetch_one(&get_pool().await)
+        .await?;
 
-    // Calculate team coverage as percentage of shifts that are assigned (not open)
-    let assigned_shifts_count: i64 = sqlx::query_scalar(&sql(r#"
+    Ok(count)
+}
+
+/// Time off requests overlapping the period by status. A location or team narrows them to
+/// the members of its teams.
+pub async fn get_time_off_stats(filter: &StatsFilter) -> Result<TimeOffStats, sqlx::Error> {
+    let (total_requests, approved_requests, denied_requests, pending_requests, cancelled_requests) =
+        sqlx::query_as::<_, (i64, i64, i64, i64, i64)>(&sql(r#"
             SELECT
-                COUNT(*)
+                COUNT(*),
+                COUNT(*) FILTER (
+                    WHERE
+                        t.status = 'approved'
+                ),
+                COUNT(*) FILTER (
+                    WHERE
+                        t.status = 'denied'
+                ),
+                COUNT(*) FILTER (
+                    WHERE
+                        t.status = 'pending'
+                ),
+                COUNT(*) FILTER (
+                    WHERE
+                        t.status = 'cancelled'
+                )
             FROM
-                shifts
+                time_off_requests t
             WHERE
-                status != 'open'
+                t.company_id = ?
+                AND t.start_date < ?
+                AND t.end_date > ?
+                AND (?::UUID IS NULL OR t.user_id = ?)
+                AND (
+                    ?::UUID IS NULL
+                    OR t.user_id IN (
+                        SELECT
+                            tm.user_id
+                        FROM
+                            team_members tm
+                            JOIN teams tt ON tt.id = tm.team_id
+                        WHERE
+                            tt.location_id = ?
+                    )
+                )
+                AND (
+                    ?::UUID IS NULL
+                    OR t.user_id IN (
+                        SELECT
+                            user_id
+                        FROM
+                            team_members
+                        WHERE
+                            team_id = ?
+                    )
+                )
+                AND (
+                    ?
+                    OR t.user_id = ANY(?)
+                )
         "#))
-    .fetch_one(&get_pool().await)
-    .await?;
+        .bind(filter.company_id)
+        .bind(filter.end_date)
+        .bind(filter.start_date)
+        .bind(filter.user_id)
+        .bind(filter.user_id)
+        .bind(filter.location_id)
+        .bind(filter.location_id)
+        .bind(filter.team_id)
+        .bind(filter.team_id)
+        .bind(filter.scope_user_ids.is_none())
+        .bind(filter.scope_user_ids.clone().unwrap_or_default())
+        .fetch_one(&get_pool().await)
+        .await?;
 
-    let team_coverage = if total_shifts > 0 {
-        (assigned_shifts_count as f64 / total_shifts as f64) * 100.0
+    Ok(TimeOffStats {
+        total_requests,
+        approved_requests,
+        denied_requests,
+        pending_requests,
+        cancelled_requests,
+    })
+}
+
+pub async fn get_dashboard_stats(filter: &StatsFilter) -> Result<DashboardStats, sqlx::Error> {
+    let shifts = get_shift_totals(filter).await?;
+    let time_off = get_time_off_stats(filter).await?;
+    let pending_swap_requests = get_pending_swap_count(filter).await?;
+
+    let team_coverage = if shifts.required_slots > 0 {
+        shifts.filled_slots as f64 / shifts.required_slots as f64 * 100.0
     } else {
         0.0
     };
 
     Ok(DashboardStats {
-        total_shifts,
-        upcoming_shifts,
-        pending_time_off_requests,
+        total_shifts: shifts.total_shifts,
+        upcoming_shifts: shifts.upcoming_shifts,
+        pending_time_off_requests: time_off.pending_requests,
         pending_swap_requests,
-        approved_time_off,
-        total_hours,
+        approved_time_off: time_off.approved_requests,
+        total_hours: shifts.total_hours,
         team_coverage,
     })
 }
 
-pub async fn get_dashboard_stats_for_company(
-    _company_id: Uuid,
-    _start_date: Option<DateTime<Utc>>,
-    _end_date: Option<DateTime<Utc>>,
-) -> Result<DashboardStats, sqlx::Error> {
-    // Implement the logic to fetch dashboard stats for a specific company
-    todo!()
-}
+pub async fn get_shift_stats(filter: &StatsFilter) -> Result<ShiftStats, sqlx::Error> {
+    let shifts = get_shift_totals(filter).await?;
 
-// Get shift-specific statistics
-pub async fn get_shift_stats(
-    _user_id: Option<Uuid>,
-    _start_date: Option<DateTime<Utc>>,
-    _end_date: Option<DateTime<Utc>>,
-) -> Result<ShiftStats, sqlx::Error> {
-    let total_shifts: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM shifts")
-        .fetch_one(&get_pool().await)
-        .await?;
+    Ok(ShiftStats {
+        total_shifts: shifts.total_shifts,
+        assigned_shifts: shifts.assigned_shifts,
+        unassigned_shifts: shifts.unassigned_shifts,
+        completed_shifts: shifts.completed_shifts,
+        cancelled_shifts: shifts.cancelled_shifts,
+        required_slots: shifts.required_slots,
+        filled_slots: shifts.filled_slots,
+    })
+}
 
-    // Count shifts that are assigned (not open status)
-    let assigned_shifts: i64 = sqlx::query_scalar(
-        "SELECT COUNT(*) FROM shifts WHERE status IN ('assigned', 'confirmed', 'completed')",
-    )
-    .fetch_one(&get_pool().await)
-    .await?;
+/// Per-employee figures for the members of the company in the filter's location, team and
+/// manager scope. Hours and arrivals come from the shifts they work that start in the period
+/// and were not cancelled; overtime adds up the hours past the limit in each week of it.
+pub async fn get_employee_report(
+    filter: &StatsFilter,
+) -> Result<Vec<EmployeeReportRow>, sqlx::Error> {
+    let query = format!(
+        r#"
+        WITH
+            {FILLED_SLOTS},
+            {REPORT_MEMBERS},
+            worked AS (
+                SELECT
+                    f.user_id,
+                    s.start_time,
+                    s.end_time,
+                    a.clock_in_at,
+                    a.clock_out_at
+                FROM
+                    filled f
+                    JOIN shifts s ON s.id = f.shift_id
+                    LEFT JOIN shift_attendance a ON a.shift_id = s.id
+                    AND a.user_id = f.user_id
+                WHERE
+                    {SHIFT_SCOPE}
+                    AND s.status <> 'cancelled'
+            ),
+            weekly AS (
+                SELECT
+                    user_id,
+                    SUM(EXTRACT(EPOCH FROM (clock_out_at - clock_in_at)) / 3600) AS hours
+                FROM
+                    worked
+                WHERE
+                    clock_out_at IS NOT NULL
+                GROUP BY
+                    user_id,
+                    DATE_TRUNC('week', clock_in_at)
+            ),
+            dropped AS (
+                SELECT
+                    sw.requesting_user_id AS user_id,
+                    COUNT(*) AS shifts
+                FROM
+                    shift_swaps sw
+                    JOIN shifts s ON s.id = sw.original_shift_id
+                WHERE
+                    {SHIFT_SCOPE}
+                    AND sw.status IN ('approved', 'completed')
+                GROUP BY
+                    sw.requesting_user_id
+            ),
+            time_off AS (
+                SELECT
+                    t.user_id,
+                    COUNT(*) AS requests,
+                    SUM(
+                        LEAST(t.end_date, ?::TIMESTAMPTZ - INTERVAL '1 microsecond')::DATE
+                        - GREATEST(t.start_date, ?::TIMESTAMPTZ)::DATE
+                        + 1
+                    ) AS days
+                FROM
+                    time_off_requests t
+                WHERE
+                    t.company_id = ?
+                    AND t.status = 'approved'
+                    AND t.start_date < ?
+                    AND t.end_date > ?
+                GROUP BY
+                    t.user_id
+            )
+        SELECT
+            m.id AS user_id,
+            m.name,
+            m.email,
+            COALESCE(w.scheduled_shifts, 0) AS scheduled_shifts,
+            COALESCE(w.scheduled_hours, 0)::DOUBLE PRECISION AS scheduled_hours,
+            COALESCE(w.worked_hours, 0)::DOUBLE PRECISION AS worked_hours,
+            COALESCE(
+                (
+                    SELECT
+                        SUM(GREATEST(wk.hours - ?, 0))
+                    FROM
+                        weekly wk
+                    WHERE
+                        wk.user_id = m.id
+                ),
+                0
+            )::DOUBLE PRECISION AS overtime_hours,
+            COALESCE(w.late_arrivals, 0) AS late_arrivals,
+            COALESCE(w.no_shows, 0) AS no_shows,
+            COALESCE(d.shifts, 0) AS dropped_shifts,
+            COALESCE(t.requests, 0) AS time_off_requests,
+            COALESCE(t.days, 0)::BIGINT AS time_off_days
+        FROM
+            members m
+            LEFT JOIN (
+                SELECT
+                    user_id,
+                    COUNT(*) AS scheduled_shifts,
+                    SUM(EXTRACT(EPOCH FROM (end_time - start_time)) / 3600) AS scheduled_hours,
+                    SUM(EXTRACT(EPOCH FROM (clock_out_at - clock_in_at)) / 3600) AS worked_hours,
+                    COUNT(*) FILTER (
+                        WHERE
+                            clock_in_at > start_time + MAKE_INTERVAL(mins => ?)
+                    ) AS late_arrivals,
+                    COUNT(*) FILTER (
+                        WHERE
+                            clock_in_at IS NULL
+                            AND end_time < NOW()
+                            AND NOT EXISTS (
+                                SELECT
+                                    1
+                                FROM
+                                    time_off_requests off
+                                WHERE
+                                    off.user_id = worked.user_id
+                                    AND off.status = 'approved'
+                                    AND off.start_date < worked.end_time
+                                    AND off.end_date > worked.start_time
+                            )
+                    ) AS no_shows
+                FROM
+                    worked
+                GROUP BY
+                    user_id
+            ) w ON w.user_id = m.id
+            LEFT JOIN dropped d ON d.user_id = m.id
+            LEFT JOIN time_off t ON t.user_id = m.id
+        ORDER BY
+            m.name,
+            m.id
+    "#
+    );
 
-    let unassigned_shifts: i64 =
-        sqlx::query_scalar("SELECT COUNT(*) FROM shifts WHERE status = 'open'")
-            .fetch_one(&get_pool().await)
-            .await?;
+    let query = sql(&query);
+    let members = bind_report_members(sqlx::query_as::<_, EmployeeReportRow>(&query), filter);
+    let rows = bind_shift_scope(bind_shift_scope(members, filter), filter)
+        .bind(filter.end_date)
+        .bind(filter.start_date)
+        .bind(filter.company_id)
+        .bind(filter.end_date)
+        .bind(filter.start_date)
+        .bind(OVERTIME_WEEKLY_HOURS)
+        .bind(LATE_GRACE_MINUTES)
+        .fetch_all(&get_pool().await)
+        .await?;
 
-    let completed_shifts: i64 =
-        sqlx::query_scalar("SELECT COUNT(*) FROM shifts WHERE status = 'completed'")
-            .fetch_one(&get_pool().await)
-            .await?;
+    Ok(rows)
+}
 
-    let cancelled_shifts: i64 =
-        sqlx::query_scalar("SELECT COUNT(*) FROM shifts WHERE status = 'cancelled'")
-            .fetch_one(&get_pool().await)
-            .await?;
+/// The teams, or the locations of the teams, the users belong to in the company as
+/// (user, group, group name)
+pub async fn get_report_groups(
+    company_id: Uuid,
+    user_ids: &[Uuid],
+    by_location: bool,
+) -> Result<Vec<(Uuid, Uuid, String)>, sqlx::Error> {
+    let groups = sqlx::query_as::<_, (Uuid, Uuid, String)>(&sql(r#"
+        SELECT DISTINCT
+            tm.user_id,
+            CASE
+                WHEN ? THEN l.id
+                ELSE t.id
+            END,
+            CASE
+                WHEN ? THEN l.name
+                ELSE t.name
+            END
+        FROM
+            team_members tm
+            JOIN teams t ON t.id = tm.team_id
+            JOIN locations l ON l.id = t.location_id
+        WHERE
+            l.company_id = ?
+            AND tm.user_id = ANY(?)
+    "#))
+    .bind(by_location)
+    .bind(by_location)
+    .bind(company_id)
+    .bind(user_ids)
+    .fetch_all(&get_pool().await)
+    .await?;
 
-    Ok(ShiftStats {
-        total_shifts,
-        assigned_shifts,
-        unassigned_shifts,
-        completed_shifts,
-        cancelled_shifts,
-    })
+    Ok(groups)
 }
 
-// Get time-off statistics
-pub async fn get_time_off_stats(
-    _user_id: Option<Uuid>,
-    _start_date: Option<DateTime<Utc>>,
-    _end_date: Option<DateTime<Utc>>,
-) -> Result<TimeOffStats, sqlx::Error> {
-    let total_requests: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM time_off_requests")
-        .fetch_one(&get_pool().await)
-        .await?;
+/// Headcount per interval of the period, which is widened to whole intervals. Every shift
+/// that was not cancelled counts toward each interval it overlaps, needing `max_people` (or
+/// one) workers and bringing those who work it.
+pub async fn get_coverage_buckets(
+    filter: &StatsFilter,
+    interval_minutes: i32,
+) -> Result<Vec<CoverageBucket>, sqlx::Error> {
+    let query = format!(
+        r#"
+        WITH
+            {FILLED_SLOTS},
+            buckets AS (
+                SELECT
+                    b AS bucket_start,
+                    b + MAKE_INTERVAL(mins => ?) AS bucket_end
+                FROM
+                    GENERATE_SERIES(
+                        DATE_BIN(MAKE_INTERVAL(mins => ?), ?::TIMESTAMPTZ, TIMESTAMPTZ '2000-01-01'),
+                        ?::TIMESTAMPTZ - INTERVAL '1 microsecond',
+                        MAKE_INTERVAL(mins => ?)
+                    ) b
+            ),
+            scoped AS (
+                SELECT
+                    s.id,
+                    s.start_time,
+                    s.end_time,
+                    COALESCE(s.max_people, 1) AS required,
+                    (
+                        SELECT
+                            COUNT(DISTINCT f.user_id)
+                        FROM
+                            filled f
+                        WHERE
+                            f.shift_id = s.id
+                    ) AS assigned
+                FROM
+                    shifts s
+                WHERE
+                    s.company_id = ?
+                    AND s.status <> 'cancelled'
+                    AND s.start_time < ?
+                    AND s.end_time > ?
+                    AND s.location_id = ?
+                    AND (?::UUID IS NULL OR s.team_id = ?)
+                    AND (
+                        ?
+                        OR s.location_id = ANY(?)
+                        OR s.team_id = ANY(?)
+                    )
+            ),
+            totals AS (
+                SELECT
+                    b.bucket_start,
+                    b.bucket_end,
+                    COUNT(sc.id) AS shifts,
+                    COALESCE(SUM(sc.required), 0)::BIGINT AS required,
+                    COALESCE(SUM(sc.assigned), 0)::BIGINT AS assigned
+                FROM
+                    buckets b
+                    LEFT JOIN scoped sc ON sc.start_time < b.bucket_end
+                    AND sc.end_time > b.bucket_start
+                GROUP BY
+                    b.bucket_start,
+                    b.bucket_end
+            )
+        SELECT
+            bucket_start,
+            bucket_end,
+            shifts,
+            required,
+            assigned,
+            CASE
+                WHEN shifts = 0 THEN 'unscheduled'
+                WHEN assigned < required THEN 'understaffed'
+                WHEN assigned > required THEN 'overstaffed'
+                ELSE 'covered'
+            END AS status
+        FROM
+            totals
+        ORDER BY
+            bucket_start
+    "#
+    );
 
-    let approved_requests: i64 =
-        sqlx::query_scalar("SELECT COUNT(*) FROM time_off_requests WHERE status = 'approved'")
-            .fetch_one(&get_pool().await)
-            .await?;
+    let buckets = sqlx::query_as::<_, CoverageBucket>(&sql(&query))
+        .bind(interval_minutes)
+        .bind(interval_minutes)
+        .bind(filter.start_date)
+        .bind(filter.end_date)
+        .bind(interval_minutes)
+        .bind(filter.company_id)
+        .bind(filter.end_date)
+        .bind(filter.start_date)
+        .bind(filter.location_id)
+        .bind(filter.team_id)
+        .bind(filter.team_id)
+        .bind(filter.scope.is_none())
+        .bind(
+            filter
+                .scope
+                .as_ref()
+                .map(|scope| scope.location_ids.clone())
+                .unwrap_or_default(),
+        )
+        .bind(
+            filter
+                .scope
+                .as_ref()
+                .map(|scope| scope.team_ids.clone())
+                .unwrap_or_default(),
+        )
+        .fetch_all(&get_pool().await)
+        .await?;
 
-    let denied_requests: i64 =
-        sqlx::query_scalar("SELECT COUNT(*) FROM time_off_requests WHERE status = 'denied'")
-            .fetch_one(&get_pool().await)
-            .await?;
+    Ok(buckets)
+}
 
-    let pending_requests: i64 =
-        sqlx::query_scalar("SELECT COUNT(*) FROM time_off_requests WHERE status = 'pending'")
-            .fetch_one(&get_pool().await)
-            .await?;
+/// Shifts each member works that start in the period, per local `trend` period (week or
+/// month) they start in, with the unpopular ones counted. Times are local to the company's
+/// timezone. Members without shifts get a single row with no trend period.
+pub async fn get_fairness_rows(
+    filter: &StatsFilter,
+    trend: &str,
+    holidays: &[NaiveDate],
+) -> Result<Vec<FairnessRow>, sqlx::Error> {
+    let query = format!(
+        r#"
+        WITH
+            {FILLED_SLOTS},
+            {REPORT_MEMBERS},
+            zone AS (
+                SELECT
+                    COALESCE(
+                        (
+                            SELECT
+                                name
+                            FROM
+                                pg_timezone_names
+                            WHERE
+                                name = c.timezone
+                            LIMIT
+                                1
+                        ),
+                        'UTC'
+                    ) AS tz
+                FROM
+                    companies c
+                WHERE
+                    c.id = ?
+            ),
+            worked AS (
+                SELECT
+                    f.user_id,
+                    s.start_time AT TIME ZONE z.tz AS local_start,
+                    s.end_time AT TIME ZONE z.tz AS local_end,
+                    EXTRACT(EPOCH FROM (s.end_time - s.start_time)) / 3600 AS hours,
+                    EXISTS (
+                        SELECT
+                            1
+                        FROM
+                            shift_assignments sa
+                        WHERE
+                            sa.shift_id = s.id
+                            AND sa.user_id = f.user_id
+                            AND sa.assignment_status = 'accepted'
+                            AND sa.created_at > s.start_time - MAKE_INTERVAL(hours => ?)
+                    ) AS short_notice
+                FROM
+                    filled f
+                    JOIN shifts s ON s.id = f.shift_id
+                    CROSS JOIN zone z
+                WHERE
+                    {SHIFT_SCOPE}
+                    AND s.status <> 'cancelled'
+            )
+        SELECT
+            m.id AS user_id,
+            m.name,
+            DATE_TRUNC(?, w.local_start)::DATE AS trend_start,
+            COUNT(w.user_id) AS shifts,
+            COALESCE(SUM(w.hours), 0)::DOUBLE PRECISION AS hours,
+            COUNT(*) FILTER (
+                WHERE
+                    EXTRACT(ISODOW FROM w.local_start) >= 6
+            ) AS weekend_shifts,
+            COUNT(*) FILTER (
+                WHERE
+                    w.local_start::TIME >= MAKE_TIME(?, 0, 0)
+                    OR w.local_start::TIME < MAKE_TIME(?, 0, 0)
+                    OR w.local_end > DATE_TRUNC('day', w.local_start) + MAKE_INTERVAL(hours => ?)
+            ) AS night_shifts,
+            COUNT(*) FILTER (
+                WHERE
+                    w.local_start::DATE = ANY(?)
+            ) AS holiday_shifts,
+            COUNT(*) FILTER (
+                WHERE
+                    w.short_notice
+            ) AS short_notice_shifts,
+            uss.min_hours_per_week,
+            uss.max_hours_per_week
+        FROM
+            members m
+            LEFT JOIN worked w ON w.user_id = m.id
+            LEFT JOIN user_shift_schedules uss ON uss.user_id = m.id
+            AND uss.company_id = ?
+        GROUP BY
+            m.id,
+            m.name,
+            trend_start,
+            uss.min_hours_per_week,
+            uss.max_hours_per_week
+        ORDER BY
+            m.name,
+            m.id,
+            trend_start
+    "#
+    );
 
-    let cancelled_requests: i64 =
-        sqlx::query_scalar("SELECT COUNT(*) FROM time_off_requests WHERE status = 'cancelled'")
-            .fetch_one(&get_pool().await)
-            .await?;
+    let query = sql(&query);
+    let members = bind_report_members(sqlx::query_as::<_, FairnessRow>(&query), filter)
+        .bind(filter.company_id)
+        .bind(SHORT_NOTICE_HOURS);
+    let rows = bind_shift_scope(members, filter)
+        .bind(trend)
+        .bind(NIGHT_START_HOUR)
+        .bind(NIGHT_END_HOUR)
+        .bind(NIGHT_START_HOUR)
+        .bind(holidays)
+        .bind(filter.company_id)
+        .fetch_all(&get_pool().await)
+        .await?;
 
-    Ok(TimeOffStats {
-        total_requests,
-        approved_requests,
-        denied_requests,
-        pending_requests,
-        cancelled_requests,
-    })
+    Ok(rows)
 }
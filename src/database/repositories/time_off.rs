@@ -1,3 +1,4 @@
+use bigdecimal::BigDecimal;
 use chrono::{NaiveDate, Utc};
 use sqlx::{Postgres, Transaction};
 use uuid::Uuid;
@@ -7,6 +8,24 @@ use crate::database::{
     models::{TimeOffRequest, TimeOffRequestInput, TimeOffStatus},
     utils::sql,
 };
+
+const REQUEST_COLUMNS: &str = r#"
+    id,
+    user_id,
+    company_id,
+    start_date,
+    end_date,
+    reason,
+    request_type,
+    status,
+    duration_type,
+    hours_deducted,
+    actioned_by,
+    action_notes,
+    created_at,
+    updated_at
+"#;
+
 /// Create a new time-off request
 pub async fn create_request(
     tx: &mut Transaction<'_, Postgres>,
@@ -16,23 +35,10 @@ pub async fn create_request(
     let request_type_str = input.request_type.to_string();
     let status_str = TimeOffStatus::Pending.to_string();
 
-    let time_off_request = sqlx::query_as::<_, TimeOffRequest>(&sql(r#"
-            INSERT INTO
-                time_off_requests (
-                    user_id,
-                    company_id,
-                    start_date,
-                    end_date,
-                    reason,
-                    request_type,
-                    status,
-                    created_at,
-                    updated_at
-                )
-            VALUES
-                (?, ?, ?, ?, ?, ?, ?, ?)
-            RETURNING
-                id,
+    let query = format!(
+        r#"
+        INSERT INTO
+            time_off_requests (
                 user_id,
                 company_id,
                 start_date,
@@ -40,116 +46,92 @@ pub async fn create_request(
                 reason,
                 request_type,
                 status,
-                approved_by,
-                approval_notes,
+                duration_type,
                 created_at,
                 updated_at
-        "#))
-    .bind(input.user_id)
-    .bind(input.company_id)
-    .bind(input.start_date)
-    .bind(input.end_date)
-    .bind(input.reason)
-    .bind(request_type_str)
-    .bind(status_str)
-    .bind(now)
-    .bind(now)
-    .fetch_one(&mut **tx)
-    .await?;
+            )
+        VALUES
+            (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING
+            {REQUEST_COLUMNS}
+        "#
+    );
+    let query = sql(&query);
+    let time_off_request = sqlx::query_as::<_, TimeOffRequest>(&query)
+        .bind(input.user_id)
+        .bind(input.company_id)
+        .bind(input.start_date)
+        .bind(input.end_date)
+        .bind(input.reason)
+        .bind(request_type_str)
+        .bind(status_str)
+        .bind(input.duration_type)
+        .bind(now)
+        .bind(now)
+        .fetch_one(&mut **tx)
+        .await?;
 
     Ok(time_off_request)
 }
 
-/// Get all time-off requests with optional filtering
+/// Get the company's time-off requests with optional filtering
 pub async fn get_requests(
+    company_id: Uuid,
     user_id: Option<Uuid>,
     status: Option<TimeOffStatus>,
     start_date: Option<NaiveDate>,
     end_date: Option<NaiveDate>,
 ) -> Result<Vec<TimeOffRequest>, sqlx::Error> {
-    let mut query = r#"
-            SELECT
-                id,
-                user_id,
-                start_date,
-                end_date,
-                reason,
-                request_type,
-                status,
-                approved_by,
-                approval_notes,
-                created_at,
-                updated_at
-            FROM
-                time_off_requests
-            "#
-    .to_string();
-
-    let mut params = Vec::new();
-    let mut conditions = vec![];
-
-    if let Some(uid) = user_id {
-        conditions.push(format!("user_id = ${}", params.len() + 1));
-        params.push(uid.to_string());
-    }
-
-    if let Some(s) = status {
-        conditions.push(format!("status = ${}", params.len() + 1));
-        params.push(s.to_string());
-    }
-
-    if let Some(sd) = start_date {
-        conditions.push(format!("start_date >= ${}", params.len() + 1));
-        params.push(sd.to_string());
-    }
-
-    if let Some(ed) = end_date {
-        conditions.push(format!("end_date <= ${}", params.len() + 1));
-        params.push(ed.to_string());
-    }
-
-    if !params.is_empty() {
-        query.push_str(" WHERE ");
-        query.push_str(&conditions.join(" AND "));
-    }
-
-    query.push_str(" ORDER BY created_at DESC");
-
-    let mut prepared = sqlx::query_as::<_, TimeOffRequest>(&query);
-    for param in params {
-        prepared = prepared.bind(param);
-    }
-
-    let requests = prepared.fetch_all(&get_pool().await).await?;
+    let query = format!(
+        r#"
+        SELECT
+            {REQUEST_COLUMNS}
+        FROM
+            time_off_requests
+        WHERE
+            company_id = ?
+            AND (?::UUID IS NULL OR user_id = ?)
+            AND (?::VARCHAR IS NULL OR status = ?)
+            AND (?::DATE IS NULL OR start_date >= ?)
+            AND (?::DATE IS NULL OR end_date <= ?)
+        ORDER BY
+            created_at DESC
+        "#
+    );
+    let query = sql(&query);
+    let requests = sqlx::query_as::<_, TimeOffRequest>(&query)
+        .bind(company_id)
+        .bind(user_id)
+        .bind(user_id)
+        .bind(&status)
+        .bind(&status)
+        .bind(start_date)
+        .bind(start_date)
+        .bind(end_date)
+        .bind(end_date)
+        .fetch_all(&get_pool().await)
+        .await?;
 
     Ok(requests)
 }
 
 /// Get a specific time-off request by ID
 pub async fn get_request_by_id(id: Uuid) -> Result<Option<TimeOffRequest>, sqlx::Error> {
-    let time_off_request = sqlx::query_as::<_, TimeOffRequest>(
+    let query = format!(
         r#"
-            SELECT
-                id,
-                user_id,
-                start_date,
-                end_date,
-                reason,
-                request_type,
-                status,
-                approved_by,
-                approval_notes,
-                created_at,
-                updated_at
-            FROM
-                time_off_requests
-            WHERE
-                id = $1
-            "#,
-    )
-    .bind(id)
-    .fetch_optional(&get_pool().await)
-    .await?;
+        SELECT
+            {REQUEST_COLUMNS}
+        FROM
+            time_off_requests
+        WHERE
+            id = ?
+        "#
+    );
+    let query = sql(&query);
+    let time_off_request = sqlx::query_as::<_, TimeOffRequest>(&query)
+        .bind(id)
+        .fetch_optional(&get_pool().await)
+        .await?;
 
     Ok(time_off_request)
 }
@@ -163,170 +145,157 @@ pub async fn update_request(
     let now = Utc::now();
     let request_type_str = input.request_type.to_string();
 
-    let time_off_request = sqlx::query_as::<_, TimeOffRequest>(
+    let query = format!(
         r#"
-            UPDATE
-                time_off_requests
-            SET
-                start_date = $1,
-                end_date = $2,
-                reason = $3,
-                request_type = $4,
-                updated_at = $5
-            WHERE
-                id = $6
-            RETURNING
-                id,
-                user_id,
-                start_date,
-                end_date,
-                reason,
-                request_type,
-                status,
-                approved_by,
-                approval_notes,
-                created_at,
-                updated_at
-            "#,
-    )
-    .bind(input.start_date)
-    .bind(input.end_date)
-    .bind(input.reason)
-    .bind(request_type_str)
-    .bind(now)
-    .bind(id)
-    .fetch_one(&mut **tx)
-    .await?;
+        UPDATE
+            time_off_requests
+        SET
+            start_date = ?,
+            end_date = ?,
+            reason = ?,
+            request_type = ?,
+            duration_type = ?,
+            updated_at = ?
+        WHERE
+            id = ?
+        RETURNING
+            {REQUEST_COLUMNS}
+        "#
+    );
+    let query = sql(&query);
+    let time_off_request = sqlx::query_as::<_, TimeOffRequest>(&query)
+        .bind(input.start_date)
+        .bind(input.end_date)
+        .bind(input.reason)
+        .bind(request_type_str)
+        .bind(input.duration_type)
+        .bind(now)
+        .bind(id)
+        .fetch_one(&mut **tx)
+        .await?;
 
     Ok(time_off_request)
 }
 
-/// Approve a time-off request
+/// Approve a pending time-off request, recording the hours taken from the balance. Returns
+/// None when the request is no longer pending.
 pub async fn approve_request(
     tx: &mut Transaction<'_, Postgres>,
     id: Uuid,
     approved_by: Uuid,
     notes: Option<String>,
-) -> Result<TimeOffRequest, sqlx::Error> {
+    hours_deducted: Option<BigDecimal>,
+) -> Result<Option<TimeOffRequest>, sqlx::Error> {
     let now = Utc::now();
     let status_str = TimeOffStatus::Approved.to_string();
 
-    let time_off_request = sqlx::query_as::<_, TimeOffRequest>(
+    let query = format!(
         r#"
-            UPDATE time_off_requests
-            SET
-                status = $1,
-                approved_by = $2,
-                approval_notes = $3,
-                updated_at = $4
-            WHERE
-                id = $5
-            RETURNING
-                id,
-                user_id,
-                start_date,
-                end_date,
-                reason,
-                request_type,
-                status,
-                approved_by,
-                approval_notes,
-                created_at,
-                updated_at
-            "#,
-    )
-    .bind(status_str)
-    .bind(approved_by)
-    .bind(notes)
-    .bind(now)
-    .bind(id)
-    .fetch_one(&mut **tx)
-    .await?;
+        UPDATE time_off_requests
+        SET
+            status = ?,
+            actioned_by = ?,
+            action_notes = ?,
+            hours_deducted = ?,
+            updated_at = ?
+        WHERE
+            id = ?
+            AND status = ?
+        RETURNING
+            {REQUEST_COLUMNS}
+        "#
+    );
+    let query = sql(&query);
+    let time_off_request = sqlx::query_as::<_, TimeOffRequest>(&query)
+        .bind(status_str)
+        .bind(approved_by)
+        .bind(notes)
+        .bind(hours_deducted)
+        .bind(now)
+        .bind(id)
+        .bind(TimeOffStatus::Pending.to_string())
+        .fetch_optional(&mut **tx)
+        .await?;
 
     Ok(time_off_request)
 }
 
-/// Deny a time-off request
+/// Deny a pending time-off request. Returns None when the request is no longer pending.
 pub async fn deny_request(
     tx: &mut Transaction<'_, Postgres>,
     id: Uuid,
     denied_by: Uuid,
     notes: Option<String>,
-) -> Result<TimeOffRequest, sqlx::Error> {
+) -> Result<Option<TimeOffRequest>, sqlx::Error> {
     let now = Utc::now();
     let status_str = TimeOffStatus::Denied.to_string();
 
-    let time_off_request = sqlx::query_as::<_, TimeOffRequest>(
+    let query = format!(
         r#"
-            UPDATE
-                time_off_requests
-            SET
-                status = $1,
-                approved_by = $2,
-                approval_notes = $3,
-                updated_at = $4
-            WHERE
-                id = $5
-            RETURNING
-                id,
-                user_id,
-                start_date,
-                end_date,
-                reason,
-                request_type,
-                status,
-                approved_by,
-                approval_notes,
-                created_at,
-                updated_at
-            "#,
-    )
-    .bind(status_str)
-    .bind(denied_by)
-    .bind(notes)
-    .bind(now)
-    .bind(id)
-    .fetch_one(&mut **tx)
-    .await?;
+        UPDATE
+            time_off_requests
+        SET
+            status = ?,
+            actioned_by = ?,
+            action_notes = ?,
+            updated_at = ?
+        WHERE
+            id = ?
+            AND status = ?
+        RETURNING
+            {REQUEST_COLUMNS}
+        "#
+    );
+    let query = sql(&query);
+    let time_off_request = sqlx::query_as::<_, TimeOffRequest>(&query)
+        .bind(status_str)
+        .bind(denied_by)
+        .bind(notes)
+        .bind(now)
+        .bind(id)
+        .bind(TimeOffStatus::Pending.to_string())
+        .fetch_optional(&mut **tx)
+        .await?;
 
     Ok(time_off_request)
 }
 
-/// Cancel a time-off request
+/// Cancel a pending or approved time-off request. Returns None when it is neither any more.
 pub async fn cancel_request(
     tx: &mut Transaction<'_, Postgres>,
     id: Uuid,
-) -> Result<TimeOffRequest, sqlx::Error> {
+    cancelled_by: Uuid,
+    notes: Option<String>,
+) -> Result<Option<TimeOffRequest>, sqlx::Error> {
     let now = Utc::now();
     let status_str = TimeOffStatus::Cancelled.to_string();
 
-    let time_off_request = sqlx::query_as::<_, TimeOffRequest>(
+    let query = format!(
         r#"
-            UPDATE time_off_requests
-            SET
-                status = $1,
-                updated_at = $2
-            WHERE
-                id = $3
-            RETURNING
-                id,
-                user_id,
-                start_date,
-                end_date,
-                reason,
-                request_type,
-                status,
-                approved_by,
-                approval_notes,
-                created_at,
-                updated_at
-            "#,
-    )
-    .bind(status_str)
-    .bind(now)
-    .bind(id)
-    .fetch_one(&mut **tx)
-    .await?;
+        UPDATE time_off_requests
+        SET
+            status = ?,
+            actioned_by = ?,
+            action_notes = ?,
+            updated_at = ?
+        WHERE
+            id = ?
+            AND status IN (?, ?)
+        RETURNING
+            {REQUEST_COLUMNS}
+        "#
+    );
+    let query = sql(&query);
+    let time_off_request = sqlx::query_as::<_, TimeOffRequest>(&query)
+        .bind(status_str)
+        .bind(cancelled_by)
+        .bind(notes)
+        .bind(now)
+        .bind(id)
+        .bind(TimeOffStatus::Pending.to_string())
+        .bind(TimeOffStatus::Approved.to_string())
+        .fetch_optional(&mut **tx)
+        .await?;
 
     Ok(time_off_request)
 }
@@ -343,3 +312,55 @@ pub async fn delete_request(
 
     Ok(())
 }
+
+/// Hours of the company's shifts the member holds per day from `from` through `through`,
+/// by the day each shift starts. Cancelled shifts don't count.
+pub async fn get_scheduled_hours(
+    user_id: Uuid,
+    company_id: Uuid,
+    from: NaiveDate,
+    through: NaiveDate,
+) -> Result<Vec<(NaiveDate, BigDecimal)>, sqlx::Error> {
+    let hours = sqlx::query_as::<_, (NaiveDate, BigDecimal)>(&sql(r#"
+        SELECT
+            s.start_time::DATE AS day,
+            (SUM(EXTRACT(EPOCH FROM (s.end_time - s.start_time))) / 3600)::DECIMAL(10, 4)
+        FROM
+            shifts s
+        WHERE
+            s.company_id = ?
+            AND s.status <> 'cancelled'
+            AND s.start_time::DATE >= ?
+            AND s.start_time::DATE <= ?
+            AND s.id IN (
+                SELECT
+                    shift_id
+                FROM
+                    shift_assignments
+                WHERE
+                    user_id = ?
+                    AND assignment_status = 'accepted'
+                UNION
+                SELECT
+                    shift_id
+                FROM
+                    shift_claims
+                WHERE
+                    user_id = ?
+                    AND status = 'approved'
+            )
+        GROUP BY
+            day
+        ORDER BY
+            day
+    "#))
+    .bind(company_id)
+    .bind(from)
+    .bind(through)
+    .bind(user_id)
+    .bind(user_id)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(hours)
+}
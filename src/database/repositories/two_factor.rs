@@ -0,0 +1,263 @@
+use chrono::Utc;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::database::{get_pool, models::UserTwoFactor, utils::sql};
+
+pub async fn find_by_user_id(user_id: Uuid) -> Result<Option<UserTwoFactor>, sqlx::Error> {
+    let two_factor = sqlx::query_as::<_, UserTwoFactor>(&sql(r#"
+        SELECT
+            user_id,
+            secret,
+            enabled_at,
+            last_used_step,
+            created_at,
+            updated_at
+        FROM
+            user_two_factor
+        WHERE
+            user_id = ?
+    "#))
+    .bind(user_id)
+    .fetch_optional(&get_pool().await)
+    .await?;
+
+    Ok(two_factor)
+}
+
+/// Read the user's 2FA settings and lock them until the transaction ends, so a code
+/// cannot be checked and consumed by two requests at once
+pub async fn find_by_user_id_for_update(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<Option<UserTwoFactor>, sqlx::Error> {
+    let two_factor = sqlx::query_as::<_, UserTwoFactor>(&sql(r#"
+        SELECT
+            user_id,
+            secret,
+            enabled_at,
+            last_used_step,
+            created_at,
+            updated_at
+        FROM
+            user_two_factor
+        WHERE
+            user_id = ?
+        FOR UPDATE
+    "#))
+    .bind(user_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(two_factor)
+}
+
+/// Store a new, not yet confirmed, secret for the user (replacing any pending one)
+pub async fn upsert_pending_secret(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    secret: &str,
+) -> Result<UserTwoFactor, sqlx::Error> {
+    let two_factor = sqlx::query_as::<_, UserTwoFactor>(&sql(r#"
+        INSERT INTO
+            user_two_factor (user_id, secret)
+        VALUES
+            (?, ?)
+        ON CONFLICT (user_id) DO UPDATE
+        SET
+            secret = EXCLUDED.secret,
+            enabled_at = NULL,
+            last_used_step = NULL,
+            updated_at = NOW()
+        RETURNING
+            user_id,
+            secret,
+            enabled_at,
+            last_used_step,
+            created_at,
+            updated_at
+    "#))
+    .bind(user_id)
+    .bind(secret)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(two_factor)
+}
+
+/// Mark enrolment as confirmed
+pub async fn enable(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    step: i64,
+) -> Result<Option<()>, sqlx::Error> {
+    let now = Utc::now();
+
+    let result = sqlx::query(&sql(r#"
+        UPDATE user_two_factor
+        SET
+            enabled_at = ?,
+            last_used_step = ?,
+            updated_at = ?
+        WHERE
+            user_id = ?
+            AND enabled_at IS NULL
+    "#))
+    .bind(now)
+    .bind(step)
+    .bind(now)
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(()))
+}
+
+/// Record an accepted TOTP step. Returns None if that step (or a later one) was already used.
+pub async fn record_used_step(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    step: i64,
+) -> Result<Option<()>, sqlx::Error> {
+    let result = sqlx::query(&sql(r#"
+        UPDATE user_two_factor
+        SET
+            last_used_step = ?,
+            updated_at = NOW()
+        WHERE
+            user_id = ?
+            AND (
+                last_used_step IS NULL
+                OR last_used_step < ?
+            )
+    "#))
+    .bind(step)
+    .bind(user_id)
+    .bind(step)
+    .execute(&mut **tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(()))
+}
+
+/// Remove the user's secret and recovery codes
+pub async fn delete_for_user(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&sql(r#"
+        DELETE FROM two_factor_recovery_codes
+        WHERE
+            user_id = ?
+    "#))
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query(&sql(r#"
+        DELETE FROM user_two_factor
+        WHERE
+            user_id = ?
+    "#))
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Replace all recovery codes for the user with the given hashes
+pub async fn replace_recovery_codes(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    code_hashes: &[String],
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&sql(r#"
+        DELETE FROM two_factor_recovery_codes
+        WHERE
+            user_id = ?
+    "#))
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+
+    for code_hash in code_hashes {
+        sqlx::query(&sql(r#"
+            INSERT INTO
+                two_factor_recovery_codes (user_id, code_hash)
+            VALUES
+                (?, ?)
+        "#))
+        .bind(user_id)
+        .bind(code_hash)
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Consume an unused recovery code. Returns None if no matching unused code exists.
+pub async fn use_recovery_code(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    code_hash: &str,
+) -> Result<Option<()>, sqlx::Error> {
+    let now = Utc::now();
+
+    let result = sqlx::query(&sql(r#"
+        UPDATE two_factor_recovery_codes
+        SET
+            used_at = ?
+        WHERE
+            id = (
+                SELECT
+                    id
+                FROM
+                    two_factor_recovery_codes
+                WHERE
+                    user_id = ?
+                    AND code_hash = ?
+                    AND used_at IS NULL
+                LIMIT
+                    1
+                FOR UPDATE
+            )
+    "#))
+    .bind(now)
+    .bind(user_id)
+    .bind(code_hash)
+    .execute(&mut **tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(()))
+}
+
+pub async fn count_unused_recovery_codes(user_id: Uuid) -> Result<i64, sqlx::Error> {
+    let count: i64 = sqlx::query_scalar(&sql(r#"
+        SELECT
+            COUNT(*)
+        FROM
+            two_factor_recovery_codes
+        WHERE
+            user_id = ?
+            AND used_at IS NULL
+    "#))
+    .bind(user_id)
+    .fetch_one(&get_pool().await)
+    .await?;
+
+    Ok(count)
+}
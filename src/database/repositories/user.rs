@@ -15,16 +15,19 @@ pub async fn create_user(
                 email,
                 password_hash,
                 name,
+                email_verified_at,
                 created_at,
                 updated_at
             )
         VALUES
-            (?, ?, ?, ?, ?, ?)
+            (?, ?, ?, ?, ?, ?, ?)
         RETURNING 
             id,
             email,
             password_hash,
             name,
+            email_verified_at,
+            erased_at,
             created_at,
             updated_at
     "#))
@@ -32,6 +35,7 @@ pub async fn create_user(
     .bind(&user.email)
     .bind(&user.password_hash)
     .bind(&user.name)
+    .bind(user.email_verified_at)
     .bind(&user.created_at)
     .bind(&user.updated_at)
     .fetch_one(&mut **tx)
@@ -47,6 +51,8 @@ pub async fn find_by_email(email: &str) -> Result<Option<User>, sqlx::Error> {
             email,
             password_hash,
             name,
+            email_verified_at,
+            erased_at,
             created_at,
             updated_at
         FROM
@@ -61,6 +67,29 @@ pub async fn find_by_email(email: &str) -> Result<Option<User>, sqlx::Error> {
     Ok(user)
 }
 
+pub async fn find_by_emails(emails: &[String]) -> Result<Vec<User>, sqlx::Error> {
+    let users = sqlx::query_as::<_, User>(&sql(r#"
+        SELECT
+            id,
+            email,
+            password_hash,
+            name,
+            email_verified_at,
+            erased_at,
+            created_at,
+            updated_at
+        FROM
+            users
+        WHERE
+            email = ANY(?)
+    "#))
+    .bind(emails)
+    .fetch_all(&get_pool().await)
+    .await?;
+
+    Ok(users)
+}
+
 pub async fn find_by_id(id: Uuid) -> Result<Option<User>, sqlx::Error> {
     let user = sqlx::query_as::<_, User>(&sql(r#"
         SELECT
@@ -68,6 +97,8 @@ pub async fn find_by_id(id: Uuid) -> Result<Option<User>, sqlx::Error> {
             email,
             password_hash,
             name,
+            email_verified_at,
+            erased_at,
             created_at,
             updated_at
         FROM
@@ -89,6 +120,8 @@ pub async fn get_all_users() -> Result<Vec<User>, sqlx::Error> {
             email,
             password_hash,
             name,
+            email_verified_at,
+            erased_at,
             created_at,
             updated_at
         FROM
@@ -115,6 +148,11 @@ pub async fn update_user(
         SET
             name = ?,
             email = ?,
+            -- A new address has to be verified again
+            email_verified_at = CASE
+                WHEN email = ? THEN email_verified_at
+                ELSE NULL
+            END,
             updated_at = ?
         WHERE
             id = ?
@@ -123,11 +161,14 @@ pub async fn update_user(
             email,
             password_hash,
             name,
+            email_verified_at,
+            erased_at,
             created_at,
             updated_at
     "#))
     .bind(name)
     .bind(email)
+    .bind(email)
     .bind(updated_at)
     .bind(id)
     .fetch_one(&mut **tx)
@@ -188,3 +229,39 @@ pub async fn update_password(
 
     Ok(())
 }
+
+/// Mark `email` as verified, moving the account to it if it is a new address
+pub async fn set_verified_email(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    email: &str,
+) -> Result<User, sqlx::Error> {
+    let now = Utc::now();
+
+    let user = sqlx::query_as::<_, User>(&sql(r#"
+        UPDATE users
+        SET
+            email = ?,
+            email_verified_at = ?,
+            updated_at = ?
+        WHERE
+            id = ?
+        RETURNING
+            id,
+            email,
+            password_hash,
+            name,
+            email_verified_at,
+            erased_at,
+            created_at,
+            updated_at
+    "#))
+    .bind(email)
+    .bind(now)
+    .bind(now)
+    .bind(user_id)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(user)
+}
@@ -40,10 +40,15 @@ pub async fn create_balance(
     )
     .bind(&request.user_id)
     .bind(&request.company_id)
-    .bind(request.pto_balance_hours.unwrap_or(0))
-    .bind(request.sick_balance_hours.unwrap_or(0))
-    .bind(request.personal_balance_hours.unwrap_or(0))
-    .bind(request.pto_accrual_rate.as_ref().map_or_else(|| BigDecimal::from(0), |v| v.clone()))
+    .bind(request.pto_balance_hours.clone().unwrap_or_default())
+    .bind(request.sick_balance_hours.clone().unwrap_or_default())
+    .bind(request.personal_balance_hours.clone().unwrap_or_default())
+    .bind(
+        request
+            .pto_accrual_rate
+            .as_ref()
+            .map_or_else(|| BigDecimal::from(0), |v| v.clone()),
+    )
     .bind(request.hire_date)
     .fetch_one(&mut **tx)
     .await?;
@@ -160,12 +165,18 @@ pub async fn update_balance(
     // Bind parameters in order
     for param in &params {
         match param.as_str() {
-            "pto_balance_hours" => query_builder = query_builder.bind(request.pto_balance_hours),
-            "sick_balance_hours" => query_builder = query_builder.bind(request.sick_balance_hours),
+            "pto_balance_hours" => {
+                query_builder = query_builder.bind(request.pto_balance_hours.clone())
+            }
+            "sick_balance_hours" => {
+                query_builder = query_builder.bind(request.sick_balance_hours.clone())
+            }
             "personal_balance_hours" => {
-                query_builder = query_builder.bind(request.personal_balance_hours)
+                query_builder = query_builder.bind(request.personal_balance_hours.clone())
+            }
+            "pto_accrual_rate" => {
+                query_builder = query_builder.bind(request.pto_accrual_rate.clone())
             }
-            "pto_accrual_rate" => query_builder = query_builder.bind(request.pto_accrual_rate.clone()),
             "hire_date" => query_builder = query_builder.bind(request.hire_date),
             "last_accrual_date" => query_builder = query_builder.bind(request.last_accrual_date),
             _ => {}
@@ -239,9 +250,9 @@ pub async fn create_or_update_balance(
             role: request.role.clone(),
             is_primary: request.is_primary,
             hire_date: request.hire_date,
-            pto_balance_hours: request.pto_balance_hours,
-            sick_balance_hours: request.sick_balance_hours,
-            personal_balance_hours: request.personal_balance_hours,
+            pto_balance_hours: request.pto_balance_hours.clone(),
+            sick_balance_hours: request.sick_balance_hours.clone(),
+            personal_balance_hours: request.personal_balance_hours.clone(),
             pto_accrual_rate: request.pto_accrual_rate.clone(),
             last_accrual_date: None,
             hourly_rate: request.hourly_rate.clone(),
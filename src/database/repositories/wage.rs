@@ -0,0 +1,50 @@
+use sqlx::{Postgres, Transaction};
+
+use crate::database::{
+    models::{CreateWageHistoryInput, WageHistory},
+    utils::sql,
+};
+
+pub async fn create_wage_history(
+    tx: &mut Transaction<'_, Postgres>,
+    input: &CreateWageHistoryInput,
+) -> Result<WageHistory, sqlx::Error> {
+    let wage = sqlx::query_as::<_, WageHistory>(&sql(r#"
+        INSERT INTO
+            wage_history (
+                user_id,
+                company_id,
+                hourly_rate,
+                overtime_rate_multiplier,
+                effective_date,
+                end_date,
+                changed_by,
+                change_reason
+            )
+        VALUES
+            (?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING
+            id,
+            user_id,
+            company_id,
+            hourly_rate,
+            overtime_rate_multiplier,
+            effective_date,
+            end_date,
+            changed_by,
+            change_reason,
+            created_at
+    "#))
+    .bind(input.user_id)
+    .bind(input.company_id)
+    .bind(&input.hourly_rate)
+    .bind(&input.overtime_rate_multiplier)
+    .bind(input.effective_date)
+    .bind(input.end_date)
+    .bind(input.changed_by)
+    .bind(&input.change_reason)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(wage)
+}
@@ -20,9 +20,15 @@ pub enum AppError {
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("Unauthorized access")]
     Unauthorized,
 
+    #[error("Too many requests: {0}")]
+    TooManyRequests(String),
+
     #[error("Internal server error{}", .0.as_ref().map_or("".to_string(), |s| format!(": {}", s)))]
     InternalServerError(Option<String>),
 }
@@ -34,9 +40,11 @@ impl ResponseError for AppError {
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
             AppError::PermissionDenied(_) => StatusCode::FORBIDDEN,
             AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
             AppError::Forbidden(_) => StatusCode::FORBIDDEN,
             AppError::InternalServerError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
         }
     }
 
@@ -77,6 +85,12 @@ impl From<anyhow::Error> for AppError {
     fn from(error: anyhow::Error) -> Self {
         log::error!("Anyhow error: {}", error);
 
+        // Keep the original status for application errors passed through anyhow
+        let error = match error.downcast::<AppError>() {
+            Ok(app_error) => return app_error,
+            Err(error) => error,
+        };
+
         // Check if this is a sqlx::Error and handle it appropriately
         if error.is::<sqlx::Error>() {
             // Downcast the error to sqlx::Error by consuming the anyhow::Error
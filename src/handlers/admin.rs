@@ -7,11 +7,15 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
+    config::config,
     database::{
         models::{
-            Action, CompanyRole, CreateUpdateLocationInput, CreateUpdateTeamInput, LocationInput,
+            Action, ActivityQuery, ActivityRetention, ActivityType, CompanyRole,
+            CreateUpdateLocationInput, CreateUpdateTeamInput, EntityType, ImpersonateInput,
+            LocationInput, Permission,
         },
         repositories::{
+            account_lockout as account_lockout_repo, activity as activity_repo,
             company as company_repo, location as location_repo, team as team_repo,
             user as user_repo,
         },
@@ -20,7 +24,10 @@ use crate::{
     error::AppError,
     handlers::shared::ApiResponse,
     middleware::{CacheLayer, cache::InvalidationContext, request_info::RequestInfo},
-    services::{activity_logger, user_context::UserContext},
+    services::{
+        activity_archive, activity_chain, activity_log, activity_logger, impersonation,
+        manager_scope, user_context::UserContext,
+    },
 };
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -51,7 +58,7 @@ pub async fn create_location(
     req_info: RequestInfo,
     cache: Data<CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::LOCATIONS_MANAGE)?;
 
     let user_id = ctx.user_id();
     let company_id = ctx.strict_company_id()?;
@@ -222,12 +229,18 @@ pub async fn update_location(
 ) -> Result<HttpResponse> {
     let company_id = ctx.strict_company_id()?;
 
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::LOCATIONS_MANAGE)?;
 
     let location_id = path.into_inner();
     let path_for_cache = req_info.path.clone();
     let user_id = ctx.user_id();
 
+    let previous = location_repo::find_by_id(location_id)
+        .await
+        .map_err(AppError::from)?
+        .filter(|location| location.company_id == company_id)
+        .ok_or_else(|| AppError::NotFound("Location not found".to_string()))?;
+
     let location = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
             let location = location_repo::update_location(
@@ -256,6 +269,7 @@ pub async fn update_location(
                 ("location_name", location.name.clone()),
                 ("location_id", location.id.to_string()),
             ]);
+            let metadata = activity_logger::with_changes(metadata, &previous, &location);
             activity_logger::log_location_activity(
                 tx,
                 company_id,
@@ -466,15 +480,21 @@ pub async fn get_teams(
                 AppError::DatabaseError(e)
             })?
     } else {
-        ctx.requires_manager()?;
+        ctx.requires_permission(Permission::TEAMS_MANAGE)?;
         let company_id = ctx.strict_company_id()?;
 
-        team_repo::get_all_teams_for_company(company_id)
+        let mut teams = team_repo::get_all_teams_for_company(company_id)
             .await
             .map_err(|e| {
                 log::error!("Error fetching teams for company: {}", e);
                 AppError::DatabaseError(e)
-            })?
+            })?;
+
+        // Scoped managers only see the teams they manage
+        let scope = manager_scope::load(&ctx).await?;
+        teams.retain(|team| scope.includes_team(team.id));
+
+        teams
     };
 
     // Cache teams retrieval - affects teams, locations, company
@@ -507,7 +527,7 @@ pub async fn get_team(
 ) -> Result<HttpResponse> {
     let team_id = path.into_inner();
 
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::TEAMS_MANAGE)?;
 
     let location = get_location_for_team(team_id).await?;
 
@@ -551,7 +571,7 @@ pub async fn update_team(
     let team_id = path.into_inner();
     let path_for_cache = req_info.path.clone();
 
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::TEAMS_MANAGE)?;
 
     let location = location_repo::find_by_team_id(team_id)
         .await
@@ -566,6 +586,11 @@ pub async fn update_team(
 
     ctx.requires_same_company(location.company_id)?;
 
+    let previous = team_repo::get_team_by_id(team_id)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::NotFound("Team not found".to_string()))?;
+
     let team = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
             let team = team_repo::update_team(tx, team_id, input.into_inner())
@@ -581,6 +606,7 @@ pub async fn update_team(
                 ("team_id", team.id.to_string()),
                 ("location_id", location.id.to_string()),
             ]);
+            let metadata = activity_logger::with_changes(metadata, &previous, &team);
 
             activity_logger::log_team_activity(
                 tx,
@@ -624,7 +650,7 @@ pub async fn delete_team(
     let path_for_cache = req_info.path.clone();
     let user_id = ctx.user_id();
 
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::TEAMS_MANAGE)?;
 
     let location = get_location_for_team(team_id).await?;
 
@@ -686,11 +712,12 @@ pub async fn add_team_member(
     let path_for_cache = req_info.path.clone();
     let manager_id = ctx.user_id();
 
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::TEAMS_MANAGE)?;
 
     let location = get_location_for_team(team_id).await?;
 
     ctx.requires_same_company(location.company_id)?;
+    manager_scope::load(&ctx).await?.requires_team(team_id)?;
 
     let team_member = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
@@ -739,11 +766,12 @@ pub async fn add_team_member(
 pub async fn get_team_members(path: Path<Uuid>, ctx: UserContext) -> Result<HttpResponse> {
     let team_id = path.into_inner();
 
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::TEAMS_MANAGE)?;
 
     let location = get_location_for_team(team_id).await?;
 
     ctx.requires_same_company(location.company_id)?;
+    manager_scope::load(&ctx).await?.requires_team(team_id)?;
 
     let members = team_repo::get_team_members(team_id).await.map_err(|e| {
         log::error!("Error fetching team members for team {}: {}", team_id, e);
@@ -763,11 +791,12 @@ pub async fn remove_team_member(
     let path_for_cache = req_info.path.clone();
     let manager_id = ctx.user_id();
 
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::TEAMS_MANAGE)?;
 
     let location = get_location_for_team(team_id).await?;
 
     ctx.requires_same_company(location.company_id)?;
+    manager_scope::load(&ctx).await?.requires_team(team_id)?;
 
     DatabaseTransaction::run(|tx| {
         Box::pin(async move {
@@ -827,7 +856,7 @@ pub struct TeamQuery {
 
 // User management handlers
 pub async fn get_users(ctx: UserContext) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::EMPLOYEES_VIEW)?;
 
     let company_id = ctx.strict_company_id()?;
 
@@ -867,9 +896,14 @@ pub async fn update_user(
 
     // Check permissions based on whether role is being updated
     if update_request.role.is_some() {
-        ctx.requires_manager()?;
+        ctx.requires_permission(Permission::EMPLOYEES_MANAGE)?;
     } else {
-        ctx.requires_same_user(user_id_to_update)?;
+        ctx.requires_same_user_or_permission(user_id_to_update, Permission::EMPLOYEES_MANAGE)?;
+    }
+
+    // An admin acting as the user cannot change the account's email
+    if user_id_to_update == user_id && update_request.email != ctx.user_email() {
+        ctx.requires_not_impersonating()?;
     }
 
     // Get the company ID from user context
@@ -883,6 +917,15 @@ pub async fn update_user(
                 AppError::PermissionDenied("User does not belong to the same company".to_string())
             })?;
 
+    let previous_user = user_repo::find_by_id(user_id_to_update)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    let previous = serde_json::json!({
+        "name": previous_user.name,
+        "email": previous_user.email,
+        "role": user_company_info.role,
+    });
+
     let (updated_user, final_role) = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
             // Update basic user information
@@ -920,6 +963,15 @@ pub async fn update_user(
                 ("updated_by", user_id.to_string()),
                 ("role", final_role.to_string()),
             ]);
+            let metadata = activity_logger::with_changes(
+                metadata,
+                &previous,
+                &serde_json::json!({
+                    "name": updated_user.name,
+                    "email": updated_user.email,
+                    "role": final_role,
+                }),
+            );
 
             activity_logger::log_user_activity(
                 tx,
@@ -970,7 +1022,7 @@ pub async fn delete_user(
     let admin_id = ctx.user_id();
 
     // Only admins can delete users - check company-specific admin role
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::EMPLOYEES_MANAGE)?;
 
     // Get the company ID from user context
     let company_id = ctx.strict_company_id()?;
@@ -1031,6 +1083,309 @@ pub async fn delete_user(
     Ok(ApiResponse::success_message("User deleted successfully"))
 }
 
+pub async fn unlock_user(
+    path: Path<Uuid>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    let user_id_to_unlock = path.into_inner();
+    let path_for_cache = req_info.path.clone();
+    let admin_id = ctx.user_id();
+
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    // Admins can only unlock members of their own company
+    company_repo::find_user_company_info_by_id(user_id_to_unlock, company_id)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| {
+            AppError::PermissionDenied("User does not belong to the same company".to_string())
+        })?;
+
+    let unlocked = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let Some(lockout) = account_lockout_repo::clear(tx, user_id_to_unlock).await? else {
+                return Ok(false);
+            };
+
+            let metadata = activity_logger::metadata(vec![(
+                "failed_attempts",
+                lockout.failed_attempts.to_string(),
+            )]);
+            activity_logger::log_user_activity(
+                tx,
+                company_id,
+                Some(admin_id),
+                user_id_to_unlock,
+                Action::ACCOUNT_UNLOCKED,
+                format!("User with ID {} unlocked", user_id_to_unlock),
+                Some(metadata),
+                &req_info,
+            )
+            .await?;
+
+            Ok(true)
+        })
+    })
+    .await?;
+
+    if !unlocked {
+        return Ok(ApiResponse::success_message("User account is not locked"));
+    }
+
+    let invalidation_ctx = InvalidationContext {
+        company_id: Some(company_id),
+        user_id: Some(admin_id),
+        resource_id: Some(user_id_to_unlock),
+    };
+
+    cache.invalidate(&path_for_cache, &invalidation_ctx).await;
+
+    Ok(ApiResponse::success_message("User account unlocked"))
+}
+
+pub async fn impersonate_user(
+    path: Path<Uuid>,
+    ctx: UserContext,
+    input: Json<ImpersonateInput>,
+    req_info: RequestInfo,
+) -> Result<HttpResponse> {
+    let user_id_to_impersonate = path.into_inner();
+
+    ctx.requires_permission(Permission::USERS_IMPERSONATE)?;
+
+    let response =
+        impersonation::start(&ctx, user_id_to_impersonate, input.into_inner(), &req_info).await?;
+
+    Ok(ApiResponse::created(response))
+}
+
+// Activity log handlers
+pub async fn get_activity(ctx: UserContext, query: Query<ActivityQuery>) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::ACTIVITY_VIEW)?;
+    let company_id = ctx.strict_company_id()?;
+
+    let page = activity_log::page(company_id, &query).await?;
+
+    Ok(ApiResponse::success(page))
+}
+
+/// Everything that happened to one entity, oldest first
+pub async fn get_entity_activity(
+    path: Path<(String, Uuid)>,
+    ctx: UserContext,
+    query: Query<ActivityQuery>,
+) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::ACTIVITY_VIEW)?;
+    let company_id = ctx.strict_company_id()?;
+    let (entity_type, entity_id) = path.into_inner();
+
+    let page = activity_log::timeline(company_id, entity_type, entity_id, &query).await?;
+
+    Ok(ApiResponse::success(page))
+}
+
+/// Field-level change history of one entity, oldest first
+pub async fn get_entity_changes(
+    path: Path<(String, Uuid)>,
+    ctx: UserContext,
+    query: Query<ActivityQuery>,
+) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::ACTIVITY_VIEW)?;
+    let company_id = ctx.strict_company_id()?;
+    let (entity_type, entity_id) = path.into_inner();
+
+    let page = activity_log::change_history(company_id, entity_type, entity_id, &query).await?;
+
+    Ok(ApiResponse::success(page))
+}
+
+/// Download the filtered activity log as CSV or NDJSON
+pub async fn export_activity(
+    ctx: UserContext,
+    query: Query<ActivityQuery>,
+    req_info: RequestInfo,
+) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::ACTIVITY_VIEW)?;
+    let company_id = ctx.strict_company_id()?;
+    let format = activity_log::ExportFormat::parse(query.format.as_deref())?;
+
+    let (body, _) =
+        activity_log::export(company_id, ctx.user_id(), &query, format, &req_info).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"activity-{}.{}\"",
+                Utc::now().format("%Y%m%d%H%M%S"),
+                format.extension()
+            ),
+        ))
+        .body(body))
+}
+
+/// Check the company's activity hash chain and checkpoints, reporting the first break
+pub async fn verify_activity(ctx: UserContext) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::ACTIVITY_VIEW)?;
+    let company_id = ctx.strict_company_id()?;
+
+    let verification = activity_chain::verify(company_id).await?;
+
+    Ok(ApiResponse::success(verification))
+}
+
+/// Signed checkpoints of the company's activity chain, for safekeeping outside ShiftLinkr
+pub async fn get_activity_checkpoints(ctx: UserContext) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::ACTIVITY_VIEW)?;
+    let company_id = ctx.strict_company_id()?;
+
+    let checkpoints = activity_repo::get_checkpoints(company_id)
+        .await
+        .map_err(AppError::from)?;
+
+    Ok(ApiResponse::success(checkpoints))
+}
+
+/// Sign the current head of the company's activity chain
+pub async fn create_activity_checkpoint(ctx: UserContext) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
+    let company_id = ctx.strict_company_id()?;
+
+    match activity_chain::create_checkpoint(company_id).await? {
+        Some(checkpoint) => Ok(ApiResponse::created(checkpoint)),
+        None => Ok(ApiResponse::success_message(
+            "Nothing was logged since the last checkpoint",
+        )),
+    }
+}
+
+/// Months of activity the company keeps before it is archived
+pub async fn get_activity_retention(ctx: UserContext) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::ACTIVITY_VIEW)?;
+    let company_id = ctx.strict_company_id()?;
+
+    let months = company_repo::get_activity_retention_months(company_id)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::NotFound("Company not found".to_string()))?;
+
+    Ok(ApiResponse::success(ActivityRetention { months }))
+}
+
+pub async fn update_activity_retention(
+    ctx: UserContext,
+    input: Json<ActivityRetention>,
+    req_info: RequestInfo,
+) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
+    let company_id = ctx.strict_company_id()?;
+    let user_id = ctx.user_id();
+
+    let months = input.months;
+    if !(activity_archive::MIN_RETENTION_MONTHS..=activity_archive::MAX_RETENTION_MONTHS)
+        .contains(&months)
+    {
+        return Err(AppError::BadRequest(format!(
+            "Retention must be between {} and {} months",
+            activity_archive::MIN_RETENTION_MONTHS,
+            activity_archive::MAX_RETENTION_MONTHS
+        ))
+        .into());
+    }
+
+    let previous = company_repo::get_activity_retention_months(company_id)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::NotFound("Company not found".to_string()))?;
+
+    let retention = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let months = company_repo::update_activity_retention_months(tx, company_id, months)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Company not found".to_string()))?;
+            let retention = ActivityRetention { months };
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                ActivityType::SYSTEM.to_string(),
+                EntityType::COMPANY.to_string(),
+                company_id,
+                Action::UPDATED.to_string(),
+                format!("Activity retention set to {} months", months),
+                Some(activity_logger::with_changes(
+                    activity_logger::metadata(vec![]),
+                    &ActivityRetention { months: previous },
+                    &retention,
+                )),
+                &req_info,
+            )
+            .await?;
+
+            Ok(retention)
+        })
+    })
+    .await?;
+
+    Ok(ApiResponse::success(retention))
+}
+
+/// Months of activity that were archived to disk and removed from the log
+pub async fn get_activity_archives(ctx: UserContext) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::ACTIVITY_VIEW)?;
+    let company_id = ctx.strict_company_id()?;
+
+    let archives = activity_repo::get_archives(company_id)
+        .await
+        .map_err(AppError::from)?;
+
+    Ok(ApiResponse::success(archives))
+}
+
+/// Download one archive as the gzipped NDJSON file it was written to
+pub async fn download_activity_archive(ctx: UserContext, path: Path<Uuid>) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::ACTIVITY_VIEW)?;
+    let company_id = ctx.strict_company_id()?;
+    let archive_id = path.into_inner();
+
+    let archive = activity_repo::find_archive(company_id, archive_id)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::NotFound("Archive not found".to_string()))?;
+
+    let file_path = activity_archive::archive_path(
+        std::path::Path::new(&config().activity_archive_dir),
+        &archive,
+    );
+    let contents = tokio::fs::read(&file_path).await.map_err(|e| {
+        log::error!(
+            "Activity archive {} is unreadable at {}: {}",
+            archive.id,
+            file_path.display(),
+            e
+        );
+        AppError::NotFound("Archive file is missing".to_string())
+    })?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/gzip")
+        .insert_header((
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"activity-{}.ndjson.gz\"",
+                archive.period_start.format("%Y-%m")
+            ),
+        ))
+        .insert_header(("Digest", format!("sha-256={}", archive.sha256)))
+        .body(contents))
+}
+
 // Utilities
 async fn get_location_for_team(
     team_id: Uuid,
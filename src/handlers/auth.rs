@@ -11,17 +11,26 @@ use crate::{
     config::config,
     database::{
         models::{
-            Action, AddEmployeeToCompanyInput, CompanyInfo, CreateInviteInput, CreateUserInput,
-            ForgotPasswordInput, GetInviteResponse, InviteTokenStatus, LoginInput,
-            ResetPasswordInput, User,
+            Action, AddEmployeeToCompanyInput, ChangeEmailInput, CompanyInfo, CreateInviteInput,
+            CreateUserInput, EraseAccountInput, ForgotPasswordInput, GetInviteResponse,
+            ImpersonationInfo, InviteTokenStatus, LoginInput, Permission, RefreshTokenInput,
+            ResetPasswordInput, SessionResponse, SsoCallbackInput, SsoLinkInput,
+            TwoFactorCodeInput, TwoFactorEnabledResponse, TwoFactorLoginInput, User,
+            VerifyEmailInput,
+        },
+        repositories::{
+            company as company_repo, invite as invite_repo, session as session_repo,
+            user as user_repo,
         },
-        repositories::{company as company_repo, invite as invite_repo, user as user_repo},
         transaction::DatabaseTransaction,
     },
     error::AppError,
     handlers::shared::ApiResponse,
     middleware::{CacheLayer, cache::InvalidationContext, request_info::RequestInfo},
-    services::{activity_logger, auth},
+    services::{
+        activity_logger, auth, email_verification, impersonation, invites, jwt_keys, personal_data,
+        sso, two_factor,
+    },
     user_context::UserContext,
 };
 
@@ -30,6 +39,10 @@ use crate::{
 pub struct MeResponse {
     pub user: User,
     pub companies: Vec<CompanyInfo>,
+    pub permissions: Vec<String>, // Granted in the current company
+    pub impersonating: bool,      // An admin is acting as this user; show a banner
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub impersonation: Option<ImpersonationInfo>,
 }
 
 #[derive(Debug, Serialize)]
@@ -41,14 +54,17 @@ pub struct InviteTokenResponse {
 
 pub async fn register(
     request: Json<CreateUserInput>,
+    req_info: RequestInfo,
     cache: Data<CacheLayer>,
 ) -> Result<HttpResponse> {
     let register_request = request.into_inner();
 
-    let response = auth::register(register_request).await.map_err(|e| {
-        log::error!("Failed to register user: {}", e);
-        AppError::internal_server_error_message(e.to_string())
-    })?;
+    let response = auth::register(register_request, &req_info)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to register user: {}", e);
+            AppError::internal_server_error_message(e.to_string())
+        })?;
 
     // Smart cache invalidation - register (minimal - new user affects stats)
     cache
@@ -61,10 +77,14 @@ pub async fn register(
     Ok(ApiResponse::success(response))
 }
 
-pub async fn login(request: Json<LoginInput>, cache: Data<CacheLayer>) -> Result<HttpResponse> {
+pub async fn login(
+    request: Json<LoginInput>,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
     let login_request = request.into_inner();
 
-    let response = auth::login(login_request).await.map_err(|e| {
+    let response = auth::login(login_request, &req_info).await.map_err(|e| {
         log::error!("Failed to login user: {}", e);
         AppError::from(e)
     })?;
@@ -78,6 +98,417 @@ pub async fn login(request: Json<LoginInput>, cache: Data<CacheLayer>) -> Result
     Ok(ApiResponse::success(response))
 }
 
+pub async fn login_two_factor(
+    request: Json<TwoFactorLoginInput>,
+    req_info: RequestInfo,
+) -> Result<HttpResponse> {
+    let response = auth::login_with_two_factor(request.into_inner(), &req_info)
+        .await
+        .map_err(|e| {
+            log::warn!("Failed two-factor login: {}", e);
+            e
+        })?;
+
+    Ok(ApiResponse::success(response))
+}
+
+pub async fn sso_start(path: Path<Uuid>) -> Result<HttpResponse> {
+    let company_id = path.into_inner();
+
+    let response = sso::start_login(company_id).await.map_err(|e| {
+        log::warn!(
+            "Failed to start SSO login for company {}: {}",
+            company_id,
+            e
+        );
+        e
+    })?;
+
+    Ok(ApiResponse::success(response))
+}
+
+pub async fn sso_callback(
+    input: Json<SsoCallbackInput>,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    let response = sso::complete_login(input.into_inner(), &req_info)
+        .await
+        .map_err(|e| {
+            log::warn!("Failed SSO login: {}", e);
+            e
+        })?;
+
+    // SSO may have provisioned a user or company membership
+    cache
+        .invalidate("users", &InvalidationContext::default())
+        .await;
+
+    Ok(ApiResponse::success(response))
+}
+
+/// Confirm with the account's password that an SSO identity may sign in to it
+pub async fn sso_link(
+    input: Json<SsoLinkInput>,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    let response = sso::confirm_link(input.into_inner(), &req_info)
+        .await
+        .map_err(|e| {
+            log::warn!("Failed SSO link: {}", e);
+            e
+        })?;
+
+    // Linking may have added a company membership
+    cache
+        .invalidate("users", &InvalidationContext::default())
+        .await;
+
+    Ok(ApiResponse::success(response))
+}
+
+pub async fn refresh_token(
+    input: Json<RefreshTokenInput>,
+    req_info: RequestInfo,
+) -> Result<HttpResponse> {
+    let response = auth::refresh(&input.refresh_token, &req_info)
+        .await
+        .map_err(|e| {
+            log::warn!("Failed to refresh token: {}", e);
+            e
+        })?;
+
+    Ok(ApiResponse::success(response))
+}
+
+pub async fn logout(
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+
+    let session_id = ctx.session_id.ok_or_else(|| {
+        AppError::BadRequest("Token is not associated with a session".to_string())
+    })?;
+
+    auth::logout(user_id, session_id).await.map_err(|e| {
+        log::error!("Failed to log out user {}: {}", user_id, e);
+        e
+    })?;
+
+    if let Some(company_id) = ctx.company_id() {
+        DatabaseTransaction::run(|tx| {
+            Box::pin(async move {
+                activity_logger::log_auth_activity(
+                    tx,
+                    company_id,
+                    Some(user_id),
+                    Action::LOGOUT,
+                    format!("User {} logged out", ctx.user_email()),
+                    Some(activity_logger::metadata(vec![(
+                        "session_id",
+                        session_id.to_string(),
+                    )])),
+                    &req_info,
+                )
+                .await?;
+                Ok(())
+            })
+        })
+        .await?;
+    }
+
+    cache
+        .invalidate(
+            "users",
+            &InvalidationContext {
+                user_id: Some(user_id),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    Ok(ApiResponse::success_message("Logged out successfully."))
+}
+
+pub async fn logout_all(
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    ctx.requires_not_impersonating()?;
+
+    let user_id = ctx.user_id();
+
+    let revoked = auth::logout_all(user_id).await.map_err(|e| {
+        log::error!("Failed to revoke sessions for user {}: {}", user_id, e);
+        e
+    })?;
+
+    if let Some(company_id) = ctx.company_id() {
+        DatabaseTransaction::run(|tx| {
+            Box::pin(async move {
+                activity_logger::log_auth_activity(
+                    tx,
+                    company_id,
+                    Some(user_id),
+                    Action::LOGOUT,
+                    format!("User {} logged out of all sessions", ctx.user_email()),
+                    Some(activity_logger::metadata(vec![(
+                        "revoked_sessions",
+                        revoked.to_string(),
+                    )])),
+                    &req_info,
+                )
+                .await?;
+                Ok(())
+            })
+        })
+        .await?;
+    }
+
+    cache
+        .invalidate(
+            "users",
+            &InvalidationContext {
+                user_id: Some(user_id),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    Ok(ApiResponse::success(json!({ "revokedSessions": revoked })))
+}
+
+pub async fn get_sessions(ctx: UserContext) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+
+    let sessions = session_repo::get_active_sessions_for_user(user_id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to get sessions for user {}: {}", user_id, e);
+            AppError::DatabaseError(e)
+        })?;
+
+    let response: Vec<SessionResponse> = sessions
+        .into_iter()
+        .map(|session| SessionResponse {
+            is_current: ctx.session_id == Some(session.id),
+            session,
+        })
+        .collect();
+
+    Ok(ApiResponse::success(response))
+}
+
+pub async fn revoke_session(
+    path: Path<Uuid>,
+    ctx: UserContext,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    ctx.requires_not_impersonating()?;
+
+    let session_id = path.into_inner();
+    let user_id = ctx.user_id();
+
+    auth::logout(user_id, session_id).await.map_err(|e| {
+        log::error!(
+            "Failed to revoke session {} for user {}: {}",
+            session_id,
+            user_id,
+            e
+        );
+        e
+    })?;
+
+    cache
+        .invalidate(
+            "users",
+            &InvalidationContext {
+                user_id: Some(user_id),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    Ok(ApiResponse::success_message(
+        "Session revoked successfully.",
+    ))
+}
+
+pub async fn get_two_factor_status(ctx: UserContext) -> Result<HttpResponse> {
+    let status = two_factor::status(ctx.user_id(), ctx.company.as_ref()).await?;
+
+    Ok(ApiResponse::success(status))
+}
+
+pub async fn setup_two_factor(ctx: UserContext) -> Result<HttpResponse> {
+    ctx.requires_not_impersonating()?;
+
+    let setup = two_factor::begin_setup(&ctx.user).await.map_err(|e| {
+        log::error!(
+            "Failed to start 2FA setup for user {}: {}",
+            ctx.user_id(),
+            e
+        );
+        e
+    })?;
+
+    Ok(ApiResponse::success(setup))
+}
+
+pub async fn enable_two_factor(
+    ctx: UserContext,
+    input: Json<TwoFactorCodeInput>,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    ctx.requires_not_impersonating()?;
+
+    let user_id = ctx.user_id();
+
+    let recovery_codes = two_factor::confirm_setup(user_id, &input.code, ctx.session_id)
+        .await
+        .map_err(|e| {
+            log::warn!("Failed to enable 2FA for user {}: {}", user_id, e);
+            e
+        })?;
+
+    if let Some(company_id) = ctx.company_id() {
+        let user_email = ctx.user_email().to_string();
+        DatabaseTransaction::run(|tx| {
+            Box::pin(async move {
+                activity_logger::log_auth_activity(
+                    tx,
+                    company_id,
+                    Some(user_id),
+                    Action::TWO_FACTOR_ENABLED,
+                    format!("User {} enabled two-factor authentication", user_email),
+                    None,
+                    &req_info,
+                )
+                .await?;
+                Ok(())
+            })
+        })
+        .await?;
+    }
+
+    // The current session now counts as verified; hand back a token that says so
+    let token = match ctx.session_id {
+        Some(session_id) => {
+            Some(auth::token_for_session(&ctx.user, ctx.company.as_ref(), session_id).await?)
+        }
+        None => None,
+    };
+
+    cache
+        .invalidate(
+            "auth",
+            &InvalidationContext {
+                user_id: Some(user_id),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    Ok(ApiResponse::success(TwoFactorEnabledResponse {
+        recovery_codes,
+        token,
+    }))
+}
+
+pub async fn disable_two_factor(
+    ctx: UserContext,
+    input: Json<TwoFactorCodeInput>,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    ctx.requires_not_impersonating()?;
+
+    let user_id = ctx.user_id();
+
+    two_factor::disable(user_id, &input.code, ctx.company.as_ref())
+        .await
+        .map_err(|e| {
+            log::warn!("Failed to disable 2FA for user {}: {}", user_id, e);
+            e
+        })?;
+
+    if let Some(company_id) = ctx.company_id() {
+        let user_email = ctx.user_email().to_string();
+        DatabaseTransaction::run(|tx| {
+            Box::pin(async move {
+                activity_logger::log_auth_activity(
+                    tx,
+                    company_id,
+                    Some(user_id),
+                    Action::TWO_FACTOR_DISABLED,
+                    format!("User {} disabled two-factor authentication", user_email),
+                    None,
+                    &req_info,
+                )
+                .await?;
+                Ok(())
+            })
+        })
+        .await?;
+    }
+
+    cache
+        .invalidate(
+            "auth",
+            &InvalidationContext {
+                user_id: Some(user_id),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    Ok(ApiResponse::success_message(
+        "Two-factor authentication disabled.",
+    ))
+}
+
+pub async fn regenerate_recovery_codes(
+    ctx: UserContext,
+    input: Json<TwoFactorCodeInput>,
+) -> Result<HttpResponse> {
+    ctx.requires_not_impersonating()?;
+
+    let user_id = ctx.user_id();
+
+    let recovery_codes = two_factor::regenerate_recovery_codes(user_id, &input.code)
+        .await
+        .map_err(|e| {
+            log::warn!(
+                "Failed to regenerate recovery codes for user {}: {}",
+                user_id,
+                e
+            );
+            e
+        })?;
+
+    Ok(ApiResponse::success(TwoFactorEnabledResponse {
+        recovery_codes,
+        token: None,
+    }))
+}
+
+/// Serve the public verification keys as a standard JWK Set
+pub async fn jwks() -> Result<HttpResponse> {
+    let key_ring = jwt_keys::key_ring().map_err(|e| {
+        log::error!("Failed to load JWT keys: {}", e);
+        AppError::from(e)
+    })?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(("Cache-Control", "public, max-age=300"))
+        .json(key_ring.jwks()))
+}
+
 pub async fn me(ctx: UserContext) -> Result<HttpResponse> {
     let user_id = ctx.user_id();
     // Get the user's information
@@ -91,11 +522,84 @@ pub async fn me(ctx: UserContext) -> Result<HttpResponse> {
             AppError::DatabaseError(e)
         })?;
 
-    let response = MeResponse { user, companies };
+    let impersonation = impersonation::info(&ctx).await?;
+
+    let response = MeResponse {
+        user,
+        companies,
+        permissions: ctx.permissions,
+        impersonating: impersonation.is_some(),
+        impersonation,
+    };
 
     Ok(ApiResponse::success(response))
 }
 
+/// Download everything stored about the current user as a JSON file
+pub async fn export_personal_data(ctx: UserContext) -> Result<HttpResponse> {
+    let export = personal_data::export(&ctx).await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header((
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"shiftlinkr-data-{}.json\"",
+                ctx.user_id()
+            ),
+        ))
+        .json(export))
+}
+
+/// Anonymize the current user's account and erase their personal data
+pub async fn erase_account(
+    ctx: UserContext,
+    input: Json<EraseAccountInput>,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+    let company_ids = personal_data::erase(&ctx, &input, &req_info).await?;
+
+    for company_id in company_ids {
+        cache
+            .invalidate(
+                "users",
+                &InvalidationContext {
+                    company_id: Some(company_id),
+                    user_id: Some(user_id),
+                    ..Default::default()
+                },
+            )
+            .await;
+    }
+
+    Ok(ApiResponse::success_message(
+        "Your account and personal data have been erased.",
+    ))
+}
+
+/// End the impersonation the request was made with and invalidate its token
+pub async fn end_impersonation(
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    let session = impersonation::end(&ctx, &req_info).await?;
+
+    cache
+        .invalidate(
+            "users",
+            &InvalidationContext {
+                company_id: Some(session.company_id),
+                user_id: Some(session.target_user_id),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    Ok(ApiResponse::success_message("Impersonation ended."))
+}
+
 pub async fn forgot_password(request: Json<ForgotPasswordInput>) -> Result<HttpResponse> {
     let token = auth::forgot_password(&request.email).await.map_err(|e| {
         log::error!("Failed to send password reset email: {}", e);
@@ -132,6 +636,61 @@ pub async fn reset_password(
     ))
 }
 
+pub async fn verify_email(
+    input: Json<VerifyEmailInput>,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    let user = email_verification::verify_email(&input.token, &req_info)
+        .await
+        .map_err(|e| {
+            log::warn!("Failed to verify email: {}", e);
+            e
+        })?;
+
+    // Smart cache invalidation - verify_email (the user's email may have changed)
+    cache
+        .invalidate("users", &InvalidationContext::default())
+        .await;
+
+    Ok(ApiResponse::success(user))
+}
+
+pub async fn resend_verification(ctx: UserContext) -> Result<HttpResponse> {
+    let token = email_verification::resend(&ctx.user).await?;
+
+    if config().environment != "production" {
+        return Ok(ApiResponse::success(json!({ "token": token })));
+    }
+
+    Ok(ApiResponse::success_message(
+        "Verification email sent successfully. Please check your inbox.",
+    ))
+}
+
+pub async fn change_email(ctx: UserContext, input: Json<ChangeEmailInput>) -> Result<HttpResponse> {
+    ctx.requires_not_impersonating()?;
+
+    let token = email_verification::request_email_change(&ctx.user, &input)
+        .await
+        .map_err(|e| {
+            log::warn!(
+                "Failed to start email change for user {}: {}",
+                ctx.user_id(),
+                e
+            );
+            e
+        })?;
+
+    if config().environment != "production" {
+        return Ok(ApiResponse::success(json!({ "token": token })));
+    }
+
+    Ok(ApiResponse::success_message(
+        "Verification email sent to the new address. Your email changes once it is confirmed.",
+    ))
+}
+
 pub async fn create_invite(
     ctx: UserContext,
     input: Json<CreateInviteInput>,
@@ -140,7 +699,7 @@ pub async fn create_invite(
 ) -> Result<HttpResponse> {
     let user_id = ctx.user_id();
 
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::EMPLOYEES_INVITE)?;
 
     let company_id = ctx.company_id().ok_or_else(|| {
         log::error!("User {} does not belong to any company", user_id);
@@ -175,11 +734,7 @@ pub async fn create_invite(
             )
             .await?;
 
-            let invite_link = format!(
-                "{}/auth/invite/{}",
-                config().client_base_url,
-                invite_token.token
-            );
+            let invite_link = invites::invite_link(&invite_token);
 
             // Log invite creation activity
             let metadata = activity_logger::metadata(vec![
@@ -216,10 +771,7 @@ pub async fn create_invite(
     })
     .await?;
 
-    // TODO: Send invite email to the user
-    // This is a placeholder for the actual email sending logic
-    // You can use a service like SendGrid, Mailgun, etc. to send the email
-    // For now, we will just return the invite link in the response
+    invites::send_invite(&invite_token, &ctx.user.name).await;
 
     let invite_token_response = InviteTokenResponse {
         invite_link,
@@ -324,6 +876,14 @@ pub async fn accept_invite(
         return Err(AppError::Forbidden("You cannot accept this invite".to_string()).into());
     }
 
+    // Invites go to an address, so only someone who has proven they own it may accept
+    if !user.is_email_verified() {
+        return Err(AppError::Forbidden(
+            "Verify your email address before accepting invites".to_string(),
+        )
+        .into());
+    }
+
     let accepting_user = match DatabaseTransaction::run(|tx| {
         Box::pin(async move {
             // Check if the user is already part of the company
@@ -426,7 +986,13 @@ pub async fn accept_invite(
             AppError::DatabaseError(e)
         })?;
 
-    let response = MeResponse { user, companies };
+    let response = MeResponse {
+        user,
+        companies,
+        permissions: ctx.permissions,
+        impersonating: false,
+        impersonation: None,
+    };
 
     // Smart cache invalidation - accept_invite
     cache
@@ -579,6 +1145,13 @@ pub async fn reject_invite(
 }
 
 pub async fn get_my_invites(ctx: UserContext) -> Result<HttpResponse> {
+    if !ctx.user.is_email_verified() {
+        return Err(AppError::Forbidden(
+            "Verify your email address before viewing invites".to_string(),
+        )
+        .into());
+    }
+
     let email_address = ctx.user_email();
 
     let invites = invite_repo::get_invites_by_email(email_address)
@@ -593,6 +1166,8 @@ pub async fn switch_company(
     ctx: UserContext,
     cache: Data<CacheLayer>,
 ) -> Result<HttpResponse> {
+    ctx.requires_not_impersonating()?;
+
     let new_company_id = path.into_inner();
     let user_id = ctx.user_id();
 
@@ -611,7 +1186,7 @@ pub async fn switch_company(
             AppError::PermissionDenied("You don't have access to this company".to_string())
         })?;
 
-    let response = auth::switch_company(user_id, new_company_id)
+    let response = auth::switch_company(user_id, new_company_id, ctx.session_id)
         .await
         .map_err(|e| {
             log::error!("Failed to switch company for user {}: {}", user_id, e);
@@ -1,22 +1,29 @@
 use actix_web::{
     HttpResponse, Result,
-    web::{Data, Json, Path},
+    web::{Data, Json, Path, Query},
 };
 use uuid::Uuid;
 
 use crate::{
     database::{
         models::{
-            AddEmployeeToCompanyInput, CompanyInfo, CompanyRole, CreateCompanyInput,
-            activity::Action,
+            AddEmployeeToCompanyInput, AssignRoleInput, CompanyInfo, CompanyRole,
+            CompanyTimeOffSettingsInput, CreateApiKeyInput, CreateCompanyInput,
+            CreateInviteResponse, EmployeeImportQuery, InviteListQuery, InviteToken,
+            InviteTokenStatus, ManagerScopeInput, ManagerScopeResponse, Permission, Role,
+            RoleInput, SsoConfigInput, SsoDomainInput, UpdateCompanySecurityInput,
+            activity::{Action, ActivityType, EntityType},
+        },
+        repositories::{
+            api_key as api_key_repo, company as company_repo, invite as invite_repo,
+            manager_scope as manager_scope_repo, role as role_repo, sso as sso_repo,
         },
-        repositories::company as company_repo,
         transaction::DatabaseTransaction,
     },
     error::AppError,
     handlers::shared::ApiResponse,
     middleware::{CacheLayer, cache::InvalidationContext, request_info::RequestInfo},
-    services::activity_logger,
+    services::{activity_logger, api_keys, employee_import, invites, manager_scope, roles, sso},
     user_context::UserContext,
 };
 
@@ -135,7 +142,9 @@ pub async fn create_company(
         address: company.address,
         logo_url: company.logo_url,
         timezone: company.timezone,
+        require_two_factor: company.require_two_factor,
         role: CompanyRole::Admin,
+        role_id: None,
         is_primary: true,
         hire_date: None,
         created_at: company.created_at,
@@ -166,7 +175,7 @@ pub async fn add_employee_to_company(
     let employee_user_id = input.user_id;
     let employee_role = input.role.clone();
 
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::EMPLOYEES_MANAGE)?;
 
     let company_id = ctx.strict_company_id()?;
 
@@ -261,6 +270,37 @@ pub async fn add_employee_to_company(
     ))
 }
 
+/// Import employees from a CSV body with a header line. With `dryRun=true` the rows are
+/// only validated; otherwise they are imported if every row is valid.
+pub async fn import_employees(
+    query: Query<EmployeeImportQuery>,
+    body: String,
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::EMPLOYEES_MANAGE)?;
+
+    let company_id = ctx.strict_company_id()?;
+    let dry_run = query.dry_run.unwrap_or(false);
+
+    let response = employee_import::import_employees(&ctx, &body, dry_run, &req_info).await?;
+
+    if response.imported {
+        cache
+            .invalidate(
+                "users",
+                &InvalidationContext {
+                    company_id: Some(company_id),
+                    ..Default::default()
+                },
+            )
+            .await;
+    }
+
+    Ok(ApiResponse::success(response))
+}
+
 pub async fn remove_employee_from_company(
     path: Path<Uuid>,
     ctx: UserContext,
@@ -271,7 +311,7 @@ pub async fn remove_employee_from_company(
 
     let target_user_id = path.into_inner();
 
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::EMPLOYEES_MANAGE)?;
 
     let company_id = ctx.strict_company_id()?;
 
@@ -355,10 +395,21 @@ pub async fn update_employee_role(
 
     let target_user_id = path.into_inner();
 
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::EMPLOYEES_MANAGE)?;
 
     let company_id = ctx.strict_company_id()?;
 
+    let previous_role = company_repo::find_user_company_info_by_id(target_user_id, company_id)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "User {} not found in company {}",
+                target_user_id, company_id
+            ))
+        })?
+        .role;
+
     DatabaseTransaction::run(|tx| {
         Box::pin(async move {
             company_repo::update_employee_role(tx, company_id, target_user_id, &input)
@@ -376,6 +427,11 @@ pub async fn update_employee_role(
                 (&"user_id".to_string(), target_user_id.to_string()),
                 (&"new_role".to_string(), input.to_string()),
             ]);
+            let metadata = activity_logger::with_changes(
+                metadata,
+                &serde_json::json!({ "role": previous_role }),
+                &serde_json::json!({ "role": *input }),
+            );
 
             activity_logger::log_user_activity(
                 tx,
@@ -424,3 +480,1142 @@ pub async fn update_employee_role(
         "Employee role updated successfully",
     ))
 }
+
+pub async fn update_security_settings(
+    input: Json<UpdateCompanySecurityInput>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    // Don't let an admin lock themselves out of the policy they are turning on
+    if input.require_two_factor && !ctx.mfa_verified {
+        return Err(AppError::BadRequest(
+            "Sign in with two-factor authentication before requiring it for the company"
+                .to_string(),
+        )
+        .into());
+    }
+
+    let company = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let company =
+                company_repo::update_require_two_factor(tx, company_id, input.require_two_factor)
+                    .await?
+                    .ok_or_else(|| AppError::NotFound("Company not found".to_string()))?;
+
+            let metadata = activity_logger::metadata(vec![(
+                "require_two_factor",
+                input.require_two_factor.to_string(),
+            )]);
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                "company_management".to_string(),
+                "company".to_string(),
+                company_id,
+                Action::UPDATED.to_string(),
+                format!(
+                    "Two-factor requirement {} by user {}",
+                    if input.require_two_factor {
+                        "enabled"
+                    } else {
+                        "disabled"
+                    },
+                    user_id
+                ),
+                Some(metadata),
+                &req_info,
+            )
+            .await?;
+
+            Ok(company)
+        })
+    })
+    .await?;
+
+    cache
+        .invalidate(
+            "users",
+            &InvalidationContext {
+                company_id: Some(company_id),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    Ok(ApiResponse::success(company))
+}
+
+/// Workday length and negative balance setting used when deducting time off
+pub async fn get_time_off_settings(ctx: UserContext) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::PTO_VIEW)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let settings = company_repo::get_time_off_settings(company_id)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::NotFound("Company not found".to_string()))?;
+
+    Ok(ApiResponse::success(settings))
+}
+
+pub async fn update_time_off_settings(
+    input: Json<CompanyTimeOffSettingsInput>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    if input.workday_hours <= 0.into() || input.workday_hours > 24.into() {
+        return Err(AppError::BadRequest(
+            "workdayHours must be more than 0 and at most 24".to_string(),
+        )
+        .into());
+    }
+
+    let settings = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let settings = company_repo::update_time_off_settings(tx, company_id, &input)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Company not found".to_string()))?;
+
+            let metadata = activity_logger::metadata(vec![
+                (
+                    "workday_hours",
+                    settings.workday_hours.normalized().to_string(),
+                ),
+                (
+                    "allow_negative_pto",
+                    settings.allow_negative_pto.to_string(),
+                ),
+            ]);
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                "company_management".to_string(),
+                "company".to_string(),
+                company_id,
+                Action::UPDATED.to_string(),
+                format!("Time-off settings updated by user {}", user_id),
+                Some(metadata),
+                &req_info,
+            )
+            .await?;
+
+            Ok(settings)
+        })
+    })
+    .await?;
+
+    Ok(ApiResponse::success(settings))
+}
+
+pub async fn get_sso_config(ctx: UserContext) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let sso_config = sso_repo::find_config_by_company_id(company_id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to get SSO config for company {}: {}", company_id, e);
+            AppError::DatabaseError(e)
+        })?
+        .ok_or_else(|| AppError::NotFound("Single sign-on is not configured".to_string()))?;
+
+    Ok(ApiResponse::success(sso_config))
+}
+
+pub async fn update_sso_config(
+    input: Json<SsoConfigInput>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let mut input = input.into_inner();
+    input.issuer = input.issuer.trim().to_string();
+    input.client_id = input.client_id.trim().to_string();
+    input.allowed_domains = sso::normalize_domains(&input.allowed_domains);
+    if input.issuer.is_empty() || input.client_id.is_empty() {
+        return Err(AppError::BadRequest("Issuer and client id are required".to_string()).into());
+    }
+
+    // The secret is write-only; keep the stored one unless a new one is given
+    let existing = sso_repo::find_config_by_company_id(company_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    let client_secret = match (input.client_secret.take(), existing) {
+        (Some(secret), _) if !secret.is_empty() => secret,
+        (_, Some(existing)) => existing.client_secret,
+        _ => {
+            return Err(AppError::BadRequest("Client secret is required".to_string()).into());
+        }
+    };
+
+    let endpoints = sso::discover(&input.issuer).await?;
+
+    let sso_config = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let sso_config =
+                sso_repo::upsert_config(tx, company_id, &input, &client_secret, &endpoints).await?;
+
+            let metadata = activity_logger::metadata(vec![
+                ("issuer", sso_config.issuer.clone()),
+                ("client_id", sso_config.client_id.clone()),
+                ("allowed_domains", sso_config.allowed_domains.join(",")),
+                ("default_role", sso_config.default_role.to_string()),
+                ("enabled", sso_config.enabled.to_string()),
+            ]);
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                "company_management".to_string(),
+                "company".to_string(),
+                company_id,
+                Action::UPDATED.to_string(),
+                format!("Single sign-on configured by user {}", user_id),
+                Some(metadata),
+                &req_info,
+            )
+            .await?;
+
+            Ok(sso_config)
+        })
+    })
+    .await?;
+
+    Ok(ApiResponse::success(sso_config))
+}
+
+pub async fn delete_sso_config(ctx: UserContext, req_info: RequestInfo) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            sso_repo::delete_config(tx, company_id)
+                .await?
+                .ok_or_else(|| {
+                    AppError::NotFound("Single sign-on is not configured".to_string())
+                })?;
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                "company_management".to_string(),
+                "company".to_string(),
+                company_id,
+                Action::DELETED.to_string(),
+                format!("Single sign-on removed by user {}", user_id),
+                None,
+                &req_info,
+            )
+            .await?;
+
+            Ok(())
+        })
+    })
+    .await?;
+
+    Ok(ApiResponse::success_message(
+        "Single sign-on configuration removed.",
+    ))
+}
+
+pub async fn get_sso_domains(ctx: UserContext) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let domains = sso_repo::get_domains(company_id).await.map_err(|e| {
+        log::error!(
+            "Failed to get SSO domains for company {}: {}",
+            company_id,
+            e
+        );
+        AppError::DatabaseError(e)
+    })?;
+
+    Ok(ApiResponse::success(domains))
+}
+
+pub async fn add_sso_domain(
+    input: Json<SsoDomainInput>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let domain = sso::normalize_domain(&input.domain)?;
+    let existing = sso_repo::get_domains(company_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    if existing.iter().any(|existing| existing.domain == domain) {
+        return Err(AppError::BadRequest(format!("{} has already been added", domain)).into());
+    }
+
+    let verification_token = Uuid::new_v4().simple().to_string();
+
+    let sso_domain = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let sso_domain =
+                sso_repo::create_domain(tx, company_id, &domain, &verification_token).await?;
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                "company_management".to_string(),
+                "company".to_string(),
+                company_id,
+                Action::CREATED.to_string(),
+                format!("SSO domain {} added by user {}", sso_domain.domain, user_id),
+                Some(activity_logger::metadata(vec![(
+                    "domain",
+                    sso_domain.domain.clone(),
+                )])),
+                &req_info,
+            )
+            .await?;
+
+            Ok(sso_domain)
+        })
+    })
+    .await?;
+
+    Ok(ApiResponse::created(sso_domain))
+}
+
+/// Check the domain's DNS for its verification record
+pub async fn verify_sso_domain(
+    path: Path<Uuid>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+    let domain_id = path.into_inner();
+
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let sso_domain = sso_repo::find_domain(company_id, domain_id)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound("SSO domain not found".to_string()))?;
+    if sso_domain.verified_at.is_some() {
+        return Ok(ApiResponse::success(sso_domain));
+    }
+
+    match sso_repo::find_verified_company(&sso_domain.domain)
+        .await
+        .map_err(AppError::DatabaseError)?
+    {
+        Some(owner) if owner != company_id => {
+            return Err(AppError::BadRequest(format!(
+                "{} is verified by another company",
+                sso_domain.domain
+            ))
+            .into());
+        }
+        _ => {}
+    }
+
+    if !sso::has_verification_record(&sso_domain).await? {
+        return Err(AppError::BadRequest(format!(
+            "No TXT record '{}' found on {}",
+            sso::verification_record(&sso_domain),
+            sso_domain.domain
+        ))
+        .into());
+    }
+
+    let sso_domain = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let sso_domain = sso_repo::mark_domain_verified(tx, domain_id).await?;
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                "company_management".to_string(),
+                "company".to_string(),
+                company_id,
+                Action::UPDATED.to_string(),
+                format!(
+                    "SSO domain {} verified by user {}",
+                    sso_domain.domain, user_id
+                ),
+                Some(activity_logger::metadata(vec![(
+                    "domain",
+                    sso_domain.domain.clone(),
+                )])),
+                &req_info,
+            )
+            .await?;
+
+            Ok(sso_domain)
+        })
+    })
+    .await?;
+
+    Ok(ApiResponse::success(sso_domain))
+}
+
+pub async fn delete_sso_domain(
+    path: Path<Uuid>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+    let domain_id = path.into_inner();
+
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let sso_domain = sso_repo::find_domain(company_id, domain_id)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound("SSO domain not found".to_string()))?;
+
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            if !sso_repo::delete_domain(tx, company_id, domain_id).await? {
+                return Err(AppError::NotFound("SSO domain not found".to_string()));
+            }
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                "company_management".to_string(),
+                "company".to_string(),
+                company_id,
+                Action::DELETED.to_string(),
+                format!(
+                    "SSO domain {} removed by user {}",
+                    sso_domain.domain, user_id
+                ),
+                None,
+                &req_info,
+            )
+            .await?;
+
+            Ok(())
+        })
+    })
+    .await?;
+
+    Ok(ApiResponse::success_message("SSO domain removed."))
+}
+
+pub async fn get_api_keys(ctx: UserContext) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let api_keys = api_key_repo::get_company_api_keys(company_id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to get API keys for company {}: {}", company_id, e);
+            AppError::DatabaseError(e)
+        })?;
+
+    Ok(ApiResponse::success(api_keys))
+}
+
+pub async fn create_api_key(
+    input: Json<CreateApiKeyInput>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let mut input = input.into_inner();
+    api_keys::validate_create_input(&mut input)?;
+
+    let created = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let created = api_keys::create(tx, company_id, user_id, &input).await?;
+
+            let metadata = activity_logger::metadata(vec![
+                ("name", created.api_key.name.clone()),
+                ("prefix", created.api_key.prefix.clone()),
+                ("scopes", created.api_key.scopes.join(",")),
+                (
+                    "rate_limit_per_minute",
+                    created.api_key.rate_limit_per_minute.to_string(),
+                ),
+            ]);
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                "company_management".to_string(),
+                EntityType::API_KEY.to_string(),
+                created.api_key.id,
+                Action::CREATED.to_string(),
+                format!(
+                    "API key '{}' created by user {}",
+                    created.api_key.name, user_id
+                ),
+                Some(metadata),
+                &req_info,
+            )
+            .await?;
+
+            Ok(created)
+        })
+    })
+    .await?;
+
+    Ok(ApiResponse::success(created))
+}
+
+pub async fn revoke_api_key(
+    path: Path<Uuid>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+    let api_key_id = path.into_inner();
+
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let api_key = api_key_repo::revoke_api_key(tx, company_id, api_key_id)
+                .await?
+                .ok_or_else(|| AppError::NotFound("API key not found".to_string()))?;
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                "company_management".to_string(),
+                EntityType::API_KEY.to_string(),
+                api_key.id,
+                Action::DELETED.to_string(),
+                format!("API key '{}' revoked by user {}", api_key.name, user_id),
+                None,
+                &req_info,
+            )
+            .await?;
+
+            Ok(())
+        })
+    })
+    .await?;
+
+    Ok(ApiResponse::success_message("API key revoked."))
+}
+
+pub async fn get_permissions(ctx: UserContext) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::ROLES_MANAGE)?;
+
+    Ok(ApiResponse::success(Permission::ALL))
+}
+
+pub async fn get_roles(ctx: UserContext) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::ROLES_MANAGE)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let roles = role_repo::get_company_roles(company_id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to get roles for company {}: {}", company_id, e);
+            AppError::DatabaseError(e)
+        })?;
+
+    Ok(ApiResponse::success(roles))
+}
+
+pub async fn create_role(
+    input: Json<RoleInput>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+
+    ctx.requires_permission(Permission::ROLES_MANAGE)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let mut input = input.into_inner();
+    roles::validate_input(company_id, None, &mut input).await?;
+
+    let role = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let role = role_repo::create_role(tx, company_id, &input).await?;
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                "company_management".to_string(),
+                EntityType::ROLE.to_string(),
+                role.id,
+                Action::CREATED.to_string(),
+                format!("Role '{}' created by user {}", role.name, user_id),
+                Some(activity_logger::metadata(vec![(
+                    "permissions",
+                    role.permissions.join(","),
+                )])),
+                &req_info,
+            )
+            .await?;
+
+            Ok(role)
+        })
+    })
+    .await?;
+
+    Ok(ApiResponse::created(role))
+}
+
+pub async fn update_role(
+    path: Path<Uuid>,
+    input: Json<RoleInput>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+    let role_id = path.into_inner();
+
+    ctx.requires_permission(Permission::ROLES_MANAGE)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let existing = find_custom_role(company_id, role_id).await?;
+
+    let mut input = input.into_inner();
+    roles::validate_input(company_id, Some(role_id), &mut input).await?;
+
+    let role = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let role = role_repo::update_role(tx, company_id, role_id, &input)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Role not found".to_string()))?;
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                "company_management".to_string(),
+                EntityType::ROLE.to_string(),
+                role.id,
+                Action::UPDATED.to_string(),
+                format!("Role '{}' updated by user {}", role.name, user_id),
+                Some(activity_logger::metadata(vec![
+                    ("previous_permissions", existing.permissions.join(",")),
+                    ("permissions", role.permissions.join(",")),
+                ])),
+                &req_info,
+            )
+            .await?;
+
+            Ok(role)
+        })
+    })
+    .await?;
+
+    // Members of the role gain or lose permissions
+    cache
+        .invalidate(
+            "users",
+            &InvalidationContext {
+                company_id: Some(company_id),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    Ok(ApiResponse::success(role))
+}
+
+pub async fn delete_role(
+    path: Path<Uuid>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+    let role_id = path.into_inner();
+
+    ctx.requires_permission(Permission::ROLES_MANAGE)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    find_custom_role(company_id, role_id).await?;
+
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            // Members of the role fall back to their built-in role
+            let role = role_repo::delete_role(tx, company_id, role_id)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Role not found".to_string()))?;
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                "company_management".to_string(),
+                EntityType::ROLE.to_string(),
+                role.id,
+                Action::DELETED.to_string(),
+                format!("Role '{}' deleted by user {}", role.name, user_id),
+                None,
+                &req_info,
+            )
+            .await?;
+
+            Ok(())
+        })
+    })
+    .await?;
+
+    cache
+        .invalidate(
+            "users",
+            &InvalidationContext {
+                company_id: Some(company_id),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    Ok(ApiResponse::success_message("Role deleted."))
+}
+
+pub async fn assign_role(
+    path: Path<Uuid>,
+    input: Json<AssignRoleInput>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+    let target_user_id = path.into_inner();
+
+    ctx.requires_permission(Permission::ROLES_MANAGE)?;
+
+    // Changing your own role could lock you out of role management
+    if target_user_id == user_id {
+        return Err(AppError::BadRequest("You cannot change your own role".to_string()).into());
+    }
+
+    let company_id = ctx.strict_company_id()?;
+
+    let role = match input.role_id {
+        Some(role_id) => Some(find_custom_role(company_id, role_id).await?),
+        None => None,
+    };
+
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            role_repo::assign_member_role(tx, company_id, target_user_id, input.role_id)
+                .await?
+                .ok_or_else(|| {
+                    AppError::NotFound(format!(
+                        "User {} not found in company {}",
+                        target_user_id, company_id
+                    ))
+                })?;
+
+            let (action, description) = match &role {
+                Some(role) => (
+                    Action::ASSIGNED,
+                    format!("User {} given role '{}'", target_user_id, role.name),
+                ),
+                None => (
+                    Action::UNASSIGNED,
+                    format!("User {} returned to their built-in role", target_user_id),
+                ),
+            };
+            let metadata = role.as_ref().map(|role| {
+                activity_logger::metadata(vec![
+                    ("role_id", role.id.to_string()),
+                    ("role_name", role.name.clone()),
+                ])
+            });
+
+            activity_logger::log_user_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                target_user_id,
+                action,
+                description,
+                metadata,
+                &req_info,
+            )
+            .await?;
+
+            Ok(())
+        })
+    })
+    .await?;
+
+    cache
+        .invalidate(
+            "users",
+            &InvalidationContext {
+                company_id: Some(company_id),
+                user_id: Some(target_user_id),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    Ok(ApiResponse::success_message(
+        "Employee role updated successfully",
+    ))
+}
+
+pub async fn get_member_scope(path: Path<Uuid>, ctx: UserContext) -> Result<HttpResponse> {
+    let target_user_id = path.into_inner();
+
+    ctx.requires_permission(Permission::ROLES_MANAGE)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let scopes = manager_scope_repo::get_member_scopes(target_user_id, company_id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to get scope for user {}: {}", target_user_id, e);
+            AppError::DatabaseError(e)
+        })?;
+
+    Ok(ApiResponse::success(ManagerScopeResponse::new(
+        target_user_id,
+        &scopes,
+    )))
+}
+
+pub async fn update_member_scope(
+    path: Path<Uuid>,
+    input: Json<ManagerScopeInput>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+    let target_user_id = path.into_inner();
+
+    ctx.requires_permission(Permission::ROLES_MANAGE)?;
+
+    // Widening your own scope would bypass the restriction
+    if target_user_id == user_id {
+        return Err(AppError::BadRequest("You cannot change your own scope".to_string()).into());
+    }
+
+    let company_id = ctx.strict_company_id()?;
+
+    let mut input = input.into_inner();
+    manager_scope::validate_input(company_id, &mut input).await?;
+
+    let scopes = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let scopes = manager_scope_repo::replace_member_scopes(
+                tx,
+                company_id,
+                target_user_id,
+                &input.location_ids,
+                &input.team_ids,
+            )
+            .await?
+            .ok_or_else(|| {
+                AppError::NotFound(format!(
+                    "User {} not found in company {}",
+                    target_user_id, company_id
+                ))
+            })?;
+
+            let join = |ids: &[Uuid]| {
+                ids.iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            };
+            let description = if scopes.is_empty() {
+                format!("User {} can now manage the whole company", target_user_id)
+            } else {
+                format!(
+                    "User {} limited to {} location(s) and {} team(s)",
+                    target_user_id,
+                    input.location_ids.len(),
+                    input.team_ids.len()
+                )
+            };
+
+            activity_logger::log_user_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                target_user_id,
+                Action::UPDATED,
+                description,
+                Some(activity_logger::metadata(vec![
+                    ("location_ids", join(&input.location_ids)),
+                    ("team_ids", join(&input.team_ids)),
+                ])),
+                &req_info,
+            )
+            .await?;
+
+            Ok(scopes)
+        })
+    })
+    .await?;
+
+    cache
+        .invalidate(
+            "users",
+            &InvalidationContext {
+                company_id: Some(company_id),
+                user_id: Some(target_user_id),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    Ok(ApiResponse::success(ManagerScopeResponse::new(
+        target_user_id,
+        &scopes,
+    )))
+}
+
+/// Look up one of the company's own roles; built-in roles cannot be changed or assigned here
+async fn find_custom_role(company_id: Uuid, role_id: Uuid) -> Result<Role, AppError> {
+    let role = role_repo::find_company_role(company_id, role_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Role not found".to_string()))?;
+
+    if role.is_builtin {
+        return Err(AppError::BadRequest(
+            "Built-in roles cannot be modified or assigned as custom roles".to_string(),
+        ));
+    }
+
+    Ok(role)
+}
+
+pub async fn get_invites(query: Query<InviteListQuery>, ctx: UserContext) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::EMPLOYEES_INVITE)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let invites =
+        invite_repo::get_company_invites(company_id, query.status.as_ref(), query.email.as_deref())
+            .await
+            .map_err(|e| {
+                log::error!("Failed to get invites for company {}: {}", company_id, e);
+                AppError::DatabaseError(e)
+            })?;
+
+    Ok(ApiResponse::success(invites))
+}
+
+pub async fn revoke_invite(
+    path: Path<Uuid>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+    let invite_id = path.into_inner();
+
+    ctx.requires_permission(Permission::EMPLOYEES_INVITE)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let invite = find_open_invite(company_id, invite_id).await?;
+
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            if !invite_repo::revoke_invite_token(tx, &invite.token).await? {
+                return Err(AppError::BadRequest(
+                    "Invite has already been answered".to_string(),
+                ));
+            }
+
+            let metadata = activity_logger::metadata(vec![("email", invite.email.clone())]);
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                ActivityType::USER_MANAGEMENT.to_string(),
+                EntityType::INVITE.to_string(),
+                invite.id,
+                Action::INVITE_REVOKED.to_string(),
+                format!("Invite for email {} revoked", invite.email),
+                Some(metadata),
+                &req_info,
+            )
+            .await?;
+
+            Ok(())
+        })
+    })
+    .await?;
+
+    cache
+        .invalidate(
+            "users",
+            &InvalidationContext {
+                company_id: Some(company_id),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    Ok(ApiResponse::success_message("Invite revoked."))
+}
+
+pub async fn resend_invite(
+    path: Path<Uuid>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+    let invite_id = path.into_inner();
+
+    ctx.requires_permission(Permission::EMPLOYEES_INVITE)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    find_open_invite(company_id, invite_id).await?;
+
+    let invite = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            // The old link stops working once the token is replaced
+            let invite = invite_repo::renew_invite_token(tx, invite_id)
+                .await?
+                .ok_or_else(|| {
+                    AppError::BadRequest("Invite has already been answered".to_string())
+                })?;
+
+            let metadata = activity_logger::metadata(vec![
+                ("email", invite.email.clone()),
+                ("expires_at", invite.expires_at.to_rfc3339()),
+            ]);
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                ActivityType::USER_MANAGEMENT.to_string(),
+                EntityType::INVITE.to_string(),
+                invite.id,
+                Action::INVITE_RESENT.to_string(),
+                format!("Invite for email {} resent", invite.email),
+                Some(metadata),
+                &req_info,
+            )
+            .await?;
+
+            Ok(invite)
+        })
+    })
+    .await?;
+
+    invites::send_invite(&invite, &ctx.user.name).await;
+
+    cache
+        .invalidate(
+            "users",
+            &InvalidationContext {
+                company_id: Some(company_id),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    Ok(ApiResponse::success(CreateInviteResponse {
+        invite_link: invites::invite_link(&invite),
+        expires_at: invite.expires_at,
+    }))
+}
+
+/// Invite people from a CSV body of `email,role,team` lines
+pub async fn bulk_invite(
+    body: String,
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::EMPLOYEES_INVITE)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let response = invites::bulk_invite(&ctx, &body, &req_info).await?;
+
+    if response.invited > 0 {
+        cache
+            .invalidate(
+                "users",
+                &InvalidationContext {
+                    company_id: Some(company_id),
+                    ..Default::default()
+                },
+            )
+            .await;
+    }
+
+    Ok(ApiResponse::success(response))
+}
+
+/// An invite of the company that can still be revoked or resent
+async fn find_open_invite(company_id: Uuid, invite_id: Uuid) -> Result<InviteToken, AppError> {
+    let invite = invite_repo::find_company_invite(company_id, invite_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Invite not found".to_string()))?;
+
+    match invite.status {
+        InviteTokenStatus::Pending | InviteTokenStatus::Expired => Ok(invite),
+        _ => Err(AppError::BadRequest(format!(
+            "Invite is already {}",
+            invite.status
+        ))),
+    }
+}
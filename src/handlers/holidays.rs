@@ -0,0 +1,235 @@
+use actix_web::{
+    HttpResponse, Result,
+    web::{Data, Json, Path, Query},
+};
+use uuid::Uuid;
+
+use crate::{
+    database::{
+        models::{
+            Action, ActivityType, CompanyHolidayInput, EntityType, HolidayCalendarQuery,
+            HolidayImportQuery, HolidayListQuery, Permission,
+        },
+        repositories::holiday as holiday_repo,
+        transaction::DatabaseTransaction,
+    },
+    error::AppError,
+    handlers::shared::ApiResponse,
+    middleware::{CacheLayer, cache::InvalidationContext, request_info::RequestInfo},
+    services::{activity_logger, holidays, user_context::UserContext},
+};
+
+/// Holidays change which shifts are suggested and what time off deducts
+async fn invalidate_shifts(cache: &CacheLayer, company_id: Uuid) {
+    cache
+        .invalidate(
+            "shifts",
+            &InvalidationContext {
+                company_id: Some(company_id),
+                ..Default::default()
+            },
+        )
+        .await;
+}
+
+/// List the company's holidays, or those that apply to one location
+pub async fn get_holidays(
+    query: Query<HolidayListQuery>,
+    ctx: UserContext,
+) -> Result<HttpResponse> {
+    let company_id = ctx.strict_company_id()?;
+
+    let holidays = holiday_repo::get_holidays(company_id, query.location_id)
+        .await
+        .map_err(AppError::from)?;
+
+    Ok(ApiResponse::success(holidays))
+}
+
+/// The days a location keeps a holiday on, with recurring holidays expanded
+pub async fn get_calendar(
+    query: Query<HolidayCalendarQuery>,
+    ctx: UserContext,
+) -> Result<HttpResponse> {
+    let company_id = ctx.strict_company_id()?;
+
+    let calendar = holidays::calendar(company_id, &query).await?;
+
+    Ok(ApiResponse::success(calendar))
+}
+
+/// Add a holiday for the company or one location (admins only)
+pub async fn create_holiday(
+    input: Json<CompanyHolidayInput>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let input = input.into_inner();
+    holidays::validate_input(company_id, &input).await?;
+
+    let holiday = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let holiday = holiday_repo::create_holiday(tx, company_id, &input).await?;
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                ActivityType::SCHEDULE_MANAGEMENT.to_string(),
+                EntityType::HOLIDAY.to_string(),
+                holiday.id,
+                Action::CREATED.to_string(),
+                format!("Holiday '{}' created by user {}", holiday.name, user_id),
+                Some(activity_logger::metadata(vec![
+                    ("holiday_date", holiday.holiday_date.to_string()),
+                    ("recurring", holiday.recurring.to_string()),
+                ])),
+                &req_info,
+            )
+            .await?;
+
+            Ok(holiday)
+        })
+    })
+    .await?;
+
+    invalidate_shifts(&cache, company_id).await;
+
+    Ok(ApiResponse::created(holiday))
+}
+
+/// Replace a holiday's settings (admins only)
+pub async fn update_holiday(
+    path: Path<Uuid>,
+    input: Json<CompanyHolidayInput>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+    let holiday_id = path.into_inner();
+
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let existing = holiday_repo::find_holiday(company_id, holiday_id)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::NotFound("Holiday not found".to_string()))?;
+
+    let input = input.into_inner();
+    holidays::validate_input(company_id, &input).await?;
+
+    let holiday = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let holiday = holiday_repo::update_holiday(tx, company_id, holiday_id, &input)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Holiday not found".to_string()))?;
+
+            let metadata = activity_logger::with_changes(
+                activity_logger::metadata(vec![]),
+                &existing,
+                &holiday,
+            );
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                ActivityType::SCHEDULE_MANAGEMENT.to_string(),
+                EntityType::HOLIDAY.to_string(),
+                holiday.id,
+                Action::UPDATED.to_string(),
+                format!("Holiday '{}' updated by user {}", holiday.name, user_id),
+                Some(metadata),
+                &req_info,
+            )
+            .await?;
+
+            Ok(holiday)
+        })
+    })
+    .await?;
+
+    invalidate_shifts(&cache, company_id).await;
+
+    Ok(ApiResponse::success(holiday))
+}
+
+/// Remove a holiday (admins only)
+pub async fn delete_holiday(
+    path: Path<Uuid>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+    let holiday_id = path.into_inner();
+
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let existing = holiday_repo::find_holiday(company_id, holiday_id)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::NotFound("Holiday not found".to_string()))?;
+
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            if !holiday_repo::delete_holiday(tx, company_id, holiday_id).await? {
+                return Err(AppError::NotFound("Holiday not found".to_string()));
+            }
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                ActivityType::SCHEDULE_MANAGEMENT.to_string(),
+                EntityType::HOLIDAY.to_string(),
+                holiday_id,
+                Action::DELETED.to_string(),
+                format!("Holiday '{}' deleted by user {}", existing.name, user_id),
+                None,
+                &req_info,
+            )
+            .await?;
+
+            Ok(())
+        })
+    })
+    .await?;
+
+    invalidate_shifts(&cache, company_id).await;
+
+    Ok(ApiResponse::success_message("Holiday deleted"))
+}
+
+/// Import holidays from an iCalendar (.ics) file sent as the request body (admins only)
+pub async fn import_holidays(
+    query: Query<HolidayImportQuery>,
+    body: String,
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let response = holidays::import(company_id, ctx.user_id(), &body, &query, &req_info).await?;
+
+    if !response.dry_run {
+        invalidate_shifts(&cache, company_id).await;
+    }
+
+    Ok(ApiResponse::success(response))
+}
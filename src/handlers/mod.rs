@@ -1,7 +1,9 @@
 pub mod admin;
 pub mod auth;
 pub mod company;
+pub mod holidays;
 pub mod pto_balance;
+pub mod pto_policies;
 pub mod schedules;
 pub mod shared;
 pub mod shifts;
@@ -2,13 +2,17 @@ use actix_web::{
     HttpResponse, Result,
     web::{Data, Json, Path, Query},
 };
+use chrono::Utc;
 use serde::Deserialize;
 use uuid::Uuid;
 
 use crate::{
     database::{
-        models::{PtoBalanceAdjustmentInput, PtoBalanceUpdateInput},
-        repositories::pto_balance as pto_repo,
+        models::{
+            Permission, PtoBalanceAdjustmentInput, PtoBalanceAsOfQuery, PtoBalanceType,
+            PtoBalanceUpdateInput,
+        },
+        repositories::{pto_balance as pto_repo, pto_policy as pto_policy_repo},
         transaction::DatabaseTransaction,
     },
     error::AppError,
@@ -33,7 +37,7 @@ pub async fn get_pto_balance(
 ) -> Result<HttpResponse> {
     let user_id = path.map(|p| p.into_inner()).unwrap_or(ctx.user.id);
 
-    ctx.requires_same_user(user_id)?;
+    ctx.requires_same_user_or_permission(user_id, Permission::PTO_VIEW)?;
 
     let company_id = ctx.strict_company_id()?;
 
@@ -71,18 +75,28 @@ pub async fn update_pto_balance(
     req_info: RequestInfo,
     cache: Data<CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::PTO_MANAGE)?;
 
     let company_id = ctx.strict_company_id()?;
     let user_id = path.into_inner();
     let update_data = input.into_inner();
     let path_for_cache = req_info.path.clone();
 
+    let previous = pto_repo::get_balance_for_company(user_id, company_id)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::NotFound("PTO balance not found".to_string()))?;
+
     let balance = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
-            let balance =
-                pto_repo::update_balance_for_company(tx, user_id, company_id, update_data.clone())
-                    .await?;
+            let balance = pto_repo::update_balance_for_company(
+                tx,
+                user_id,
+                company_id,
+                update_data.clone(),
+                Some(ctx.user.id),
+            )
+            .await?;
 
             // Log the update activity
             let metadata = activity_logger::metadata(vec![
@@ -90,15 +104,27 @@ pub async fn update_pto_balance(
                 ("user_id", user_id.to_string()),
                 (
                     "pto_balance_hours",
-                    update_data.pto_balance_hours.unwrap_or(0).to_string(),
+                    update_data
+                        .pto_balance_hours
+                        .clone()
+                        .unwrap_or_default()
+                        .to_string(),
                 ),
                 (
                     "sick_balance_hours",
-                    update_data.sick_balance_hours.unwrap_or(0).to_string(),
+                    update_data
+                        .sick_balance_hours
+                        .clone()
+                        .unwrap_or_default()
+                        .to_string(),
                 ),
                 (
                     "personal_balance_hours",
-                    update_data.personal_balance_hours.unwrap_or(0).to_string(),
+                    update_data
+                        .personal_balance_hours
+                        .clone()
+                        .unwrap_or_default()
+                        .to_string(),
                 ),
                 (
                     "pto_accrual_rate",
@@ -109,6 +135,7 @@ pub async fn update_pto_balance(
                     update_data.hire_date.unwrap_or_default().to_string(),
                 ),
             ]);
+            let metadata = activity_logger::with_changes(metadata, &previous, &balance);
 
             activity_logger::log_user_activity(
                 tx,
@@ -153,7 +180,7 @@ pub async fn adjust_pto_balance(
     req: RequestInfo,
     cache: Data<CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::PTO_MANAGE)?;
 
     let company_id = ctx.strict_company_id()?;
     let user_id = path.into_inner();
@@ -167,6 +194,7 @@ pub async fn adjust_pto_balance(
                 user_id,
                 company_id,
                 adjustment_data.clone(),
+                Some(ctx.user.id),
             )
             .await?;
 
@@ -226,7 +254,7 @@ pub async fn get_pto_balance_history(
     let user_id = path.into_inner();
     let company_id = ctx.strict_company_id()?;
 
-    ctx.requires_same_user(user_id)?;
+    ctx.requires_same_user_or_permission(user_id, Permission::PTO_VIEW)?;
 
     let balance_history = pto_repo::get_balance_history(user_id, company_id, query.limit)
         .await
@@ -250,6 +278,30 @@ pub async fn get_pto_balance_history(
     Ok(ApiResponse::success(balance_history))
 }
 
+/// Get balances on a date, summed from the ledger, for payroll reconciliation. Without a user,
+/// covers every member of the company.
+pub async fn get_pto_balance_as_of(
+    query: Query<PtoBalanceAsOfQuery>,
+    ctx: UserContext,
+) -> Result<HttpResponse> {
+    match query.user_id {
+        Some(user_id) => ctx.requires_same_user_or_permission(user_id, Permission::PTO_VIEW)?,
+        None => ctx.requires_permission(Permission::PTO_VIEW)?,
+    }
+
+    let company_id = ctx.strict_company_id()?;
+    let as_of = query.date.unwrap_or_else(|| Utc::now().date_naive());
+
+    let balances = pto_repo::get_balances_as_of(company_id, as_of, query.user_id)
+        .await
+        .map_err(|err| {
+            log::error!("Error fetching PTO balances as of {}: {}", as_of, err);
+            AppError::DatabaseError(err)
+        })?;
+
+    Ok(ApiResponse::success(balances))
+}
+
 /// Process PTO accrual for a user (admins/managers only)
 pub async fn process_pto_accrual(
     path: Path<Uuid>,
@@ -257,20 +309,32 @@ pub async fn process_pto_accrual(
     ctx: UserContext,
     cache: Data<CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::PTO_MANAGE)?;
 
     let company_id = ctx.strict_company_id()?;
     let user_id = path.into_inner();
     let path_for_cache = req.path.clone();
 
+    // Members on an accrual policy accrue through policy runs instead of the flat rate
+    if pto_policy_repo::has_policy(user_id, company_id, PtoBalanceType::Pto)
+        .await
+        .map_err(AppError::from)?
+    {
+        return Err(AppError::BadRequest(
+            "User accrues PTO under an accrual policy; run the policy accrual instead".to_string(),
+        )
+        .into());
+    }
+
     let result = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
-            let result = pto_repo::process_accrual_for_company(tx, user_id, company_id)
-                .await?
-                .ok_or_else(|| {
-                    log::error!("PTO accrual not found for user {}", user_id);
-                    AppError::NotFound(format!("PTO accrual not found for user {}", user_id))
-                })?;
+            let result =
+                pto_repo::process_accrual_for_company(tx, user_id, company_id, Some(ctx.user.id))
+                    .await?
+                    .ok_or_else(|| {
+                        log::error!("PTO accrual not found for user {}", user_id);
+                        AppError::NotFound(format!("PTO accrual not found for user {}", user_id))
+                    })?;
 
             // Log the accrual activity within the transaction
             let metadata = activity_logger::metadata(vec![
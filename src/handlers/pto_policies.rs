@@ -0,0 +1,398 @@
+use actix_web::{
+    HttpResponse, Result,
+    web::{Data, Json, Path, Query},
+};
+use uuid::Uuid;
+
+use crate::{
+    database::{
+        models::{
+            Action, ActivityType, EntityType, Permission, PtoAccrualPolicy,
+            PtoAccrualPolicyDetails, PtoAccrualPolicyInput, PtoAccrualRunQuery,
+            PtoPolicyAssignmentInput,
+        },
+        repositories::pto_policy as pto_policy_repo,
+        transaction::DatabaseTransaction,
+    },
+    error::AppError,
+    handlers::shared::ApiResponse,
+    middleware::{CacheLayer, cache::InvalidationContext, request_info::RequestInfo},
+    services::{activity_logger, pto_accrual, user_context::UserContext},
+};
+
+/// Attach tiers and assignments to the company's policies
+async fn policy_details(
+    company_id: Uuid,
+    policies: Vec<PtoAccrualPolicy>,
+) -> Result<Vec<PtoAccrualPolicyDetails>, AppError> {
+    let policy_ids: Vec<Uuid> = policies.iter().map(|policy| policy.id).collect();
+    let tiers = pto_policy_repo::get_tiers(&policy_ids).await?;
+    let assignments = pto_policy_repo::get_assignments(company_id).await?;
+
+    Ok(policies
+        .into_iter()
+        .map(|policy| PtoAccrualPolicyDetails {
+            tiers: tiers
+                .iter()
+                .filter(|tier| tier.policy_id == policy.id)
+                .cloned()
+                .collect(),
+            assignments: assignments
+                .iter()
+                .filter(|assignment| assignment.policy_id == policy.id)
+                .cloned()
+                .collect(),
+            policy,
+        })
+        .collect())
+}
+
+async fn find_policy_details(
+    company_id: Uuid,
+    policy_id: Uuid,
+) -> Result<PtoAccrualPolicyDetails, AppError> {
+    let policy = pto_policy_repo::find_policy(company_id, policy_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("PTO policy not found".to_string()))?;
+
+    policy_details(company_id, vec![policy])
+        .await?
+        .pop()
+        .ok_or_else(|| AppError::NotFound("PTO policy not found".to_string()))
+}
+
+/// List the company's accrual policies
+pub async fn get_policies(ctx: UserContext) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::PTO_VIEW)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let policies = pto_policy_repo::get_policies(company_id)
+        .await
+        .map_err(AppError::from)?;
+
+    Ok(ApiResponse::success(
+        policy_details(company_id, policies).await?,
+    ))
+}
+
+/// Get an accrual policy with its tiers and assignments
+pub async fn get_policy(path: Path<Uuid>, ctx: UserContext) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::PTO_VIEW)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    Ok(ApiResponse::success(
+        find_policy_details(company_id, path.into_inner()).await?,
+    ))
+}
+
+/// Create an accrual policy (admins/managers only)
+pub async fn create_policy(
+    input: Json<PtoAccrualPolicyInput>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+
+    ctx.requires_permission(Permission::PTO_MANAGE)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let mut input = input.into_inner();
+    pto_accrual::validate_input(company_id, None, &mut input).await?;
+
+    let policy = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let policy = pto_policy_repo::create_policy(tx, company_id, &input).await?;
+            pto_policy_repo::replace_tiers(tx, policy.id, &input.tiers).await?;
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                ActivityType::TIME_OFF_MANAGEMENT.to_string(),
+                EntityType::PTO_POLICY.to_string(),
+                policy.id,
+                Action::CREATED.to_string(),
+                format!("PTO policy '{}' created by user {}", policy.name, user_id),
+                Some(activity_logger::metadata(vec![
+                    ("balance_type", policy.balance_type.to_string()),
+                    ("accrual_method", policy.accrual_method.to_string()),
+                ])),
+                &req_info,
+            )
+            .await?;
+
+            Ok(policy)
+        })
+    })
+    .await?;
+
+    Ok(ApiResponse::created(
+        find_policy_details(company_id, policy.id).await?,
+    ))
+}
+
+/// Replace an accrual policy's settings and tiers (admins/managers only)
+pub async fn update_policy(
+    path: Path<Uuid>,
+    input: Json<PtoAccrualPolicyInput>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+    let policy_id = path.into_inner();
+
+    ctx.requires_permission(Permission::PTO_MANAGE)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let existing = find_policy_details(company_id, policy_id).await?;
+
+    let mut input = input.into_inner();
+    pto_accrual::validate_input(company_id, Some(policy_id), &mut input).await?;
+
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let policy = pto_policy_repo::update_policy(tx, company_id, policy_id, &input)
+                .await?
+                .ok_or_else(|| AppError::NotFound("PTO policy not found".to_string()))?;
+            pto_policy_repo::replace_tiers(tx, policy.id, &input.tiers).await?;
+
+            let metadata = activity_logger::with_changes(
+                activity_logger::metadata(vec![]),
+                &existing.policy,
+                &policy,
+            );
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                ActivityType::TIME_OFF_MANAGEMENT.to_string(),
+                EntityType::PTO_POLICY.to_string(),
+                policy.id,
+                Action::UPDATED.to_string(),
+                format!("PTO policy '{}' updated by user {}", policy.name, user_id),
+                Some(metadata),
+                &req_info,
+            )
+            .await?;
+
+            Ok(())
+        })
+    })
+    .await?;
+
+    Ok(ApiResponse::success(
+        find_policy_details(company_id, policy_id).await?,
+    ))
+}
+
+/// Delete an accrual policy (admins/managers only). Balances already accrued are kept.
+pub async fn delete_policy(
+    path: Path<Uuid>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+    let policy_id = path.into_inner();
+
+    ctx.requires_permission(Permission::PTO_MANAGE)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let existing = find_policy_details(company_id, policy_id).await?;
+
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            if !pto_policy_repo::delete_policy(tx, company_id, policy_id).await? {
+                return Err(AppError::NotFound("PTO policy not found".to_string()));
+            }
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                ActivityType::TIME_OFF_MANAGEMENT.to_string(),
+                EntityType::PTO_POLICY.to_string(),
+                policy_id,
+                Action::DELETED.to_string(),
+                format!(
+                    "PTO policy '{}' deleted by user {}",
+                    existing.policy.name, user_id
+                ),
+                None,
+                &req_info,
+            )
+            .await?;
+
+            Ok(())
+        })
+    })
+    .await?;
+
+    Ok(ApiResponse::success_message("PTO policy deleted"))
+}
+
+/// Assign a policy to a member, team, location or the whole company (admins/managers only)
+pub async fn create_assignment(
+    path: Path<Uuid>,
+    input: Json<PtoPolicyAssignmentInput>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+    let policy_id = path.into_inner();
+
+    ctx.requires_permission(Permission::PTO_MANAGE)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let existing = find_policy_details(company_id, policy_id).await?;
+
+    let input = input.into_inner();
+    pto_accrual::validate_assignment(company_id, &input).await?;
+
+    let assignment = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let assignment = pto_policy_repo::create_assignment(tx, company_id, policy_id, &input)
+                .await?
+                .ok_or_else(|| {
+                    AppError::BadRequest("The policy is already assigned to this group".to_string())
+                })?;
+
+            let target = [
+                ("user_id", assignment.user_id),
+                ("team_id", assignment.team_id),
+                ("location_id", assignment.location_id),
+            ]
+            .into_iter()
+            .find_map(|(key, id)| id.map(|id| (key, id.to_string())))
+            .unwrap_or(("company_id", company_id.to_string()));
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                ActivityType::TIME_OFF_MANAGEMENT.to_string(),
+                EntityType::PTO_POLICY.to_string(),
+                policy_id,
+                Action::ASSIGNED.to_string(),
+                format!(
+                    "PTO policy '{}' assigned by user {}",
+                    existing.policy.name, user_id
+                ),
+                Some(activity_logger::metadata(vec![
+                    ("assignment_id", assignment.id.to_string()),
+                    target,
+                ])),
+                &req_info,
+            )
+            .await?;
+
+            Ok(assignment)
+        })
+    })
+    .await?;
+
+    Ok(ApiResponse::created(assignment))
+}
+
+/// Remove a policy assignment (admins/managers only)
+pub async fn delete_assignment(
+    path: Path<(Uuid, Uuid)>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+) -> Result<HttpResponse> {
+    let user_id = ctx.user_id();
+    let (policy_id, assignment_id) = path.into_inner();
+
+    ctx.requires_permission(Permission::PTO_MANAGE)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let existing = find_policy_details(company_id, policy_id).await?;
+
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            if !pto_policy_repo::delete_assignment(tx, company_id, policy_id, assignment_id).await?
+            {
+                return Err(AppError::NotFound(
+                    "PTO policy assignment not found".to_string(),
+                ));
+            }
+
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                ActivityType::TIME_OFF_MANAGEMENT.to_string(),
+                EntityType::PTO_POLICY.to_string(),
+                policy_id,
+                Action::UNASSIGNED.to_string(),
+                format!(
+                    "PTO policy '{}' unassigned by user {}",
+                    existing.policy.name, user_id
+                ),
+                Some(activity_logger::metadata(vec![(
+                    "assignment_id",
+                    assignment_id.to_string(),
+                )])),
+                &req_info,
+            )
+            .await?;
+
+            Ok(())
+        })
+    })
+    .await?;
+
+    Ok(ApiResponse::success_message(
+        "PTO policy assignment removed",
+    ))
+}
+
+/// Show what an accrual run would do without changing any balance (admins/managers only)
+pub async fn preview_accrual(
+    query: Query<PtoAccrualRunQuery>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::PTO_MANAGE)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let run = pto_accrual::run(company_id, ctx.user_id(), &query, true, &req_info).await?;
+
+    Ok(ApiResponse::success(run))
+}
+
+/// Accrue balances under the company's policies up to a date (admins/managers only)
+pub async fn run_accrual(
+    query: Query<PtoAccrualRunQuery>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    ctx.requires_permission(Permission::PTO_MANAGE)?;
+
+    let company_id = ctx.strict_company_id()?;
+
+    let run = pto_accrual::run(company_id, ctx.user_id(), &query, false, &req_info).await?;
+
+    // Cache invalidation for accrued balances - affects pto, users, stats
+    cache
+        .invalidate(
+            "pto_balance",
+            &InvalidationContext {
+                company_id: Some(company_id),
+                user_id: query.user_id,
+                resource_id: None,
+            },
+        )
+        .await;
+
+    Ok(ApiResponse::success(run))
+}
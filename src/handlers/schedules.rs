@@ -7,7 +7,7 @@ use uuid::Uuid;
 
 use crate::{
     database::{
-        models::{ShiftAssignmentInput, UserShiftScheduleInput},
+        models::{Permission, ShiftAssignmentInput, UserShiftScheduleInput},
         repositories::schedule as schedule_repo,
         transaction::DatabaseTransaction,
     },
@@ -33,7 +33,7 @@ pub async fn create_user_schedule(
 ) -> Result<HttpResponse> {
     let user_id = input.user_id;
 
-    ctx.requires_same_user(user_id)?;
+    ctx.requires_same_user_or_permission(user_id, Permission::SCHEDULES_MANAGE)?;
 
     let schedule = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
@@ -67,7 +67,7 @@ pub async fn get_user_schedule(
 ) -> Result<HttpResponse> {
     let user_id = path.into_inner();
 
-    ctx.requires_same_user(user_id)?;
+    ctx.requires_same_user_or_permission(user_id, Permission::SCHEDULES_MANAGE)?;
 
     let schedule = schedule_repo::get_user_schedule(user_id)
         .await
@@ -103,7 +103,7 @@ pub async fn update_user_schedule(
 ) -> Result<HttpResponse> {
     let user_id = path.into_inner();
 
-    ctx.requires_same_user(user_id)?;
+    ctx.requires_same_user_or_permission(user_id, Permission::SCHEDULES_MANAGE)?;
 
     let schedule = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
@@ -139,7 +139,7 @@ pub async fn delete_user_schedule(
 ) -> Result<HttpResponse> {
     let user_id = path.into_inner();
 
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SCHEDULES_MANAGE)?;
 
     DatabaseTransaction::run(|tx| {
         Box::pin(async move {
@@ -176,7 +176,7 @@ pub async fn create_shift_assignment(
     req_info: RequestInfo,
     cache: Data<CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SHIFTS_ASSIGN)?;
     let company_id = ctx.strict_company_id()?;
     let user_id = ctx.user_id();
 
@@ -220,7 +220,7 @@ pub async fn get_shift_assignment(
         })?
         .ok_or_else(|| AppError::NotFound("Shift assignment not found".to_string()))?;
 
-    ctx.requires_same_user(assignment.user_id)?;
+    ctx.requires_same_user_or_permission(assignment.user_id, Permission::SHIFTS_VIEW)?;
 
     // Cache assignment retrieval - affects assignments
     cache
@@ -243,7 +243,7 @@ pub async fn get_shift_assignments_by_shift(
     req_info: RequestInfo,
     cache: Data<CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SHIFTS_VIEW)?;
 
     let shift_id = path.into_inner();
 
@@ -277,7 +277,7 @@ pub async fn get_shift_assignments_by_user(
 ) -> Result<HttpResponse> {
     let user_id = path.into_inner();
 
-    ctx.requires_same_user(user_id)?;
+    ctx.requires_same_user_or_permission(user_id, Permission::SHIFTS_VIEW)?;
 
     let assignments = schedule_repo::get_shift_assignments_by_user(user_id)
         .await
@@ -309,7 +309,7 @@ pub async fn get_pending_assignments_for_user(
 ) -> Result<HttpResponse> {
     let user_id = path.into_inner();
 
-    ctx.requires_same_user(user_id)?;
+    ctx.requires_same_user_or_permission(user_id, Permission::SHIFTS_VIEW)?;
 
     let assignments = schedule_repo::get_pending_assignments_for_user(user_id)
         .await
@@ -388,7 +388,7 @@ pub async fn cancel_assignment(
     req_info: RequestInfo,
     cache: Data<CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SHIFTS_ASSIGN)?;
 
     let assignment_id = path.into_inner();
 
@@ -420,7 +420,7 @@ pub async fn expire_overdue_assignments(
     req_info: RequestInfo,
     cache: Data<CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_admin()?;
+    ctx.requires_permission(Permission::COMPANY_SETTINGS)?;
 
     let expired_assignments = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
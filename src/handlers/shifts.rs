@@ -2,27 +2,32 @@ use actix_web::{
     HttpResponse, Result,
     web::{Data, Json, Path, Query},
 };
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
     database::{
         models::{
-            Action, CreateUpdateShiftInput, Shift, ShiftAssignment, ShiftAssignmentInput,
-            ShiftClaimInput, ShiftClaimResponse, ShiftQuery, ShiftQueryType, ShiftStatus,
+            Action, CreateUpdateShiftInput, CreatedShift, Permission, Shift, ShiftAssignment,
+            ShiftAssignmentInput, ShiftClaimInput, ShiftClaimResponse, ShiftQuery, ShiftQueryType,
+            ShiftStatus,
         },
         repositories::{
-            schedule as schedule_repo, shift as shift_repo, shift_claim as shift_claim_repo,
+            attendance as attendance_repo, schedule as schedule_repo, shift as shift_repo,
+            shift_claim as shift_claim_repo,
         },
         transaction::DatabaseTransaction,
     },
     error::AppError,
     handlers::shared::ApiResponse,
     middleware::{cache::InvalidationContext, request_info::RequestInfo},
-    services::{activity_logger, user_context::UserContext},
+    services::{activity_logger, holidays, manager_scope, user_context::UserContext},
 };
 
+/// How early before a shift starts its workers may clock in
+const CLOCK_IN_EARLY_MINUTES: i64 = 60;
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AssignShiftInput {
@@ -59,6 +64,31 @@ pub struct AssignmentResponseInput {
     pub notes: Option<String>,
 }
 
+/// Load a shift of the caller's company, checking it is within the locations and teams they manage
+async fn find_managed_shift(ctx: &UserContext, shift_id: Uuid) -> Result<Shift, AppError> {
+    let company_id = ctx.strict_company_id()?;
+
+    let shift = shift_repo::find_by_id(shift_id, company_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Shift not found".to_string()))?;
+
+    manager_scope::load(ctx).await?.requires_shift(&shift)?;
+
+    Ok(shift)
+}
+
+/// Load a claim on one of the caller's shifts, checking the shift is within the locations and
+/// teams they manage
+async fn find_managed_claim(ctx: &UserContext, claim_id: Uuid) -> Result<(), AppError> {
+    let claim = shift_claim_repo::find_by_id(claim_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Claim not found".to_string()))?;
+
+    find_managed_shift(ctx, claim.shift_id).await?;
+
+    Ok(())
+}
+
 // Shift handlers
 pub async fn create_shift(
     ctx: UserContext,
@@ -66,13 +96,26 @@ pub async fn create_shift(
     req_info: RequestInfo,
     cache: Data<crate::middleware::CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SHIFTS_CREATE)?;
     ctx.requires_same_company(input.company_id)?;
 
+    manager_scope::load(&ctx)
+        .await?
+        .requires_shift_at(input.location_id, input.team_id)?;
+
     let user_id = ctx.user_id();
     let company_id = ctx.strict_company_id()?;
     let shift_input = input.into_inner();
 
+    // The shift is still created on a closed holiday, but the scheduler is told about it
+    let warnings = holidays::closed_holiday_warnings(
+        company_id,
+        shift_input.location_id,
+        shift_input.start_time,
+        shift_input.end_time,
+    )
+    .await?;
+
     let shift = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
             let shift = shift_repo::create_shift(tx, shift_input).await?;
@@ -126,7 +169,7 @@ pub async fn create_shift(
             },
         )
         .await;
-    Ok(ApiResponse::created(shift))
+    Ok(ApiResponse::created(CreatedShift { shift, warnings }))
 }
 
 // In shifts.rs handler
@@ -135,17 +178,28 @@ pub async fn get_shifts(
     query: Query<ShiftQuery>,
 ) -> Result<HttpResponse, AppError> {
     match &query.query_type {
-        ShiftQueryType::User(user_id) => ctx.requires_same_user(*user_id)?,
-        _ => ctx.requires_manager()?,
+        ShiftQueryType::User(user_id) => {
+            ctx.requires_same_user_or_permission(*user_id, Permission::SHIFTS_VIEW)?
+        }
+        _ => ctx.requires_permission(Permission::SHIFTS_VIEW)?,
     }
 
-    let shifts = shift_repo::find_by_query(query.into_inner())
+    let own_shifts =
+        matches!(query.query_type, ShiftQueryType::User(user_id) if user_id == ctx.user_id());
+
+    let mut shifts = shift_repo::find_by_query(query.into_inner())
         .await
         .map_err(|e| {
             log::error!("Failed to fetch shifts: {}", e);
             AppError::DatabaseError(e)
         })?;
 
+    // Scoped managers only see shifts at the locations and teams they manage, besides their own
+    if !own_shifts {
+        let scope = manager_scope::load(&ctx).await?;
+        shifts.retain(|shift| scope.includes_shift(shift.location_id, shift.team_id));
+    }
+
     Ok(ApiResponse::success(shifts))
 }
 
@@ -171,7 +225,7 @@ pub async fn update_shift(
     cache: Data<crate::middleware::CacheLayer>,
 ) -> Result<HttpResponse> {
     // Check if user is admin or manager
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SHIFTS_EDIT)?;
 
     // Ensure the user has access to the company
     ctx.requires_same_company(input.company_id)?;
@@ -180,6 +234,12 @@ pub async fn update_shift(
     let company_id = ctx.strict_company_id()?;
     let user_id = ctx.user_id();
 
+    // Scoped managers can neither edit shifts elsewhere nor move theirs out of scope
+    find_managed_shift(&ctx, shift_id).await?;
+    manager_scope::load(&ctx)
+        .await?
+        .requires_shift_at(input.location_id, input.team_id)?;
+
     let updated_shift = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
             let shift = shift_repo::find_by_id(shift_id, company_id)
@@ -210,6 +270,7 @@ pub async fn update_shift(
                 ("start_time", updated_shift.start_time.to_string()),
                 ("end_time", updated_shift.end_time.to_string()),
             ]);
+            let metadata = activity_logger::with_changes(metadata, &shift, &updated_shift);
 
             activity_logger::log_shift_activity(
                 tx,
@@ -258,13 +319,15 @@ pub async fn assign_shift(
     req_info: RequestInfo,
     cache: Data<crate::middleware::CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SHIFTS_ASSIGN)?;
     let company_id = ctx.strict_company_id()?;
 
     let shift_id = path.into_inner();
     let assigned_user_id = input.user_id;
     let acceptance_deadline = input.acceptance_deadline;
 
+    find_managed_shift(&ctx, shift_id).await?;
+
     let user_id = ctx.user_id();
 
     let (shift, assignment) = DatabaseTransaction::run(|tx| {
@@ -354,12 +417,14 @@ pub async fn unassign_shift(
     req_info: RequestInfo,
     cache: Data<crate::middleware::CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SHIFTS_ASSIGN)?;
     let company_id = ctx.strict_company_id()?;
 
     let shift_id = path.into_inner();
     let user_id = ctx.user_id();
 
+    find_managed_shift(&ctx, shift_id).await?;
+
     let shift = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
             let shift = shift_repo::unassign_shift(tx, shift_id)
@@ -428,13 +493,15 @@ pub async fn update_shift_status(
     req_info: RequestInfo,
     cache: Data<crate::middleware::CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SHIFTS_EDIT)?;
     let company_id = ctx.strict_company_id()?;
     let user_id = ctx.user_id();
 
     let shift_id = path.into_inner();
     let status = input.status.clone();
 
+    let previous = find_managed_shift(&ctx, shift_id).await?;
+
     let shift = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
             let shift = shift_repo::update_shift_status(tx, shift_id, status.clone())
@@ -447,6 +514,7 @@ pub async fn update_shift_status(
                 ("shift_id", shift_id.to_string()),
                 ("location_id", shift.location_id.to_string()),
             ]);
+            let metadata = activity_logger::with_changes(metadata, &previous, &shift);
 
             activity_logger::log_shift_activity(
                 tx,
@@ -496,12 +564,14 @@ pub async fn delete_shift(
     req_info: RequestInfo,
     cache: Data<crate::middleware::CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SHIFTS_EDIT)?;
     let company_id = ctx.strict_company_id()?;
     let user_id = ctx.user_id();
 
     let shift_id = path.into_inner();
 
+    find_managed_shift(&ctx, shift_id).await?;
+
     DatabaseTransaction::run(|tx| {
         Box::pin(async move {
             shift_repo::delete_shift(tx, shift_id)
@@ -553,10 +623,12 @@ pub async fn delete_shift(
 
 // Get shift assignments for a specific shift (managers/admins only)
 pub async fn get_shift_assignments(path: Path<Uuid>, ctx: UserContext) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SHIFTS_VIEW)?;
 
     let shift_id = path.into_inner();
 
+    find_managed_shift(&ctx, shift_id).await?;
+
     let assignments = schedule_repo::get_shift_assignments_by_shift(shift_id)
         .await
         .map_err(|e| {
@@ -804,12 +876,151 @@ pub async fn claim_shift(
     Ok(ApiResponse::created(claim))
 }
 
+/// Clock in to a shift the current user works, from an hour before it starts until it ends
+pub async fn clock_in(
+    path: Path<Uuid>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<crate::middleware::CacheLayer>,
+) -> Result<HttpResponse> {
+    let company_id = ctx.strict_company_id()?;
+    let shift_id = path.into_inner();
+    let user_id = ctx.user_id();
+
+    let shift = shift_repo::find_by_id(shift_id, company_id)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::NotFound("Shift not found".to_string()))?;
+    if !attendance_repo::works_shift(shift_id, user_id)
+        .await
+        .map_err(AppError::from)?
+    {
+        return Err(AppError::Forbidden("You are not working this shift".to_string()).into());
+    }
+
+    let now = Utc::now();
+    if matches!(shift.status, ShiftStatus::Cancelled)
+        || now < shift.start_time - Duration::minutes(CLOCK_IN_EARLY_MINUTES)
+        || now >= shift.end_time
+    {
+        return Err(AppError::BadRequest(format!(
+            "You can clock in from {} minutes before the shift starts until it ends",
+            CLOCK_IN_EARLY_MINUTES
+        ))
+        .into());
+    }
+
+    let attendance = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let attendance = attendance_repo::clock_in(tx, company_id, shift_id, user_id, now)
+                .await?
+                .ok_or_else(|| {
+                    AppError::BadRequest("You already clocked in to this shift".to_string())
+                })?;
+
+            let minutes_late = (now - shift.start_time).num_minutes().max(0);
+            let metadata = activity_logger::metadata(vec![
+                ("shift_id", shift_id.to_string()),
+                ("minutes_late", minutes_late.to_string()),
+            ]);
+
+            activity_logger::log_shift_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                shift_id,
+                Action::CLOCKED_IN,
+                "User clocked in to shift".to_string(),
+                Some(metadata),
+                &req_info,
+            )
+            .await?;
+
+            Ok(attendance)
+        })
+    })
+    .await?;
+
+    cache
+        .invalidate(
+            "stats",
+            &InvalidationContext {
+                company_id: Some(company_id),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    Ok(ApiResponse::created(attendance))
+}
+
+/// Clock out of the shift the current user clocked in to
+pub async fn clock_out(
+    path: Path<Uuid>,
+    ctx: UserContext,
+    req_info: RequestInfo,
+    cache: Data<crate::middleware::CacheLayer>,
+) -> Result<HttpResponse> {
+    let company_id = ctx.strict_company_id()?;
+    let shift_id = path.into_inner();
+    let user_id = ctx.user_id();
+
+    shift_repo::find_by_id(shift_id, company_id)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::NotFound("Shift not found".to_string()))?;
+
+    let attendance = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let attendance = attendance_repo::clock_out(tx, shift_id, user_id, Utc::now())
+                .await?
+                .ok_or_else(|| {
+                    AppError::BadRequest("You are not clocked in to this shift".to_string())
+                })?;
+
+            let metadata = activity_logger::metadata(vec![
+                ("shift_id", shift_id.to_string()),
+                ("clock_in_at", attendance.clock_in_at.to_rfc3339()),
+            ]);
+
+            activity_logger::log_shift_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                shift_id,
+                Action::CLOCKED_OUT,
+                "User clocked out of shift".to_string(),
+                Some(metadata),
+                &req_info,
+            )
+            .await?;
+
+            Ok(attendance)
+        })
+    })
+    .await?;
+
+    cache
+        .invalidate(
+            "stats",
+            &InvalidationContext {
+                company_id: Some(company_id),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    Ok(ApiResponse::success(attendance))
+}
+
 // Get claims for a specific shift (managers/admins only)
 pub async fn get_shift_claims(path: Path<Uuid>, ctx: UserContext) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SHIFTS_VIEW)?;
 
     let shift_id = path.into_inner();
 
+    find_managed_shift(&ctx, shift_id).await?;
+
     let claims = shift_claim_repo::find_by_shift_id(shift_id)
         .await
         .map_err(AppError::from)?;
@@ -836,12 +1047,14 @@ pub async fn approve_shift_claim(
     req_info: RequestInfo,
     cache: Data<crate::middleware::CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SHIFTS_ASSIGN)?;
 
     let claim_id = path.into_inner();
     let approver_id = ctx.user_id();
     let company_id = ctx.strict_company_id()?;
 
+    find_managed_claim(&ctx, claim_id).await?;
+
     let (claim, shift) = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
             // Approve the claim
@@ -920,12 +1133,14 @@ pub async fn reject_shift_claim(
     req_info: RequestInfo,
     cache: Data<crate::middleware::CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SHIFTS_ASSIGN)?;
 
     let claim_id = path.into_inner();
     let approver_id = ctx.user_id();
     let company_id = ctx.strict_company_id()?;
 
+    find_managed_claim(&ctx, claim_id).await?;
+
     let (claim, shift) = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
             let claim =
@@ -1078,11 +1293,14 @@ pub async fn cancel_shift_claim(
 
 // Get pending claims for approval (managers/admins only)
 pub async fn get_pending_claims(ctx: UserContext) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SHIFTS_VIEW)?;
 
-    let claims = shift_claim_repo::find_pending_by_company_id(ctx.strict_company_id()?)
-        .await
-        .map_err(AppError::from)?;
+    let scope = manager_scope::load(&ctx).await?;
+
+    let claims =
+        shift_claim_repo::find_pending_by_company_id(ctx.strict_company_id()?, scope.filter())
+            .await
+            .map_err(AppError::from)?;
 
     Ok(ApiResponse::success(claims))
 }
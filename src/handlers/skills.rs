@@ -7,7 +7,10 @@ use uuid::Uuid;
 
 use crate::{
     database::{
-        models::{Action, ProficiencyLevel, ShiftRequiredSkillInput, SkillInput, UserSkillInput},
+        models::{
+            Action, Permission, ProficiencyLevel, ShiftRequiredSkillInput, SkillInput,
+            UserSkillInput,
+        },
         repositories::{company as company_repo, shift as shifts_repo, skill as skill_repo},
         transaction::DatabaseTransaction,
     },
@@ -35,7 +38,7 @@ pub async fn create_skill(
     input: Json<SkillInput>,
     req_info: RequestInfo,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SKILLS_MANAGE)?;
 
     let company_id = ctx.strict_company_id()?;
     let user_id = ctx.user_id();
@@ -98,14 +101,14 @@ pub async fn update_skill(
     input: Json<SkillInput>,
     req_info: RequestInfo,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SKILLS_MANAGE)?;
     let company_id = ctx.strict_company_id()?;
     let user_id = ctx.user_id();
     let skill_id = path.into_inner();
 
     let updated_skill = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
-            let _skill = skill_repo::find_by_id(skill_id, company_id)
+            let skill = skill_repo::find_by_id(skill_id, company_id)
                 .await
                 .map_err(AppError::from)?
                 .ok_or_else(|| AppError::NotFound("Skill not found".to_string()))?;
@@ -119,6 +122,7 @@ pub async fn update_skill(
                 ("skill_id", updated_skill.id.to_string()),
                 ("skill_name", updated_skill.name.clone()),
             ]);
+            let metadata = activity_logger::with_changes(metadata, &skill, &updated_skill);
 
             activity_logger::log_skill_activity(
                 tx,
@@ -145,7 +149,7 @@ pub async fn delete_skill(
     ctx: UserContext,
     req_info: RequestInfo,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SKILLS_MANAGE)?;
     let company_id = ctx.strict_company_id()?;
     let user_id = ctx.user_id();
     let skill_id = path.into_inner();
@@ -192,7 +196,7 @@ pub async fn add_user_skill(
     req_info: RequestInfo,
     cache: Data<CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SKILLS_MANAGE)?;
 
     let user_id = ctx.user_id();
     let company_id = ctx.strict_company_id()?;
@@ -301,7 +305,7 @@ pub async fn update_user_skill(
     input: Json<UpdateUserSkillRequest>,
     req_info: RequestInfo,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SKILLS_MANAGE)?;
     let user_id = ctx.user_id();
     let company_id = ctx.strict_company_id()?;
     let (target_user_id, skill_id) = path.into_inner();
@@ -363,7 +367,7 @@ pub async fn remove_user_skill(
     req_info: RequestInfo,
     cache: Data<CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SKILLS_MANAGE)?;
     let user_id = ctx.user_id();
     let company_id = ctx.strict_company_id()?;
     let (target_user_id, skill_id) = path.into_inner();
@@ -461,7 +465,7 @@ pub async fn add_shift_required_skill(
     input: Json<ShiftRequiredSkillInput>,
     req_info: RequestInfo,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SKILLS_MANAGE)?;
     let user_id = ctx.user_id();
     let company_id = ctx.strict_company_id()?;
     let shift_id = input.shift_id;
@@ -531,7 +535,7 @@ pub async fn remove_shift_required_skill(
     ctx: UserContext,
     req_info: RequestInfo,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SKILLS_MANAGE)?;
     let user_id = ctx.user_id();
     let company_id = ctx.strict_company_id()?;
     let (shift_id, skill_id) = path.into_inner();
@@ -584,7 +588,7 @@ pub async fn get_users_with_skill(
     query: Query<SkillSearchQuery>,
     ctx: UserContext,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::EMPLOYEES_VIEW)?;
     let company_id = ctx.strict_company_id()?;
     let skill_id = path.into_inner();
     let min_level = query.min_level.clone();
@@ -2,44 +2,33 @@ use actix_web::{
     HttpResponse, Result,
     web::{Data, Query},
 };
-use chrono::{DateTime, Utc};
-use serde::Deserialize;
-use uuid::Uuid;
+use chrono::Utc;
 
 use crate::{
-    database::repositories::stats as stats_repo,
+    database::{
+        models::{CoverageQuery, EmployeeReportQuery, FairnessQuery, StatsQuery},
+        repositories::stats as stats_repo,
+    },
     error::AppError,
     handlers::shared::ApiResponse,
     middleware::{CacheLayer, cache::InvalidationContext, request_info::RequestInfo},
-    services::user_context::UserContext,
+    services::{stats, user_context::UserContext},
 };
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct StatsQuery {
-    pub start_date: Option<DateTime<Utc>>,
-    pub end_date: Option<DateTime<Utc>>,
-    pub user_id: Option<Uuid>,
-}
-
 /// Get dashboard statistics
 pub async fn get_dashboard_stats(
     query: Query<StatsQuery>,
     ctx: UserContext,
 ) -> Result<HttpResponse> {
-    let user_id = ctx.user_id();
-
-    let target_user_id = query.user_id.or_else(|| Some(user_id)).unwrap();
-
-    ctx.requires_same_user(target_user_id)?;
+    let filter = stats::build_filter(&ctx, &query).await?;
 
-    let stats =
-        stats_repo::get_dashboard_stats_for_user(query.user_id, query.start_date, query.end_date)
-            .await
-            .map_err(|err| {
-                log::error!("Error fetching dashboard stats: {}", err);
-                AppError::DatabaseError(err)
-            })?;
+    let stats = stats::with_comparison(
+        filter,
+        query.compare.unwrap_or(false),
+        |filter| async move { stats_repo::get_dashboard_stats(&filter).await },
+    )
+    .await
+    .inspect_err(|err| log::error!("Error fetching dashboard stats: {}", err))?;
 
     Ok(ApiResponse::success(stats))
 }
@@ -51,25 +40,24 @@ pub async fn get_shift_stats(
     req_info: RequestInfo,
     cache: Data<CacheLayer>,
 ) -> Result<HttpResponse> {
-    let user_id = ctx.user_id();
-
-    let target_user_id = query.user_id.or_else(|| Some(user_id)).unwrap();
-
-    ctx.requires_same_user(target_user_id)?;
-
-    let stats = stats_repo::get_shift_stats(query.user_id, query.start_date, query.end_date)
-        .await
-        .map_err(|err| {
-            log::error!("Error fetching shift stats: {}", err);
-            AppError::DatabaseError(err)
-        })?;
+    let filter = stats::build_filter(&ctx, &query).await?;
+    let company_id = filter.company_id;
+    let target_user_id = filter.user_id.unwrap_or_else(|| ctx.user_id());
+
+    let stats = stats::with_comparison(
+        filter,
+        query.compare.unwrap_or(false),
+        |filter| async move { stats_repo::get_shift_stats(&filter).await },
+    )
+    .await
+    .inspect_err(|err| log::error!("Error fetching shift stats: {}", err))?;
 
     // Cache shift stats - affects shifts and stats resources
     cache
         .invalidate(
             &req_info.path,
             &InvalidationContext {
-                company_id: Some(ctx.strict_company_id()?),
+                company_id: Some(company_id),
                 user_id: Some(target_user_id),
                 resource_id: None,
             },
@@ -86,25 +74,24 @@ pub async fn get_time_off_stats(
     req_info: RequestInfo,
     cache: Data<CacheLayer>,
 ) -> Result<HttpResponse> {
-    let user_id = ctx.user_id();
-
-    let target_user_id = query.user_id.or_else(|| Some(user_id)).unwrap();
-
-    ctx.requires_same_user(target_user_id)?;
-
-    let stats = stats_repo::get_time_off_stats(query.user_id, query.start_date, query.end_date)
-        .await
-        .map_err(|err| {
-            log::error!("Error fetching time-off stats: {}", err);
-            AppError::DatabaseError(err)
-        })?;
+    let filter = stats::build_filter(&ctx, &query).await?;
+    let company_id = filter.company_id;
+    let target_user_id = filter.user_id.unwrap_or_else(|| ctx.user_id());
+
+    let stats = stats::with_comparison(
+        filter,
+        query.compare.unwrap_or(false),
+        |filter| async move { stats_repo::get_time_off_stats(&filter).await },
+    )
+    .await
+    .inspect_err(|err| log::error!("Error fetching time-off stats: {}", err))?;
 
     // Cache time-off stats - affects time-off and stats resources
     cache
         .invalidate(
             &req_info.path,
             &InvalidationContext {
-                company_id: Some(ctx.strict_company_id()?),
+                company_id: Some(company_id),
                 user_id: Some(target_user_id),
                 resource_id: None,
             },
@@ -113,3 +100,61 @@ pub async fn get_time_off_stats(
 
     Ok(ApiResponse::success(stats))
 }
+
+/// Per-employee hours and attendance report, as JSON or a CSV download
+pub async fn get_employee_report(
+    query: Query<EmployeeReportQuery>,
+    ctx: UserContext,
+) -> Result<HttpResponse> {
+    let csv = match query.format.as_deref().unwrap_or("json") {
+        "json" => false,
+        "csv" => true,
+        other => {
+            return Err(AppError::BadRequest(format!(
+                "Unknown report format '{}'; use json or csv",
+                other
+            ))
+            .into());
+        }
+    };
+
+    let report = stats::employee_report(&ctx, &query)
+        .await
+        .inspect_err(|err| log::error!("Error building employee report: {}", err))?;
+
+    if !csv {
+        return Ok(ApiResponse::success(report));
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header((
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"employee-report-{}.csv\"",
+                Utc::now().format("%Y%m%d%H%M%S")
+            ),
+        ))
+        .body(stats::employee_report_csv(&report)))
+}
+
+/// Staffing coverage heatmap for a location
+pub async fn get_coverage(query: Query<CoverageQuery>, ctx: UserContext) -> Result<HttpResponse> {
+    let heatmap = stats::coverage_heatmap(&ctx, &query)
+        .await
+        .inspect_err(|err| log::error!("Error building coverage heatmap: {}", err))?;
+
+    Ok(ApiResponse::success(heatmap))
+}
+
+/// How unpopular shifts are shared across a team or location
+pub async fn get_fairness_report(
+    query: Query<FairnessQuery>,
+    ctx: UserContext,
+) -> Result<HttpResponse> {
+    let report = stats::fairness_report(&ctx, &query)
+        .await
+        .inspect_err(|err| log::error!("Error building fairness report: {}", err))?;
+
+    Ok(ApiResponse::success(report))
+}
@@ -7,14 +7,16 @@ use uuid::Uuid;
 
 use crate::{
     database::{
-        models::{Action, ShiftSwapInput, ShiftSwapResponseType, ShiftSwapStatus},
-        repositories::shift_swap as shift_swap_repo,
+        models::{
+            Action, Permission, ShiftSwap, ShiftSwapInput, ShiftSwapResponseType, ShiftSwapStatus,
+        },
+        repositories::{shift as shift_repo, shift_swap as shift_swap_repo},
         transaction::DatabaseTransaction,
     },
     error::AppError,
     handlers::shared::ApiResponse,
     middleware::{cache::InvalidationContext, request_info::RequestInfo},
-    services::{activity_logger, user_context::UserContext},
+    services::{activity_logger, manager_scope, user_context::UserContext},
 };
 
 #[derive(Debug, Deserialize)]
@@ -39,6 +41,23 @@ pub struct ApprovalRequest {
     pub notes: Option<String>,
 }
 
+/// Check the shifts of a swap belong to the caller's company and are within the locations and
+/// teams they manage
+async fn requires_managed_swap(ctx: &UserContext, swap: &ShiftSwap) -> Result<(), AppError> {
+    let company_id = ctx.strict_company_id()?;
+    let scope = manager_scope::load(ctx).await?;
+
+    for shift_id in std::iter::once(swap.original_shift_id).chain(swap.target_shift_id) {
+        let shift = shift_repo::find_by_id(shift_id, company_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Shift not found".to_string()))?;
+
+        scope.requires_shift(&shift)?;
+    }
+
+    Ok(())
+}
+
 /// Create a new shift swap request
 pub async fn create_swap_request(
     ctx: UserContext,
@@ -132,12 +151,15 @@ pub async fn get_swap_requests(query: Query<SwapQuery>, ctx: UserContext) -> Res
     let status_filter = query.status.clone();
 
     if requesting_user_id.is_none() {
-        ctx.requires_manager_or("Manager access required to view all swap requests")?;
+        ctx.requires_permission(Permission::SWAPS_VIEW)?;
     } else {
-        ctx.requires_same_user(requesting_user_id.unwrap_or(user_id))?;
+        ctx.requires_same_user_or_permission(
+            requesting_user_id.unwrap_or(user_id),
+            Permission::SWAPS_VIEW,
+        )?;
     }
 
-    let requests = shift_swap_repo::get_swap_requests_with_details(
+    let mut requests = shift_swap_repo::get_swap_requests_with_details(
         requesting_user_id,
         company_id,
         status_filter,
@@ -146,6 +168,17 @@ pub async fn get_swap_requests(query: Query<SwapQuery>, ctx: UserContext) -> Res
     .await
     .map_err(AppError::from)?;
 
+    // Scoped managers only see swaps of shifts at the locations and teams they manage
+    if requesting_user_id != Some(user_id) {
+        let scope = manager_scope::load(&ctx).await?;
+        requests.retain(|request| {
+            scope.includes_shift(
+                request.original_shift.location_id,
+                request.original_shift.team_id,
+            )
+        });
+    }
+
     Ok(ApiResponse::success(requests))
 }
 
@@ -158,7 +191,7 @@ pub async fn get_swap_request(path: Path<Uuid>, ctx: UserContext) -> Result<Http
         .map_err(AppError::from)?
         .ok_or_else(|| AppError::NotFound("Swap request not found".to_string()))?;
 
-    if !ctx.is_manager_or_admin() {
+    if !ctx.has_permission(Permission::SWAPS_VIEW) {
         let is_involved = swap_request.requesting_user_id == ctx.user.id
             || swap_request.target_user_id.as_ref() == Some(&ctx.user.id);
         if !is_involved {
@@ -307,7 +340,7 @@ pub async fn approve_swap_request(
     req_info: RequestInfo,
     cache: Data<crate::middleware::CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SWAPS_APPROVE)?;
 
     let swap_id = path.into_inner();
 
@@ -316,6 +349,8 @@ pub async fn approve_swap_request(
         .map_err(AppError::from)?
         .ok_or_else(|| AppError::NotFound("Swap request not found".to_string()))?;
 
+    requires_managed_swap(&ctx, &swap_request).await?;
+
     let company_id = ctx.strict_company_id()?;
 
     let shift_swap = DatabaseTransaction::run(|tx| {
@@ -424,7 +459,7 @@ pub async fn deny_swap_request(
     req_info: RequestInfo,
     cache: Data<crate::middleware::CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::SWAPS_APPROVE)?;
 
     let swap_id = path.into_inner();
 
@@ -433,6 +468,8 @@ pub async fn deny_swap_request(
         .map_err(AppError::from)?
         .ok_or_else(|| AppError::NotFound("Swap request not found".to_string()))?;
 
+    requires_managed_swap(&ctx, &swap_request).await?;
+
     let company_id = ctx.strict_company_id()?;
 
     let shift_swap = DatabaseTransaction::run(|tx| {
@@ -2,20 +2,21 @@ use actix_web::{
     HttpResponse, Result,
     web::{Data, Json, Path, Query},
 };
+use bigdecimal::BigDecimal;
 use chrono::NaiveDate;
 use serde::Deserialize;
 use uuid::Uuid;
 
 use crate::{
     database::{
-        models::{Action, PtoBalanceType, TimeOffRequestInput, TimeOffStatus, TimeOffType},
+        models::{Action, Permission, TimeOffRequestInput, TimeOffStatus},
         repositories::{pto_balance as pto_repo, time_off as time_off_repo},
         transaction::DatabaseTransaction,
     },
     error::AppError,
     handlers::shared::ApiResponse,
     middleware::{CacheLayer, cache::InvalidationContext, request_info::RequestInfo},
-    services::{activity_logger, user_context::UserContext},
+    services::{activity_logger, manager_scope, time_off, user_context::UserContext},
 };
 
 #[derive(Debug, Deserialize)]
@@ -32,6 +33,15 @@ pub struct ApprovalRequest {
     pub notes: Option<String>,
 }
 
+/// Check that a manager acting on someone else's requests manages that employee
+async fn requires_managed_user(ctx: &UserContext, user_id: Uuid) -> Result<(), AppError> {
+    if user_id == ctx.user_id() {
+        return Ok(());
+    }
+
+    manager_scope::load(ctx).await?.requires_user(user_id)
+}
+
 /// Create a new time-off request
 pub async fn create_time_off_request(
     ctx: UserContext,
@@ -42,7 +52,8 @@ pub async fn create_time_off_request(
     let request_input = input.into_inner();
     let request_user_id = request_input.user_id.clone();
 
-    ctx.requires_same_user(request_user_id)?;
+    ctx.requires_same_user_or_permission(request_user_id, Permission::TIMEOFF_APPROVE)?;
+    requires_managed_user(&ctx, request_user_id).await?;
 
     let request_type = request_input.request_type.clone();
     let start_date = request_input.start_date;
@@ -52,6 +63,20 @@ pub async fn create_time_off_request(
 
     ctx.requires_same_company(company_id)?;
 
+    time_off::validate_request(&request_input)?;
+    if let Some(deduction) = time_off::deduction(
+        requesting_user_id,
+        company_id,
+        &request_type,
+        request_input.duration_type,
+        start_date,
+        end_date,
+    )
+    .await?
+    {
+        time_off::requires_balance(requesting_user_id, company_id, &deduction).await?;
+    }
+
     let time_off_request = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
             let time_off_request = time_off_repo::create_request(tx, request_input).await?;
@@ -127,12 +152,20 @@ pub async fn get_time_off_requests(
     let start_date = query.start_date;
     let end_date = query.end_date;
 
-    ctx.requires_same_user_or(target_user_id, "Cannot view other users' requests")?;
+    ctx.requires_same_user_or_permission(target_user_id, Permission::TIMEOFF_VIEW)?;
+    requires_managed_user(&ctx, target_user_id).await?;
+
+    let company_id = ctx.strict_company_id()?;
 
-    let time_off_requests =
-        time_off_repo::get_requests(Some(target_user_id), status_filter, start_date, end_date)
-            .await
-            .map_err(AppError::from)?;
+    let time_off_requests = time_off_repo::get_requests(
+        company_id,
+        Some(target_user_id),
+        status_filter,
+        start_date,
+        end_date,
+    )
+    .await
+    .map_err(AppError::from)?;
 
     Ok(ApiResponse::success(time_off_requests))
 }
@@ -146,10 +179,8 @@ pub async fn get_time_off_request(path: Path<Uuid>, ctx: UserContext) -> Result<
         .map_err(AppError::from)?
         .ok_or_else(|| AppError::NotFound(format!("Time-off request not found: {}", request_id)))?;
 
-    ctx.requires_same_user_or(
-        time_off_request.user_id,
-        "Cannot view other users' requests",
-    )?;
+    ctx.requires_same_user_or_permission(time_off_request.user_id, Permission::TIMEOFF_VIEW)?;
+    requires_managed_user(&ctx, time_off_request.user_id).await?;
 
     Ok(ApiResponse::success(time_off_request))
 }
@@ -176,10 +207,8 @@ pub async fn update_time_off_request(
         .map_err(AppError::from)?
         .ok_or_else(|| AppError::NotFound(format!("Time-off request not found: {}", request_id)))?;
 
-    ctx.requires_same_user_or(
-        time_off_request.user_id,
-        "Cannot update other users' requests",
-    )?;
+    ctx.requires_same_user_or_permission(time_off_request.user_id, Permission::TIMEOFF_APPROVE)?;
+    requires_managed_user(&ctx, time_off_request.user_id).await?;
 
     // Only allow updates to pending requests
     if time_off_request.status != TimeOffStatus::Pending {
@@ -193,6 +222,20 @@ pub async fn update_time_off_request(
             .json(ApiResponse::<()>::error("Cannot update request user ID")));
     }
 
+    time_off::validate_request(&request_input)?;
+    if let Some(deduction) = time_off::deduction(
+        requesting_user_id,
+        company_id,
+        &new_request_type,
+        request_input.duration_type,
+        new_start_date,
+        new_end_date,
+    )
+    .await?
+    {
+        time_off::requires_balance(requesting_user_id, company_id, &deduction).await?;
+    }
+
     let updated_request = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
             let updated_request =
@@ -215,6 +258,8 @@ pub async fn update_time_off_request(
                 ),
                 ("previous_end_date", time_off_request.end_date.to_string()),
             ]);
+            let metadata =
+                activity_logger::with_changes(metadata, &time_off_request, &updated_request);
 
             activity_logger::log_time_off_activity(
                 tx,
@@ -288,7 +333,8 @@ pub async fn delete_time_off_request(
     let target_user_id = time_off_request.user_id;
     let company_id = ctx.strict_company_id()?;
 
-    ctx.requires_same_user_or(target_user_id, "Cannot delete other users' requests")?;
+    ctx.requires_same_user_or_permission(target_user_id, Permission::TIMEOFF_APPROVE)?;
+    requires_managed_user(&ctx, target_user_id).await?;
 
     // Only allow deletion of pending requests
     if time_off_request.status != TimeOffStatus::Pending {
@@ -368,7 +414,7 @@ pub async fn approve_time_off_request(
     req_info: RequestInfo,
     cache: Data<CacheLayer>,
 ) -> Result<HttpResponse> {
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::TIMEOFF_APPROVE)?;
 
     let request_id = path.into_inner();
     let company_id = ctx.strict_company_id()?;
@@ -379,78 +425,71 @@ pub async fn approve_time_off_request(
         .map_err(AppError::from)?
         .ok_or_else(|| AppError::NotFound(format!("Time-off request not found: {}", request_id)))?;
 
-    // Calculate hours for the request (simple calculation: 8 hours per day)
-    let days = (time_off_request.end_date - time_off_request.start_date).num_days() + 1;
-    let hours_needed = (days * 8) as i32;
-
-    // Map TimeOffType to PtoBalanceType
-    let balance_type = match time_off_request.request_type {
-        TimeOffType::Vacation => PtoBalanceType::Pto,
-        TimeOffType::Sick => PtoBalanceType::Sick,
-        TimeOffType::Personal => PtoBalanceType::Personal,
-        TimeOffType::Emergency => PtoBalanceType::Personal,
-        TimeOffType::Bereavement => PtoBalanceType::Personal,
-        TimeOffType::MaternityPaternity => PtoBalanceType::Pto,
-        TimeOffType::Other => PtoBalanceType::Pto, // Default to PTO for other types
-    };
-
-    // Check if user has sufficient balance
-    let user_balance = pto_repo::get_balance_for_company(time_off_request.user_id, company_id)
-        .await
-        .map_err(AppError::from)?
-        .ok_or_else(|| {
-            AppError::NotFound(format!(
-                "PTO balance not found for user: {}",
-                time_off_request.user_id
-            ))
-        })?;
-
-    let available_balance = match balance_type {
-        PtoBalanceType::Pto => user_balance.pto_balance_hours,
-        PtoBalanceType::Sick => user_balance.sick_balance_hours,
-        PtoBalanceType::Personal => user_balance.personal_balance_hours,
-    };
-
-    if available_balance < hours_needed {
-        return Err(AppError::BadRequest(format!(
-            "Insufficient {} balance: requested {} hours, available {} hours",
-            balance_type, hours_needed, available_balance
-        ))
-        .into());
+    manager_scope::load(&ctx)
+        .await?
+        .requires_user(time_off_request.user_id)?;
+
+    if time_off_request.status != TimeOffStatus::Pending {
+        return Err(
+            AppError::BadRequest("Only pending requests can be approved".to_string()).into(),
+        );
+    }
+
+    // Work out the hours from the current schedule and check the balance covers them
+    let deduction = time_off::deduction(
+        time_off_request.user_id,
+        company_id,
+        &time_off_request.request_type,
+        time_off_request.duration_type,
+        time_off_request.start_date,
+        time_off_request.end_date,
+    )
+    .await?;
+    if let Some(deduction) = &deduction {
+        time_off::requires_balance(time_off_request.user_id, company_id, deduction).await?;
     }
 
     let approved_request = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
             // Approve the request
-            let balance_type_for_logging = balance_type.clone();
             let approved_request = time_off_repo::approve_request(
                 tx,
                 request_id,
                 ctx.user_id(),
                 approval.notes.clone(),
+                deduction.as_ref().map(|deduction| deduction.hours.clone()),
             )
-            .await?;
-
-            // Deduct PTO balance
-            pto_repo::use_balance_for_time_off_for_company(
-                tx,
-                time_off_request.user_id,
-                company_id,
-                request_id,
-                balance_type,
-                hours_needed,
-            )
-            .await?;
+            .await?
+            .ok_or_else(|| {
+                AppError::Conflict("Time-off request is no longer pending".to_string())
+            })?;
 
-            // Log time-off request approval activity
-            let metadata = activity_logger::metadata(vec![
+            let mut metadata = vec![
                 ("request_type", time_off_request.request_type.to_string()),
                 ("target_user", time_off_request.user_id.to_string()),
-                ("hours_deducted", hours_needed.to_string()),
-                ("balance_type", format!("{:?}", balance_type_for_logging)),
+                ("duration_type", time_off_request.duration_type.to_string()),
                 ("approval_notes", approval.notes.clone().unwrap_or_default()),
-            ]);
+            ];
+
+            // Deduct PTO balance
+            if let Some(deduction) = deduction {
+                if deduction.hours > BigDecimal::from(0) {
+                    pto_repo::use_balance_for_time_off_for_company(
+                        tx,
+                        &time_off_request,
+                        deduction.balance_type,
+                        deduction.hours.clone(),
+                        deduction.allow_negative,
+                        Some(ctx.user_id()),
+                    )
+                    .await?;
+                }
+
+                metadata.push(("hours_deducted", deduction.hours.normalized().to_string()));
+                metadata.push(("balance_type", deduction.balance_type.to_string()));
+            }
 
+            // Log time-off request approval activity
             activity_logger::log_time_off_activity(
                 tx,
                 company_id,
@@ -458,7 +497,7 @@ pub async fn approve_time_off_request(
                 request_id,
                 &Action::APPROVED,
                 "Time-off request approved".to_string(),
-                Some(metadata),
+                Some(activity_logger::metadata(metadata)),
                 &req_info,
             )
             .await?;
@@ -502,6 +541,18 @@ pub async fn approve_time_off_request(
         )
         .await;
 
+    // Deducted hours change the balance
+    cache
+        .invalidate(
+            "pto_balance",
+            &InvalidationContext {
+                company_id: Some(company_id),
+                user_id: Some(approved_request.user_id),
+                ..Default::default()
+            },
+        )
+        .await;
+
     Ok(ApiResponse::success(approved_request))
 }
 
@@ -514,7 +565,7 @@ pub async fn deny_time_off_request(
     cache: Data<CacheLayer>,
 ) -> Result<HttpResponse> {
     // Only managers and admins can deny requests
-    ctx.requires_manager()?;
+    ctx.requires_permission(Permission::TIMEOFF_APPROVE)?;
 
     let request_id = path.into_inner();
     let company_id = ctx.strict_company_id()?;
@@ -525,11 +576,18 @@ pub async fn deny_time_off_request(
         .map_err(AppError::from)?
         .ok_or_else(|| AppError::NotFound(format!("Time-off request not found: {}", request_id)))?;
 
+    manager_scope::load(&ctx)
+        .await?
+        .requires_user(time_off_request.user_id)?;
+
     let denied_request = DatabaseTransaction::run(|tx| {
         Box::pin(async move {
             let denied_request =
                 time_off_repo::deny_request(tx, request_id, ctx.user_id(), denial.notes.clone())
-                    .await?;
+                    .await?
+                    .ok_or_else(|| {
+                        AppError::Conflict("Time-off request is no longer pending".to_string())
+                    })?;
 
             // Log time-off request denial activity
             let metadata = activity_logger::metadata(vec![
@@ -592,6 +650,140 @@ pub async fn deny_time_off_request(
     Ok(ApiResponse::success(denied_request))
 }
 
+/// Cancel a pending or approved time-off request, giving back any hours it took from the balance
+pub async fn cancel_time_off_request(
+    path: Path<Uuid>,
+    ctx: UserContext,
+    cancellation: Json<ApprovalRequest>,
+    req_info: RequestInfo,
+    cache: Data<CacheLayer>,
+) -> Result<HttpResponse> {
+    let request_id = path.into_inner();
+    let company_id = ctx.strict_company_id()?;
+
+    let time_off_request = time_off_repo::get_request_by_id(request_id)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::NotFound(format!("Time-off request not found: {}", request_id)))?;
+
+    ctx.requires_same_company(time_off_request.company_id)?;
+    ctx.requires_same_user_or_permission(time_off_request.user_id, Permission::TIMEOFF_APPROVE)?;
+    requires_managed_user(&ctx, time_off_request.user_id).await?;
+
+    if !matches!(
+        time_off_request.status,
+        TimeOffStatus::Pending | TimeOffStatus::Approved
+    ) {
+        return Err(AppError::BadRequest(
+            "Only pending or approved requests can be cancelled".to_string(),
+        )
+        .into());
+    }
+
+    let cancelled_request = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let cancelled_request = time_off_repo::cancel_request(
+                tx,
+                request_id,
+                ctx.user_id(),
+                cancellation.notes.clone(),
+            )
+            .await?
+            .ok_or_else(|| {
+                AppError::Conflict("Time-off request is no longer pending or approved".to_string())
+            })?;
+
+            let refunds = pto_repo::refund_time_off_for_company(
+                tx,
+                time_off_request.user_id,
+                company_id,
+                request_id,
+                Some(ctx.user_id()),
+            )
+            .await?;
+
+            let mut metadata = vec![
+                ("request_type", time_off_request.request_type.to_string()),
+                ("target_user", time_off_request.user_id.to_string()),
+                ("previous_status", time_off_request.status.to_string()),
+                (
+                    "cancellation_notes",
+                    cancellation.notes.clone().unwrap_or_default(),
+                ),
+            ];
+            if let Some(refund) = refunds.first() {
+                metadata.push(("balance_type", refund.balance_type.to_string()));
+                metadata.push((
+                    "hours_refunded",
+                    refund.hours_changed.normalized().to_string(),
+                ));
+            }
+
+            activity_logger::log_time_off_activity(
+                tx,
+                company_id,
+                Some(ctx.user_id()),
+                request_id,
+                Action::CANCELLED,
+                "Time-off request cancelled".to_string(),
+                Some(activity_logger::metadata(metadata)),
+                &req_info,
+            )
+            .await?;
+
+            Ok(cancelled_request)
+        })
+    })
+    .await?;
+
+    // Smart cache invalidation - cancel_time_off_request
+    cache
+        .invalidate(
+            "time-off",
+            &InvalidationContext {
+                company_id: Some(company_id),
+                user_id: Some(cancelled_request.user_id),
+                resource_id: Some(request_id),
+            },
+        )
+        .await;
+
+    // Time-off affects shift availability and stats
+    cache
+        .invalidate(
+            "shifts",
+            &InvalidationContext {
+                company_id: Some(company_id),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    cache
+        .invalidate(
+            "stats",
+            &InvalidationContext {
+                company_id: Some(company_id),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    // Refunded hours change the balance
+    cache
+        .invalidate(
+            "pto_balance",
+            &InvalidationContext {
+                company_id: Some(company_id),
+                user_id: Some(cancelled_request.user_id),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    Ok(ApiResponse::success(cancelled_request))
+}
+
 // /// Wrapper function for approving time-off requests with PTO balance integration
 // async fn approve_time_off_with_balance_check(
 //     AsyncUserContext(user_context): AsyncUserContext,
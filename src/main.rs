@@ -7,10 +7,13 @@ use be::{
     database::init_database,
     handlers::shared::ApiResponse,
     middleware::{
-        CacheLayer, GlobalRateLimiter, RateLimitStore, RequestIdMiddleware, RequestInfoMiddleware,
-        ResponseCacheMiddleware, cleanup_rate_limits,
+        ApiKeyMiddleware, CacheLayer, GlobalRateLimiter, RateLimitStore, RequestIdMiddleware,
+        RequestInfoMiddleware, ResponseCacheMiddleware, cleanup_rate_limits,
     },
     routes,
+    services::{
+        activity_archive::run_archiver, activity_chain::run_checkpoints, jwt_keys::init_key_ring,
+    },
 };
 
 #[get("/")]
@@ -40,6 +43,10 @@ async fn main() -> Result<()> {
         config.environment
     );
 
+    // Load JWT signing and verification keys up front so bad keys fail the boot
+    init_key_ring(&config)?;
+    println!("🔑 JWT keys loaded (algorithm: {})", config.jwt_algorithm);
+
     // Initialize database
     let run_migrations = config.run_migrations;
     println!("🔗 Initializing database at {}", config.database_url);
@@ -56,6 +63,23 @@ async fn main() -> Result<()> {
         cleanup_rate_limits(cleanup_store, 300).await; // Cleanup every 5 minutes
     });
 
+    // Periodically sign the activity log chain heads
+    if config.activity_checkpoint_minutes > 0 {
+        let interval_minutes = config.activity_checkpoint_minutes;
+        tokio::spawn(async move {
+            run_checkpoints(interval_minutes).await;
+        });
+    }
+
+    // Archive activity that is past each company's retention period
+    if config.activity_archive_hours > 0 {
+        let archive_dir = config.activity_archive_dir.clone();
+        let interval_hours = config.activity_archive_hours;
+        tokio::spawn(async move {
+            run_archiver(archive_dir, interval_hours).await;
+        });
+    }
+
     // Create shared cache layer
     let cache_layer = CacheLayer::new(10000, 300); // 10k capacity, 5min TTL
     println!("🧠 Cache layer initialized (capacity: 10000, TTL: 300s)");
@@ -84,6 +108,7 @@ async fn main() -> Result<()> {
             )
             .wrap(ResponseCacheMiddleware::new(cache_layer.clone()))
             .wrap(GlobalRateLimiter::general()) // Global rate limiting
+            .wrap(ApiKeyMiddleware) // Resolves API keys for per-key rate limits
             .wrap(RequestIdMiddleware)
             .wrap(RequestInfoMiddleware)
             .wrap(Logger::new(
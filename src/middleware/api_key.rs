@@ -0,0 +1,91 @@
+use std::{
+    future::{Ready, ready},
+    rc::Rc,
+};
+
+use actix_web::{
+    Error, HttpMessage,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    http::header::AUTHORIZATION,
+};
+use futures_util::future::LocalBoxFuture;
+
+use crate::{middleware::RequestInfo, services::api_keys};
+
+// Middleware factory
+//
+// Resolves `Authorization: Bearer slk_...` keys before the rate limiter runs so requests
+// can be limited per key. Unknown or revoked keys are passed through untouched and
+// rejected when the handler extracts its `UserContext`.
+pub struct ApiKeyMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for ApiKeyMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = ApiKeyMiddlewareService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ApiKeyMiddlewareService {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct ApiKeyMiddlewareService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for ApiKeyMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+
+        let key = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .filter(|token| api_keys::is_api_key(token))
+            .map(str::to_string);
+
+        Box::pin(async move {
+            if let Some(key) = key {
+                let ip_address = req
+                    .extensions()
+                    .get::<RequestInfo>()
+                    .map(|info| info.ip_address.clone())
+                    .unwrap_or_else(|| "unknown".to_string());
+
+                match api_keys::authenticate(&key, &ip_address).await {
+                    Ok(Some(api_key)) => {
+                        if let Some(info) = req.extensions_mut().get_mut::<RequestInfo>() {
+                            info.api_key_id = Some(api_key.id);
+                        }
+                        req.extensions_mut().insert(api_key);
+                    }
+                    Ok(None) => {}
+                    Err(e) => log::error!("Failed to authenticate API key: {}", e),
+                }
+            }
+
+            service.call(req).await
+        })
+    }
+}
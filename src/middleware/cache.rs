@@ -144,7 +144,7 @@ impl TagInferenceEngine {
             // Auth routes
             ResourcePattern {
                 name: "auth",
-                path_regex: Regex::new(r"/api/v1/auth/(register|login|forgot-password|reset-password|me|invite|invites|switch-company)").unwrap(),
+                path_regex: Regex::new(r"/api/v1/auth/(register|login|2fa|sso|refresh|logout|logout-all|sessions|forgot-password|reset-password|me|invite|invites|switch-company)").unwrap(),
                 id_capture_group: None,
                 query_params: vec![],
             },
@@ -1,8 +1,10 @@
+pub mod api_key;
 pub mod cache;
 pub mod rate_limit;
 pub mod request_id;
 pub mod request_info;
 
+pub use api_key::{ApiKeyMiddleware, ApiKeyMiddlewareService};
 pub use cache::{CacheLayer, ResponseCacheMiddleware};
 pub use rate_limit::{
     AuthRateLimiter, GlobalRateLimiter, RateLimitConfig, RateLimitMiddleware, RateLimitStore,
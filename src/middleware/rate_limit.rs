@@ -12,7 +12,7 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use crate::{handlers::shared::ApiResponse, user_context::UserContext};
+use crate::{database::models::ApiKey, handlers::shared::ApiResponse, user_context::UserContext};
 
 /// Rate limit configuration
 #[derive(Clone, Debug)]
@@ -147,6 +147,7 @@ pub struct RateLimitMiddleware {
     store: RateLimitStore,
     config: RateLimitConfig,
     apply_to_authenticated: bool,
+    api_key_limits: bool,
 }
 
 impl RateLimitMiddleware {
@@ -155,6 +156,7 @@ impl RateLimitMiddleware {
             store: RateLimitStore::new(),
             config,
             apply_to_authenticated: false,
+            api_key_limits: false,
         }
     }
 
@@ -163,6 +165,7 @@ impl RateLimitMiddleware {
             store,
             config,
             apply_to_authenticated: false,
+            api_key_limits: false,
         }
     }
 
@@ -171,6 +174,12 @@ impl RateLimitMiddleware {
         self.apply_to_authenticated = true;
         self
     }
+
+    /// Limit API key requests by each key's own per-minute allowance
+    pub fn with_api_key_limits(mut self) -> Self {
+        self.api_key_limits = true;
+        self
+    }
 }
 
 impl<S, B> Transform<S, ServiceRequest> for RateLimitMiddleware
@@ -191,6 +200,7 @@ where
             store: self.store.clone(),
             config: self.config.clone(),
             apply_to_authenticated: self.apply_to_authenticated,
+            api_key_limits: self.api_key_limits,
         }))
     }
 }
@@ -200,6 +210,7 @@ pub struct RateLimitService<S> {
     store: RateLimitStore,
     config: RateLimitConfig,
     apply_to_authenticated: bool,
+    api_key_limits: bool,
 }
 
 impl<S, B> Service<ServiceRequest> for RateLimitService<S>
@@ -219,8 +230,37 @@ where
         let store = self.store.clone();
         let config = self.config.clone();
         let apply_to_authenticated = self.apply_to_authenticated;
+        let api_key_limits = self.api_key_limits;
 
         Box::pin(async move {
+            // API keys are tracked per key rather than by the caller's IP
+            let api_key = if apply_to_authenticated {
+                req.extensions()
+                    .get::<ApiKey>()
+                    .map(|key| (key.id, key.rate_limit_per_minute))
+            } else {
+                None
+            };
+
+            if let Some((api_key_id, rate_limit_per_minute)) = api_key {
+                let key_config = if api_key_limits {
+                    RateLimitConfig::new(rate_limit_per_minute.max(1) as u32, 60)
+                        .with_message(config.message.clone())
+                } else {
+                    config.clone()
+                };
+
+                if !store.check_and_update_user(&format!("api_key:{}", api_key_id), &key_config) {
+                    log::warn!("Rate limit exceeded for API key: {}", api_key_id);
+                    let response = HttpResponse::TooManyRequests()
+                        .json(ApiResponse::<()>::error(&key_config.message));
+                    return Ok(req.into_response(response).map_into_right_body());
+                }
+
+                let res = service.call(req).await?;
+                return Ok(res.map_into_left_body());
+            }
+
             // Extract information we need from the request before using it
             let client_ip = req
                 .connection_info()
@@ -296,6 +336,16 @@ impl AuthRateLimiter {
                 ),
         )
     }
+
+    /// Create rate limiter for refresh token exchanges
+    pub fn token_refresh() -> RateLimitMiddleware {
+        RateLimitMiddleware::new(
+            RateLimitConfig::new(30, 300) // 30 refreshes per 5 minutes
+                .with_message(
+                    "Too many token refresh attempts. Please try again later.".to_string(),
+                ),
+        )
+    }
 }
 
 /// Global rate limiter configurations
@@ -308,6 +358,7 @@ impl GlobalRateLimiter {
             RateLimitConfig::new(100, 60), // 100 requests per minute
         )
         .with_authenticated_users()
+        .with_api_key_limits()
     }
 
     /// Stricter rate limiter for sensitive operations
@@ -5,6 +5,9 @@ use actix_web::{
     Error, FromRequest, HttpMessage, HttpRequest,
 };
 use futures_util::future::{ready, Ready};
+use uuid::Uuid;
+
+use crate::services::auth::verify_token;
 
 #[derive(Clone, Debug)]
 pub struct RequestInfo {
@@ -12,6 +15,21 @@ pub struct RequestInfo {
     pub ip_address: String,
     pub method: String,
     pub path: String,
+    pub api_key_id: Option<Uuid>, // Set when the request authenticated with an API key
+    pub impersonator_id: Option<Uuid>, // Admin behind an impersonation token
+    pub impersonated_user_id: Option<Uuid>, // User the admin is acting as
+}
+
+/// The admin and the user they act as, when the request carries an impersonation token
+fn impersonation(req: &HttpRequest) -> Option<(Uuid, Uuid)> {
+    let token = req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))?;
+    let claims = verify_token(token).ok()?;
+
+    claims.act.map(|admin_id| (admin_id, claims.sub))
 }
 
 impl FromRequest for RequestInfo {
@@ -24,6 +42,7 @@ impl FromRequest for RequestInfo {
             ready(Ok(request_info.clone()))
         } else {
             // Fallback: create RequestInfo directly from HttpRequest
+            let impersonation = impersonation(req);
             let request_info = RequestInfo {
                 user_agent: req
                     .headers()
@@ -38,6 +57,9 @@ impl FromRequest for RequestInfo {
                     .to_string(),
                 method: req.method().to_string(),
                 path: req.path().to_string(),
+                api_key_id: None, // Filled in by ApiKeyMiddleware
+                impersonator_id: impersonation.map(|(admin_id, _)| admin_id),
+                impersonated_user_id: impersonation.map(|(_, user_id)| user_id),
             };
             ready(Ok(request_info))
         }
@@ -88,6 +110,7 @@ where
         Box::pin(async move {
             // Create request info directly from ServiceRequest
             let http_req = req.request();
+            let impersonation = impersonation(http_req);
             let request_info = RequestInfo {
                 user_agent: http_req
                     .headers()
@@ -102,6 +125,9 @@ where
                     .to_string(),
                 method: http_req.method().to_string(),
                 path: http_req.path().to_string(),
+                api_key_id: None, // Filled in by ApiKeyMiddleware
+                impersonator_id: impersonation.map(|(admin_id, _)| admin_id),
+                impersonated_user_id: impersonation.map(|(_, user_id)| user_id),
             };
 
             req.extensions_mut().insert(request_info);
@@ -29,8 +29,48 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
                 "/teams/{team_id}/members/{user_id}",
                 web::delete().to(admin::remove_team_member),
             )
+            .route("/activity", web::get().to(admin::get_activity))
+            .route("/activity/export", web::get().to(admin::export_activity))
+            .route("/activity/verify", web::get().to(admin::verify_activity))
+            .route(
+                "/activity/retention",
+                web::get().to(admin::get_activity_retention),
+            )
+            .route(
+                "/activity/retention",
+                web::put().to(admin::update_activity_retention),
+            )
+            .route(
+                "/activity/archives",
+                web::get().to(admin::get_activity_archives),
+            )
+            .route(
+                "/activity/archives/{id}/download",
+                web::get().to(admin::download_activity_archive),
+            )
+            .route(
+                "/activity/checkpoints",
+                web::get().to(admin::get_activity_checkpoints),
+            )
+            .route(
+                "/activity/checkpoints",
+                web::post().to(admin::create_activity_checkpoint),
+            )
+            .route(
+                "/activity/{entity_type}/{entity_id}",
+                web::get().to(admin::get_entity_activity),
+            )
+            .route(
+                "/activity/{entity_type}/{entity_id}/changes",
+                web::get().to(admin::get_entity_changes),
+            )
             .route("/users", web::get().to(admin::get_users))
             .route("/users/{id}", web::put().to(admin::update_user))
-            .route("/users/{id}", web::delete().to(admin::delete_user)),
+            .route("/users/{id}", web::delete().to(admin::delete_user))
+            .route("/users/{id}/unlock", web::post().to(admin::unlock_user))
+            .route(
+                "/users/{id}/impersonate",
+                web::post().to(admin::impersonate_user),
+            ),
     );
 }
@@ -19,6 +19,52 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
                     .wrap(AuthRateLimiter::login())
                     .route(web::post().to(auth::login)),
             )
+            .service(
+                web::resource("/login/2fa")
+                    .wrap(AuthRateLimiter::login())
+                    .route(web::post().to(auth::login_two_factor)),
+            )
+            .service(
+                web::resource("/sso/{company_id}/start")
+                    .wrap(AuthRateLimiter::login())
+                    .route(web::post().to(auth::sso_start)),
+            )
+            .service(
+                web::resource("/sso/callback")
+                    .wrap(AuthRateLimiter::login())
+                    .route(web::post().to(auth::sso_callback)),
+            )
+            .service(
+                web::resource("/sso/link")
+                    .wrap(AuthRateLimiter::login())
+                    .route(web::post().to(auth::sso_link)),
+            )
+            .route("/2fa", web::get().to(auth::get_two_factor_status))
+            .route("/2fa/setup", web::post().to(auth::setup_two_factor))
+            .service(
+                web::resource("/2fa/enable")
+                    .wrap(AuthRateLimiter::login())
+                    .route(web::post().to(auth::enable_two_factor)),
+            )
+            .service(
+                web::resource("/2fa/disable")
+                    .wrap(AuthRateLimiter::login())
+                    .route(web::post().to(auth::disable_two_factor)),
+            )
+            .service(
+                web::resource("/2fa/recovery-codes")
+                    .wrap(AuthRateLimiter::login())
+                    .route(web::post().to(auth::regenerate_recovery_codes)),
+            )
+            .service(
+                web::resource("/refresh")
+                    .wrap(AuthRateLimiter::token_refresh())
+                    .route(web::post().to(auth::refresh_token)),
+            )
+            .route("/logout", web::post().to(auth::logout))
+            .route("/logout-all", web::post().to(auth::logout_all))
+            .route("/sessions", web::get().to(auth::get_sessions))
+            .route("/sessions/{id}", web::delete().to(auth::revoke_session))
             .service(
                 web::resource("/forgot-password")
                     .wrap(AuthRateLimiter::password_reset())
@@ -29,7 +75,32 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
                     .wrap(AuthRateLimiter::password_reset())
                     .route(web::post().to(auth::reset_password)),
             )
+            .service(
+                web::resource("/verify-email")
+                    .wrap(AuthRateLimiter::password_reset())
+                    .route(web::post().to(auth::verify_email)),
+            )
+            .service(
+                web::resource("/verify-email/resend")
+                    .wrap(AuthRateLimiter::password_reset())
+                    .route(web::post().to(auth::resend_verification)),
+            )
+            .service(
+                web::resource("/change-email")
+                    .wrap(AuthRateLimiter::password_reset())
+                    .route(web::post().to(auth::change_email)),
+            )
             .route("/me", web::get().to(auth::me))
+            .route("/me/export", web::get().to(auth::export_personal_data))
+            .service(
+                web::resource("/me/erase")
+                    .wrap(AuthRateLimiter::password_reset())
+                    .route(web::post().to(auth::erase_account)),
+            )
+            .route(
+                "/impersonation/end",
+                web::post().to(auth::end_impersonation),
+            )
             .route("/invite", web::post().to(auth::create_invite))
             .route("/invite/{token}", web::get().to(auth::get_invite))
             .route(
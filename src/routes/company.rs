@@ -20,15 +20,111 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
                     .wrap(GlobalRateLimiter::sensitive())
                     .route(web::post().to(company::add_employee_to_company)),
             )
+            .service(
+                web::resource("/employees/import")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::post().to(company::import_employees)),
+            )
             .service(
                 web::resource("/employees/{user_id}")
                     .wrap(GlobalRateLimiter::sensitive())
                     .route(web::delete().to(company::remove_employee_from_company)),
             )
+            .service(
+                web::resource("/security")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::put().to(company::update_security_settings)),
+            )
+            .route(
+                "/time-off-settings",
+                web::get().to(company::get_time_off_settings),
+            )
+            .service(
+                web::resource("/time-off-settings")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::put().to(company::update_time_off_settings)),
+            )
+            .route("/sso", web::get().to(company::get_sso_config))
+            .service(
+                web::resource("/sso")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::put().to(company::update_sso_config))
+                    .route(web::delete().to(company::delete_sso_config)),
+            )
+            .route("/sso/domains", web::get().to(company::get_sso_domains))
+            .service(
+                web::resource("/sso/domains")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::post().to(company::add_sso_domain)),
+            )
+            .service(
+                web::resource("/sso/domains/{id}/verify")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::post().to(company::verify_sso_domain)),
+            )
+            .service(
+                web::resource("/sso/domains/{id}")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::delete().to(company::delete_sso_domain)),
+            )
+            .route("/api-keys", web::get().to(company::get_api_keys))
+            .service(
+                web::resource("/api-keys")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::post().to(company::create_api_key)),
+            )
+            .service(
+                web::resource("/api-keys/{id}")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::delete().to(company::revoke_api_key)),
+            )
+            .route("/permissions", web::get().to(company::get_permissions))
+            .route("/roles", web::get().to(company::get_roles))
+            .service(
+                web::resource("/roles")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::post().to(company::create_role)),
+            )
+            .service(
+                web::resource("/roles/{id}")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::put().to(company::update_role))
+                    .route(web::delete().to(company::delete_role)),
+            )
+            .service(
+                web::resource("/roles/members/{user_id}")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::put().to(company::assign_role)),
+            )
             .service(
                 web::resource("/employees/{user_id}/role")
                     .wrap(GlobalRateLimiter::sensitive())
                     .route(web::put().to(company::update_employee_role)),
+            )
+            .route(
+                "/employees/{user_id}/scope",
+                web::get().to(company::get_member_scope),
+            )
+            .service(
+                web::resource("/employees/{user_id}/scope")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::put().to(company::update_member_scope)),
+            )
+            .route("/invites", web::get().to(company::get_invites))
+            .service(
+                web::resource("/invites/bulk")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::post().to(company::bulk_invite)),
+            )
+            .service(
+                web::resource("/invites/{id}")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::delete().to(company::revoke_invite)),
+            )
+            .service(
+                web::resource("/invites/{id}/resend")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::post().to(company::resend_invite)),
             ),
     );
 }
@@ -0,0 +1,29 @@
+use actix_web::web;
+
+use crate::handlers::holidays;
+use crate::middleware::GlobalRateLimiter;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/holidays")
+            .route("/calendar", web::get().to(holidays::get_calendar))
+            .service(
+                // Apply stricter rate limiting to calendar imports and holiday changes
+                web::resource("/import")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::post().to(holidays::import_holidays)),
+            )
+            .service(
+                web::resource("")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::get().to(holidays::get_holidays))
+                    .route(web::post().to(holidays::create_holiday)),
+            )
+            .service(
+                web::resource("/{holiday_id}")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::put().to(holidays::update_holiday))
+                    .route(web::delete().to(holidays::delete_holiday)),
+            ),
+    );
+}
@@ -1,9 +1,13 @@
 use actix_web::web;
 
+use crate::handlers::auth as auth_handlers;
+
 pub mod admin;
 pub mod auth;
 pub mod company;
+pub mod holidays;
 pub mod pto_balance;
+pub mod pto_policies;
 pub mod schedules;
 pub mod shifts;
 pub mod skills;
@@ -13,6 +17,9 @@ pub mod swaps;
 pub mod time_off;
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
+    // Public signing keys for services verifying our tokens
+    cfg.route("/.well-known/jwks.json", web::get().to(auth_handlers::jwks));
+
     cfg.service(
         web::scope("/api/v1")
             .configure(auth::configure)
@@ -23,6 +30,8 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             .configure(stats::configure)
             .configure(subscription::configure)
             .configure(pto_balance::configure)
+            .configure(pto_policies::configure)
+            .configure(holidays::configure)
             .configure(skills::configure)
             .configure(schedules::configure)
             .configure(company::configure),
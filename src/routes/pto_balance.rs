@@ -7,6 +7,7 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/pto-balance")
             .route("", web::get().to(pto_balance::get_pto_balance))
+            .route("/as-of", web::get().to(pto_balance::get_pto_balance_as_of))
             .route("/{user_id}", web::get().to(pto_balance::get_pto_balance))
             .route(
                 "/{user_id}/history",
@@ -0,0 +1,43 @@
+use actix_web::web;
+
+use crate::handlers::pto_policies;
+use crate::middleware::GlobalRateLimiter;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/pto-policies")
+            .route(
+                "/accrual/preview",
+                web::get().to(pto_policies::preview_accrual),
+            )
+            .service(
+                // Apply stricter rate limiting to accrual runs and policy changes
+                web::resource("/accrual/run")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::post().to(pto_policies::run_accrual)),
+            )
+            .service(
+                web::resource("")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::get().to(pto_policies::get_policies))
+                    .route(web::post().to(pto_policies::create_policy)),
+            )
+            .service(
+                web::resource("/{policy_id}")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::get().to(pto_policies::get_policy))
+                    .route(web::put().to(pto_policies::update_policy))
+                    .route(web::delete().to(pto_policies::delete_policy)),
+            )
+            .service(
+                web::resource("/{policy_id}/assignments")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::post().to(pto_policies::create_assignment)),
+            )
+            .service(
+                web::resource("/{policy_id}/assignments/{assignment_id}")
+                    .wrap(GlobalRateLimiter::sensitive())
+                    .route(web::delete().to(pto_policies::delete_assignment)),
+            ),
+    );
+}
@@ -18,6 +18,8 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             .route("/{id}/unassign", web::post().to(shifts::unassign_shift))
             .route("/{id}/status", web::post().to(shifts::update_shift_status))
             .route("/{id}/claim", web::post().to(shifts::claim_shift))
+            .route("/{id}/clock-in", web::post().to(shifts::clock_in))
+            .route("/{id}/clock-out", web::post().to(shifts::clock_out))
             .route("/{id}/claims", web::get().to(shifts::get_shift_claims)),
     )
     .service(
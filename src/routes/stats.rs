@@ -10,6 +10,15 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             .wrap(ResponseCacheMiddleware::new(cache_layer))
             .route("/dashboard", web::get().to(stats::get_dashboard_stats))
             .route("/shifts", web::get().to(stats::get_shift_stats))
-            .route("/time-off", web::get().to(stats::get_time_off_stats)),
+            .route("/time-off", web::get().to(stats::get_time_off_stats))
+            .route("/coverage", web::get().to(stats::get_coverage))
+            .route(
+                "/reports/employees",
+                web::get().to(stats::get_employee_report),
+            )
+            .route(
+                "/reports/fairness",
+                web::get().to(stats::get_fairness_report),
+            ),
     );
 }
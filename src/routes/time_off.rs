@@ -21,6 +21,10 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             .route(
                 "/{id}/deny",
                 web::post().to(time_off::deny_time_off_request),
+            )
+            .route(
+                "/{id}/cancel",
+                web::post().to(time_off::cancel_time_off_request),
             ),
     );
 }
@@ -0,0 +1,237 @@
+use std::future::Future;
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::database::{
+    models::{AccountLockout, Action, User},
+    repositories::{account_lockout as account_lockout_repo, company as company_repo},
+    transaction::DatabaseTransaction,
+};
+use crate::error::AppError;
+use crate::middleware::request_info::RequestInfo;
+use crate::services::{activity_logger, mailer};
+
+/// Failures older than this no longer count towards a lockout
+const ATTEMPT_WINDOW_MINUTES: i64 = 15;
+/// Failures allowed before each further attempt is delayed
+const FREE_ATTEMPTS: i32 = 2;
+/// Delay after the first attempt past `FREE_ATTEMPTS`, doubling with each failure
+const BASE_DELAY_SECONDS: i64 = 5;
+/// Failures that lock the account
+const MAX_FAILED_ATTEMPTS: i32 = 5;
+const LOCKOUT_MINUTES: i64 = 15;
+
+/// How long to refuse attempts after `failed_attempts` failures, and whether that is a lockout
+fn block_after(failed_attempts: i32) -> Option<(Duration, bool)> {
+    if failed_attempts >= MAX_FAILED_ATTEMPTS {
+        Some((Duration::minutes(LOCKOUT_MINUTES), true))
+    } else if failed_attempts > FREE_ATTEMPTS {
+        let step = (failed_attempts - FREE_ATTEMPTS - 1) as u32;
+        Some((Duration::seconds(BASE_DELAY_SECONDS << step), false))
+    } else {
+        None
+    }
+}
+
+/// Outcome of a sign-in attempt
+pub struct Attempt {
+    pub verified: bool,
+    pub lockout: Option<AccountLockout>, // Failures tracked before this attempt
+}
+
+/// Check a credential with `verify` unless the account is delayed or locked. The attempt is
+/// counted as a failure before `verify` runs and refunded if the credential matches, so
+/// concurrent attempts cannot get past the limit without holding a lock while it is checked.
+/// A refused attempt looks like a failed one to the caller, so it reveals nothing about the
+/// account.
+pub async fn attempt<V>(
+    user: &User,
+    reason: &str,
+    req_info: &RequestInfo,
+    verify: V,
+) -> Result<Attempt, AppError>
+where
+    V: Future<Output = Result<bool, AppError>>,
+{
+    let user_id = user.id;
+    let reservation = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let lockout = account_lockout_repo::lock(tx, user_id).await?;
+            if lockout.is_blocked() {
+                return Ok(Err(lockout));
+            }
+            let tracked = (lockout.failed_attempts > 0).then_some(lockout);
+            let reserved = reserve_failure(tx, user_id).await?;
+            Ok(Ok((tracked, reserved)))
+        })
+    })
+    .await?;
+
+    let (tracked, reserved) = match reservation {
+        Ok(reservation) => reservation,
+        Err(lockout) => {
+            log::warn!(
+                "Refused sign-in for user {} from {}: {} until {:?}",
+                user_id,
+                req_info.ip_address,
+                if lockout.is_locked() {
+                    "locked"
+                } else {
+                    "delayed"
+                },
+                lockout.locked_until
+            );
+            return Ok(Attempt {
+                verified: false,
+                lockout: Some(lockout),
+            });
+        }
+    };
+
+    let verified = match verify.await {
+        Ok(verified) => verified,
+        Err(e) => {
+            refund(user_id, &reserved).await?;
+            return Err(e);
+        }
+    };
+    if verified {
+        refund(user_id, &reserved).await?;
+        return Ok(Attempt {
+            verified: true,
+            lockout: tracked,
+        });
+    }
+
+    let company_id = company_repo::get_primary_company_for_user(user_id)
+        .await?
+        .map(|company| company.id);
+    if let Some(company_id) = company_id {
+        let user_email = user.email.clone();
+        let reason = reason.to_string();
+        let request_info = req_info.clone();
+        let lockout = reserved.clone();
+        DatabaseTransaction::run(|tx| {
+            Box::pin(async move {
+                log_failure(tx, company_id, &user_email, &lockout, reason, &request_info).await
+            })
+        })
+        .await?;
+    }
+
+    if reserved.is_locked()
+        && let Some(locked_until) = reserved.locked_until
+    {
+        notify_locked(user, locked_until, req_info).await;
+    }
+
+    Ok(Attempt {
+        verified: false,
+        lockout: tracked,
+    })
+}
+
+/// Count an attempt as failed, delaying or locking the account once it has failed too often
+async fn reserve_failure(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<AccountLockout, AppError> {
+    let window_start = Utc::now() - Duration::minutes(ATTEMPT_WINDOW_MINUTES);
+    let mut lockout = account_lockout_repo::record_failure(tx, user_id, window_start).await?;
+
+    if let Some((delay, locked)) = block_after(lockout.failed_attempts) {
+        lockout =
+            account_lockout_repo::block_until(tx, user_id, Utc::now() + delay, locked).await?;
+    }
+
+    Ok(lockout)
+}
+
+/// Take back a failure counted by `reserve_failure`, lifting the block it set, if any
+async fn refund(user_id: Uuid, reserved: &AccountLockout) -> Result<(), AppError> {
+    let locked_until = reserved.locked_until;
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            account_lockout_repo::refund_failure(tx, user_id, locked_until).await?;
+            Ok(())
+        })
+    })
+    .await
+}
+
+/// Record a failed attempt, and the lockout it caused, in the account's primary company
+async fn log_failure(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    user_email: &str,
+    lockout: &AccountLockout,
+    reason: String,
+    request_info: &RequestInfo,
+) -> Result<(), AppError> {
+    let user_id = lockout.user_id;
+    activity_logger::log_auth_activity(
+        tx,
+        company_id,
+        Some(user_id),
+        Action::LOGIN_FAILED,
+        format!("Failed login for {}", user_email),
+        Some(activity_logger::metadata(vec![
+            ("reason", reason),
+            ("failed_attempts", lockout.failed_attempts.to_string()),
+        ])),
+        request_info,
+    )
+    .await?;
+
+    if lockout.is_locked() {
+        activity_logger::log_auth_activity(
+            tx,
+            company_id,
+            Some(user_id),
+            Action::ACCOUNT_LOCKED,
+            format!(
+                "Account {} locked after {} failed login attempts",
+                user_email, lockout.failed_attempts
+            ),
+            None,
+            request_info,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Forget failed attempts after a successful login
+pub async fn reset(user_id: Uuid) -> Result<(), AppError> {
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            account_lockout_repo::clear(tx, user_id).await?;
+            Ok(())
+        })
+    })
+    .await
+}
+
+async fn notify_locked(user: &User, locked_until: DateTime<Utc>, req_info: &RequestInfo) {
+    let body = format!(
+        "Hi {},\n\n\
+         Your ShiftLinkr account was locked after several failed sign-in attempts, \
+         the last one from {} ({}).\n\n\
+         You can try again after {}. If this wasn't you, reset your password and \
+         contact your administrator.",
+        user.name,
+        req_info.ip_address,
+        req_info.user_agent,
+        locked_until.format("%Y-%m-%d %H:%M UTC"),
+    );
+
+    mailer::send(
+        &user.email,
+        "Your ShiftLinkr account has been locked",
+        &body,
+    )
+    .await;
+}
@@ -0,0 +1,384 @@
+use std::{
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Datelike, Months, NaiveDate, Utc};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::database::{
+    models::{ActivityArchive, ActivityCursor, ActivityFilter, CompanyActivity},
+    repositories::activity as activity_repo,
+    transaction::DatabaseTransaction,
+};
+use crate::error::AppError;
+
+const ARCHIVE_BATCH_SIZE: i64 = 1000;
+/// Partitions are kept ready for the current month and this many after it
+const PARTITION_MONTHS_AHEAD: u32 = 2;
+pub const MIN_RETENTION_MONTHS: i32 = 1;
+pub const MAX_RETENTION_MONTHS: i32 = 120;
+
+/// What one archival run did
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveRun {
+    pub partitions_created: usize,
+    pub archives_created: usize,
+    pub entries_archived: i64,
+    pub partitions_dropped: usize,
+}
+
+fn io_error(e: std::io::Error) -> AppError {
+    AppError::internal_server_error_message(format!("Activity archive I/O failed: {}", e))
+}
+
+fn month_start(date: DateTime<Utc>) -> NaiveDate {
+    NaiveDate::from_ymd_opt(date.year(), date.month(), 1).unwrap_or(date.date_naive())
+}
+
+/// Where an archive file lives on disk
+pub fn archive_path(dir: &Path, archive: &ActivityArchive) -> PathBuf {
+    dir.join(&archive.file_name)
+}
+
+/// A new file name, relative to the archive directory, for a company's month
+fn file_name(company_id: Uuid, period_start: DateTime<Utc>) -> String {
+    format!(
+        "{}/activity-{}-{}.ndjson.gz",
+        company_id,
+        period_start.format("%Y-%m"),
+        Uuid::new_v4()
+    )
+}
+
+/// Start writing an archive that ends up at `path`. It is written under a temporary name so
+/// a crash never leaves a partial file that looks complete.
+fn create_file(path: &Path) -> Result<(PathBuf, GzEncoder<fs::File>), AppError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(io_error)?;
+    }
+    let partial_path = path.with_extension("partial");
+    let encoder = GzEncoder::new(
+        fs::File::create(&partial_path).map_err(io_error)?,
+        Compression::default(),
+    );
+    Ok((partial_path, encoder))
+}
+
+/// Flush the archive and move it into place. Returns its compressed contents.
+fn finish_file(
+    encoder: GzEncoder<fs::File>,
+    partial_path: &Path,
+    path: &Path,
+) -> Result<Vec<u8>, AppError> {
+    encoder
+        .finish()
+        .and_then(|file| file.sync_all())
+        .map_err(io_error)?;
+
+    let contents = fs::read(partial_path).map_err(io_error)?;
+    fs::rename(partial_path, path).map_err(io_error)?;
+    Ok(contents)
+}
+
+/// Create upcoming partitions, archive every company's months that are past its retention
+/// period into `dir`, then drop the past partitions that were emptied
+pub async fn run_archival(dir: &Path, as_of: DateTime<Utc>) -> Result<ArchiveRun, AppError> {
+    let mut run = ArchiveRun::default();
+    let this_month = month_start(as_of);
+
+    for ahead in 0..=PARTITION_MONTHS_AHEAD {
+        if activity_repo::create_partition(this_month + Months::new(ahead)).await? {
+            run.partitions_created += 1;
+        }
+    }
+
+    for (company_id, period_start) in activity_repo::get_expired_months(as_of).await? {
+        let archive = archive_month(dir, company_id, period_start).await?;
+        run.archives_created += 1;
+        run.entries_archived += archive.entry_count as i64;
+    }
+
+    for (_, partition_month) in activity_repo::get_partitions().await? {
+        if partition_month < this_month
+            && activity_repo::drop_partition_if_empty(partition_month).await?
+        {
+            run.partitions_dropped += 1;
+        }
+    }
+
+    Ok(run)
+}
+
+/// Write one company's activity for the month starting at `period_start` to a gzipped NDJSON
+/// file, oldest first, then record the archive and delete the entries in one transaction
+async fn archive_month(
+    dir: &Path,
+    company_id: Uuid,
+    period_start: DateTime<Utc>,
+) -> Result<ActivityArchive, AppError> {
+    let period_end = period_start + Months::new(1);
+    let id = Uuid::new_v4();
+    let file_name = file_name(company_id, period_start);
+    let path = dir.join(&file_name);
+    let (partial_path, mut encoder) = create_file(&path)?;
+
+    let mut filter = ActivityFilter {
+        company_id,
+        activity_type: None,
+        entity_type: None,
+        entity_id: None,
+        user_id: None,
+        action: None,
+        start_date: Some(period_start),
+        end_date: Some(period_end),
+        limit: Some(ARCHIVE_BATCH_SIZE),
+        cursor: None,
+        oldest_first: true,
+        changes_only: false,
+    };
+    let mut ids = Vec::new();
+    let mut first_seq: Option<i64> = None;
+    let mut last: Option<(i64, Option<String>)> = None;
+    loop {
+        let batch = activity_repo::get_activities(&filter).await?;
+        for activity in &batch {
+            serde_json::to_writer(&mut encoder, activity)
+                .map_err(|e| AppError::internal_server_error_message(e.to_string()))?;
+            encoder.write_all(b"\n").map_err(io_error)?;
+
+            ids.push(activity.id);
+            if let Some(seq) = activity.chain_seq {
+                first_seq = Some(first_seq.map_or(seq, |first| first.min(seq)));
+                if last.as_ref().is_none_or(|(last_seq, _)| seq > *last_seq) {
+                    last = Some((seq, activity.row_hash.clone()));
+                }
+            }
+        }
+
+        if (batch.len() as i64) < ARCHIVE_BATCH_SIZE {
+            break;
+        }
+        filter.cursor = batch.last().map(|last| ActivityCursor {
+            created_at: last.created_at,
+            id: last.id,
+        });
+    }
+    let contents = finish_file(encoder, &partial_path, &path)?;
+
+    let (last_seq, last_row_hash) = last.map_or((None, None), |(seq, hash)| (Some(seq), hash));
+    let archive = ActivityArchive {
+        id,
+        company_id,
+        period_start,
+        period_end,
+        file_name,
+        entry_count: ids.len() as i32,
+        size_bytes: contents.len() as i64,
+        sha256: format!("{:x}", Sha256::digest(&contents)),
+        first_seq,
+        last_seq,
+        last_row_hash,
+        created_at: Utc::now(),
+    };
+
+    let result = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let archive = activity_repo::create_archive(tx, &archive).await?;
+            activity_repo::delete_activities(tx, company_id, period_start, period_end, &ids)
+                .await?;
+            Ok(archive)
+        })
+    })
+    .await;
+
+    // Without its database record the file is an orphan that would be written again next run
+    if result.is_err() {
+        let _ = fs::remove_file(&path);
+    }
+    result
+}
+
+/// An archive rewritten without a user's personal data, not yet pointed to by its record
+#[derive(Debug, Clone)]
+pub struct ScrubbedArchive {
+    pub archive: ActivityArchive, // With the new file's name, size and hash
+    pub previous_file_name: String, // File it replaces
+    pub redacted: Vec<CompanyActivity>, // Scrubbed entries, in their new state
+}
+
+/// Rewrite every archive holding the user's personal data the way erasure scrubs the activity
+/// table: the network details of their own actions are dropped and their email is replaced
+/// with `anonymized_email`. The rewritten files sit next to the originals until
+/// `finish_scrub` keeps one set or the other.
+pub async fn scrub_archives(
+    dir: &Path,
+    user_id: Uuid,
+    email: &str,
+    anonymized_email: &str,
+) -> Result<Vec<ScrubbedArchive>, AppError> {
+    let mut scrubbed = Vec::new();
+    for archive in activity_repo::get_all_archives().await? {
+        match scrub_archive(dir, archive, user_id, email, anonymized_email) {
+            Ok(Some(archive)) => scrubbed.push(archive),
+            Ok(None) => {}
+            Err(e) => {
+                finish_scrub(dir, &scrubbed, false);
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(scrubbed)
+}
+
+fn scrub_archive(
+    dir: &Path,
+    mut archive: ActivityArchive,
+    user_id: Uuid,
+    email: &str,
+    anonymized_email: &str,
+) -> Result<Option<ScrubbedArchive>, AppError> {
+    // A file that was removed from disk holds nothing to scrub
+    let path = archive_path(dir, &archive);
+    let file = match fs::File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            log::warn!("Activity archive {} is missing", path.display());
+            return Ok(None);
+        }
+        Err(e) => return Err(io_error(e)),
+    };
+    let mut ndjson = String::new();
+    GzDecoder::new(file)
+        .read_to_string(&mut ndjson)
+        .map_err(io_error)?;
+
+    let now = Utc::now();
+    let mut activities = Vec::new();
+    let mut redacted = Vec::new();
+    for line in ndjson.lines().filter(|line| !line.is_empty()) {
+        let mut activity: CompanyActivity = serde_json::from_str(line)
+            .map_err(|e| AppError::internal_server_error_message(e.to_string()))?;
+        if scrub_entry(&mut activity, user_id, email, anonymized_email) {
+            activity.redacted_at = Some(now);
+            redacted.push(activity.clone());
+        }
+        activities.push(activity);
+    }
+    if redacted.is_empty() {
+        return Ok(None);
+    }
+
+    let previous_file_name = archive.file_name.clone();
+    archive.file_name = file_name(archive.company_id, archive.period_start);
+    let path = archive_path(dir, &archive);
+    let (partial_path, mut encoder) = create_file(&path)?;
+    for activity in &activities {
+        serde_json::to_writer(&mut encoder, activity)
+            .map_err(|e| AppError::internal_server_error_message(e.to_string()))?;
+        encoder.write_all(b"\n").map_err(io_error)?;
+    }
+    let contents = finish_file(encoder, &partial_path, &path)?;
+    archive.size_bytes = contents.len() as i64;
+    archive.sha256 = format!("{:x}", Sha256::digest(&contents));
+
+    Ok(Some(ScrubbedArchive {
+        archive,
+        previous_file_name,
+        redacted,
+    }))
+}
+
+/// Scrub one archived entry. Returns whether anything was removed.
+fn scrub_entry(
+    activity: &mut CompanyActivity,
+    user_id: Uuid,
+    email: &str,
+    anonymized_email: &str,
+) -> bool {
+    let mut scrubbed = false;
+    if activity.user_id == Some(user_id) {
+        scrubbed |= activity.ip_address.take().is_some() | activity.user_agent.take().is_some();
+    }
+    if activity.description.contains(email) {
+        activity.description = activity.description.replace(email, anonymized_email);
+        scrubbed = true;
+    }
+    if let Some(metadata) = activity.metadata.as_mut() {
+        scrubbed |= replace_in_json(metadata, email, anonymized_email);
+    }
+    scrubbed
+}
+
+/// Replace `from` in every key and string of a JSON value. Returns whether anything changed.
+fn replace_in_json(value: &mut Value, from: &str, to: &str) -> bool {
+    match value {
+        Value::String(text) if text.contains(from) => {
+            *text = text.replace(from, to);
+            true
+        }
+        Value::Array(items) => items.iter_mut().fold(false, |changed, item| {
+            replace_in_json(item, from, to) | changed
+        }),
+        Value::Object(map) => {
+            let entries = std::mem::take(map);
+            let mut changed = false;
+            for (key, mut item) in entries {
+                changed |= replace_in_json(&mut item, from, to);
+                if key.contains(from) {
+                    changed = true;
+                    map.insert(key.replace(from, to), item);
+                } else {
+                    map.insert(key, item);
+                }
+            }
+            changed
+        }
+        _ => false,
+    }
+}
+
+/// Once the archive records are updated, remove the files they no longer point to. If they
+/// were not, remove the rewritten files instead.
+pub fn finish_scrub(dir: &Path, scrubbed: &[ScrubbedArchive], committed: bool) {
+    for archive in scrubbed {
+        let path = if committed {
+            dir.join(&archive.previous_file_name)
+        } else {
+            archive_path(dir, &archive.archive)
+        };
+        if let Err(e) = fs::remove_file(&path) {
+            log::error!(
+                "Failed to remove activity archive {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Background task that archives expired activity every `interval_hours`
+pub async fn run_archiver(dir: String, interval_hours: u64) {
+    let mut interval =
+        tokio::time::interval(std::time::Duration::from_secs(interval_hours * 60 * 60));
+
+    loop {
+        interval.tick().await;
+        match run_archival(Path::new(&dir), Utc::now()).await {
+            Ok(run) if run.archives_created == 0 => {}
+            Ok(run) => log::info!(
+                "Archived {} activity log entries into {} files",
+                run.entries_archived,
+                run.archives_created
+            ),
+            Err(e) => log::error!("Failed to archive the activity log: {}", e),
+        }
+    }
+}
@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::database::{
+    models::{
+        Action, ActivityChainHead, ActivityCheckpoint, ActivityType, ChainBreak, ChainVerification,
+        CompanyActivity, CreateActivityInput, EntityType, GENESIS_HASH,
+    },
+    repositories::activity as activity_repo,
+    transaction::DatabaseTransaction,
+};
+use crate::error::AppError;
+use crate::services::jwt_keys::key_ring;
+
+const VERIFY_BATCH_SIZE: i64 = 1000;
+const CHECKPOINT_TOKEN_TYPE: &str = "activity_checkpoint";
+const REDACTION_RECORD_SIZE: usize = 500;
+
+/// Claims of a checkpoint signature
+#[derive(Debug, Serialize, Deserialize)]
+struct CheckpointClaims {
+    sub: String, // Company id
+    seq: i64,
+    hash: String,
+    iat: i64,
+    typ: String,
+}
+
+/// A redacted entry as listed by the redaction record that covers it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RedactedEntry {
+    id: Uuid,
+    content_hash: String,  // Hash the entry was logged with
+    redacted_hash: String, // Hash of its content after scrubbing
+}
+
+/// The redaction record covering an entry and the chain position it was logged at
+struct Redaction {
+    record_seq: i64,
+    entry: RedactedEntry,
+}
+
+/// Record scrubbed entries in their companies' chains. Each record lists the entries with the
+/// content hash they were logged with, so verification can tell a redaction from an edit.
+/// Records are part of the chain's integrity and are written even when activity logging is
+/// turned off.
+pub async fn record_redactions(
+    tx: &mut Transaction<'_, Postgres>,
+    entity_id: Uuid,
+    redacted: &[CompanyActivity],
+) -> Result<(), sqlx::Error> {
+    let mut by_company: HashMap<Uuid, Vec<RedactedEntry>> = HashMap::new();
+    for activity in redacted {
+        let Some(content_hash) = activity.content_hash.clone() else {
+            continue;
+        };
+        by_company
+            .entry(activity.company_id)
+            .or_default()
+            .push(RedactedEntry {
+                id: activity.id,
+                content_hash,
+                redacted_hash: activity.compute_content_hash(),
+            });
+    }
+
+    for (company_id, entries) in by_company {
+        for chunk in entries.chunks(REDACTION_RECORD_SIZE) {
+            let input = CreateActivityInput {
+                company_id,
+                user_id: None,
+                activity_type: ActivityType::SYSTEM.to_string(),
+                entity_type: EntityType::USER.to_string(),
+                entity_id,
+                action: Action::ACTIVITY_REDACTED.to_string(),
+                description: format!("Redacted personal data from {} entries", chunk.len()),
+                metadata: Some(HashMap::from([(
+                    "redacted".to_string(),
+                    serde_json::to_value(chunk).unwrap_or_default(),
+                )])),
+                // Erasure is the only caller; the request's network details are personal data
+                ip_address: String::new(),
+                user_agent: String::new(),
+                api_key_id: None,
+                impersonated_user_id: None,
+            };
+            activity_repo::log_activity(tx, input).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Redactions recorded in a company's chain by entry id. A later record for the same entry
+/// replaces an earlier one.
+async fn get_redactions(company_id: Uuid) -> Result<HashMap<Uuid, Redaction>, AppError> {
+    let mut redactions = HashMap::new();
+    for record in activity_repo::get_redaction_records(company_id).await? {
+        let entries = record
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.get("redacted").cloned())
+            .and_then(|entries| serde_json::from_value::<Vec<RedactedEntry>>(entries).ok())
+            .unwrap_or_default();
+        for entry in entries {
+            redactions.insert(
+                entry.id,
+                Redaction {
+                    record_seq: record.chain_seq.unwrap_or_default(),
+                    entry,
+                },
+            );
+        }
+    }
+
+    Ok(redactions)
+}
+
+/// Walk a company's chain from its first remaining entry to the head and report the first
+/// entry that was modified, removed or inserted, or a checkpoint that no longer matches
+pub async fn verify(company_id: Uuid) -> Result<ChainVerification, AppError> {
+    let head = activity_repo::get_chain_head(company_id).await?;
+    let (head_seq, head_hash) = head
+        .map(|head| (head.chain_seq, head.row_hash))
+        .unwrap_or((0, GENESIS_HASH.to_string()));
+
+    let checkpoints = activity_repo::get_checkpoints(company_id).await?;
+    let redactions = get_redactions(company_id).await?;
+    let mut result = ChainVerification {
+        company_id,
+        verified: false,
+        first_seq: None,
+        archived_through: None,
+        head_seq,
+        head_hash: head_hash.clone(),
+        checked: 0,
+        redacted: 0,
+        checkpoints_checked: 0,
+        first_break: None,
+    };
+
+    let mut pinned: HashMap<i64, String> = HashMap::new();
+    for checkpoint in &checkpoints {
+        if let Err(reason) = check_signature(checkpoint) {
+            result.first_break = Some(ChainBreak {
+                chain_seq: checkpoint.chain_seq,
+                activity_id: None,
+                reason,
+            });
+            return Ok(result);
+        }
+        if checkpoint.chain_seq > head_seq {
+            result.first_break = Some(ChainBreak {
+                chain_seq: checkpoint.chain_seq,
+                activity_id: None,
+                reason: format!(
+                    "Checkpoint is ahead of the chain head at {}; entries were removed",
+                    head_seq
+                ),
+            });
+            return Ok(result);
+        }
+        pinned.insert(checkpoint.chain_seq, checkpoint.row_hash.clone());
+    }
+
+    // Archived entries are gone from the table; the chain picks up after the newest of them
+    let mut last: Option<(i64, String)> = activity_repo::get_latest_chained_archive(company_id)
+        .await?
+        .and_then(|archive| Some((archive.last_seq?, archive.last_row_hash?)));
+    if let Some((seq, hash)) = &last {
+        result.archived_through = Some(*seq);
+        if let Some(pinned_hash) = pinned.remove(seq)
+            && &pinned_hash != hash
+        {
+            result.first_break = Some(ChainBreak {
+                chain_seq: *seq,
+                activity_id: None,
+                reason: "Archived entry does not match its signed checkpoint".to_string(),
+            });
+            return Ok(result);
+        }
+    }
+
+    loop {
+        let after_seq = last.as_ref().map_or(0, |(seq, _)| *seq);
+        let batch =
+            activity_repo::get_chained_activities(company_id, after_seq, VERIFY_BATCH_SIZE).await?;
+
+        for activity in &batch {
+            let seq = activity.chain_seq.unwrap_or_default();
+            let redaction = redactions.get(&activity.id);
+            if let Err(reason) = check_link(activity, last.as_ref(), redaction) {
+                result.first_break = Some(ChainBreak {
+                    chain_seq: seq,
+                    activity_id: Some(activity.id),
+                    reason,
+                });
+                return Ok(result);
+            }
+            if let Some(pinned_hash) = pinned.remove(&seq)
+                && Some(&pinned_hash) != activity.row_hash.as_ref()
+            {
+                result.first_break = Some(ChainBreak {
+                    chain_seq: seq,
+                    activity_id: Some(activity.id),
+                    reason: "Entry does not match its signed checkpoint".to_string(),
+                });
+                return Ok(result);
+            }
+
+            result.first_seq.get_or_insert(seq);
+            result.checked += 1;
+            if activity.redacted_at.is_some() {
+                result.redacted += 1;
+            }
+            last = Some((seq, activity.row_hash.clone().unwrap_or_default()));
+        }
+
+        if (batch.len() as i64) < VERIFY_BATCH_SIZE {
+            break;
+        }
+    }
+
+    // Checkpoints for archived entries other than the newest cannot be compared
+    result.checkpoints_checked = checkpoints.len() as i64 - pinned.len() as i64;
+
+    let (last_seq, last_hash) = last.unwrap_or((0, GENESIS_HASH.to_string()));
+    if last_seq != head_seq || last_hash != head_hash {
+        result.first_break = Some(ChainBreak {
+            chain_seq: last_seq + 1,
+            activity_id: None,
+            reason: format!(
+                "The chain ends at {} but its head is at {}; entries were removed",
+                last_seq, head_seq
+            ),
+        });
+        return Ok(result);
+    }
+
+    result.verified = true;
+    Ok(result)
+}
+
+/// Check that an entry follows the previous one (or the last archived entry) and still hashes
+/// to its recorded value. Without either, the entry has to start the chain. A redacted entry
+/// must be covered by a later redaction record.
+fn check_link(
+    activity: &CompanyActivity,
+    previous: Option<&(i64, String)>,
+    redaction: Option<&Redaction>,
+) -> Result<(), String> {
+    let seq = activity.chain_seq.unwrap_or_default();
+    match previous {
+        Some((previous_seq, previous_hash)) => {
+            if seq != previous_seq + 1 {
+                return Err(format!(
+                    "Entries {} to {} are missing",
+                    previous_seq + 1,
+                    seq - 1
+                ));
+            }
+            if activity.prev_hash.as_ref() != Some(previous_hash) {
+                return Err("Entry does not link to the previous entry".to_string());
+            }
+        }
+        None if seq != 1 => {
+            return Err(format!(
+                "Entries 1 to {} are missing and were not archived",
+                seq - 1
+            ));
+        }
+        None if activity.prev_hash.as_deref() != Some(GENESIS_HASH) => {
+            return Err("First entry does not start the chain".to_string());
+        }
+        None => {}
+    }
+
+    // Redacted entries had personal data scrubbed. The redaction record vouches for both the
+    // hash they were logged with and the content left after scrubbing.
+    let content_hash = activity.compute_content_hash();
+    if activity.redacted_at.is_some() {
+        let Some(redaction) = redaction.filter(|redaction| redaction.record_seq > seq) else {
+            return Err("Entry was redacted without a redaction record".to_string());
+        };
+        if activity.content_hash.as_ref() != Some(&redaction.entry.content_hash)
+            || content_hash != redaction.entry.redacted_hash
+        {
+            return Err("Entry content was modified".to_string());
+        }
+    } else if activity.content_hash.as_deref() != Some(content_hash.as_str()) {
+        return Err("Entry content was modified".to_string());
+    }
+    if activity.row_hash.as_deref() != Some(activity.compute_row_hash().as_str()) {
+        return Err("Entry was modified".to_string());
+    }
+
+    Ok(())
+}
+
+fn check_signature(checkpoint: &ActivityCheckpoint) -> Result<(), String> {
+    let claims = key_ring()
+        .and_then(|key_ring| {
+            key_ring.decode_without_expiry::<CheckpointClaims>(&checkpoint.signature)
+        })
+        .map_err(|e| format!("Checkpoint signature is invalid: {}", e))?;
+
+    if claims.typ != CHECKPOINT_TOKEN_TYPE
+        || claims.sub != checkpoint.company_id.to_string()
+        || claims.seq != checkpoint.chain_seq
+        || claims.hash != checkpoint.row_hash
+    {
+        return Err("Checkpoint does not match its signature".to_string());
+    }
+
+    Ok(())
+}
+
+/// Sign the company's current chain head. Returns None when nothing was logged since the
+/// last checkpoint.
+pub async fn create_checkpoint(company_id: Uuid) -> Result<Option<ActivityCheckpoint>, AppError> {
+    let Some(head) = activity_repo::get_chain_head(company_id).await? else {
+        return Ok(None);
+    };
+    let latest = activity_repo::get_checkpoints(company_id)
+        .await?
+        .into_iter()
+        .map(|checkpoint| checkpoint.chain_seq)
+        .max()
+        .unwrap_or(0);
+    if head.chain_seq <= latest {
+        return Ok(None);
+    }
+
+    sign(head).await.map(Some)
+}
+
+async fn sign(head: ActivityChainHead) -> Result<ActivityCheckpoint, AppError> {
+    let claims = CheckpointClaims {
+        sub: head.company_id.to_string(),
+        seq: head.chain_seq,
+        hash: head.row_hash.clone(),
+        iat: Utc::now().timestamp(),
+        typ: CHECKPOINT_TOKEN_TYPE.to_string(),
+    };
+    let signature = key_ring()
+        .and_then(|key_ring| key_ring.encode(&claims))
+        .map_err(|e| AppError::internal_server_error_message(e.to_string()))?;
+
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let checkpoint = activity_repo::create_checkpoint(
+                tx,
+                head.company_id,
+                head.chain_seq,
+                &head.row_hash,
+                &signature,
+            )
+            .await?;
+            Ok(checkpoint)
+        })
+    })
+    .await
+}
+
+/// Sign the chain head of every company that logged activity since its last checkpoint
+pub async fn create_due_checkpoints() -> Result<usize, AppError> {
+    let heads = activity_repo::get_unsigned_chain_heads().await?;
+    let count = heads.len();
+    for head in heads {
+        sign(head).await?;
+    }
+
+    Ok(count)
+}
+
+/// Background task that signs checkpoints every `interval_minutes`
+pub async fn run_checkpoints(interval_minutes: u64) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_minutes * 60));
+
+    loop {
+        interval.tick().await;
+        match create_due_checkpoints().await {
+            Ok(0) => {}
+            Ok(count) => log::info!("Signed {} activity log checkpoints", count),
+            Err(e) => log::error!("Failed to sign activity log checkpoints: {}", e),
+        }
+    }
+}
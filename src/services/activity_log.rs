@@ -0,0 +1,334 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::database::{
+    models::{
+        Action, ActivityCursor, ActivityFilter, ActivityPage, ActivityQuery, ActivityType,
+        CompanyActivity, EntityChange, EntityChangePage, EntityType,
+    },
+    repositories::activity as activity_repo,
+    transaction::DatabaseTransaction,
+};
+use crate::error::AppError;
+use crate::middleware::request_info::RequestInfo;
+use crate::services::activity_logger;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+const EXPORT_BATCH_SIZE: i64 = 1000;
+/// Exports stop here; narrow the date range to get the rest
+pub const MAX_EXPORT_ROWS: usize = 100_000;
+
+const CSV_COLUMNS: [&str; 16] = [
+    "id",
+    "created_at",
+    "company_id",
+    "user_id",
+    "impersonated_user_id",
+    "api_key_id",
+    "activity_type",
+    "entity_type",
+    "entity_id",
+    "action",
+    "description",
+    "metadata",
+    "ip_address",
+    "user_agent",
+    "chain_seq",
+    "row_hash",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub fn parse(format: Option<&str>) -> Result<Self, AppError> {
+        match format.unwrap_or("csv").to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "ndjson" | "jsonl" => Ok(ExportFormat::Ndjson),
+            other => Err(AppError::BadRequest(format!(
+                "Unknown export format '{}'; use csv or ndjson",
+                other
+            ))),
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+}
+
+/// Cursors are opaque to clients: the created time and id of the last entry on a page
+pub fn encode_cursor(activity: &CompanyActivity) -> String {
+    URL_SAFE_NO_PAD.encode(format!(
+        "{}|{}",
+        activity.created_at.to_rfc3339(),
+        activity.id
+    ))
+}
+
+pub fn decode_cursor(cursor: &str) -> Result<ActivityCursor, AppError> {
+    let invalid = || AppError::BadRequest("Invalid cursor".to_string());
+    let decoded = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+    let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+    let (created_at, id) = decoded.split_once('|').ok_or_else(invalid)?;
+
+    Ok(ActivityCursor {
+        created_at: DateTime::parse_from_rfc3339(created_at)
+            .map_err(|_| invalid())?
+            .with_timezone(&Utc),
+        id: Uuid::parse_str(id).map_err(|_| invalid())?,
+    })
+}
+
+fn build_filter(company_id: Uuid, query: &ActivityQuery) -> Result<ActivityFilter, AppError> {
+    if let (Some(start), Some(end)) = (query.start_date, query.end_date)
+        && start >= end
+    {
+        return Err(AppError::BadRequest(
+            "startDate must be before endDate".to_string(),
+        ));
+    }
+
+    Ok(ActivityFilter {
+        company_id,
+        activity_type: query.activity_type.clone(),
+        entity_type: query.entity_type.clone(),
+        entity_id: query.entity_id,
+        user_id: query.user_id,
+        action: query.action.clone(),
+        start_date: query.start_date,
+        end_date: query.end_date,
+        limit: Some(
+            query
+                .limit
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .clamp(1, MAX_PAGE_SIZE),
+        ),
+        cursor: query.cursor.as_deref().map(decode_cursor).transpose()?,
+        oldest_first: false,
+        changes_only: false,
+    })
+}
+
+/// Fetch one page of the filter, asking for one extra row to know whether another page follows
+async fn fetch_page(mut filter: ActivityFilter) -> Result<ActivityPage, AppError> {
+    let limit = filter.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    filter.limit = Some(limit + 1);
+
+    let mut items = activity_repo::get_activities(&filter).await?;
+    let next_cursor = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        items.last().map(encode_cursor)
+    } else {
+        None
+    };
+
+    Ok(ActivityPage { items, next_cursor })
+}
+
+/// Company activity matching the query, newest first
+pub async fn page(company_id: Uuid, query: &ActivityQuery) -> Result<ActivityPage, AppError> {
+    fetch_page(build_filter(company_id, query)?).await
+}
+
+/// Everything that happened to one entity, oldest first
+pub async fn timeline(
+    company_id: Uuid,
+    entity_type: String,
+    entity_id: Uuid,
+    query: &ActivityQuery,
+) -> Result<ActivityPage, AppError> {
+    let mut filter = build_filter(company_id, query)?;
+    filter.entity_type = Some(entity_type);
+    filter.entity_id = Some(entity_id);
+    filter.oldest_first = true;
+
+    fetch_page(filter).await
+}
+
+/// The field-level changes recorded for one entity, oldest first
+pub async fn change_history(
+    company_id: Uuid,
+    entity_type: String,
+    entity_id: Uuid,
+    query: &ActivityQuery,
+) -> Result<EntityChangePage, AppError> {
+    let mut filter = build_filter(company_id, query)?;
+    filter.entity_type = Some(entity_type);
+    filter.entity_id = Some(entity_id);
+    filter.oldest_first = true;
+    filter.changes_only = true;
+
+    let page = fetch_page(filter).await?;
+    let items = page
+        .items
+        .into_iter()
+        .map(|activity| EntityChange {
+            activity_id: activity.id,
+            user_id: activity.user_id,
+            impersonated_user_id: activity.impersonated_user_id,
+            api_key_id: activity.api_key_id,
+            action: activity.action,
+            description: activity.description,
+            changes: activity
+                .metadata
+                .and_then(|metadata| metadata.get(activity_logger::CHANGES_KEY).cloned())
+                .unwrap_or_default(),
+            created_at: activity.created_at,
+        })
+        .collect();
+
+    Ok(EntityChangePage {
+        items,
+        next_cursor: page.next_cursor,
+    })
+}
+
+/// Render every entry matching the query, newest first, and record that the log was exported.
+/// Returns the file contents and the number of entries written.
+pub async fn export(
+    company_id: Uuid,
+    user_id: Uuid,
+    query: &ActivityQuery,
+    format: ExportFormat,
+    req_info: &RequestInfo,
+) -> Result<(String, usize), AppError> {
+    let mut filter = build_filter(company_id, query)?;
+    filter.limit = Some(EXPORT_BATCH_SIZE);
+
+    let mut output = String::new();
+    if format == ExportFormat::Csv {
+        output.push_str(&CSV_COLUMNS.join(","));
+        output.push('\n');
+    }
+
+    let mut count = 0;
+    loop {
+        let batch = activity_repo::get_activities(&filter).await?;
+        for activity in &batch {
+            if count == MAX_EXPORT_ROWS {
+                break;
+            }
+            match format {
+                ExportFormat::Csv => output.push_str(&csv_row(activity)),
+                ExportFormat::Ndjson => output.push_str(
+                    &serde_json::to_string(activity)
+                        .map_err(|e| AppError::internal_server_error_message(e.to_string()))?,
+                ),
+            }
+            output.push('\n');
+            count += 1;
+        }
+
+        if count == MAX_EXPORT_ROWS || (batch.len() as i64) < EXPORT_BATCH_SIZE {
+            break;
+        }
+        filter.cursor = batch.last().map(|last| ActivityCursor {
+            created_at: last.created_at,
+            id: last.id,
+        });
+    }
+
+    let meta = activity_logger::metadata(vec![
+        ("format", format.extension().to_string()),
+        ("entries", count.to_string()),
+        (
+            "filter",
+            serde_json::json!({
+                "activityType": query.activity_type,
+                "entityType": query.entity_type,
+                "entityId": query.entity_id,
+                "userId": query.user_id,
+                "action": query.action,
+                "startDate": query.start_date,
+                "endDate": query.end_date,
+            })
+            .to_string(),
+        ),
+    ]);
+    let req_info = req_info.clone();
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                ActivityType::SYSTEM.to_string(),
+                EntityType::COMPANY.to_string(),
+                company_id,
+                Action::EXPORTED.to_string(),
+                format!("Exported {} activity log entries", count),
+                Some(meta),
+                &req_info,
+            )
+            .await?;
+            Ok(())
+        })
+    })
+    .await?;
+
+    Ok((output, count))
+}
+
+fn csv_row(activity: &CompanyActivity) -> String {
+    let optional = |id: Option<Uuid>| id.map(|id| id.to_string()).unwrap_or_default();
+    [
+        activity.id.to_string(),
+        activity.created_at.to_rfc3339(),
+        activity.company_id.to_string(),
+        optional(activity.user_id),
+        optional(activity.impersonated_user_id),
+        optional(activity.api_key_id),
+        activity.activity_type.clone(),
+        activity.entity_type.clone(),
+        activity.entity_id.to_string(),
+        activity.action.clone(),
+        activity.description.clone(),
+        activity
+            .metadata
+            .as_ref()
+            .map(|metadata| metadata.to_string())
+            .unwrap_or_default(),
+        activity.ip_address.clone().unwrap_or_default(),
+        activity.user_agent.clone().unwrap_or_default(),
+        activity
+            .chain_seq
+            .map(|seq| seq.to_string())
+            .unwrap_or_default(),
+        activity.row_hash.clone().unwrap_or_default(),
+    ]
+    .iter()
+    .map(|field| csv_field(field))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// Quote a CSV field, and defuse values a spreadsheet would otherwise run as a formula
+pub fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
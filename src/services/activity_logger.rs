@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+use serde::Serialize;
+use serde_json::{Map, Value, json};
 use sqlx::{Postgres, Transaction};
 use uuid::Uuid;
 
@@ -9,6 +11,16 @@ use crate::database::{
 };
 use crate::middleware::request_info::RequestInfo;
 
+/// Actions taken with an API key are attributed to the key, not the user who created it.
+/// Actions taken while impersonating are attributed to the admin behind the token.
+fn actor(user_id: Option<Uuid>, req: &RequestInfo) -> Option<Uuid> {
+    if req.api_key_id.is_some() {
+        None
+    } else {
+        req.impersonator_id.or(user_id)
+    }
+}
+
 fn logging_disabled() -> bool {
     match std::env::var("SKIP_ACTIVITY_LOG") {
         Ok(v) => v == "1" || v.eq_ignore_ascii_case("true"),
@@ -34,7 +46,7 @@ pub async fn log_activity(
     }
     let request = CreateActivityInput {
         company_id,
-        user_id,
+        user_id: actor(user_id, req),
         activity_type,
         entity_type,
         entity_id,
@@ -43,6 +55,8 @@ pub async fn log_activity(
         metadata,
         ip_address: req.ip_address.clone(),
         user_agent: req.user_agent.clone(),
+        api_key_id: req.api_key_id,
+        impersonated_user_id: req.impersonated_user_id,
     };
 
     activity_repo::log_activity(tx, request).await?;
@@ -65,7 +79,7 @@ pub async fn log_user_activity(
     }
     let request = CreateActivityInput {
         company_id,
-        user_id,
+        user_id: actor(user_id, req),
         activity_type: ActivityType::USER_MANAGEMENT.to_string(),
         entity_type: EntityType::USER.to_string(),
         entity_id: target_user_id,
@@ -74,6 +88,8 @@ pub async fn log_user_activity(
         metadata,
         ip_address: req.ip_address.clone(),
         user_agent: req.user_agent.clone(),
+        api_key_id: req.api_key_id,
+        impersonated_user_id: req.impersonated_user_id,
     };
 
     activity_repo::log_activity(tx, request).await?;
@@ -95,7 +111,7 @@ pub async fn log_auth_activity(
     }
     let request = CreateActivityInput {
         company_id,
-        user_id,
+        user_id: actor(user_id, req),
         activity_type: ActivityType::AUTHENTICATION.to_string(),
         entity_type: EntityType::USER.to_string(),
         entity_id: user_id.unwrap_or(Uuid::nil()), // Use nil UUID for failed logins where user_id is unknown
@@ -104,6 +120,8 @@ pub async fn log_auth_activity(
         metadata,
         ip_address: req.ip_address.clone(),
         user_agent: req.user_agent.clone(),
+        api_key_id: req.api_key_id,
+        impersonated_user_id: req.impersonated_user_id,
     };
 
     activity_repo::log_activity(tx, request).await?;
@@ -126,7 +144,7 @@ pub async fn log_location_activity(
     }
     let request = CreateActivityInput {
         company_id,
-        user_id,
+        user_id: actor(user_id, req),
         activity_type: ActivityType::LOCATION_MANAGEMENT.to_string(),
         entity_type: EntityType::LOCATION.to_string(),
         entity_id: location_id,
@@ -135,6 +153,8 @@ pub async fn log_location_activity(
         metadata,
         ip_address: req.ip_address.clone(),
         user_agent: req.user_agent.clone(),
+        api_key_id: req.api_key_id,
+        impersonated_user_id: req.impersonated_user_id,
     };
 
     activity_repo::log_activity(tx, request).await?;
@@ -157,7 +177,7 @@ pub async fn log_team_activity(
     }
     let request = CreateActivityInput {
         company_id,
-        user_id,
+        user_id: actor(user_id, req),
         activity_type: ActivityType::TEAM_MANAGEMENT.to_string(),
         entity_type: EntityType::TEAM.to_string(),
         entity_id: team_id,
@@ -166,6 +186,8 @@ pub async fn log_team_activity(
         metadata,
         ip_address: req.ip_address.clone(),
         user_agent: req.user_agent.clone(),
+        api_key_id: req.api_key_id,
+        impersonated_user_id: req.impersonated_user_id,
     };
 
     activity_repo::log_activity(tx, request).await?;
@@ -188,7 +210,7 @@ pub async fn log_shift_activity(
     }
     let request = CreateActivityInput {
         company_id,
-        user_id,
+        user_id: actor(user_id, req),
         activity_type: ActivityType::SHIFT_MANAGEMENT.to_string(),
         entity_type: EntityType::SHIFT.to_string(),
         entity_id: shift_id,
@@ -197,6 +219,8 @@ pub async fn log_shift_activity(
         metadata,
         ip_address: req.ip_address.clone(),
         user_agent: req.user_agent.clone(),
+        api_key_id: req.api_key_id,
+        impersonated_user_id: req.impersonated_user_id,
     };
 
     activity_repo::log_activity(tx, request).await?;
@@ -219,7 +243,7 @@ pub async fn log_time_off_activity(
     }
     let request = CreateActivityInput {
         company_id,
-        user_id,
+        user_id: actor(user_id, req),
         activity_type: ActivityType::TIME_OFF_MANAGEMENT.to_string(),
         entity_type: EntityType::TIME_OFF.to_string(),
         entity_id: time_off_id,
@@ -228,6 +252,8 @@ pub async fn log_time_off_activity(
         metadata,
         ip_address: req.ip_address.clone(),
         user_agent: req.user_agent.clone(),
+        api_key_id: req.api_key_id,
+        impersonated_user_id: req.impersonated_user_id,
     };
 
     activity_repo::log_activity(tx, request).await?;
@@ -250,7 +276,7 @@ pub async fn log_shift_swap_activity(
     }
     let request = CreateActivityInput {
         company_id,
-        user_id,
+        user_id: actor(user_id, req),
         activity_type: ActivityType::SHIFT_MANAGEMENT.to_string(),
         entity_type: EntityType::SHIFT_SWAP.to_string(),
         entity_id: swap_id,
@@ -259,6 +285,8 @@ pub async fn log_shift_swap_activity(
         metadata,
         ip_address: req.ip_address.clone(),
         user_agent: req.user_agent.clone(),
+        api_key_id: req.api_key_id,
+        impersonated_user_id: req.impersonated_user_id,
     };
 
     activity_repo::log_activity(tx, request).await?;
@@ -277,7 +305,7 @@ pub async fn log_skill_activity(
 ) -> Result<(), sqlx::Error> {
     let request = CreateActivityInput {
         company_id,
-        user_id,
+        user_id: actor(user_id, req),
         activity_type: ActivityType::SKILL_MANAGEMENT.to_string(),
         entity_type: EntityType::SKILL.to_string(),
         entity_id: skill_id,
@@ -286,6 +314,8 @@ pub async fn log_skill_activity(
         metadata,
         ip_address: req.ip_address.clone(),
         user_agent: req.user_agent.clone(),
+        api_key_id: req.api_key_id,
+        impersonated_user_id: req.impersonated_user_id,
     };
 
     activity_repo::log_activity(tx, request).await?;
@@ -298,3 +328,70 @@ pub fn metadata(pairs: Vec<(&str, String)>) -> HashMap<String, serde_json::Value
         .map(|(k, v)| (k.to_string(), serde_json::Value::String(v)))
         .collect()
 }
+
+/// Metadata key holding the field-level diff of an update
+pub const CHANGES_KEY: &str = "changes";
+
+const REDACTED: &str = "[redacted]";
+
+/// Fields that are bookkeeping rather than part of the change
+const IGNORED_FIELDS: [&str; 2] = ["createdAt", "updatedAt"];
+
+/// Values of fields whose name contains one of these are never written to the log
+const SENSITIVE_FIELDS: [&str; 4] = ["password", "secret", "token", "keyhash"];
+
+fn is_sensitive(field: &str) -> bool {
+    let field = field.to_lowercase();
+    SENSITIVE_FIELDS
+        .iter()
+        .any(|sensitive| field.contains(sensitive))
+}
+
+/// Field-level diff of two serialized versions of a record, as
+/// `{ "field": { "old": .., "new": .. } }` for every field that differs. Sensitive values are
+/// replaced with a placeholder, so the log shows that they changed but not what to.
+pub fn changes<T: Serialize>(before: &T, after: &T) -> Map<String, Value> {
+    let as_object = |value: &T| match serde_json::to_value(value) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    };
+    let before = as_object(before);
+    let mut after = as_object(after);
+
+    let mut fields: Vec<String> = before.keys().chain(after.keys()).cloned().collect();
+    fields.sort();
+    fields.dedup();
+
+    let mut changes = Map::new();
+    for field in fields {
+        if IGNORED_FIELDS.contains(&field.as_str()) {
+            continue;
+        }
+        let old = before.get(&field).cloned().unwrap_or(Value::Null);
+        let new = after.remove(&field).unwrap_or(Value::Null);
+        if old == new {
+            continue;
+        }
+        let change = if is_sensitive(&field) {
+            json!({ "old": REDACTED, "new": REDACTED })
+        } else {
+            json!({ "old": old, "new": new })
+        };
+        changes.insert(field, change);
+    }
+
+    changes
+}
+
+/// Add the diff between two versions of a record to activity metadata
+pub fn with_changes<T: Serialize>(
+    mut metadata: HashMap<String, Value>,
+    before: &T,
+    after: &T,
+) -> HashMap<String, Value> {
+    metadata.insert(
+        CHANGES_KEY.to_string(),
+        Value::Object(changes(before, after)),
+    );
+    metadata
+}
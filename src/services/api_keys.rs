@@ -0,0 +1,174 @@
+use actix_web::http::Method;
+use chrono::{Duration, Utc};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::database::{
+    models::{ApiKey, ApiKeyScope, CreateApiKeyInput, CreatedApiKeyResponse},
+    repositories::api_key as api_key_repo,
+    transaction::DatabaseTransaction,
+};
+use crate::error::AppError;
+
+/// Every key starts with this so it can be told apart from a JWT
+pub const API_KEY_PREFIX: &str = "slk_";
+const PREFIX_RANDOM_LEN: usize = 8;
+const SECRET_LEN: usize = 40;
+const MAX_RATE_LIMIT_PER_MINUTE: i32 = 10_000;
+/// `last_used_at` is written at most this often per key
+const USAGE_WRITE_INTERVAL_SECONDS: i64 = 60;
+
+pub fn is_api_key(token: &str) -> bool {
+    token.starts_with(API_KEY_PREFIX)
+}
+
+/// Generate a new key, returning the full key and its public prefix
+pub fn generate_key() -> (String, String) {
+    let prefix = format!(
+        "{}{}",
+        API_KEY_PREFIX,
+        random_alphanumeric(PREFIX_RANDOM_LEN)
+    );
+    let key = format!("{}_{}", prefix, random_alphanumeric(SECRET_LEN));
+    (key, prefix)
+}
+
+/// The public prefix of a key, e.g. `slk_ab12cd34` for `slk_ab12cd34_<secret>`
+pub fn key_prefix(key: &str) -> Option<&str> {
+    let prefix_len = API_KEY_PREFIX.len() + PREFIX_RANDOM_LEN;
+    if !is_api_key(key) || key.len() <= prefix_len + 1 || key.as_bytes()[prefix_len] != b'_' {
+        return None;
+    }
+    Some(&key[..prefix_len])
+}
+
+/// Keys are only ever stored as SHA-256 digests
+pub fn hash_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+/// The scope an API key needs for a request, or `None` if keys may not call the endpoint.
+/// Safe methods need `area:read`; anything else needs `area:write`.
+pub fn required_scope(method: &Method, path: &str) -> Option<String> {
+    let path = path.strip_prefix("/api/v1/")?;
+    let mut segments = path.split('/');
+    let area = match (segments.next()?, segments.next()) {
+        ("shifts", _) => "shifts",
+        ("time-off", _) => "timeoff",
+        ("swaps", _) => "swaps",
+        ("schedules" | "assignments", _) => "schedules",
+        ("skills" | "user-skills" | "shift-skills", _) => "skills",
+        ("pto-balance", _) => "pto",
+        ("stats", _) => "stats",
+        ("companies", Some("employees")) | ("admin", Some("users")) => "employees",
+        ("admin", Some("locations")) => "locations",
+        ("admin", Some("teams")) => "teams",
+        _ => return None,
+    };
+
+    let access = if matches!(*method, Method::GET | Method::HEAD) {
+        "read"
+    } else {
+        "write"
+    };
+
+    Some(format!("{}:{}", area, access))
+}
+
+/// Look up an active key from its full value and note that it was used
+pub async fn authenticate(key: &str, ip_address: &str) -> Result<Option<ApiKey>, AppError> {
+    let Some(prefix) = key_prefix(key) else {
+        return Ok(None);
+    };
+
+    let api_key = match api_key_repo::find_by_prefix(prefix).await? {
+        Some(api_key) if api_key.key_hash == hash_key(key) && api_key.is_active() => api_key,
+        _ => return Ok(None),
+    };
+
+    let stale = api_key.last_used_at.is_none_or(|last_used_at| {
+        Utc::now() - last_used_at > Duration::seconds(USAGE_WRITE_INTERVAL_SECONDS)
+    });
+    if stale {
+        let api_key_id = api_key.id;
+        let ip_address = ip_address.to_string();
+        DatabaseTransaction::run(|tx| {
+            Box::pin(async move {
+                api_key_repo::record_usage(tx, api_key_id, &ip_address).await?;
+                Ok(())
+            })
+        })
+        .await?;
+    }
+
+    Ok(Some(api_key))
+}
+
+/// Normalise and check a new key's settings
+pub fn validate_create_input(input: &mut CreateApiKeyInput) -> Result<(), AppError> {
+    input.name = input.name.trim().to_string();
+    if input.name.is_empty() {
+        return Err(AppError::BadRequest("API key name is required".to_string()));
+    }
+
+    input.scopes.sort();
+    input.scopes.dedup();
+    if input.scopes.is_empty() {
+        return Err(AppError::BadRequest(
+            "At least one scope is required".to_string(),
+        ));
+    }
+    if let Some(scope) = input
+        .scopes
+        .iter()
+        .find(|scope| !ApiKeyScope::ALL.contains(&scope.as_str()))
+    {
+        return Err(AppError::BadRequest(format!("Unknown scope: {}", scope)));
+    }
+
+    if input
+        .rate_limit_per_minute
+        .is_some_and(|limit| !(1..=MAX_RATE_LIMIT_PER_MINUTE).contains(&limit))
+    {
+        return Err(AppError::BadRequest(format!(
+            "Rate limit must be between 1 and {} requests per minute",
+            MAX_RATE_LIMIT_PER_MINUTE
+        )));
+    }
+
+    if input
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Utc::now())
+    {
+        return Err(AppError::BadRequest(
+            "Expiry must be in the future".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Create a key from validated input; the full key is only ever returned here
+pub async fn create(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: Uuid,
+    created_by: Uuid,
+    input: &CreateApiKeyInput,
+) -> Result<CreatedApiKeyResponse, AppError> {
+    let (key, prefix) = generate_key();
+    let api_key =
+        api_key_repo::create_api_key(tx, company_id, created_by, input, &prefix, &hash_key(&key))
+            .await?;
+
+    Ok(CreatedApiKeyResponse { api_key, key })
+}
+
+fn random_alphanumeric(len: usize) -> String {
+    rand::rng()
+        .sample_iter(&rand::distr::Alphanumeric)
+        .take(len)
+        .map(|c| char::from(c).to_ascii_lowercase())
+        .collect()
+}
@@ -6,19 +6,35 @@ use actix_web::{
 use anyhow::{Result, anyhow};
 use bcrypt::{DEFAULT_COST, hash, verify};
 use chrono::{Duration, Utc};
-use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::config::config;
 use crate::database::transaction::DatabaseTransaction;
 use crate::database::{
-    models::{AuthResponse, CompanyRole, CreateUserInput, LoginInput, User},
+    models::{
+        Action, AuthResponse, CompanyInfo, CompanyRole, CreateSessionInput, CreateUserInput,
+        ImpersonationSession, LoginInput, LoginResponse, TwoFactorChallenge, TwoFactorLoginInput,
+        User, UserSession,
+    },
     repositories::{
-        company as company_repo, password_reset as password_reset_repo, user as user_repo,
+        account_lockout as account_lockout_repo, company as company_repo,
+        password_reset as password_reset_repo, session as session_repo,
+        two_factor as two_factor_repo, user as user_repo,
     },
 };
 use crate::error::AppError;
+use crate::middleware::request_info::RequestInfo;
+use crate::services::{
+    account_lockout, activity_logger, email_verification, jwt_keys::key_ring, two_factor,
+};
+
+/// How long the password step of a 2FA login stays valid
+const MFA_CHALLENGE_MINUTES: i64 = 5;
+const MFA_CHALLENGE_PURPOSE: &str = "mfa";
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
@@ -26,9 +42,26 @@ pub struct Claims {
     pub email: String,
     pub company_id: Option<Uuid>, // optional company ID for company-specific roles
     pub role: Option<CompanyRole>, // user role (admin, manager, employee)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<Uuid>, // session the token was issued for
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub mfa: bool, // signed in with a second factor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Uuid>, // admin acting as `sub` through impersonation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub imp: Option<Uuid>, // impersonation session the token was issued for
     pub exp: usize,               // expiration time
 }
 
+/// Claims of the short-lived token issued between the password and 2FA steps of login.
+/// It deliberately lacks `email`, so it can never be decoded as `Claims`.
+#[derive(Debug, Serialize, Deserialize)]
+struct MfaChallengeClaims {
+    sub: Uuid,
+    purpose: String,
+    exp: usize,
+}
+
 impl Claims {
     pub fn user_id(&self) -> Uuid {
         self.sub
@@ -59,14 +92,10 @@ impl FromRequest for Claims {
                 if auth_str.starts_with("Bearer ") {
                     let token = &auth_str[7..]; // Remove "Bearer " prefix
 
-                    // Get the config from app data
-                    match decode::<Claims>(
-                        token,
-                        &DecodingKey::from_secret(config().jwt_secret.as_ref()),
-                        &Validation::new(Algorithm::HS256),
-                    ) {
-                        Ok(token_data) => {
-                            return ready(Ok(token_data.claims));
+                    // Verify against the key selected by the token's `kid`
+                    match verify_token(token) {
+                        Ok(claims) => {
+                            return ready(Ok(claims));
                         }
                         Err(_) => {
                             return ready(Err(ErrorUnauthorized("Invalid token")));
@@ -82,7 +111,10 @@ impl FromRequest for Claims {
     }
 }
 
-pub async fn register(request: CreateUserInput) -> Result<AuthResponse, AppError> {
+pub async fn register(
+    request: CreateUserInput,
+    req_info: &RequestInfo,
+) -> Result<AuthResponse, AppError> {
     // Check if email already exists
     if user_repo::email_exists(&request.email).await? {
         return Err(AppError::BadRequest("Email already exists".into()));
@@ -97,43 +129,167 @@ pub async fn register(request: CreateUserInput) -> Result<AuthResponse, AppError
     // Create user
     let user = User::new(request.email, password_hash, request.name);
 
-    let created_user = DatabaseTransaction::run(|tx| {
-        Box::pin(async move {
-            // Save to database
-            Ok(user_repo::create_user(tx, &user).await?)
+    let req_info = req_info.clone();
+    let (created_user, session, refresh_token, verification_token) =
+        DatabaseTransaction::run(|tx| {
+            Box::pin(async move {
+                // Save to database
+                let created_user = user_repo::create_user(tx, &user).await?;
+
+                let (session, refresh_token) =
+                    open_session(tx, created_user.id, None, false, &req_info).await?;
+
+                let verification_token =
+                    email_verification::issue(tx, created_user.id, &created_user.email).await?;
+
+                Ok((created_user, session, refresh_token, verification_token))
+            })
         })
-    })
-    .await?;
+        .await?;
 
-    let token = generate_token(&created_user, None, None).map_err(|e| {
+    email_verification::send(&created_user.name, &verification_token).await;
+
+    let token = generate_token(&created_user, None, None, Some(&session)).map_err(|e| {
         log::error!("Failed to generate token: {}", e);
         AppError::internal_server_error_message(e.to_string())
     })?;
 
     Ok(AuthResponse {
         token,
+        refresh_token: Some(refresh_token),
         user: created_user.into(),
         company: None, // No company info on registration
     })
 }
 
-pub async fn login(request: LoginInput) -> Result<AuthResponse> {
+pub async fn login(request: LoginInput, req_info: &RequestInfo) -> Result<LoginResponse> {
     // Find user by email
     let user = user_repo::find_by_email(&request.email)
         .await?
         .ok_or_else(|| anyhow!("Invalid email or password"))?;
 
-    // Verify password
-    if !verify(&request.password, &user.password_hash)? {
+    // Accounts with too many recent failures are refused before the password is checked
+    let attempt = account_lockout::attempt(&user, "invalid_password", req_info, async {
+        verify(&request.password, &user.password_hash)
+            .map_err(|e| AppError::internal_server_error_message(e.to_string()))
+    })
+    .await?;
+    if !attempt.verified {
         return Err(anyhow!("Invalid email or password"));
     }
 
+    let response = finish_login(user, None, req_info).await?;
+
+    // Failures only reset once the login is complete, so a pending 2FA step keeps them
+    if let LoginResponse::Authenticated(auth) = &response {
+        if attempt.lockout.is_some() {
+            account_lockout::reset(auth.user.id).await?;
+        }
+        log_login(auth, req_info).await?;
+    }
+
+    Ok(response)
+}
+
+/// Issue tokens for a user whose first factor has been checked, or a challenge if they use 2FA.
+/// The session opens in `company_id` when given, otherwise in the user's primary company.
+pub async fn finish_login(
+    user: User,
+    company_id: Option<Uuid>,
+    req_info: &RequestInfo,
+) -> Result<LoginResponse> {
+    // Users with 2FA get a challenge instead of tokens
+    let two_factor = two_factor_repo::find_by_user_id(user.id).await?;
+    if two_factor.is_some_and(|two_factor| two_factor.is_enabled()) {
+        return Ok(LoginResponse::TwoFactorRequired(generate_mfa_challenge(
+            user.id,
+        )?));
+    }
+
+    let response = complete_login(user, company_id, false, req_info).await?;
+
+    Ok(LoginResponse::Authenticated(Box::new(response)))
+}
+
+/// Finish a login that was paused for a second factor
+pub async fn login_with_two_factor(
+    request: TwoFactorLoginInput,
+    req_info: &RequestInfo,
+) -> Result<AuthResponse, AppError> {
+    let challenge = key_ring()?
+        .decode::<MfaChallengeClaims>(&request.mfa_token)
+        .map_err(|_| AppError::Unauthorized)?;
+    if challenge.purpose != MFA_CHALLENGE_PURPOSE {
+        return Err(AppError::Unauthorized);
+    }
+
+    let user_id = challenge.sub;
+    let user = user_repo::find_by_id(user_id)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    // Failed codes count towards the same lockout as failed passwords
+    let code = request.code;
+    let verify_code = DatabaseTransaction::run(|tx| {
+        Box::pin(async move { Ok(two_factor::verify_and_consume(tx, user_id, &code, true).await?) })
+    });
+    let attempt =
+        account_lockout::attempt(&user, "invalid_two_factor_code", req_info, verify_code).await?;
+    if !attempt.verified {
+        return Err(AppError::Unauthorized);
+    }
+
+    if attempt.lockout.is_some() {
+        account_lockout::reset(user_id).await?;
+    }
+
+    let response = complete_login(user, None, true, req_info).await?;
+    log_login(&response, req_info).await?;
+
+    Ok(response)
+}
+
+/// Record a completed password login in the company the session opened in
+async fn log_login(response: &AuthResponse, req_info: &RequestInfo) -> Result<(), AppError> {
+    let Some(company_id) = response.company.as_ref().map(|c| c.id) else {
+        return Ok(());
+    };
+    let user_id = response.user.id;
+    let user_email = response.user.email.clone();
+    let req_info = req_info.clone();
+
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            activity_logger::log_auth_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                Action::LOGIN,
+                format!("User {} logged in", user_email),
+                None,
+                &req_info,
+            )
+            .await?;
+            Ok(())
+        })
+    })
+    .await
+}
+
+/// Open a session in the requested (or primary) company and issue its tokens
+async fn complete_login(
+    user: User,
+    company_id: Option<Uuid>,
+    mfa_verified: bool,
+    req_info: &RequestInfo,
+) -> Result<AuthResponse> {
     // Get user's companies
     let companies = company_repo::get_companies_for_user(user.id).await?;
 
     let primary_company = companies
         .iter()
-        .find(|c| c.is_primary)
+        .find(|c| Some(c.id) == company_id)
+        .or_else(|| companies.iter().find(|c| c.is_primary))
         .or_else(|| companies.first())
         .cloned();
 
@@ -144,24 +300,213 @@ pub async fn login(request: LoginInput) -> Result<AuthResponse> {
         None => None,
     };
 
+    // Open a session for this device
+    let user_id = user.id;
+    let req_info = req_info.clone();
+    let (session, refresh_token) = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            Ok(open_session(tx, user_id, company_id, mfa_verified, &req_info).await?)
+        })
+    })
+    .await?;
+
     // Generate JWT token
-    let token = generate_token(&user, company_id, role)?;
+    let token = generate_token(&user, company_id, role, Some(&session))?;
 
     Ok(AuthResponse {
         token,
+        refresh_token: Some(refresh_token),
         user: user.into(),
         company: primary_company,
     })
 }
 
-pub fn verify_token(token: &str) -> Result<Claims> {
-    let token_data = decode::<Claims>(
+fn generate_mfa_challenge(user_id: Uuid) -> Result<TwoFactorChallenge> {
+    let expires_at = Utc::now() + Duration::minutes(MFA_CHALLENGE_MINUTES);
+    let claims = MfaChallengeClaims {
+        sub: user_id,
+        purpose: MFA_CHALLENGE_PURPOSE.to_string(),
+        exp: expires_at.timestamp() as usize,
+    };
+
+    Ok(TwoFactorChallenge {
+        mfa_required: true,
+        mfa_token: key_ring()?.encode(&claims)?,
+        expires_at,
+    })
+}
+
+/// Issue a new access token for an existing session, e.g. after it was upgraded by 2FA
+pub async fn token_for_session(
+    user: &User,
+    company: Option<&CompanyInfo>,
+    session_id: Uuid,
+) -> Result<String, AppError> {
+    let session = session_repo::find_by_id(session_id)
+        .await?
+        .filter(|session| session.user_id == user.id && session.is_active())
+        .ok_or(AppError::Unauthorized)?;
+
+    generate_token(
+        user,
+        company.map(|c| c.id),
+        company.map(|c| c.role.clone()),
+        Some(&session),
+    )
+    .map_err(|e| {
+        log::error!("Failed to generate token: {}", e);
+        AppError::internal_server_error_message(e.to_string())
+    })
+}
+
+/// Exchange a refresh token for a new access token, rotating the refresh token
+pub async fn refresh(
+    refresh_token: &str,
+    req_info: &RequestInfo,
+) -> Result<AuthResponse, AppError> {
+    let token_hash = hash_refresh_token(refresh_token);
+
+    let session = match session_repo::find_by_refresh_token_hash(&token_hash).await? {
+        Some(session) => session,
+        None => {
+            // A rotated token being presented again means it has leaked; kill the session
+            if let Some(session) =
+                session_repo::find_by_previous_refresh_token_hash(&token_hash).await?
+            {
+                log::warn!(
+                    "Refresh token reuse detected for session {}, revoking",
+                    session.id
+                );
+                DatabaseTransaction::run(|tx| {
+                    Box::pin(async move {
+                        session_repo::revoke_session(tx, session.user_id, session.id).await?;
+                        Ok(())
+                    })
+                })
+                .await?;
+            }
+            return Err(AppError::Unauthorized);
+        }
+    };
+
+    if !session.is_active() {
+        return Err(AppError::Unauthorized);
+    }
+
+    let user = user_repo::find_by_id(session.user_id)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    // Re-read the membership so role changes take effect on the next refresh
+    let company = match session.company_id {
+        Some(company_id) => company_repo::find_user_company_info_by_id(user.id, company_id).await?,
+        None => None,
+    };
+
+    let new_refresh_token = generate_refresh_token();
+    let new_token_hash = hash_refresh_token(&new_refresh_token);
+    let ip_address = req_info.ip_address.clone();
+    let user_agent = req_info.user_agent.clone();
+
+    let session = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            session_repo::rotate_refresh_token(
+                tx,
+                session.id,
+                &token_hash,
+                &new_token_hash,
+                &ip_address,
+                &user_agent,
+            )
+            .await?
+            .ok_or(AppError::Unauthorized)
+        })
+    })
+    .await?;
+
+    let token = generate_token(
+        &user,
+        company.as_ref().map(|c| c.id),
+        company.as_ref().map(|c| c.role.clone()),
+        Some(&session),
+    )
+    .map_err(|e| {
+        log::error!("Failed to generate token: {}", e);
+        AppError::internal_server_error_message(e.to_string())
+    })?;
+
+    Ok(AuthResponse {
         token,
-        &DecodingKey::from_secret(config().jwt_secret.as_ref()),
-        &Validation::new(Algorithm::HS256),
-    )?;
+        refresh_token: Some(new_refresh_token),
+        user,
+        company,
+    })
+}
 
-    Ok(token_data.claims)
+/// Revoke a single session of the given user
+pub async fn logout(user_id: Uuid, session_id: Uuid) -> Result<(), AppError> {
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            session_repo::revoke_session(tx, user_id, session_id)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Session not found".to_string()))
+        })
+    })
+    .await
+}
+
+/// Revoke every session of the given user ("log out everywhere")
+pub async fn logout_all(user_id: Uuid) -> Result<u64, AppError> {
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move { Ok(session_repo::revoke_all_sessions_for_user(tx, user_id).await?) })
+    })
+    .await
+}
+
+/// Create a session row and return it along with the raw refresh token
+async fn open_session(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    company_id: Option<Uuid>,
+    mfa_verified: bool,
+    req_info: &RequestInfo,
+) -> Result<(UserSession, String), sqlx::Error> {
+    let refresh_token = generate_refresh_token();
+    let expires_at = Utc::now() + Duration::days(config().jwt_expiration_days);
+
+    let session = session_repo::create_session(
+        tx,
+        &CreateSessionInput {
+            user_id,
+            company_id,
+            refresh_token_hash: hash_refresh_token(&refresh_token),
+            user_agent: req_info.user_agent.clone(),
+            ip_address: req_info.ip_address.clone(),
+            expires_at,
+            mfa_verified,
+        },
+    )
+    .await?;
+
+    Ok((session, refresh_token))
+}
+
+/// Generate an opaque, cryptographically secure refresh token
+fn generate_refresh_token() -> String {
+    rand::rng()
+        .sample_iter(&rand::distr::Alphanumeric)
+        .take(64)
+        .map(char::from)
+        .collect()
+}
+
+/// Refresh tokens are only ever stored as SHA-256 digests
+fn hash_refresh_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+pub fn verify_token(token: &str) -> Result<Claims> {
+    key_ring()?.decode::<Claims>(token)
 }
 
 pub async fn get_user_from_token(token: &str) -> Result<User> {
@@ -187,20 +532,44 @@ pub async fn generate_company_token(user_id: Uuid, company_id: Uuid) -> Result<S
     let role = Some(CompanyRole::Employee); // Placeholder
     let retreived_company_id = Some(company.id);
 
-    let token = generate_token(&user, retreived_company_id, role)?;
+    let token = generate_token(&user, retreived_company_id, role, None)?;
     Ok(token)
 }
 
-// Updated generate_token method
+/// Issue an access token that lets an admin act as `user`. It expires with the impersonation
+/// session and has no refresh token, so it cannot outlive it.
+pub fn impersonation_token(
+    user: &User,
+    company: &CompanyInfo,
+    session: &ImpersonationSession,
+    mfa_verified: bool,
+) -> Result<String> {
+    let claims = Claims {
+        sub: user.id,
+        email: user.email.clone(),
+        company_id: Some(company.id),
+        role: Some(company.role.clone()),
+        sid: None,
+        mfa: mfa_verified,
+        act: Some(session.admin_user_id),
+        imp: Some(session.id),
+        exp: session.expires_at.timestamp() as usize,
+    };
+
+    key_ring()?.encode(&claims)
+}
+
+// Access tokens are short-lived; sessions are kept alive through refresh tokens
 fn generate_token(
     user: &User,
     company_id: Option<Uuid>,
     role: Option<CompanyRole>,
+    session: Option<&UserSession>,
 ) -> Result<String> {
     // Get the config
     let config = config();
     let expiration = Utc::now()
-        .checked_add_signed(Duration::days(config.jwt_expiration_days))
+        .checked_add_signed(Duration::minutes(config.access_token_minutes))
         .expect("valid timestamp")
         .timestamp() as usize;
 
@@ -209,16 +578,14 @@ fn generate_token(
         email: user.email.clone(),
         company_id,
         role,
+        sid: session.map(|s| s.id),
+        mfa: session.is_some_and(|s| s.mfa_verified),
+        act: None,
+        imp: None,
         exp: expiration,
     };
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(config.jwt_secret.as_ref()),
-    )?;
-
-    Ok(token)
+    key_ring()?.encode(&claims)
 }
 
 /// Request password reset - generates and stores a reset token
@@ -272,6 +639,12 @@ pub async fn reset_password(token: &str, new_password: &str) -> Result<()> {
             // Invalidate all other reset tokens for this user
             password_reset_repo::invalidate_user_tokens(tx, reset_token.user_id).await?;
 
+            // Sign the user out everywhere now that the old password is gone
+            session_repo::revoke_all_sessions_for_user(tx, reset_token.user_id).await?;
+
+            // A new password lifts any lockout on the account
+            account_lockout_repo::clear(tx, reset_token.user_id).await?;
+
             Ok(())
         })
     })
@@ -289,7 +662,11 @@ pub async fn get_user_by_email(email: &str) -> Result<User> {
     Ok(user)
 }
 
-pub async fn switch_company(user_id: Uuid, new_company_id: Uuid) -> Result<AuthResponse> {
+pub async fn switch_company(
+    user_id: Uuid,
+    new_company_id: Uuid,
+    session_id: Option<Uuid>,
+) -> Result<AuthResponse> {
     // Check if user belongs to the new company
     match company_repo::check_user_company_access(user_id, new_company_id).await {
         Ok(Some(_)) => {}
@@ -307,10 +684,28 @@ pub async fn switch_company(user_id: Uuid, new_company_id: Uuid) -> Result<AuthR
 
     let role = Some(company.role.clone());
 
-    let token = generate_token(&user, Some(new_company_id), role)?;
+    // Keep the session pointed at the new company so refreshes stay in it
+    let session = match session_id {
+        Some(session_id) => {
+            DatabaseTransaction::run(|tx| {
+                Box::pin(async move {
+                    Ok(
+                        session_repo::update_session_company(tx, session_id, new_company_id)
+                            .await?,
+                    )
+                })
+            })
+            .await?;
+            session_repo::find_by_id(session_id).await?
+        }
+        None => None,
+    };
+
+    let token = generate_token(&user, Some(new_company_id), role, session.as_ref())?;
 
     Ok(AuthResponse {
         token,
+        refresh_token: None, // The existing refresh token stays valid
         user: user.into(),
         company: Some(company),
     })
@@ -0,0 +1,190 @@
+use bcrypt::verify;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::config::config;
+use crate::database::{
+    models::{Action, ChangeEmailInput, EmailVerificationToken, User},
+    repositories::{
+        company as company_repo, email_verification as email_verification_repo, user as user_repo,
+    },
+    transaction::DatabaseTransaction,
+};
+use crate::error::AppError;
+use crate::middleware::request_info::RequestInfo;
+use crate::services::{activity_logger, mailer};
+
+/// Replace any outstanding verification token for the user with a new one for `email`
+pub async fn issue(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    email: &str,
+) -> Result<EmailVerificationToken, sqlx::Error> {
+    email_verification_repo::invalidate_user_tokens(tx, user_id).await?;
+    email_verification_repo::create_token(tx, user_id, email).await
+}
+
+/// Email the verification link for `token` to the address it verifies
+pub async fn send(name: &str, token: &EmailVerificationToken) {
+    let body = format!(
+        "Hi {},\n\n\
+         Confirm your email address by opening the link below:\n\n\
+         {}/auth/verify-email?token={}\n\n\
+         The link expires at {}.",
+        name,
+        config().client_base_url,
+        token.token,
+        token.expires_at.format("%Y-%m-%d %H:%M UTC"),
+    );
+
+    mailer::send(&token.email, "Verify your ShiftLinkr email address", &body).await;
+}
+
+/// Send a fresh verification link for the user's current address
+pub async fn resend(user: &User) -> Result<String, AppError> {
+    if user.is_email_verified() {
+        return Err(AppError::BadRequest(
+            "Email address is already verified".into(),
+        ));
+    }
+
+    let user_id = user.id;
+    let email = user.email.clone();
+    let token = DatabaseTransaction::run(|tx| {
+        Box::pin(async move { Ok(issue(tx, user_id, &email).await?) })
+    })
+    .await?;
+
+    send(&user.name, &token).await;
+
+    Ok(token.token)
+}
+
+/// Start changing the user's email; the address only changes once the link sent to it is used
+pub async fn request_email_change(
+    user: &User,
+    input: &ChangeEmailInput,
+) -> Result<String, AppError> {
+    let valid_password = verify(&input.password, &user.password_hash).map_err(|_| {
+        log::error!("Failed to verify password");
+        AppError::internal_server_error_message("Failed to verify password")
+    })?;
+    if !valid_password {
+        return Err(AppError::BadRequest("Invalid password".into()));
+    }
+
+    let new_email = input.new_email.trim().to_string();
+    if new_email.is_empty() || !new_email.contains('@') {
+        return Err(AppError::BadRequest("Invalid email address".into()));
+    }
+    if new_email.eq_ignore_ascii_case(&user.email) {
+        return Err(AppError::BadRequest(
+            "New email is the same as the current email".into(),
+        ));
+    }
+    if user_repo::email_exists(&new_email).await? {
+        return Err(AppError::BadRequest("Email already exists".into()));
+    }
+
+    let user_id = user.id;
+    let token = DatabaseTransaction::run(|tx| {
+        Box::pin(async move { Ok(issue(tx, user_id, &new_email).await?) })
+    })
+    .await?;
+
+    send(&user.name, &token).await;
+
+    let body = format!(
+        "Hi {},\n\n\
+         A change of your ShiftLinkr email address to {} was requested. Your address \
+         stays the same until the change is confirmed from the new inbox.\n\n\
+         If this wasn't you, change your password and contact your administrator.",
+        user.name, token.email,
+    );
+    mailer::send(
+        &user.email,
+        "Your ShiftLinkr email address is changing",
+        &body,
+    )
+    .await;
+
+    Ok(token.token)
+}
+
+/// Verify the address a token was issued for, switching the user's email to it if needed
+pub async fn verify_email(token: &str, req_info: &RequestInfo) -> Result<User, AppError> {
+    let verification_token = email_verification_repo::find_valid_token(token)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Invalid or expired verification token".into()))?;
+
+    let user = user_repo::find_by_id(verification_token.user_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".into()))?;
+
+    let changing_email = user.email != verification_token.email;
+    if changing_email && user_repo::email_exists(&verification_token.email).await? {
+        return Err(AppError::BadRequest("Email already exists".into()));
+    }
+
+    let company_id = company_repo::get_primary_company_for_user(user.id)
+        .await?
+        .map(|company| company.id);
+    let old_email = user.email.clone();
+    let token = token.to_string();
+    let request_info = req_info.clone();
+
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            if email_verification_repo::mark_token_used(tx, &token)
+                .await?
+                .is_none()
+            {
+                return Err(AppError::BadRequest(
+                    "Invalid or expired verification token".into(),
+                ));
+            }
+
+            let user = user_repo::set_verified_email(
+                tx,
+                verification_token.user_id,
+                &verification_token.email,
+            )
+            .await?;
+
+            // Any other outstanding links are for addresses the user no longer wants
+            email_verification_repo::invalidate_user_tokens(tx, user.id).await?;
+
+            if let Some(company_id) = company_id {
+                if changing_email {
+                    activity_logger::log_auth_activity(
+                        tx,
+                        company_id,
+                        Some(user.id),
+                        Action::EMAIL_CHANGED,
+                        format!("Email changed from {} to {}", old_email, user.email),
+                        Some(activity_logger::metadata(vec![
+                            ("old_email", old_email.clone()),
+                            ("new_email", user.email.clone()),
+                        ])),
+                        &request_info,
+                    )
+                    .await?;
+                } else {
+                    activity_logger::log_auth_activity(
+                        tx,
+                        company_id,
+                        Some(user.id),
+                        Action::EMAIL_VERIFIED,
+                        format!("Email {} verified", user.email),
+                        None,
+                        &request_info,
+                    )
+                    .await?;
+                }
+            }
+
+            Ok(user)
+        })
+    })
+    .await
+}
@@ -0,0 +1,609 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use bcrypt::{DEFAULT_COST, hash};
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{NaiveDate, Utc};
+use rand::Rng;
+use uuid::Uuid;
+
+use crate::config::config;
+use crate::database::{
+    models::{
+        AddEmployeeToCompanyInput, CompanyRole, CreateWageHistoryInput, EmployeeImportResponse,
+        EmployeeImportRowResult, Location, PasswordResetToken, Permission, ProficiencyLevel,
+        PtoBalanceUpdateInput, Skill, Team, User,
+    },
+    repositories::{
+        company as company_repo, location as location_repo, password_reset as password_reset_repo,
+        pto_balance as pto_balance_repo, skill as skill_repo, team as team_repo, user as user_repo,
+        wage as wage_repo,
+    },
+    transaction::DatabaseTransaction,
+};
+use crate::error::AppError;
+use crate::middleware::request_info::RequestInfo;
+use crate::services::{
+    activity_logger,
+    invites::{find_team, split_csv_line},
+    mailer,
+    manager_scope::{self, ManagerScope},
+    user_context::UserContext,
+};
+
+/// Most employee rows accepted in one import
+pub const MAX_IMPORT_ROWS: usize = 500;
+
+/// Columns an import file may contain, in any order. `name` and `email` are required.
+pub const IMPORT_COLUMNS: [&str; 11] = [
+    "name",
+    "email",
+    "role",
+    "hire_date",
+    "team",
+    "location",
+    "hourly_rate",
+    "pto_hours",
+    "sick_hours",
+    "personal_hours",
+    "skills",
+];
+
+const PTO_COLUMNS: [&str; 3] = ["pto_hours", "sick_hours", "personal_hours"];
+
+/// An import line that passed validation
+struct ImportRow {
+    line: usize,
+    name: String,
+    email: String,
+    role: CompanyRole,
+    hire_date: Option<NaiveDate>,
+    team_id: Option<Uuid>,
+    hourly_rate: Option<BigDecimal>,
+    pto_hours: Option<BigDecimal>,
+    sick_hours: Option<BigDecimal>,
+    personal_hours: Option<BigDecimal>,
+    skills: Vec<(Uuid, ProficiencyLevel)>,
+    existing_user_id: Option<Uuid>,
+}
+
+/// Company data the rows are checked against
+struct Lookup {
+    teams: Vec<Team>,
+    locations: Vec<Location>,
+    skills: Vec<Skill>,
+    members: HashSet<String>,
+    users: HashMap<String, Uuid>,
+    scope: ManagerScope,
+    can_import_admins: bool,
+}
+
+/// Import employees from a CSV whose header line names the columns (see `IMPORT_COLUMNS`).
+/// Every line is validated first; the import only goes ahead when all lines are valid and
+/// `dry_run` is off, and then happens in a single transaction. New accounts get an email
+/// with a link to choose their password; existing accounts are added to the company.
+pub async fn import_employees(
+    ctx: &UserContext,
+    csv: &str,
+    dry_run: bool,
+    req_info: &RequestInfo,
+) -> Result<EmployeeImportResponse, AppError> {
+    let company_id = ctx.strict_company_id()?;
+    let importer_id = ctx.user_id();
+
+    let mut lines = csv
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| (index + 1, split_csv_line(line)));
+
+    let columns = match lines.next() {
+        Some((_, Ok(header))) => parse_header(header)?,
+        Some((_, Err(error))) => {
+            return Err(AppError::BadRequest(format!(
+                "Invalid header line: {}",
+                error
+            )));
+        }
+        None => {
+            return Err(AppError::BadRequest(
+                "The CSV does not contain a header line".to_string(),
+            ));
+        }
+    };
+    let lines: Vec<(usize, Result<Vec<String>, String>)> = lines.collect();
+    if lines.is_empty() {
+        return Err(AppError::BadRequest(
+            "The CSV does not contain any employees".to_string(),
+        ));
+    }
+    if lines.len() > MAX_IMPORT_ROWS {
+        return Err(AppError::BadRequest(format!(
+            "An import can contain at most {} rows",
+            MAX_IMPORT_ROWS
+        )));
+    }
+
+    // Wages and balances need the same permissions as editing them directly
+    if columns.contains_key("hourly_rate") {
+        ctx.requires_permission(Permission::WAGES_EDIT)?;
+    }
+    if PTO_COLUMNS
+        .iter()
+        .any(|column| columns.contains_key(*column))
+    {
+        ctx.requires_permission(Permission::PTO_MANAGE)?;
+    }
+
+    let emails: Vec<String> = lines
+        .iter()
+        .filter_map(|(_, fields)| fields.as_ref().ok())
+        .filter_map(|fields| fields.get(columns["email"]))
+        .cloned()
+        .collect();
+    let lookup = Lookup {
+        teams: team_repo::get_all_teams_for_company(company_id).await?,
+        locations: location_repo::get_locations_by_company(company_id).await?,
+        skills: skill_repo::get_all_skills(company_id).await?,
+        members: company_repo::get_company_employees(company_id)
+            .await?
+            .into_iter()
+            .map(|employee| employee.email.to_lowercase())
+            .collect(),
+        users: user_repo::find_by_emails(&emails)
+            .await?
+            .into_iter()
+            .map(|user| (user.email, user.id))
+            .collect(),
+        scope: manager_scope::load(ctx).await?,
+        can_import_admins: ctx.has_permission(Permission::ROLES_MANAGE),
+    };
+
+    let mut results = Vec::new();
+    let mut rows = Vec::new();
+    let mut seen = HashSet::new();
+    for (line, fields) in lines {
+        let fields = match fields {
+            Ok(fields) => fields,
+            Err(error) => {
+                results.push(EmployeeImportRowResult {
+                    line,
+                    email: String::new(),
+                    valid: false,
+                    errors: vec![error],
+                    user_id: None,
+                    new_user: false,
+                });
+                continue;
+            }
+        };
+
+        let email = field(&fields, &columns, "email").unwrap_or_default();
+        let mut row = validate_row(line, &fields, &columns, &lookup);
+        if !email.is_empty() && !seen.insert(email.to_lowercase()) {
+            let error = "Email appears more than once in the file".to_string();
+            row = match row {
+                Ok(_) => Err(vec![error]),
+                Err(mut errors) => {
+                    errors.push(error);
+                    Err(errors)
+                }
+            };
+        }
+
+        results.push(EmployeeImportRowResult {
+            line,
+            new_user: !lookup.users.contains_key(&email),
+            email,
+            valid: row.is_ok(),
+            errors: row.as_ref().err().cloned().unwrap_or_default(),
+            user_id: None,
+        });
+        if let Ok(row) = row {
+            rows.push(row);
+        }
+    }
+
+    let failed = results.iter().filter(|result| !result.valid).count();
+    if dry_run || failed > 0 {
+        return Ok(EmployeeImportResponse {
+            dry_run,
+            imported: false,
+            total: results.len(),
+            failed,
+            results,
+        });
+    }
+
+    // Accounts created here have no usable password until their owner picks one through the
+    // emailed link, so one random hash serves the whole import
+    let password_hash = hash(
+        URL_SAFE_NO_PAD.encode(rand::rng().random::<[u8; 32]>()),
+        DEFAULT_COST,
+    )
+    .map_err(|e| AppError::internal_server_error_message(e.to_string()))?;
+
+    let req_info = req_info.clone();
+    let imported = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let mut imported = Vec::new();
+            for row in rows {
+                let (user_id, reset_token) = match row.existing_user_id {
+                    Some(user_id) => (user_id, None),
+                    None => {
+                        let user = user_repo::create_user(
+                            tx,
+                            &User::new(row.email.clone(), password_hash.clone(), row.name.clone()),
+                        )
+                        .await?;
+                        let token = password_reset_repo::create_token(tx, user.id).await?;
+                        (user.id, Some(token))
+                    }
+                };
+
+                company_repo::add_employee_to_company(
+                    tx,
+                    company_id,
+                    &AddEmployeeToCompanyInput {
+                        user_id,
+                        role: Some(row.role.clone()),
+                        is_primary: Some(reset_token.is_some()),
+                        hire_date: row.hire_date,
+                    },
+                )
+                .await?;
+
+                pto_balance_repo::set_balance_fields(
+                    tx,
+                    user_id,
+                    company_id,
+                    PtoBalanceUpdateInput {
+                        pto_balance_hours: row.pto_hours,
+                        sick_balance_hours: row.sick_hours,
+                        personal_balance_hours: row.personal_hours,
+                        pto_accrual_rate: None,
+                        hire_date: None,
+                    },
+                    Some(importer_id),
+                )
+                .await?;
+
+                if let Some(team_id) = row.team_id {
+                    team_repo::add_team_member(tx, team_id, user_id).await?;
+                }
+
+                for (skill_id, level) in &row.skills {
+                    skill_repo::add_skill_to_user(tx, *skill_id, user_id, level.clone()).await?;
+                }
+
+                if let Some(hourly_rate) = &row.hourly_rate {
+                    wage_repo::create_wage_history(
+                        tx,
+                        &CreateWageHistoryInput {
+                            user_id,
+                            company_id,
+                            hourly_rate: hourly_rate.clone(),
+                            overtime_rate_multiplier: None,
+                            effective_date: row
+                                .hire_date
+                                .unwrap_or_else(|| Utc::now().date_naive()),
+                            end_date: None,
+                            changed_by: Some(importer_id),
+                            change_reason: Some("Imported from CSV".to_string()),
+                        },
+                    )
+                    .await?;
+                }
+
+                let metadata = activity_logger::metadata(vec![
+                    ("employee_user_id", user_id.to_string()),
+                    ("employee_role", row.role.to_string()),
+                    ("new_user", reset_token.is_some().to_string()),
+                    ("import", "true".to_string()),
+                ]);
+                activity_logger::log_user_activity(
+                    tx,
+                    company_id,
+                    Some(importer_id),
+                    user_id,
+                    "add_employee",
+                    format!(
+                        "User {} imported into company {} with role {}",
+                        row.email, company_id, row.role
+                    ),
+                    Some(metadata),
+                    &req_info,
+                )
+                .await?;
+
+                imported.push((row.line, user_id, row.name, row.email, reset_token));
+            }
+            Ok(imported)
+        })
+    })
+    .await?;
+
+    let company_name = ctx
+        .company
+        .as_ref()
+        .map_or_else(String::new, |company| company.name.clone());
+    for (line, user_id, name, email, reset_token) in imported {
+        if let Some(token) = reset_token {
+            send_welcome(&name, &email, &company_name, &token).await;
+        }
+        if let Some(result) = results.iter_mut().find(|result| result.line == line) {
+            result.user_id = Some(user_id);
+        }
+    }
+
+    Ok(EmployeeImportResponse {
+        dry_run,
+        imported: true,
+        total: results.len(),
+        failed,
+        results,
+    })
+}
+
+/// Email a new account the link to choose its password
+async fn send_welcome(name: &str, email: &str, company_name: &str, token: &PasswordResetToken) {
+    let body = format!(
+        "Hi {},\n\n\
+         An account was created for you to join {} on ShiftLinkr.\n\n\
+         Choose your password here: {}/auth/reset-password?token={}\n\n\
+         The link expires at {}. After that, use \"Forgot password\" to get a new one.",
+        name,
+        company_name,
+        config().client_base_url,
+        token.token,
+        token.expires_at.format("%Y-%m-%d %H:%M UTC"),
+    );
+    mailer::send(
+        email,
+        &format!("Welcome to {} on ShiftLinkr", company_name),
+        &body,
+    )
+    .await;
+}
+
+/// Map each known column name to its position in the file
+fn parse_header(header: Vec<String>) -> Result<HashMap<&'static str, usize>, AppError> {
+    let mut columns = HashMap::new();
+    for (index, name) in header.iter().enumerate() {
+        let normalized = name.to_lowercase().replace([' ', '-'], "_");
+        let column = IMPORT_COLUMNS
+            .iter()
+            .find(|column| **column == normalized)
+            .ok_or_else(|| {
+                AppError::BadRequest(format!(
+                    "Unknown column '{}'; expected {}",
+                    name,
+                    IMPORT_COLUMNS.join(", ")
+                ))
+            })?;
+        if columns.insert(*column, index).is_some() {
+            return Err(AppError::BadRequest(format!(
+                "Column '{}' appears more than once",
+                column
+            )));
+        }
+    }
+
+    for required in ["name", "email"] {
+        if !columns.contains_key(required) {
+            return Err(AppError::BadRequest(format!(
+                "The header line must include a '{}' column",
+                required
+            )));
+        }
+    }
+
+    Ok(columns)
+}
+
+/// The non-empty value of a column on this line, if any
+fn field(fields: &[String], columns: &HashMap<&str, usize>, column: &str) -> Option<String> {
+    columns
+        .get(column)
+        .and_then(|index| fields.get(*index))
+        .filter(|value| !value.is_empty())
+        .cloned()
+}
+
+/// Check every column of a line, collecting all problems rather than stopping at the first
+fn validate_row(
+    line: usize,
+    fields: &[String],
+    columns: &HashMap<&str, usize>,
+    lookup: &Lookup,
+) -> Result<ImportRow, Vec<String>> {
+    let mut errors = Vec::new();
+    if fields.len() > columns.len() {
+        errors.push(format!(
+            "Expected at most {} columns, found {}",
+            columns.len(),
+            fields.len()
+        ));
+    }
+
+    let email = field(fields, columns, "email").unwrap_or_default();
+    if email.is_empty() || !email.contains('@') {
+        errors.push("Invalid email address".to_string());
+    } else if lookup.members.contains(&email.to_lowercase()) {
+        errors.push("User is already a member of the company".to_string());
+    }
+    let existing_user_id = lookup.users.get(&email).copied();
+
+    let name = field(fields, columns, "name").unwrap_or_default();
+    if name.is_empty() && existing_user_id.is_none() {
+        errors.push("Name is required for new users".to_string());
+    }
+
+    let role = match field(fields, columns, "role") {
+        Some(role) => match role.parse::<CompanyRole>() {
+            Ok(CompanyRole::Admin) if !lookup.can_import_admins => {
+                errors.push(format!(
+                    "Importing admins requires the {} permission",
+                    Permission::ROLES_MANAGE
+                ));
+                CompanyRole::Admin
+            }
+            Ok(role) => role,
+            Err(_) => {
+                errors.push(format!("Unknown role '{}'", role));
+                CompanyRole::Employee
+            }
+        },
+        None => CompanyRole::Employee,
+    };
+
+    let hire_date = field(fields, columns, "hire_date").and_then(|date| {
+        NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+            .map_err(|_| errors.push(format!("Invalid hire date '{}'; use YYYY-MM-DD", date)))
+            .ok()
+    });
+
+    let location = field(fields, columns, "location");
+    let team_id = match (field(fields, columns, "team"), location) {
+        (Some(team), location) => {
+            match location
+                .map(|location| find_location(&location, &lookup.locations))
+                .transpose()
+            {
+                Ok(location_id) => {
+                    let teams: Vec<Team> = lookup
+                        .teams
+                        .iter()
+                        .filter(|t| location_id.is_none_or(|id| t.location_id == id))
+                        .cloned()
+                        .collect();
+                    match find_team(&team, &teams) {
+                        Ok(team_id) if !lookup.scope.includes_team(team_id) => {
+                            errors.push(format!(
+                                "Team '{}' is outside the locations and teams you manage",
+                                team
+                            ));
+                            None
+                        }
+                        Ok(team_id) => Some(team_id),
+                        Err(error) => {
+                            errors.push(error);
+                            None
+                        }
+                    }
+                }
+                Err(error) => {
+                    errors.push(error);
+                    None
+                }
+            }
+        }
+        (None, Some(_)) => {
+            errors.push("A location can only be given together with a team".to_string());
+            None
+        }
+        (None, None) => None,
+    };
+
+    let hourly_rate =
+        field(fields, columns, "hourly_rate").and_then(|rate| match BigDecimal::from_str(&rate) {
+            Ok(value) if value > BigDecimal::zero() => Some(value),
+            _ => {
+                errors.push(format!("Invalid hourly rate '{}'", rate));
+                None
+            }
+        });
+
+    let mut hours = |column: &str| {
+        field(fields, columns, column).and_then(|value| match BigDecimal::from_str(&value) {
+            Ok(hours) if hours >= BigDecimal::zero() => Some(hours),
+            _ => {
+                errors.push(format!("Invalid {} '{}'", column.replace('_', " "), value));
+                None
+            }
+        })
+    };
+    let pto_hours = hours("pto_hours");
+    let sick_hours = hours("sick_hours");
+    let personal_hours = hours("personal_hours");
+
+    let mut skills: Vec<(Uuid, ProficiencyLevel)> = Vec::new();
+    for entry in field(fields, columns, "skills")
+        .unwrap_or_default()
+        .split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        match parse_skill(entry, &lookup.skills) {
+            Ok((skill_id, _)) if skills.iter().any(|(id, _)| *id == skill_id) => {
+                errors.push(format!("Skill '{}' is listed more than once", entry));
+            }
+            Ok(skill) => skills.push(skill),
+            Err(error) => errors.push(error),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok(ImportRow {
+        line,
+        name,
+        email,
+        role,
+        hire_date,
+        team_id,
+        hourly_rate,
+        pto_hours,
+        sick_hours,
+        personal_hours,
+        skills,
+        existing_user_id,
+    })
+}
+
+/// Resolve a location by id or, failing that, by its name
+fn find_location(location: &str, locations: &[Location]) -> Result<Uuid, String> {
+    if let Ok(location_id) = location.parse::<Uuid>() {
+        return locations
+            .iter()
+            .find(|l| l.id == location_id)
+            .map(|l| l.id)
+            .ok_or_else(|| format!("Location {} does not belong to this company", location_id));
+    }
+
+    let matches: Vec<&Location> = locations
+        .iter()
+        .filter(|l| l.name.eq_ignore_ascii_case(location))
+        .collect();
+    match matches.as_slice() {
+        [location] => Ok(location.id),
+        [] => Err(format!("Unknown location '{}'", location)),
+        _ => Err(format!(
+            "Location name '{}' is ambiguous; use its id",
+            location
+        )),
+    }
+}
+
+/// Parse a `name` or `name:level` skill entry; the level defaults to beginner
+fn parse_skill(entry: &str, skills: &[Skill]) -> Result<(Uuid, ProficiencyLevel), String> {
+    let (name, level) = match entry.rsplit_once(':') {
+        Some((name, level)) => (
+            name.trim(),
+            level
+                .trim()
+                .parse::<ProficiencyLevel>()
+                .map_err(|_| format!("Unknown proficiency level '{}'", level.trim()))?,
+        ),
+        None => (entry, ProficiencyLevel::Beginner),
+    };
+
+    skills
+        .iter()
+        .find(|skill| skill.name.eq_ignore_ascii_case(name))
+        .map(|skill| (skill.id, level))
+        .ok_or_else(|| format!("Unknown skill '{}'", name))
+}
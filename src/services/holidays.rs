@@ -0,0 +1,397 @@
+use std::collections::HashSet;
+
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use uuid::Uuid;
+
+use crate::database::{
+    models::{
+        Action, ActivityType, CompanyHolidayInput, EntityType, HolidayCalendarQuery,
+        HolidayImportQuery, HolidayImportResponse, ImportedHoliday, ObservedHoliday,
+    },
+    repositories::{holiday as holiday_repo, location as location_repo},
+    transaction::DatabaseTransaction,
+};
+use crate::error::AppError;
+use crate::middleware::request_info::RequestInfo;
+use crate::services::activity_logger;
+
+/// Most holidays accepted from one calendar file
+pub const MAX_IMPORT_HOLIDAYS: usize = 500;
+
+/// Longest period the observed calendar covers
+const MAX_CALENDAR_DAYS: u64 = 731;
+
+/// Check a holiday's location belongs to the company and its pay multiplier is sensible
+pub async fn validate_input(company_id: Uuid, input: &CompanyHolidayInput) -> Result<(), AppError> {
+    if input.name.trim().is_empty() {
+        return Err(AppError::BadRequest("Holiday name is required".to_string()));
+    }
+    if let Some(pay_multiplier) = &input.pay_multiplier {
+        validate_pay_multiplier(pay_multiplier)?;
+    }
+    validate_location(company_id, input.location_id).await
+}
+
+fn validate_pay_multiplier(pay_multiplier: &BigDecimal) -> Result<(), AppError> {
+    if *pay_multiplier <= BigDecimal::from(0) || *pay_multiplier > BigDecimal::from(10) {
+        return Err(AppError::BadRequest(
+            "payMultiplier must be more than 0 and at most 10".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+async fn validate_location(company_id: Uuid, location_id: Option<Uuid>) -> Result<(), AppError> {
+    if let Some(location_id) = location_id {
+        location_repo::find_by_id(location_id)
+            .await?
+            .filter(|location| location.company_id == company_id)
+            .ok_or_else(|| {
+                AppError::BadRequest(format!(
+                    "Location {} does not belong to this company",
+                    location_id
+                ))
+            })?;
+    }
+    Ok(())
+}
+
+/// The days a location keeps a holiday on in the period, a year from today by default
+pub async fn calendar(
+    company_id: Uuid,
+    query: &HolidayCalendarQuery,
+) -> Result<Vec<ObservedHoliday>, AppError> {
+    validate_location(company_id, query.location_id).await?;
+
+    let start_date = query.start_date.unwrap_or_else(|| Utc::now().date_naive());
+    let end_date = match query.end_date {
+        Some(end_date) => end_date,
+        None => start_date
+            .checked_add_days(Days::new(364))
+            .ok_or_else(|| AppError::BadRequest("Invalid startDate".to_string()))?,
+    };
+    if end_date < start_date {
+        return Err(AppError::BadRequest(
+            "endDate must not be before startDate".to_string(),
+        ));
+    }
+    if (end_date - start_date).num_days() as u64 >= MAX_CALENDAR_DAYS {
+        return Err(AppError::BadRequest(format!(
+            "The calendar covers at most {} days",
+            MAX_CALENDAR_DAYS
+        )));
+    }
+
+    Ok(holiday_repo::get_observed(company_id, query.location_id, start_date, end_date).await?)
+}
+
+/// Company-wide holidays kept from `from` through `through`
+pub async fn observed_dates(
+    company_id: Uuid,
+    from: NaiveDate,
+    through: NaiveDate,
+) -> Result<HashSet<NaiveDate>, AppError> {
+    Ok(holiday_repo::get_observed(company_id, None, from, through)
+        .await?
+        .into_iter()
+        .map(|holiday| holiday.date)
+        .collect())
+}
+
+/// Warnings for a shift at the location on days it is closed for a holiday
+pub async fn closed_holiday_warnings(
+    company_id: Uuid,
+    location_id: Uuid,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<Vec<String>, AppError> {
+    let holidays = holiday_repo::get_observed(
+        company_id,
+        Some(location_id),
+        start_time.date_naive(),
+        end_time.date_naive().max(start_time.date_naive()),
+    )
+    .await?;
+
+    Ok(holidays
+        .into_iter()
+        .filter(|observed| observed.holiday.is_closed)
+        .map(|observed| {
+            format!(
+                "The location is closed on {} for {}",
+                observed.date, observed.holiday.name
+            )
+        })
+        .collect())
+}
+
+/// Import holidays from an iCalendar file. Events imported before under the same UID are
+/// refreshed rather than added again. Nothing is written with `dry_run`.
+pub async fn import(
+    company_id: Uuid,
+    user_id: Uuid,
+    ics: &str,
+    query: &HolidayImportQuery,
+    req_info: &RequestInfo,
+) -> Result<HolidayImportResponse, AppError> {
+    validate_location(company_id, query.location_id).await?;
+    if let Some(pay_multiplier) = &query.pay_multiplier {
+        validate_pay_multiplier(pay_multiplier)?;
+    }
+
+    let holidays = parse_ics(ics)?;
+    if holidays.is_empty() {
+        return Err(AppError::BadRequest(
+            "The calendar does not contain any events".to_string(),
+        ));
+    }
+    if holidays.len() > MAX_IMPORT_HOLIDAYS {
+        return Err(AppError::BadRequest(format!(
+            "An import can contain at most {} holidays",
+            MAX_IMPORT_HOLIDAYS
+        )));
+    }
+
+    let dry_run = query.dry_run.unwrap_or(false);
+    if dry_run {
+        return Ok(HolidayImportResponse {
+            dry_run,
+            total: holidays.len(),
+            created: 0,
+            updated: 0,
+            holidays,
+        });
+    }
+
+    let location_id = query.location_id;
+    let is_closed = query.is_closed.unwrap_or(true);
+    let pay_multiplier = query
+        .pay_multiplier
+        .clone()
+        .unwrap_or_else(|| BigDecimal::from(1));
+    let req_info = req_info.clone();
+
+    let holidays = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let mut imported = Vec::with_capacity(holidays.len());
+            for mut holiday in holidays {
+                let existing =
+                    holiday_repo::find_imported(tx, company_id, location_id, &holiday.uid).await?;
+                let saved = match existing {
+                    Some(existing) => {
+                        holiday_repo::update_imported(tx, existing.id, &holiday).await?
+                    }
+                    None => {
+                        holiday.created = true;
+                        holiday_repo::create_imported(
+                            tx,
+                            company_id,
+                            location_id,
+                            &holiday,
+                            is_closed,
+                            &pay_multiplier,
+                        )
+                        .await?
+                    }
+                };
+                holiday.holiday_id = Some(saved.id);
+
+                activity_logger::log_activity(
+                    tx,
+                    company_id,
+                    Some(user_id),
+                    ActivityType::SCHEDULE_MANAGEMENT.to_string(),
+                    EntityType::HOLIDAY.to_string(),
+                    saved.id,
+                    if holiday.created {
+                        Action::CREATED.to_string()
+                    } else {
+                        Action::UPDATED.to_string()
+                    },
+                    format!("Holiday '{}' imported by user {}", saved.name, user_id),
+                    Some(activity_logger::metadata(vec![
+                        ("holiday_date", saved.holiday_date.to_string()),
+                        ("source_uid", holiday.uid.clone()),
+                    ])),
+                    &req_info,
+                )
+                .await?;
+
+                imported.push(holiday);
+            }
+            Ok(imported)
+        })
+    })
+    .await?;
+
+    let created = holidays.iter().filter(|holiday| holiday.created).count();
+    Ok(HolidayImportResponse {
+        dry_run,
+        total: holidays.len(),
+        created,
+        updated: holidays.len() - created,
+        holidays,
+    })
+}
+
+/// Read the events of an iCalendar file as holidays on their start date. Yearly rules make
+/// a holiday recurring; other rules are not supported. Events without a UID are keyed by
+/// their date and name.
+pub fn parse_ics(ics: &str) -> Result<Vec<ImportedHoliday>, AppError> {
+    // Lines starting with a space or tab continue the previous one
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (index, line) in ics.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continued), Some((_, previous))) => previous.push_str(continued),
+            _ if line.trim().is_empty() => {}
+            _ => lines.push((index + 1, line.to_string())),
+        }
+    }
+
+    if lines.first().map(|(_, line)| line.as_str()) != Some("BEGIN:VCALENDAR") {
+        return Err(AppError::BadRequest(
+            "The file is not an iCalendar file".to_string(),
+        ));
+    }
+
+    let mut holidays = Vec::new();
+    let mut event: Option<ParsedEvent> = None;
+    for (line_number, line) in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(AppError::BadRequest(format!(
+                "Invalid line {}: expected NAME:VALUE",
+                line_number
+            )));
+        };
+        let name = name.split_once(';').map_or(name, |(name, _)| name);
+
+        match (name.to_ascii_uppercase().as_str(), event.as_mut()) {
+            ("BEGIN", None) if value == "VEVENT" => {
+                event = Some(ParsedEvent {
+                    start_line: line_number,
+                    ..Default::default()
+                });
+            }
+            ("END", Some(_)) if value == "VEVENT" => {
+                let event = event.take().unwrap();
+                let date = event.date.ok_or_else(|| {
+                    AppError::BadRequest(format!(
+                        "The event starting on line {} has no DTSTART",
+                        event.start_line
+                    ))
+                })?;
+                let summary = event
+                    .summary
+                    .filter(|summary| !summary.trim().is_empty())
+                    .unwrap_or_else(|| "Holiday".to_string());
+                holidays.push(ImportedHoliday {
+                    uid: event.uid.unwrap_or_else(|| format!("{}-{}", date, summary)),
+                    name: summary.chars().take(100).collect(),
+                    holiday_date: date,
+                    recurring: event.recurring,
+                    holiday_id: None,
+                    created: false,
+                });
+            }
+            ("UID", Some(event)) => event.uid = Some(value.chars().take(255).collect()),
+            ("SUMMARY", Some(event)) => event.summary = Some(unescape_text(value)),
+            ("DTSTART", Some(event)) => {
+                let day = value.get(..8).unwrap_or(value);
+                event.date = Some(NaiveDate::parse_from_str(day, "%Y%m%d").map_err(|_| {
+                    AppError::BadRequest(format!(
+                        "Invalid DTSTART '{}' on line {}",
+                        value, line_number
+                    ))
+                })?);
+            }
+            ("RRULE", Some(event)) => {
+                if !value.to_ascii_uppercase().contains("FREQ=YEARLY") {
+                    return Err(AppError::BadRequest(format!(
+                        "Unsupported RRULE '{}' on line {}; only yearly holidays can repeat",
+                        value, line_number
+                    )));
+                }
+                event.recurring = true;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(holidays)
+}
+
+/// The fields read so far from the VEVENT being parsed
+#[derive(Default)]
+struct ParsedEvent {
+    start_line: usize,
+    uid: Option<String>,
+    summary: Option<String>,
+    date: Option<NaiveDate>,
+    recurring: bool,
+}
+
+/// Undo iCalendar text escaping
+fn unescape_text(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n' | 'N') => text.push(' '),
+                Some(escaped) => text.push(escaped),
+                None => {}
+            }
+        } else {
+            text.push(c);
+        }
+    }
+    text.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ics() {
+        let ics = "BEGIN:VCALENDAR\r\n\
+                   VERSION:2.0\r\n\
+                   BEGIN:VEVENT\r\n\
+                   UID:christmas@example.com\r\n\
+                   DTSTART;VALUE=DATE:20261225\r\n\
+                   RRULE:FREQ=YEARLY\r\n\
+                   SUMMARY:Christmas Day\r\n\
+                   END:VEVENT\r\n\
+                   BEGIN:VEVENT\r\n\
+                   DTSTART:20260907T000000Z\r\n\
+                   SUMMARY:Labour Day\\, observed\r\n\
+                   \x20 (first Monday)\r\n\
+                   END:VEVENT\r\n\
+                   END:VCALENDAR\r\n";
+
+        let holidays = parse_ics(ics).unwrap();
+        assert_eq!(holidays.len(), 2);
+        assert_eq!(holidays[0].uid, "christmas@example.com");
+        assert_eq!(holidays[0].name, "Christmas Day");
+        assert_eq!(
+            holidays[0].holiday_date,
+            NaiveDate::from_ymd_opt(2026, 12, 25).unwrap()
+        );
+        assert!(holidays[0].recurring);
+        assert_eq!(holidays[1].name, "Labour Day, observed (first Monday)");
+        assert_eq!(
+            holidays[1].uid,
+            "2026-09-07-Labour Day, observed (first Monday)"
+        );
+        assert!(!holidays[1].recurring);
+
+        // Monthly rules and events without a start are rejected
+        let monthly = ics.replace("FREQ=YEARLY", "FREQ=MONTHLY");
+        assert!(parse_ics(&monthly).is_err());
+        let no_start = ics.replace("DTSTART:20260907T000000Z\r\n", "");
+        assert!(parse_ics(&no_start).is_err());
+        assert!(parse_ics("not a calendar").is_err());
+    }
+}
@@ -0,0 +1,172 @@
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+use crate::config::config;
+use crate::database::{
+    models::{
+        Action, CreateImpersonationInput, ImpersonateInput, ImpersonationInfo,
+        ImpersonationResponse, ImpersonationSession, Permission,
+    },
+    repositories::{
+        company as company_repo, impersonation as impersonation_repo, role as role_repo,
+        user as user_repo,
+    },
+    transaction::DatabaseTransaction,
+};
+use crate::error::AppError;
+use crate::middleware::request_info::RequestInfo;
+use crate::services::{activity_logger, auth, user_context::UserContext};
+
+/// Start acting as another member of the admin's company. Members who can impersonate
+/// cannot be impersonated, and an impersonation token cannot be used to start another one.
+pub async fn start(
+    ctx: &UserContext,
+    user_id: Uuid,
+    input: ImpersonateInput,
+    req_info: &RequestInfo,
+) -> Result<ImpersonationResponse, AppError> {
+    ctx.requires_not_impersonating()?;
+    if ctx.is_api_key() {
+        return Err(AppError::Forbidden(
+            "API keys cannot impersonate users".to_string(),
+        ));
+    }
+    let company_id = ctx.strict_company_id()?;
+    let admin_id = ctx.user_id();
+
+    if user_id == admin_id {
+        return Err(AppError::BadRequest(
+            "You cannot impersonate yourself".to_string(),
+        ));
+    }
+
+    let company = company_repo::find_user_company_info_by_id(user_id, company_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    let permissions = role_repo::get_member_permissions(user_id, company_id).await?;
+    if permissions
+        .iter()
+        .any(|permission| permission == Permission::USERS_IMPERSONATE)
+    {
+        return Err(AppError::Forbidden(
+            "Members who can impersonate cannot be impersonated".to_string(),
+        ));
+    }
+    let user = user_repo::find_by_id(user_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    let reason = input
+        .reason
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty());
+    let create_input = CreateImpersonationInput {
+        company_id,
+        admin_user_id: admin_id,
+        target_user_id: user_id,
+        reason,
+        user_agent: req_info.user_agent.clone(),
+        ip_address: req_info.ip_address.clone(),
+        expires_at: Utc::now() + Duration::minutes(config().impersonation_minutes),
+    };
+
+    let req_info = req_info.clone();
+    let session = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let session = impersonation_repo::create_session(tx, &create_input).await?;
+
+            let metadata = activity_logger::metadata(vec![
+                ("impersonation_id", session.id.to_string()),
+                ("reason", session.reason.clone().unwrap_or_default()),
+                ("expires_at", session.expires_at.to_rfc3339()),
+            ]);
+            activity_logger::log_user_activity(
+                tx,
+                company_id,
+                Some(admin_id),
+                user_id,
+                Action::IMPERSONATION_STARTED,
+                format!("Started impersonating user with ID {}", user_id),
+                Some(metadata),
+                &req_info,
+            )
+            .await?;
+
+            Ok(session)
+        })
+    })
+    .await?;
+
+    // The admin's second factor carries over to the user they act as
+    let token =
+        auth::impersonation_token(&user, &company, &session, ctx.mfa_verified).map_err(|e| {
+            log::error!("Failed to generate impersonation token: {}", e);
+            AppError::internal_server_error_message(e.to_string())
+        })?;
+
+    Ok(ImpersonationResponse {
+        token,
+        user,
+        session,
+    })
+}
+
+/// End the impersonation the request was made with; its token stops working immediately
+pub async fn end(
+    ctx: &UserContext,
+    req_info: &RequestInfo,
+) -> Result<ImpersonationSession, AppError> {
+    let session_id = ctx
+        .impersonation
+        .as_ref()
+        .map(|session| session.id)
+        .ok_or_else(|| AppError::BadRequest("You are not impersonating a user".to_string()))?;
+    let company_id = ctx.strict_company_id()?;
+    let user_id = ctx.user_id();
+
+    let req_info = req_info.clone();
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let session = impersonation_repo::end_session(tx, session_id)
+                .await?
+                .ok_or(AppError::Unauthorized)?;
+
+            // Attributed to the admin behind the token
+            let metadata =
+                activity_logger::metadata(vec![("impersonation_id", session.id.to_string())]);
+            activity_logger::log_user_activity(
+                tx,
+                company_id,
+                Some(session.admin_user_id),
+                user_id,
+                Action::IMPERSONATION_ENDED,
+                format!("Stopped impersonating user with ID {}", user_id),
+                Some(metadata),
+                &req_info,
+            )
+            .await?;
+
+            Ok(session)
+        })
+    })
+    .await
+}
+
+/// Who is behind the current impersonation, for clients to show a banner
+pub async fn info(ctx: &UserContext) -> Result<Option<ImpersonationInfo>, AppError> {
+    let Some(session) = ctx.impersonation.as_ref() else {
+        return Ok(None);
+    };
+
+    let admin = user_repo::find_by_id(session.admin_user_id)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    Ok(Some(ImpersonationInfo {
+        session_id: session.id,
+        admin_user_id: admin.id,
+        admin_name: admin.name,
+        admin_email: admin.email,
+        expires_at: session.expires_at,
+    }))
+}
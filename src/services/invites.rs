@@ -0,0 +1,288 @@
+use std::collections::HashSet;
+
+use uuid::Uuid;
+
+use crate::config::config;
+use crate::database::{
+    models::{
+        Action, ActivityType, BulkInviteResponse, BulkInviteRowResult, CompanyRole, EntityType,
+        InviteToken, Team,
+    },
+    repositories::{company as company_repo, invite as invite_repo, team as team_repo},
+    transaction::DatabaseTransaction,
+};
+use crate::error::AppError;
+use crate::middleware::request_info::RequestInfo;
+use crate::services::{activity_logger, mailer, user_context::UserContext};
+
+/// Most invite rows accepted in one bulk upload
+pub const MAX_BULK_INVITE_ROWS: usize = 500;
+
+/// Link the invited person follows to accept or reject the invite
+pub fn invite_link(invite: &InviteToken) -> String {
+    format!("{}/auth/invite/{}", config().client_base_url, invite.token)
+}
+
+/// Email an invite link to the invited address
+pub async fn send_invite(invite: &InviteToken, inviter_name: &str) {
+    let body = format!(
+        "Hi,\n\n\
+         {} invited you to join {} on ShiftLinkr as {}.\n\n\
+         Accept the invite here: {}\n\n\
+         The link expires on {}.",
+        inviter_name,
+        invite.company_name,
+        invite.role,
+        invite_link(invite),
+        invite.expires_at.format("%Y-%m-%d %H:%M UTC"),
+    );
+    mailer::send(
+        &invite.email,
+        &format!("You're invited to join {}", invite.company_name),
+        &body,
+    )
+    .await;
+}
+
+/// A CSV line that passed validation
+struct ValidRow {
+    line: usize,
+    email: String,
+    role: CompanyRole,
+    team_id: Option<Uuid>,
+}
+
+/// Invite everyone listed in a CSV of `email,role,team` lines. The header line is optional,
+/// role defaults to employee and team may be a team id or name. Lines are validated on their
+/// own; valid lines are invited even if others fail, and each line gets a result.
+pub async fn bulk_invite(
+    ctx: &UserContext,
+    csv: &str,
+    req_info: &RequestInfo,
+) -> Result<BulkInviteResponse, AppError> {
+    let company_id = ctx.strict_company_id()?;
+    let inviter_id = ctx.user_id();
+
+    let mut lines: Vec<(usize, Result<Vec<String>, String>)> = csv
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| (index + 1, split_csv_line(line)))
+        .collect();
+    let has_header = lines.first().is_some_and(|(_, fields)| {
+        fields
+            .as_ref()
+            .is_ok_and(|fields| fields[0].eq_ignore_ascii_case("email"))
+    });
+    if has_header {
+        lines.drain(..1);
+    }
+    if lines.is_empty() {
+        return Err(AppError::BadRequest(
+            "The CSV does not contain any invites".to_string(),
+        ));
+    }
+    if lines.len() > MAX_BULK_INVITE_ROWS {
+        return Err(AppError::BadRequest(format!(
+            "A bulk invite can contain at most {} rows",
+            MAX_BULK_INVITE_ROWS
+        )));
+    }
+
+    let teams = team_repo::get_all_teams_for_company(company_id).await?;
+    let members: HashSet<String> = company_repo::get_company_employees(company_id)
+        .await?
+        .into_iter()
+        .map(|employee| employee.email.to_lowercase())
+        .collect();
+    let emails: Vec<String> = lines
+        .iter()
+        .filter_map(|(_, fields)| fields.as_ref().ok())
+        .map(|fields| fields[0].to_lowercase())
+        .collect();
+    let pending: HashSet<String> = invite_repo::get_pending_invite_emails(company_id, &emails)
+        .await?
+        .into_iter()
+        .collect();
+
+    let mut results = Vec::new();
+    let mut valid_rows = Vec::new();
+    let mut seen = HashSet::new();
+    for (line, fields) in lines {
+        let email = fields
+            .as_ref()
+            .map(|fields| fields[0].clone())
+            .unwrap_or_default();
+        let row = fields
+            .and_then(|fields| validate_row(line, fields, &teams))
+            .and_then(|row| {
+                let key = row.email.to_lowercase();
+                if members.contains(&key) {
+                    Err("User is already a member of the company".to_string())
+                } else if pending.contains(&key) {
+                    Err("User already has a pending invite".to_string())
+                } else if !seen.insert(key) {
+                    Err("Email appears more than once in the file".to_string())
+                } else {
+                    Ok(row)
+                }
+            });
+
+        match row {
+            Ok(row) => valid_rows.push(row),
+            Err(error) => results.push(BulkInviteRowResult {
+                line,
+                email,
+                success: false,
+                error: Some(error),
+                invite_link: None,
+                expires_at: None,
+            }),
+        }
+    }
+
+    let req_info = req_info.clone();
+    let created = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let mut created = Vec::new();
+            for row in valid_rows {
+                let invite = invite_repo::create_invite_token(
+                    tx,
+                    &row.email,
+                    inviter_id,
+                    row.role.clone(),
+                    company_id,
+                    row.team_id,
+                )
+                .await?;
+
+                let metadata = activity_logger::metadata(vec![
+                    ("email", row.email.clone()),
+                    ("role", row.role.to_string()),
+                    (
+                        "team_id",
+                        row.team_id
+                            .map_or_else(|| "None".to_string(), |id| id.to_string()),
+                    ),
+                    ("expires_at", invite.expires_at.to_rfc3339()),
+                    ("bulk", "true".to_string()),
+                ]);
+                activity_logger::log_activity(
+                    tx,
+                    company_id,
+                    Some(inviter_id),
+                    ActivityType::USER_MANAGEMENT.to_string(),
+                    EntityType::INVITE.to_string(),
+                    invite.id,
+                    Action::INVITED.to_string(),
+                    format!("Invite created for email {}", row.email),
+                    Some(metadata),
+                    &req_info,
+                )
+                .await?;
+
+                created.push((row.line, invite));
+            }
+            Ok(created)
+        })
+    })
+    .await?;
+
+    for (line, invite) in &created {
+        send_invite(invite, &ctx.user.name).await;
+        results.push(BulkInviteRowResult {
+            line: *line,
+            email: invite.email.clone(),
+            success: true,
+            error: None,
+            invite_link: Some(invite_link(invite)),
+            expires_at: Some(invite.expires_at),
+        });
+    }
+    results.sort_by_key(|result| result.line);
+
+    Ok(BulkInviteResponse {
+        invited: created.len(),
+        failed: results.len() - created.len(),
+        results,
+    })
+}
+
+fn validate_row(line: usize, fields: Vec<String>, teams: &[Team]) -> Result<ValidRow, String> {
+    if fields.len() > 3 {
+        return Err("Expected at most 3 columns: email, role, team".to_string());
+    }
+    let mut fields = fields.into_iter();
+
+    let email = fields.next().unwrap_or_default();
+    if email.is_empty() || !email.contains('@') {
+        return Err("Invalid email address".to_string());
+    }
+
+    let role = match fields.next().filter(|role| !role.is_empty()) {
+        Some(role) => role
+            .parse::<CompanyRole>()
+            .map_err(|_| format!("Unknown role '{}'", role))?,
+        None => CompanyRole::Employee,
+    };
+
+    let team_id = match fields.next().filter(|team| !team.is_empty()) {
+        Some(team) => Some(find_team(&team, teams)?),
+        None => None,
+    };
+
+    Ok(ValidRow {
+        line,
+        email,
+        role,
+        team_id,
+    })
+}
+
+/// Resolve a team by id or, failing that, by its name
+pub(crate) fn find_team(team: &str, teams: &[Team]) -> Result<Uuid, String> {
+    if let Ok(team_id) = team.parse::<Uuid>() {
+        return teams
+            .iter()
+            .find(|t| t.id == team_id)
+            .map(|t| t.id)
+            .ok_or_else(|| format!("Team {} does not belong to this company", team_id));
+    }
+
+    let matches: Vec<&Team> = teams
+        .iter()
+        .filter(|t| t.name.eq_ignore_ascii_case(team))
+        .collect();
+    match matches.as_slice() {
+        [team] => Ok(team.id),
+        [] => Err(format!("Unknown team '{}'", team)),
+        _ => Err(format!("Team name '{}' is ambiguous; use its id", team)),
+    }
+}
+
+/// Split a CSV line into trimmed fields. Fields may be quoted to contain commas, with `""`
+/// for a literal quote.
+pub(crate) fn split_csv_line(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field).trim().to_string()),
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err("Unterminated quoted field".to_string());
+    }
+    fields.push(field.trim().to_string());
+
+    Ok(fields)
+}
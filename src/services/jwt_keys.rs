@@ -0,0 +1,276 @@
+use std::{collections::HashMap, fs, sync::OnceLock};
+
+use anyhow::{Context, Result, anyhow, bail};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, decode_header, encode,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+};
+use serde::{Serialize, de::DeserializeOwned};
+use simple_asn1::{ASN1Block, from_der};
+
+use crate::config::{Config, DEFAULT_JWT_SECRET, config};
+
+/// A key that tokens may be verified with, selected by the `kid` header
+struct VerificationKey {
+    algorithm: Algorithm,
+    decoding_key: DecodingKey,
+    jwk: Option<Jwk>, // Only asymmetric keys are published
+}
+
+/// The active signing key plus every key that is still accepted for verification
+pub struct KeyRing {
+    signing_kid: String,
+    signing_algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    verification_keys: HashMap<String, VerificationKey>,
+    legacy_key: Option<DecodingKey>, // HS256 key for tokens issued before `kid` was set
+}
+
+static KEY_RING: OnceLock<KeyRing> = OnceLock::new();
+
+/// Build the global key ring from the given config, failing on unreadable or invalid keys
+pub fn init_key_ring(config: &Config) -> Result<&'static KeyRing> {
+    if let Some(key_ring) = KEY_RING.get() {
+        return Ok(key_ring);
+    }
+
+    let key_ring = KeyRing::from_config(config)?;
+    Ok(KEY_RING.get_or_init(|| key_ring))
+}
+
+/// Get the global key ring, building it from the global config on first use
+pub fn key_ring() -> Result<&'static KeyRing> {
+    init_key_ring(config())
+}
+
+impl KeyRing {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let signing_algorithm = parse_algorithm(&config.jwt_algorithm)?;
+
+        let (encoding_key, current_key) = match signing_algorithm {
+            Algorithm::HS256 => (
+                EncodingKey::from_secret(config.jwt_secret.as_bytes()),
+                load_verification_key(&config.jwt_key_id, signing_algorithm, &config.jwt_secret)?,
+            ),
+            _ => {
+                let private_key_path = config.jwt_private_key_path.as_ref().ok_or_else(|| {
+                    anyhow!(
+                        "JWT_PRIVATE_KEY_PATH is required for {:?}",
+                        signing_algorithm
+                    )
+                })?;
+                let public_key_path = config.jwt_public_key_path.as_ref().ok_or_else(|| {
+                    anyhow!(
+                        "JWT_PUBLIC_KEY_PATH is required for {:?}",
+                        signing_algorithm
+                    )
+                })?;
+
+                let private_pem = fs::read(private_key_path)
+                    .with_context(|| format!("Failed to read {}", private_key_path))?;
+                let encoding_key = match signing_algorithm {
+                    Algorithm::RS256 => EncodingKey::from_rsa_pem(&private_pem)?,
+                    _ => EncodingKey::from_ed_pem(&private_pem)?,
+                };
+
+                (
+                    encoding_key,
+                    load_verification_key(&config.jwt_key_id, signing_algorithm, public_key_path)?,
+                )
+            }
+        };
+
+        let mut verification_keys = HashMap::new();
+        verification_keys.insert(config.jwt_key_id.clone(), current_key);
+
+        for previous in &config.jwt_previous_keys {
+            if verification_keys.contains_key(&previous.kid) {
+                bail!("Duplicate JWT key id: {}", previous.kid);
+            }
+            let algorithm = parse_algorithm(&previous.algorithm)?;
+            verification_keys.insert(
+                previous.kid.clone(),
+                load_verification_key(&previous.kid, algorithm, &previous.key)?,
+            );
+        }
+
+        // Tokens without a `kid` predate rotation support and were signed with the HS256 secret.
+        // Once signing has moved off HS256 the well-known default secret must not be trusted.
+        let legacy_key =
+            if signing_algorithm == Algorithm::HS256 || config.jwt_secret != DEFAULT_JWT_SECRET {
+                Some(DecodingKey::from_secret(config.jwt_secret.as_bytes()))
+            } else {
+                None
+            };
+
+        Ok(KeyRing {
+            signing_kid: config.jwt_key_id.clone(),
+            signing_algorithm,
+            encoding_key,
+            verification_keys,
+            legacy_key,
+        })
+    }
+
+    /// Sign claims with the active key, tagging the token with its `kid`
+    pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String> {
+        let mut header = Header::new(self.signing_algorithm);
+        header.kid = Some(self.signing_kid.clone());
+
+        Ok(encode(&header, claims, &self.encoding_key)?)
+    }
+
+    /// Verify a token against the key named by its `kid` header
+    pub fn decode<T: DeserializeOwned>(&self, token: &str) -> Result<T> {
+        let (algorithm, decoding_key) = self.verification_key_for(token)?;
+
+        // The algorithm comes from our key, never from the token header
+        Ok(decode::<T>(token, decoding_key, &Validation::new(algorithm))?.claims)
+    }
+
+    /// Verify a signed record that never expires, such as an activity log checkpoint
+    pub fn decode_without_expiry<T: DeserializeOwned>(&self, token: &str) -> Result<T> {
+        let (algorithm, decoding_key) = self.verification_key_for(token)?;
+
+        let mut validation = Validation::new(algorithm);
+        validation.validate_exp = false;
+        validation.required_spec_claims.clear();
+        Ok(decode::<T>(token, decoding_key, &validation)?.claims)
+    }
+
+    fn verification_key_for(&self, token: &str) -> Result<(Algorithm, &DecodingKey)> {
+        let header = decode_header(token)?;
+
+        match header.kid {
+            Some(kid) => {
+                let key = self
+                    .verification_keys
+                    .get(&kid)
+                    .ok_or_else(|| anyhow!("Unknown signing key: {}", kid))?;
+                Ok((key.algorithm, &key.decoding_key))
+            }
+            None => Ok((
+                Algorithm::HS256,
+                self.legacy_key
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("Token is missing a key id"))?,
+            )),
+        }
+    }
+
+    /// Public keys for other services to verify tokens with
+    pub fn jwks(&self) -> JwkSet {
+        let mut keys: Vec<Jwk> = self
+            .verification_keys
+            .values()
+            .filter_map(|key| key.jwk.clone())
+            .collect();
+        keys.sort_by(|a, b| a.common.key_id.cmp(&b.common.key_id));
+
+        JwkSet { keys }
+    }
+}
+
+fn parse_algorithm(name: &str) -> Result<Algorithm> {
+    match name {
+        "HS256" => Ok(Algorithm::HS256),
+        "RS256" => Ok(Algorithm::RS256),
+        "EdDSA" => Ok(Algorithm::EdDSA),
+        _ => bail!(
+            "Unsupported JWT algorithm: {} (expected HS256, RS256 or EdDSA)",
+            name
+        ),
+    }
+}
+
+/// Load a verification key; `key` is the secret for HS256 and a public key PEM path otherwise
+fn load_verification_key(kid: &str, algorithm: Algorithm, key: &str) -> Result<VerificationKey> {
+    if algorithm == Algorithm::HS256 {
+        return Ok(VerificationKey {
+            algorithm,
+            decoding_key: DecodingKey::from_secret(key.as_bytes()),
+            jwk: None,
+        });
+    }
+
+    let pem_bytes = fs::read(key).with_context(|| format!("Failed to read {}", key))?;
+    let (decoding_key, params) = match algorithm {
+        Algorithm::RS256 => {
+            let (n, e) = rsa_public_components(&pem_bytes)?;
+            (
+                DecodingKey::from_rsa_pem(&pem_bytes)?,
+                AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n: URL_SAFE_NO_PAD.encode(n),
+                    e: URL_SAFE_NO_PAD.encode(e),
+                }),
+            )
+        }
+        _ => (
+            DecodingKey::from_ed_pem(&pem_bytes)?,
+            AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(spki_public_key(&pem_bytes)?),
+            }),
+        ),
+    };
+
+    let key_algorithm = match algorithm {
+        Algorithm::RS256 => KeyAlgorithm::RS256,
+        _ => KeyAlgorithm::EdDSA,
+    };
+
+    Ok(VerificationKey {
+        algorithm,
+        decoding_key,
+        jwk: Some(Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm),
+                key_id: Some(kid.to_string()),
+                ..Default::default()
+            },
+            algorithm: params,
+        }),
+    })
+}
+
+/// Extract the raw key bytes from a SubjectPublicKeyInfo ("PUBLIC KEY") PEM
+fn spki_public_key(pem_bytes: &[u8]) -> Result<Vec<u8>> {
+    let pem = pem::parse(pem_bytes)?;
+    if pem.tag() != "PUBLIC KEY" {
+        bail!("Expected a PUBLIC KEY PEM, found {}", pem.tag());
+    }
+
+    match from_der(pem.contents())?.as_slice() {
+        [ASN1Block::Sequence(_, items)] => match items.as_slice() {
+            [_, ASN1Block::BitString(_, _, key)] => Ok(key.clone()),
+            _ => bail!("Malformed public key"),
+        },
+        _ => bail!("Malformed public key"),
+    }
+}
+
+/// Extract the RSA modulus and exponent (big-endian) from a public key PEM
+fn rsa_public_components(pem_bytes: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    let pem = pem::parse(pem_bytes)?;
+    let rsa_der = match pem.tag() {
+        "RSA PUBLIC KEY" => pem.contents().to_vec(),
+        _ => spki_public_key(pem_bytes)?,
+    };
+
+    match from_der(&rsa_der)?.as_slice() {
+        [ASN1Block::Sequence(_, items)] => match items.as_slice() {
+            [ASN1Block::Integer(_, n), ASN1Block::Integer(_, e)] => {
+                Ok((n.to_bytes_be().1, e.to_bytes_be().1))
+            }
+            _ => bail!("Malformed RSA public key"),
+        },
+        _ => bail!("Malformed RSA public key"),
+    }
+}
@@ -0,0 +1,9 @@
+//! Outgoing email to users.
+//!
+//! No mail provider is configured yet, so messages are written to the application log
+//! where they can be picked up in development.
+
+/// Send a plain text email
+pub async fn send(to: &str, subject: &str, body: &str) {
+    log::info!("📧 Email to {}: {}\n{}", to, subject, body);
+}
@@ -0,0 +1,153 @@
+use uuid::Uuid;
+
+use crate::database::{
+    models::{ManagerScopeInput, Permission, ScopeFilter, Shift},
+    repositories::{
+        location as location_repo, manager_scope as manager_scope_repo, team as team_repo,
+    },
+};
+use crate::error::AppError;
+use crate::services::user_context::UserContext;
+
+/// Where a manager's authority applies within their company
+#[derive(Debug, Clone)]
+pub enum ManagerScope {
+    CompanyWide,
+    Limited {
+        filter: ScopeFilter,
+        /// Members of the scoped teams, whose requests the manager may act on
+        user_ids: Vec<Uuid>,
+    },
+}
+
+/// Load the scope of the current user. Members who may bypass scopes or have no scope
+/// assignments act company-wide.
+pub async fn load(ctx: &UserContext) -> Result<ManagerScope, AppError> {
+    if ctx.has_permission(Permission::SCOPE_BYPASS) {
+        return Ok(ManagerScope::CompanyWide);
+    }
+
+    let company_id = ctx.strict_company_id()?;
+    let scopes = manager_scope_repo::get_member_scopes(ctx.user_id(), company_id).await?;
+    if scopes.is_empty() {
+        return Ok(ManagerScope::CompanyWide);
+    }
+
+    let location_ids: Vec<Uuid> = scopes
+        .iter()
+        .filter_map(|scope| scope.location_id)
+        .collect();
+    let team_ids: Vec<Uuid> = scopes.iter().filter_map(|scope| scope.team_id).collect();
+    let team_ids = manager_scope_repo::get_scoped_team_ids(&location_ids, &team_ids).await?;
+    let user_ids = manager_scope_repo::get_team_member_ids(&team_ids).await?;
+
+    Ok(ManagerScope::Limited {
+        filter: ScopeFilter {
+            location_ids,
+            team_ids,
+        },
+        user_ids,
+    })
+}
+
+impl ManagerScope {
+    /// The filter to apply to lists, or `None` when nothing needs filtering
+    pub fn filter(&self) -> Option<&ScopeFilter> {
+        match self {
+            ManagerScope::CompanyWide => None,
+            ManagerScope::Limited { filter, .. } => Some(filter),
+        }
+    }
+
+    pub fn includes_shift(&self, location_id: Uuid, team_id: Option<Uuid>) -> bool {
+        self.filter()
+            .is_none_or(|filter| filter.includes_shift(location_id, team_id))
+    }
+
+    pub fn includes_team(&self, team_id: Uuid) -> bool {
+        self.filter()
+            .is_none_or(|filter| filter.team_ids.contains(&team_id))
+    }
+
+    pub fn includes_user(&self, user_id: Uuid) -> bool {
+        match self {
+            ManagerScope::CompanyWide => true,
+            ManagerScope::Limited { user_ids, .. } => user_ids.contains(&user_id),
+        }
+    }
+
+    pub fn requires_shift_at(
+        &self,
+        location_id: Uuid,
+        team_id: Option<Uuid>,
+    ) -> Result<(), AppError> {
+        if !self.includes_shift(location_id, team_id) {
+            return Err(AppError::PermissionDenied(
+                "Shift is outside the locations and teams you manage".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn requires_shift(&self, shift: &Shift) -> Result<(), AppError> {
+        self.requires_shift_at(shift.location_id, shift.team_id)
+    }
+
+    pub fn requires_team(&self, team_id: Uuid) -> Result<(), AppError> {
+        if !self.includes_team(team_id) {
+            return Err(AppError::PermissionDenied(
+                "Team is outside the locations and teams you manage".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn requires_user(&self, user_id: Uuid) -> Result<(), AppError> {
+        if !self.includes_user(user_id) {
+            return Err(AppError::PermissionDenied(
+                "Employee is outside the locations and teams you manage".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Deduplicate the requested locations and teams and check they belong to the company
+pub async fn validate_input(
+    company_id: Uuid,
+    input: &mut ManagerScopeInput,
+) -> Result<(), AppError> {
+    input.location_ids.sort();
+    input.location_ids.dedup();
+    input.team_ids.sort();
+    input.team_ids.dedup();
+
+    let locations = location_repo::get_locations_by_company(company_id).await?;
+    if let Some(location_id) = input
+        .location_ids
+        .iter()
+        .find(|id| !locations.iter().any(|location| location.id == **id))
+    {
+        return Err(AppError::BadRequest(format!(
+            "Location {} does not belong to this company",
+            location_id
+        )));
+    }
+
+    let teams = team_repo::get_all_teams_for_company(company_id).await?;
+    if let Some(team_id) = input
+        .team_ids
+        .iter()
+        .find(|id| !teams.iter().any(|team| team.id == **id))
+    {
+        return Err(AppError::BadRequest(format!(
+            "Team {} does not belong to this company",
+            team_id
+        )));
+    }
+
+    Ok(())
+}
@@ -1,3 +1,23 @@
+pub mod account_lockout;
+pub mod activity_archive;
+pub mod activity_chain;
+pub mod activity_log;
 pub mod activity_logger;
+pub mod api_keys;
 pub mod auth;
+pub mod email_verification;
+pub mod employee_import;
+pub mod holidays;
+pub mod impersonation;
+pub mod invites;
+pub mod jwt_keys;
+pub mod mailer;
+pub mod manager_scope;
+pub mod personal_data;
+pub mod pto_accrual;
+pub mod roles;
+pub mod sso;
+pub mod stats;
+pub mod time_off;
+pub mod two_factor;
 pub mod user_context;
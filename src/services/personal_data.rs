@@ -0,0 +1,180 @@
+use std::path::Path;
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use bcrypt::{DEFAULT_COST, hash, verify};
+use chrono::Utc;
+use rand::Rng;
+use uuid::Uuid;
+
+use crate::config::config;
+use crate::database::{
+    models::{Action, ActivityType, EntityType, EraseAccountInput, PersonalDataExport, User},
+    repositories::{
+        activity as activity_repo, attendance as attendance_repo,
+        personal_data as personal_data_repo, pto_balance as pto_repo,
+        shift_claim as shift_claim_repo,
+    },
+    transaction::DatabaseTransaction,
+};
+use crate::error::AppError;
+use crate::middleware::request_info::RequestInfo;
+use crate::services::{
+    activity_archive, activity_chain, activity_logger, mailer, user_context::UserContext,
+};
+
+/// Name left on an erased account
+pub const ERASED_USER_NAME: &str = "Erased user";
+
+/// Personal data may only be exported or erased by the person themselves
+fn requires_account_owner(ctx: &UserContext) -> Result<(), AppError> {
+    ctx.requires_not_impersonating()?;
+    if ctx.is_api_key() {
+        return Err(AppError::Forbidden(
+            "API keys cannot access personal data".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Collect everything stored about the current user across all of their companies
+pub async fn export(ctx: &UserContext) -> Result<PersonalDataExport, AppError> {
+    requires_account_owner(ctx)?;
+    let user_id = ctx.user_id();
+
+    Ok(PersonalDataExport {
+        exported_at: Utc::now(),
+        profile: ctx.user.clone(),
+        memberships: personal_data_repo::get_memberships(user_id).await?,
+        pto_balance_history: pto_repo::find_history_by_user_id(user_id).await?,
+        shifts: personal_data_repo::get_shifts(user_id).await?,
+        shift_assignments: personal_data_repo::get_shift_assignments(user_id).await?,
+        shift_claims: shift_claim_repo::find_by_user_id(user_id).await?,
+        attendance: attendance_repo::find_by_user_id(user_id).await?,
+        shift_swaps: personal_data_repo::get_shift_swaps(user_id).await?,
+        shift_swap_responses: personal_data_repo::get_shift_swap_responses(user_id).await?,
+        time_off_requests: personal_data_repo::get_time_off_requests(user_id).await?,
+        skills: personal_data_repo::get_skills(user_id).await?,
+        wage_history: personal_data_repo::get_wage_history(user_id).await?,
+        activity: personal_data_repo::get_activity(user_id).await?,
+    })
+}
+
+/// Erase the current user's personal data. The account is anonymized rather than deleted,
+/// so the shifts they worked, their time off and their wage history stay attached to a
+/// (now anonymous) user. Sign-in data, memberships and team, skill and availability rows
+/// are removed, and open requests are cancelled. Returns the company ids the user left.
+pub async fn erase(
+    ctx: &UserContext,
+    input: &EraseAccountInput,
+    req_info: &RequestInfo,
+) -> Result<Vec<Uuid>, AppError> {
+    requires_account_owner(ctx)?;
+    let user = ctx.user.clone();
+
+    let valid_password = verify(&input.password, &user.password_hash).map_err(|_| {
+        log::error!("Failed to verify password");
+        AppError::internal_server_error_message("Failed to verify password")
+    })?;
+    if !valid_password {
+        return Err(AppError::BadRequest("Invalid password".into()));
+    }
+
+    if !personal_data_repo::get_sole_admin_company_ids(user.id)
+        .await?
+        .is_empty()
+    {
+        return Err(AppError::BadRequest(
+            "You are the only admin of a company with other members; make someone else an admin first"
+                .into(),
+        ));
+    }
+
+    let company_ids: Vec<Uuid> = personal_data_repo::get_memberships(user.id)
+        .await?
+        .into_iter()
+        .map(|membership| membership.company_id)
+        .collect();
+
+    let anonymized_email = format!("erased-{}@erased.invalid", user.id);
+    let password_hash = hash(
+        URL_SAFE_NO_PAD.encode(rand::rng().random::<[u8; 32]>()),
+        DEFAULT_COST,
+    )
+    .map_err(|e| AppError::internal_server_error_message(e.to_string()))?;
+
+    // Archived activity is rewritten first and only takes effect with the rest of the erasure
+    let archive_dir = Path::new(&config().activity_archive_dir);
+    let scrubbed_archives =
+        activity_archive::scrub_archives(archive_dir, user.id, &user.email, &anonymized_email)
+            .await?;
+
+    let req_info = req_info.clone();
+    let erased_user = user.clone();
+    let erased_company_ids = company_ids.clone();
+    let archives = scrubbed_archives.clone();
+    let result = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let user = erased_user;
+            for company_id in erased_company_ids {
+                activity_logger::log_activity(
+                    tx,
+                    company_id,
+                    Some(user.id),
+                    ActivityType::USER_MANAGEMENT.to_string(),
+                    EntityType::USER.to_string(),
+                    user.id,
+                    Action::ACCOUNT_ERASED.to_string(),
+                    format!("User {} erased their personal data", user.id),
+                    None,
+                    &req_info,
+                )
+                .await?;
+            }
+
+            let mut redacted =
+                personal_data_repo::scrub_activity(tx, user.id, &user.email, &anonymized_email)
+                    .await?;
+            for archive in archives {
+                activity_repo::update_archive_file(tx, &archive.archive).await?;
+                redacted.extend(
+                    archive
+                        .redacted
+                        .into_iter()
+                        .filter(|activity| activity.chain_seq.is_some()),
+                );
+            }
+            activity_chain::record_redactions(tx, user.id, &redacted).await?;
+            personal_data_repo::close_open_requests(tx, user.id).await?;
+            personal_data_repo::delete_account_data(tx, user.id).await?;
+            personal_data_repo::anonymize_user(
+                tx,
+                user.id,
+                &anonymized_email,
+                ERASED_USER_NAME,
+                &password_hash,
+            )
+            .await?;
+
+            Ok(())
+        })
+    })
+    .await;
+    activity_archive::finish_scrub(archive_dir, &scrubbed_archives, result.is_ok());
+    result?;
+
+    send_erased(&user).await;
+
+    Ok(company_ids)
+}
+
+/// Confirm the erasure to the address the account used to have
+async fn send_erased(user: &User) {
+    let body = format!(
+        "Hi {},\n\n\
+         Your ShiftLinkr account and the personal data tied to it have been erased. \
+         Your employers keep anonymous records of the shifts you worked and your pay history.\n\n\
+         If this wasn't you, contact your administrator.",
+        user.name,
+    );
+    mailer::send(&user.email, "Your ShiftLinkr account was erased", &body).await;
+}
@@ -0,0 +1,590 @@
+use bigdecimal::{BigDecimal, RoundingMode};
+use chrono::{Datelike, Days, Duration, NaiveDate, Utc, Weekday};
+use uuid::Uuid;
+
+use crate::database::{
+    models::{
+        AccrualMethod, AccrualYearStart, Action, PayPeriod, PtoAccrualPolicy,
+        PtoAccrualPolicyInput, PtoAccrualResult, PtoAccrualRun, PtoAccrualRunQuery,
+        PtoAccrualState, PtoAccrualTier, PtoBalanceType, PtoChangeType, PtoLedgerEntryInput,
+        PtoPolicyAssignmentInput, PtoPolicyMember,
+    },
+    repositories::{
+        company as company_repo, location as location_repo, pto_balance as pto_repo,
+        pto_policy as pto_policy_repo, team as team_repo,
+    },
+    transaction::DatabaseTransaction,
+};
+use crate::error::AppError;
+use crate::middleware::request_info::RequestInfo;
+use crate::services::activity_logger;
+
+/// Biweekly pay periods run in two-week cycles from this Monday
+const BIWEEKLY_ANCHOR: NaiveDate = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+/// Hours are kept to this many decimals
+const HOURS_SCALE: i64 = 4;
+
+/// Normalise a policy definition and reject inconsistent settings or clashing names.
+/// `policy_id` is the policy being updated, if any.
+pub async fn validate_input(
+    company_id: Uuid,
+    policy_id: Option<Uuid>,
+    input: &mut PtoAccrualPolicyInput,
+) -> Result<(), AppError> {
+    input.name = input.name.trim().to_string();
+    if input.name.is_empty() {
+        return Err(AppError::BadRequest("Policy name is required".to_string()));
+    }
+
+    if input.accrual_method == AccrualMethod::PerPayPeriod && input.pay_period.is_none() {
+        return Err(AppError::BadRequest(
+            "payPeriod is required for per-pay-period accrual".to_string(),
+        ));
+    }
+
+    input.tiers.sort_by_key(|tier| tier.min_tenure_months);
+    if input.tiers.is_empty() {
+        return Err(AppError::BadRequest(
+            "A policy needs at least one tier".to_string(),
+        ));
+    }
+    if input
+        .tiers
+        .windows(2)
+        .any(|pair| pair[0].min_tenure_months == pair[1].min_tenure_months)
+    {
+        return Err(AppError::BadRequest(
+            "Tiers must have different minimum tenures".to_string(),
+        ));
+    }
+    let zero = BigDecimal::from(0);
+    if input
+        .tiers
+        .iter()
+        .any(|tier| tier.min_tenure_months < 0 || tier.accrual_rate < zero)
+    {
+        return Err(AppError::BadRequest(
+            "Tier tenures and rates cannot be negative".to_string(),
+        ));
+    }
+
+    if [
+        &input.annual_cap_hours,
+        &input.max_balance_hours,
+        &input.carryover_limit_hours,
+    ]
+    .iter()
+    .any(|hours| hours.as_ref().is_some_and(|hours| *hours < zero))
+        || input.waiting_period_days.is_some_and(|days| days < 0)
+    {
+        return Err(AppError::BadRequest(
+            "Caps, limits and waiting periods cannot be negative".to_string(),
+        ));
+    }
+
+    let month = input.year_start_month.unwrap_or(1);
+    let day = input.year_start_day.unwrap_or(1);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(AppError::BadRequest(
+            "yearStartMonth must be 1 to 12 and yearStartDay 1 to 31".to_string(),
+        ));
+    }
+
+    let policies = pto_policy_repo::get_policies(company_id).await?;
+    if policies
+        .iter()
+        .any(|policy| Some(policy.id) != policy_id && policy.name.eq_ignore_ascii_case(&input.name))
+    {
+        return Err(AppError::BadRequest(format!(
+            "A policy named '{}' already exists",
+            input.name
+        )));
+    }
+
+    Ok(())
+}
+
+/// Check an assignment names at most one member, team or location of the company
+pub async fn validate_assignment(
+    company_id: Uuid,
+    input: &PtoPolicyAssignmentInput,
+) -> Result<(), AppError> {
+    match (input.user_id, input.team_id, input.location_id) {
+        (None, None, None) => Ok(()),
+        (Some(user_id), None, None) => {
+            company_repo::check_user_company_access(user_id, company_id)
+                .await?
+                .ok_or_else(|| {
+                    AppError::BadRequest("User is not a member of this company".to_string())
+                })?;
+            Ok(())
+        }
+        (None, Some(team_id), None) => {
+            let teams = team_repo::get_all_teams_for_company(company_id).await?;
+            if !teams.iter().any(|team| team.id == team_id) {
+                return Err(AppError::BadRequest(format!(
+                    "Team {} does not belong to this company",
+                    team_id
+                )));
+            }
+            Ok(())
+        }
+        (None, None, Some(location_id)) => {
+            location_repo::find_by_id(location_id)
+                .await?
+                .filter(|location| location.company_id == company_id)
+                .ok_or_else(|| {
+                    AppError::BadRequest(format!(
+                        "Location {} does not belong to this company",
+                        location_id
+                    ))
+                })?;
+            Ok(())
+        }
+        _ => Err(AppError::BadRequest(
+            "Assign a policy to one user, team or location, or to none for the whole company"
+                .to_string(),
+        )),
+    }
+}
+
+/// Accrue every balance of the company's members that a policy applies to, up to `as_of`.
+/// With `preview`, work out the same results without changing anything.
+pub async fn run(
+    company_id: Uuid,
+    actor_id: Uuid,
+    query: &PtoAccrualRunQuery,
+    preview: bool,
+    req_info: &RequestInfo,
+) -> Result<PtoAccrualRun, AppError> {
+    let as_of = query.as_of.unwrap_or_else(|| Utc::now().date_naive());
+    let policies = pto_policy_repo::get_policies(company_id).await?;
+    let policy_ids: Vec<Uuid> = policies.iter().map(|policy| policy.id).collect();
+    let tiers = pto_policy_repo::get_tiers(&policy_ids).await?;
+
+    let mut results = Vec::new();
+    for balance_type in [
+        PtoBalanceType::Pto,
+        PtoBalanceType::Sick,
+        PtoBalanceType::Personal,
+    ] {
+        let members =
+            pto_policy_repo::get_policy_members(company_id, balance_type, query.user_id).await?;
+        for member in members {
+            let Some(policy) = policies.iter().find(|policy| policy.id == member.policy_id) else {
+                continue;
+            };
+            let policy_tiers: Vec<&PtoAccrualTier> = tiers
+                .iter()
+                .filter(|tier| tier.policy_id == policy.id)
+                .collect();
+            let state = pto_policy_repo::find_state(member.user_id, company_id, balance_type)
+                .await?
+                .map(|mut state| {
+                    state.policy_id = Some(policy.id);
+                    state
+                });
+            let read_accrued_through = state.as_ref().map(|state| state.accrued_through);
+
+            let worked = match (policy.accrual_method, &state, member.hire_date) {
+                (AccrualMethod::PerHourWorked, Some(state), _) => {
+                    pto_policy_repo::get_daily_worked_hours(
+                        member.user_id,
+                        company_id,
+                        state.accrued_through,
+                        as_of,
+                    )
+                    .await?
+                }
+                (AccrualMethod::PerHourWorked, None, Some(hire_date)) => {
+                    pto_policy_repo::get_daily_worked_hours(
+                        member.user_id,
+                        company_id,
+                        initial_accrued_through(policy, &member, hire_date),
+                        as_of,
+                    )
+                    .await?
+                }
+                _ => Vec::new(),
+            };
+
+            let (mut result, accrual) = accrue(
+                company_id,
+                policy,
+                &policy_tiers,
+                &member,
+                state,
+                &worked,
+                as_of,
+            );
+
+            if let (false, Some((state, entries))) = (preview, accrual) {
+                let saved = save(
+                    company_id,
+                    actor_id,
+                    &result,
+                    read_accrued_through,
+                    state,
+                    entries,
+                    req_info,
+                )
+                .await?;
+                match saved {
+                    Some(Some(new_balance)) => result.new_balance = round_hours(new_balance),
+                    Some(None) => {}
+                    None => {
+                        let zero = BigDecimal::from(0);
+                        result.accrued_hours = zero.clone();
+                        result.capped_hours = zero.clone();
+                        result.forfeited_hours = zero;
+                        result.new_balance = result.previous_balance.clone();
+                        result.accrued_through = read_accrued_through;
+                        result.skipped_reason = Some("Accrued by another run".to_string());
+                    }
+                }
+            }
+            results.push(result);
+        }
+    }
+
+    Ok(PtoAccrualRun {
+        as_of,
+        preview,
+        results,
+    })
+}
+
+/// Record the member's ledger entries and accrual state. Returns the balance after the last
+/// entry, if there were any, or None without recording anything when another run saved the
+/// state since it was read as `read_accrued_through`.
+async fn save(
+    company_id: Uuid,
+    actor_id: Uuid,
+    result: &PtoAccrualResult,
+    read_accrued_through: Option<NaiveDate>,
+    state: PtoAccrualState,
+    entries: Vec<PtoLedgerEntryInput>,
+    req_info: &RequestInfo,
+) -> Result<Option<Option<BigDecimal>>, AppError> {
+    let result = result.clone();
+    let req_info = req_info.clone();
+
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            // Saving the state first holds its row until commit, so concurrent runs for the
+            // member wait here and then find it moved on
+            if !pto_policy_repo::save_state(tx, &state, read_accrued_through).await? {
+                return Ok(None);
+            }
+
+            let mut new_balance = None;
+            for mut entry in entries {
+                entry.created_by = Some(actor_id);
+                let history_row =
+                    pto_repo::record_entry(tx, result.user_id, company_id, &entry).await?;
+                new_balance = Some(history_row.new_balance);
+            }
+
+            if let Some(new_balance) = &new_balance {
+                let metadata = activity_logger::metadata(vec![
+                    ("policy_id", result.policy_id.to_string()),
+                    ("balance_type", result.balance_type.to_string()),
+                    ("accrued_hours", result.accrued_hours.to_string()),
+                    ("capped_hours", result.capped_hours.to_string()),
+                    ("forfeited_hours", result.forfeited_hours.to_string()),
+                    ("previous_balance", result.previous_balance.to_string()),
+                    ("new_balance", new_balance.to_string()),
+                    ("accrued_through", state.accrued_through.to_string()),
+                ]);
+
+                activity_logger::log_user_activity(
+                    tx,
+                    company_id,
+                    Some(actor_id),
+                    result.user_id,
+                    Action::ACCRUED,
+                    format!(
+                        "Accrued {} {} hours under policy '{}'",
+                        result.accrued_hours, result.balance_type, result.policy_name
+                    ),
+                    Some(metadata),
+                    &req_info,
+                )
+                .await?;
+            }
+
+            Ok(Some(new_balance))
+        })
+    })
+    .await
+}
+
+/// The day before accrual first counts for a member new to policies: the end of their waiting
+/// period, or the day they were assigned if later
+fn initial_accrued_through(
+    policy: &PtoAccrualPolicy,
+    member: &PtoPolicyMember,
+    hire_date: NaiveDate,
+) -> NaiveDate {
+    let eligible = hire_date + Duration::days(policy.waiting_period_days as i64);
+    eligible.max(member.assigned_at.date_naive()) - Duration::days(1)
+}
+
+/// Work out what accrual up to `as_of` does for the member: their state after it and the
+/// ledger entries to record. There is nothing to save when the member is skipped.
+fn accrue(
+    company_id: Uuid,
+    policy: &PtoAccrualPolicy,
+    tiers: &[&PtoAccrualTier],
+    member: &PtoPolicyMember,
+    state: Option<PtoAccrualState>,
+    worked: &[(NaiveDate, BigDecimal)],
+    as_of: NaiveDate,
+) -> (
+    PtoAccrualResult,
+    Option<(PtoAccrualState, Vec<PtoLedgerEntryInput>)>,
+) {
+    let zero = BigDecimal::from(0);
+    let previous_balance = match policy.balance_type {
+        PtoBalanceType::Pto => &member.pto_balance_hours,
+        PtoBalanceType::Sick => &member.sick_balance_hours,
+        PtoBalanceType::Personal => &member.personal_balance_hours,
+    };
+    let mut result = PtoAccrualResult {
+        user_id: member.user_id,
+        name: member.name.clone(),
+        policy_id: policy.id,
+        policy_name: policy.name.clone(),
+        balance_type: policy.balance_type,
+        tenure_months: 0,
+        accrual_rate: None,
+        pay_periods: 0,
+        hours_worked: zero.clone(),
+        accrued_hours: zero.clone(),
+        capped_hours: zero.clone(),
+        forfeited_hours: zero.clone(),
+        previous_balance: round_hours(previous_balance.clone()),
+        new_balance: round_hours(previous_balance.clone()),
+        accrued_through: state.as_ref().map(|state| state.accrued_through),
+        skipped_reason: None,
+    };
+    let skip = |mut result: PtoAccrualResult, reason: String| {
+        result.skipped_reason = Some(reason);
+        (result, None)
+    };
+
+    let Some(hire_date) = member.hire_date else {
+        return skip(result, "No hire date".to_string());
+    };
+    let eligible = hire_date + Duration::days(policy.waiting_period_days as i64);
+    if as_of < eligible {
+        return skip(result, format!("Waiting period ends on {}", eligible));
+    }
+
+    result.tenure_months = tenure_months(hire_date, as_of);
+    let Some(tier) = tiers
+        .iter()
+        .rev()
+        .find(|tier| tier.min_tenure_months <= result.tenure_months)
+    else {
+        return skip(result, "No tier covers this tenure".to_string());
+    };
+    result.accrual_rate = Some(tier.accrual_rate.clone());
+
+    let mut state = state.unwrap_or_else(|| {
+        let accrued_through = initial_accrued_through(policy, member, hire_date);
+        PtoAccrualState {
+            user_id: member.user_id,
+            company_id,
+            balance_type: policy.balance_type,
+            policy_id: Some(policy.id),
+            accrued_through,
+            year_start: year_start_on(policy, hire_date, accrued_through + Duration::days(1)),
+            accrued_this_year: zero.clone(),
+            updated_at: Utc::now(),
+        }
+    });
+    if state.accrued_through >= as_of {
+        return skip(
+            result,
+            format!("Already accrued through {}", state.accrued_through),
+        );
+    }
+
+    let mut balance = previous_balance.clone();
+    let mut entries = Vec::new();
+    let entry = |change_type, hours_changed, effective_date, description| PtoLedgerEntryInput {
+        balance_type: policy.balance_type,
+        change_type,
+        hours_changed,
+        effective_date,
+        description,
+        related_time_off_id: None,
+        created_by: None,
+    };
+    // Accrue year by year, so each year's cap and carryover applies to what was earned in it
+    loop {
+        let next_year = year_start_in(policy, hire_date, state.year_start.year() + 1);
+        let segment_end = as_of.min(next_year - Duration::days(1));
+
+        let (earned, accrued_through) = match policy.accrual_method {
+            AccrualMethod::PerPayPeriod => {
+                let period = policy.pay_period.unwrap_or(PayPeriod::Monthly);
+                let ends = period_ends(period, state.accrued_through, segment_end);
+                result.pay_periods += ends.len() as i64;
+                (
+                    &tier.accrual_rate * BigDecimal::from(ends.len() as i64),
+                    ends.last().copied().unwrap_or(state.accrued_through),
+                )
+            }
+            AccrualMethod::PerHourWorked => {
+                let hours: BigDecimal = worked
+                    .iter()
+                    .filter(|(day, _)| *day > state.accrued_through && *day <= segment_end)
+                    .map(|(_, hours)| hours.clone())
+                    .sum();
+                result.hours_worked += &hours;
+                (&tier.accrual_rate * hours, segment_end)
+            }
+        };
+        let earned = round_hours(earned);
+
+        let mut credited = earned.clone();
+        if let Some(cap) = &policy.annual_cap_hours {
+            credited = credited.min((cap - &state.accrued_this_year).max(zero.clone()));
+        }
+        if let Some(max_balance) = &policy.max_balance_hours {
+            credited = credited.min((max_balance - &balance).max(zero.clone()));
+        }
+        result.capped_hours += &earned - &credited;
+        result.accrued_hours += &credited;
+        state.accrued_this_year += &credited;
+        state.accrued_through = accrued_through;
+        if credited > zero {
+            balance += &credited;
+            entries.push(entry(
+                PtoChangeType::Accrual,
+                credited,
+                accrued_through,
+                format!("Accrued under policy '{}'", policy.name),
+            ));
+        }
+
+        if next_year > as_of {
+            break;
+        }
+
+        // The year resets: keep up to the carryover limit and start counting toward the cap again
+        if let Some(limit) = policy
+            .carryover_limit_hours
+            .as_ref()
+            .filter(|limit| balance > **limit)
+        {
+            let forfeited = &balance - limit;
+            result.forfeited_hours += &forfeited;
+            balance = limit.clone();
+            entries.push(entry(
+                PtoChangeType::Forfeiture,
+                -forfeited,
+                next_year,
+                format!("Forfeited over the carryover limit of {} hours", limit),
+            ));
+        }
+        state.year_start = next_year;
+        state.accrued_this_year = zero.clone();
+    }
+
+    result.hours_worked = round_hours(result.hours_worked);
+    result.accrued_hours = round_hours(result.accrued_hours);
+    result.capped_hours = round_hours(result.capped_hours);
+    result.forfeited_hours = round_hours(result.forfeited_hours);
+    result.new_balance = round_hours(balance);
+    result.accrued_through = Some(state.accrued_through);
+    (result, Some((state, entries)))
+}
+
+pub(crate) fn round_hours(hours: BigDecimal) -> BigDecimal {
+    hours.with_scale_round(HOURS_SCALE, RoundingMode::HalfUp)
+}
+
+/// Whole months employed on `date`
+fn tenure_months(hire_date: NaiveDate, date: NaiveDate) -> i32 {
+    let months = (date.year() - hire_date.year()) * 12 + date.month() as i32
+        - hire_date.month() as i32
+        - i32::from(date.day() < hire_date.day());
+    months.max(0)
+}
+
+/// The day the accrual year starts in `year`, on the last day of the month when the month is
+/// shorter
+fn year_start_in(policy: &PtoAccrualPolicy, hire_date: NaiveDate, year: i32) -> NaiveDate {
+    let (month, day) = match policy.year_start {
+        AccrualYearStart::Calendar => {
+            (policy.year_start_month as u32, policy.year_start_day as u32)
+        }
+        AccrualYearStart::Anniversary => (hire_date.month(), hire_date.day()),
+    };
+    (1..=day)
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        .unwrap_or_default()
+}
+
+/// Start of the accrual year `date` falls in
+fn year_start_on(policy: &PtoAccrualPolicy, hire_date: NaiveDate, date: NaiveDate) -> NaiveDate {
+    let start = year_start_in(policy, hire_date, date.year());
+    if start <= date {
+        start
+    } else {
+        year_start_in(policy, hire_date, date.year() - 1)
+    }
+}
+
+/// The last days of the pay periods ending after `after`, up to and including `through`.
+/// Weeks end on Sunday, semimonthly periods on the 15th and the last day of the month.
+fn period_ends(period: PayPeriod, after: NaiveDate, through: NaiveDate) -> Vec<NaiveDate> {
+    after
+        .iter_days()
+        .skip(1)
+        .take_while(|day| *day <= through)
+        .filter(|day| {
+            let last_of_month =
+                day.checked_add_days(Days::new(1)).map(|next| next.day()) == Some(1);
+            match period {
+                PayPeriod::Weekly => day.weekday() == Weekday::Sun,
+                PayPeriod::Biweekly => (*day - BIWEEKLY_ANCHOR).num_days().rem_euclid(14) == 13,
+                PayPeriod::Semimonthly => day.day() == 15 || last_of_month,
+                PayPeriod::Monthly => last_of_month,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_period_ends() {
+        let after = date(2026, 1, 31);
+        let through = date(2026, 3, 31);
+        assert_eq!(
+            period_ends(PayPeriod::Monthly, after, through),
+            vec![date(2026, 2, 28), date(2026, 3, 31)]
+        );
+        assert_eq!(period_ends(PayPeriod::Semimonthly, after, through).len(), 4);
+        assert_eq!(period_ends(PayPeriod::Weekly, after, through).len(), 9);
+        assert_eq!(period_ends(PayPeriod::Biweekly, after, through).len(), 4);
+    }
+
+    #[test]
+    fn test_tenure_months() {
+        assert_eq!(tenure_months(date(2025, 3, 15), date(2026, 3, 14)), 11);
+        assert_eq!(tenure_months(date(2025, 3, 15), date(2026, 3, 15)), 12);
+        assert_eq!(tenure_months(date(2026, 3, 15), date(2026, 1, 1)), 0);
+    }
+}
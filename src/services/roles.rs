@@ -0,0 +1,47 @@
+use uuid::Uuid;
+
+use crate::database::{
+    models::{Permission, RoleInput},
+    repositories::role as role_repo,
+};
+use crate::error::AppError;
+
+/// Normalise a role definition and reject unknown permissions or clashing names.
+/// `role_id` is the role being updated, if any.
+pub async fn validate_input(
+    company_id: Uuid,
+    role_id: Option<Uuid>,
+    input: &mut RoleInput,
+) -> Result<(), AppError> {
+    input.name = input.name.trim().to_string();
+    if input.name.is_empty() {
+        return Err(AppError::BadRequest("Role name is required".to_string()));
+    }
+
+    input.permissions.sort();
+    input.permissions.dedup();
+    if let Some(permission) = input
+        .permissions
+        .iter()
+        .find(|permission| !Permission::ALL.contains(&permission.as_str()))
+    {
+        return Err(AppError::BadRequest(format!(
+            "Unknown permission: {}",
+            permission
+        )));
+    }
+
+    // Names are shared with the built-in roles, so they must be unique across both
+    let roles = role_repo::get_company_roles(company_id).await?;
+    if roles
+        .iter()
+        .any(|role| Some(role.id) != role_id && role.name.eq_ignore_ascii_case(&input.name))
+    {
+        return Err(AppError::BadRequest(format!(
+            "A role named '{}' already exists",
+            input.name
+        )));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,710 @@
+use std::{net::IpAddr, sync::OnceLock};
+
+use anyhow::{Result, bail};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use bcrypt::{DEFAULT_COST, hash, verify};
+use chrono::{Duration, Utc};
+use hickory_resolver::TokioAsyncResolver;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header, jwk::JwkSet};
+use rand::Rng;
+use reqwest::{Client, Url, redirect};
+use serde::{Deserialize, Deserializer, Serialize, de::DeserializeOwned};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::config::config;
+use crate::database::{
+    models::{
+        Action, AddEmployeeToCompanyInput, CompanySsoConfig, CompanySsoDomain, LoginResponse,
+        SsoCallbackInput, SsoEndpoints, SsoLinkChallenge, SsoLinkInput, SsoStartResponse, User,
+        UserIdentity,
+    },
+    repositories::{company as company_repo, sso as sso_repo, user as user_repo},
+    transaction::DatabaseTransaction,
+};
+use crate::error::AppError;
+use crate::middleware::request_info::RequestInfo;
+use crate::services::{
+    account_lockout, activity_logger, auth, email_verification, jwt_keys::key_ring,
+};
+
+/// How long the user has to finish signing in at the provider
+const LOGIN_STATE_MINUTES: i64 = 10;
+const SCOPES: &str = "openid email profile";
+/// Applies to each request to a provider
+const PROVIDER_TIMEOUT_SECONDS: u64 = 10;
+
+const DOMAIN_VERIFICATION_PREFIX: &str = "shiftlinkr-verification=";
+/// How long the owner of an existing account has to confirm a link
+const LINK_CHALLENGE_MINUTES: i64 = 10;
+const LINK_CHALLENGE_PURPOSE: &str = "sso_link";
+
+static PROVIDER_CLIENT: OnceLock<Client> = OnceLock::new();
+
+/// Signed link challenge; carries the provider identity until the account owner confirms
+#[derive(Debug, Serialize, Deserialize)]
+struct LinkChallengeClaims {
+    sub: Uuid, // Existing user the provider's email matched
+    purpose: String,
+    company_id: Uuid,
+    issuer: String,
+    subject: String,
+    email: String,
+    exp: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct DiscoveryDocument {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    email: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_email_verified")]
+    email_verified: bool,
+    name: Option<String>,
+    nonce: Option<String>,
+}
+
+/// Some providers send `email_verified` as a string
+fn deserialize_email_verified<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<bool, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum BoolOrString {
+        Bool(bool),
+        String(String),
+    }
+
+    Ok(match BoolOrString::deserialize(deserializer)? {
+        BoolOrString::Bool(value) => value,
+        BoolOrString::String(value) => value.eq_ignore_ascii_case("true"),
+    })
+}
+
+/// Where providers send the browser back to; the client forwards `code` and `state` to the callback
+pub fn redirect_uri() -> String {
+    format!(
+        "{}/auth/sso/callback",
+        config().client_base_url.trim_end_matches('/')
+    )
+}
+
+/// Resolve the provider's endpoints from its discovery document
+pub async fn discover(issuer: &str) -> Result<SsoEndpoints, AppError> {
+    let discovery_url = format!(
+        "{}/.well-known/openid-configuration",
+        issuer.trim_end_matches('/')
+    );
+
+    let document: DiscoveryDocument = get_json(&discovery_url).await.map_err(|e| {
+        log::warn!("OIDC discovery failed for {}: {}", issuer, e);
+        AppError::BadRequest(format!("Could not load OIDC discovery document: {}", e))
+    })?;
+
+    if document.issuer != issuer {
+        return Err(AppError::BadRequest(format!(
+            "Discovery document is for issuer {}, not {}",
+            document.issuer, issuer
+        )));
+    }
+
+    Ok(SsoEndpoints {
+        authorization_endpoint: document.authorization_endpoint,
+        token_endpoint: document.token_endpoint,
+        jwks_uri: document.jwks_uri,
+    })
+}
+
+/// Lowercase allowed domains and drop any leading `@`
+pub fn normalize_domains(domains: &[String]) -> Vec<String> {
+    domains
+        .iter()
+        .map(|domain| domain.trim().trim_start_matches('@').to_lowercase())
+        .filter(|domain| !domain.is_empty())
+        .collect()
+}
+
+/// Check a domain the company wants to verify, returning it lowercased
+pub fn normalize_domain(domain: &str) -> Result<String, AppError> {
+    let domain = domain
+        .trim()
+        .trim_start_matches('@')
+        .trim_end_matches('.')
+        .to_lowercase();
+
+    let valid = domain.len() <= 253
+        && domain.contains('.')
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    if !valid {
+        return Err(AppError::BadRequest(format!(
+            "'{}' is not a valid domain",
+            domain
+        )));
+    }
+
+    Ok(domain)
+}
+
+/// TXT record value the company publishes on the domain to prove it controls it
+pub fn verification_record(domain: &CompanySsoDomain) -> String {
+    format!(
+        "{}{}",
+        DOMAIN_VERIFICATION_PREFIX, domain.verification_token
+    )
+}
+
+/// Whether the domain currently publishes its verification record
+pub async fn has_verification_record(domain: &CompanySsoDomain) -> Result<bool, AppError> {
+    let resolver = TokioAsyncResolver::tokio_from_system_conf().map_err(|e| {
+        log::error!("Failed to create DNS resolver: {}", e);
+        AppError::internal_server_error_message("DNS lookups are unavailable")
+    })?;
+
+    let expected = verification_record(domain);
+    match resolver.txt_lookup(format!("{}.", domain.domain)).await {
+        // Long records arrive split into several strings
+        Ok(records) => Ok(records.iter().any(|record| {
+            record
+                .txt_data()
+                .iter()
+                .map(|part| String::from_utf8_lossy(part))
+                .collect::<String>()
+                == expected
+        })),
+        Err(e) => {
+            log::info!("TXT lookup for {} failed: {}", domain.domain, e);
+            Ok(false)
+        }
+    }
+}
+
+/// Begin an authorization code + PKCE login for a company
+pub async fn start_login(company_id: Uuid) -> Result<SsoStartResponse, AppError> {
+    let sso_config = enabled_config(company_id).await?;
+
+    let state = random_token();
+    let nonce = random_token();
+    let code_verifier = random_token();
+    let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+    let redirect_uri = redirect_uri();
+    let expires_at = Utc::now() + Duration::minutes(LOGIN_STATE_MINUTES);
+
+    let mut authorization_url = Url::parse(&sso_config.authorization_endpoint)
+        .map_err(|e| AppError::internal_server_error_message(e.to_string()))?;
+    authorization_url
+        .query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &sso_config.client_id)
+        .append_pair("redirect_uri", &redirect_uri)
+        .append_pair("scope", SCOPES)
+        .append_pair("state", &state)
+        .append_pair("nonce", &nonce)
+        .append_pair("code_challenge", &code_challenge)
+        .append_pair("code_challenge_method", "S256");
+
+    let state_value = state.clone();
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            sso_repo::cleanup_expired_login_states(tx).await?;
+            sso_repo::create_login_state(
+                tx,
+                &state_value,
+                company_id,
+                &code_verifier,
+                &nonce,
+                &redirect_uri,
+                expires_at,
+            )
+            .await?;
+            Ok(())
+        })
+    })
+    .await?;
+
+    Ok(SsoStartResponse {
+        authorization_url: authorization_url.to_string(),
+        state,
+        expires_at,
+    })
+}
+
+/// Finish a login when the provider redirects back with an authorization code
+pub async fn complete_login(
+    input: SsoCallbackInput,
+    req_info: &RequestInfo,
+) -> Result<LoginResponse, AppError> {
+    let state = input.state;
+    let login_state = DatabaseTransaction::run(|tx| {
+        Box::pin(async move { Ok(sso_repo::take_login_state(tx, &state).await?) })
+    })
+    .await?
+    .filter(|login_state| login_state.expires_at > Utc::now())
+    .ok_or(AppError::Unauthorized)?;
+
+    let sso_config = enabled_config(login_state.company_id).await?;
+
+    let response = exchange_code(
+        &sso_config.token_endpoint,
+        &[
+            ("grant_type", "authorization_code"),
+            ("code", &input.code),
+            ("redirect_uri", &login_state.redirect_uri),
+            ("client_id", &sso_config.client_id),
+            ("client_secret", &sso_config.client_secret),
+            ("code_verifier", &login_state.code_verifier),
+        ],
+    )
+    .await
+    .map_err(|e| {
+        log::error!("OIDC token request to {} failed: {}", sso_config.issuer, e);
+        AppError::internal_server_error_message("Identity provider is unavailable")
+    })?;
+
+    let success = response.status().is_success();
+    let tokens: TokenResponse = response.json().await.map_err(|e| {
+        log::warn!("Invalid token response from {}: {}", sso_config.issuer, e);
+        AppError::Unauthorized
+    })?;
+    let id_token = match (success, tokens.id_token) {
+        (true, Some(id_token)) => id_token,
+        _ => {
+            log::warn!(
+                "Code exchange with {} failed: {} {}",
+                sso_config.issuer,
+                tokens.error.unwrap_or_default(),
+                tokens.error_description.unwrap_or_default()
+            );
+            return Err(AppError::Unauthorized);
+        }
+    };
+
+    let claims = verify_id_token(&sso_config, &id_token, &login_state.nonce).await?;
+
+    let email = claims.email.clone().ok_or_else(|| {
+        AppError::Forbidden("Identity provider did not share an email".to_string())
+    })?;
+    if !sso_config.allows_email(&email) {
+        return Err(AppError::Forbidden(
+            "Email domain is not allowed for this company".to_string(),
+        ));
+    }
+
+    match provision_user(&sso_config, &claims, &email, req_info).await? {
+        Provisioned::User(user) => {
+            Ok(auth::finish_login(user, Some(sso_config.company_id), req_info).await?)
+        }
+        Provisioned::LinkRequired(challenge) => Ok(LoginResponse::SsoLinkRequired(challenge)),
+    }
+}
+
+async fn enabled_config(company_id: Uuid) -> Result<CompanySsoConfig, AppError> {
+    sso_repo::find_config_by_company_id(company_id)
+        .await?
+        .filter(|sso_config| sso_config.enabled)
+        .ok_or_else(|| {
+            AppError::NotFound("Single sign-on is not configured for this company".to_string())
+        })
+}
+
+/// Check the ID token's signature against the provider's published keys, then its claims
+async fn verify_id_token(
+    sso_config: &CompanySsoConfig,
+    id_token: &str,
+    nonce: &str,
+) -> Result<IdTokenClaims, AppError> {
+    let header = decode_header(id_token).map_err(|_| AppError::Unauthorized)?;
+
+    // Shared-secret algorithms would let anyone holding the client secret mint tokens
+    if matches!(
+        header.alg,
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+    ) {
+        return Err(AppError::Unauthorized);
+    }
+
+    let jwks: JwkSet = get_json(&sso_config.jwks_uri).await.map_err(|e| {
+        log::error!("Failed to fetch JWKS for {}: {}", sso_config.issuer, e);
+        AppError::internal_server_error_message("Identity provider is unavailable")
+    })?;
+
+    let jwk = match &header.kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+    .ok_or(AppError::Unauthorized)?;
+    let decoding_key = DecodingKey::from_jwk(jwk).map_err(|_| AppError::Unauthorized)?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[&sso_config.issuer]);
+    validation.set_audience(&[&sso_config.client_id]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+    let claims = decode::<IdTokenClaims>(id_token, &decoding_key, &validation)
+        .map_err(|e| {
+            log::warn!("Rejected ID token from {}: {}", sso_config.issuer, e);
+            AppError::Unauthorized
+        })?
+        .claims;
+
+    if claims.nonce.as_deref() != Some(nonce) {
+        return Err(AppError::Unauthorized);
+    }
+
+    Ok(claims)
+}
+
+/// The account a provider login resolved to
+enum Provisioned {
+    User(User),
+    /// An existing account that only its owner can link
+    LinkRequired(SsoLinkChallenge),
+}
+
+/// Find the user behind an external identity, linking by verified email or creating them,
+/// and make sure they belong to the company
+async fn provision_user(
+    sso_config: &CompanySsoConfig,
+    claims: &IdTokenClaims,
+    email: &str,
+    req_info: &RequestInfo,
+) -> Result<Provisioned, AppError> {
+    let company_id = sso_config.company_id;
+
+    if let Some(identity) = sso_repo::find_identity(&sso_config.issuer, &claims.sub).await? {
+        let user = user_repo::find_by_id(identity.user_id)
+            .await?
+            .ok_or(AppError::Unauthorized)?;
+        let user = join_company(
+            sso_config,
+            user,
+            Some(identity),
+            false,
+            &claims.sub,
+            email,
+            req_info,
+        )
+        .await?;
+        return Ok(Provisioned::User(user));
+    }
+
+    match user_repo::find_by_email(email).await? {
+        // Only take over an existing account when the provider vouches for the address
+        Some(_) if !claims.email_verified => Err(AppError::Forbidden(
+            "An account with this email exists, but the identity provider has not verified the address"
+                .to_string(),
+        )),
+        Some(user) => {
+            // Any company admin can point SSO at a provider they control, so its word alone
+            // only links members of this company on a domain the company has verified
+            let member = company_repo::find_user_company_info_by_id(user.id, company_id)
+                .await?
+                .is_some();
+            let domain_verified = match email.rsplit_once('@') {
+                Some((_, domain)) => {
+                    sso_repo::find_verified_company(domain).await? == Some(company_id)
+                }
+                None => false,
+            };
+
+            if member && domain_verified {
+                let user =
+                    join_company(sso_config, user, None, false, &claims.sub, email, req_info)
+                        .await?;
+                Ok(Provisioned::User(user))
+            } else {
+                Ok(Provisioned::LinkRequired(generate_link_challenge(
+                    sso_config, user.id, &claims.sub, email,
+                )?))
+            }
+        }
+        None => {
+            // SSO users have no usable password until they reset it
+            let password_hash = hash(random_token(), DEFAULT_COST)
+                .map_err(|e| AppError::internal_server_error_message(e.to_string()))?;
+            let name = claims
+                .name
+                .clone()
+                .unwrap_or_else(|| email.split('@').next().unwrap_or(email).to_string());
+            // Any company can connect a provider, so its email_verified claim never verifies
+            // the address; new users confirm it like everyone else
+            let user = User::new(email.to_string(), password_hash, name);
+
+            let user =
+                join_company(sso_config, user, None, true, &claims.sub, email, req_info).await?;
+            Ok(Provisioned::User(user))
+        }
+    }
+}
+
+/// Link a confirmed account to the identity from a link challenge, then sign it in
+pub async fn confirm_link(
+    input: SsoLinkInput,
+    req_info: &RequestInfo,
+) -> Result<LoginResponse, AppError> {
+    let challenge = key_ring()?
+        .decode::<LinkChallengeClaims>(&input.link_token)
+        .map_err(|_| AppError::Unauthorized)?;
+    if challenge.purpose != LINK_CHALLENGE_PURPOSE {
+        return Err(AppError::Unauthorized);
+    }
+
+    let sso_config = enabled_config(challenge.company_id).await?;
+    if sso_config.issuer != challenge.issuer {
+        return Err(AppError::Unauthorized);
+    }
+    // Each challenge links once
+    if sso_repo::find_identity(&challenge.issuer, &challenge.subject)
+        .await?
+        .is_some()
+    {
+        return Err(AppError::Unauthorized);
+    }
+
+    let user = user_repo::find_by_id(challenge.sub)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    // Wrong passwords count towards the same lockout as failed logins
+    let attempt = account_lockout::attempt(&user, "invalid_password", req_info, async {
+        verify(&input.password, &user.password_hash)
+            .map_err(|e| AppError::internal_server_error_message(e.to_string()))
+    })
+    .await?;
+    if !attempt.verified {
+        return Err(AppError::Unauthorized);
+    }
+    if attempt.lockout.is_some() {
+        account_lockout::reset(user.id).await?;
+    }
+
+    let user = join_company(
+        &sso_config,
+        user,
+        None,
+        false,
+        &challenge.subject,
+        &challenge.email,
+        req_info,
+    )
+    .await?;
+
+    Ok(auth::finish_login(user, Some(sso_config.company_id), req_info).await?)
+}
+
+fn generate_link_challenge(
+    sso_config: &CompanySsoConfig,
+    user_id: Uuid,
+    subject: &str,
+    email: &str,
+) -> Result<SsoLinkChallenge, AppError> {
+    let expires_at = Utc::now() + Duration::minutes(LINK_CHALLENGE_MINUTES);
+    let claims = LinkChallengeClaims {
+        sub: user_id,
+        purpose: LINK_CHALLENGE_PURPOSE.to_string(),
+        company_id: sso_config.company_id,
+        issuer: sso_config.issuer.clone(),
+        subject: subject.to_string(),
+        email: email.to_string(),
+        exp: expires_at.timestamp() as usize,
+    };
+
+    Ok(SsoLinkChallenge {
+        link_required: true,
+        link_token: key_ring()?.encode(&claims)?,
+        email: email.to_string(),
+        expires_at,
+    })
+}
+
+/// Store the identity link (when new) and membership for a resolved user and record the login
+async fn join_company(
+    sso_config: &CompanySsoConfig,
+    user: User,
+    identity: Option<UserIdentity>,
+    new_user: bool,
+    subject: &str,
+    email: &str,
+    req_info: &RequestInfo,
+) -> Result<User, AppError> {
+    let company_id = sso_config.company_id;
+
+    let membership = if new_user {
+        None
+    } else {
+        company_repo::find_user_company_info_by_id(user.id, company_id).await?
+    };
+    let has_primary_company = !new_user && company_repo::has_primary_company(user.id).await?;
+
+    let issuer = sso_config.issuer.clone();
+    let subject = subject.to_string();
+    let email = email.to_string();
+    let default_role = sso_config.default_role.clone();
+    let req_info = req_info.clone();
+    let (user, verification_token) = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let (user, verification_token) = if new_user {
+                let user = user_repo::create_user(tx, &user).await?;
+                let verification_token =
+                    email_verification::issue(tx, user.id, &user.email).await?;
+                (user, Some(verification_token))
+            } else {
+                (user, None)
+            };
+
+            match identity {
+                Some(identity) => sso_repo::touch_identity(tx, identity.id, &email).await?,
+                None => {
+                    sso_repo::create_identity(tx, user.id, company_id, &issuer, &subject, &email)
+                        .await?;
+
+                    activity_logger::log_auth_activity(
+                        tx,
+                        company_id,
+                        Some(user.id),
+                        Action::SSO_LINKED,
+                        format!("User {} linked to identity provider {}", user.email, issuer),
+                        Some(activity_logger::metadata(vec![
+                            ("issuer", issuer.clone()),
+                            ("new_user", new_user.to_string()),
+                        ])),
+                        &req_info,
+                    )
+                    .await?;
+                }
+            }
+
+            // Just-in-time membership with the company's default role
+            if membership.is_none() {
+                company_repo::add_employee_to_company(
+                    tx,
+                    company_id,
+                    &AddEmployeeToCompanyInput {
+                        user_id: user.id,
+                        role: Some(default_role.clone()),
+                        is_primary: Some(!has_primary_company),
+                        hire_date: None,
+                    },
+                )
+                .await?;
+
+                activity_logger::log_user_activity(
+                    tx,
+                    company_id,
+                    Some(user.id),
+                    user.id,
+                    Action::CREATED,
+                    format!(
+                        "User {} joined company {} through single sign-on",
+                        user.email, company_id
+                    ),
+                    Some(activity_logger::metadata(vec![(
+                        "role",
+                        default_role.to_string(),
+                    )])),
+                    &req_info,
+                )
+                .await?;
+            }
+
+            activity_logger::log_auth_activity(
+                tx,
+                company_id,
+                Some(user.id),
+                Action::SSO_LOGIN,
+                format!("User {} signed in with {}", user.email, issuer),
+                None,
+                &req_info,
+            )
+            .await?;
+
+            Ok((user, verification_token))
+        })
+    })
+    .await?;
+
+    if let Some(verification_token) = verification_token {
+        email_verification::send(&user.name, &verification_token).await;
+    }
+
+    Ok(user)
+}
+
+/// Client for provider requests. Redirects are not followed, so every request goes to the
+/// URL the provider published.
+fn provider_client() -> &'static Client {
+    PROVIDER_CLIENT.get_or_init(|| {
+        Client::builder()
+            .timeout(std::time::Duration::from_secs(PROVIDER_TIMEOUT_SECONDS))
+            .redirect(redirect::Policy::none())
+            .user_agent("shiftlinkr")
+            .build()
+            .expect("Failed to build the identity provider HTTP client")
+    })
+}
+
+/// Providers must use HTTPS; plain HTTP is only allowed for loopback hosts, e.g. a local
+/// mock provider
+fn provider_url(url: &str) -> Result<Url> {
+    let url = Url::parse(url)?;
+    match url.scheme() {
+        "https" => Ok(url),
+        "http" if is_loopback(url.host_str().unwrap_or_default()) => Ok(url),
+        "http" => bail!("Refusing plain HTTP to non-local host {}", url),
+        scheme => bail!("Unsupported URL scheme: {}", scheme),
+    }
+}
+
+fn is_loopback(host: &str) -> bool {
+    host == "localhost"
+        || host
+            .trim_matches(|c| c == '[' || c == ']')
+            .parse::<IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+/// GET a provider URL and decode its JSON body, failing on non-2xx responses
+async fn get_json<T: DeserializeOwned>(url: &str) -> Result<T> {
+    let response = provider_client()
+        .get(provider_url(url)?)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(response.json().await?)
+}
+
+/// POST the code exchange form; the response is returned whatever its status, since errors
+/// are described in the body
+async fn exchange_code(url: &str, form: &[(&str, &str)]) -> Result<reqwest::Response> {
+    Ok(provider_client()
+        .post(provider_url(url)?)
+        .form(form)
+        .send()
+        .await?)
+}
+
+/// 256 bits of randomness, URL safe; used for state, nonce and PKCE verifiers
+fn random_token() -> String {
+    let bytes: [u8; 32] = rand::rng().random();
+    URL_SAFE_NO_PAD.encode(bytes)
+}
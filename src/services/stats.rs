@@ -0,0 +1,635 @@
+use std::{cmp::Ordering, collections::BTreeMap, future::Future};
+
+use chrono::{Duration, NaiveDate, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::database::{
+    models::{
+        CoverageHeatmap, CoverageQuery, EmployeeFairness, EmployeeReport, EmployeeReportGroup,
+        EmployeeReportQuery, EmployeeReportRow, EmployeeReportTotals, FairnessQuery,
+        FairnessReport, FairnessRow, FairnessTrendPoint, PeriodStats, Permission, StatsFilter,
+        StatsQuery,
+    },
+    repositories::{holiday as holiday_repo, location as location_repo, stats as stats_repo},
+};
+use crate::error::AppError;
+use crate::services::{
+    activity_log::csv_field,
+    manager_scope::{self, ManagerScope},
+    user_context::UserContext,
+};
+
+/// Period used when the query gives no start date
+const DEFAULT_PERIOD_DAYS: i64 = 30;
+
+/// Resolve the query into a filter for the caller's company. Without `userId`, members
+/// who may view stats get the company (or the locations and teams they manage) and
+/// everyone else gets their own figures.
+pub async fn build_filter(ctx: &UserContext, query: &StatsQuery) -> Result<StatsFilter, AppError> {
+    let company_id = ctx.strict_company_id()?;
+
+    let end_date = query.end_date.unwrap_or_else(Utc::now);
+    let start_date = query
+        .start_date
+        .unwrap_or(end_date - Duration::days(DEFAULT_PERIOD_DAYS));
+    if start_date >= end_date {
+        return Err(AppError::BadRequest(
+            "startDate must be before endDate".to_string(),
+        ));
+    }
+
+    let user_id = match query.user_id {
+        Some(user_id) => {
+            ctx.requires_same_user_or_permission(user_id, Permission::STATS_VIEW)?;
+            Some(user_id)
+        }
+        None if ctx.has_permission(Permission::STATS_VIEW) => None,
+        None => Some(ctx.user_id()),
+    };
+
+    // Managers limited to some locations and teams only see figures for those
+    let (scope, scope_user_ids) = if user_id == Some(ctx.user_id()) {
+        (None, None)
+    } else {
+        let scope = manager_scope::load(ctx).await?;
+        if let Some(user_id) = user_id {
+            scope.requires_user(user_id)?;
+        }
+        match scope {
+            ManagerScope::CompanyWide => (None, None),
+            ManagerScope::Limited { filter, user_ids } => (Some(filter), Some(user_ids)),
+        }
+    };
+
+    Ok(StatsFilter {
+        company_id,
+        start_date,
+        end_date,
+        user_id,
+        location_id: query.location_id,
+        team_id: query.team_id,
+        scope,
+        scope_user_ids,
+    })
+}
+
+/// Run `fetch` for the filter's period and, when `compare` is set, for the period of the same
+/// length ending where it starts
+pub async fn with_comparison<T, F, Fut>(
+    filter: StatsFilter,
+    compare: bool,
+    fetch: F,
+) -> Result<PeriodStats<T>, AppError>
+where
+    T: Serialize,
+    F: Fn(StatsFilter) -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    let period_start = filter.start_date;
+    let period_end = filter.end_date;
+
+    let previous = if compare {
+        let mut previous_filter = filter.clone();
+        previous_filter.start_date = period_start - (period_end - period_start);
+        previous_filter.end_date = period_start;
+        Some(fetch(previous_filter).await?)
+    } else {
+        None
+    };
+    let current = fetch(filter).await?;
+
+    let changes = match &previous {
+        Some(previous) => Some(percent_changes(
+            &serde_json::to_value(&current)
+                .map_err(|e| AppError::internal_server_error_message(e.to_string()))?,
+            &serde_json::to_value(previous)
+                .map_err(|e| AppError::internal_server_error_message(e.to_string()))?,
+        )),
+        None => None,
+    };
+
+    Ok(PeriodStats {
+        current,
+        period_start,
+        period_end,
+        previous,
+        changes,
+    })
+}
+
+/// Percent change of every numeric field, rounded to one decimal. Null when the previous value
+/// was zero and the current one is not.
+fn percent_changes(current: &Value, previous: &Value) -> Map<String, Value> {
+    let mut changes = Map::new();
+    let (Some(current), Some(previous)) = (current.as_object(), previous.as_object()) else {
+        return changes;
+    };
+
+    for (field, value) in current {
+        let (Some(now), Some(before)) = (
+            value.as_f64(),
+            previous.get(field).and_then(|value| value.as_f64()),
+        ) else {
+            continue;
+        };
+        let change = if before == 0.0 {
+            (now == 0.0).then_some(0.0)
+        } else {
+            Some(((now - before) / before * 1000.0).round() / 10.0)
+        };
+        changes.insert(field.clone(), change.map_or(Value::Null, Value::from));
+    }
+
+    changes
+}
+
+/// Bucket sizes the coverage heatmap accepts, in minutes
+const COVERAGE_INTERVALS: [i32; 3] = [15, 30, 60];
+/// Longest heatmap returned in one response; narrow the period or widen the buckets beyond it
+const MAX_COVERAGE_BUCKETS: i64 = 10_000;
+
+const REPORT_CSV_COLUMNS: [&str; 14] = [
+    "group",
+    "user_id",
+    "name",
+    "email",
+    "scheduled_shifts",
+    "scheduled_hours",
+    "worked_hours",
+    "overtime_hours",
+    "late_arrivals",
+    "no_shows",
+    "dropped_shifts",
+    "time_off_requests",
+    "time_off_days",
+    "period",
+];
+
+/// Build the per-employee report for the members the caller may view stats for, sorted by
+/// `sort` and optionally grouped by team or location. An employee in several teams appears
+/// in each of their groups; the overall totals count them once.
+pub async fn employee_report(
+    ctx: &UserContext,
+    query: &EmployeeReportQuery,
+) -> Result<EmployeeReport, AppError> {
+    ctx.requires_permission(Permission::STATS_VIEW)?;
+
+    let by_location = match query.group_by.as_deref() {
+        None => None,
+        Some("team") => Some(false),
+        Some("location") => Some(true),
+        Some(other) => {
+            return Err(AppError::BadRequest(format!(
+                "Unknown groupBy '{}'; use team or location",
+                other
+            )));
+        }
+    };
+    let descending = match query.order.as_deref().unwrap_or("asc") {
+        "asc" => false,
+        "desc" => true,
+        other => {
+            return Err(AppError::BadRequest(format!(
+                "Unknown order '{}'; use asc or desc",
+                other
+            )));
+        }
+    };
+    let sort = query.sort.as_deref().unwrap_or("name");
+
+    let filter = build_filter(
+        ctx,
+        &StatsQuery {
+            start_date: query.start_date,
+            end_date: query.end_date,
+            user_id: None,
+            location_id: query.location_id,
+            team_id: query.team_id,
+            compare: None,
+        },
+    )
+    .await?;
+
+    let mut rows = stats_repo::get_employee_report(&filter).await?;
+    sort_rows(&mut rows, sort, descending)?;
+    let totals = report_totals(&rows);
+
+    let (employees, groups) = match by_location {
+        None => (Some(rows), None),
+        Some(by_location) => {
+            let user_ids: Vec<Uuid> = rows.iter().map(|row| row.user_id).collect();
+            let memberships =
+                stats_repo::get_report_groups(filter.company_id, &user_ids, by_location).await?;
+            (None, Some(group_rows(rows, memberships)))
+        }
+    };
+
+    Ok(EmployeeReport {
+        period_start: filter.start_date,
+        period_end: filter.end_date,
+        employees,
+        groups,
+        totals,
+    })
+}
+
+fn sort_rows(rows: &mut [EmployeeReportRow], sort: &str, descending: bool) -> Result<(), AppError> {
+    let compare: fn(&EmployeeReportRow, &EmployeeReportRow) -> Ordering = match sort {
+        "name" => |a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        "email" => |a, b| a.email.cmp(&b.email),
+        "scheduledShifts" => |a, b| a.scheduled_shifts.cmp(&b.scheduled_shifts),
+        "scheduledHours" => |a, b| a.scheduled_hours.total_cmp(&b.scheduled_hours),
+        "workedHours" => |a, b| a.worked_hours.total_cmp(&b.worked_hours),
+        "overtimeHours" => |a, b| a.overtime_hours.total_cmp(&b.overtime_hours),
+        "lateArrivals" => |a, b| a.late_arrivals.cmp(&b.late_arrivals),
+        "noShows" => |a, b| a.no_shows.cmp(&b.no_shows),
+        "droppedShifts" => |a, b| a.dropped_shifts.cmp(&b.dropped_shifts),
+        "timeOffRequests" => |a, b| a.time_off_requests.cmp(&b.time_off_requests),
+        "timeOffDays" => |a, b| a.time_off_days.cmp(&b.time_off_days),
+        other => {
+            return Err(AppError::BadRequest(format!(
+                "Cannot sort the report by '{}'",
+                other
+            )));
+        }
+    };
+
+    // Rows come ordered by name, which the stable sort keeps for ties
+    rows.sort_by(|a, b| {
+        let ordering = compare(a, b);
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    Ok(())
+}
+
+fn report_totals(rows: &[EmployeeReportRow]) -> EmployeeReportTotals {
+    rows.iter()
+        .fold(EmployeeReportTotals::default(), |mut totals, row| {
+            totals.employees += 1;
+            totals.scheduled_shifts += row.scheduled_shifts;
+            totals.scheduled_hours += row.scheduled_hours;
+            totals.worked_hours += row.worked_hours;
+            totals.overtime_hours += row.overtime_hours;
+            totals.late_arrivals += row.late_arrivals;
+            totals.no_shows += row.no_shows;
+            totals.dropped_shifts += row.dropped_shifts;
+            totals.time_off_requests += row.time_off_requests;
+            totals.time_off_days += row.time_off_days;
+            totals
+        })
+}
+
+/// Split the sorted rows into groups ordered by name, with the ungrouped employees last
+fn group_rows(
+    rows: Vec<EmployeeReportRow>,
+    memberships: Vec<(Uuid, Uuid, String)>,
+) -> Vec<EmployeeReportGroup> {
+    let mut groups: BTreeMap<(bool, String, Option<Uuid>), Vec<EmployeeReportRow>> =
+        BTreeMap::new();
+    for row in rows {
+        let mut grouped = false;
+        for (_, group_id, group_name) in memberships
+            .iter()
+            .filter(|(user_id, _, _)| *user_id == row.user_id)
+        {
+            grouped = true;
+            groups
+                .entry((false, group_name.clone(), Some(*group_id)))
+                .or_default()
+                .push(row.clone());
+        }
+        if !grouped {
+            groups
+                .entry((true, "Unassigned".to_string(), None))
+                .or_default()
+                .push(row);
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(
+            |((_, group_name, group_id), employees)| EmployeeReportGroup {
+                group_id,
+                group_name,
+                totals: report_totals(&employees),
+                employees,
+            },
+        )
+        .collect()
+}
+
+/// One CSV line per employee, per group when grouped, ending with the overall totals
+pub fn employee_report_csv(report: &EmployeeReport) -> String {
+    let period = format!(
+        "{}/{}",
+        report.period_start.to_rfc3339(),
+        report.period_end.to_rfc3339()
+    );
+    let mut output = REPORT_CSV_COLUMNS.join(",");
+    output.push('\n');
+
+    let mut push_row = |group: &str, row: &EmployeeReportRow| {
+        output.push_str(
+            &[
+                group.to_string(),
+                row.user_id.to_string(),
+                row.name.clone(),
+                row.email.clone(),
+                row.scheduled_shifts.to_string(),
+                format!("{:.2}", row.scheduled_hours),
+                format!("{:.2}", row.worked_hours),
+                format!("{:.2}", row.overtime_hours),
+                row.late_arrivals.to_string(),
+                row.no_shows.to_string(),
+                row.dropped_shifts.to_string(),
+                row.time_off_requests.to_string(),
+                row.time_off_days.to_string(),
+                period.clone(),
+            ]
+            .iter()
+            .map(|field| csv_field(field))
+            .collect::<Vec<_>>()
+            .join(","),
+        );
+        output.push('\n');
+    };
+
+    if let Some(groups) = &report.groups {
+        for group in groups {
+            for row in &group.employees {
+                push_row(&group.group_name, row);
+            }
+        }
+    }
+    for row in report.employees.iter().flatten() {
+        push_row("", row);
+    }
+
+    let totals = &report.totals;
+    output.push_str(&format!(
+        "Total,,,,{},{:.2},{:.2},{:.2},{},{},{},{},{},{}\n",
+        totals.scheduled_shifts,
+        totals.scheduled_hours,
+        totals.worked_hours,
+        totals.overtime_hours,
+        totals.late_arrivals,
+        totals.no_shows,
+        totals.dropped_shifts,
+        totals.time_off_requests,
+        totals.time_off_days,
+        csv_field(&period)
+    ));
+    output
+}
+
+/// Required against assigned headcount at a location, per interval of the period
+pub async fn coverage_heatmap(
+    ctx: &UserContext,
+    query: &CoverageQuery,
+) -> Result<CoverageHeatmap, AppError> {
+    ctx.requires_permission(Permission::STATS_VIEW)?;
+
+    let interval_minutes = query.interval_minutes.unwrap_or(60);
+    if !COVERAGE_INTERVALS.contains(&interval_minutes) {
+        return Err(AppError::BadRequest(
+            "intervalMinutes must be 15, 30 or 60".to_string(),
+        ));
+    }
+
+    let filter = build_filter(
+        ctx,
+        &StatsQuery {
+            start_date: query.start_date,
+            end_date: query.end_date,
+            user_id: None,
+            location_id: Some(query.location_id),
+            team_id: query.team_id,
+            compare: None,
+        },
+    )
+    .await?;
+
+    let location = location_repo::find_by_id(query.location_id)
+        .await?
+        .filter(|location| location.company_id == filter.company_id)
+        .ok_or_else(|| AppError::NotFound("Location not found".to_string()))?;
+
+    if (filter.end_date - filter.start_date).num_minutes() / interval_minutes as i64
+        > MAX_COVERAGE_BUCKETS
+    {
+        return Err(AppError::BadRequest(format!(
+            "The period holds more than {} intervals; shorten it or use longer intervals",
+            MAX_COVERAGE_BUCKETS
+        )));
+    }
+
+    let buckets = stats_repo::get_coverage_buckets(&filter, interval_minutes).await?;
+
+    Ok(CoverageHeatmap {
+        location_id: location.id,
+        team_id: query.team_id,
+        interval_minutes,
+        period_start: filter.start_date,
+        period_end: filter.end_date,
+        understaffed_buckets: buckets
+            .iter()
+            .filter(|bucket| bucket.status == "understaffed")
+            .count(),
+        overstaffed_buckets: buckets
+            .iter()
+            .filter(|bucket| bucket.status == "overstaffed")
+            .count(),
+        buckets,
+    })
+}
+
+/// How weekend, night, holiday and short-notice shifts are spread across the members of a
+/// team or location, with their hours against their weekly limits and a fairness score for
+/// the period and for each week or month of it
+pub async fn fairness_report(
+    ctx: &UserContext,
+    query: &FairnessQuery,
+) -> Result<FairnessReport, AppError> {
+    ctx.requires_permission(Permission::STATS_VIEW)?;
+
+    let trend = query.trend.as_deref().unwrap_or("week");
+    if !["week", "month"].contains(&trend) {
+        return Err(AppError::BadRequest(format!(
+            "Unknown trend '{}'; use week or month",
+            trend
+        )));
+    }
+    let holidays = query
+        .holidays
+        .as_deref()
+        .map(|holidays| {
+            holidays
+                .split(',')
+                .map(str::trim)
+                .filter(|date| !date.is_empty())
+                .map(|date| {
+                    NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+                        AppError::BadRequest(format!("Invalid holiday '{}'; use YYYY-MM-DD", date))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?;
+
+    let filter = build_filter(
+        ctx,
+        &StatsQuery {
+            start_date: query.start_date,
+            end_date: query.end_date,
+            user_id: None,
+            location_id: query.location_id,
+            team_id: query.team_id,
+            compare: None,
+        },
+    )
+    .await?;
+
+    // Without a list, the holidays the location, or the whole company, keeps
+    let holidays = match holidays {
+        Some(holidays) => holidays,
+        None => holiday_repo::get_observed(
+            filter.company_id,
+            query.location_id,
+            filter.start_date.date_naive(),
+            filter.end_date.date_naive(),
+        )
+        .await?
+        .into_iter()
+        .map(|holiday| holiday.date)
+        .collect(),
+    };
+
+    let rows = stats_repo::get_fairness_rows(&filter, trend, &holidays).await?;
+    let weeks = (filter.end_date - filter.start_date).num_seconds() as f64 / (7.0 * 86_400.0);
+
+    // One entry per member, in the name order the rows come in
+    let mut members: Vec<FairnessRow> = Vec::new();
+    for row in &rows {
+        match members.last_mut() {
+            Some(member) if member.user_id == row.user_id => {
+                member.shifts += row.shifts;
+                member.hours += row.hours;
+                member.weekend_shifts += row.weekend_shifts;
+                member.night_shifts += row.night_shifts;
+                member.holiday_shifts += row.holiday_shifts;
+                member.short_notice_shifts += row.short_notice_shifts;
+            }
+            _ => members.push(row.clone()),
+        }
+    }
+
+    let share = |count: i64, total: i64| {
+        if total == 0 {
+            0.0
+        } else {
+            (count as f64 / total as f64 * 1000.0).round() / 10.0
+        }
+    };
+    let total = |count: fn(&FairnessRow) -> i64| members.iter().map(count).sum::<i64>();
+    let (weekend, night, holiday, short_notice) = (
+        total(|member| member.weekend_shifts),
+        total(|member| member.night_shifts),
+        total(|member| member.holiday_shifts),
+        total(|member| member.short_notice_shifts),
+    );
+
+    let employees = members
+        .iter()
+        .map(|member| {
+            let hours_per_week = (member.hours / weeks * 10.0).round() / 10.0;
+            let hours_status = if member
+                .min_hours_per_week
+                .is_some_and(|min| hours_per_week < min as f64)
+            {
+                "below_min"
+            } else if member
+                .max_hours_per_week
+                .is_some_and(|max| hours_per_week > max as f64)
+            {
+                "above_max"
+            } else {
+                "within"
+            };
+
+            EmployeeFairness {
+                user_id: member.user_id,
+                name: member.name.clone(),
+                shifts: member.shifts,
+                weekend_shifts: member.weekend_shifts,
+                weekend_share: share(member.weekend_shifts, weekend),
+                night_shifts: member.night_shifts,
+                night_share: share(member.night_shifts, night),
+                holiday_shifts: member.holiday_shifts,
+                holiday_share: share(member.holiday_shifts, holiday),
+                short_notice_shifts: member.short_notice_shifts,
+                short_notice_share: share(member.short_notice_shifts, short_notice),
+                hours: member.hours,
+                hours_per_week,
+                min_hours_per_week: member.min_hours_per_week,
+                max_hours_per_week: member.max_hours_per_week,
+                hours_status: hours_status.to_string(),
+            }
+        })
+        .collect();
+
+    let mut periods: BTreeMap<NaiveDate, Vec<&FairnessRow>> = BTreeMap::new();
+    for row in &rows {
+        if let Some(trend_start) = row.trend_start {
+            periods.entry(trend_start).or_default().push(row);
+        }
+    }
+    let trend = periods
+        .into_iter()
+        .map(|(period_start, rows)| FairnessTrendPoint {
+            period_start,
+            score: fairness_score(rows.into_iter()),
+        })
+        .collect();
+
+    Ok(FairnessReport {
+        period_start: filter.start_date,
+        period_end: filter.end_date,
+        score: fairness_score(members.iter()),
+        employees,
+        trend,
+    })
+}
+
+/// 100 minus the Gini coefficient, in %, of the share of unpopular shifts among each member's
+/// shifts. Only members who worked count; 100 when nobody did.
+fn fairness_score<'a>(members: impl Iterator<Item = &'a FairnessRow>) -> f64 {
+    let rates: Vec<f64> = members
+        .filter(|member| member.shifts > 0)
+        .map(|member| {
+            (member.weekend_shifts
+                + member.night_shifts
+                + member.holiday_shifts
+                + member.short_notice_shifts) as f64
+                / member.shifts as f64
+        })
+        .collect();
+
+    let mean = rates.iter().sum::<f64>() / rates.len() as f64;
+    if rates.is_empty() || mean == 0.0 {
+        return 100.0;
+    }
+    let differences: f64 = rates
+        .iter()
+        .flat_map(|a| rates.iter().map(move |b| (a - b).abs()))
+        .sum();
+    let gini = differences / (2.0 * (rates.len() * rates.len()) as f64 * mean);
+
+    ((1.0 - gini) * 1000.0).round() / 10.0
+}
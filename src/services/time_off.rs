@@ -0,0 +1,248 @@
+use std::collections::{HashMap, HashSet};
+
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use uuid::Uuid;
+
+use crate::database::{
+    models::{PtoBalanceType, TimeOffDuration, TimeOffRequestInput, TimeOffType},
+    repositories::{company as company_repo, pto_balance as pto_repo, time_off as time_off_repo},
+};
+use crate::error::AppError;
+use crate::services::{holidays, pto_accrual::round_hours};
+
+/// Hours a time-off request takes from a balance
+#[derive(Debug, Clone)]
+pub struct TimeOffDeduction {
+    pub balance_type: PtoBalanceType,
+    pub hours: BigDecimal,
+    pub allow_negative: bool, // The company lets balances go below zero
+}
+
+/// Check the dates fit the duration: partial-day and hourly requests cover a single day
+pub fn validate_request(input: &TimeOffRequestInput) -> Result<(), AppError> {
+    if input.end_date < input.start_date {
+        return Err(AppError::BadRequest(
+            "End date must not be before the start date".to_string(),
+        ));
+    }
+
+    if input.duration_type != TimeOffDuration::FullDay
+        && input.start_date.date_naive() != input.end_date.date_naive()
+    {
+        return Err(AppError::BadRequest(
+            "Partial-day and hourly requests must start and end on the same day".to_string(),
+        ));
+    }
+
+    if input.duration_type == TimeOffDuration::Hourly && input.end_date == input.start_date {
+        return Err(AppError::BadRequest(
+            "An hourly request must end after it starts".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Work out what a request takes from the member's balance. Members with shifts scheduled
+/// during the request use their scheduled hours; others use the company's workday on
+/// weekdays. Company holidays take nothing, and neither do types without a balance, such as
+/// bereavement.
+pub async fn deduction(
+    user_id: Uuid,
+    company_id: Uuid,
+    request_type: &TimeOffType,
+    duration_type: TimeOffDuration,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Option<TimeOffDeduction>, AppError> {
+    let Some(balance_type) = request_type.balance_type() else {
+        return Ok(None);
+    };
+
+    let settings = company_repo::get_time_off_settings(company_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Company not found".to_string()))?;
+
+    let scheduled: HashMap<NaiveDate, BigDecimal> = time_off_repo::get_scheduled_hours(
+        user_id,
+        company_id,
+        start.date_naive(),
+        end.date_naive(),
+    )
+    .await?
+    .into_iter()
+    .collect();
+
+    // Members work at several locations, so only company-wide holidays count
+    let holidays =
+        holidays::observed_dates(company_id, start.date_naive(), end.date_naive()).await?;
+
+    Ok(Some(TimeOffDeduction {
+        balance_type,
+        hours: request_hours(
+            duration_type,
+            start,
+            end,
+            &scheduled,
+            &holidays,
+            &settings.workday_hours,
+        ),
+        allow_negative: settings.allow_negative_pto,
+    }))
+}
+
+/// Reject a deduction larger than the member's balance, unless the company allows negative
+/// balances
+pub async fn requires_balance(
+    user_id: Uuid,
+    company_id: Uuid,
+    deduction: &TimeOffDeduction,
+) -> Result<(), AppError> {
+    if deduction.allow_negative {
+        return Ok(());
+    }
+
+    let balance = pto_repo::get_balance_for_company(user_id, company_id)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!("PTO balance not found for user: {}", user_id))
+        })?;
+
+    let available = match deduction.balance_type {
+        PtoBalanceType::Pto => balance.pto_balance_hours,
+        PtoBalanceType::Sick => balance.sick_balance_hours,
+        PtoBalanceType::Personal => balance.personal_balance_hours,
+    };
+
+    if available < deduction.hours {
+        return Err(AppError::BadRequest(format!(
+            "Insufficient {} balance: requested {} hours, available {} hours",
+            deduction.balance_type,
+            deduction.hours.normalized(),
+            available.normalized()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Hours from `start` to `end` for the duration. A day counts the hours scheduled on it
+/// when the member has a schedule, and otherwise the workday on weekdays. Holidays count
+/// nothing.
+fn request_hours(
+    duration_type: TimeOffDuration,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    scheduled: &HashMap<NaiveDate, BigDecimal>,
+    holidays: &HashSet<NaiveDate>,
+    workday_hours: &BigDecimal,
+) -> BigDecimal {
+    let day_hours = |day: NaiveDate| {
+        if holidays.contains(&day) {
+            BigDecimal::from(0)
+        } else if !scheduled.is_empty() {
+            scheduled.get(&day).cloned().unwrap_or_default()
+        } else if day.weekday().number_from_monday() >= 6 {
+            BigDecimal::from(0)
+        } else {
+            workday_hours.clone()
+        }
+    };
+
+    let hours = match duration_type {
+        TimeOffDuration::FullDay => start
+            .date_naive()
+            .iter_days()
+            .take_while(|day| *day <= end.date_naive())
+            .map(day_hours)
+            .sum(),
+        TimeOffDuration::PartialDay => day_hours(start.date_naive()) / BigDecimal::from(2),
+        TimeOffDuration::Hourly if holidays.contains(&start.date_naive()) => BigDecimal::from(0),
+        TimeOffDuration::Hourly => {
+            BigDecimal::from((end - start).num_minutes()) / BigDecimal::from(60)
+        }
+    };
+
+    round_hours(hours)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::str::FromStr;
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, day, hour, 0, 0).unwrap()
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 3, day).unwrap()
+    }
+
+    #[test]
+    fn test_request_hours() {
+        let workday = BigDecimal::from(8);
+        let none = HashMap::new();
+        let no_holidays = HashSet::new();
+
+        // Friday 6 March to Monday 9 March without shifts skips the weekend
+        let hours = request_hours(
+            TimeOffDuration::FullDay,
+            at(6, 0),
+            at(9, 0),
+            &none,
+            &no_holidays,
+            &workday,
+        );
+        assert_eq!(hours, BigDecimal::from(16));
+
+        // A holiday on the Monday counts nothing
+        let holidays = HashSet::from([date(9)]);
+        let hours = request_hours(
+            TimeOffDuration::FullDay,
+            at(6, 0),
+            at(9, 0),
+            &none,
+            &holidays,
+            &workday,
+        );
+        assert_eq!(hours, BigDecimal::from(8));
+
+        // Scheduled shifts replace the workday, weekend included
+        let scheduled = HashMap::from([
+            (date(7), BigDecimal::from(6)),
+            (date(9), BigDecimal::from_str("4.5").unwrap()),
+        ]);
+        let hours = request_hours(
+            TimeOffDuration::FullDay,
+            at(6, 0),
+            at(9, 0),
+            &scheduled,
+            &no_holidays,
+            &workday,
+        );
+        assert_eq!(hours, BigDecimal::from_str("10.5").unwrap());
+
+        let hours = request_hours(
+            TimeOffDuration::PartialDay,
+            at(9, 9),
+            at(9, 13),
+            &scheduled,
+            &no_holidays,
+            &workday,
+        );
+        assert_eq!(hours, BigDecimal::from_str("2.25").unwrap());
+
+        let hours = request_hours(
+            TimeOffDuration::Hourly,
+            at(10, 9),
+            Utc.with_ymd_and_hms(2026, 3, 10, 11, 30, 0).unwrap(),
+            &none,
+            &no_holidays,
+            &workday,
+        );
+        assert_eq!(hours, BigDecimal::from_str("2.5").unwrap());
+    }
+}
@@ -1,20 +1,32 @@
 use std::future::Future;
 use std::pin::Pin;
 
-use actix_web::{FromRequest, HttpRequest, dev::Payload};
+use actix_web::{
+    FromRequest, HttpMessage, HttpRequest,
+    dev::Payload,
+    http::{Method, header::AUTHORIZATION},
+};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::services::api_keys;
 use crate::services::auth::Claims;
+use crate::services::two_factor;
 use crate::user_context;
 use crate::{
     database::models::{
+        api_key::ApiKey,
         company::{CompanyInfo, CompanyRole},
+        impersonation::ImpersonationSession,
+        role::Permission,
         user::User,
     },
     error::AppError,
-    repositories::{company as company_repo, user as user_repo},
+    repositories::{
+        company as company_repo, impersonation as impersonation_repo, role as role_repo,
+        session as session_repo, user as user_repo,
+    },
 };
 
 /// User context that contains the current user and their company information
@@ -23,6 +35,11 @@ use crate::{
 pub struct UserContext {
     pub user: User,
     pub company: Option<CompanyInfo>,
+    pub session_id: Option<Uuid>, // Session the access token belongs to, if any
+    pub mfa_verified: bool,       // Token was issued after a second factor check
+    pub api_key: Option<ApiKey>,  // Set when the request was made with an API key
+    pub permissions: Vec<String>, // Granted by the user's role in the current company
+    pub impersonation: Option<ImpersonationSession>, // Set when an admin is acting as the user
 }
 
 impl UserContext {
@@ -32,6 +49,20 @@ impl UserContext {
         let user = user_repo::find_by_id(claims.sub)
             .await?
             .ok_or_else(|| AppError::Unauthorized)?;
+        if user.is_erased() {
+            return Err(AppError::Unauthorized.into());
+        }
+
+        // Tokens tied to a session stop working as soon as the session is revoked
+        if let Some(session_id) = claims.sid {
+            let session = session_repo::find_by_id(session_id)
+                .await?
+                .ok_or_else(|| AppError::Unauthorized)?;
+
+            if session.user_id != user.id || !session.is_active() {
+                return Err(AppError::Unauthorized.into());
+            }
+        }
 
         // Get the company if company_id is present in claims
         let company = if let Some(company_id) = claims.company_id {
@@ -39,8 +70,78 @@ impl UserContext {
         } else {
             None
         };
+        let mut permissions = load_permissions(user.id, company.as_ref()).await?;
+
+        let impersonation = match (claims.act, claims.imp) {
+            (None, None) => None,
+            (Some(admin_id), Some(session_id)) => {
+                let session = load_impersonation(session_id, admin_id, &user, company.as_ref())
+                    .await?
+                    .ok_or_else(|| AppError::Unauthorized)?;
+                permissions
+                    .retain(|p| !Permission::BLOCKED_WHILE_IMPERSONATING.contains(&p.as_str()));
+                Some(session)
+            }
+            _ => return Err(AppError::Unauthorized.into()),
+        };
+
+        Ok(UserContext {
+            user,
+            company,
+            session_id: claims.sid,
+            mfa_verified: claims.mfa,
+            api_key: None,
+            permissions,
+            impersonation,
+        })
+    }
+
+    /// Create a UserContext for an API key request. Keys act with their creator's role in
+    /// the key's company, limited to the endpoints their scopes allow.
+    pub async fn from_api_key(
+        api_key: ApiKey,
+        method: &Method,
+        path: &str,
+    ) -> Result<Self, AppError> {
+        let scope = api_keys::required_scope(method, path).ok_or_else(|| {
+            AppError::Forbidden("API keys cannot access this endpoint".to_string())
+        })?;
+        if !api_key.has_scope(&scope) {
+            return Err(AppError::Forbidden(format!(
+                "API key is missing the {} scope",
+                scope
+            )));
+        }
+
+        let user = user_repo::find_by_id(api_key.created_by)
+            .await?
+            .ok_or_else(|| AppError::Unauthorized)?;
+
+        // Keys stop working once their creator leaves the company
+        let company = company_repo::find_user_company_info_by_id(user.id, api_key.company_id)
+            .await?
+            .ok_or_else(|| AppError::Unauthorized)?;
+        let permissions = load_permissions(user.id, Some(&company)).await?;
+
+        Ok(UserContext {
+            user,
+            company: Some(company),
+            session_id: None,
+            mfa_verified: false,
+            api_key: Some(api_key),
+            permissions,
+            impersonation: None,
+        })
+    }
 
-        Ok(UserContext { user, company })
+    /// Check if the request was made with an API key
+    pub fn is_api_key(&self) -> bool {
+        self.api_key.is_some()
+    }
+
+    /// Check if an admin is acting as the user
+    pub fn is_impersonating(&self) -> bool {
+        self.impersonation.is_some()
     }
 
     /// Get the user ID
@@ -83,6 +184,11 @@ impl UserContext {
         self.is_manager() || self.is_admin()
     }
 
+    /// Check if the user's role grants a permission in the current company
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|granted| granted == permission)
+    }
+
     /// Check if user has a specific role
     pub fn has_role(&self, role: &CompanyRole) -> bool {
         self.role() == Some(role)
@@ -107,20 +213,8 @@ impl UserContext {
             return true;
         }
 
-        // Admins and managers can access resources of users in their company
-        self.is_manager_or_admin()
-    }
-
-    /// Check if user can manage another user (for admin operations)
-    /// Only admins can manage other users
-    pub fn can_manage_user(&self, target_user_id: Uuid) -> bool {
-        // Users cannot manage themselves through admin operations
-        if self.user_id() == target_user_id {
-            return false;
-        }
-
-        // Only admins can manage other users
-        self.is_admin()
+        // Roles that can see other employees can access their resources
+        self.has_permission(Permission::EMPLOYEES_VIEW)
     }
 
     pub fn strict_company_id(&self) -> Result<Uuid, AppError> {
@@ -129,43 +223,43 @@ impl UserContext {
         })
     }
 
-    pub fn requires_admin(&self) -> Result<(), AppError> {
-        if !self.is_admin() {
-            return Err(AppError::PermissionDenied(
-                "Admin access required".to_string(),
-            ));
+    /// Companies can require admins and managers to sign in with a second factor
+    pub fn requires_two_factor_if_enforced(&self) -> Result<(), AppError> {
+        // API keys are scoped by the admin who issued them instead
+        if self.is_api_key() {
+            return Ok(());
         }
-        Ok(())
-    }
-
-    pub fn requires_admin_or(&self, message: Option<String>) -> Result<(), AppError> {
-        if !self.is_manager_or_admin() {
-            return Err(AppError::PermissionDenied(
-                message.unwrap_or_else(|| "Admin or manager access required".to_string()),
+        if two_factor::is_required_for(self.company.as_ref()) && !self.mfa_verified {
+            return Err(AppError::Forbidden(
+                "Two-factor authentication is required for your role".to_string(),
             ));
         }
         Ok(())
     }
 
-    pub fn requires_manager(&self) -> Result<(), AppError> {
-        if !self.is_manager_or_admin() {
-            return Err(AppError::PermissionDenied(
-                "Manager access required".to_string(),
+    /// Account credentials and pay stay out of reach of an admin acting as the user
+    pub fn requires_not_impersonating(&self) -> Result<(), AppError> {
+        if self.is_impersonating() {
+            return Err(AppError::Forbidden(
+                "This action is not allowed while impersonating a user".to_string(),
             ));
         }
         Ok(())
     }
 
-    pub fn requires_manager_or(&self, message: &str) -> Result<(), AppError> {
-        if !self.is_manager_or_admin() {
-            return Err(AppError::PermissionDenied(message.to_string()));
+    pub fn requires_permission(&self, permission: &str) -> Result<(), AppError> {
+        if !self.has_permission(permission) {
+            return Err(AppError::PermissionDenied(format!(
+                "Missing permission: {}",
+                permission
+            )));
         }
-        Ok(())
+        self.requires_two_factor_if_enforced()
     }
 
     pub fn requires_same_user(&self, target_user_id: Uuid) -> Result<(), AppError> {
-        // Managers and admins can access any user's resources
-        if self.is_manager_or_admin() || self.user_id() == target_user_id {
+        // Roles that can see other employees can access any user's resources
+        if self.has_permission(Permission::EMPLOYEES_VIEW) || self.user_id() == target_user_id {
             return Ok(());
         }
 
@@ -179,12 +273,24 @@ impl UserContext {
         target_user_id: Uuid,
         message: &str,
     ) -> Result<(), AppError> {
-        if self.is_manager_or_admin() || self.user_id() == target_user_id {
+        if self.has_permission(Permission::EMPLOYEES_VIEW) || self.user_id() == target_user_id {
             return Ok(());
         }
         Err(AppError::PermissionDenied(message.to_string()))
     }
 
+    /// Users can always act on their own resources; other users' need `permission`
+    pub fn requires_same_user_or_permission(
+        &self,
+        target_user_id: Uuid,
+        permission: &str,
+    ) -> Result<(), AppError> {
+        if self.user_id() == target_user_id {
+            return Ok(());
+        }
+        self.requires_permission(permission)
+    }
+
     pub fn requires_same_company(&self, target_company_id: Uuid) -> Result<(), AppError> {
         if self.company_id() != Some(target_company_id) {
             return Err(AppError::PermissionDenied(
@@ -205,8 +311,81 @@ impl UserContext {
     }
 }
 
+/// Permissions the user's role grants in a company; none outside a company
+async fn load_permissions(
+    user_id: Uuid,
+    company: Option<&CompanyInfo>,
+) -> Result<Vec<String>, sqlx::Error> {
+    match company {
+        Some(company) => role_repo::get_member_permissions(user_id, company.id).await,
+        None => Ok(Vec::new()),
+    }
+}
+
+/// The impersonation session behind a token, if it is still active and still matches the
+/// admin, the user and the company the token was issued for
+async fn load_impersonation(
+    session_id: Uuid,
+    admin_id: Uuid,
+    user: &User,
+    company: Option<&CompanyInfo>,
+) -> Result<Option<ImpersonationSession>, sqlx::Error> {
+    let Some(company) = company else {
+        return Ok(None);
+    };
+    let Some(session) = impersonation_repo::find_by_id(session_id).await? else {
+        return Ok(None);
+    };
+    if !session.is_active()
+        || session.admin_user_id != admin_id
+        || session.target_user_id != user.id
+        || session.company_id != company.id
+    {
+        return Ok(None);
+    }
+
+    // The admin loses the session as soon as they may no longer impersonate in the company
+    let admin_permissions = role_repo::get_member_permissions(admin_id, company.id).await?;
+    if !admin_permissions
+        .iter()
+        .any(|permission| permission == Permission::USERS_IMPERSONATE)
+    {
+        return Ok(None);
+    }
+
+    Ok(Some(session))
+}
+
 /// Extract UserContext from a request
 pub async fn extract_context(req: &HttpRequest) -> Result<UserContext, AppError> {
+    let api_key_token = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .filter(|token| api_keys::is_api_key(token))
+        .map(str::to_string);
+
+    if let Some(token) = api_key_token {
+        // ApiKeyMiddleware has usually resolved the key already
+        let resolved = req.extensions().get::<ApiKey>().cloned();
+        let api_key = match resolved {
+            Some(api_key) => api_key,
+            None => {
+                let ip_address = req
+                    .connection_info()
+                    .realip_remote_addr()
+                    .unwrap_or("unknown")
+                    .to_string();
+                api_keys::authenticate(&token, &ip_address)
+                    .await?
+                    .ok_or(AppError::Unauthorized)?
+            }
+        };
+
+        return UserContext::from_api_key(api_key, req.method(), req.path()).await;
+    }
+
     // Extract claims from the request
     let mut payload = Payload::None;
     let claims_result = Claims::from_request(req, &mut payload);
@@ -245,8 +424,17 @@ pub async fn get_user_context(
     } else {
         None
     };
-
-    Ok(UserContext { user, company })
+    let permissions = load_permissions(user.id, company.as_ref()).await?;
+
+    Ok(UserContext {
+        user,
+        company,
+        session_id: None,
+        mfa_verified: false,
+        api_key: None,
+        permissions,
+        impersonation: None,
+    })
 }
 
 impl FromRequest for UserContext {
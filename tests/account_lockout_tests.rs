@@ -0,0 +1,251 @@
+use actix_web::{App, http::StatusCode, test, web};
+use be::database::{
+    get_pool,
+    models::{AddEmployeeToCompanyInput, CompanyRole, LoginInput},
+    repositories::{account_lockout as account_lockout_repo, company as company_repo},
+    transaction::DatabaseTransaction,
+};
+use be::error::AppError;
+use be::handlers::admin;
+use be::middleware::CacheLayer;
+use be::services::auth as auth_service;
+use serial_test::serial;
+use uuid::Uuid;
+
+mod common;
+
+async fn attempt(email: &str, password: &str) -> Result<(), AppError> {
+    auth_service::login(
+        LoginInput {
+            email: email.to_string(),
+            password: password.to_string(),
+        },
+        &common::test_request_info(),
+    )
+    .await
+    .map(|_| ())
+    .map_err(AppError::from)
+}
+
+/// Let any progressive delay run out so the next attempt is checked
+async fn skip_delay(user_id: Uuid) {
+    sqlx::query(
+        "UPDATE account_lockouts SET locked_until = NOW() - INTERVAL '1 second' WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .execute(&get_pool().await)
+    .await
+    .unwrap();
+}
+
+/// Refused attempts fail the same way as a wrong password
+fn is_rejected(result: &Result<(), AppError>) -> bool {
+    matches!(
+        result,
+        Err(AppError::InternalServerError(Some(message))) if message.contains("Invalid email or password")
+    )
+}
+
+async fn lock_out(email: &str, user_id: Uuid) {
+    for _ in 0..5 {
+        skip_delay(user_id).await;
+        let result = attempt(email, "wrong_password").await;
+        assert!(is_rejected(&result));
+    }
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_failed_logins_delay_then_lock_account() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    // The test context turns activity logging off; this test checks the login events
+    unsafe {
+        std::env::remove_var("SKIP_ACTIVITY_LOG");
+    }
+
+    let (user_id, _, company_id) =
+        common::create_test_user_with_token("lockout@example.com", "password123", "Lockout")
+            .await
+            .unwrap();
+
+    // The first failures are only counted
+    for _ in 0..2 {
+        let result = attempt("lockout@example.com", "wrong_password").await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Invalid email or password")
+        );
+    }
+
+    // The third starts a delay that refuses even the right password
+    attempt("lockout@example.com", "wrong_password")
+        .await
+        .unwrap_err();
+    let delayed = attempt("lockout@example.com", "password123").await;
+    assert!(is_rejected(&delayed));
+
+    // Reaching the limit locks the account
+    skip_delay(user_id).await;
+    for _ in 0..2 {
+        attempt("lockout@example.com", "wrong_password")
+            .await
+            .unwrap_err();
+        let lockout = account_lockout_repo::find_by_user_id(user_id)
+            .await
+            .unwrap()
+            .unwrap();
+        if !lockout.is_locked() {
+            skip_delay(user_id).await;
+        }
+    }
+    let lockout = account_lockout_repo::find_by_user_id(user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(lockout.failed_attempts, 5);
+    assert!(lockout.is_locked());
+
+    let locked = attempt("lockout@example.com", "password123").await;
+    assert!(is_rejected(&locked));
+
+    let (failed, locked_events): (i64, i64) = sqlx::query_as(
+        "SELECT COUNT(*) FILTER (WHERE action = 'login_failed'), COUNT(*) FILTER (WHERE action = 'account_locked') \
+         FROM company_activity WHERE company_id = $1 AND entity_id = $2",
+    )
+    .bind(company_id)
+    .bind(user_id)
+    .fetch_one(&get_pool().await)
+    .await
+    .unwrap();
+    assert_eq!(failed, 5);
+    assert_eq!(locked_events, 1);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_concurrent_failures_cannot_pass_the_limit() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let (user_id, _, _) =
+        common::create_test_user_with_token("burst@example.com", "password123", "Burst")
+            .await
+            .unwrap();
+
+    // Each attempt is counted before its password is checked, so only the free ones and the
+    // one that starts the delay get that far
+    let results =
+        futures::future::join_all((0..8).map(|_| attempt("burst@example.com", "wrong_password")))
+            .await;
+    assert!(results.iter().all(is_rejected));
+
+    let lockout = account_lockout_repo::find_by_user_id(user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(lockout.failed_attempts, 3);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_successful_login_clears_failures() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let (user_id, _, _) =
+        common::create_test_user_with_token("recover@example.com", "password123", "Recover")
+            .await
+            .unwrap();
+
+    for _ in 0..2 {
+        attempt("recover@example.com", "wrong_password")
+            .await
+            .unwrap_err();
+    }
+    attempt("recover@example.com", "password123").await.unwrap();
+
+    assert!(
+        account_lockout_repo::find_by_user_id(user_id)
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_admin_can_unlock_account() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let (_, company_id, admin_token) = common::create_user_with_company(
+        "unlock-admin@example.com",
+        "password123",
+        "Unlock Admin",
+        "Unlock Co",
+    )
+    .await
+    .unwrap();
+    let (employee_id, _, _) =
+        common::create_test_user_with_token("locked@example.com", "password123", "Locked")
+            .await
+            .unwrap();
+    let (outsider_id, _, _) =
+        common::create_test_user_with_token("outsider@example.com", "password123", "Outsider")
+            .await
+            .unwrap();
+
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            company_repo::add_employee_to_company(
+                tx,
+                company_id,
+                &AddEmployeeToCompanyInput {
+                    user_id: employee_id,
+                    role: Some(CompanyRole::Employee),
+                    is_primary: Some(false),
+                    hire_date: None,
+                },
+            )
+            .await?;
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+
+    lock_out("locked@example.com", employee_id).await;
+    assert!(is_rejected(
+        &attempt("locked@example.com", "password123").await
+    ));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(CacheLayer::new(1000, 60)))
+            .service(
+                web::scope("/api/v1").service(
+                    web::scope("/admin")
+                        .route("/users/{id}/unlock", web::post().to(admin::unlock_user)),
+                ),
+            ),
+    )
+    .await;
+    let unlock = |user_id: Uuid| {
+        test::TestRequest::post()
+            .uri(&format!("/api/v1/admin/users/{}/unlock", user_id))
+            .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+            .to_request()
+    };
+
+    // Admins cannot unlock accounts outside their company
+    let resp = test::call_service(&app, unlock(outsider_id)).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = test::call_service(&app, unlock(employee_id)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    attempt("locked@example.com", "password123").await.unwrap();
+}
@@ -0,0 +1,356 @@
+use std::{io::Read, path::Path};
+
+use actix_web::{App, http::StatusCode, test, web};
+use be::config::config;
+use be::database::{
+    get_pool, models::CreateActivityInput, repositories::activity as activity_repo,
+    transaction::DatabaseTransaction,
+};
+use be::handlers::{admin, auth};
+use be::middleware::CacheLayer;
+use be::services::{activity_archive, activity_chain};
+use chrono::{Months, Utc};
+use flate2::read::GzDecoder;
+use serde_json::json;
+use serial_test::serial;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+mod common;
+
+macro_rules! app {
+    () => {
+        test::init_service(
+            App::new()
+                .app_data(web::Data::new(CacheLayer::new(1000, 60)))
+                .service(
+                    web::scope("/api/v1/admin")
+                        .route("/activity/verify", web::get().to(admin::verify_activity))
+                        .route(
+                            "/activity/retention",
+                            web::get().to(admin::get_activity_retention),
+                        )
+                        .route(
+                            "/activity/retention",
+                            web::put().to(admin::update_activity_retention),
+                        )
+                        .route(
+                            "/activity/archives",
+                            web::get().to(admin::get_activity_archives),
+                        )
+                        .route(
+                            "/activity/archives/{id}/download",
+                            web::get().to(admin::download_activity_archive),
+                        ),
+                ),
+        )
+        .await
+    };
+}
+
+fn get(token: &str, uri: &str) -> test::TestRequest {
+    test::TestRequest::get()
+        .uri(uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+}
+
+/// A company with four chained entries, the first three of them three years old.
+/// Returns the company and the admin's token.
+async fn setup() -> (Uuid, String) {
+    let (admin_id, company_id, token) = common::create_user_with_company(
+        "archive-admin@example.com",
+        "password123",
+        "Archive Admin",
+        "Archive Co",
+    )
+    .await
+    .unwrap();
+
+    for description in ["First", "Second", "Third", "Fourth"] {
+        let input = CreateActivityInput {
+            company_id,
+            user_id: Some(admin_id),
+            activity_type: "shift_management".to_string(),
+            entity_type: "shift".to_string(),
+            entity_id: Uuid::new_v4(),
+            action: "created".to_string(),
+            description: description.to_string(),
+            metadata: None,
+            ip_address: "127.0.0.1".to_string(),
+            user_agent: "tests".to_string(),
+            api_key_id: None,
+            impersonated_user_id: None,
+        };
+        DatabaseTransaction::run(|tx| {
+            Box::pin(async move {
+                activity_repo::log_activity(tx, input).await?;
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+    }
+
+    sqlx::query(
+        "UPDATE company_activity SET created_at = created_at - INTERVAL '3 years'
+         WHERE company_id = $1 AND chain_seq <= 3",
+    )
+    .bind(company_id)
+    .execute(&get_pool().await)
+    .await
+    .unwrap();
+
+    (company_id, token)
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_expired_activity_is_archived_and_downloadable() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (company_id, token) = setup().await;
+    let app = app!();
+    let dir = Path::new(&config().activity_archive_dir);
+
+    let run = activity_archive::run_archival(dir, Utc::now())
+        .await
+        .unwrap();
+    assert!(run.archives_created >= 1);
+
+    // Only the entries past the default two years left the database
+    let remaining: Vec<String> = sqlx::query_scalar(
+        "SELECT description FROM company_activity WHERE company_id = $1 ORDER BY chain_seq",
+    )
+    .bind(company_id)
+    .fetch_all(&get_pool().await)
+    .await
+    .unwrap();
+    assert_eq!(remaining, vec!["Fourth"]);
+
+    let resp = test::call_service(
+        &app,
+        get(&token, "/api/v1/admin/activity/archives").to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let archives = body["data"].as_array().unwrap();
+    assert_eq!(archives.len(), 1);
+    assert_eq!(archives[0]["entryCount"], 3);
+    assert_eq!(archives[0]["firstSeq"], 1);
+    assert_eq!(archives[0]["lastSeq"], 3);
+
+    let resp = test::call_service(
+        &app,
+        get(
+            &token,
+            &format!(
+                "/api/v1/admin/activity/archives/{}/download",
+                archives[0]["id"].as_str().unwrap()
+            ),
+        )
+        .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get("Content-Type").unwrap(),
+        "application/gzip"
+    );
+    let compressed = test::read_body(resp).await.to_vec();
+    assert_eq!(
+        format!("{:x}", Sha256::digest(&compressed)),
+        archives[0]["sha256"].as_str().unwrap()
+    );
+    let mut ndjson = String::new();
+    GzDecoder::new(compressed.as_slice())
+        .read_to_string(&mut ndjson)
+        .unwrap();
+    let descriptions: Vec<String> = ndjson
+        .lines()
+        .map(|line| {
+            let entry: serde_json::Value = serde_json::from_str(line).unwrap();
+            entry["description"].as_str().unwrap().to_string()
+        })
+        .collect();
+    assert_eq!(descriptions, vec!["First", "Second", "Third"]);
+
+    // The chain still verifies, picking up where the archive ends
+    let resp = test::call_service(
+        &app,
+        get(&token, "/api/v1/admin/activity/verify").to_request(),
+    )
+    .await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["verified"], true);
+    assert_eq!(body["data"]["archivedThrough"], 3);
+    assert_eq!(body["data"]["firstSeq"], 4);
+
+    // Deleting entries without archiving them is still caught
+    sqlx::query("DELETE FROM activity_archives WHERE company_id = $1")
+        .bind(company_id)
+        .execute(&get_pool().await)
+        .await
+        .unwrap();
+    let resp = test::call_service(
+        &app,
+        get(&token, "/api/v1/admin/activity/verify").to_request(),
+    )
+    .await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["verified"], false);
+    assert_eq!(
+        body["data"]["firstBreak"]["reason"],
+        "Entries 1 to 3 are missing and were not archived"
+    );
+
+    let _ = std::fs::remove_dir_all(dir.join(company_id.to_string()));
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_retention_is_validated_and_applied() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (company_id, token) = setup().await;
+    let app = app!();
+
+    let resp = test::call_service(
+        &app,
+        get(&token, "/api/v1/admin/activity/retention").to_request(),
+    )
+    .await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["months"], 24);
+
+    let put = |months: i32| {
+        test::TestRequest::put()
+            .uri("/api/v1/admin/activity/retention")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({ "months": months }))
+            .to_request()
+    };
+    let resp = test::call_service(&app, put(0)).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = test::call_service(&app, put(48)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Three-year-old entries are now within the retention period
+    activity_archive::run_archival(Path::new(&config().activity_archive_dir), Utc::now())
+        .await
+        .unwrap();
+    assert!(
+        activity_repo::get_archives(company_id)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_partition_takes_over_entries_from_default() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (company_id, _) = setup().await;
+    let pool = get_pool().await;
+
+    // Partitions only reach two months ahead, so a later entry lands in the default partition
+    sqlx::query(
+        "UPDATE company_activity SET created_at = created_at + INTERVAL '4 months'
+         WHERE company_id = $1 AND chain_seq = 4",
+    )
+    .bind(company_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let partition_of = || async {
+        sqlx::query_as::<_, (String, String)>(
+            "SELECT tableoid::REGCLASS::TEXT,
+                    'company_activity_' || to_char(created_at AT TIME ZONE 'UTC', 'YYYY_MM')
+             FROM company_activity WHERE company_id = $1 AND chain_seq = 4",
+        )
+        .bind(company_id)
+        .fetch_one(&get_pool().await)
+        .await
+        .unwrap()
+    };
+    let (partition, _) = partition_of().await;
+    assert_eq!(partition, "company_activity_default");
+
+    // Creating that month's partition moves the entry into it, and later runs keep working
+    let dir = Path::new(&config().activity_archive_dir);
+    let as_of = Utc::now() + Months::new(2);
+    let run = activity_archive::run_archival(dir, as_of).await.unwrap();
+    assert!(run.partitions_created >= 1);
+    let (partition, expected) = partition_of().await;
+    assert_eq!(partition, expected);
+    activity_archive::run_archival(dir, as_of).await.unwrap();
+
+    let _ = std::fs::remove_dir_all(dir.join(company_id.to_string()));
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_erasure_scrubs_archived_activity() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (company_id, token) = setup().await;
+    let dir = Path::new(&config().activity_archive_dir);
+    activity_archive::run_archival(dir, Utc::now())
+        .await
+        .unwrap();
+    let archived = activity_repo::get_archives(company_id).await.unwrap();
+    assert_eq!(archived.len(), 1);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(CacheLayer::new(1000, 60)))
+            .service(
+                web::scope("/api/v1/auth").route("/me/erase", web::post().to(auth::erase_account)),
+            ),
+    )
+    .await;
+    let req = test::TestRequest::post()
+        .uri("/api/v1/auth/me/erase")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "password": "password123" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // The archive was rewritten without the network details of the erased user's actions
+    let archive = activity_repo::get_archives(company_id)
+        .await
+        .unwrap()
+        .remove(0);
+    assert_ne!(archive.file_name, archived[0].file_name);
+    assert!(!dir.join(&archived[0].file_name).exists());
+    let compressed = std::fs::read(activity_archive::archive_path(dir, &archive)).unwrap();
+    assert_eq!(format!("{:x}", Sha256::digest(&compressed)), archive.sha256);
+    let mut ndjson = String::new();
+    GzDecoder::new(compressed.as_slice())
+        .read_to_string(&mut ndjson)
+        .unwrap();
+    for line in ndjson.lines() {
+        let entry: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(entry["ipAddress"], serde_json::Value::Null);
+        assert_eq!(entry["userAgent"], serde_json::Value::Null);
+        assert!(entry["redactedAt"].is_string());
+    }
+
+    // Archived and online entries are both covered by the redaction record
+    let result = activity_chain::verify(company_id).await.unwrap();
+    assert!(result.verified);
+    assert_eq!(result.redacted, 1);
+    let record: serde_json::Value = sqlx::query_scalar(
+        "SELECT metadata FROM company_activity WHERE company_id = $1 AND action = 'activity_redacted'",
+    )
+    .bind(company_id)
+    .fetch_one(&get_pool().await)
+    .await
+    .unwrap();
+    assert_eq!(record["redacted"].as_array().unwrap().len(), 4);
+
+    let _ = std::fs::remove_dir_all(dir.join(company_id.to_string()));
+}
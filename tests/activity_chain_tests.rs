@@ -0,0 +1,246 @@
+use actix_web::{App, http::StatusCode, test, web};
+use be::database::{
+    get_pool,
+    models::CreateActivityInput,
+    repositories::{activity as activity_repo, personal_data as personal_data_repo},
+    transaction::DatabaseTransaction,
+};
+use be::handlers::admin;
+use be::middleware::CacheLayer;
+use be::services::activity_chain;
+use serial_test::serial;
+use uuid::Uuid;
+
+mod common;
+
+macro_rules! app {
+    () => {
+        test::init_service(
+            App::new()
+                .app_data(web::Data::new(CacheLayer::new(1000, 60)))
+                .service(
+                    web::scope("/api/v1/admin")
+                        .route("/activity/verify", web::get().to(admin::verify_activity))
+                        .route(
+                            "/activity/checkpoints",
+                            web::get().to(admin::get_activity_checkpoints),
+                        )
+                        .route(
+                            "/activity/checkpoints",
+                            web::post().to(admin::create_activity_checkpoint),
+                        ),
+                ),
+        )
+        .await
+    };
+}
+
+/// A company with three chained activity entries by its admin. Returns the company, the admin
+/// and the admin's token.
+async fn setup() -> (Uuid, Uuid, String) {
+    let (admin_id, company_id, token) = common::create_user_with_company(
+        "chain-admin@example.com",
+        "password123",
+        "Chain Admin",
+        "Chain Co",
+    )
+    .await
+    .unwrap();
+
+    for description in ["First", "Second", "Third"] {
+        let input = CreateActivityInput {
+            company_id,
+            user_id: Some(admin_id),
+            activity_type: "shift_management".to_string(),
+            entity_type: "shift".to_string(),
+            entity_id: Uuid::new_v4(),
+            action: "created".to_string(),
+            description: description.to_string(),
+            metadata: Some(
+                [
+                    ("b".to_string(), serde_json::json!("x")),
+                    ("a".to_string(), serde_json::json!(1)),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+            ip_address: "127.0.0.1".to_string(),
+            user_agent: "tests".to_string(),
+            api_key_id: None,
+            impersonated_user_id: None,
+        };
+        DatabaseTransaction::run(|tx| {
+            Box::pin(async move {
+                activity_repo::log_activity(tx, input).await?;
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+    }
+
+    (company_id, admin_id, token)
+}
+
+fn verify_request(token: &str) -> test::TestRequest {
+    test::TestRequest::get()
+        .uri("/api/v1/admin/activity/verify")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+}
+
+macro_rules! verify {
+    ($app:expr, $token:expr) => {{
+        let resp = test::call_service(&$app, verify_request($token).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        body["data"].clone()
+    }};
+}
+
+async fn execute(query: &str, company_id: Uuid) {
+    sqlx::query(query)
+        .bind(company_id)
+        .execute(&get_pool().await)
+        .await
+        .unwrap();
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_chain_detects_edits_and_allows_redaction() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (company_id, admin_id, token) = setup().await;
+    let app = app!();
+
+    let result = verify!(app, &token);
+    assert_eq!(result["verified"], true);
+    assert_eq!(result["checked"], 3);
+    assert_eq!(result["headSeq"], 3);
+
+    // Marking an entry redacted does not excuse an edit without a redaction record
+    execute(
+        "UPDATE company_activity SET user_agent = NULL, redacted_at = NOW()
+         WHERE company_id = $1 AND chain_seq = 1",
+        company_id,
+    )
+    .await;
+    let result = verify!(app, &token);
+    assert_eq!(result["verified"], false);
+    assert_eq!(result["firstBreak"]["chainSeq"], 1);
+    assert_eq!(
+        result["firstBreak"]["reason"],
+        "Entry was redacted without a redaction record"
+    );
+
+    // Scrubbing personal data the way erasure does records the redaction in the chain
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let redacted = personal_data_repo::scrub_activity(
+                tx,
+                admin_id,
+                "chain-admin@example.com",
+                "erased@erased.invalid",
+            )
+            .await?;
+            activity_chain::record_redactions(tx, admin_id, &redacted).await?;
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+    let result = verify!(app, &token);
+    assert_eq!(result["verified"], true);
+    assert_eq!(result["checked"], 4);
+    assert_eq!(result["redacted"], 3);
+
+    // Any other edit breaks it at that entry
+    execute(
+        "UPDATE company_activity SET description = 'Edited'
+         WHERE company_id = $1 AND chain_seq = 2",
+        company_id,
+    )
+    .await;
+    let result = verify!(app, &token);
+    assert_eq!(result["verified"], false);
+    assert_eq!(result["firstBreak"]["chainSeq"], 2);
+    assert_eq!(result["firstBreak"]["reason"], "Entry content was modified");
+
+    execute(
+        "DELETE FROM company_activity WHERE company_id = $1 AND chain_seq = 2",
+        company_id,
+    )
+    .await;
+    let result = verify!(app, &token);
+    assert_eq!(result["firstBreak"]["chainSeq"], 3);
+    assert_eq!(result["firstBreak"]["reason"], "Entries 2 to 2 are missing");
+
+    // Removing the newest entries takes the redaction record with them
+    execute(
+        "DELETE FROM company_activity WHERE company_id = $1 AND chain_seq >= 2",
+        company_id,
+    )
+    .await;
+    let result = verify!(app, &token);
+    assert_eq!(result["verified"], false);
+    assert_eq!(result["firstBreak"]["chainSeq"], 1);
+    assert_eq!(
+        result["firstBreak"]["reason"],
+        "Entry was redacted without a redaction record"
+    );
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_checkpoints_are_signed_and_checked() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (company_id, _, token) = setup().await;
+    let app = app!();
+
+    let checkpoint = |method: test::TestRequest| {
+        method
+            .uri("/api/v1/admin/activity/checkpoints")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, checkpoint(test::TestRequest::post())).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["chainSeq"], 3);
+
+    // Nothing new to sign
+    let resp = test::call_service(&app, checkpoint(test::TestRequest::post())).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = test::call_service(&app, checkpoint(test::TestRequest::get())).await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let checkpoints = body["data"].as_array().unwrap();
+    assert_eq!(checkpoints.len(), 1);
+    assert_eq!(
+        checkpoints[0]["signature"]
+            .as_str()
+            .unwrap()
+            .split('.')
+            .count(),
+        3
+    );
+
+    let result = verify!(app, &token);
+    assert_eq!(result["verified"], true);
+    assert_eq!(result["checkpointsChecked"], 1);
+
+    // Rewriting the chain head and checkpoint together does not match the signature
+    execute(
+        "UPDATE activity_checkpoints SET row_hash = repeat('a', 64) WHERE company_id = $1",
+        company_id,
+    )
+    .await;
+    let result = verify!(app, &token);
+    assert_eq!(result["verified"], false);
+    assert_eq!(
+        result["firstBreak"]["reason"],
+        "Checkpoint does not match its signature"
+    );
+}
@@ -0,0 +1,364 @@
+use actix_web::{App, http::StatusCode, test, web};
+use be::database::{
+    models::{CreateActivityInput, LocationInput},
+    repositories::{activity as activity_repo, location as location_repo},
+    transaction::DatabaseTransaction,
+};
+use be::handlers::{admin, pto_balance};
+use be::middleware::CacheLayer;
+use serde_json::json;
+use serial_test::serial;
+use uuid::Uuid;
+
+mod common;
+
+macro_rules! app {
+    () => {
+        test::init_service(
+            App::new()
+                .app_data(web::Data::new(CacheLayer::new(1000, 60)))
+                .service(
+                    web::scope("/api/v1/admin")
+                        .route("/activity", web::get().to(admin::get_activity))
+                        .route("/activity/export", web::get().to(admin::export_activity))
+                        .route(
+                            "/activity/{entity_type}/{entity_id}",
+                            web::get().to(admin::get_entity_activity),
+                        )
+                        .route(
+                            "/activity/{entity_type}/{entity_id}/changes",
+                            web::get().to(admin::get_entity_changes),
+                        )
+                        .route("/locations/{id}", web::put().to(admin::update_location)),
+                )
+                .service(
+                    web::scope("/api/v1/pto-balance")
+                        .route("/{user_id}", web::put().to(pto_balance::update_pto_balance)),
+                ),
+        )
+        .await
+    };
+}
+
+fn get(token: &str, uri: &str) -> test::TestRequest {
+    test::TestRequest::get()
+        .uri(uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+}
+
+/// Log one entry in its own transaction so every entry gets a distinct timestamp
+async fn log(
+    company_id: Uuid,
+    entity_type: &str,
+    entity_id: Uuid,
+    action: &str,
+    description: &str,
+) {
+    let input = CreateActivityInput {
+        company_id,
+        user_id: None,
+        activity_type: "shift_management".to_string(),
+        entity_type: entity_type.to_string(),
+        entity_id,
+        action: action.to_string(),
+        description: description.to_string(),
+        metadata: None,
+        ip_address: "127.0.0.1".to_string(),
+        user_agent: "tests".to_string(),
+        api_key_id: None,
+        impersonated_user_id: None,
+    };
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            activity_repo::log_activity(tx, input).await?;
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_filter_paginate_and_timeline() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (_, company_id, token) = common::create_user_with_company(
+        "activity-admin@example.com",
+        "password123",
+        "Activity Admin",
+        "Activity Co",
+    )
+    .await
+    .unwrap();
+    let (_, other_company_id, _) = common::create_user_with_company(
+        "activity-other@example.com",
+        "password123",
+        "Other Admin",
+        "Other Co",
+    )
+    .await
+    .unwrap();
+
+    let shift_id = Uuid::new_v4();
+    log(company_id, "shift", shift_id, "created", "Shift created").await;
+    log(company_id, "shift", shift_id, "updated", "Shift moved").await;
+    log(company_id, "shift", shift_id, "assigned", "Shift assigned").await;
+    log(
+        company_id,
+        "team",
+        Uuid::new_v4(),
+        "created",
+        "Team created",
+    )
+    .await;
+    log(other_company_id, "shift", shift_id, "deleted", "Elsewhere").await;
+    let app = app!();
+
+    // Pages follow the cursor, newest first, and stay inside the company
+    let resp = test::call_service(
+        &app,
+        get(&token, "/api/v1/admin/activity?entityType=shift&limit=2").to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let items = body["data"]["items"].as_array().unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0]["description"], "Shift assigned");
+    let cursor = body["data"]["nextCursor"].as_str().unwrap().to_string();
+
+    let resp = test::call_service(
+        &app,
+        get(
+            &token,
+            &format!(
+                "/api/v1/admin/activity?entityType=shift&limit=2&cursor={}",
+                cursor
+            ),
+        )
+        .to_request(),
+    )
+    .await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let items = body["data"]["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["description"], "Shift created");
+    assert!(body["data"]["nextCursor"].is_null());
+
+    let resp = test::call_service(
+        &app,
+        get(&token, "/api/v1/admin/activity?action=created").to_request(),
+    )
+    .await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["items"].as_array().unwrap().len(), 2);
+
+    let resp = test::call_service(
+        &app,
+        get(&token, "/api/v1/admin/activity?cursor=garbage").to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // The timeline of one shift reads oldest first
+    let resp = test::call_service(
+        &app,
+        get(
+            &token,
+            &format!("/api/v1/admin/activity/shift/{}", shift_id),
+        )
+        .to_request(),
+    )
+    .await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let descriptions: Vec<&str> = body["data"]["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["description"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        descriptions,
+        vec!["Shift created", "Shift moved", "Shift assigned"]
+    );
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_export_formats_and_access() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (_, company_id, token) = common::create_user_with_company(
+        "export-admin@example.com",
+        "password123",
+        "Export Admin",
+        "Export Co",
+    )
+    .await
+    .unwrap();
+    let (_, employee_token, _) =
+        common::create_test_user_with_token("export-employee@example.com", "password123", "Emp")
+            .await
+            .unwrap();
+
+    let shift_id = Uuid::new_v4();
+    log(
+        company_id,
+        "shift",
+        shift_id,
+        "created",
+        "=HYPERLINK(\"x\")",
+    )
+    .await;
+    log(company_id, "shift", shift_id, "updated", "Moved, again").await;
+    let app = app!();
+
+    let resp = test::call_service(
+        &app,
+        get(&token, "/api/v1/admin/activity/export?format=csv").to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(
+        resp.headers()
+            .get("Content-Type")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("text/csv")
+    );
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("id,created_at,"));
+    assert!(lines[1].contains(",\"Moved, again\","));
+    assert!(lines[2].contains(",\"'=HYPERLINK(\"\"x\"\")\","));
+
+    let resp = test::call_service(
+        &app,
+        get(
+            &token,
+            "/api/v1/admin/activity/export?format=ndjson&action=updated",
+        )
+        .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    let entries: Vec<serde_json::Value> = body
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["entityId"], shift_id.to_string());
+
+    let resp = test::call_service(
+        &app,
+        get(&token, "/api/v1/admin/activity/export?format=xml").to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = test::call_service(
+        &app,
+        get(&employee_token, "/api/v1/admin/activity").to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_updates_record_field_changes() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    // The test context turns activity logging off; this test needs it
+    unsafe {
+        std::env::remove_var("SKIP_ACTIVITY_LOG");
+    }
+    let (admin_id, company_id, token) = common::create_user_with_company(
+        "changes-admin@example.com",
+        "password123",
+        "Changes Admin",
+        "Changes Co",
+    )
+    .await
+    .unwrap();
+    let location = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let location = location_repo::create_location(
+                tx,
+                LocationInput {
+                    company_id,
+                    name: "Depot".to_string(),
+                    address: Some("1 Main St".to_string()),
+                    phone: None,
+                    email: None,
+                },
+            )
+            .await?;
+            Ok(location)
+        })
+    })
+    .await
+    .unwrap();
+    let app = app!();
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/v1/admin/locations/{}", location.id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "name": "North Depot", "address": "1 Main St" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Only the fields that changed are recorded
+    let resp = test::call_service(
+        &app,
+        get(
+            &token,
+            &format!("/api/v1/admin/activity/location/{}/changes", location.id),
+        )
+        .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let items = body["data"]["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["userId"], admin_id.to_string());
+    assert_eq!(
+        items[0]["changes"],
+        json!({ "name": { "old": "Depot", "new": "North Depot" } })
+    );
+
+    // The balance update is diffed against the values written in the same transaction
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/v1/pto-balance/{}", admin_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "ptoBalanceHours": 16 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["ptoBalanceHours"], "16");
+
+    let resp = test::call_service(
+        &app,
+        get(
+            &token,
+            &format!("/api/v1/admin/activity/user/{}/changes", admin_id),
+        )
+        .to_request(),
+    )
+    .await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let items = body["data"]["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["action"], "update_pto_balance");
+    assert_eq!(
+        items[0]["changes"]["ptoBalanceHours"],
+        json!({ "old": "0", "new": "16" })
+    );
+}
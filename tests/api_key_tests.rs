@@ -0,0 +1,250 @@
+use actix_web::{App, http::Method, http::StatusCode, test, web};
+use be::database::{
+    get_pool,
+    models::{ApiKeyScope, CreateApiKeyInput, CreatedApiKeyResponse},
+    repositories::api_key as api_key_repo,
+    transaction::DatabaseTransaction,
+};
+use be::handlers::{company, stats};
+use be::middleware::{ApiKeyMiddleware, CacheLayer, GlobalRateLimiter, RequestInfoMiddleware};
+use be::services::{activity_logger, api_keys};
+use serial_test::serial;
+use uuid::Uuid;
+
+mod common;
+
+async fn create_key(
+    company_id: Uuid,
+    created_by: Uuid,
+    scopes: &[&str],
+    rate_limit_per_minute: Option<i32>,
+) -> CreatedApiKeyResponse {
+    let mut input = CreateApiKeyInput {
+        name: " Payroll sync ".to_string(),
+        scopes: scopes.iter().map(|s| s.to_string()).collect(),
+        rate_limit_per_minute,
+        expires_at: None,
+    };
+    api_keys::validate_create_input(&mut input).unwrap();
+
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move { api_keys::create(tx, company_id, created_by, &input).await })
+    })
+    .await
+    .unwrap()
+}
+
+#[actix_web::test]
+async fn test_required_scope_mapping() {
+    let scope = |method: Method, path: &str| api_keys::required_scope(&method, path);
+
+    assert_eq!(
+        scope(Method::GET, "/api/v1/shifts/123"),
+        Some("shifts:read".to_string())
+    );
+    assert_eq!(
+        scope(Method::POST, "/api/v1/time-off"),
+        Some("timeoff:write".to_string())
+    );
+    assert_eq!(
+        scope(Method::PUT, "/api/v1/admin/locations/1"),
+        Some("locations:write".to_string())
+    );
+    assert_eq!(
+        scope(Method::GET, "/api/v1/companies/employees"),
+        Some("employees:read".to_string())
+    );
+
+    // Key management and account endpoints are never reachable with a key
+    assert_eq!(scope(Method::GET, "/api/v1/companies/api-keys"), None);
+    assert_eq!(scope(Method::GET, "/api/v1/auth/me"), None);
+}
+
+#[actix_web::test]
+async fn test_validate_create_input_rejects_bad_settings() {
+    let input = |scopes: Vec<&str>, rate_limit_per_minute| CreateApiKeyInput {
+        name: "Integration".to_string(),
+        scopes: scopes.into_iter().map(String::from).collect(),
+        rate_limit_per_minute,
+        expires_at: None,
+    };
+
+    assert!(api_keys::validate_create_input(&mut input(vec![], None)).is_err());
+    assert!(api_keys::validate_create_input(&mut input(vec!["payroll:admin"], None)).is_err());
+    assert!(
+        api_keys::validate_create_input(&mut input(vec![ApiKeyScope::SHIFTS_READ], Some(0)))
+            .is_err()
+    );
+    assert!(
+        api_keys::validate_create_input(&mut input(vec![ApiKeyScope::SHIFTS_READ], Some(120)))
+            .is_ok()
+    );
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_api_key_authentication_and_revocation() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let (user_id, company_id, _) = common::create_user_with_company(
+        "keys-admin@example.com",
+        "password123",
+        "Keys Admin",
+        "Keys Co",
+    )
+    .await
+    .unwrap();
+
+    let created = create_key(company_id, user_id, &[ApiKeyScope::SHIFTS_WRITE], None).await;
+    assert!(created.key.starts_with(&created.api_key.prefix));
+    assert_eq!(created.api_key.name, "Payroll sync");
+    assert_eq!(created.api_key.rate_limit_per_minute, 60);
+    assert!(created.api_key.has_scope(ApiKeyScope::SHIFTS_READ));
+    assert!(!created.api_key.has_scope(ApiKeyScope::TIMEOFF_READ));
+
+    // Only the exact key authenticates, and usage is recorded
+    let authenticated = api_keys::authenticate(&created.key, "10.0.0.1")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(authenticated.id, created.api_key.id);
+    let tampered = format!("{}x", &created.key[..created.key.len() - 1]);
+    assert!(
+        api_keys::authenticate(&tampered, "10.0.0.1")
+            .await
+            .unwrap()
+            .is_none()
+    );
+    let stored = api_key_repo::find_by_prefix(&created.api_key.prefix)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(stored.last_used_ip.as_deref(), Some("10.0.0.1"));
+
+    // Revoked keys stop working immediately
+    let api_key_id = created.api_key.id;
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            api_key_repo::revoke_api_key(tx, company_id, api_key_id).await?;
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+    assert!(
+        api_keys::authenticate(&created.key, "10.0.0.1")
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_api_key_scopes_and_rate_limits_over_http() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let (user_id, company_id, _) = common::create_user_with_company(
+        "keys-http@example.com",
+        "password123",
+        "Keys Http",
+        "Keys Http Co",
+    )
+    .await
+    .unwrap();
+    let created = create_key(company_id, user_id, &[ApiKeyScope::STATS_READ], Some(2)).await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(CacheLayer::new(500, 60)))
+            .wrap(GlobalRateLimiter::general())
+            .wrap(ApiKeyMiddleware)
+            .wrap(RequestInfoMiddleware)
+            .service(
+                web::scope("/api/v1")
+                    .route(
+                        "/stats/dashboard",
+                        web::get().to(stats::get_dashboard_stats),
+                    )
+                    .route("/companies/api-keys", web::get().to(company::get_api_keys)),
+            ),
+    )
+    .await;
+    let request = |uri: &str, key: &str| {
+        test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("Authorization", format!("Bearer {}", key)))
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, request("/api/v1/stats/dashboard", &created.key)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // The creator is an admin, but keys can never manage keys
+    let resp = test::call_service(&app, request("/api/v1/companies/api-keys", &created.key)).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // The key's own limit of two requests per minute applies
+    let resp = test::call_service(&app, request("/api/v1/stats/dashboard", &created.key)).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let resp =
+        test::call_service(&app, request("/api/v1/stats/dashboard", "slk_unknown_key")).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_activity_is_attributed_to_api_key() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    // The test context turns activity logging off; this test needs it
+    unsafe {
+        std::env::remove_var("SKIP_ACTIVITY_LOG");
+    }
+
+    let (user_id, company_id, _) = common::create_user_with_company(
+        "keys-audit@example.com",
+        "password123",
+        "Keys Audit",
+        "Keys Audit Co",
+    )
+    .await
+    .unwrap();
+    let created = create_key(company_id, user_id, &[ApiKeyScope::SHIFTS_WRITE], None).await;
+
+    let mut req_info = common::test_request_info();
+    req_info.api_key_id = Some(created.api_key.id);
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            activity_logger::log_activity(
+                tx,
+                company_id,
+                Some(user_id),
+                "shift_management".to_string(),
+                "shift".to_string(),
+                Uuid::new_v4(),
+                "created".to_string(),
+                "Shift created".to_string(),
+                None,
+                &req_info,
+            )
+            .await?;
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+
+    let (logged_user_id, logged_api_key_id): (Option<Uuid>, Option<Uuid>) = sqlx::query_as(
+        "SELECT user_id, api_key_id FROM company_activity WHERE company_id = $1 AND entity_type = 'shift'",
+    )
+    .bind(company_id)
+    .fetch_one(&get_pool().await)
+    .await
+    .unwrap();
+    assert_eq!(logged_user_id, None);
+    assert_eq!(logged_api_key_id, Some(created.api_key.id));
+}
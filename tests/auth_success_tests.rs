@@ -1,4 +1,5 @@
 use actix_web::{App, http::StatusCode, test, web};
+use be::database::get_pool;
 use be::handlers::auth;
 use be::middleware::CacheLayer;
 use pretty_assertions::assert_eq;
@@ -179,6 +180,7 @@ async fn test_auth_invite_workflow() {
                         "/invite/{token}/accept",
                         web::post().to(auth::accept_invite),
                     )
+                    .route("/verify-email", web::post().to(auth::verify_email))
                     .route("/invites", web::get().to(auth::get_my_invites)),
             ),
         ),
@@ -245,6 +247,21 @@ async fn test_auth_invite_workflow() {
     let invitee_body: serde_json::Value = test::read_body_json(resp).await;
     let invitee_token = invitee_body["token"].as_str().unwrap();
 
+    // Invites can only be accepted once the invitee has verified their address
+    let (verification_token,): (String,) = sqlx::query_as(
+        "SELECT token FROM email_verification_tokens WHERE email = $1 AND used_at IS NULL",
+    )
+    .bind("invitee@example.com")
+    .fetch_one(&get_pool().await)
+    .await
+    .unwrap();
+    let req = test::TestRequest::post()
+        .uri("/api/v1/auth/verify-email")
+        .set_json(json!({ "token": verification_token }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
     // Test 3: Accept the invite with invitee's token and token in path
     let req = test::TestRequest::post()
         .uri(&format!("/api/v1/auth/invite/{}/accept", invite_token))
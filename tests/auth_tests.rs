@@ -1,5 +1,5 @@
-use be::database::models::{CreateUserInput, LoginInput};
-use be::services::auth as auth_service;
+use be::database::models::{CreateUserInput, LoginInput, LoginResponse, TwoFactorLoginInput};
+use be::services::{auth as auth_service, two_factor};
 use chrono::Utc;
 
 mod common;
@@ -15,7 +15,7 @@ async fn test_user_registration() {
         name: "Register User".to_string(),
     };
 
-    let result = auth_service::register(request).await;
+    let result = auth_service::register(request, &common::test_request_info()).await;
     assert!(result.is_ok());
 
     let response = result.unwrap();
@@ -36,7 +36,7 @@ async fn test_duplicate_email_registration() {
     };
 
     // First registration should succeed
-    let result1 = auth_service::register(request).await;
+    let result1 = auth_service::register(request, &common::test_request_info()).await;
     assert!(result1.is_ok());
 
     // Second registration with same email should fail
@@ -46,7 +46,7 @@ async fn test_duplicate_email_registration() {
         name: "Second User".to_string(),
     };
 
-    let result2 = auth_service::register(request2).await;
+    let result2 = auth_service::register(request2, &common::test_request_info()).await;
     assert!(result2.is_err());
     assert!(
         result2
@@ -68,7 +68,9 @@ async fn test_user_login() {
         name: "Login User".to_string(),
     };
 
-    auth_service::register(register_request).await.unwrap();
+    auth_service::register(register_request, &common::test_request_info())
+        .await
+        .unwrap();
 
     // Now try to login
     let login_request = LoginInput {
@@ -76,10 +78,12 @@ async fn test_user_login() {
         password: "password123".to_string(),
     };
 
-    let result = auth_service::login(login_request).await;
+    let result = auth_service::login(login_request, &common::test_request_info()).await;
     assert!(result.is_ok());
 
-    let response = result.unwrap();
+    let LoginResponse::Authenticated(response) = result.unwrap() else {
+        panic!("expected tokens for a user without 2FA");
+    };
     assert!(!response.token.is_empty());
     assert_eq!(response.user.email, "login@example.com");
     assert_eq!(response.user.name, "Login User");
@@ -97,7 +101,9 @@ async fn test_login_with_wrong_password() {
         name: "Wrong Pass User".to_string(),
     };
 
-    auth_service::register(register_request).await.unwrap();
+    auth_service::register(register_request, &common::test_request_info())
+        .await
+        .unwrap();
 
     // Try to login with wrong password
     let login_request = LoginInput {
@@ -105,7 +111,7 @@ async fn test_login_with_wrong_password() {
         password: "wrong_password".to_string(),
     };
 
-    let result = auth_service::login(login_request).await;
+    let result = auth_service::login(login_request, &common::test_request_info()).await;
     assert!(result.is_err());
     assert!(
         result
@@ -125,7 +131,7 @@ async fn test_login_with_nonexistent_email() {
         password: "password123".to_string(),
     };
 
-    let result = auth_service::login(login_request).await;
+    let result = auth_service::login(login_request, &common::test_request_info()).await;
     assert!(result.is_err());
     assert!(
         result
@@ -147,7 +153,9 @@ async fn test_jwt_token_verification() {
         name: "JWT User".to_string(),
     };
 
-    let auth_response = auth_service::register(register_request).await.unwrap();
+    let auth_response = auth_service::register(register_request, &common::test_request_info())
+        .await
+        .unwrap();
     let token = auth_response.token;
 
     // Verify token
@@ -185,7 +193,9 @@ async fn test_get_user_from_token() {
         name: "Token User".to_string(),
     };
 
-    let auth_response = auth_service::register(register_request).await.unwrap();
+    let auth_response = auth_service::register(register_request, &common::test_request_info())
+        .await
+        .unwrap();
     let token = auth_response.token;
 
     // Get user from token
@@ -219,17 +229,179 @@ async fn test_jwt_token_expiration_configuration() {
         name: "Expiry User".to_string(),
     };
 
-    let auth_response = auth_service::register(register_request).await.unwrap();
+    let auth_response = auth_service::register(register_request, &common::test_request_info())
+        .await
+        .unwrap();
     let token = auth_response.token;
 
     // Verify token and check expiration
     let claims = auth_service::verify_token(&token).unwrap();
 
-    // Should expire in 1 day (as configured in test context)
+    // Access tokens are short-lived (15 minutes by default)
     let now = Utc::now().timestamp() as usize;
-    let expected_expiry = now + (24 * 60 * 60); // 1 day in seconds
+    let expected_expiry = now + (15 * 60); // 15 minutes in seconds
 
     // Allow for some time difference in test execution
     assert!(claims.exp > now);
     assert!(claims.exp <= expected_expiry + 60); // Allow 1 minute buffer
 }
+
+#[tokio::test]
+async fn test_refresh_token_rotation() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let register_request = CreateUserInput {
+        email: "refresh@example.com".to_string(),
+        password: "password123".to_string(),
+        name: "Refresh User".to_string(),
+    };
+
+    let auth_response = auth_service::register(register_request, &common::test_request_info())
+        .await
+        .unwrap();
+    let refresh_token = auth_response.refresh_token.unwrap();
+    let session_id = auth_service::verify_token(&auth_response.token)
+        .unwrap()
+        .sid
+        .unwrap();
+
+    // Refreshing issues a new token pair for the same session
+    let refreshed = auth_service::refresh(&refresh_token, &common::test_request_info())
+        .await
+        .unwrap();
+    let new_refresh_token = refreshed.refresh_token.unwrap();
+    assert_ne!(new_refresh_token, refresh_token);
+    let claims = auth_service::verify_token(&refreshed.token).unwrap();
+    assert_eq!(claims.sid, Some(session_id));
+
+    // Replaying the rotated token is rejected and revokes the session
+    let replay = auth_service::refresh(&refresh_token, &common::test_request_info()).await;
+    assert!(replay.is_err());
+
+    let after_replay =
+        auth_service::refresh(&new_refresh_token, &common::test_request_info()).await;
+    assert!(after_replay.is_err());
+}
+
+#[tokio::test]
+async fn test_logout_revokes_session() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let register_request = CreateUserInput {
+        email: "logout@example.com".to_string(),
+        password: "password123".to_string(),
+        name: "Logout User".to_string(),
+    };
+
+    let auth_response = auth_service::register(register_request, &common::test_request_info())
+        .await
+        .unwrap();
+    let claims = auth_service::verify_token(&auth_response.token).unwrap();
+
+    // The access token works until its session is revoked
+    assert!(be::user_context::from_claims(&claims).await.is_ok());
+
+    auth_service::logout(claims.sub, claims.sid.unwrap())
+        .await
+        .unwrap();
+
+    assert!(be::user_context::from_claims(&claims).await.is_err());
+    let refresh = auth_service::refresh(
+        &auth_response.refresh_token.unwrap(),
+        &common::test_request_info(),
+    )
+    .await;
+    assert!(refresh.is_err());
+}
+
+#[tokio::test]
+async fn test_two_factor_login_flow() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let register_request = CreateUserInput {
+        email: "totp@example.com".to_string(),
+        password: "password123".to_string(),
+        name: "TOTP User".to_string(),
+    };
+    let registration = auth_service::register(register_request, &common::test_request_info())
+        .await
+        .unwrap();
+
+    // Enrol: the secret only becomes active once a valid code is confirmed
+    let setup = two_factor::begin_setup(&registration.user).await.unwrap();
+    assert!(setup.provisioning_uri.starts_with("otpauth://totp/"));
+    assert!(
+        two_factor::confirm_setup(registration.user.id, "000000", None)
+            .await
+            .is_err()
+    );
+    let code = two_factor::totp_code(&setup.secret, Utc::now().timestamp()).unwrap();
+    let recovery_codes = two_factor::confirm_setup(registration.user.id, &code, None)
+        .await
+        .unwrap();
+    assert_eq!(recovery_codes.len(), 10);
+
+    // Password alone now only yields a challenge
+    let login_request = LoginInput {
+        email: "totp@example.com".to_string(),
+        password: "password123".to_string(),
+    };
+    let LoginResponse::TwoFactorRequired(challenge) =
+        auth_service::login(login_request, &common::test_request_info())
+            .await
+            .unwrap()
+    else {
+        panic!("expected a two-factor challenge");
+    };
+    assert!(auth_service::verify_token(&challenge.mfa_token).is_err());
+
+    // The code used for enrolment cannot be replayed
+    let replay = auth_service::login_with_two_factor(
+        TwoFactorLoginInput {
+            mfa_token: challenge.mfa_token.clone(),
+            code,
+        },
+        &common::test_request_info(),
+    )
+    .await;
+    assert!(replay.is_err());
+
+    // A recovery code works exactly once
+    let response = auth_service::login_with_two_factor(
+        TwoFactorLoginInput {
+            mfa_token: challenge.mfa_token.clone(),
+            code: recovery_codes[0].clone(),
+        },
+        &common::test_request_info(),
+    )
+    .await
+    .unwrap();
+    assert!(auth_service::verify_token(&response.token).unwrap().mfa);
+
+    let reused = auth_service::login_with_two_factor(
+        TwoFactorLoginInput {
+            mfa_token: challenge.mfa_token.clone(),
+            code: recovery_codes[0].clone(),
+        },
+        &common::test_request_info(),
+    )
+    .await;
+    assert!(reused.is_err());
+
+    // Even when it is sent twice at once
+    let req_info = common::test_request_info();
+    let attempt = || {
+        auth_service::login_with_two_factor(
+            TwoFactorLoginInput {
+                mfa_token: challenge.mfa_token.clone(),
+                code: recovery_codes[1].clone(),
+            },
+            &req_info,
+        )
+    };
+    let (first, second) = tokio::join!(attempt(), attempt());
+    assert!(first.is_ok() != second.is_ok());
+}
@@ -6,6 +6,7 @@ use be::config::Config;
 use be::database::models::{AddEmployeeToCompanyInput, CompanyRole, CreateCompanyInput, User};
 use be::database::repositories::{company as company_repo, user as user_repo};
 use be::database::transaction::DatabaseTransaction;
+use be::middleware::request_info::RequestInfo;
 use be::services::auth;
 use chrono::Utc;
 use sqlx::PgPool;
@@ -84,6 +85,19 @@ pub fn setup_test_env() {
     let _ = env_logger::builder().is_test(true).try_init();
 }
 
+/// Request metadata used when calling services that record client details
+pub fn test_request_info() -> RequestInfo {
+    RequestInfo {
+        user_agent: "test-agent".to_string(),
+        ip_address: "127.0.0.1".to_string(),
+        method: "POST".to_string(),
+        path: "/test".to_string(),
+        api_key_id: None,
+        impersonator_id: None,
+        impersonated_user_id: None,
+    }
+}
+
 /// Create a simple configuration data for tests
 pub async fn create_test_config() -> web::Data<Config> {
     let config = Config {
@@ -91,7 +105,17 @@ pub async fn create_test_config() -> web::Data<Config> {
             .unwrap_or_else(|_| "postgres://localhost:5432/shiftlinkr_test".to_string()),
         run_migrations: true,
         jwt_secret: "test-jwt-secret-key".to_string(),
+        jwt_algorithm: "HS256".to_string(),
+        jwt_key_id: "primary".to_string(),
+        jwt_private_key_path: None,
+        jwt_public_key_path: None,
+        jwt_previous_keys: vec![],
         jwt_expiration_days: 1,
+        access_token_minutes: 15,
+        impersonation_minutes: 30,
+        activity_checkpoint_minutes: 60,
+        activity_archive_dir: "./data/activity-archives".to_string(),
+        activity_archive_hours: 24,
         host: "127.0.0.1".to_string(),
         port: 0,
         environment: "test".to_string(),
@@ -119,6 +143,8 @@ pub async fn create_test_user_with_token(
                 name: name_s,
                 email: email_s.clone(),
                 password_hash: bcrypt::hash(password_s, 4).unwrap(),
+                email_verified_at: Some(Utc::now()),
+                erased_at: None,
                 created_at: Utc::now(),
                 updated_at: Utc::now(),
             };
@@ -278,6 +304,8 @@ pub async fn create_admin_user_str(email: &str) -> Result<Uuid> {
                 name: "Admin User".to_string(),
                 email: email_s,
                 password_hash: bcrypt::hash("password", 4).unwrap(),
+                email_verified_at: Some(Utc::now()),
+                erased_at: None,
                 created_at: Utc::now(),
                 updated_at: Utc::now(),
             };
@@ -97,7 +97,17 @@ fn test_config_environment_detection() {
         database_url: "test".to_string(),
         run_migrations: true,
         jwt_secret: "test".to_string(),
+        jwt_algorithm: "HS256".to_string(),
+        jwt_key_id: "primary".to_string(),
+        jwt_private_key_path: None,
+        jwt_public_key_path: None,
+        jwt_previous_keys: vec![],
         jwt_expiration_days: 1,
+        access_token_minutes: 15,
+        impersonation_minutes: 30,
+        activity_checkpoint_minutes: 60,
+        activity_archive_dir: "./data/activity-archives".to_string(),
+        activity_archive_hours: 24,
         host: "localhost".to_string(),
         port: 8080,
         environment: "production".to_string(),
@@ -108,7 +118,17 @@ fn test_config_environment_detection() {
         database_url: "test".to_string(),
         run_migrations: true,
         jwt_secret: "test".to_string(),
+        jwt_algorithm: "HS256".to_string(),
+        jwt_key_id: "primary".to_string(),
+        jwt_private_key_path: None,
+        jwt_public_key_path: None,
+        jwt_previous_keys: vec![],
         jwt_expiration_days: 1,
+        access_token_minutes: 15,
+        impersonation_minutes: 30,
+        activity_checkpoint_minutes: 60,
+        activity_archive_dir: "./data/activity-archives".to_string(),
+        activity_archive_hours: 24,
         host: "localhost".to_string(),
         port: 8080,
         environment: "development".to_string(),
@@ -128,7 +148,17 @@ fn test_server_address_formatting() {
         database_url: "test".to_string(),
         run_migrations: true,
         jwt_secret: "test".to_string(),
+        jwt_algorithm: "HS256".to_string(),
+        jwt_key_id: "primary".to_string(),
+        jwt_private_key_path: None,
+        jwt_public_key_path: None,
+        jwt_previous_keys: vec![],
         jwt_expiration_days: 1,
+        access_token_minutes: 15,
+        impersonation_minutes: 30,
+        activity_checkpoint_minutes: 60,
+        activity_archive_dir: "./data/activity-archives".to_string(),
+        activity_archive_hours: 24,
         host: "192.168.1.1".to_string(),
         port: 9000,
         environment: "test".to_string(),
@@ -16,6 +16,8 @@ async fn test_create_user() {
         name: "Test User".to_string(),
         email: "test@example.com".to_string(),
         password_hash: "hashed_password".to_string(),
+        email_verified_at: None,
+        erased_at: None,
         created_at: Utc::now(),
         updated_at: Utc::now(),
     };
@@ -41,6 +43,8 @@ async fn test_find_user_by_email() {
         name: "Find Me".to_string(),
         email: "findme@example.com".to_string(),
         password_hash: "hashed_password".to_string(),
+        email_verified_at: None,
+        erased_at: None,
         created_at: Utc::now(),
         updated_at: Utc::now(),
     };
@@ -72,6 +76,8 @@ async fn test_find_user_by_id() {
         name: "Find By ID".to_string(),
         email: "findbyid@example.com".to_string(),
         password_hash: "hashed_password".to_string(),
+        email_verified_at: None,
+        erased_at: None,
         created_at: Utc::now(),
         updated_at: Utc::now(),
     };
@@ -108,6 +114,8 @@ async fn test_email_exists() {
         name: "Exists User".to_string(),
         email: "exists@example.com".to_string(),
         password_hash: "hashed_password".to_string(),
+        email_verified_at: None,
+        erased_at: None,
         created_at: Utc::now(),
         updated_at: Utc::now(),
     };
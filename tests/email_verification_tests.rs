@@ -0,0 +1,211 @@
+use actix_web::{App, http::StatusCode, test, web};
+use be::database::{
+    models::{AuthResponse, ChangeEmailInput, CompanyRole, CreateUserInput, LoginInput},
+    repositories::{invite as invite_repo, user as user_repo},
+    transaction::DatabaseTransaction,
+};
+use be::error::AppError;
+use be::handlers::auth;
+use be::middleware::CacheLayer;
+use be::services::{auth as auth_service, email_verification};
+use serial_test::serial;
+
+mod common;
+
+async fn register(email: &str) -> AuthResponse {
+    auth_service::register(
+        CreateUserInput {
+            email: email.to_string(),
+            password: "password123".to_string(),
+            name: "New User".to_string(),
+        },
+        &common::test_request_info(),
+    )
+    .await
+    .unwrap()
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_registration_sends_verification() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let response = register("verify-me@example.com").await;
+    assert!(!response.user.is_email_verified());
+
+    // Asking again replaces the first link
+    let first_token = email_verification::resend(&response.user).await.unwrap();
+    let token = email_verification::resend(&response.user).await.unwrap();
+    let stale = email_verification::verify_email(&first_token, &common::test_request_info()).await;
+    assert!(matches!(stale, Err(AppError::BadRequest(_))));
+
+    let user = email_verification::verify_email(&token, &common::test_request_info())
+        .await
+        .unwrap();
+    assert!(user.is_email_verified());
+    assert_eq!(user.email, "verify-me@example.com");
+
+    // Links work once, and verified accounts have nothing to resend
+    let reused = email_verification::verify_email(&token, &common::test_request_info()).await;
+    assert!(matches!(reused, Err(AppError::BadRequest(_))));
+    assert!(matches!(
+        email_verification::resend(&user).await,
+        Err(AppError::BadRequest(_))
+    ));
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_unverified_user_cannot_accept_invite() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let (admin_id, company_id, _) = common::create_user_with_company(
+        "verify-admin@example.com",
+        "password123",
+        "Verify Admin",
+        "Verify Co",
+    )
+    .await
+    .unwrap();
+    let invite = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            Ok(invite_repo::create_invite_token(
+                tx,
+                "invitee@example.com",
+                admin_id,
+                CompanyRole::Employee,
+                company_id,
+                None,
+            )
+            .await?)
+        })
+    })
+    .await
+    .unwrap();
+
+    let invitee = register("invitee@example.com").await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(CacheLayer::new(1000, 60)))
+            .service(
+                web::scope("/api/v1").service(
+                    web::scope("/auth")
+                        .route(
+                            "/verify-email/resend",
+                            web::post().to(auth::resend_verification),
+                        )
+                        .route("/verify-email", web::post().to(auth::verify_email))
+                        .route(
+                            "/invite/{token}/accept",
+                            web::post().to(auth::accept_invite),
+                        )
+                        .route("/invites", web::get().to(auth::get_my_invites)),
+                ),
+            ),
+    )
+    .await;
+    let accept = || {
+        test::TestRequest::post()
+            .uri(&format!("/api/v1/auth/invite/{}/accept", invite.token))
+            .insert_header(("Authorization", format!("Bearer {}", invitee.token)))
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, accept()).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/auth/invites")
+        .insert_header(("Authorization", format!("Bearer {}", invitee.token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // Outside production the new link is returned for testing
+    let req = test::TestRequest::post()
+        .uri("/api/v1/auth/verify-email/resend")
+        .insert_header(("Authorization", format!("Bearer {}", invitee.token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/auth/verify-email")
+        .set_json(serde_json::json!({ "token": token }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = test::call_service(&app, accept()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_email_change_applies_after_verification() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let (user_id, _, _) =
+        common::create_test_user_with_token("old@example.com", "password123", "Changer")
+            .await
+            .unwrap();
+    common::create_test_user_with_token("taken@example.com", "password123", "Taken")
+        .await
+        .unwrap();
+    let user = user_repo::find_by_id(user_id).await.unwrap().unwrap();
+
+    let change = |new_email: &str, password: &str| ChangeEmailInput {
+        new_email: new_email.to_string(),
+        password: password.to_string(),
+    };
+    assert!(matches!(
+        email_verification::request_email_change(&user, &change("new@example.com", "wrong")).await,
+        Err(AppError::BadRequest(_))
+    ));
+    assert!(matches!(
+        email_verification::request_email_change(
+            &user,
+            &change("taken@example.com", "password123")
+        )
+        .await,
+        Err(AppError::BadRequest(_))
+    ));
+
+    let token =
+        email_verification::request_email_change(&user, &change("new@example.com", "password123"))
+            .await
+            .unwrap();
+
+    // Nothing changes until the new address is confirmed
+    let unchanged = user_repo::find_by_id(user_id).await.unwrap().unwrap();
+    assert_eq!(unchanged.email, "old@example.com");
+    assert!(unchanged.is_email_verified());
+
+    let changed = email_verification::verify_email(&token, &common::test_request_info())
+        .await
+        .unwrap();
+    assert_eq!(changed.email, "new@example.com");
+    assert!(changed.is_email_verified());
+
+    let login = auth_service::login(
+        LoginInput {
+            email: "new@example.com".to_string(),
+            password: "password123".to_string(),
+        },
+        &common::test_request_info(),
+    )
+    .await;
+    assert!(login.is_ok());
+    assert!(
+        user_repo::find_by_email("old@example.com")
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
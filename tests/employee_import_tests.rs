@@ -0,0 +1,277 @@
+use actix_web::{App, http::StatusCode, test, web};
+use be::database::{
+    get_pool,
+    models::{
+        AddEmployeeToCompanyInput, CompanyRole, CreateUpdateTeamInput, LocationInput,
+        ProficiencyLevel, SkillInput,
+    },
+    repositories::{
+        company as company_repo, location as location_repo, pto_balance as pto_balance_repo,
+        skill as skill_repo, team as team_repo, user as user_repo,
+    },
+    transaction::DatabaseTransaction,
+};
+use be::handlers::company;
+use be::middleware::CacheLayer;
+use be::services::auth as auth_service;
+use bigdecimal::BigDecimal;
+use serial_test::serial;
+use std::str::FromStr;
+use uuid::Uuid;
+
+mod common;
+
+macro_rules! app {
+    () => {
+        test::init_service(
+            App::new()
+                .app_data(web::Data::new(CacheLayer::new(1000, 60)))
+                .service(web::scope("/api/v1/companies").route(
+                    "/employees/import",
+                    web::post().to(company::import_employees),
+                )),
+        )
+        .await
+    };
+}
+
+fn import(token: &str, dry_run: bool, csv: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri(&format!(
+            "/api/v1/companies/employees/import?dryRun={}",
+            dry_run
+        ))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .insert_header(("Content-Type", "text/csv"))
+        .set_payload(csv.to_string())
+}
+
+/// A company with one location, one team there and a forklift skill. Returns the company,
+/// the admin's token and the team.
+async fn setup() -> (Uuid, String, Uuid) {
+    let (_, company_id, token) = common::create_user_with_company(
+        "import-admin@example.com",
+        "password123",
+        "Import Admin",
+        "Import Co",
+    )
+    .await
+    .unwrap();
+
+    let team_id = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let location = location_repo::create_location(
+                tx,
+                LocationInput {
+                    company_id,
+                    name: "Warehouse".to_string(),
+                    address: None,
+                    phone: None,
+                    email: None,
+                },
+            )
+            .await?;
+            let team = team_repo::create_team(
+                tx,
+                CreateUpdateTeamInput {
+                    name: "Loaders".to_string(),
+                    description: None,
+                    location_id: location.id,
+                },
+            )
+            .await?;
+            skill_repo::create_skill(
+                tx,
+                company_id,
+                SkillInput {
+                    name: "Forklift".to_string(),
+                    description: None,
+                },
+            )
+            .await?;
+            Ok(team.id)
+        })
+    })
+    .await
+    .unwrap();
+
+    (company_id, token, team_id)
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_import_validates_then_commits_everything() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (company_id, token, team_id) = setup().await;
+    let app = app!();
+
+    let csv = "Name,Email,Role,Hire Date,Team,Location,Hourly Rate,PTO Hours,Skills\n\
+               Ada Loader,ada@example.com,employee,2026-01-05,Loaders,Warehouse,21.50,40,forklift:expert\n\
+               Bob Broken,bob@example.com,boss,05/01/2026,Packers,,-3,many,Welding\n\
+               ,ada@example.com,,,,,,,\n";
+
+    // The dry run reports every problem on every line
+    let resp = test::call_service(&app, import(&token, true, csv).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["imported"], false);
+    assert_eq!(body["data"]["failed"], 2);
+    let results = body["data"]["results"].as_array().unwrap();
+    assert_eq!(results[0]["valid"], true);
+    assert_eq!(results[0]["newUser"], true);
+    assert_eq!(results[1]["errors"].as_array().unwrap().len(), 6);
+    assert_eq!(results[2]["line"], 4);
+    assert!(
+        results[2]["errors"]
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!(
+                "Email appears more than once in the file"
+            ))
+    );
+
+    // A failing row stops the whole import even without a dry run
+    let resp = test::call_service(&app, import(&token, false, csv).to_request()).await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["imported"], false);
+    assert!(
+        user_repo::find_by_email("ada@example.com")
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    // An existing account is added to the company instead of being created
+    let (existing_id, _, _) =
+        common::create_test_user_with_token("carol@example.com", "password123", "Carol")
+            .await
+            .unwrap();
+    let csv = "email,name,team,hourly_rate,pto_hours,skills\n\
+               ada@example.com,Ada Loader,Loaders,21.50,40,forklift:expert\n\
+               carol@example.com,,,,8,\n";
+    let resp = test::call_service(&app, import(&token, false, csv).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["imported"], true);
+    assert_eq!(body["data"]["results"][1]["newUser"], false);
+    assert_eq!(
+        body["data"]["results"][1]["userId"],
+        existing_id.to_string()
+    );
+
+    let ada = user_repo::find_by_email("ada@example.com")
+        .await
+        .unwrap()
+        .unwrap();
+    let balance = pto_balance_repo::get_balance_for_company(ada.id, company_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(balance.pto_balance_hours, BigDecimal::from(40));
+    let members = team_repo::get_team_members(team_id).await.unwrap();
+    assert!(members.iter().any(|member| member.user_id == ada.id));
+    let levels: Vec<String> =
+        sqlx::query_scalar("SELECT proficiency_level FROM user_skills WHERE user_id = $1")
+            .bind(ada.id)
+            .fetch_all(&get_pool().await)
+            .await
+            .unwrap();
+    assert_eq!(levels, vec![ProficiencyLevel::Expert.to_string()]);
+    let rate: BigDecimal = sqlx::query_scalar(
+        "SELECT hourly_rate FROM wage_history WHERE user_id = $1 AND company_id = $2",
+    )
+    .bind(ada.id)
+    .bind(company_id)
+    .fetch_one(&get_pool().await)
+    .await
+    .unwrap();
+    assert_eq!(rate, BigDecimal::from_str("21.50").unwrap());
+
+    let carol = pto_balance_repo::get_balance_for_company(existing_id, company_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(carol.pto_balance_hours, BigDecimal::from(8));
+
+    // Importing the same people again is rejected per row
+    let resp = test::call_service(&app, import(&token, true, csv).to_request()).await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["failed"], 2);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_import_checks_permissions() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (company_id, admin_token, _) = setup().await;
+    let (manager_id, _, _) =
+        common::create_test_user_with_token("import-manager@example.com", "password123", "Mgr")
+            .await
+            .unwrap();
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            company_repo::add_employee_to_company(
+                tx,
+                company_id,
+                &AddEmployeeToCompanyInput {
+                    user_id: manager_id,
+                    role: Some(CompanyRole::Manager),
+                    is_primary: Some(false),
+                    hire_date: None,
+                },
+            )
+            .await?;
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+    let manager_token = auth_service::generate_company_token(manager_id, company_id)
+        .await
+        .unwrap();
+    let app = app!();
+
+    // Managers cannot set wages or import admins
+    let resp = test::call_service(
+        &app,
+        import(
+            &manager_token,
+            true,
+            "name,email,hourly_rate\nDan,dan@example.com,20\n",
+        )
+        .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = test::call_service(
+        &app,
+        import(
+            &manager_token,
+            true,
+            "name,email,role\nDan,dan@example.com,admin\n",
+        )
+        .to_request(),
+    )
+    .await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(
+        body["data"]["results"][0]["errors"][0],
+        "Importing admins requires the roles.manage permission"
+    );
+
+    // The header must name known columns
+    let resp = test::call_service(
+        &app,
+        import(
+            &admin_token,
+            true,
+            "name,email,shoe_size\nDan,dan@example.com,9\n",
+        )
+        .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
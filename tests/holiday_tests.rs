@@ -0,0 +1,392 @@
+use actix_web::{App, http::StatusCode, test, web};
+use be::database::get_pool;
+use be::handlers::{holidays, schedules, shifts, time_off};
+use be::middleware::CacheLayer;
+use be::services::auth;
+use bigdecimal::BigDecimal;
+use chrono::{Duration, NaiveTime, Utc};
+use serde_json::{Value, json};
+use serial_test::serial;
+use std::str::FromStr;
+use uuid::Uuid;
+
+mod common;
+
+macro_rules! app {
+    () => {
+        test::init_service(
+            App::new()
+                .app_data(web::Data::new(CacheLayer::new(1000, 60)))
+                .service(
+                    web::scope("/api/v1")
+                        .service(
+                            web::scope("/holidays")
+                                .route("/calendar", web::get().to(holidays::get_calendar))
+                                .route("/import", web::post().to(holidays::import_holidays))
+                                .route("", web::get().to(holidays::get_holidays))
+                                .route("", web::post().to(holidays::create_holiday))
+                                .route("/{holiday_id}", web::put().to(holidays::update_holiday))
+                                .route("/{holiday_id}", web::delete().to(holidays::delete_holiday)),
+                        )
+                        .service(
+                            web::scope("/shifts").route("", web::post().to(shifts::create_shift)),
+                        )
+                        .service(web::scope("/schedules").route(
+                            "/{user_id}/suggestions",
+                            web::get().to(schedules::get_user_shift_suggestions),
+                        ))
+                        .service(
+                            web::scope("/time-off")
+                                .route("", web::post().to(time_off::create_time_off_request))
+                                .route(
+                                    "/{id}/approve",
+                                    web::post().to(time_off::approve_time_off_request),
+                                ),
+                        ),
+                ),
+        )
+        .await
+    };
+}
+
+async fn create_location(company_id: Uuid, name: &str) -> Uuid {
+    sqlx::query_scalar::<_, Uuid>(
+        "INSERT INTO locations (name, company_id) VALUES ($1, $2) RETURNING id",
+    )
+    .bind(name)
+    .bind(company_id)
+    .fetch_one(&get_pool().await)
+    .await
+    .unwrap()
+}
+
+fn calendar_dates(body: &Value) -> Vec<String> {
+    body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|day| day["date"].as_str().unwrap().to_string())
+        .collect()
+}
+
+const ICS: &str = "BEGIN:VCALENDAR\r\n\
+VERSION:2.0\r\n\
+BEGIN:VEVENT\r\n\
+UID:new-year@example.com\r\n\
+DTSTART;VALUE=DATE:20260101\r\n\
+RRULE:FREQ=YEARLY\r\n\
+SUMMARY:New Year's Day\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+UID:founders-2026@example.com\r\n\
+DTSTART;VALUE=DATE:20260612\r\n\
+SUMMARY:Founders' Day\r\n\
+END:VEVENT\r\n\
+END:VCALENDAR\r\n";
+
+#[actix_web::test]
+#[serial]
+async fn test_calendar_overrides_and_ics_import() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let (_, company_id, token) =
+        common::create_user_with_company("hol@example.com", "password123", "Admin", "Hol Co")
+            .await
+            .unwrap();
+    let (worker_id, _, _) =
+        common::create_test_user_with_token("hol-worker@example.com", "password123", "Worker")
+            .await
+            .unwrap();
+    sqlx::query("INSERT INTO user_company (user_id, company_id, role) VALUES ($1, $2, 'employee')")
+        .bind(worker_id)
+        .bind(company_id)
+        .execute(&get_pool().await)
+        .await
+        .unwrap();
+    let worker_token = auth::generate_company_token(worker_id, company_id)
+        .await
+        .unwrap();
+    let store_id = create_location(company_id, "Store").await;
+
+    let app = app!();
+
+    let post = |uri: &str, token: &str, body: Value| {
+        test::TestRequest::post()
+            .uri(uri)
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(body)
+            .to_request()
+    };
+    let get = |uri: &str| {
+        test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+
+    // A recurring company holiday from 2025 shows up in 2026
+    let resp = test::call_service(
+        &app,
+        post(
+            "/api/v1/holidays",
+            &token,
+            json!({
+                "name": "Christmas Day",
+                "holidayDate": "2025-12-25",
+                "recurring": true,
+                "payMultiplier": "2"
+            }),
+        ),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["isClosed"], true);
+
+    // Only admins change the calendar
+    let resp = test::call_service(
+        &app,
+        post(
+            "/api/v1/holidays",
+            &worker_token,
+            json!({ "name": "Day off", "holidayDate": "2026-12-24" }),
+        ),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = test::call_service(
+        &app,
+        post(
+            "/api/v1/holidays",
+            &token,
+            json!({ "name": "Boxing Day", "holidayDate": "2026-12-26", "payMultiplier": "0" }),
+        ),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // The store opens on Christmas Day
+    let resp = test::call_service(
+        &app,
+        post(
+            "/api/v1/holidays",
+            &token,
+            json!({
+                "name": "Christmas trading",
+                "holidayDate": "2026-12-25",
+                "locationId": store_id,
+                "observed": false
+            }),
+        ),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let december = "startDate=2026-12-01&endDate=2026-12-31";
+    let resp = test::call_service(
+        &app,
+        get(&format!("/api/v1/holidays/calendar?{}", december)),
+    )
+    .await;
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(calendar_dates(&body), vec!["2026-12-25"]);
+    assert_eq!(body["data"][0]["name"], "Christmas Day");
+    assert_eq!(
+        BigDecimal::from_str(body["data"][0]["payMultiplier"].as_str().unwrap()).unwrap(),
+        BigDecimal::from(2)
+    );
+
+    let resp = test::call_service(
+        &app,
+        get(&format!(
+            "/api/v1/holidays/calendar?locationId={}&{}",
+            store_id, december
+        )),
+    )
+    .await;
+    let body: Value = test::read_body_json(resp).await;
+    assert!(calendar_dates(&body).is_empty());
+
+    // Importing a calendar file, then again, doesn't add duplicates
+    let import = |query: &str| {
+        test::TestRequest::post()
+            .uri(&format!("/api/v1/holidays/import?{}", query))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .insert_header(("Content-Type", "text/calendar"))
+            .set_payload(ICS)
+            .to_request()
+    };
+    let resp = test::call_service(&app, import("dryRun=true")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["total"], 2);
+    assert_eq!(body["data"]["created"], 0);
+
+    let resp = test::call_service(&app, import("payMultiplier=1.5")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["created"], 2);
+
+    let resp = test::call_service(&app, import("")).await;
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["created"], 0);
+    assert_eq!(body["data"]["updated"], 2);
+
+    let resp = test::call_service(&app, get("/api/v1/holidays")).await;
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 4);
+
+    let resp = test::call_service(
+        &app,
+        get("/api/v1/holidays/calendar?startDate=2027-01-01&endDate=2027-06-30"),
+    )
+    .await;
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(calendar_dates(&body), vec!["2027-01-01"]);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_holidays_in_time_off_shifts_and_suggestions() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let (user_id, company_id, token) =
+        common::create_user_with_company("away@example.com", "password123", "Admin", "Away Co")
+            .await
+            .unwrap();
+    let pool = get_pool().await;
+    sqlx::query(
+        "UPDATE user_company SET pto_balance_hours = 40 WHERE user_id = $1 AND company_id = $2",
+    )
+    .bind(user_id)
+    .bind(company_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let store_id = create_location(company_id, "Store").await;
+
+    // Available every day of the week
+    sqlx::query(
+        "INSERT INTO user_shift_schedules (user_id, company_id, monday_start, monday_end, \
+         tuesday_start, tuesday_end, wednesday_start, wednesday_end, thursday_start, \
+         thursday_end, friday_start, friday_end, saturday_start, saturday_end, sunday_start, \
+         sunday_end) VALUES ($1, $2, $3, $4, $3, $4, $3, $4, $3, $4, $3, $4, $3, $4, $3, $4)",
+    )
+    .bind(user_id)
+    .bind(company_id)
+    .bind(NaiveTime::from_hms_opt(0, 0, 0).unwrap())
+    .bind(NaiveTime::from_hms_opt(23, 59, 0).unwrap())
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let app = app!();
+
+    let post = |uri: &str, body: Value| {
+        test::TestRequest::post()
+            .uri(uri)
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(body)
+            .to_request()
+    };
+
+    let holiday = (Utc::now() + Duration::days(3)).date_naive();
+    for (name, date) in [
+        ("Spring holiday", "2026-03-09".to_string()),
+        ("Store closed", holiday.to_string()),
+    ] {
+        let resp = test::call_service(
+            &app,
+            post(
+                "/api/v1/holidays",
+                json!({ "name": name, "holidayDate": date }),
+            ),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+    }
+
+    // Friday to Monday takes only the Friday when Monday is a holiday
+    let resp = test::call_service(
+        &app,
+        post(
+            "/api/v1/time-off",
+            json!({
+                "userId": user_id,
+                "companyId": company_id,
+                "startDate": "2026-03-06T00:00:00Z",
+                "endDate": "2026-03-09T00:00:00Z",
+                "reason": "Long weekend",
+                "requestType": "Vacation",
+                "durationType": "full_day",
+            }),
+        ),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: Value = test::read_body_json(resp).await;
+    let resp = test::call_service(
+        &app,
+        post(
+            &format!(
+                "/api/v1/time-off/{}/approve",
+                body["data"]["id"].as_str().unwrap()
+            ),
+            json!({ "notes": null }),
+        ),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(
+        BigDecimal::from_str(body["data"]["hoursDeducted"].as_str().unwrap()).unwrap(),
+        BigDecimal::from(8)
+    );
+
+    // A shift on the closed holiday is created with a warning
+    let mut shift_ids = Vec::new();
+    for (day, warnings) in [(holiday, 1), (holiday + Duration::days(1), 0)] {
+        let start_time = day.and_hms_opt(10, 0, 0).unwrap().and_utc();
+        let resp = test::call_service(
+            &app,
+            post(
+                "/api/v1/shifts",
+                json!({
+                    "companyId": company_id,
+                    "title": "Open up",
+                    "locationId": store_id,
+                    "startTime": start_time,
+                    "endTime": start_time + Duration::hours(4),
+                    "maxPeople": 1,
+                    "status": "Open",
+                    "createdAt": Utc::now(),
+                    "updatedAt": Utc::now(),
+                }),
+            ),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["data"]["warnings"].as_array().unwrap().len(), warnings);
+        shift_ids.push(body["data"]["id"].as_str().unwrap().to_string());
+    }
+
+    // Suggestions leave out the holiday
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/schedules/{}/suggestions", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    let suggested: Vec<&str> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|shift| shift["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(suggested, vec![shift_ids[1].as_str()]);
+}
@@ -0,0 +1,306 @@
+use actix_web::{App, http::StatusCode, test, web};
+use be::database::{
+    get_pool,
+    models::{AddEmployeeToCompanyInput, CompanyRole, Permission, RoleInput},
+    repositories::{company as company_repo, role as role_repo},
+    transaction::DatabaseTransaction,
+};
+use be::handlers::{admin, auth};
+use be::middleware::CacheLayer;
+use be::services::{auth as auth_service, user_context};
+use serde_json::json;
+use serial_test::serial;
+use uuid::Uuid;
+
+mod common;
+
+/// An admin and an employee of the admin's company
+async fn setup() -> (Uuid, String, Uuid, Uuid) {
+    let (admin_id, company_id, admin_token) = common::create_user_with_company(
+        "support@example.com",
+        "password123",
+        "Support Admin",
+        "Support Co",
+    )
+    .await
+    .unwrap();
+    let (employee_id, _, _) =
+        common::create_test_user_with_token("worker@example.com", "password123", "Worker")
+            .await
+            .unwrap();
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            company_repo::add_employee_to_company(
+                tx,
+                company_id,
+                &AddEmployeeToCompanyInput {
+                    user_id: employee_id,
+                    role: Some(CompanyRole::Employee),
+                    is_primary: Some(false),
+                    hire_date: None,
+                },
+            )
+            .await?;
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+
+    (admin_id, admin_token, employee_id, company_id)
+}
+
+fn impersonate(user_id: Uuid, token: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri(&format!("/api/v1/admin/users/{}/impersonate", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "reason": "Schedule looks wrong" }))
+}
+
+macro_rules! app {
+    () => {
+        test::init_service(
+            App::new()
+                .app_data(web::Data::new(CacheLayer::new(1000, 60)))
+                .service(
+                    web::scope("/api/v1")
+                        .service(
+                            web::scope("/auth")
+                                .route("/me", web::get().to(auth::me))
+                                .route("/change-email", web::post().to(auth::change_email))
+                                .route(
+                                    "/impersonation/end",
+                                    web::post().to(auth::end_impersonation),
+                                ),
+                        )
+                        .service(web::scope("/admin").route(
+                            "/users/{id}/impersonate",
+                            web::post().to(admin::impersonate_user),
+                        )),
+                ),
+        )
+        .await
+    };
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_impersonation_token_acts_as_user_until_ended() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (admin_id, admin_token, employee_id, company_id) = setup().await;
+    let employee_token = auth_service::generate_company_token(employee_id, company_id)
+        .await
+        .unwrap();
+    let app = app!();
+
+    // Only admins can impersonate, and never themselves or another admin
+    let resp = test::call_service(&app, impersonate(admin_id, &employee_token).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = test::call_service(&app, impersonate(admin_id, &admin_token).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp =
+        test::call_service(&app, impersonate(Uuid::new_v4(), &admin_token).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = test::call_service(&app, impersonate(employee_id, &admin_token).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["user"]["id"], employee_id.to_string());
+    assert_eq!(body["data"]["session"]["reason"], "Schedule looks wrong");
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+
+    let claims = auth_service::verify_token(&token).unwrap();
+    assert_eq!(claims.sub, employee_id);
+    assert_eq!(claims.act, Some(admin_id));
+
+    let me = || {
+        test::TestRequest::get()
+            .uri("/api/v1/auth/me")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+    let resp = test::call_service(&app, me()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["user"]["id"], employee_id.to_string());
+    assert_eq!(body["data"]["impersonating"], true);
+    assert_eq!(
+        body["data"]["impersonation"]["adminUserId"],
+        admin_id.to_string()
+    );
+
+    // Account credentials are off limits while impersonating
+    let req = test::TestRequest::post()
+        .uri("/api/v1/auth/change-email")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "newEmail": "support-owned@example.com", "password": "password123" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/auth/impersonation/end")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = test::call_service(&app, me()).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // The admin's own token is unaffected
+    let req = test::TestRequest::get()
+        .uri("/api/v1/auth/me")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["data"]["impersonating"], false);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_impersonated_activity_is_attributed_to_admin() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    // The test context turns activity logging off; this test needs it
+    unsafe {
+        std::env::remove_var("SKIP_ACTIVITY_LOG");
+    }
+    let (admin_id, admin_token, employee_id, company_id) = setup().await;
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let role = role_repo::create_role(
+                tx,
+                company_id,
+                &RoleInput {
+                    name: "Payroll".to_string(),
+                    description: None,
+                    permissions: vec![
+                        Permission::WAGES_VIEW.to_string(),
+                        Permission::WAGES_EDIT.to_string(),
+                    ],
+                },
+            )
+            .await?;
+            role_repo::assign_member_role(tx, company_id, employee_id, Some(role.id)).await?;
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+    let app = app!();
+
+    let resp = test::call_service(&app, impersonate(employee_id, &admin_token).to_request()).await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+
+    // Sensitive permissions are withheld even if the user's role grants them
+    let claims = auth_service::verify_token(&token).unwrap();
+    let ctx = user_context::from_claims(&claims).await.unwrap();
+    assert!(ctx.is_impersonating());
+    assert!(ctx.has_permission(Permission::WAGES_VIEW));
+    assert!(!ctx.has_permission(Permission::WAGES_EDIT));
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/auth/impersonation/end")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let rows: Vec<(String, Option<Uuid>, Option<Uuid>)> = sqlx::query_as(
+        "SELECT action, user_id, impersonated_user_id FROM company_activity WHERE company_id = $1 AND entity_id = $2 ORDER BY created_at",
+    )
+    .bind(company_id)
+    .bind(employee_id)
+    .fetch_all(&get_pool().await)
+    .await
+    .unwrap();
+    assert_eq!(
+        rows,
+        vec![
+            ("impersonation_started".to_string(), Some(admin_id), None),
+            (
+                "impersonation_ended".to_string(),
+                Some(admin_id),
+                Some(employee_id)
+            ),
+        ]
+    );
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_impersonation_follows_the_impersonate_permission() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (admin_id, _, employee_id, company_id) = setup().await;
+    let (support_id, _, _) =
+        common::create_test_user_with_token("helpdesk@example.com", "password123", "Helpdesk")
+            .await
+            .unwrap();
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            company_repo::add_employee_to_company(
+                tx,
+                company_id,
+                &AddEmployeeToCompanyInput {
+                    user_id: support_id,
+                    role: Some(CompanyRole::Employee),
+                    is_primary: Some(false),
+                    hire_date: None,
+                },
+            )
+            .await?;
+            let role = role_repo::create_role(
+                tx,
+                company_id,
+                &RoleInput {
+                    name: "Support".to_string(),
+                    description: None,
+                    permissions: vec![Permission::USERS_IMPERSONATE.to_string()],
+                },
+            )
+            .await?;
+            role_repo::assign_member_role(tx, company_id, support_id, Some(role.id)).await?;
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+    let support_token = auth_service::generate_company_token(support_id, company_id)
+        .await
+        .unwrap();
+    let app = app!();
+
+    // The permission is enough to impersonate, but not someone who holds it too
+    let resp = test::call_service(&app, impersonate(admin_id, &support_token).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp =
+        test::call_service(&app, impersonate(employee_id, &support_token).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+
+    let me = || {
+        test::TestRequest::get()
+            .uri("/api/v1/auth/me")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+    let resp = test::call_service(&app, me()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Losing the permission ends the session
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            role_repo::assign_member_role(tx, company_id, support_id, None).await?;
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+    let resp = test::call_service(&app, me()).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
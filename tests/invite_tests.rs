@@ -0,0 +1,265 @@
+use actix_web::{App, http::StatusCode, test, web};
+use be::database::{
+    get_pool,
+    models::{CompanyRole, CreateUpdateTeamInput, LocationInput},
+    repositories::{invite as invite_repo, location as location_repo, team as team_repo},
+    transaction::DatabaseTransaction,
+};
+use be::handlers::company;
+use be::middleware::CacheLayer;
+use serial_test::serial;
+use uuid::Uuid;
+
+mod common;
+
+macro_rules! app {
+    () => {
+        test::init_service(
+            App::new()
+                .app_data(web::Data::new(CacheLayer::new(1000, 60)))
+                .service(
+                    web::scope("/api/v1/companies")
+                        .route("/invites", web::get().to(company::get_invites))
+                        .route("/invites/bulk", web::post().to(company::bulk_invite))
+                        .route("/invites/{id}", web::delete().to(company::revoke_invite))
+                        .route(
+                            "/invites/{id}/resend",
+                            web::post().to(company::resend_invite),
+                        ),
+                ),
+        )
+        .await
+    };
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_list_revoke_and_resend_invites() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (admin_id, company_id, admin_token) = common::create_user_with_company(
+        "inviter@example.com",
+        "password123",
+        "Inviter",
+        "Invite Co",
+    )
+    .await
+    .unwrap();
+    let (_, employee_token, _) =
+        common::create_test_user_with_token("outsider@example.com", "password123", "Outsider")
+            .await
+            .unwrap();
+
+    let (pending_id, expired_id, expired_token) = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let pending = invite_repo::create_invite_token(
+                tx,
+                "pending@example.com",
+                admin_id,
+                CompanyRole::Employee,
+                company_id,
+                None,
+            )
+            .await?;
+            let expired = invite_repo::create_invite_token(
+                tx,
+                "expired@example.com",
+                admin_id,
+                CompanyRole::Manager,
+                company_id,
+                None,
+            )
+            .await?;
+            sqlx::query(
+                "UPDATE invite_tokens SET expires_at = NOW() - INTERVAL '1 day' WHERE id = $1",
+            )
+            .bind(expired.id)
+            .execute(&mut **tx)
+            .await?;
+            Ok((pending.id, expired.id, expired.token))
+        })
+    })
+    .await
+    .unwrap();
+
+    let app = app!();
+    let list = |query: &str, token: &str| {
+        test::TestRequest::get()
+            .uri(&format!("/api/v1/companies/invites{}", query))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, list("", &employee_token)).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // Stale pending invites are reported as expired
+    let body: serde_json::Value =
+        test::read_body_json(test::call_service(&app, list("?status=expired", &admin_token)).await)
+            .await;
+    let invites = body["data"].as_array().unwrap();
+    assert_eq!(invites.len(), 1);
+    assert_eq!(invites[0]["id"], expired_id.to_string());
+
+    // Resending renews the expired invite with a new link
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/companies/invites/{}/resend", expired_id))
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(
+        !body["data"]["inviteLink"]
+            .as_str()
+            .unwrap()
+            .ends_with(&expired_token)
+    );
+    assert!(
+        invite_repo::get_invite_token(&expired_token)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    let body: serde_json::Value =
+        test::read_body_json(test::call_service(&app, list("?status=pending", &admin_token)).await)
+            .await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 2);
+
+    let revoke = || {
+        test::TestRequest::delete()
+            .uri(&format!("/api/v1/companies/invites/{}", pending_id))
+            .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+            .to_request()
+    };
+    let resp = test::call_service(&app, revoke()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(&app, revoke()).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let body: serde_json::Value = test::read_body_json(
+        test::call_service(&app, list("?status=revoked&email=PENDING", &admin_token)).await,
+    )
+    .await;
+    let invites = body["data"].as_array().unwrap();
+    assert_eq!(invites.len(), 1);
+    assert_eq!(invites[0]["status"], "revoked");
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_bulk_invite_reports_each_row() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (_, company_id, admin_token) = common::create_user_with_company(
+        "bulk@example.com",
+        "password123",
+        "Bulk Admin",
+        "Bulk Co",
+    )
+    .await
+    .unwrap();
+
+    let team_id = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let location = location_repo::create_location(
+                tx,
+                LocationInput {
+                    company_id,
+                    name: "North".to_string(),
+                    address: None,
+                    phone: None,
+                    email: None,
+                },
+            )
+            .await?;
+            let team = team_repo::create_team(
+                tx,
+                CreateUpdateTeamInput {
+                    name: "North crew".to_string(),
+                    description: None,
+                    location_id: location.id,
+                },
+            )
+            .await?;
+            Ok(team.id)
+        })
+    })
+    .await
+    .unwrap();
+
+    let csv = format!(
+        "email,role,team\n\
+         alice@example.com,manager,north crew\n\
+         \"bob@example.com\",,{}\n\
+         \n\
+         not-an-email,employee,\n\
+         carol@example.com,owner,\n\
+         bulk@example.com,employee,\n\
+         ALICE@example.com,employee,\n\
+         dave@example.com,employee,{}\n",
+        team_id,
+        Uuid::new_v4()
+    );
+
+    let app = app!();
+    let req = test::TestRequest::post()
+        .uri("/api/v1/companies/invites/bulk")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .insert_header(("Content-Type", "text/csv"))
+        .set_payload(csv)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["invited"], 2);
+    assert_eq!(body["data"]["failed"], 5);
+
+    let results = body["data"]["results"].as_array().unwrap();
+    let lines: Vec<u64> = results
+        .iter()
+        .map(|r| r["line"].as_u64().unwrap())
+        .collect();
+    assert_eq!(lines, vec![2, 3, 5, 6, 7, 8, 9]);
+    assert_eq!(results[0]["success"], true);
+    assert_eq!(results[1]["success"], true);
+    assert_eq!(results[2]["error"], "Invalid email address");
+    assert_eq!(results[3]["error"], "Unknown role 'owner'");
+    assert_eq!(
+        results[4]["error"],
+        "User is already a member of the company"
+    );
+    assert_eq!(
+        results[5]["error"],
+        "Email appears more than once in the file"
+    );
+    assert!(
+        results[6]["error"]
+            .as_str()
+            .unwrap()
+            .contains("does not belong to this company")
+    );
+
+    let (role, invite_team_id): (String, Option<Uuid>) = sqlx::query_as(
+        "SELECT role, team_id FROM invite_tokens WHERE company_id = $1 AND email = 'alice@example.com'",
+    )
+    .bind(company_id)
+    .fetch_one(&get_pool().await)
+    .await
+    .unwrap();
+    assert_eq!(role, "manager");
+    assert_eq!(invite_team_id, Some(team_id));
+
+    // A second upload does not invite the same people twice
+    let req = test::TestRequest::post()
+        .uri("/api/v1/companies/invites/bulk")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .set_payload("bob@example.com")
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(
+        body["data"]["results"][0]["error"],
+        "User already has a pending invite"
+    );
+}
@@ -0,0 +1,154 @@
+use be::config::{Config, DEFAULT_JWT_SECRET, VerificationKeyConfig, parse_previous_keys};
+use be::services::jwt_keys::KeyRing;
+use jsonwebtoken::jwk::AlgorithmParameters;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct TestClaims {
+    sub: String,
+    exp: usize,
+}
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/jwt/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+fn claims() -> TestClaims {
+    TestClaims {
+        sub: "user-1".to_string(),
+        exp: (chrono::Utc::now().timestamp() + 600) as usize,
+    }
+}
+
+fn test_config(algorithm: &str, kid: &str, key_name: Option<&str>) -> Config {
+    Config {
+        database_url: "test".to_string(),
+        run_migrations: false,
+        jwt_secret: "test-secret".to_string(),
+        jwt_algorithm: algorithm.to_string(),
+        jwt_key_id: kid.to_string(),
+        jwt_private_key_path: key_name.map(|name| fixture(&format!("{}_private.pem", name))),
+        jwt_public_key_path: key_name.map(|name| fixture(&format!("{}_public.pem", name))),
+        jwt_previous_keys: vec![],
+        jwt_expiration_days: 1,
+        access_token_minutes: 15,
+        impersonation_minutes: 30,
+        activity_checkpoint_minutes: 60,
+        activity_archive_dir: "./data/activity-archives".to_string(),
+        activity_archive_hours: 24,
+        host: "localhost".to_string(),
+        port: 8080,
+        environment: "test".to_string(),
+        client_base_url: "http://localhost:3000".to_string(),
+    }
+}
+
+#[test]
+fn test_asymmetric_round_trip_and_jwks() {
+    for (algorithm, key_name) in [("RS256", "rsa"), ("EdDSA", "ed25519")] {
+        let key_ring = KeyRing::from_config(&test_config(algorithm, "k1", Some(key_name))).unwrap();
+
+        let token = key_ring.encode(&claims()).unwrap();
+        let header = jsonwebtoken::decode_header(&token).unwrap();
+        assert_eq!(header.kid.as_deref(), Some("k1"));
+        assert_eq!(key_ring.decode::<TestClaims>(&token).unwrap(), claims());
+
+        // The published JWK must verify tokens on its own
+        let jwks = key_ring.jwks();
+        assert_eq!(jwks.keys.len(), 1);
+        let jwk = jwks.find("k1").unwrap();
+        match (algorithm, &jwk.algorithm) {
+            ("RS256", AlgorithmParameters::RSA(_))
+            | ("EdDSA", AlgorithmParameters::OctetKeyPair(_)) => {}
+            _ => panic!("unexpected JWK parameters for {}", algorithm),
+        }
+        let decoding_key = jsonwebtoken::DecodingKey::from_jwk(jwk).unwrap();
+        let validation = jsonwebtoken::Validation::new(header.alg);
+        assert!(jsonwebtoken::decode::<TestClaims>(&token, &decoding_key, &validation).is_ok());
+    }
+}
+
+#[test]
+fn test_rotation_keeps_previous_tokens_valid() {
+    let old_ring = KeyRing::from_config(&test_config("HS256", "old", None)).unwrap();
+    let old_token = old_ring.encode(&claims()).unwrap();
+
+    let mut config = test_config("EdDSA", "new", Some("ed25519"));
+    config.jwt_previous_keys = vec![VerificationKeyConfig {
+        kid: "old".to_string(),
+        algorithm: "HS256".to_string(),
+        key: "test-secret".to_string(),
+    }];
+    let new_ring = KeyRing::from_config(&config).unwrap();
+
+    assert!(new_ring.decode::<TestClaims>(&old_token).is_ok());
+    assert!(
+        new_ring
+            .decode::<TestClaims>(&new_ring.encode(&claims()).unwrap())
+            .is_ok()
+    );
+
+    // HMAC secrets are never published
+    assert_eq!(new_ring.jwks().keys.len(), 1);
+
+    // Once the old key is dropped its tokens stop verifying
+    let without_old = KeyRing::from_config(&test_config("EdDSA", "new", Some("ed25519"))).unwrap();
+    assert!(without_old.decode::<TestClaims>(&old_token).is_err());
+}
+
+#[test]
+fn test_token_without_kid_uses_hs256_secret() {
+    let header = jsonwebtoken::Header::default();
+    let legacy_token = jsonwebtoken::encode(
+        &header,
+        &claims(),
+        &jsonwebtoken::EncodingKey::from_secret(b"test-secret"),
+    )
+    .unwrap();
+    let key_ring = KeyRing::from_config(&test_config("HS256", "primary", None)).unwrap();
+    assert!(key_ring.decode::<TestClaims>(&legacy_token).is_ok());
+
+    // The public default secret is not trusted once signing moves to asymmetric keys
+    let default_token = jsonwebtoken::encode(
+        &header,
+        &claims(),
+        &jsonwebtoken::EncodingKey::from_secret(DEFAULT_JWT_SECRET.as_bytes()),
+    )
+    .unwrap();
+    let mut config = test_config("RS256", "primary", Some("rsa"));
+    config.jwt_secret = DEFAULT_JWT_SECRET.to_string();
+    let key_ring = KeyRing::from_config(&config).unwrap();
+    assert!(key_ring.decode::<TestClaims>(&default_token).is_err());
+}
+
+#[test]
+fn test_invalid_key_configuration_is_rejected() {
+    assert!(KeyRing::from_config(&test_config("HS512", "primary", None)).is_err());
+    assert!(KeyRing::from_config(&test_config("RS256", "primary", None)).is_err());
+
+    let mut config = test_config("RS256", "primary", Some("rsa"));
+    config.jwt_public_key_path = Some(fixture("missing.pem"));
+    assert!(KeyRing::from_config(&config).is_err());
+}
+
+#[test]
+fn test_production_rejects_default_secret() {
+    let mut config = test_config("HS256", "primary", None);
+    config.environment = "production".to_string();
+    assert!(config.validate().is_ok());
+
+    config.jwt_secret = DEFAULT_JWT_SECRET.to_string();
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn test_parse_previous_keys() {
+    let keys = parse_previous_keys("2024:HS256:old-secret, 2025:RS256:/keys/2025.pem").unwrap();
+    assert_eq!(keys.len(), 2);
+    assert_eq!(keys[1].kid, "2025");
+    assert_eq!(keys[1].algorithm, "RS256");
+    assert_eq!(keys[1].key, "/keys/2025.pem");
+
+    assert!(parse_previous_keys("").unwrap().is_empty());
+    assert!(parse_previous_keys("missing-parts").is_err());
+}
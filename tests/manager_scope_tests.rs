@@ -0,0 +1,343 @@
+use actix_web::{App, http::StatusCode, test, web};
+use be::database::{
+    models::{
+        AddEmployeeToCompanyInput, CompanyRole, CreateUpdateShiftInput, CreateUpdateTeamInput,
+        LocationInput, ManagerScopeInput, ShiftClaimInput, ShiftStatus,
+    },
+    repositories::{
+        company as company_repo, location as location_repo, manager_scope as manager_scope_repo,
+        shift as shift_repo, shift_claim as shift_claim_repo, team as team_repo,
+    },
+    transaction::DatabaseTransaction,
+};
+use be::handlers::{admin, company, shifts};
+use be::middleware::CacheLayer;
+use be::services::{auth as auth_service, manager_scope, user_context};
+use chrono::{Duration, Utc};
+use serde_json::json;
+use serial_test::serial;
+use uuid::Uuid;
+
+mod common;
+
+struct Fixture {
+    company_id: Uuid,
+    admin_token: String,
+    manager_id: Uuid,
+    north_location_id: Uuid,
+    south_location_id: Uuid,
+    north_team_id: Uuid,
+    south_team_id: Uuid,
+    north_employee_id: Uuid,
+    south_employee_id: Uuid,
+}
+
+/// A company with two locations, one team at each and an employee in each team
+async fn setup() -> Fixture {
+    let (_, company_id, admin_token) = common::create_user_with_company(
+        "scope-admin@example.com",
+        "password123",
+        "Scope Admin",
+        "Scope Co",
+    )
+    .await
+    .unwrap();
+
+    let mut members = Vec::new();
+    for email in [
+        "scope-manager@example.com",
+        "north@example.com",
+        "south@example.com",
+    ] {
+        let (user_id, _, _) = common::create_test_user_with_token(email, "password123", email)
+            .await
+            .unwrap();
+        members.push(user_id);
+    }
+    let (manager_id, north_employee_id, south_employee_id) = (members[0], members[1], members[2]);
+
+    let (north_location_id, south_location_id, north_team_id, south_team_id) =
+        DatabaseTransaction::run(|tx| {
+            Box::pin(async move {
+                for (user_id, role) in [
+                    (manager_id, CompanyRole::Manager),
+                    (north_employee_id, CompanyRole::Employee),
+                    (south_employee_id, CompanyRole::Employee),
+                ] {
+                    company_repo::add_employee_to_company(
+                        tx,
+                        company_id,
+                        &AddEmployeeToCompanyInput {
+                            user_id,
+                            role: Some(role),
+                            is_primary: Some(false),
+                            hire_date: None,
+                        },
+                    )
+                    .await?;
+                }
+
+                let mut ids = Vec::new();
+                for (name, employee_id) in
+                    [("North", north_employee_id), ("South", south_employee_id)]
+                {
+                    let location = location_repo::create_location(
+                        tx,
+                        LocationInput {
+                            company_id,
+                            name: name.to_string(),
+                            address: None,
+                            phone: None,
+                            email: None,
+                        },
+                    )
+                    .await?;
+                    let team = team_repo::create_team(
+                        tx,
+                        CreateUpdateTeamInput {
+                            name: format!("{} crew", name),
+                            description: None,
+                            location_id: location.id,
+                        },
+                    )
+                    .await?;
+                    team_repo::add_team_member(tx, team.id, employee_id).await?;
+                    ids.push((location.id, team.id));
+                }
+
+                Ok((ids[0].0, ids[1].0, ids[0].1, ids[1].1))
+            })
+        })
+        .await
+        .unwrap();
+
+    Fixture {
+        company_id,
+        admin_token,
+        manager_id,
+        north_location_id,
+        south_location_id,
+        north_team_id,
+        south_team_id,
+        north_employee_id,
+        south_employee_id,
+    }
+}
+
+fn shift_input(company_id: Uuid, location_id: Uuid) -> CreateUpdateShiftInput {
+    let start_time = Utc::now() + Duration::days(1);
+    CreateUpdateShiftInput {
+        company_id,
+        title: "Morning".to_string(),
+        description: None,
+        location_id,
+        team_id: None,
+        start_time,
+        end_time: start_time + Duration::hours(8),
+        min_duration_minutes: None,
+        max_duration_minutes: None,
+        max_people: Some(1),
+        status: ShiftStatus::Open,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+    }
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_scope_limits_manager_to_assigned_locations() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let fixture = setup().await;
+    let manager_token =
+        auth_service::generate_company_token(fixture.manager_id, fixture.company_id)
+            .await
+            .unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(CacheLayer::new(1000, 60)))
+            .service(
+                web::scope("/api/v1")
+                    .service(
+                        web::scope("/companies")
+                            .route(
+                                "/employees/{user_id}/scope",
+                                web::get().to(company::get_member_scope),
+                            )
+                            .route(
+                                "/employees/{user_id}/scope",
+                                web::put().to(company::update_member_scope),
+                            ),
+                    )
+                    .service(
+                        web::scope("/shifts")
+                            .route("", web::post().to(shifts::create_shift))
+                            .route("/claims/pending", web::get().to(shifts::get_pending_claims)),
+                    )
+                    .service(web::scope("/admin").route(
+                        "/teams/{team_id}/members/{user_id}",
+                        web::post().to(admin::add_team_member),
+                    )),
+            ),
+    )
+    .await;
+
+    // Only admins manage scopes, and a team from another company is rejected
+    let scope_uri = format!("/api/v1/companies/employees/{}/scope", fixture.manager_id);
+    let req = test::TestRequest::put()
+        .uri(&scope_uri)
+        .insert_header(("Authorization", format!("Bearer {}", fixture.admin_token)))
+        .set_json(json!({ "teamIds": [Uuid::new_v4()] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::put()
+        .uri(&scope_uri)
+        .insert_header(("Authorization", format!("Bearer {}", fixture.admin_token)))
+        .set_json(json!({ "locationIds": [fixture.north_location_id] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["companyWide"], false);
+
+    let req = test::TestRequest::put()
+        .uri(&scope_uri)
+        .insert_header(("Authorization", format!("Bearer {}", manager_token)))
+        .set_json(json!({ "locationIds": [] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // Shifts can only be created at the managed location
+    let create_shift = |location_id: Uuid| {
+        test::TestRequest::post()
+            .uri("/api/v1/shifts")
+            .insert_header(("Authorization", format!("Bearer {}", manager_token)))
+            .set_json(shift_input(fixture.company_id, location_id))
+            .to_request()
+    };
+    let resp = test::call_service(&app, create_shift(fixture.south_location_id)).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = test::call_service(&app, create_shift(fixture.north_location_id)).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    // Pending claims at other locations are hidden
+    let company_id = fixture.company_id;
+    let (north_employee_id, south_employee_id) =
+        (fixture.north_employee_id, fixture.south_employee_id);
+    let (north_location_id, south_location_id) =
+        (fixture.north_location_id, fixture.south_location_id);
+    let north_claim_id = DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let north =
+                shift_repo::create_shift(tx, shift_input(company_id, north_location_id)).await?;
+            let south =
+                shift_repo::create_shift(tx, shift_input(company_id, south_location_id)).await?;
+            let claim = shift_claim_repo::create_claim(
+                tx,
+                &ShiftClaimInput {
+                    shift_id: north.id,
+                    user_id: north_employee_id,
+                },
+            )
+            .await?;
+            shift_claim_repo::create_claim(
+                tx,
+                &ShiftClaimInput {
+                    shift_id: south.id,
+                    user_id: south_employee_id,
+                },
+            )
+            .await?;
+            Ok(claim.id)
+        })
+    })
+    .await
+    .unwrap();
+
+    let pending = |token: &str| {
+        test::TestRequest::get()
+            .uri("/api/v1/shifts/claims/pending")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+    let body: serde_json::Value =
+        test::read_body_json(test::call_service(&app, pending(&manager_token)).await).await;
+    let claims = body["data"].as_array().unwrap();
+    assert_eq!(claims.len(), 1);
+    assert_eq!(claims[0]["id"], north_claim_id.to_string());
+
+    let body: serde_json::Value =
+        test::read_body_json(test::call_service(&app, pending(&fixture.admin_token)).await).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 2);
+
+    // Team membership changes are limited to teams at the managed location
+    let add_member = |team_id: Uuid| {
+        test::TestRequest::post()
+            .uri(&format!(
+                "/api/v1/admin/teams/{}/members/{}",
+                team_id, fixture.manager_id
+            ))
+            .insert_header(("Authorization", format!("Bearer {}", manager_token)))
+            .to_request()
+    };
+    let resp = test::call_service(&app, add_member(fixture.south_team_id)).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = test::call_service(&app, add_member(fixture.north_team_id)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_scope_covers_team_members() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let fixture = setup().await;
+
+    let ctx = user_context::get_user_context(fixture.manager_id, Some(fixture.company_id))
+        .await
+        .unwrap();
+
+    // Without assignments a manager acts company-wide
+    let scope = manager_scope::load(&ctx).await.unwrap();
+    assert!(scope.filter().is_none());
+    assert!(scope.includes_user(fixture.south_employee_id));
+
+    let mut input = ManagerScopeInput {
+        location_ids: vec![],
+        team_ids: vec![fixture.south_team_id, fixture.south_team_id],
+    };
+    manager_scope::validate_input(fixture.company_id, &mut input)
+        .await
+        .unwrap();
+    assert_eq!(input.team_ids.len(), 1);
+
+    let (company_id, manager_id) = (fixture.company_id, fixture.manager_id);
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            manager_scope_repo::replace_member_scopes(
+                tx,
+                company_id,
+                manager_id,
+                &input.location_ids,
+                &input.team_ids,
+            )
+            .await?
+            .unwrap();
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+
+    let scope = manager_scope::load(&ctx).await.unwrap();
+    assert!(scope.includes_team(fixture.south_team_id));
+    assert!(!scope.includes_team(fixture.north_team_id));
+    assert!(scope.includes_user(fixture.south_employee_id));
+    assert!(scope.requires_user(fixture.north_employee_id).is_err());
+    assert!(scope.includes_shift(fixture.south_location_id, Some(fixture.south_team_id)));
+    assert!(!scope.includes_shift(fixture.south_location_id, None));
+}
@@ -19,7 +19,7 @@ async fn test_forgot_password_valid_email() {
         name: "Forgot User".to_string(),
     };
 
-    let registration = auth_service::register(register_request).await;
+    let registration = auth_service::register(register_request, &common::test_request_info()).await;
     assert!(registration.is_ok());
 
     // Test forgot password
@@ -54,8 +54,9 @@ async fn test_reset_password_valid_token() {
         name: "Reset User".to_string(),
     };
 
-    let registration = auth_service::register(register_request).await;
+    let registration = auth_service::register(register_request, &common::test_request_info()).await;
     assert!(registration.is_ok());
+    let refresh_token = registration.unwrap().refresh_token.unwrap();
 
     // Request password reset
     let token = auth_service::forgot_password("reset@example.com")
@@ -66,19 +67,29 @@ async fn test_reset_password_valid_token() {
     let result = auth_service::reset_password(&token, "newpassword123").await;
     assert!(result.is_ok());
 
+    // Existing sessions are revoked by the reset
+    let refresh = auth_service::refresh(&refresh_token, &common::test_request_info()).await;
+    assert!(refresh.is_err());
+
     // Verify old password doesn't work
-    let old_login = auth_service::login(be::database::models::LoginInput {
-        email: "reset@example.com".to_string(),
-        password: "oldpassword123".to_string(),
-    })
+    let old_login = auth_service::login(
+        be::database::models::LoginInput {
+            email: "reset@example.com".to_string(),
+            password: "oldpassword123".to_string(),
+        },
+        &common::test_request_info(),
+    )
     .await;
     assert!(old_login.is_err());
 
     // Verify new password works
-    let new_login = auth_service::login(be::database::models::LoginInput {
-        email: "reset@example.com".to_string(),
-        password: "newpassword123".to_string(),
-    })
+    let new_login = auth_service::login(
+        be::database::models::LoginInput {
+            email: "reset@example.com".to_string(),
+            password: "newpassword123".to_string(),
+        },
+        &common::test_request_info(),
+    )
     .await;
     assert!(new_login.is_ok());
 }
@@ -111,7 +122,7 @@ async fn test_reset_password_used_token() {
         name: "Used Token User".to_string(),
     };
 
-    let registration = auth_service::register(register_request).await;
+    let registration = auth_service::register(register_request, &common::test_request_info()).await;
     assert!(registration.is_ok());
 
     // Request password reset
@@ -145,7 +156,7 @@ async fn test_multiple_reset_tokens_invalidated() {
         name: "Multiple Tokens User".to_string(),
     };
 
-    let registration = auth_service::register(register_request).await;
+    let registration = auth_service::register(register_request, &common::test_request_info()).await;
     assert!(registration.is_ok());
 
     // Request multiple password resets
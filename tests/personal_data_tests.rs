@@ -0,0 +1,201 @@
+use actix_web::{App, http::StatusCode, test, web};
+use be::database::{
+    get_pool,
+    models::{AddEmployeeToCompanyInput, CompanyRole, CreateWageHistoryInput},
+    repositories::{company as company_repo, user as user_repo, wage as wage_repo},
+    transaction::DatabaseTransaction,
+};
+use be::handlers::auth;
+use be::middleware::CacheLayer;
+use bigdecimal::BigDecimal;
+use chrono::Utc;
+use serde_json::json;
+use serial_test::serial;
+use uuid::Uuid;
+
+mod common;
+
+macro_rules! app {
+    () => {
+        test::init_service(
+            App::new()
+                .app_data(web::Data::new(CacheLayer::new(1000, 60)))
+                .service(
+                    web::scope("/api/v1/auth")
+                        .route("/me", web::get().to(auth::me))
+                        .route("/me/export", web::get().to(auth::export_personal_data))
+                        .route("/me/erase", web::post().to(auth::erase_account)),
+                ),
+        )
+        .await
+    };
+}
+
+/// An admin's company with one employee who has a wage entry and a pending time off
+/// request there. Returns the admin's token, the employee and the employee's token.
+async fn setup() -> (String, Uuid, String, Uuid) {
+    let (_, company_id, admin_token) = common::create_user_with_company(
+        "gdpr-admin@example.com",
+        "password123",
+        "Gdpr Admin",
+        "Gdpr Co",
+    )
+    .await
+    .unwrap();
+    let (employee_id, employee_token, _) =
+        common::create_test_user_with_token("gdpr-employee@example.com", "password123", "Erin")
+            .await
+            .unwrap();
+
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            company_repo::add_employee_to_company(
+                tx,
+                company_id,
+                &AddEmployeeToCompanyInput {
+                    user_id: employee_id,
+                    role: Some(CompanyRole::Employee),
+                    is_primary: Some(false),
+                    hire_date: None,
+                },
+            )
+            .await?;
+            wage_repo::create_wage_history(
+                tx,
+                &CreateWageHistoryInput {
+                    user_id: employee_id,
+                    company_id,
+                    hourly_rate: BigDecimal::from(20),
+                    overtime_rate_multiplier: None,
+                    effective_date: Utc::now().date_naive(),
+                    end_date: None,
+                    changed_by: None,
+                    change_reason: None,
+                },
+            )
+            .await?;
+            sqlx::query(
+                "INSERT INTO time_off_requests (user_id, company_id, start_date, end_date, reason, request_type)
+                 VALUES ($1, $2, NOW() + INTERVAL '7 days', NOW() + INTERVAL '8 days', 'Family visit', 'vacation')",
+            )
+            .bind(employee_id)
+            .bind(company_id)
+            .execute(&mut **tx)
+            .await?;
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+
+    (admin_token, employee_id, employee_token, company_id)
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_export_covers_all_companies() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (_, employee_id, employee_token, company_id) = setup().await;
+    let app = app!();
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/auth/me/export")
+        .insert_header(("Authorization", format!("Bearer {}", employee_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(
+        resp.headers()
+            .get("Content-Disposition")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("attachment")
+    );
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["profile"]["id"], employee_id.to_string());
+    assert!(body["profile"].get("passwordHash").is_none());
+    assert_eq!(body["memberships"].as_array().unwrap().len(), 2);
+    assert_eq!(body["wageHistory"][0]["companyId"], company_id.to_string());
+    assert_eq!(body["timeOffRequests"][0]["reason"], "Family visit");
+    for key in [
+        "shifts",
+        "shiftClaims",
+        "attendance",
+        "shiftSwaps",
+        "skills",
+        "activity",
+    ] {
+        assert!(body[key].is_array(), "missing {}", key);
+    }
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_erase_anonymizes_but_keeps_history() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (admin_token, employee_id, employee_token, _) = setup().await;
+    let app = app!();
+
+    let erase = |token: &str, password: &str| {
+        test::TestRequest::post()
+            .uri("/api/v1/auth/me/erase")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({ "password": password }))
+            .to_request()
+    };
+
+    // The only admin of a company with other members has to hand over first
+    let resp = test::call_service(&app, erase(&admin_token, "password123")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = test::call_service(&app, erase(&employee_token, "wrong")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = test::call_service(&app, erase(&employee_token, "password123")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // The account can no longer be used or found by its old address
+    let req = test::TestRequest::get()
+        .uri("/api/v1/auth/me")
+        .insert_header(("Authorization", format!("Bearer {}", employee_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert!(
+        user_repo::find_by_email("gdpr-employee@example.com")
+            .await
+            .unwrap()
+            .is_none()
+    );
+    let user = user_repo::find_by_id(employee_id).await.unwrap().unwrap();
+    assert!(user.is_erased());
+    assert_eq!(user.name, "Erased user");
+
+    // Payroll and time off history stay, without the personal notes
+    let pool = get_pool().await;
+    let wages: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM wage_history WHERE user_id = $1")
+        .bind(employee_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(wages, 1);
+    let (status, reason): (String, Option<String>) =
+        sqlx::query_as("SELECT status, reason FROM time_off_requests WHERE user_id = $1")
+            .bind(employee_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(status, "cancelled");
+    assert_eq!(reason, None);
+    let memberships: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM user_company WHERE user_id = $1")
+            .bind(employee_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(memberships, 0);
+}
@@ -0,0 +1,150 @@
+use actix_web::{App, http::StatusCode, test, web};
+use be::database::get_pool;
+use be::handlers::pto_balance;
+use be::middleware::CacheLayer;
+use bigdecimal::BigDecimal;
+use serde_json::{Value, json};
+use serial_test::serial;
+use std::str::FromStr;
+
+mod common;
+
+macro_rules! app {
+    () => {
+        test::init_service(
+            App::new()
+                .app_data(web::Data::new(CacheLayer::new(1000, 60)))
+                .service(
+                    web::scope("/api/v1").service(
+                        web::scope("/pto-balance")
+                            .route("/as-of", web::get().to(pto_balance::get_pto_balance_as_of))
+                            .route("/{user_id}", web::get().to(pto_balance::get_pto_balance))
+                            .route(
+                                "/{user_id}/history",
+                                web::get().to(pto_balance::get_pto_balance_history),
+                            )
+                            .route(
+                                "/{user_id}/adjust",
+                                web::post().to(pto_balance::adjust_pto_balance),
+                            ),
+                    ),
+                ),
+        )
+        .await
+    };
+}
+
+fn hours_of(value: &Value) -> BigDecimal {
+    BigDecimal::from_str(value.as_str().unwrap()).unwrap()
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_ledger_running_totals_and_as_of_balances() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let (_, company_id, token) =
+        common::create_user_with_company("ledger@example.com", "password123", "Admin", "Ledger Co")
+            .await
+            .unwrap();
+    let (employee_id, _, _) =
+        common::create_test_user_with_token("worker@example.com", "password123", "Worker")
+            .await
+            .unwrap();
+    sqlx::query("INSERT INTO user_company (user_id, company_id, role) VALUES ($1, $2, 'employee')")
+        .bind(employee_id)
+        .bind(company_id)
+        .execute(&get_pool().await)
+        .await
+        .unwrap();
+
+    let app = app!();
+
+    let adjust = |hours: &str, date: &str| {
+        test::TestRequest::post()
+            .uri(&format!("/api/v1/pto-balance/{}/adjust", employee_id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({
+                "changeType": "adjustment",
+                "balanceType": "pto",
+                "hoursChanged": hours,
+                "description": "Payroll correction",
+                "effectiveDate": date,
+            }))
+            .to_request()
+    };
+
+    // Fractional hours are kept exactly
+    for (hours, date) in [("8.5", "2026-01-15"), ("-2.25", "2026-03-01")] {
+        let resp = test::call_service(&app, adjust(hours, date)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    // Overdrawing is rejected and leaves no entry behind
+    let resp = test::call_service(&app, adjust("-100", "2026-03-02")).await;
+    assert!(!resp.status().is_success());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/pto-balance/{}/history", employee_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let history = body["data"].as_array().unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(
+        hours_of(&history[0]["newBalance"]),
+        BigDecimal::from_str("6.25").unwrap()
+    );
+    assert_eq!(
+        hours_of(&history[0]["previousBalance"]),
+        BigDecimal::from_str("8.5").unwrap()
+    );
+    assert_eq!(history[0]["effectiveDate"], "2026-03-01");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/pto-balance/{}", employee_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(
+        hours_of(&body["data"]["ptoBalanceHours"]),
+        BigDecimal::from_str("6.25").unwrap()
+    );
+
+    // Balances on a past date only count entries effective by then
+    for (date, expected) in [
+        ("2026-01-01", "0"),
+        ("2026-02-01", "8.5"),
+        ("2026-03-01", "6.25"),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/v1/pto-balance/as-of?date={}&userId={}",
+                date, employee_id
+            ))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request();
+        let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        let rows = body["data"].as_array().unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["asOf"], date);
+        assert_eq!(
+            hours_of(&rows[0]["ptoBalanceHours"]),
+            BigDecimal::from_str(expected).unwrap()
+        );
+    }
+
+    // Without a user, every member is listed
+    let req = test::TestRequest::get()
+        .uri("/api/v1/pto-balance/as-of?date=2026-02-01")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let rows = body["data"].as_array().unwrap();
+    assert_eq!(rows.len(), 2);
+    assert!(
+        rows.iter()
+            .any(|row| row["userId"] == employee_id.to_string())
+    );
+}
@@ -0,0 +1,410 @@
+use actix_web::{App, http::StatusCode, test, web};
+use be::database::get_pool;
+use be::handlers::{pto_balance, pto_policies};
+use be::middleware::CacheLayer;
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use serde_json::{Value, json};
+use serial_test::serial;
+use std::str::FromStr;
+use uuid::Uuid;
+
+mod common;
+
+macro_rules! app {
+    () => {
+        test::init_service(
+            App::new()
+                .app_data(web::Data::new(CacheLayer::new(1000, 60)))
+                .service(
+                    web::scope("/api/v1")
+                        .service(
+                            web::scope("/pto-policies")
+                                .route(
+                                    "/accrual/preview",
+                                    web::get().to(pto_policies::preview_accrual),
+                                )
+                                .route("/accrual/run", web::post().to(pto_policies::run_accrual))
+                                .route("", web::get().to(pto_policies::get_policies))
+                                .route("", web::post().to(pto_policies::create_policy))
+                                .route("/{policy_id}", web::get().to(pto_policies::get_policy))
+                                .route("/{policy_id}", web::put().to(pto_policies::update_policy))
+                                .route(
+                                    "/{policy_id}",
+                                    web::delete().to(pto_policies::delete_policy),
+                                )
+                                .route(
+                                    "/{policy_id}/assignments",
+                                    web::post().to(pto_policies::create_assignment),
+                                )
+                                .route(
+                                    "/{policy_id}/assignments/{assignment_id}",
+                                    web::delete().to(pto_policies::delete_assignment),
+                                ),
+                        )
+                        .service(web::scope("/pto-balance").route(
+                            "/{user_id}/accrual",
+                            web::post().to(pto_balance::process_pto_accrual),
+                        )),
+                ),
+        )
+        .await
+    };
+}
+
+async fn execute(query: &str, ids: &[Uuid]) {
+    let pool = get_pool().await;
+    let mut query = sqlx::query(query);
+    for id in ids {
+        query = query.bind(*id);
+    }
+    query.execute(&pool).await.unwrap();
+}
+
+/// Add an employee to the company with a hire date
+async fn add_employee(email: &str, company_id: Uuid, hire_date: &str) -> Uuid {
+    let (user_id, _, _) = common::create_test_user_with_token(email, "password123", email)
+        .await
+        .unwrap();
+    execute(
+        &format!(
+            "INSERT INTO user_company (user_id, company_id, role, hire_date) \
+             VALUES ($1, $2, 'employee', '{}')",
+            hire_date
+        ),
+        &[user_id, company_id],
+    )
+    .await;
+    user_id
+}
+
+fn monthly_policy() -> Value {
+    json!({
+        "name": "Standard PTO",
+        "accrualMethod": "per_pay_period",
+        "payPeriod": "monthly",
+        "tiers": [
+            { "minTenureMonths": 12, "accrualRate": "8" },
+            { "minTenureMonths": 0, "accrualRate": "4" }
+        ],
+        "maxBalanceHours": "60",
+        "carryoverLimitHours": "40",
+        "waitingPeriodDays": 30
+    })
+}
+
+fn hours_of(value: &Value) -> BigDecimal {
+    BigDecimal::from_str(value.as_str().unwrap()).unwrap()
+}
+
+fn result_for(body: &Value, user_id: Uuid) -> Value {
+    body["data"]["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|result| result["userId"] == user_id.to_string())
+        .cloned()
+        .unwrap()
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_pto_policy_crud_and_validation() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let (_, _, token) = common::create_user_with_company(
+        "policy-admin@example.com",
+        "password123",
+        "Policy Admin",
+        "Policy Co",
+    )
+    .await
+    .unwrap();
+    let (_, employee_token, _) =
+        common::create_test_user_with_token("policy-emp@example.com", "password123", "Emp")
+            .await
+            .unwrap();
+    let app = app!();
+
+    let post = |uri: String, body: Value| {
+        test::TestRequest::post()
+            .uri(&uri)
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(body)
+            .to_request()
+    };
+
+    let req = post("/api/v1/pto-policies".to_string(), monthly_policy());
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: Value = test::read_body_json(resp).await;
+    let policy_id = body["data"]["id"].as_str().unwrap().to_string();
+    assert_eq!(body["data"]["balanceType"], "pto");
+    assert_eq!(body["data"]["yearStart"], "calendar");
+    let tiers = body["data"]["tiers"].as_array().unwrap();
+    assert_eq!(tiers.len(), 2);
+    assert_eq!(tiers[0]["minTenureMonths"], 0);
+
+    // Names are unique within the company and pay periods are required for per-period accrual
+    let resp = test::call_service(
+        &app,
+        post("/api/v1/pto-policies".to_string(), monthly_policy()),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let mut invalid = monthly_policy();
+    invalid["name"] = json!("No period");
+    invalid["payPeriod"] = Value::Null;
+    let resp = test::call_service(&app, post("/api/v1/pto-policies".to_string(), invalid)).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let mut invalid = monthly_policy();
+    invalid["name"] = json!("No tiers");
+    invalid["tiers"] = json!([]);
+    let resp = test::call_service(&app, post("/api/v1/pto-policies".to_string(), invalid)).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // Assignments name at most one group, which must belong to the company
+    let assignments = format!("/api/v1/pto-policies/{}/assignments", policy_id);
+    let resp = test::call_service(
+        &app,
+        post(
+            assignments.clone(),
+            json!({ "teamId": Uuid::new_v4(), "locationId": Uuid::new_v4() }),
+        ),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = test::call_service(
+        &app,
+        post(assignments.clone(), json!({ "teamId": Uuid::new_v4() })),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = test::call_service(&app, post(assignments.clone(), json!({}))).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: Value = test::read_body_json(resp).await;
+    let assignment_id = body["data"]["id"].as_str().unwrap().to_string();
+    let resp = test::call_service(&app, post(assignments.clone(), json!({}))).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let mut update = monthly_policy();
+    update["name"] = json!("Renamed PTO");
+    update["tiers"] = json!([{ "minTenureMonths": 0, "accrualRate": "6.5" }]);
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/v1/pto-policies/{}", policy_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(update)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["name"], "Renamed PTO");
+    assert_eq!(body["data"]["tiers"][0]["accrualRate"], "6.5000");
+    assert_eq!(body["data"]["assignments"].as_array().unwrap().len(), 1);
+
+    // Employees without PTO permissions cannot see policies
+    let req = test::TestRequest::get()
+        .uri("/api/v1/pto-policies")
+        .insert_header(("Authorization", format!("Bearer {}", employee_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("{}/{}", assignments, assignment_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/v1/pto-policies/{}", policy_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/pto-policies")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let body: Value = test::read_body_json(resp).await;
+    assert!(body["data"].as_array().unwrap().is_empty());
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_pto_policy_accrual_preview_and_run() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let (_, company_id, token) = common::create_user_with_company(
+        "accrual-admin@example.com",
+        "password123",
+        "Accrual Admin",
+        "Accrual Co",
+    )
+    .await
+    .unwrap();
+    let veteran_id = add_employee("veteran@example.com", company_id, "2025-01-10").await;
+    let new_hire_id = add_employee("new-hire@example.com", company_id, "2025-06-15").await;
+    let app = app!();
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/pto-policies")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(monthly_policy())
+        .to_request();
+    let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let policy_id = body["data"]["id"].as_str().unwrap().to_string();
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/pto-policies/{}/assignments", policy_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    // Accrual counts from when the policy was assigned, so assign it before the hires
+    execute(
+        "UPDATE pto_policy_assignments SET created_at = '2025-01-01' WHERE company_id = $1",
+        &[company_id],
+    )
+    .await;
+
+    // Policy members no longer accrue at the flat rate
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/pto-balance/{}/accrual", veteran_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let accrual = |method: &str, as_of: &str| {
+        let uri = format!("/api/v1/pto-policies/accrual/{}?asOf={}", method, as_of);
+        let req = if method == "preview" {
+            test::TestRequest::get()
+        } else {
+            test::TestRequest::post()
+        };
+        req.uri(&uri)
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+
+    // Five monthly periods after the 30-day waiting period, at the first tier's rate
+    let resp = test::call_service(&app, accrual("preview", "2025-06-30")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["preview"], true);
+    let veteran = result_for(&body, veteran_id);
+    assert_eq!(veteran["tenureMonths"], 5);
+    assert_eq!(veteran["payPeriods"], 5);
+    assert_eq!(hours_of(&veteran["accruedHours"]), BigDecimal::from(20));
+    assert_eq!(hours_of(&veteran["previousBalance"]), BigDecimal::from(0));
+    assert_eq!(hours_of(&veteran["newBalance"]), BigDecimal::from(20));
+    let new_hire = result_for(&body, new_hire_id);
+    assert_eq!(
+        new_hire["skippedReason"],
+        "Waiting period ends on 2025-07-15"
+    );
+
+    // Previewing changes nothing, so running gives the same result. Of two runs at the same
+    // time, only one accrues.
+    let (first, second) = tokio::join!(
+        test::call_service(&app, accrual("run", "2025-06-30")),
+        test::call_service(&app, accrual("run", "2025-06-30")),
+    );
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(second.status(), StatusCode::OK);
+    let first: Value = test::read_body_json(first).await;
+    let second: Value = test::read_body_json(second).await;
+    assert_eq!(first["data"]["preview"], false);
+    let (accrued, skipped) = if result_for(&first, veteran_id) == veteran {
+        (first, second)
+    } else {
+        (second, first)
+    };
+    assert_eq!(result_for(&accrued, veteran_id), veteran);
+    let skipped_reason = result_for(&skipped, veteran_id)["skippedReason"].clone();
+    assert!(
+        skipped_reason == "Already accrued through 2025-06-30"
+            || skipped_reason == "Accrued by another run",
+        "unexpected {}",
+        skipped_reason
+    );
+
+    let resp = test::call_service(&app, accrual("run", "2025-06-30")).await;
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(
+        result_for(&body, veteran_id)["skippedReason"],
+        "Already accrued through 2025-06-30"
+    );
+
+    // After a year the higher tier applies. The balance stops at 60 hours, 20 of them are
+    // forfeited over the 40-hour carryover at the new year, and it fills up to 60 again.
+    let resp = test::call_service(&app, accrual("run", "2026-03-31")).await;
+    let body: Value = test::read_body_json(resp).await;
+    let veteran = result_for(&body, veteran_id);
+    assert_eq!(veteran["tenureMonths"], 14);
+    assert_eq!(veteran["payPeriods"], 9);
+    assert_eq!(hours_of(&veteran["previousBalance"]), BigDecimal::from(20));
+    assert_eq!(hours_of(&veteran["accruedHours"]), BigDecimal::from(60));
+    assert_eq!(hours_of(&veteran["cappedHours"]), BigDecimal::from(12));
+    assert_eq!(hours_of(&veteran["forfeitedHours"]), BigDecimal::from(20));
+    assert_eq!(hours_of(&veteran["newBalance"]), BigDecimal::from(60));
+    assert_eq!(veteran["accruedThrough"], "2026-03-31");
+
+    // Each accrual and forfeiture is on the ledger, which adds up to the cached balance
+    let pool = get_pool().await;
+    let entries: Vec<(String, BigDecimal, NaiveDate, BigDecimal)> = sqlx::query_as(
+        "SELECT change_type, hours_changed, effective_date, new_balance FROM pto_balance_history \
+         WHERE user_id = $1 AND company_id = $2 ORDER BY entry_number",
+    )
+    .bind(veteran_id)
+    .bind(company_id)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    let date = |value: &str| NaiveDate::from_str(value).unwrap();
+    let hours = |value: i32| BigDecimal::from(value);
+    assert_eq!(
+        entries,
+        vec![
+            (
+                "accrual".to_string(),
+                hours(20),
+                date("2025-06-30"),
+                hours(20)
+            ),
+            (
+                "accrual".to_string(),
+                hours(40),
+                date("2025-12-31"),
+                hours(60)
+            ),
+            (
+                "forfeiture".to_string(),
+                hours(-20),
+                date("2026-01-01"),
+                hours(40)
+            ),
+            (
+                "accrual".to_string(),
+                hours(20),
+                date("2026-03-31"),
+                hours(60)
+            ),
+        ]
+    );
+    let balance: BigDecimal = sqlx::query_scalar(
+        "SELECT pto_balance_hours FROM user_company WHERE user_id = $1 AND company_id = $2",
+    )
+    .bind(veteran_id)
+    .bind(company_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(balance, hours(60));
+}
@@ -0,0 +1,205 @@
+use actix_web::{App, http::StatusCode, test, web};
+use be::database::{
+    models::{AddEmployeeToCompanyInput, CompanyRole, Permission, RoleInput},
+    repositories::{company as company_repo, role as role_repo},
+    transaction::DatabaseTransaction,
+};
+use be::error::AppError;
+use be::handlers::{admin, company};
+use be::middleware::CacheLayer;
+use be::services::{auth as auth_service, roles, user_context};
+use serde_json::json;
+use serial_test::serial;
+
+mod common;
+
+#[actix_web::test]
+#[serial]
+async fn test_builtin_roles_are_seeded_with_company() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let (admin_id, company_id, _) = common::create_user_with_company(
+        "roles-admin@example.com",
+        "password123",
+        "Roles Admin",
+        "Roles Co",
+    )
+    .await
+    .unwrap();
+    let (employee_id, _, employee_company_id) =
+        common::create_test_user_with_token("roles-employee@example.com", "password123", "Emp")
+            .await
+            .unwrap();
+
+    let roles = role_repo::get_company_roles(company_id).await.unwrap();
+    assert_eq!(roles.len(), 3);
+    assert!(roles.iter().all(|role| role.is_builtin));
+
+    let admin = user_context::get_user_context(admin_id, Some(company_id))
+        .await
+        .unwrap();
+    assert!(admin.has_permission(Permission::ROLES_MANAGE));
+    assert!(admin.has_permission(Permission::WAGES_EDIT));
+
+    let employee = user_context::get_user_context(employee_id, Some(employee_company_id))
+        .await
+        .unwrap();
+    assert!(employee.permissions.is_empty());
+    assert!(matches!(
+        employee.requires_permission(Permission::SHIFTS_CREATE),
+        Err(AppError::PermissionDenied(_))
+    ));
+
+    // Role definitions are checked before they are saved
+    let input = |name: &str, permissions: Vec<&str>| RoleInput {
+        name: name.to_string(),
+        description: None,
+        permissions: permissions.into_iter().map(String::from).collect(),
+    };
+    assert!(
+        roles::validate_input(company_id, None, &mut input("Auditor", vec!["payroll.run"]))
+            .await
+            .is_err()
+    );
+    assert!(
+        roles::validate_input(company_id, None, &mut input(" MANAGER ", vec![]))
+            .await
+            .is_err()
+    );
+    let mut valid = input(" Auditor ", vec!["stats.view", "shifts.view", "stats.view"]);
+    roles::validate_input(company_id, None, &mut valid)
+        .await
+        .unwrap();
+    assert_eq!(valid.name, "Auditor");
+    assert_eq!(valid.permissions, vec!["shifts.view", "stats.view"]);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_custom_role_grants_and_revokes_permissions() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let (_, company_id, admin_token) = common::create_user_with_company(
+        "custom-admin@example.com",
+        "password123",
+        "Custom Admin",
+        "Custom Co",
+    )
+    .await
+    .unwrap();
+    let (employee_id, _, _) =
+        common::create_test_user_with_token("scheduler@example.com", "password123", "Scheduler")
+            .await
+            .unwrap();
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            company_repo::add_employee_to_company(
+                tx,
+                company_id,
+                &AddEmployeeToCompanyInput {
+                    user_id: employee_id,
+                    role: Some(CompanyRole::Employee),
+                    is_primary: Some(false),
+                    hire_date: None,
+                },
+            )
+            .await?;
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+    let employee_token = auth_service::generate_company_token(employee_id, company_id)
+        .await
+        .unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(CacheLayer::new(1000, 60)))
+            .service(
+                web::scope("/api/v1")
+                    .service(
+                        web::scope("/companies")
+                            .route("/roles", web::get().to(company::get_roles))
+                            .route("/roles", web::post().to(company::create_role))
+                            .route("/roles/{id}", web::put().to(company::update_role))
+                            .route("/roles/{id}", web::delete().to(company::delete_role))
+                            .route(
+                                "/roles/members/{user_id}",
+                                web::put().to(company::assign_role),
+                            ),
+                    )
+                    .service(web::scope("/admin").route("/users", web::get().to(admin::get_users))),
+            ),
+    )
+    .await;
+    let list_users = || {
+        test::TestRequest::get()
+            .uri("/api/v1/admin/users")
+            .insert_header(("Authorization", format!("Bearer {}", employee_token)))
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, list_users()).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // Employees cannot manage roles themselves
+    let req = test::TestRequest::get()
+        .uri("/api/v1/companies/roles")
+        .insert_header(("Authorization", format!("Bearer {}", employee_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/companies/roles")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .set_json(json!({
+            "name": "Directory",
+            "description": "Can look up coworkers",
+            "permissions": ["employees.view"]
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let role_id = body["data"]["id"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/v1/companies/roles/members/{}", employee_id))
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .set_json(json!({ "roleId": role_id }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = test::call_service(&app, list_users()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Built-in roles are fixed
+    let builtin = role_repo::get_company_roles(company_id)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|role| role.is_builtin)
+        .unwrap();
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/v1/companies/roles/{}", builtin.id))
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // Deleting the role returns its members to their built-in role
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/v1/companies/roles/{}", role_id))
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = test::call_service(&app, list_users()).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
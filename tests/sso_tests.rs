@@ -0,0 +1,379 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use actix_web::{App, HttpResponse, HttpServer, web};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use be::database::{
+    models::{
+        AddEmployeeToCompanyInput, CompanyRole, LoginResponse, SsoCallbackInput, SsoConfigInput,
+        SsoLinkInput,
+    },
+    repositories::{company as company_repo, sso as sso_repo},
+    transaction::DatabaseTransaction,
+};
+use be::error::AppError;
+use be::services::{jwt_keys::KeyRing, sso};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+mod common;
+
+const CLIENT_ID: &str = "shiftlinkr-test";
+const CLIENT_SECRET: &str = "mock-client-secret";
+
+/// A user signed in at the mock provider, waiting for the code exchange
+struct PendingCode {
+    nonce: String,
+    code_challenge: String,
+    subject: String,
+    email: String,
+    email_verified: bool,
+}
+
+/// Local OIDC provider serving discovery, JWKS and the token endpoint
+struct MockIdp {
+    issuer: String,
+    codes: Arc<Mutex<HashMap<String, PendingCode>>>,
+}
+
+struct MockIdpState {
+    issuer: String,
+    key_ring: KeyRing,
+    codes: Arc<Mutex<HashMap<String, PendingCode>>>,
+}
+
+impl MockIdp {
+    async fn start() -> MockIdp {
+        let mut config = (**common::create_test_config().await).clone();
+        config.jwt_algorithm = "RS256".to_string();
+        config.jwt_key_id = "mock-idp".to_string();
+        config.jwt_private_key_path = Some(fixture("rsa_private.pem"));
+        config.jwt_public_key_path = Some(fixture("rsa_public.pem"));
+
+        let codes = Arc::new(Mutex::new(HashMap::new()));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+
+        let state = web::Data::new(MockIdpState {
+            issuer: issuer.clone(),
+            key_ring: KeyRing::from_config(&config).unwrap(),
+            codes: codes.clone(),
+        });
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(state.clone())
+                .route(
+                    "/.well-known/openid-configuration",
+                    web::get().to(discovery),
+                )
+                .route("/jwks", web::get().to(jwks))
+                .route("/token", web::post().to(token))
+        })
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+        actix_web::rt::spawn(server);
+
+        MockIdp { issuer, codes }
+    }
+
+    /// Simulate the user signing in at the provider for a given authorization URL
+    fn authorize(
+        &self,
+        authorization_url: &str,
+        subject: &str,
+        email: &str,
+        verified: bool,
+    ) -> String {
+        let url = reqwest::Url::parse(authorization_url).unwrap();
+        let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        assert_eq!(params["client_id"], CLIENT_ID);
+        assert_eq!(params["code_challenge_method"], "S256");
+
+        let code = Uuid::new_v4().to_string();
+        self.codes.lock().unwrap().insert(
+            code.clone(),
+            PendingCode {
+                nonce: params["nonce"].clone(),
+                code_challenge: params["code_challenge"].clone(),
+                subject: subject.to_string(),
+                email: email.to_string(),
+                email_verified: verified,
+            },
+        );
+        code
+    }
+}
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/jwt/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+async fn discovery(state: web::Data<MockIdpState>) -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "issuer": state.issuer,
+        "authorization_endpoint": format!("{}/authorize", state.issuer),
+        "token_endpoint": format!("{}/token", state.issuer),
+        "jwks_uri": format!("{}/jwks", state.issuer),
+    }))
+}
+
+async fn jwks(state: web::Data<MockIdpState>) -> HttpResponse {
+    HttpResponse::Ok().json(state.key_ring.jwks())
+}
+
+async fn token(
+    state: web::Data<MockIdpState>,
+    form: web::Form<HashMap<String, String>>,
+) -> HttpResponse {
+    let pending = form
+        .get("code")
+        .and_then(|code| state.codes.lock().unwrap().remove(code));
+    let Some(pending) = pending else {
+        return HttpResponse::BadRequest().json(json!({ "error": "invalid_grant" }));
+    };
+
+    let verifier = form.get("code_verifier").cloned().unwrap_or_default();
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+    if challenge != pending.code_challenge
+        || form.get("client_id").map(String::as_str) != Some(CLIENT_ID)
+        || form.get("client_secret").map(String::as_str) != Some(CLIENT_SECRET)
+    {
+        return HttpResponse::BadRequest().json(json!({ "error": "invalid_grant" }));
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let id_token = state
+        .key_ring
+        .encode(&json!({
+            "iss": state.issuer,
+            "aud": CLIENT_ID,
+            "sub": pending.subject,
+            "email": pending.email,
+            "email_verified": pending.email_verified,
+            "name": "SSO User",
+            "nonce": pending.nonce,
+            "iat": now,
+            "exp": now + 300,
+        }))
+        .unwrap();
+
+    HttpResponse::Ok().json(json!({
+        "access_token": "mock-access-token",
+        "token_type": "Bearer",
+        "id_token": id_token,
+    }))
+}
+
+async fn configure_sso(company_id: Uuid, issuer: &str) {
+    let endpoints = sso::discover(issuer).await.unwrap();
+    let input = SsoConfigInput {
+        issuer: issuer.to_string(),
+        client_id: CLIENT_ID.to_string(),
+        client_secret: None,
+        allowed_domains: vec!["example.com".to_string()],
+        default_role: Some(CompanyRole::Employee),
+        enabled: None,
+    };
+
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            sso_repo::upsert_config(tx, company_id, &input, CLIENT_SECRET, &endpoints).await?;
+            Ok(())
+        })
+    })
+    .await
+    .unwrap();
+}
+
+async fn sso_login(
+    idp: &MockIdp,
+    company_id: Uuid,
+    subject: &str,
+    email: &str,
+    verified: bool,
+) -> Result<LoginResponse, AppError> {
+    let start = sso::start_login(company_id).await?;
+    let code = idp.authorize(&start.authorization_url, subject, email, verified);
+
+    sso::complete_login(
+        SsoCallbackInput {
+            state: start.state,
+            code,
+        },
+        &common::test_request_info(),
+    )
+    .await
+}
+
+#[actix_web::test]
+async fn test_sso_provisions_and_links_users() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let idp = MockIdp::start().await;
+
+    let (_, _, company_id) =
+        common::create_test_user_with_token("sso-admin@example.com", "password123", "Admin")
+            .await
+            .unwrap();
+    configure_sso(company_id, &idp.issuer).await;
+
+    // First login provisions the user and their membership
+    let LoginResponse::Authenticated(response) =
+        sso_login(&idp, company_id, "subject-1", "new.hire@example.com", true)
+            .await
+            .unwrap()
+    else {
+        panic!("expected tokens");
+    };
+    let company = response.company.unwrap();
+    assert_eq!(company.id, company_id);
+    assert_eq!(company.role, CompanyRole::Employee);
+    let new_user_id = response.user.id;
+    // The provider's email_verified claim doesn't verify the address here
+    assert!(response.user.email_verified_at.is_none());
+
+    // The same identity signs in to the same account
+    let LoginResponse::Authenticated(again) =
+        sso_login(&idp, company_id, "subject-1", "new.hire@example.com", true)
+            .await
+            .unwrap()
+    else {
+        panic!("expected tokens");
+    };
+    assert_eq!(again.user.id, new_user_id);
+
+    // An existing password account is only linked when the provider verified the email
+    let (existing_user_id, _, _) =
+        common::create_test_user_with_token("existing@example.com", "password123", "Existing")
+            .await
+            .unwrap();
+    let unverified = sso_login(&idp, company_id, "subject-2", "existing@example.com", false).await;
+    assert!(matches!(unverified, Err(AppError::Forbidden(_))));
+
+    // and, as it isn't a member on a verified domain, its owner confirms with their password
+    let LoginResponse::SsoLinkRequired(challenge) =
+        sso_login(&idp, company_id, "subject-2", "existing@example.com", true)
+            .await
+            .unwrap()
+    else {
+        panic!("expected a link challenge");
+    };
+    assert!(
+        company_repo::find_user_company_info_by_id(existing_user_id, company_id)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    let confirm = |password: &str| SsoLinkInput {
+        link_token: challenge.link_token.clone(),
+        password: password.to_string(),
+    };
+    let wrong_password = sso::confirm_link(confirm("guess"), &common::test_request_info()).await;
+    assert!(matches!(wrong_password, Err(AppError::Unauthorized)));
+
+    let LoginResponse::Authenticated(linked) =
+        sso::confirm_link(confirm("password123"), &common::test_request_info())
+            .await
+            .unwrap()
+    else {
+        panic!("expected tokens");
+    };
+    assert_eq!(linked.user.id, existing_user_id);
+    assert!(
+        company_repo::find_user_company_info_by_id(existing_user_id, company_id)
+            .await
+            .unwrap()
+            .is_some()
+    );
+    let replayed = sso::confirm_link(confirm("password123"), &common::test_request_info()).await;
+    assert!(matches!(replayed, Err(AppError::Unauthorized)));
+
+    // Members on a domain the company verified are linked straight away
+    let (member_id, _, _) =
+        common::create_test_user_with_token("member@example.com", "password123", "Member")
+            .await
+            .unwrap();
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            company_repo::add_employee_to_company(
+                tx,
+                company_id,
+                &AddEmployeeToCompanyInput {
+                    user_id: member_id,
+                    role: Some(CompanyRole::Employee),
+                    is_primary: Some(false),
+                    hire_date: None,
+                },
+            )
+            .await?;
+            let domain = sso_repo::create_domain(tx, company_id, "example.com", "token").await?;
+            sso_repo::mark_domain_verified(tx, domain.id).await?;
+            Ok::<_, AppError>(())
+        })
+    })
+    .await
+    .unwrap();
+
+    let LoginResponse::Authenticated(member) =
+        sso_login(&idp, company_id, "subject-4", "member@example.com", true)
+            .await
+            .unwrap()
+    else {
+        panic!("expected tokens");
+    };
+    assert_eq!(member.user.id, member_id);
+
+    // Emails outside the allowed domains are refused
+    let outsider = sso_login(&idp, company_id, "subject-3", "someone@other.org", true).await;
+    assert!(matches!(outsider, Err(AppError::Forbidden(_))));
+}
+
+#[actix_web::test]
+async fn test_sso_rejects_replayed_or_tampered_callbacks() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let idp = MockIdp::start().await;
+
+    let (_, _, company_id) =
+        common::create_test_user_with_token("sso-owner@example.com", "password123", "Owner")
+            .await
+            .unwrap();
+    configure_sso(company_id, &idp.issuer).await;
+
+    // A forged state is rejected before any code exchange
+    let forged = sso::complete_login(
+        SsoCallbackInput {
+            state: "forged".to_string(),
+            code: "anything".to_string(),
+        },
+        &common::test_request_info(),
+    )
+    .await;
+    assert!(matches!(forged, Err(AppError::Unauthorized)));
+
+    // Each state can only complete one login
+    let start = sso::start_login(company_id).await.unwrap();
+    let code = idp.authorize(
+        &start.authorization_url,
+        "subject-9",
+        "replay@example.com",
+        true,
+    );
+    let callback = || SsoCallbackInput {
+        state: start.state.clone(),
+        code: code.clone(),
+    };
+    assert!(
+        sso::complete_login(callback(), &common::test_request_info())
+            .await
+            .is_ok()
+    );
+    let replay = sso::complete_login(callback(), &common::test_request_info()).await;
+    assert!(matches!(replay, Err(AppError::Unauthorized)));
+}
@@ -1,7 +1,13 @@
 use actix_web::{App, http::StatusCode, test, web};
-use be::handlers::stats;
+use be::database::{
+    get_pool, models::LocationInput, repositories::location as location_repo,
+    transaction::DatabaseTransaction,
+};
+use be::handlers::{shifts, stats};
 use be::middleware::CacheLayer;
+use chrono::{DateTime, Datelike, Duration, Utc};
 use serial_test::serial;
+use uuid::Uuid;
 
 mod common;
 
@@ -51,3 +57,668 @@ test_unauthorized!(
     get,
     "/api/v1/stats/time-off"
 );
+
+macro_rules! app {
+    () => {
+        test::init_service(
+            App::new()
+                .app_data(web::Data::new(CacheLayer::new(500, 60)))
+                .service(
+                    web::scope("/api/v1/stats")
+                        .route("/dashboard", web::get().to(stats::get_dashboard_stats))
+                        .route("/shifts", web::get().to(stats::get_shift_stats))
+                        .route("/time-off", web::get().to(stats::get_time_off_stats))
+                        .route("/coverage", web::get().to(stats::get_coverage))
+                        .route(
+                            "/reports/employees",
+                            web::get().to(stats::get_employee_report),
+                        )
+                        .route(
+                            "/reports/fairness",
+                            web::get().to(stats::get_fairness_report),
+                        ),
+                )
+                .service(
+                    web::scope("/api/v1/shifts")
+                        .route("/{id}/clock-in", web::post().to(shifts::clock_in))
+                        .route("/{id}/clock-out", web::post().to(shifts::clock_out)),
+                ),
+        )
+        .await
+    };
+}
+
+/// Insert a shift starting `days` from now, worked by `workers` through approved claims
+async fn insert_shift(
+    company_id: Uuid,
+    location_id: Uuid,
+    days: i64,
+    status: &str,
+    max_people: Option<i32>,
+    workers: &[Uuid],
+) {
+    let start = Utc::now() + Duration::days(days);
+    insert_shift_at(company_id, location_id, start, status, max_people, workers).await;
+}
+
+/// Insert an eight-hour shift starting at `start`, worked by `workers` through approved claims
+async fn insert_shift_at(
+    company_id: Uuid,
+    location_id: Uuid,
+    start: DateTime<Utc>,
+    status: &str,
+    max_people: Option<i32>,
+    workers: &[Uuid],
+) -> Uuid {
+    let pool = get_pool().await;
+    let shift_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO shifts (company_id, title, location_id, start_time, end_time, max_people, status)
+         VALUES ($1, 'Shift', $2, $3, $4, $5, $6) RETURNING id",
+    )
+    .bind(company_id)
+    .bind(location_id)
+    .bind(start)
+    .bind(start + Duration::hours(8))
+    .bind(max_people)
+    .bind(status)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    for user_id in workers {
+        sqlx::query(
+            "INSERT INTO shift_claims (shift_id, user_id, status) VALUES ($1, $2, 'approved')",
+        )
+        .bind(shift_id)
+        .bind(user_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+
+    shift_id
+}
+
+async fn create_location(company_id: Uuid, name: &str) -> Uuid {
+    let name = name.to_string();
+    DatabaseTransaction::run(|tx| {
+        Box::pin(async move {
+            let location = location_repo::create_location(
+                tx,
+                LocationInput {
+                    company_id,
+                    name,
+                    address: None,
+                    phone: None,
+                    email: None,
+                },
+            )
+            .await?;
+            Ok(location.id)
+        })
+    })
+    .await
+    .unwrap()
+}
+
+fn get(token: &str, uri: &str) -> test::TestRequest {
+    test::TestRequest::get()
+        .uri(uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_dashboard_is_scoped_to_company_and_period() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (admin_id, company_id, token) = common::create_user_with_company(
+        "stats-admin@example.com",
+        "password123",
+        "Stats Admin",
+        "Stats Co",
+    )
+    .await
+    .unwrap();
+    let (_, other_company_id, _) = common::create_user_with_company(
+        "stats-other@example.com",
+        "password123",
+        "Other Admin",
+        "Other Co",
+    )
+    .await
+    .unwrap();
+    let depot = create_location(company_id, "Depot").await;
+    let store = create_location(company_id, "Store").await;
+    let elsewhere = create_location(other_company_id, "Elsewhere").await;
+
+    // Two slots filled out of three at the depot, one open shift at the store
+    insert_shift(company_id, depot, 2, "assigned", Some(2), &[admin_id]).await;
+    insert_shift(company_id, depot, 3, "assigned", None, &[admin_id]).await;
+    insert_shift(company_id, store, 4, "open", None, &[]).await;
+    // Outside the period, cancelled, or another company's
+    insert_shift(company_id, depot, -20, "assigned", None, &[admin_id]).await;
+    insert_shift(company_id, depot, 5, "cancelled", None, &[]).await;
+    insert_shift(other_company_id, elsewhere, 2, "open", None, &[]).await;
+
+    sqlx::query(
+        "INSERT INTO time_off_requests (user_id, company_id, start_date, end_date, request_type, status)
+         VALUES ($1, $2, NOW() + INTERVAL '1 day', NOW() + INTERVAL '2 days', 'vacation', 'pending'),
+                ($1, $3, NOW() + INTERVAL '1 day', NOW() + INTERVAL '2 days', 'vacation', 'pending')",
+    )
+    .bind(admin_id)
+    .bind(company_id)
+    .bind(other_company_id)
+    .execute(&get_pool().await)
+    .await
+    .unwrap();
+    let app = app!();
+
+    let start = (Utc::now() - Duration::days(1)).format("%Y-%m-%dT%H:%M:%SZ");
+    let end = (Utc::now() + Duration::days(10)).format("%Y-%m-%dT%H:%M:%SZ");
+    let resp = test::call_service(
+        &app,
+        get(
+            &token,
+            &format!(
+                "/api/v1/stats/dashboard?startDate={}&endDate={}&compare=true",
+                start, end
+            ),
+        )
+        .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let data = &body["data"];
+    assert_eq!(data["totalShifts"], 4);
+    assert_eq!(data["upcomingShifts"], 3);
+    assert_eq!(data["totalHours"], 24.0);
+    assert_eq!(data["teamCoverage"], 50.0);
+    assert_eq!(data["pendingTimeOffRequests"], 1);
+
+    // The previous eleven days hold nothing, so there is no percent change to give
+    assert_eq!(data["previous"]["totalShifts"], 0);
+    assert!(data["changes"]["totalShifts"].is_null());
+
+    let resp = test::call_service(
+        &app,
+        get(
+            &token,
+            &format!(
+                "/api/v1/stats/shifts?startDate={}&endDate={}&locationId={}",
+                start, end, depot
+            ),
+        )
+        .to_request(),
+    )
+    .await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["totalShifts"], 3);
+    assert_eq!(body["data"]["requiredSlots"], 3);
+    assert_eq!(body["data"]["filledSlots"], 2);
+    assert!(body["data"]["previous"].is_null());
+
+    let resp = test::call_service(
+        &app,
+        get(
+            &token,
+            &format!(
+                "/api/v1/stats/dashboard?startDate={}&endDate={}",
+                end, start
+            ),
+        )
+        .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+async fn execute(query: &str, binds: &[Uuid]) {
+    let mut query = sqlx::query(query);
+    for bind in binds {
+        query = query.bind(bind);
+    }
+    query.execute(&get_pool().await).await.unwrap();
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_employee_report_counts_hours_and_attendance() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (admin_id, company_id, token) = common::create_user_with_company(
+        "report-admin@example.com",
+        "password123",
+        "Ada Admin",
+        "Report Co",
+    )
+    .await
+    .unwrap();
+    let (worker_id, _, _) = common::create_test_user_with_token(
+        "report-worker@example.com",
+        "password123",
+        "Bea Worker",
+    )
+    .await
+    .unwrap();
+    let depot = create_location(company_id, "Depot").await;
+    let pool = get_pool().await;
+    sqlx::query("INSERT INTO user_company (user_id, company_id, role) VALUES ($1, $2, 'employee')")
+        .bind(worker_id)
+        .bind(company_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let team_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO teams (name, location_id) VALUES ('Morning', $1) RETURNING id",
+    )
+    .bind(depot)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    execute(
+        "INSERT INTO team_members (team_id, user_id) VALUES ($1, $2)",
+        &[team_id, worker_id],
+    )
+    .await;
+
+    // The worker arrives half an hour late, misses a shift, works one in full and swaps one away
+    let now = Utc::now();
+    let late = insert_shift_at(
+        company_id,
+        depot,
+        now - Duration::days(3),
+        "assigned",
+        None,
+        &[worker_id],
+    )
+    .await;
+    insert_shift_at(
+        company_id,
+        depot,
+        now - Duration::days(2),
+        "assigned",
+        None,
+        &[worker_id],
+    )
+    .await;
+    let on_time = insert_shift_at(
+        company_id,
+        depot,
+        now - Duration::days(1),
+        "assigned",
+        None,
+        &[worker_id],
+    )
+    .await;
+    let swapped = insert_shift_at(
+        company_id,
+        depot,
+        now + Duration::days(2),
+        "open",
+        None,
+        &[],
+    )
+    .await;
+    for (shift_id, late_by) in [(late, 30), (on_time, 0)] {
+        sqlx::query(
+            "INSERT INTO shift_attendance (company_id, shift_id, user_id, clock_in_at, clock_out_at)
+             SELECT company_id, id, $2, start_time + MAKE_INTERVAL(mins => $3), end_time
+             FROM shifts WHERE id = $1",
+        )
+        .bind(shift_id)
+        .bind(worker_id)
+        .bind(late_by)
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+    execute(
+        "INSERT INTO shift_swaps (requesting_user_id, original_shift_id, status) VALUES ($1, $2, 'approved')",
+        &[worker_id, swapped],
+    )
+    .await;
+
+    // The admin misses a shift during approved time off and clocks in to the current one
+    insert_shift_at(
+        company_id,
+        depot,
+        now - Duration::days(2),
+        "assigned",
+        None,
+        &[admin_id],
+    )
+    .await;
+    let current = insert_shift_at(
+        company_id,
+        depot,
+        now - Duration::minutes(10),
+        "assigned",
+        None,
+        &[admin_id],
+    )
+    .await;
+    sqlx::query(
+        "INSERT INTO time_off_requests (user_id, company_id, start_date, end_date, request_type, status)
+         VALUES ($1, $2, $3, $4, 'vacation', 'approved')",
+    )
+    .bind(admin_id)
+    .bind(company_id)
+    .bind(now - Duration::days(2) - Duration::hours(1))
+    .bind(now - Duration::days(2) + Duration::hours(9))
+    .execute(&pool)
+    .await
+    .unwrap();
+    let app = app!();
+
+    let post = |uri: String| {
+        test::TestRequest::post()
+            .uri(&uri)
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+    let resp = test::call_service(&app, post(format!("/api/v1/shifts/{}/clock-in", current))).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let resp = test::call_service(&app, post(format!("/api/v1/shifts/{}/clock-in", current))).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = test::call_service(&app, post(format!("/api/v1/shifts/{}/clock-in", late))).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp =
+        test::call_service(&app, post(format!("/api/v1/shifts/{}/clock-out", current))).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp =
+        test::call_service(&app, post(format!("/api/v1/shifts/{}/clock-out", current))).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let start = (now - Duration::days(5)).format("%Y-%m-%dT%H:%M:%SZ");
+    let end = (now + Duration::days(5)).format("%Y-%m-%dT%H:%M:%SZ");
+    let report_uri = |params: &str| {
+        format!(
+            "/api/v1/stats/reports/employees?startDate={}&endDate={}{}",
+            start, end, params
+        )
+    };
+
+    let resp = test::call_service(
+        &app,
+        get(&token, &report_uri("&sort=workedHours&order=desc")).to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let employees = body["data"]["employees"].as_array().unwrap();
+    assert_eq!(employees.len(), 2);
+    let worker = &employees[0];
+    assert_eq!(worker["name"], "Bea Worker");
+    assert_eq!(worker["scheduledShifts"], 3);
+    assert_eq!(worker["scheduledHours"], 24.0);
+    assert_eq!(worker["workedHours"], 15.5);
+    assert_eq!(worker["overtimeHours"], 0.0);
+    assert_eq!(worker["lateArrivals"], 1);
+    assert_eq!(worker["noShows"], 1);
+    assert_eq!(worker["droppedShifts"], 1);
+    let admin = &employees[1];
+    assert_eq!(admin["scheduledShifts"], 2);
+    assert_eq!(admin["lateArrivals"], 1);
+    assert_eq!(admin["noShows"], 0);
+    assert_eq!(admin["timeOffRequests"], 1);
+    assert!(admin["timeOffDays"].as_i64().unwrap() >= 1);
+    assert_eq!(body["data"]["totals"]["employees"], 2);
+    assert_eq!(body["data"]["totals"]["noShows"], 1);
+
+    let resp =
+        test::call_service(&app, get(&token, &report_uri("&groupBy=team")).to_request()).await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let groups = body["data"]["groups"].as_array().unwrap();
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[0]["groupName"], "Morning");
+    assert_eq!(groups[0]["employees"][0]["name"], "Bea Worker");
+    assert_eq!(groups[1]["groupName"], "Unassigned");
+    assert!(groups[1]["groupId"].is_null());
+
+    let resp = test::call_service(&app, get(&token, &report_uri("&format=csv")).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get("Content-Type").unwrap(),
+        "text/csv; charset=utf-8"
+    );
+    let csv = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("group,user_id,name,email"));
+    assert!(lines[1].contains("Ada Admin"));
+    assert!(lines[3].starts_with("Total,,,,5,40.00,"));
+
+    let resp =
+        test::call_service(&app, get(&token, &report_uri("&sort=salary")).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_coverage_heatmap_flags_gaps_and_overstaffing() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (admin_id, company_id, token) = common::create_user_with_company(
+        "coverage-admin@example.com",
+        "password123",
+        "Coverage Admin",
+        "Coverage Co",
+    )
+    .await
+    .unwrap();
+    let (worker_id, _, _) = common::create_test_user_with_token(
+        "coverage-worker@example.com",
+        "password123",
+        "Coverage Worker",
+    )
+    .await
+    .unwrap();
+    let depot = create_location(company_id, "Depot").await;
+    let store = create_location(company_id, "Store").await;
+
+    // 08:00-12:00 needs two and has one, 10:00-14:00 needs one and has two
+    let day = (Utc::now() + Duration::days(1))
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_utc();
+    insert_shift_at(
+        company_id,
+        depot,
+        day + Duration::hours(8),
+        "assigned",
+        Some(2),
+        &[admin_id],
+    )
+    .await;
+    insert_shift_at(
+        company_id,
+        depot,
+        day + Duration::hours(10),
+        "assigned",
+        Some(1),
+        &[admin_id, worker_id],
+    )
+    .await;
+    insert_shift_at(
+        company_id,
+        store,
+        day + Duration::hours(8),
+        "open",
+        Some(5),
+        &[],
+    )
+    .await;
+    // insert_shift_at makes eight-hour shifts; these last four
+    sqlx::query(
+        "UPDATE shifts SET end_time = start_time + INTERVAL '4 hours' WHERE company_id = $1",
+    )
+    .bind(company_id)
+    .execute(&get_pool().await)
+    .await
+    .unwrap();
+    let app = app!();
+
+    let format = |time: DateTime<Utc>| time.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    let uri = |interval: i32| {
+        format!(
+            "/api/v1/stats/coverage?locationId={}&startDate={}&endDate={}&intervalMinutes={}",
+            depot,
+            format(day + Duration::hours(8)),
+            format(day + Duration::hours(14)),
+            interval
+        )
+    };
+    let resp = test::call_service(&app, get(&token, &uri(60)).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let statuses: Vec<(i64, i64, &str)> = body["data"]["buckets"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|bucket| {
+            (
+                bucket["required"].as_i64().unwrap(),
+                bucket["assigned"].as_i64().unwrap(),
+                bucket["status"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            (2, 1, "understaffed"),
+            (2, 1, "understaffed"),
+            (3, 3, "covered"),
+            (3, 3, "covered"),
+            (1, 2, "overstaffed"),
+            (1, 2, "overstaffed"),
+        ]
+    );
+    assert_eq!(body["data"]["understaffedBuckets"], 2);
+    assert_eq!(body["data"]["overstaffedBuckets"], 2);
+
+    let resp = test::call_service(&app, get(&token, &uri(15)).to_request()).await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["buckets"].as_array().unwrap().len(), 24);
+
+    let resp = test::call_service(&app, get(&token, &uri(45)).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_fairness_report_shares_unpopular_shifts() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+    let (admin_id, company_id, token) = common::create_user_with_company(
+        "fairness-admin@example.com",
+        "password123",
+        "Ada Admin",
+        "Fairness Co",
+    )
+    .await
+    .unwrap();
+    let (worker_id, _, _) = common::create_test_user_with_token(
+        "fairness-worker@example.com",
+        "password123",
+        "Bea Worker",
+    )
+    .await
+    .unwrap();
+    let depot = create_location(company_id, "Depot").await;
+    let pool = get_pool().await;
+    sqlx::query("INSERT INTO user_company (user_id, company_id, role) VALUES ($1, $2, 'employee')")
+        .bind(worker_id)
+        .bind(company_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let team_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO teams (name, location_id) VALUES ('Crew', $1) RETURNING id",
+    )
+    .bind(depot)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    for user_id in [admin_id, worker_id] {
+        execute(
+            "INSERT INTO team_members (team_id, user_id) VALUES ($1, $2)",
+            &[team_id, user_id],
+        )
+        .await;
+    }
+    execute(
+        "INSERT INTO user_shift_schedules (user_id, company_id, min_hours_per_week) VALUES ($1, $2, 20)",
+        &[worker_id, company_id],
+    )
+    .await;
+
+    // A Monday next week onwards, in the company's UTC timezone
+    let today = Utc::now().date_naive();
+    let monday = (today + Duration::days(14 - today.weekday().num_days_from_monday() as i64))
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_utc();
+    let at = |days: i64, hour: i64| monday + Duration::days(days) + Duration::hours(hour);
+    // The worker takes the first weekend, the admin a night and a holiday
+    for (start, user_id) in [
+        (at(5, 9), worker_id),
+        (at(6, 9), worker_id),
+        (at(7, 9), worker_id),
+        (at(1, 9), admin_id),
+        (at(2, 23), admin_id),
+        (at(3, 9), admin_id),
+    ] {
+        insert_shift_at(company_id, depot, start, "assigned", None, &[user_id]).await;
+    }
+    execute(
+        "UPDATE shifts SET team_id = $1 WHERE location_id = $2",
+        &[team_id, depot],
+    )
+    .await;
+    let app = app!();
+
+    let format = |time: DateTime<Utc>| time.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    let uri = |trend: &str| {
+        format!(
+            "/api/v1/stats/reports/fairness?teamId={}&startDate={}&endDate={}&holidays={}&trend={}",
+            team_id,
+            format(monday),
+            format(at(14, 0)),
+            at(3, 0).format("%Y-%m-%d"),
+            trend
+        )
+    };
+    let resp = test::call_service(&app, get(&token, &uri("week")).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let data = &body["data"];
+    let admin = &data["employees"][0];
+    assert_eq!(admin["name"], "Ada Admin");
+    assert_eq!(admin["shifts"], 3);
+    assert_eq!(admin["nightShifts"], 1);
+    assert_eq!(admin["nightShare"], 100.0);
+    assert_eq!(admin["holidayShifts"], 1);
+    assert_eq!(admin["weekendShare"], 0.0);
+    let worker = &data["employees"][1];
+    assert_eq!(worker["weekendShifts"], 2);
+    assert_eq!(worker["weekendShare"], 100.0);
+    assert_eq!(worker["hoursPerWeek"], 12.0);
+    assert_eq!(worker["hoursStatus"], "below_min");
+
+    // Two in three shifts are unpopular for each overall, but not within each week
+    assert_eq!(data["score"], 100.0);
+    let trend: Vec<f64> = data["trend"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|point| point["score"].as_f64().unwrap())
+        .collect();
+    assert_eq!(trend, vec![90.0, 100.0]);
+
+    let resp = test::call_service(&app, get(&token, &uri("day")).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
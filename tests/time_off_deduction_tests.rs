@@ -0,0 +1,346 @@
+use actix_web::{App, http::StatusCode, test, web};
+use be::database::get_pool;
+use be::handlers::{company, time_off};
+use be::middleware::CacheLayer;
+use bigdecimal::BigDecimal;
+use serde_json::{Value, json};
+use serial_test::serial;
+use std::str::FromStr;
+use uuid::Uuid;
+
+mod common;
+
+macro_rules! app {
+    () => {
+        test::init_service(
+            App::new()
+                .app_data(web::Data::new(CacheLayer::new(1000, 60)))
+                .service(
+                    web::scope("/api/v1")
+                        .service(
+                            web::scope("/time-off")
+                                .route("", web::post().to(time_off::create_time_off_request))
+                                .route(
+                                    "/{id}/approve",
+                                    web::post().to(time_off::approve_time_off_request),
+                                )
+                                .route(
+                                    "/{id}/cancel",
+                                    web::post().to(time_off::cancel_time_off_request),
+                                ),
+                        )
+                        .service(web::scope("/companies").route(
+                            "/time-off-settings",
+                            web::put().to(company::update_time_off_settings),
+                        )),
+                ),
+        )
+        .await
+    };
+}
+
+fn hours_of(value: &Value) -> BigDecimal {
+    BigDecimal::from_str(value.as_str().unwrap()).unwrap()
+}
+
+async fn balance_of(user_id: Uuid, company_id: Uuid, column: &str) -> BigDecimal {
+    sqlx::query_scalar::<_, BigDecimal>(&format!(
+        "SELECT {} FROM user_company WHERE user_id = $1 AND company_id = $2",
+        column
+    ))
+    .bind(user_id)
+    .bind(company_id)
+    .fetch_one(&get_pool().await)
+    .await
+    .unwrap()
+}
+
+fn request_body(
+    user_id: Uuid,
+    company_id: Uuid,
+    request_type: &str,
+    duration_type: &str,
+    start: &str,
+    end: &str,
+) -> Value {
+    json!({
+        "userId": user_id,
+        "companyId": company_id,
+        "startDate": start,
+        "endDate": end,
+        "reason": "Away",
+        "requestType": request_type,
+        "durationType": duration_type,
+    })
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_approval_deducts_working_days_and_cancel_refunds() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let (user_id, company_id, token) =
+        common::create_user_with_company("pto@example.com", "password123", "Admin", "PTO Co")
+            .await
+            .unwrap();
+    sqlx::query(
+        "UPDATE user_company SET pto_balance_hours = 20 WHERE user_id = $1 AND company_id = $2",
+    )
+    .bind(user_id)
+    .bind(company_id)
+    .execute(&get_pool().await)
+    .await
+    .unwrap();
+
+    let app = app!();
+
+    let create = |body: Value| {
+        test::TestRequest::post()
+            .uri("/api/v1/time-off")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(body)
+            .to_request()
+    };
+    let action = |id: &str, action: &str| {
+        test::TestRequest::post()
+            .uri(&format!("/api/v1/time-off/{}/{}", id, action))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({ "notes": null }))
+            .to_request()
+    };
+
+    // Friday to Monday without shifts takes two workdays
+    let resp = test::call_service(
+        &app,
+        create(request_body(
+            user_id,
+            company_id,
+            "Vacation",
+            "full_day",
+            "2026-03-06T00:00:00Z",
+            "2026-03-09T00:00:00Z",
+        )),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: Value = test::read_body_json(resp).await;
+    let long_weekend = body["data"]["id"].as_str().unwrap().to_string();
+    assert_eq!(body["data"]["durationType"], "full_day");
+
+    let resp = test::call_service(&app, action(&long_weekend, "approve")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(
+        hours_of(&body["data"]["hoursDeducted"]),
+        BigDecimal::from(16)
+    );
+    assert_eq!(
+        balance_of(user_id, company_id, "pto_balance_hours").await,
+        BigDecimal::from(4)
+    );
+
+    // Another workday is more than the 4 hours left
+    let next_day = request_body(
+        user_id,
+        company_id,
+        "Vacation",
+        "full_day",
+        "2026-03-10T00:00:00Z",
+        "2026-03-10T00:00:00Z",
+    );
+    let resp = test::call_service(&app, create(next_day.clone())).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // Unless the company allows negative balances
+    let req = test::TestRequest::put()
+        .uri("/api/v1/companies/time-off-settings")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "workdayHours": "7.5", "allowNegativePto": true }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let resp = test::call_service(&app, create(next_day)).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: Value = test::read_body_json(resp).await;
+    let next_day = body["data"]["id"].as_str().unwrap().to_string();
+    let resp = test::call_service(&app, action(&next_day, "approve")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        balance_of(user_id, company_id, "pto_balance_hours").await,
+        BigDecimal::from_str("-3.5").unwrap()
+    );
+
+    // Cancelling gives the hours back, once, even when cancelled twice at the same time
+    let (first, second) = tokio::join!(
+        test::call_service(&app, action(&long_weekend, "cancel")),
+        test::call_service(&app, action(&long_weekend, "cancel")),
+    );
+    let (resp, other) = if first.status() == StatusCode::OK {
+        (first, second)
+    } else {
+        (second, first)
+    };
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(
+        [StatusCode::BAD_REQUEST, StatusCode::CONFLICT].contains(&other.status()),
+        "unexpected {}",
+        other.status()
+    );
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["status"], "Cancelled");
+    assert_eq!(
+        balance_of(user_id, company_id, "pto_balance_hours").await,
+        BigDecimal::from_str("12.5").unwrap()
+    );
+
+    let resp = test::call_service(&app, action(&long_weekend, "cancel")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let refund = sqlx::query_as::<_, (BigDecimal, String)>(
+        "SELECT hours_changed, effective_date::TEXT FROM pto_balance_history \
+         WHERE related_time_off_id = $1 AND change_type = 'refund'",
+    )
+    .bind(Uuid::parse_str(&long_weekend).unwrap())
+    .fetch_all(&get_pool().await)
+    .await
+    .unwrap();
+    assert_eq!(
+        refund,
+        vec![(BigDecimal::from(16), "2026-03-06".to_string())]
+    );
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_scheduled_shifts_partial_days_and_unpaid_types() {
+    common::setup_test_env();
+    let _ctx = common::TestContext::new().await.unwrap();
+
+    let (user_id, company_id, token) =
+        common::create_user_with_company("shift@example.com", "password123", "Admin", "Shift Co")
+            .await
+            .unwrap();
+    let pool = get_pool().await;
+    sqlx::query(
+        "UPDATE user_company SET sick_balance_hours = 10 WHERE user_id = $1 AND company_id = $2",
+    )
+    .bind(user_id)
+    .bind(company_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    // A 6 hour shift on Thursday 12 March
+    let location_id = sqlx::query_scalar::<_, Uuid>(
+        "INSERT INTO locations (name, company_id) VALUES ('Store', $1) RETURNING id",
+    )
+    .bind(company_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    let shift_id = sqlx::query_scalar::<_, Uuid>(
+        "INSERT INTO shifts (company_id, title, location_id, start_time, end_time, status) \
+         VALUES ($1, 'Close', $2, '2026-03-12T12:00:00Z', '2026-03-12T18:00:00Z', 'assigned') \
+         RETURNING id",
+    )
+    .bind(company_id)
+    .bind(location_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO shift_assignments (shift_id, user_id, assigned_by, assignment_status) \
+         VALUES ($1, $2, $2, 'accepted')",
+    )
+    .bind(shift_id)
+    .bind(user_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let app = app!();
+
+    let approve = |body: Value| {
+        let token = token.clone();
+        let app = &app;
+        async move {
+            let req = test::TestRequest::post()
+                .uri("/api/v1/time-off")
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .set_json(body)
+                .to_request();
+            let body: Value = test::read_body_json(test::call_service(app, req).await).await;
+            let req = test::TestRequest::post()
+                .uri(&format!(
+                    "/api/v1/time-off/{}/approve",
+                    body["data"]["id"].as_str().unwrap()
+                ))
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .set_json(json!({ "notes": null }))
+                .to_request();
+            let resp = test::call_service(app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let body: Value = test::read_body_json(resp).await;
+            body["data"]["hoursDeducted"].clone()
+        }
+    };
+
+    // Half of the scheduled shift
+    let hours = approve(request_body(
+        user_id,
+        company_id,
+        "Sick",
+        "partial_day",
+        "2026-03-12T12:00:00Z",
+        "2026-03-12T15:00:00Z",
+    ))
+    .await;
+    assert_eq!(hours_of(&hours), BigDecimal::from(3));
+
+    // With a schedule, days without shifts take nothing
+    let hours = approve(request_body(
+        user_id,
+        company_id,
+        "Sick",
+        "full_day",
+        "2026-03-11T00:00:00Z",
+        "2026-03-13T00:00:00Z",
+    ))
+    .await;
+    assert_eq!(hours_of(&hours), BigDecimal::from(6));
+    assert_eq!(
+        balance_of(user_id, company_id, "sick_balance_hours").await,
+        BigDecimal::from(1)
+    );
+
+    // Bereavement leave doesn't use a balance
+    let hours = approve(request_body(
+        user_id,
+        company_id,
+        "Bereavement",
+        "full_day",
+        "2026-03-16T00:00:00Z",
+        "2026-03-20T00:00:00Z",
+    ))
+    .await;
+    assert!(hours.is_null());
+
+    // Partial-day and hourly requests cover a single day
+    let req = test::TestRequest::post()
+        .uri("/api/v1/time-off")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(request_body(
+            user_id,
+            company_id,
+            "Sick",
+            "hourly",
+            "2026-03-12T12:00:00Z",
+            "2026-03-13T12:00:00Z",
+        ))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+}